@@ -30,6 +30,25 @@ fn sanitize_bot_for_api(mut bot: BotInstance) -> BotInstance {
             discord.remove("token");
         }
     }
+    if bot.platform.eq_ignore_ascii_case("satori") {
+        if let Some(satori) = bot
+            .metadata
+            .get_mut("satori")
+            .and_then(|v| v.as_object_mut())
+        {
+            satori.remove("token");
+        }
+    }
+    if bot.platform.eq_ignore_ascii_case("onebot") {
+        if let Some(onebot) = bot
+            .metadata
+            .get_mut("onebot_http")
+            .and_then(|v| v.as_object_mut())
+        {
+            onebot.remove("access_token");
+            onebot.remove("secret");
+        }
+    }
     bot
 }
 
@@ -718,6 +737,72 @@ pub async fn create_bot_handler(
         })));
     }
 
+    // Satori connects to an external gateway (Koishi/Chronocat/...), also no container.
+    if payload.platform.eq_ignore_ascii_case("satori") {
+        let id = format!("satori_{}", now_unix_secs()?);
+        let bot = BotInstance {
+            id: id.clone(),
+            name: payload.name,
+            platform: "Satori".to_string(),
+            is_connected: false,
+            is_running: false,
+            container_id: None,
+            ws_host: None,
+            ws_port: None,
+            webui_host: None,
+            webui_port: None,
+            webui_token: None,
+            qq_id: None,
+            linked_database: None,
+            metadata: serde_json::json!({
+                "satori": { "endpoint": "", "token": "", "platform": "" }
+            }),
+            modules_config: HashMap::new(),
+        };
+
+        state.bots.insert(id.clone(), bot);
+        save_bots(&state.bots);
+        info!("已创建新机器人实例: {} (Satori)", id);
+
+        return Ok(Json(serde_json::json!({
+            "status": "success",
+            "id": id,
+        })));
+    }
+
+    // OneBot HTTP: talks to an existing OneBot implementation over HTTP API + HTTP POST events.
+    if payload.platform.eq_ignore_ascii_case("onebot") {
+        let id = format!("onebot_{}", now_unix_secs()?);
+        let bot = BotInstance {
+            id: id.clone(),
+            name: payload.name,
+            platform: "OneBot".to_string(),
+            is_connected: false,
+            is_running: false,
+            container_id: None,
+            ws_host: None,
+            ws_port: None,
+            webui_host: None,
+            webui_port: None,
+            webui_token: None,
+            qq_id: None,
+            linked_database: None,
+            metadata: serde_json::json!({
+                "onebot_http": { "api_url": "", "access_token": "", "secret": "" }
+            }),
+            modules_config: HashMap::new(),
+        };
+
+        state.bots.insert(id.clone(), bot);
+        save_bots(&state.bots);
+        info!("已创建新机器人实例: {} (OneBot HTTP)", id);
+
+        return Ok(Json(serde_json::json!({
+            "status": "success",
+            "id": id,
+        })));
+    }
+
     // Default: QQ (NapCat OneBot via Docker). This may involve pulling a large image,
     // so we run provisioning in background and expose progress via /api/tasks.
     let bot_id = format!("{}_{}", payload.platform.to_lowercase(), now_unix_secs()?);
@@ -823,6 +908,10 @@ pub async fn delete_bot_handler(
 
     if bot.platform.eq_ignore_ascii_case("discord") {
         runtime.shutdown_discord_connection(&id).await;
+    } else if bot.platform.eq_ignore_ascii_case("satori") {
+        runtime.shutdown_satori_connection(&id).await;
+    } else if bot.platform.eq_ignore_ascii_case("onebot") {
+        runtime.unregister_connection(&id).await;
     } else {
         let container_id = bot.container_id.clone().unwrap_or(id.clone());
         let _ = Command::new("docker")
@@ -952,6 +1041,120 @@ pub async fn update_discord_bot_handler(
     Json(serde_json::json!({ "status": "success" }))
 }
 
+#[derive(serde::Deserialize)]
+pub struct UpdateSatoriBotPayload {
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub platform: Option<String>,
+    #[serde(default)]
+    pub is_running: Option<bool>,
+}
+
+pub async fn update_satori_bot_handler(
+    State(state): State<SharedState>,
+    Extension(runtime): Extension<std::sync::Arc<crate::bot::BotRuntime>>,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateSatoriBotPayload>,
+) -> Json<serde_json::Value> {
+    let Some(mut bot) = state.bots.get_mut(&id) else {
+        return Json(serde_json::json!({ "status": "error", "message": "Bot not found" }));
+    };
+
+    if !bot.platform.eq_ignore_ascii_case("satori") {
+        return Json(serde_json::json!({ "status": "error", "message": "Not a Satori bot" }));
+    }
+
+    let mut need_restart = false;
+    if let Some(endpoint) = payload.endpoint {
+        let endpoint = endpoint.trim().to_string();
+        if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+            return Json(
+                serde_json::json!({ "status": "error", "message": "Endpoint must start with http:// or https://" }),
+            );
+        }
+        bot.metadata["satori"]["endpoint"] = serde_json::json!(endpoint);
+        need_restart = true;
+    }
+    if let Some(token) = payload.token {
+        bot.metadata["satori"]["token"] = serde_json::json!(token.trim());
+        need_restart = true;
+    }
+    if let Some(platform) = payload.platform {
+        bot.metadata["satori"]["platform"] = serde_json::json!(platform.trim());
+        need_restart = true;
+    }
+
+    if let Some(running) = payload.is_running {
+        if bot.is_running != running {
+            bot.is_running = running;
+            need_restart = true;
+        }
+    }
+
+    drop(bot);
+    save_bots(&state.bots);
+
+    if need_restart {
+        runtime.shutdown_satori_connection(&id).await;
+    }
+
+    Json(serde_json::json!({ "status": "success" }))
+}
+
+#[derive(serde::Deserialize)]
+pub struct UpdateOneBotHttpBotPayload {
+    #[serde(default)]
+    pub api_url: Option<String>,
+    #[serde(default)]
+    pub access_token: Option<String>,
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub is_running: Option<bool>,
+}
+
+pub async fn update_onebot_http_bot_handler(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateOneBotHttpBotPayload>,
+) -> Json<serde_json::Value> {
+    let Some(mut bot) = state.bots.get_mut(&id) else {
+        return Json(serde_json::json!({ "status": "error", "message": "Bot not found" }));
+    };
+
+    if !bot.platform.eq_ignore_ascii_case("onebot") {
+        return Json(serde_json::json!({ "status": "error", "message": "Not a OneBot HTTP bot" }));
+    }
+
+    // 连接管理循环会检测 api_url / access_token 变化并重建连接，这里只需落盘。
+    if let Some(api_url) = payload.api_url {
+        let api_url = api_url.trim().to_string();
+        if !api_url.starts_with("http://") && !api_url.starts_with("https://") {
+            return Json(
+                serde_json::json!({ "status": "error", "message": "API URL must start with http:// or https://" }),
+            );
+        }
+        bot.metadata["onebot_http"]["api_url"] = serde_json::json!(api_url);
+    }
+    if let Some(token) = payload.access_token {
+        bot.metadata["onebot_http"]["access_token"] = serde_json::json!(token.trim());
+    }
+    if let Some(secret) = payload.secret {
+        bot.metadata["onebot_http"]["secret"] = serde_json::json!(secret.trim());
+    }
+    if let Some(running) = payload.is_running {
+        bot.is_running = running;
+    }
+
+    drop(bot);
+    save_bots(&state.bots);
+
+    Json(serde_json::json!({ "status": "success" }))
+}
+
 pub async fn list_bots_for_link_handler(
     State(state): State<SharedState>,
 ) -> Json<Vec<serde_json::Value>> {
@@ -1036,6 +1239,80 @@ pub async fn copy_bot_handler(
         ));
     }
 
+    if source_bot.platform.eq_ignore_ascii_case("onebot") {
+        let new_id = format!("onebot_{}", now_unix_secs()?);
+        let mut metadata = source_bot.metadata;
+        if let Some(map) = metadata
+            .get_mut("onebot_http")
+            .and_then(|v| v.as_object_mut())
+        {
+            map.insert("access_token".to_string(), serde_json::json!(""));
+            map.insert("secret".to_string(), serde_json::json!(""));
+        }
+
+        let new_bot = BotInstance {
+            id: new_id.clone(),
+            name: payload.new_name,
+            platform: "OneBot".to_string(),
+            is_connected: false,
+            is_running: false,
+            container_id: None,
+            ws_host: None,
+            ws_port: None,
+            webui_host: None,
+            webui_port: None,
+            webui_token: None,
+            qq_id: None,
+            linked_database: source_bot.linked_database,
+            metadata,
+            modules_config: source_bot.modules_config,
+        };
+
+        state.bots.insert(new_id.clone(), new_bot);
+        save_bots(&state.bots);
+        info!("复制机器人成功: {} -> {}", id, new_id);
+
+        return Ok(Json(
+            serde_json::json!({ "status": "success", "id": new_id }),
+        ));
+    }
+
+    if source_bot.platform.eq_ignore_ascii_case("satori") {
+        let new_id = format!("satori_{}", now_unix_secs()?);
+        let mut metadata = source_bot.metadata;
+        if let Some(map) = metadata.get_mut("satori").and_then(|v| v.as_object_mut()) {
+            map.remove("token");
+            map.remove("self_id");
+            map.remove("login_platform");
+        }
+
+        let new_bot = BotInstance {
+            id: new_id.clone(),
+            name: payload.new_name,
+            platform: "Satori".to_string(),
+            is_connected: false,
+            is_running: false,
+            container_id: None,
+            ws_host: None,
+            ws_port: None,
+            webui_host: None,
+            webui_port: None,
+            webui_token: None,
+            qq_id: None,
+            linked_database: source_bot.linked_database,
+            metadata,
+            modules_config: source_bot.modules_config,
+        };
+
+        state.bots.insert(new_id.clone(), new_bot);
+        save_bots(&state.bots);
+        info!("复制机器人成功: {} -> {}", id, new_id);
+
+        return Ok(Json(
+            serde_json::json!({ "status": "success", "id": new_id }),
+        ));
+    }
+
     let new_id = format!(
         "{}_{}",
         source_bot.platform.to_lowercase(),
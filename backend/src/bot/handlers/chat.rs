@@ -1,4 +1,4 @@
-use super::super::{BotRuntime, GroupSendStatus};
+use super::super::{search_chat_archive, BotRuntime, ChatArchiveQuery, GroupSendStatus};
 use axum::extract::{Json, Query};
 use axum::Extension;
 
@@ -232,3 +232,13 @@ pub async fn send_chat_message_handler(
         None => Json(serde_json::json!({ "status": "error", "message": "Failed to send message" })),
     }
 }
+
+/// 检索本地消息存档：`/api/chat/search?q=&group_id=&user_id=&since=`
+pub async fn search_chat_archive_handler(
+    Query(query): Query<ChatArchiveQuery>,
+) -> Json<serde_json::Value> {
+    match search_chat_archive(query).await {
+        Ok(messages) => Json(serde_json::json!({ "status": "success", "data": messages })),
+        Err(e) => Json(serde_json::json!({ "status": "error", "message": e })),
+    }
+}
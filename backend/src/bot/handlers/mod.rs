@@ -4,7 +4,9 @@ mod contacts;
 mod logs;
 mod modules;
 mod napcat;
+mod outbound;
 mod stats;
+mod usage;
 
 pub use bots::*;
 pub use chat::*;
@@ -12,4 +14,6 @@ pub use contacts::*;
 pub use logs::*;
 pub use modules::*;
 pub use napcat::*;
+pub use outbound::*;
 pub use stats::*;
+pub use usage::*;
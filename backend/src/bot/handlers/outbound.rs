@@ -0,0 +1,33 @@
+use super::super::BotRuntime;
+use axum::extract::{Json, Query};
+use axum::Extension;
+
+#[derive(serde::Deserialize)]
+pub struct OutboundQuery {
+    #[serde(default)]
+    pub bot_id: Option<String>,
+}
+
+/// 发送队列状态：待发送数量 + 死信列表
+pub async fn get_outbound_status_handler(
+    Extension(runtime): Extension<std::sync::Arc<BotRuntime>>,
+    Query(query): Query<OutboundQuery>,
+) -> Json<serde_json::Value> {
+    let bot_id = query.bot_id.as_deref().filter(|s| !s.is_empty());
+    let pending = runtime.outbound.pending_count(bot_id).await;
+    let dead_letters = runtime.outbound.dead_letters(bot_id).await;
+    Json(serde_json::json!({
+        "status": "success",
+        "pending": pending,
+        "dead_letters": dead_letters,
+    }))
+}
+
+pub async fn clear_dead_letters_handler(
+    Extension(runtime): Extension<std::sync::Arc<BotRuntime>>,
+    Query(query): Query<OutboundQuery>,
+) -> Json<serde_json::Value> {
+    let bot_id = query.bot_id.as_deref().filter(|s| !s.is_empty());
+    let removed = runtime.outbound.clear_dead_letters(bot_id).await;
+    Json(serde_json::json!({ "status": "success", "removed": removed }))
+}
@@ -0,0 +1,10 @@
+use super::super::{summarize_llm_usage, LlmUsageQuery};
+use axum::extract::{Json, Query};
+
+/// LLM 用量汇总：`/api/llm/usage?from=&to=&group_by=&bot_id=&group_id=&user_id=&plugin_id=`
+pub async fn get_llm_usage_handler(Query(query): Query<LlmUsageQuery>) -> Json<serde_json::Value> {
+    match summarize_llm_usage(query).await {
+        Ok(summary) => Json(serde_json::json!({ "status": "success", "data": summary })),
+        Err(e) => Json(serde_json::json!({ "status": "error", "message": e })),
+    }
+}
@@ -1,4 +1,4 @@
-use crate::models::SharedState;
+use crate::models::{BotInstance, SharedState};
 use crate::persistence::save_bots;
 use base64::Engine as _;
 use serde::{Deserialize, Serialize};
@@ -492,6 +492,14 @@ pub async fn napcat_login_monitor(state: SharedState) {
     }
 }
 
+/// Discord / Satori / OneBot HTTP 连接外部服务，没有对应的容器，运行状态由各自的连接循环维护
+fn has_docker_container(bot: &BotInstance) -> bool {
+    let external = ["discord", "satori"]
+        .iter()
+        .any(|p| bot.platform.eq_ignore_ascii_case(p));
+    !external && bot.metadata.get("onebot_http").is_none()
+}
+
 /// 同步 Docker 容器运行状态
 pub async fn docker_status_sync_loop(state: SharedState) {
     info!("启动 Docker 状态同步循环...");
@@ -518,7 +526,7 @@ pub async fn docker_status_sync_loop(state: SharedState) {
 
             let mut changed = false;
             for mut bot in state.bots.iter_mut() {
-                if bot.platform.eq_ignore_ascii_case("discord") {
+                if !has_docker_container(&bot) {
                     continue;
                 }
                 let target_name = bot.container_id.clone().unwrap_or(bot.id.clone());
@@ -6,10 +6,12 @@ use std::collections::hash_map::DefaultHasher;
 use std::collections::HashSet;
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
+use tokio::sync::oneshot;
 use tokio::time::{sleep, Duration};
 use tracing::{info, warn};
 
-use super::connection::{BotConnection, BotRuntime, DiscordConnection, GroupSendStatus};
+use super::connection::{next_echo, BotConnection, BotRuntime, DiscordConnection, GroupSendStatus};
+use super::outbound::{is_queued_action, ApiResultSender, SendFailure};
 use super::privacy;
 
 const DISCORD_API_BASE: &str = "https://discord.com/api/v10";
@@ -228,7 +230,13 @@ async fn sanitize_outgoing_text(
         (out, replacements)
     }
 
-    let (protected, replacements) = protect_cq_segments(message);
+    // LLM 输出里的 `[表情:名称]` 还原成 QQ 表情；Discord 没有对应的表情，保留文字
+    let message = if is_discord_bot(runtime, bot_id).await {
+        message.to_string()
+    } else {
+        crate::qq_face::face_tokens_to_cq(message)
+    };
+    let (protected, replacements) = protect_cq_segments(&message);
     let mut out = redact_sensitive_ids_plaintext(runtime, bot_id, group_id, &protected).await;
     for (placeholder, original) in replacements {
         out = out.replace(&placeholder, &original);
@@ -279,12 +287,58 @@ fn compute_message_hash(bot_id: &str, target: u64, message: &str) -> u64 {
     hasher.finish()
 }
 
+/// 成功响应（OneBot 风格），`data` 中可能带 `message_id`。
+pub(super) fn api_ok(data: Value) -> Value {
+    json!({ "status": "ok", "retcode": 0, "data": data })
+}
+
+/// 失败响应（OneBot 风格）；错误文本来自 `classify_onebot_response` 时带回原始 retcode。
+pub(super) fn api_failed(message: &str) -> Value {
+    let retcode = message
+        .strip_prefix("retcode=")
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|c| c.parse::<i64>().ok())
+        .unwrap_or(-1);
+    json!({ "status": "failed", "retcode": retcode, "data": null, "message": message })
+}
+
+/// 把结果交给等待方（插件 onApiResponse）；没有等待方时什么也不做。
+pub(super) fn respond(result: Option<ApiResultSender>, response: Value) {
+    if let Some(tx) = result {
+        let _ = tx.send(response);
+    }
+}
+
 pub async fn send_reply(
     runtime: &Arc<BotRuntime>,
     bot_id: &str,
     user_id: u64,
     group_id: Option<u64>,
     message: &str,
+) {
+    send_reply_inner(runtime, bot_id, user_id, group_id, message, None).await;
+}
+
+/// 与 `send_reply` 相同，但等待最终发送结果（含重试），返回 OneBot 风格响应。
+pub async fn send_reply_tracked(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    user_id: u64,
+    group_id: Option<u64>,
+    message: &str,
+) -> Value {
+    let (tx, rx) = oneshot::channel();
+    send_reply_inner(runtime, bot_id, user_id, group_id, message, Some(tx)).await;
+    rx.await.unwrap_or_else(|_| api_failed("发送结果丢失"))
+}
+
+async fn send_reply_inner(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    user_id: u64,
+    group_id: Option<u64>,
+    message: &str,
+    result: Option<ApiResultSender>,
 ) {
     if let Some(gid) = group_id {
         let message = sanitize_outgoing_text(runtime, bot_id, Some(gid), message).await;
@@ -292,6 +346,7 @@ pub async fn send_reply(
         match runtime.get_group_send_status(bot_id, gid).await {
             GroupSendStatus::Muted => {
                 warn!("[{}] 群 {} 内机器人被禁言，跳过发送群消息", bot_id, gid);
+                respond(result, api_failed("机器人在该群被禁言"));
                 return;
             }
             GroupSendStatus::Unknown | GroupSendStatus::Allowed => {}
@@ -301,10 +356,18 @@ pub async fn send_reply(
         let hash = compute_message_hash(bot_id, gid, &message);
         if runtime.message_dedup.lock().await.is_duplicate(hash) {
             warn!("[{}] 消息去重: 跳过重复消息发送", bot_id);
+            respond(result, api_failed("重复消息已跳过"));
             return;
         }
 
-        send_api(
+        // OneBot HTTP 上报上下文内：第一条回复走快速操作响应。
+        let Some(message) = quick_reply(runtime, bot_id, user_id, Some(gid), message).await else {
+            // 快速操作没有 message_id 可返回
+            respond(result, api_ok(Value::Null));
+            return;
+        };
+
+        send_api_inner(
             runtime,
             bot_id,
             "send_group_msg",
@@ -312,6 +375,7 @@ pub async fn send_reply(
                 "group_id": gid,
                 "message": message
             }),
+            result,
         )
         .await;
     } else {
@@ -321,10 +385,16 @@ pub async fn send_reply(
         let hash = compute_message_hash(bot_id, user_id, &message);
         if runtime.message_dedup.lock().await.is_duplicate(hash) {
             warn!("[{}] 消息去重: 跳过重复消息发送", bot_id);
+            respond(result, api_failed("重复消息已跳过"));
             return;
         }
 
-        send_api(
+        let Some(message) = quick_reply(runtime, bot_id, user_id, None, message).await else {
+            respond(result, api_ok(Value::Null));
+            return;
+        };
+
+        send_api_inner(
             runtime,
             bot_id,
             "send_private_msg",
@@ -332,12 +402,65 @@ pub async fn send_reply(
                 "user_id": user_id,
                 "message": message
             }),
+            result,
         )
         .await;
     }
 }
 
+/// 尝试用快速操作响应发送回复；被接管时与普通发送一样写入消息存档。
+/// 需要切块的长消息不走快速回复，交给发送队列分批发送。
+async fn quick_reply(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    user_id: u64,
+    group_id: Option<u64>,
+    message: String,
+) -> Option<String> {
+    if !super::outbound::fits_one_chunk(runtime, bot_id, &message) {
+        return Some(message);
+    }
+    let (action, params) = match group_id {
+        Some(gid) => (
+            "send_group_msg",
+            json!({ "group_id": gid, "message": &message }),
+        ),
+        None => (
+            "send_private_msg",
+            json!({ "user_id": user_id, "message": &message }),
+        ),
+    };
+    let message = super::onebot_http::try_quick_reply(user_id, group_id, message);
+    if message.is_none() {
+        let self_id = runtime.get_self_id(bot_id).await;
+        super::chat_archive::record_outgoing(bot_id, self_id, action, &params);
+    }
+    message
+}
+
 pub async fn send_api(runtime: &Arc<BotRuntime>, bot_id: &str, action: &str, params: Value) {
+    send_api_inner(runtime, bot_id, action, params, None).await;
+}
+
+/// 与 `send_api` 相同，但等待动作结果（发送类动作含排队与重试），返回 OneBot 风格响应。
+pub async fn send_api_tracked(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    action: &str,
+    params: Value,
+) -> Value {
+    let (tx, rx) = oneshot::channel();
+    send_api_inner(runtime, bot_id, action, params, Some(tx)).await;
+    rx.await.unwrap_or_else(|_| api_failed("发送结果丢失"))
+}
+
+async fn send_api_inner(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    action: &str,
+    params: Value,
+    result: Option<ApiResultSender>,
+) {
     let mut params = params;
 
     let group_id = extract_group_id_for_send_action(action, &params);
@@ -348,6 +471,7 @@ pub async fn send_api(runtime: &Arc<BotRuntime>, bot_id: &str, action: &str, par
                     "[{}] 群 {} 内机器人被禁言，跳过发送 {}",
                     bot_id, gid, action
                 );
+                respond(result, api_failed("机器人在该群被禁言"));
                 return;
             }
             GroupSendStatus::Unknown | GroupSendStatus::Allowed => {}
@@ -392,36 +516,137 @@ pub async fn send_api(runtime: &Arc<BotRuntime>, bot_id: &str, action: &str, par
         _ => {}
     }
 
+    if matches!(action, "send_group_msg" | "send_private_msg" | "send_msg") {
+        let self_id = runtime.get_self_id(bot_id).await;
+        super::chat_archive::record_outgoing(bot_id, self_id, action, &params);
+    }
+
+    if is_queued_action(action) {
+        super::outbound::enqueue(runtime, bot_id, action, params, result).await;
+        return;
+    }
+
+    match dispatch_api(runtime, bot_id, action, &params, result.is_some()).await {
+        Ok(resp) => respond(result, resp),
+        Err(SendFailure::Retry(e)) | Err(SendFailure::Fatal(e)) => {
+            warn!("[{}] API {} 失败: {}", bot_id, action, e);
+            respond(result, api_failed(&e));
+        }
+    }
+}
+
+/// OneBot 响应 -> 发送结果：1400 段（参数/权限错误）不重试，其余失败可重试。
+fn classify_onebot_response(resp: &Value) -> Result<(), SendFailure> {
+    let status = resp.get("status").and_then(|s| s.as_str()).unwrap_or("");
+    let retcode = resp.get("retcode").and_then(|v| v.as_i64()).unwrap_or(0);
+    if status != "failed" && retcode == 0 {
+        return Ok(());
+    }
+    let msg = resp
+        .get("wording")
+        .or_else(|| resp.get("message"))
+        .or_else(|| resp.get("msg"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+    let msg = format!("retcode={} {}", retcode, msg).trim().to_string();
+    if (1400..1500).contains(&retcode) {
+        Err(SendFailure::Fatal(msg))
+    } else {
+        Err(SendFailure::Retry(msg))
+    }
+}
+
+/// `HTTP 429 ...` / `HTTP 5xx ...` / 网络错误可重试，其余 4xx 与参数错误不重试。
+fn classify_http_error(e: String) -> SendFailure {
+    if e.starts_with("HTTP request failed") {
+        return SendFailure::Retry(e);
+    }
+    let code = e
+        .strip_prefix("HTTP ")
+        .and_then(|rest| rest.get(..3))
+        .and_then(|c| c.parse::<u16>().ok());
+    match code {
+        Some(429) | Some(500..=599) => SendFailure::Retry(e),
+        _ => SendFailure::Fatal(e),
+    }
+}
+
+/// 把动作真正发给连接。发送类动作（或 `wait` 为真时）会等待响应，
+/// 其余动作保持“发出即返回”，返回 `status: async`。
+pub(super) async fn dispatch_api(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    action: &str,
+    params: &Value,
+    wait: bool,
+) -> Result<Value, SendFailure> {
+    let group_id = extract_group_id_for_send_action(action, params);
+
     let conns = runtime.connections.read().await;
     let Some(conn) = conns.get(bot_id).cloned() else {
-        warn!("[{}] 无法发送API调用，连接不存在", bot_id);
-        return;
+        return Err(SendFailure::Retry("连接不存在".to_string()));
     };
     drop(conns);
 
     match conn {
         BotConnection::OneBot { sender: tx } => {
-            let now_ms = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .map(|d| d.as_millis())
-                .unwrap_or(0);
+            if wait || is_queued_action(action) {
+                let resp = runtime
+                    .call_api(bot_id, action, params.clone())
+                    .await
+                    .ok_or_else(|| SendFailure::Fatal("等待响应超时，发送结果未知".to_string()))?;
+                classify_onebot_response(&resp)?;
+                info!("[{}] 发送API: {}", bot_id, action);
+                return Ok(resp);
+            }
             let msg = json!({
                 "action": action,
                 "params": params,
-                "echo": format!("{}_{}", action, now_ms)
+                "echo": next_echo(action)
             });
             if tx.send(msg.to_string()).is_err() {
-                warn!("[{}] 发送API调用失败: {}", bot_id, action);
-            } else {
-                info!("[{}] 发送API: {}", bot_id, action);
+                return Err(SendFailure::Retry("WS 发送通道已关闭".to_string()));
             }
+            info!("[{}] 发送API: {}", bot_id, action);
+            Ok(json!({ "status": "async", "retcode": 1, "data": null }))
         }
         BotConnection::Discord(conn) => {
-            if let Err(e) = discord_send_api(runtime, bot_id, &conn, action, &params).await {
-                warn!("[{}] Discord API {} 失败: {}", bot_id, action, e);
-            } else {
-                info!("[{}] Discord API: {}", bot_id, action);
-            }
+            let message_id = discord_send_api(runtime, bot_id, &conn, action, params)
+                .await
+                .map_err(classify_http_error)?;
+            info!("[{}] Discord API: {}", bot_id, action);
+            Ok(api_ok(match message_id {
+                Some(id) => json!({ "message_id": id }),
+                None => Value::Null,
+            }))
+        }
+        BotConnection::OneBotHttp(conn) => {
+            let resp = super::onebot_http::onebot_http_call_api(bot_id, &conn, action, params)
+                .await
+                .ok_or_else(|| SendFailure::Retry("HTTP API 请求失败".to_string()))?;
+            classify_onebot_response(&resp)?;
+            info!("[{}] 发送HTTP API: {}", bot_id, action);
+            Ok(resp)
+        }
+        BotConnection::Satori(conn) => {
+            let ids = match super::satori::satori_send_api(&conn, action, params).await {
+                Ok(ids) => ids,
+                Err(e) => {
+                    if e.starts_with("HTTP 403") {
+                        if let Some(gid) = group_id {
+                            runtime
+                                .cache_group_send_status(bot_id, gid, GroupSendStatus::Muted)
+                                .await;
+                        }
+                    }
+                    return Err(classify_http_error(e));
+                }
+            };
+            info!("[{}] Satori API: {}", bot_id, action);
+            Ok(api_ok(match ids.first() {
+                Some(id) => json!({ "message_id": id }),
+                None => Value::Null,
+            }))
         }
     }
 }
@@ -468,13 +693,32 @@ fn guess_image_ext(data: &[u8]) -> &'static str {
     }
 }
 
-fn extract_base64_cq_images(message: &str) -> (String, Vec<DiscordUploadFile>) {
-    // Minimal CQ parser: extract all `[CQ:image,file=base64://...]` images and strip them from content.
+fn guess_audio_ext(data: &[u8]) -> &'static str {
+    if data.starts_with(b"OggS") {
+        "ogg"
+    } else if data.starts_with(b"ID3") || data.starts_with(&[0xFF, 0xFB]) {
+        "mp3"
+    } else if data.len() > 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WAVE" {
+        "wav"
+    } else if data.starts_with(b"#!AMR") {
+        "amr"
+    } else {
+        "bin"
+    }
+}
+
+fn extract_base64_cq_media(message: &str) -> (String, Vec<DiscordUploadFile>) {
+    // Minimal CQ parser: extract all `[CQ:image|record,file=base64://...]` media and strip them from content.
     let mut content = message.to_string();
     let mut files: Vec<DiscordUploadFile> = Vec::new();
     let mut seen: HashSet<String> = HashSet::new();
 
-    while let Some(start) = content.find("[CQ:image") {
+    while let Some(start) = ["[CQ:image", "[CQ:record"]
+        .iter()
+        .filter_map(|tag| content.find(tag))
+        .min()
+    {
+        let is_record = content[start..].starts_with("[CQ:record");
         let end = match content[start..].find(']') {
             Some(i) => start + i,
             None => break,
@@ -498,8 +742,11 @@ fn extract_base64_cq_images(message: &str) -> (String, Vec<DiscordUploadFile>) {
             let b64 = b64.trim();
             if !b64.is_empty() && seen.insert(b64.to_string()) {
                 if let Ok(bytes) = base64::engine::general_purpose::STANDARD.decode(b64) {
-                    let ext = guess_image_ext(&bytes);
-                    let filename = format!("image_{}.{}", files.len() + 1, ext);
+                    let filename = if is_record {
+                        format!("voice_{}.{}", files.len() + 1, guess_audio_ext(&bytes))
+                    } else {
+                        format!("image_{}.{}", files.len() + 1, guess_image_ext(&bytes))
+                    };
                     files.push(DiscordUploadFile { filename, bytes });
                 }
             }
@@ -570,6 +817,16 @@ async fn discord_post_json_with_retry(
     token: &str,
     url: &str,
     payload: &Value,
+) -> Result<(reqwest::StatusCode, String), String> {
+    discord_json_with_retry(http, token, reqwest::Method::POST, url, payload).await
+}
+
+async fn discord_json_with_retry(
+    http: &reqwest::Client,
+    token: &str,
+    method: reqwest::Method,
+    url: &str,
+    payload: &Value,
 ) -> Result<(reqwest::StatusCode, String), String> {
     let auth = discord_auth_header(token);
     let mut attempts = 0u32;
@@ -577,7 +834,7 @@ async fn discord_post_json_with_retry(
     loop {
         attempts += 1;
         let resp = http
-            .post(url)
+            .request(method.clone(), url)
             .header(reqwest::header::AUTHORIZATION, auth.clone())
             .json(payload)
             .send()
@@ -671,6 +928,12 @@ async fn discord_create_dm_channel(conn: &DiscordConnection, user_id: u64) -> Re
     parse_u64(v.get("id")).ok_or_else(|| "missing dm channel id".to_string())
 }
 
+fn created_message_id(body: &str) -> Option<u64> {
+    let v: Value = serde_json::from_str(body).ok()?;
+    parse_u64(v.get("id"))
+}
+
+/// 返回创建的第一条消息 ID（内容被拆成多条时）
 async fn discord_send_channel_message(
     runtime: &Arc<BotRuntime>,
     bot_id: &str,
@@ -678,9 +941,9 @@ async fn discord_send_channel_message(
     channel_id: u64,
     content: &str,
     files: Vec<DiscordUploadFile>,
-) -> Result<(), String> {
+) -> Result<Option<u64>, String> {
     if content.trim().is_empty() && files.is_empty() {
-        return Ok(());
+        return Ok(None);
     }
 
     let url = format!("{}/channels/{}/messages", DISCORD_API_BASE, channel_id);
@@ -691,6 +954,7 @@ async fn discord_send_channel_message(
 
     let mut remaining_files = files;
     let mut first = true;
+    let mut first_id: Option<u64> = None;
 
     for chunk in chunks {
         if first {
@@ -710,6 +974,7 @@ async fn discord_send_channel_message(
                         }
                         return Err(format!("HTTP {status}: {body}"));
                     }
+                    first_id = first_id.or_else(|| created_message_id(&body));
                 }
                 continue;
             }
@@ -735,6 +1000,7 @@ async fn discord_send_channel_message(
                 }
                 return Err(format!("HTTP {status}: {body}"));
             }
+            first_id = first_id.or_else(|| created_message_id(&body));
 
             while !remaining_files.is_empty() {
                 let take = cmp::min(DISCORD_MAX_ATTACHMENTS, remaining_files.len());
@@ -758,6 +1024,7 @@ async fn discord_send_channel_message(
                     }
                     return Err(format!("HTTP {status}: {body}"));
                 }
+                first_id = first_id.or_else(|| created_message_id(&body));
             }
 
             continue;
@@ -775,10 +1042,95 @@ async fn discord_send_channel_message(
                 }
                 return Err(format!("HTTP {status}: {body}"));
             }
+            first_id = first_id.or_else(|| created_message_id(&body));
+        }
+    }
+
+    Ok(first_id)
+}
+
+/// Discord 上一条可原地编辑的消息（流式回复用）。
+#[derive(Debug, Clone, Copy)]
+pub(super) struct DiscordStreamMessage {
+    channel_id: u64,
+    message_id: u64,
+}
+
+/// 当前 bot 是否走 Discord 连接（流式回复据此选择“原地编辑”还是“分段发送”）。
+pub(super) async fn is_discord_bot(runtime: &Arc<BotRuntime>, bot_id: &str) -> bool {
+    matches!(
+        runtime.connections.read().await.get(bot_id),
+        Some(BotConnection::Discord(_))
+    )
+}
+
+/// 创建或编辑流式回复消息：`existing` 为空时新建，否则 PATCH 原消息内容。
+/// 内容超出单条上限时由调用方先切分。
+pub(super) async fn discord_upsert_stream_message(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    user_id: u64,
+    group_id: Option<u64>,
+    existing: Option<DiscordStreamMessage>,
+    content: &str,
+) -> Result<DiscordStreamMessage, String> {
+    let conn = match runtime.connections.read().await.get(bot_id) {
+        Some(BotConnection::Discord(conn)) => conn.clone(),
+        _ => return Err("不是 Discord 连接".to_string()),
+    };
+    let content = sanitize_outgoing_text(runtime, bot_id, group_id, content).await;
+    let content: String = content.chars().take(DISCORD_MAX_CONTENT_CHARS).collect();
+
+    if let Some(msg) = existing {
+        let url = format!(
+            "{}/channels/{}/messages/{}",
+            DISCORD_API_BASE, msg.channel_id, msg.message_id
+        );
+        let payload = json!({ "content": content });
+        let (status, body) = discord_json_with_retry(
+            &conn.http,
+            &conn.token,
+            reqwest::Method::PATCH,
+            &url,
+            &payload,
+        )
+        .await?;
+        if !status.is_success() {
+            return Err(format!("HTTP {status}: {body}"));
         }
+        return Ok(msg);
     }
 
-    Ok(())
+    if let Some(gid) = group_id {
+        if matches!(
+            runtime.get_group_send_status(bot_id, gid).await,
+            GroupSendStatus::Muted
+        ) {
+            return Err("机器人在该频道没有发言权限".to_string());
+        }
+    }
+    let channel_id = match group_id {
+        Some(gid) => gid,
+        None => discord_create_dm_channel(&conn, user_id).await?,
+    };
+    let url = format!("{}/channels/{}/messages", DISCORD_API_BASE, channel_id);
+    let payload = json!({ "content": content });
+    let (status, body) =
+        discord_post_json_with_retry(&conn.http, &conn.token, &url, &payload).await?;
+    if !status.is_success() {
+        if is_discord_permission_error(status, &body) {
+            runtime
+                .cache_group_send_status(bot_id, channel_id, GroupSendStatus::Muted)
+                .await;
+        }
+        return Err(format!("HTTP {status}: {body}"));
+    }
+    let v: Value = serde_json::from_str(&body).map_err(|e| format!("parse message: {e}"))?;
+    let message_id = parse_u64(v.get("id")).ok_or_else(|| "missing message id".to_string())?;
+    Ok(DiscordStreamMessage {
+        channel_id,
+        message_id,
+    })
 }
 
 async fn discord_send_api(
@@ -787,7 +1139,7 @@ async fn discord_send_api(
     conn: &DiscordConnection,
     action: &str,
     params: &Value,
-) -> Result<(), String> {
+) -> Result<Option<u64>, String> {
     match action {
         "send_group_msg" => {
             let channel_id =
@@ -797,10 +1149,8 @@ async fn discord_send_api(
                 .and_then(|v| v.as_str())
                 .unwrap_or_default();
 
-            let (content, files) = extract_base64_cq_images(message);
-            discord_send_channel_message(runtime, bot_id, conn, channel_id, &content, files)
-                .await?;
-            Ok(())
+            let (content, files) = extract_base64_cq_media(message);
+            discord_send_channel_message(runtime, bot_id, conn, channel_id, &content, files).await
         }
         "send_private_msg" => {
             let user_id =
@@ -811,10 +1161,9 @@ async fn discord_send_api(
                 .unwrap_or_default();
 
             let dm_channel_id = discord_create_dm_channel(conn, user_id).await?;
-            let (content, files) = extract_base64_cq_images(message);
+            let (content, files) = extract_base64_cq_media(message);
             discord_send_channel_message(runtime, bot_id, conn, dm_channel_id, &content, files)
-                .await?;
-            Ok(())
+                .await
         }
         "send_group_forward_msg" => {
             let channel_id =
@@ -824,17 +1173,21 @@ async fn discord_send_api(
                 .and_then(|v| v.as_array())
                 .ok_or_else(|| "missing messages".to_string())?;
 
+            let mut first_id = None;
             for node in msgs {
                 let content = node
                     .get("data")
                     .and_then(|d| d.get("content"))
                     .and_then(|v| v.as_str())
                     .unwrap_or_default();
-                let (content, files) = extract_base64_cq_images(content);
-                discord_send_channel_message(runtime, bot_id, conn, channel_id, &content, files)
-                    .await?;
+                let (content, files) = extract_base64_cq_media(content);
+                let id = discord_send_channel_message(
+                    runtime, bot_id, conn, channel_id, &content, files,
+                )
+                .await?;
+                first_id = first_id.or(id);
             }
-            Ok(())
+            Ok(first_id)
         }
         "send_private_forward_msg" => {
             let user_id =
@@ -845,17 +1198,26 @@ async fn discord_send_api(
                 .ok_or_else(|| "missing messages".to_string())?;
 
             let dm_channel_id = discord_create_dm_channel(conn, user_id).await?;
+            let mut first_id = None;
             for node in msgs {
                 let content = node
                     .get("data")
                     .and_then(|d| d.get("content"))
                     .and_then(|v| v.as_str())
                     .unwrap_or_default();
-                let (content, files) = extract_base64_cq_images(content);
-                discord_send_channel_message(runtime, bot_id, conn, dm_channel_id, &content, files)
-                    .await?;
+                let (content, files) = extract_base64_cq_media(content);
+                let id = discord_send_channel_message(
+                    runtime,
+                    bot_id,
+                    conn,
+                    dm_channel_id,
+                    &content,
+                    files,
+                )
+                .await?;
+                first_id = first_id.or(id);
             }
-            Ok(())
+            Ok(first_id)
         }
         "send_msg" => {
             let ty = params
@@ -868,21 +1230,15 @@ async fn discord_send_api(
                 .and_then(|v| v.as_str())
                 .unwrap_or_default();
 
-            if ty == "group" {
-                let channel_id = parse_u64(params.get("group_id"))
-                    .ok_or_else(|| "missing group_id".to_string())?;
-                let (content, files) = extract_base64_cq_images(message);
-                discord_send_channel_message(runtime, bot_id, conn, channel_id, &content, files)
-                    .await?;
+            let channel_id = if ty == "group" {
+                parse_u64(params.get("group_id")).ok_or_else(|| "missing group_id".to_string())?
             } else {
                 let user_id = parse_u64(params.get("user_id"))
                     .ok_or_else(|| "missing user_id".to_string())?;
-                let dm_channel_id = discord_create_dm_channel(conn, user_id).await?;
-                let (content, files) = extract_base64_cq_images(message);
-                discord_send_channel_message(runtime, bot_id, conn, dm_channel_id, &content, files)
-                    .await?;
-            }
-            Ok(())
+                discord_create_dm_channel(conn, user_id).await?
+            };
+            let (content, files) = extract_base64_cq_media(message);
+            discord_send_channel_message(runtime, bot_id, conn, channel_id, &content, files).await
         }
         "send_forward_msg" => {
             let msgs = params
@@ -890,6 +1246,7 @@ async fn discord_send_api(
                 .and_then(|v| v.as_array())
                 .ok_or_else(|| "missing messages".to_string())?;
 
+            let mut first_id = None;
             if let Some(channel_id) = parse_u64(params.get("group_id")) {
                 for node in msgs {
                     let content = node
@@ -897,13 +1254,14 @@ async fn discord_send_api(
                         .and_then(|d| d.get("content"))
                         .and_then(|v| v.as_str())
                         .unwrap_or_default();
-                    let (content, files) = extract_base64_cq_images(content);
-                    discord_send_channel_message(
+                    let (content, files) = extract_base64_cq_media(content);
+                    let id = discord_send_channel_message(
                         runtime, bot_id, conn, channel_id, &content, files,
                     )
                     .await?;
+                    first_id = first_id.or(id);
                 }
-                return Ok(());
+                return Ok(first_id);
             }
 
             if let Some(user_id) = parse_u64(params.get("user_id")) {
@@ -914,8 +1272,8 @@ async fn discord_send_api(
                         .and_then(|d| d.get("content"))
                         .and_then(|v| v.as_str())
                         .unwrap_or_default();
-                    let (content, files) = extract_base64_cq_images(content);
-                    discord_send_channel_message(
+                    let (content, files) = extract_base64_cq_media(content);
+                    let id = discord_send_channel_message(
                         runtime,
                         bot_id,
                         conn,
@@ -924,8 +1282,9 @@ async fn discord_send_api(
                         files,
                     )
                     .await?;
+                    first_id = first_id.or(id);
                 }
-                return Ok(());
+                return Ok(first_id);
             }
 
             Err("missing group_id/user_id".to_string())
@@ -933,3 +1292,85 @@ async fn discord_send_api(
         _ => Err(format!("unsupported action: {}", action)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(resp: Value) -> Result<(), (bool, String)> {
+        classify_onebot_response(&resp).map_err(|e| match e {
+            SendFailure::Retry(m) => (true, m),
+            SendFailure::Fatal(m) => (false, m),
+        })
+    }
+
+    #[test]
+    fn onebot_ok_response_is_success() {
+        assert!(
+            classify(json!({ "status": "ok", "retcode": 0, "data": { "message_id": 7 } })).is_ok()
+        );
+        assert!(classify(json!({ "data": null })).is_ok());
+    }
+
+    #[test]
+    fn onebot_param_errors_are_fatal_and_others_retry() {
+        let (retry, msg) =
+            classify(json!({ "status": "failed", "retcode": 1404, "wording": "群不存在" }))
+                .unwrap_err();
+        assert!(!retry);
+        assert_eq!(msg, "retcode=1404 群不存在");
+
+        let (retry, msg) =
+            classify(json!({ "status": "failed", "retcode": 1200, "message": "风控" }))
+                .unwrap_err();
+        assert!(retry);
+        assert_eq!(msg, "retcode=1200 风控");
+
+        let (retry, _) = classify(json!({ "status": "ok", "retcode": 100 })).unwrap_err();
+        assert!(retry);
+    }
+
+    #[test]
+    fn failed_response_carries_original_retcode() {
+        let (_, msg) =
+            classify(json!({ "status": "failed", "retcode": 1403, "msg": "无权限" })).unwrap_err();
+        let resp = api_failed(&msg);
+        assert_eq!(resp["status"], "failed");
+        assert_eq!(resp["retcode"], 1403);
+        assert_eq!(resp["message"], "retcode=1403 无权限");
+
+        assert_eq!(api_failed("机器人在该群被禁言")["retcode"], -1);
+    }
+
+    #[test]
+    fn respond_delivers_to_waiting_plugin() {
+        let (tx, mut rx) = oneshot::channel();
+        respond(Some(tx), api_ok(json!({ "message_id": 42 })));
+        let resp = rx.try_recv().unwrap();
+        assert_eq!(resp["retcode"], 0);
+        assert_eq!(resp["data"]["message_id"], 42);
+
+        // 没有等待方时直接丢弃
+        respond(None, api_ok(Value::Null));
+    }
+
+    #[test]
+    fn discord_message_id_parsed_from_snowflake_string() {
+        assert_eq!(
+            created_message_id(r#"{"id":"1234567890123456789","content":"hi"}"#),
+            Some(1234567890123456789)
+        );
+        assert_eq!(created_message_id("not json"), None);
+        assert_eq!(created_message_id("{}"), None);
+    }
+
+    #[test]
+    fn http_errors_retry_only_on_rate_limit_and_server_errors() {
+        let retry = |e: &str| matches!(classify_http_error(e.to_string()), SendFailure::Retry(_));
+        assert!(retry("HTTP 429: slow down"));
+        assert!(retry("HTTP 502: bad gateway"));
+        assert!(retry("HTTP request failed: timeout"));
+        assert!(!retry("HTTP 403: forbidden"));
+        assert!(!retry("missing group_id/user_id"));
+    }
+}
@@ -0,0 +1,674 @@
+//! 本地消息存档（可选）：按 bot/群/用户保存收发消息，支持保留期与全文检索。
+//!
+//! 由内置模块 `archive` 控制（默认关闭，可按 bot 覆盖）。写入走单独线程批量提交，
+//! 检索每次独立打开只读连接（WAL），不阻塞消息处理。
+
+use crate::models::SharedState;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, Connection};
+use serde_json::Value;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use super::state_db::{self, now_secs};
+
+const ARCHIVE_DB: &str = "data/archive/messages.db";
+const ARCHIVE_MODULE_ID: &str = "archive";
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(3600);
+const WRITE_BATCH_MAX: usize = 256;
+const SEARCH_DEFAULT_LIMIT: u32 = 50;
+const SEARCH_MAX_LIMIT: u32 = 500;
+/// trigram 分词要求查询至少 3 个字符，更短的查询退化为 LIKE。
+const FTS_MIN_QUERY_CHARS: usize = 3;
+
+#[derive(Debug, Clone, Copy)]
+struct ArchivePolicy {
+    enabled: bool,
+    record_private: bool,
+    record_outgoing: bool,
+    retention_days: u64,
+}
+
+impl ArchivePolicy {
+    fn from_state(state: &SharedState, bot_id: &str) -> Self {
+        let Some(module) = crate::module::get_effective_module(state, bot_id, ARCHIVE_MODULE_ID)
+        else {
+            return Self {
+                enabled: false,
+                record_private: false,
+                record_outgoing: false,
+                retention_days: 0,
+            };
+        };
+        let cfg = &module.config;
+        Self {
+            enabled: module.enabled,
+            record_private: cfg
+                .get("record_private")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(true),
+            record_outgoing: cfg
+                .get("record_outgoing")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(true),
+            retention_days: cfg
+                .get("retention_days")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(30),
+        }
+    }
+}
+
+/// 最近一次读取到的各 bot 存档策略；发送路径拿不到 `SharedState`，只能查这里。
+static POLICIES: Lazy<DashMap<String, ArchivePolicy>> = Lazy::new(DashMap::new);
+
+fn refresh_policy(state: &SharedState, bot_id: &str) -> ArchivePolicy {
+    let policy = ArchivePolicy::from_state(state, bot_id);
+    POLICIES.insert(bot_id.to_string(), policy);
+    policy
+}
+
+struct ArchiveRecord {
+    bot_id: String,
+    group_id: Option<u64>,
+    user_id: u64,
+    sender_name: String,
+    message_id: Option<i64>,
+    time: u64,
+    is_self: bool,
+    text: String,
+    message: String,
+}
+
+enum ArchiveCmd {
+    Insert(ArchiveRecord),
+    Prune { bot_id: String, before: u64 },
+}
+
+static WRITER: Lazy<Mutex<Option<mpsc::Sender<ArchiveCmd>>>> = Lazy::new(|| Mutex::new(None));
+
+fn open_db(read_only: bool) -> Result<Connection, String> {
+    state_db::open(ARCHIVE_DB, read_only, "消息存档")
+}
+
+fn init_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "PRAGMA journal_mode = WAL;
+         PRAGMA synchronous = NORMAL;
+         CREATE TABLE IF NOT EXISTS messages (
+             id INTEGER PRIMARY KEY AUTOINCREMENT,
+             bot_id TEXT NOT NULL,
+             group_id INTEGER,
+             user_id INTEGER NOT NULL,
+             sender_name TEXT NOT NULL DEFAULT '',
+             message_id INTEGER,
+             time INTEGER NOT NULL,
+             is_self INTEGER NOT NULL DEFAULT 0,
+             text TEXT NOT NULL DEFAULT '',
+             message TEXT NOT NULL DEFAULT ''
+         );
+         CREATE INDEX IF NOT EXISTS idx_messages_group ON messages(bot_id, group_id, time);
+         CREATE INDEX IF NOT EXISTS idx_messages_user ON messages(bot_id, user_id, time);
+         CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+             text, content = 'messages', content_rowid = 'id', tokenize = 'trigram'
+         );
+         CREATE TRIGGER IF NOT EXISTS messages_ai AFTER INSERT ON messages BEGIN
+             INSERT INTO messages_fts(rowid, text) VALUES (new.id, new.text);
+         END;
+         CREATE TRIGGER IF NOT EXISTS messages_ad AFTER DELETE ON messages BEGIN
+             INSERT INTO messages_fts(messages_fts, rowid, text) VALUES ('delete', old.id, old.text);
+         END;",
+    )
+    .map_err(|e| format!("初始化消息存档失败: {e}"))
+}
+
+fn apply_cmd(conn: &Connection, cmd: ArchiveCmd) -> rusqlite::Result<()> {
+    match cmd {
+        ArchiveCmd::Insert(r) => {
+            conn.execute(
+                "INSERT INTO messages
+                     (bot_id, group_id, user_id, sender_name, message_id, time, is_self, text, message)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    r.bot_id,
+                    r.group_id.map(|g| g as i64),
+                    r.user_id as i64,
+                    r.sender_name,
+                    r.message_id,
+                    r.time as i64,
+                    r.is_self,
+                    r.text,
+                    r.message,
+                ],
+            )?;
+        }
+        ArchiveCmd::Prune { bot_id, before } => {
+            let n = conn.execute(
+                "DELETE FROM messages WHERE bot_id = ?1 AND time < ?2",
+                params![bot_id, before as i64],
+            )?;
+            if n > 0 {
+                info!("[{}] 消息存档清理过期记录 {} 条", bot_id, n);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_writer(rx: mpsc::Receiver<ArchiveCmd>) {
+    let mut conn = match open_db(false).and_then(|c| init_schema(&c).map(|_| c)) {
+        Ok(c) => c,
+        Err(e) => {
+            warn!("{}", e);
+            return;
+        }
+    };
+
+    while let Ok(first) = rx.recv() {
+        // 把已排队的命令合并进同一个事务，突发消息时减少 fsync。
+        let mut batch = vec![first];
+        while batch.len() < WRITE_BATCH_MAX {
+            match rx.try_recv() {
+                Ok(cmd) => batch.push(cmd),
+                Err(_) => break,
+            }
+        }
+        let result = conn.transaction().and_then(|tx| {
+            for cmd in batch {
+                apply_cmd(&tx, cmd)?;
+            }
+            tx.commit()
+        });
+        if let Err(e) = result {
+            warn!("写入消息存档失败: {}", e);
+        }
+    }
+}
+
+fn submit(cmd: ArchiveCmd) {
+    let Ok(mut guard) = WRITER.lock() else {
+        return;
+    };
+    if guard.is_none() {
+        let (tx, rx) = mpsc::channel();
+        if let Err(e) = std::thread::Builder::new()
+            .name("chat-archive".to_string())
+            .spawn(move || run_writer(rx))
+        {
+            warn!("启动消息存档写入线程失败: {}", e);
+            return;
+        }
+        *guard = Some(tx);
+    }
+    if let Some(tx) = guard.as_ref() {
+        if let Err(mpsc::SendError(_)) = tx.send(cmd) {
+            // 写入线程已退出（通常是打不开数据库），下次再重试。
+            *guard = None;
+        }
+    }
+}
+
+fn parse_u64(v: Option<&Value>) -> Option<u64> {
+    match v? {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.trim().parse::<u64>().ok(),
+        _ => None,
+    }
+}
+
+/// 把 CQ 码中的媒体替换为占位文本，保留纯文本部分供检索。
+fn cq_string_to_text(raw: &str) -> String {
+    let mut out = String::new();
+    let mut rest = raw;
+    while let Some(start) = rest.find("[CQ:") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 4..];
+        let Some(end) = after.find(']') else {
+            out.push_str(&rest[start..]);
+            return out;
+        };
+        let code = &after[..end];
+        let (ty, args) = code.split_once(',').unwrap_or((code, ""));
+        let arg = |key: &str| {
+            args.split(',')
+                .find_map(|kv| kv.strip_prefix(key)?.strip_prefix('='))
+                .map(|v| v.replace("&#44;", ",").replace("&amp;", "&"))
+        };
+        match ty {
+            "at" => {
+                out.push('@');
+                out.push_str(&arg("name").or_else(|| arg("qq")).unwrap_or_default());
+            }
+            "reply" => {}
+            other => out.push_str(&placeholder_for(other)),
+        }
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    out.replace("&#91;", "[")
+        .replace("&#93;", "]")
+        .replace("&amp;", "&")
+}
+
+fn placeholder_for(ty: &str) -> String {
+    match ty {
+        "image" | "mface" => "[图片]".to_string(),
+        "face" => "[表情]".to_string(),
+        "record" => "[语音]".to_string(),
+        "video" => "[视频]".to_string(),
+        "file" => "[文件]".to_string(),
+        "forward" | "node" => "[合并转发]".to_string(),
+        "json" | "xml" => "[卡片]".to_string(),
+        other => format!("[{}]", other),
+    }
+}
+
+/// OneBot 消息（字符串或段数组）转成用于全文检索的纯文本。
+fn message_to_text(message: &Value) -> String {
+    match message {
+        Value::String(s) => cq_string_to_text(s),
+        Value::Array(segments) => {
+            let mut out = String::new();
+            for seg in segments {
+                let ty = seg.get("type").and_then(|t| t.as_str()).unwrap_or("");
+                let data = seg.get("data");
+                let field = |key: &str| {
+                    data.and_then(|d| d.get(key))
+                        .map(|v| match v {
+                            Value::String(s) => s.clone(),
+                            other => other.to_string(),
+                        })
+                        .unwrap_or_default()
+                };
+                match ty {
+                    "text" => out.push_str(&field("text")),
+                    "at" => {
+                        let name = field("name");
+                        out.push('@');
+                        out.push_str(&if name.is_empty() { field("qq") } else { name });
+                    }
+                    "reply" => {}
+                    "" => {}
+                    other => out.push_str(&placeholder_for(other)),
+                }
+            }
+            out
+        }
+        _ => String::new(),
+    }
+}
+
+/// 记录一条收到的消息（需在 `handle_message` 去重之后调用）。
+pub(super) fn record_incoming(state: &SharedState, bot_id: &str, event: &Value) {
+    let policy = refresh_policy(state, bot_id);
+    if !policy.enabled {
+        return;
+    }
+    let group_id = parse_u64(event.get("group_id"));
+    if group_id.is_none() && !policy.record_private {
+        return;
+    }
+
+    let message = event
+        .get("message")
+        .cloned()
+        .unwrap_or_else(|| event.get("raw_message").cloned().unwrap_or(Value::Null));
+    let mut text = message_to_text(&message);
+    if text.is_empty() {
+        text = event
+            .get("raw_message")
+            .and_then(|v| v.as_str())
+            .map(cq_string_to_text)
+            .unwrap_or_default();
+    }
+    let sender = event.get("sender");
+    let sender_name = sender
+        .and_then(|s| s.get("card"))
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.trim().is_empty())
+        .or_else(|| {
+            sender
+                .and_then(|s| s.get("nickname"))
+                .and_then(|v| v.as_str())
+        })
+        .unwrap_or("")
+        .to_string();
+
+    submit(ArchiveCmd::Insert(ArchiveRecord {
+        bot_id: bot_id.to_string(),
+        group_id,
+        user_id: parse_u64(event.get("user_id")).unwrap_or(0),
+        sender_name,
+        message_id: event.get("message_id").and_then(|v| {
+            v.as_i64()
+                .or_else(|| v.as_str().and_then(|s| s.parse().ok()))
+        }),
+        time: parse_u64(event.get("time")).unwrap_or_else(now_secs),
+        is_self: false,
+        text,
+        message: message.to_string(),
+    }));
+}
+
+/// 记录一条机器人发出的消息（发送 API 调用时）。
+pub(super) fn record_outgoing(bot_id: &str, self_id: Option<u64>, action: &str, params: &Value) {
+    let Some(policy) = POLICIES.get(bot_id).map(|p| *p.value()) else {
+        return;
+    };
+    if !policy.enabled || !policy.record_outgoing {
+        return;
+    }
+
+    let group_id = match action {
+        "send_group_msg" => parse_u64(params.get("group_id")),
+        "send_private_msg" => None,
+        "send_msg" => {
+            if params.get("message_type").and_then(|v| v.as_str()) == Some("private") {
+                None
+            } else {
+                parse_u64(params.get("group_id"))
+            }
+        }
+        _ => return,
+    };
+    // 私聊按对方 user_id 归档，方便按会话检索。
+    let user_id = match group_id {
+        Some(_) => self_id.unwrap_or(0),
+        None => {
+            if !policy.record_private {
+                return;
+            }
+            parse_u64(params.get("user_id")).unwrap_or(0)
+        }
+    };
+    let Some(message) = params.get("message") else {
+        return;
+    };
+
+    submit(ArchiveCmd::Insert(ArchiveRecord {
+        bot_id: bot_id.to_string(),
+        group_id,
+        user_id,
+        sender_name: String::new(),
+        message_id: None,
+        time: now_secs(),
+        is_self: true,
+        text: message_to_text(message),
+        message: message.to_string(),
+    }));
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct ChatArchiveQuery {
+    #[serde(default)]
+    pub bot_id: Option<String>,
+    #[serde(default)]
+    pub q: Option<String>,
+    #[serde(default)]
+    pub group_id: Option<u64>,
+    #[serde(default)]
+    pub user_id: Option<u64>,
+    /// Unix 秒，只返回该时间之后的消息
+    #[serde(default)]
+    pub since: Option<u64>,
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ArchivedMessage {
+    pub id: i64,
+    pub bot_id: String,
+    pub group_id: Option<u64>,
+    pub user_id: u64,
+    pub sender_name: String,
+    pub message_id: Option<i64>,
+    pub time: u64,
+    pub is_self: bool,
+    pub text: String,
+    pub message: Value,
+}
+
+fn search_blocking(query: &ChatArchiveQuery) -> Result<Vec<ArchivedMessage>, String> {
+    if !std::path::Path::new(ARCHIVE_DB).exists() {
+        return Ok(Vec::new());
+    }
+    search(&open_db(true)?, query)
+}
+
+fn search(conn: &Connection, query: &ChatArchiveQuery) -> Result<Vec<ArchivedMessage>, String> {
+    let mut sql = String::from(
+        "SELECT m.id, m.bot_id, m.group_id, m.user_id, m.sender_name, m.message_id,
+                m.time, m.is_self, m.text, m.message
+         FROM messages m WHERE 1 = 1",
+    );
+    let mut args: Vec<SqlValue> = Vec::new();
+
+    if let Some(bot_id) = query.bot_id.as_deref().filter(|s| !s.is_empty()) {
+        sql.push_str(" AND m.bot_id = ?");
+        args.push(SqlValue::Text(bot_id.to_string()));
+    }
+    if let Some(gid) = query.group_id {
+        sql.push_str(" AND m.group_id = ?");
+        args.push(SqlValue::Integer(gid as i64));
+    }
+    if let Some(uid) = query.user_id {
+        sql.push_str(" AND m.user_id = ?");
+        args.push(SqlValue::Integer(uid as i64));
+    }
+    if let Some(since) = query.since {
+        sql.push_str(" AND m.time >= ?");
+        args.push(SqlValue::Integer(since as i64));
+    }
+    if let Some(q) = query.q.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        if q.chars().count() >= FTS_MIN_QUERY_CHARS {
+            // 整体作为短语匹配，避免用户输入被当作 FTS 语法解析。
+            sql.push_str(
+                " AND m.id IN (SELECT rowid FROM messages_fts WHERE messages_fts MATCH ?)",
+            );
+            args.push(SqlValue::Text(format!("\"{}\"", q.replace('"', "\"\""))));
+        } else {
+            sql.push_str(" AND m.text LIKE ? ESCAPE '\\'");
+            let escaped = q
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            args.push(SqlValue::Text(format!("%{}%", escaped)));
+        }
+    }
+
+    let limit = query
+        .limit
+        .filter(|l| *l > 0)
+        .unwrap_or(SEARCH_DEFAULT_LIMIT)
+        .min(SEARCH_MAX_LIMIT);
+    sql.push_str(" ORDER BY m.time DESC, m.id DESC LIMIT ?");
+    args.push(SqlValue::Integer(limit as i64));
+
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| format!("检索消息存档失败: {e}"))?;
+    let rows = stmt
+        .query_map(rusqlite::params_from_iter(args), |row| {
+            let message: String = row.get(9)?;
+            Ok(ArchivedMessage {
+                id: row.get(0)?,
+                bot_id: row.get(1)?,
+                group_id: row.get::<_, Option<i64>>(2)?.map(|v| v as u64),
+                user_id: row.get::<_, i64>(3)? as u64,
+                sender_name: row.get(4)?,
+                message_id: row.get(5)?,
+                time: row.get::<_, i64>(6)? as u64,
+                is_self: row.get(7)?,
+                text: row.get(8)?,
+                message: serde_json::from_str(&message).unwrap_or(Value::String(message)),
+            })
+        })
+        .map_err(|e| format!("检索消息存档失败: {e}"))?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("读取消息存档失败: {e}"))
+}
+
+/// 检索消息存档，结果按时间倒序。
+pub async fn search_chat_archive(query: ChatArchiveQuery) -> Result<Vec<ArchivedMessage>, String> {
+    tokio::task::spawn_blocking(move || search_blocking(&query))
+        .await
+        .map_err(|e| format!("检索任务失败: {e}"))?
+}
+
+/// 定期刷新各 bot 的存档策略并按保留期清理过期消息。
+pub async fn start_chat_archive_maintenance(state: SharedState) {
+    loop {
+        let bot_ids: Vec<String> = state.bots.iter().map(|b| b.id.clone()).collect();
+        for bot_id in bot_ids {
+            let policy = refresh_policy(&state, &bot_id);
+            // 关闭存档后仍按保留期清理旧数据；retention_days = 0 表示永久保留。
+            if policy.retention_days == 0 || !std::path::Path::new(ARCHIVE_DB).exists() {
+                continue;
+            }
+            let before = now_secs().saturating_sub(policy.retention_days * 86_400);
+            submit(ArchiveCmd::Prune { bot_id, before });
+        }
+        tokio::time::sleep(MAINTENANCE_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record(group_id: Option<u64>, user_id: u64, time: u64, text: &str) -> ArchiveCmd {
+        ArchiveCmd::Insert(ArchiveRecord {
+            bot_id: "bot".to_string(),
+            group_id,
+            user_id,
+            sender_name: String::new(),
+            message_id: None,
+            time,
+            is_self: false,
+            text: text.to_string(),
+            message: json!(text).to_string(),
+        })
+    }
+
+    fn archive(records: Vec<ArchiveCmd>) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_schema(&conn).unwrap();
+        for r in records {
+            apply_cmd(&conn, r).unwrap();
+        }
+        conn
+    }
+
+    fn texts(conn: &Connection, query: ChatArchiveQuery) -> Vec<String> {
+        search(conn, &query)
+            .unwrap()
+            .into_iter()
+            .map(|m| m.text)
+            .collect()
+    }
+
+    fn q(text: &str) -> ChatArchiveQuery {
+        ChatArchiveQuery {
+            q: Some(text.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn full_text_search_matches_substrings() {
+        let conn = archive(vec![
+            record(Some(1), 10, 100, "今晚服务器要重启维护"),
+            record(Some(1), 11, 200, "服务器重启完成了"),
+            record(Some(2), 10, 300, "明天吃什么"),
+        ]);
+        assert_eq!(
+            texts(&conn, q("服务器")),
+            ["服务器重启完成了", "今晚服务器要重启维护"]
+        );
+        assert!(texts(&conn, q("不存在的内容")).is_empty());
+        // FTS 语法字符按原文匹配，不会报错
+        assert!(texts(&conn, q("\"a OR b")).is_empty());
+        assert!(texts(&conn, q("重启 NEAR 维护")).is_empty());
+    }
+
+    #[test]
+    fn short_queries_fall_back_to_like() {
+        let conn = archive(vec![
+            record(Some(1), 10, 100, "猫猫"),
+            record(Some(1), 10, 200, "100% 好"),
+            record(Some(1), 10, 300, "a_b"),
+            record(Some(1), 10, 400, "axb"),
+        ]);
+        assert_eq!(texts(&conn, q("猫")), ["猫猫"]);
+        // LIKE 通配符按字面匹配
+        assert_eq!(texts(&conn, q("%")), ["100% 好"]);
+        assert_eq!(texts(&conn, q("_")), ["a_b"]);
+    }
+
+    #[test]
+    fn filters_and_limits_results() {
+        let conn = archive(vec![
+            record(Some(1), 10, 100, "hello one"),
+            record(Some(1), 11, 200, "hello two"),
+            record(Some(2), 10, 300, "hello three"),
+            record(None, 10, 400, "hello private"),
+        ]);
+        let query = |f: fn(&mut ChatArchiveQuery)| {
+            let mut query = q("hello");
+            f(&mut query);
+            texts(&conn, query)
+        };
+        assert_eq!(query(|q| q.group_id = Some(1)), ["hello two", "hello one"]);
+        assert_eq!(
+            query(|q| q.user_id = Some(10)),
+            ["hello private", "hello three", "hello one"]
+        );
+        assert_eq!(
+            query(|q| q.since = Some(300)),
+            ["hello private", "hello three"]
+        );
+        assert_eq!(query(|q| q.limit = Some(1)), ["hello private"]);
+        assert!(query(|q| q.bot_id = Some("other".to_string())).is_empty());
+    }
+
+    #[test]
+    fn pruned_messages_leave_the_index() {
+        let conn = archive(vec![
+            record(Some(1), 10, 100, "old message"),
+            record(Some(1), 10, 500, "new message"),
+        ]);
+        apply_cmd(
+            &conn,
+            ArchiveCmd::Prune {
+                bot_id: "bot".to_string(),
+                before: 300,
+            },
+        )
+        .unwrap();
+        assert_eq!(texts(&conn, q("message")), ["new message"]);
+    }
+
+    #[test]
+    fn converts_messages_to_search_text() {
+        assert_eq!(
+            message_to_text(&json!(
+                "[CQ:reply,id=1][CQ:at,qq=123,name=小明] 看[CQ:image,file=a.png]"
+            )),
+            "@小明 看[图片]"
+        );
+        assert_eq!(message_to_text(&json!("&#91;不是CQ码&#93;")), "[不是CQ码]");
+        let segments = json!([
+            { "type": "at", "data": { "qq": 456 } },
+            { "type": "text", "data": { "text": " 你好" } },
+            { "type": "record", "data": { "file": "a.silk" } }
+        ]);
+        assert_eq!(message_to_text(&segments), "@456 你好[语音]");
+    }
+}
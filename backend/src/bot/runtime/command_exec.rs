@@ -7,10 +7,19 @@ use tracing::warn;
 use super::api::send_reply;
 use super::connection::BotRuntime;
 use super::help_image::generate_help_image;
+use super::llm_usage;
 use super::message::is_admin;
 
+mod knowledge_base;
 mod llm_abuse;
+mod llm_cache;
 mod llm_forward;
+mod llm_image;
+mod llm_json;
+mod llm_memory;
+mod llm_stream;
+mod llm_tools;
+mod media_cache;
 mod plugin_outputs;
 
 pub struct CommandExecInput<'a> {
@@ -92,7 +101,12 @@ pub async fn execute_command(
 
             match state.plugin_manager.on_command(plugin_id, ctx).await {
                 Ok(outputs) => {
-                    plugin_outputs::process_plugin_outputs(state, runtime, bot_id, &outputs).await
+                    // 带 requestId 的输出需要把结果回调给插件（await nbot.llm.chat() 等）
+                    let handle = plugin_outputs::process_plugin_outputs_with_llm_response(
+                        state, runtime, bot_id, plugin_id, &outputs,
+                    );
+                    llm_usage::with_plugin_scope(plugin_id, handle).await;
+                    plugin_outputs::watch_pending_requests(state, runtime, bot_id, plugin_id);
                 }
                 Err(e) => {
                     warn!("[{}] 插件 {} onCommand 失败: {}", bot_id, plugin_id, e);
@@ -130,6 +144,17 @@ pub(super) async fn process_plugin_outputs_with_source(
     plugin_outputs::process_plugin_outputs_with_source(state, runtime, bot_id, outputs).await
 }
 
+/// 群文件上传后按知识库配置自动导入（后台执行）
+pub(super) fn index_group_upload(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    group_id: u64,
+    file: &serde_json::Value,
+) {
+    knowledge_base::index_group_upload(state, runtime, bot_id, group_id, file)
+}
+
 fn generate_help_text(state: &SharedState, bot_id: &str) -> String {
     let prefix = super::message::get_command_prefix(state, bot_id);
     let mut text = String::new();
@@ -0,0 +1,704 @@
+//! 群知识库：把群文件、群公告等文本切块后计算向量，保存在 `data/state/kb/<bot>/<group>.json`，
+//! 插件通过 `nbot.kb.search` 按语义检索。配置位于 llm 模块的 `knowledge_base`：
+//! `embedding_model`（向量模型的映射名，默认 "embedding"）、`chunk_chars`、`chunk_overlap`、
+//! `auto_ingest_uploads`（群文件上传后自动导入）与 `groups`（自动导入的群，空为全部）。
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tracing::{info, warn};
+
+use crate::models::SharedState;
+
+use super::super::connection::BotRuntime;
+use super::super::state_db::now_secs;
+use super::llm_forward::download_text_for_index;
+use super::llm_forward::multimodal::common::{call_embeddings, resolve_llm_config_by_name};
+
+const KB_DIR: &str = "data/state/kb";
+const DEFAULT_EMBEDDING_MODEL: &str = "embedding";
+const DEFAULT_CHUNK_CHARS: usize = 500;
+const DEFAULT_CHUNK_OVERLAP: usize = 80;
+/// 每个群最多保留的片段数，超出后丢弃最早导入的
+const MAX_CHUNKS_PER_GROUP: usize = 5000;
+const MAX_SEARCH_K: usize = 20;
+/// 自动导入时认为是文本文档的扩展名（压缩包另行判断）
+const TEXT_EXTS: &[&str] = &[
+    "txt", "md", "markdown", "csv", "tsv", "json", "log", "yaml", "yml", "toml", "ini", "xml",
+    "html", "htm",
+];
+const ARCHIVE_EXTS: &[&str] = &["zip", "tar", "tgz", "gz"];
+/// 内存中最多保留的索引数，超出后卸载最久未用且空闲的
+const MAX_CACHED_INDEXES: usize = 16;
+
+/// 首次使用时从磁盘加载；每个索引单独加锁，检索之间可并行
+type SharedIndex = Arc<RwLock<Option<KbIndex>>>;
+
+struct CachedIndex {
+    index: SharedIndex,
+    last_used: u64,
+}
+
+static INDEXES: Lazy<Mutex<HashMap<PathBuf, CachedIndex>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+static USE_SEQ: AtomicU64 = AtomicU64::new(0);
+
+struct KbSettings {
+    embedding_model: String,
+    chunk_chars: usize,
+    chunk_overlap: usize,
+    auto_ingest_uploads: bool,
+    groups: Vec<u64>,
+}
+
+impl KbSettings {
+    fn load(state: &SharedState, bot_id: &str) -> Self {
+        let module = crate::module::get_effective_module(state, bot_id, "llm");
+        let cfg = module
+            .as_ref()
+            .and_then(|m| m.config.get("knowledge_base"))
+            .unwrap_or(&Value::Null);
+        let chunk_chars = cfg
+            .get("chunk_chars")
+            .and_then(|v| v.as_u64())
+            .map_or(DEFAULT_CHUNK_CHARS, |v| v as usize)
+            .clamp(100, 4000);
+        Self {
+            embedding_model: cfg
+                .get("embedding_model")
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .unwrap_or(DEFAULT_EMBEDDING_MODEL)
+                .to_string(),
+            chunk_chars,
+            chunk_overlap: cfg
+                .get("chunk_overlap")
+                .and_then(|v| v.as_u64())
+                .map_or(DEFAULT_CHUNK_OVERLAP, |v| v as usize)
+                .min(chunk_chars / 2),
+            auto_ingest_uploads: cfg
+                .get("auto_ingest_uploads")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            groups: cfg
+                .get("groups")
+                .and_then(|v| v.as_array())
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|g| {
+                            g.as_u64()
+                                .or_else(|| g.as_str().and_then(|s| s.trim().parse().ok()))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct KbIndex {
+    /// 生成向量所用的模型；换模型后旧向量不可比较，需要重新导入
+    #[serde(default)]
+    model: String,
+    #[serde(default)]
+    chunks: Vec<KbChunk>,
+}
+
+impl KbIndex {
+    /// 写入某个来源的片段（替换该来源的旧内容）；换了向量模型时先清空整个索引。
+    fn replace_source(
+        &mut self,
+        label: &str,
+        model: String,
+        source: &str,
+        title: &str,
+        added_at: u64,
+        chunks: Vec<(String, Vec<f32>)>,
+    ) {
+        if !self.model.is_empty() && self.model != model && !self.chunks.is_empty() {
+            warn!(
+                "知识库 {} 的向量模型从 {} 变更为 {}，已清空旧索引",
+                label, self.model, model
+            );
+            self.chunks.clear();
+        }
+        self.model = model;
+        self.chunks.retain(|c| c.source != source);
+        for (text, values) in chunks {
+            self.chunks.push(KbChunk {
+                source: source.to_string(),
+                title: title.to_string(),
+                text,
+                added_at,
+                values: normalize(values),
+            });
+        }
+        if self.chunks.len() > MAX_CHUNKS_PER_GROUP {
+            let excess = self.chunks.len() - MAX_CHUNKS_PER_GROUP;
+            self.chunks.drain(..excess);
+        }
+    }
+
+    fn remove_source(&mut self, source: &str) -> usize {
+        let before = self.chunks.len();
+        self.chunks.retain(|c| c.source != source);
+        before - self.chunks.len()
+    }
+
+    /// 与（已归一化的）查询向量的余弦相似度；维度不同的片段跳过。
+    fn score(&self, query: &[f32]) -> Vec<KbHit> {
+        self.chunks
+            .iter()
+            .filter(|c| c.values.len() == query.len())
+            .map(|c| KbHit {
+                source: c.source.clone(),
+                title: c.title.clone(),
+                text: c.text.clone(),
+                score: c.values.iter().zip(query).map(|(a, b)| a * b).sum(),
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+struct KbChunk {
+    source: String,
+    #[serde(default)]
+    title: String,
+    text: String,
+    #[serde(default)]
+    added_at: u64,
+    /// 归一化后的向量，文件中存为 f32 小端序的 base64
+    #[serde(rename = "vector", with = "vector_base64")]
+    values: Vec<f32>,
+}
+
+mod vector_base64 {
+    use super::{decode_vector, encode_vector};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(values: &[f32], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&encode_vector(values))
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<f32>, D::Error> {
+        let raw = String::deserialize(d)?;
+        Ok(decode_vector(&raw))
+    }
+}
+
+/// 检索结果
+#[derive(Debug, Clone, serde::Serialize)]
+pub(super) struct KbHit {
+    pub(super) source: String,
+    pub(super) title: String,
+    pub(super) text: String,
+    pub(super) score: f32,
+}
+
+fn index_path(bot_id: &str, group_id: u64) -> PathBuf {
+    let safe_bot: String = bot_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    Path::new(KB_DIR)
+        .join(safe_bot)
+        .join(format!("{group_id}.json"))
+}
+
+fn encode_vector(values: &[f32]) -> String {
+    let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    BASE64.encode(bytes)
+}
+
+fn decode_vector(raw: &str) -> Vec<f32> {
+    BASE64
+        .decode(raw.as_bytes())
+        .map(|bytes| {
+            bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn normalize(mut v: Vec<f32>) -> Vec<f32> {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        v.iter_mut().for_each(|x| *x /= norm);
+    }
+    v
+}
+
+fn load_index(path: &Path) -> KbIndex {
+    let Ok(raw) = std::fs::read_to_string(path) else {
+        return KbIndex::default();
+    };
+    match serde_json::from_str::<KbIndex>(&raw) {
+        Ok(index) => index,
+        Err(e) => {
+            warn!("解析知识库索引 {} 失败: {}", path.display(), e);
+            KbIndex::default()
+        }
+    }
+}
+
+fn save_index(path: &Path, index: &KbIndex) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("创建知识库目录失败: {e}"))?;
+    }
+    let json = serde_json::to_string(index).map_err(|e| format!("序列化知识库失败: {e}"))?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, json).map_err(|e| format!("写入知识库失败: {e}"))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("写入知识库失败: {e}"))
+}
+
+fn cached_index(path: &Path) -> Result<SharedIndex, String> {
+    let mut indexes = INDEXES.lock().map_err(|_| "知识库锁已损坏".to_string())?;
+    let seq = USE_SEQ.fetch_add(1, Ordering::Relaxed);
+    let index = {
+        let entry = indexes
+            .entry(path.to_path_buf())
+            .or_insert_with(|| CachedIndex {
+                index: Arc::new(RwLock::new(None)),
+                last_used: seq,
+            });
+        entry.last_used = seq;
+        entry.index.clone()
+    };
+    if indexes.len() > MAX_CACHED_INDEXES {
+        // 正在使用的索引（还有其他引用）不卸载，避免与重新加载的副本互相覆盖
+        let idle = indexes
+            .iter()
+            .filter(|(_, c)| Arc::strong_count(&c.index) == 1)
+            .min_by_key(|(_, c)| c.last_used)
+            .map(|(p, _)| p.clone());
+        if let Some(idle) = idle {
+            indexes.remove(&idle);
+        }
+    }
+    Ok(index)
+}
+
+/// 在阻塞线程里修改某个群的索引（首次访问时从磁盘加载）。
+async fn with_index<T, F>(bot_id: &str, group_id: u64, f: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&Path, &mut KbIndex) -> Result<T, String> + Send + 'static,
+{
+    let path = index_path(bot_id, group_id);
+    tokio::task::spawn_blocking(move || {
+        let shared = cached_index(&path)?;
+        let mut guard = shared.write().map_err(|_| "知识库锁已损坏".to_string())?;
+        let index = guard.get_or_insert_with(|| load_index(&path));
+        f(&path, index)
+    })
+    .await
+    .map_err(|e| format!("知识库任务失败: {e}"))?
+}
+
+/// 在阻塞线程里只读访问某个群的索引。
+async fn read_index<T, F>(bot_id: &str, group_id: u64, f: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&KbIndex) -> T + Send + 'static,
+{
+    let path = index_path(bot_id, group_id);
+    tokio::task::spawn_blocking(move || {
+        let shared = cached_index(&path)?;
+        {
+            let guard = shared.read().map_err(|_| "知识库锁已损坏".to_string())?;
+            if let Some(index) = guard.as_ref() {
+                return Ok(f(index));
+            }
+        }
+        let mut guard = shared.write().map_err(|_| "知识库锁已损坏".to_string())?;
+        Ok(f(guard.get_or_insert_with(|| load_index(&path))))
+    })
+    .await
+    .map_err(|e| format!("知识库任务失败: {e}"))?
+}
+
+/// 按行切块：每块约 `chunk_chars` 个字符，相邻块重叠 `overlap` 个字符。
+fn split_chunks(text: &str, chunk_chars: usize, overlap: usize) -> Vec<String> {
+    let mut pieces: Vec<String> = Vec::new();
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let chars: Vec<char> = line.chars().collect();
+        pieces.extend(chars.chunks(chunk_chars).map(|c| c.iter().collect()));
+    }
+
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0usize;
+    for piece in pieces {
+        let len = piece.chars().count();
+        if current_len > 0 && current_len + len + 1 > chunk_chars {
+            let tail: String = {
+                let chars: Vec<char> = current.chars().collect();
+                chars[chars.len().saturating_sub(overlap)..]
+                    .iter()
+                    .collect()
+            };
+            chunks.push(std::mem::replace(&mut current, tail));
+            current_len = current.chars().count();
+        }
+        if current_len > 0 {
+            current.push('\n');
+            current_len += 1;
+        }
+        current.push_str(&piece);
+        current_len += len;
+    }
+    if !current.trim().is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// 导入一段文本；同一 `source` 再次导入时替换旧内容。返回写入的片段数。
+pub(super) async fn ingest_text(
+    state: &SharedState,
+    bot_id: &str,
+    group_id: u64,
+    source: &str,
+    title: &str,
+    text: &str,
+) -> Result<usize, String> {
+    let settings = KbSettings::load(state, bot_id);
+    let chunks = split_chunks(text, settings.chunk_chars, settings.chunk_overlap);
+    if chunks.is_empty() {
+        return Err("没有可导入的文本内容".to_string());
+    }
+
+    let llm = resolve_llm_config_by_name(state, bot_id, Some(&settings.embedding_model))?;
+    let vectors = call_embeddings(&llm, &chunks)
+        .await
+        .map_err(|e| format!("计算向量失败: {e}"))?;
+
+    let model = llm.model_name.clone();
+    let source = source.to_string();
+    let title = title.to_string();
+    let added_at = now_secs();
+    let count = chunks.len();
+    let label = format!("{}/{}", bot_id, group_id);
+    with_index(bot_id, group_id, move |path, index| {
+        let chunks = chunks.into_iter().zip(vectors).collect();
+        index.replace_source(&label, model, &source, &title, added_at, chunks);
+        save_index(path, index)?;
+        info!("知识库 {} 已导入 {}（{} 个片段）", label, source, count);
+        Ok(count)
+    })
+    .await
+}
+
+/// 下载文档或压缩包并导入。
+pub(super) async fn ingest_url(
+    state: &SharedState,
+    bot_id: &str,
+    group_id: u64,
+    source: &str,
+    title: &str,
+    url: &str,
+    file_name: Option<&str>,
+) -> Result<usize, String> {
+    let text = download_text_for_index(url, file_name).await?;
+    ingest_text(state, bot_id, group_id, source, title, &text).await
+}
+
+/// 删除某个来源的全部片段，返回删除数量。
+pub(super) async fn remove_source(
+    bot_id: &str,
+    group_id: u64,
+    source: &str,
+) -> Result<usize, String> {
+    let source = source.to_string();
+    with_index(bot_id, group_id, move |path, index| {
+        let removed = index.remove_source(&source);
+        if removed > 0 {
+            save_index(path, index)?;
+        }
+        Ok(removed)
+    })
+    .await
+}
+
+/// 在群知识库（以及 bot 级的公共知识库，group 0）中检索最相关的 `k` 个片段。
+pub(super) async fn search(
+    state: &SharedState,
+    bot_id: &str,
+    group_id: u64,
+    query: &str,
+    k: usize,
+) -> Result<Vec<KbHit>, String> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Err("检索内容为空".to_string());
+    }
+    let k = k.clamp(1, MAX_SEARCH_K);
+    let settings = KbSettings::load(state, bot_id);
+    let llm = resolve_llm_config_by_name(state, bot_id, Some(&settings.embedding_model))?;
+    let query_vector = call_embeddings(&llm, &[query.to_string()])
+        .await
+        .map_err(|e| format!("计算向量失败: {e}"))?
+        .pop()
+        .map(normalize)
+        .unwrap_or_default();
+    let query_vector = Arc::new(query_vector);
+
+    let mut hits = Vec::new();
+    let groups = if group_id == 0 {
+        vec![0]
+    } else {
+        vec![group_id, 0]
+    };
+    for gid in groups {
+        let query_vector = query_vector.clone();
+        let mut found = read_index(bot_id, gid, move |index| index.score(&query_vector)).await?;
+        hits.append(&mut found);
+    }
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(k);
+    Ok(hits)
+}
+
+fn indexable_file(name: &str) -> bool {
+    let ext = Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    TEXT_EXTS.contains(&ext.as_str()) || ARCHIVE_EXTS.contains(&ext.as_str())
+}
+
+/// 群文件上传通知：开启 `auto_ingest_uploads` 时在后台下载并导入该文件。
+pub(super) fn index_group_upload(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    group_id: u64,
+    file: &Value,
+) {
+    let settings = KbSettings::load(state, bot_id);
+    if !settings.auto_ingest_uploads
+        || !(settings.groups.is_empty() || settings.groups.contains(&group_id))
+    {
+        return;
+    }
+    let file_id = file
+        .get("id")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .trim()
+        .to_string();
+    let name = file
+        .get("name")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+    if file_id.is_empty() || !indexable_file(&name) {
+        return;
+    }
+    let busid = file.get("busid").cloned().unwrap_or(Value::Null);
+
+    let state = state.clone();
+    let runtime = runtime.clone();
+    let bot_id = bot_id.to_string();
+    tokio::spawn(async move {
+        let url = runtime
+            .call_api(
+                &bot_id,
+                "get_group_file_url",
+                serde_json::json!({ "group_id": group_id, "file_id": file_id, "busid": busid }),
+            )
+            .await
+            .and_then(|resp| {
+                let data = resp.get("data").unwrap_or(&resp);
+                data.get("url")
+                    .and_then(|v| v.as_str())
+                    .map(super::super::message::decode_basic_html_entities)
+            });
+        let Some(url) = url else {
+            warn!(
+                "[{}] 获取群文件 {} 的下载链接失败，跳过知识库导入",
+                bot_id, name
+            );
+            return;
+        };
+        let source = format!("file:{file_id}");
+        if let Err(e) =
+            ingest_url(&state, &bot_id, group_id, &source, &name, &url, Some(&name)).await
+        {
+            warn!("[{}] 群文件 {} 导入知识库失败: {}", bot_id, name, e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(text: &str, values: &[f32]) -> (String, Vec<f32>) {
+        (text.to_string(), values.to_vec())
+    }
+
+    fn texts(index: &KbIndex) -> Vec<&str> {
+        index.chunks.iter().map(|c| c.text.as_str()).collect()
+    }
+
+    #[test]
+    fn reingesting_a_source_replaces_its_chunks() {
+        let mut index = KbIndex::default();
+        index.replace_source(
+            "t",
+            "m".into(),
+            "file:1",
+            "a.txt",
+            1,
+            vec![chunk("旧一", &[1.0, 0.0]), chunk("旧二", &[0.0, 1.0])],
+        );
+        index.replace_source(
+            "t",
+            "m".into(),
+            "file:2",
+            "b.txt",
+            2,
+            vec![chunk("其他", &[1.0, 1.0])],
+        );
+        index.replace_source(
+            "t",
+            "m".into(),
+            "file:1",
+            "a.txt",
+            3,
+            vec![chunk("新", &[3.0, 4.0])],
+        );
+
+        assert_eq!(texts(&index), vec!["其他", "新"]);
+        // 写入时归一化
+        assert_eq!(index.chunks[1].values, vec![0.6, 0.8]);
+
+        assert_eq!(index.remove_source("file:2"), 1);
+        assert_eq!(index.remove_source("file:2"), 0);
+        assert_eq!(texts(&index), vec!["新"]);
+    }
+
+    #[test]
+    fn changing_embedding_model_clears_index() {
+        let mut index = KbIndex::default();
+        index.replace_source("t", "old".into(), "a", "", 1, vec![chunk("a", &[1.0])]);
+        index.replace_source("t", "new".into(), "b", "", 2, vec![chunk("b", &[1.0, 0.0])]);
+        assert_eq!(index.model, "new");
+        assert_eq!(texts(&index), vec!["b"]);
+    }
+
+    #[test]
+    fn oldest_chunks_dropped_over_limit() {
+        let mut index = KbIndex::default();
+        let many = (0..MAX_CHUNKS_PER_GROUP)
+            .map(|i| chunk(&format!("old{i}"), &[1.0]))
+            .collect();
+        index.replace_source("t", "m".into(), "a", "", 1, many);
+        index.replace_source("t", "m".into(), "b", "", 2, vec![chunk("new", &[1.0])]);
+        assert_eq!(index.chunks.len(), MAX_CHUNKS_PER_GROUP);
+        assert_eq!(index.chunks[0].text, "old1");
+        assert_eq!(index.chunks.last().unwrap().text, "new");
+    }
+
+    #[test]
+    fn score_ranks_by_cosine_and_skips_other_dimensions() {
+        let mut index = KbIndex::default();
+        index.replace_source(
+            "t",
+            "m".into(),
+            "a",
+            "",
+            1,
+            vec![
+                chunk("近", &[1.0, 0.1]),
+                chunk("远", &[0.0, 1.0]),
+                chunk("维度不同", &[1.0, 0.0, 0.0]),
+            ],
+        );
+        let mut hits = index.score(&normalize(vec![1.0, 0.0]));
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        let found: Vec<&str> = hits.iter().map(|h| h.text.as_str()).collect();
+        assert_eq!(found, vec!["近", "远"]);
+        assert!(hits[0].score > 0.99);
+        assert!(hits[1].score.abs() < 1e-6);
+    }
+
+    #[test]
+    fn index_round_trips_through_disk() {
+        let dir = std::env::temp_dir().join(format!("nbot-kb-test-{}", std::process::id()));
+        let path = dir.join("1.json");
+        let mut index = KbIndex::default();
+        index.replace_source(
+            "t",
+            "m".into(),
+            "a",
+            "标题",
+            7,
+            vec![chunk("内容", &[0.5, -0.25])],
+        );
+        save_index(&path, &index).unwrap();
+
+        let loaded = load_index(&path);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.model, "m");
+        assert_eq!(loaded.chunks[0].title, "标题");
+        assert_eq!(loaded.chunks[0].added_at, 7);
+        assert_eq!(loaded.chunks[0].values, index.chunks[0].values);
+        assert!(load_index(&path).chunks.is_empty());
+    }
+
+    #[test]
+    fn vectors_encode_as_little_endian_base64() {
+        let values = vec![1.0f32, -2.5, 0.0];
+        assert_eq!(decode_vector(&encode_vector(&values)), values);
+        assert!(decode_vector("not base64!").is_empty());
+    }
+
+    #[test]
+    fn chunks_split_on_lines_with_overlap() {
+        let text = "aaaa\nbbbb\n\n  cccc  \ndddd";
+        assert_eq!(
+            split_chunks(text, 9, 2),
+            vec!["aaaa\nbbbb", "bb\ncccc", "cc\ndddd"]
+        );
+        // 超长的行先按块长切开
+        assert_eq!(
+            split_chunks(&"x".repeat(10), 4, 0),
+            vec!["xxxx", "xxxx", "xx"]
+        );
+        assert!(split_chunks(" \n \n", 10, 2).is_empty());
+    }
+
+    #[test]
+    fn index_path_sanitizes_bot_id() {
+        assert_eq!(
+            index_path("../bot 1", 42),
+            Path::new(KB_DIR).join("___bot_1").join("42.json")
+        );
+    }
+
+    #[test]
+    fn only_text_documents_and_archives_are_indexed() {
+        assert!(indexable_file("说明.MD"));
+        assert!(indexable_file("logs.tar"));
+        assert!(!indexable_file("photo.png"));
+        assert!(!indexable_file("README"));
+    }
+}
@@ -4,6 +4,8 @@ use once_cell::sync::Lazy;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
 
+use super::super::llm_usage::{today_totals, UsageSubject};
+
 #[derive(Debug, Clone, Copy)]
 pub struct LlmAbuseConfig {
     pub enabled: bool,
@@ -11,6 +13,11 @@ pub struct LlmAbuseConfig {
     pub max_concurrent_per_user: usize,
     pub max_concurrent_per_group: usize,
     pub min_interval_per_user: Duration,
+    /// 每日 token / 费用预算，0 表示不限制
+    pub daily_tokens_per_user: u64,
+    pub daily_cost_per_user: f64,
+    pub daily_tokens_per_group: u64,
+    pub daily_cost_per_group: f64,
 }
 
 impl LlmAbuseConfig {
@@ -50,12 +57,25 @@ impl LlmAbuseConfig {
             .unwrap_or(10)
             .clamp(0, 3600);
 
+        let daily_tokens = |key: &str| limits.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+        let daily_cost = |key: &str| {
+            limits
+                .get(key)
+                .and_then(|v| v.as_f64())
+                .unwrap_or(0.0)
+                .max(0.0)
+        };
+
         Self {
             enabled,
             max_concurrent_global,
             max_concurrent_per_user,
             max_concurrent_per_group,
             min_interval_per_user: Duration::from_secs(min_interval_secs),
+            daily_tokens_per_user: daily_tokens("daily_tokens_per_user"),
+            daily_cost_per_user: daily_cost("daily_cost_per_user"),
+            daily_tokens_per_group: daily_tokens("daily_tokens_per_group"),
+            daily_cost_per_group: daily_cost("daily_cost_per_group"),
         }
     }
 }
@@ -199,3 +219,110 @@ pub fn try_begin_llm_task(
         group_id,
     })
 }
+
+fn over_budget(used: (u64, f64), max_tokens: u64, max_cost: f64) -> bool {
+    (max_tokens > 0 && used.0 >= max_tokens) || (max_cost > 0.0 && used.1 >= max_cost)
+}
+
+/// 检查用户/群当日的 LLM 用量是否已超出预算。
+pub fn check_llm_budget(
+    cfg: &LlmAbuseConfig,
+    bot_id: &str,
+    user_id: Option<u64>,
+    group_id: Option<u64>,
+) -> Result<(), LlmAbuseBlock> {
+    check_budget_with(cfg, user_id, group_id, |subject| {
+        today_totals(bot_id, subject)
+    })
+}
+
+/// `used` 给出对象当日累计的 (tokens, cost)。
+fn check_budget_with(
+    cfg: &LlmAbuseConfig,
+    user_id: Option<u64>,
+    group_id: Option<u64>,
+    used: impl Fn(UsageSubject) -> (u64, f64),
+) -> Result<(), LlmAbuseBlock> {
+    if !cfg.enabled {
+        return Ok(());
+    }
+
+    if let Some(uid) = user_id.filter(|u| *u != 0) {
+        let used = used(UsageSubject::User(uid));
+        if over_budget(used, cfg.daily_tokens_per_user, cfg.daily_cost_per_user) {
+            return Err(LlmAbuseBlock {
+                message: "你今日的 LLM 额度已用完，请明天再试".to_string(),
+            });
+        }
+    }
+
+    if let Some(gid) = group_id.filter(|g| *g != 0) {
+        let used = used(UsageSubject::Group(gid));
+        if over_budget(used, cfg.daily_tokens_per_group, cfg.daily_cost_per_group) {
+            return Err(LlmAbuseBlock {
+                message: "本群今日的 LLM 额度已用完，请明天再试".to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(tokens_per_user: u64, cost_per_group: f64) -> LlmAbuseConfig {
+        LlmAbuseConfig {
+            enabled: true,
+            max_concurrent_global: 2,
+            max_concurrent_per_user: 1,
+            max_concurrent_per_group: 1,
+            min_interval_per_user: Duration::from_secs(0),
+            daily_tokens_per_user: tokens_per_user,
+            daily_cost_per_user: 0.0,
+            daily_tokens_per_group: 0,
+            daily_cost_per_group: cost_per_group,
+        }
+    }
+
+    fn usage(user: (u64, f64), group: (u64, f64)) -> impl Fn(UsageSubject) -> (u64, f64) {
+        move |subject| match subject {
+            UsageSubject::User(_) => user,
+            UsageSubject::Group(_) => group,
+        }
+    }
+
+    #[test]
+    fn user_token_budget_blocks_once_reached() {
+        let cfg = config(1000, 0.0);
+        assert!(check_budget_with(&cfg, Some(1), None, usage((999, 0.0), (0, 0.0))).is_ok());
+        let block =
+            check_budget_with(&cfg, Some(1), None, usage((1000, 0.0), (0, 0.0))).unwrap_err();
+        assert!(block.message.contains("你今日"));
+    }
+
+    #[test]
+    fn group_cost_budget_blocks_once_reached() {
+        let cfg = config(0, 0.5);
+        assert!(check_budget_with(&cfg, Some(1), Some(2), usage((0, 0.0), (0, 0.49))).is_ok());
+        let block =
+            check_budget_with(&cfg, Some(1), Some(2), usage((0, 0.0), (0, 0.5))).unwrap_err();
+        assert!(block.message.contains("本群"));
+        // 私聊不受群预算限制
+        assert!(check_budget_with(&cfg, Some(1), None, usage((0, 0.0), (0, 9.0))).is_ok());
+    }
+
+    #[test]
+    fn zero_budget_or_disabled_limits_do_not_block() {
+        let heavy = usage((u64::MAX, 1e9), (u64::MAX, 1e9));
+        assert!(check_budget_with(&config(0, 0.0), Some(1), Some(2), &heavy).is_ok());
+
+        let mut cfg = config(1, 0.1);
+        cfg.enabled = false;
+        assert!(check_budget_with(&cfg, Some(1), Some(2), &heavy).is_ok());
+
+        // user_id / group_id 为 0 视为没有对象
+        assert!(check_budget_with(&config(1, 0.1), Some(0), Some(0), &heavy).is_ok());
+    }
+}
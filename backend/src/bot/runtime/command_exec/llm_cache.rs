@@ -0,0 +1,402 @@
+//! 可选的 LLM 回复缓存：同一模型、同样的提示词与同一份内容/媒体（按 sha256）直接复用上次的回复，
+//! 省去重复的转码与调用。配置位于 llm 模块的 `cache`：`enabled`（默认关闭）、`ttl_secs`、
+//! `max_entries`、`max_mb`。缓存保存在 `data/cache/llm_responses.db`。
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::models::SharedState;
+
+use super::super::llm_usage::{record_llm_cache_event, UsageOrigin};
+use super::super::state_db::{now_secs, with_db, SharedDb};
+use super::llm_forward::multimodal::common::LlmConfig;
+
+const CACHE_DB: &str = "data/cache/llm_responses.db";
+const DEFAULT_TTL_SECS: u64 = 24 * 3600;
+const DEFAULT_MAX_ENTRIES: u64 = 2000;
+const DEFAULT_MAX_MB: u64 = 100;
+
+static DB: SharedDb = SharedDb::new(CACHE_DB, "LLM 缓存数据库", init_db);
+
+#[derive(Debug, Clone)]
+struct CacheSettings {
+    ttl_secs: u64,
+    max_entries: u64,
+    max_bytes: u64,
+}
+
+impl CacheSettings {
+    /// 未开启时返回 None
+    fn load(state: &SharedState, bot_id: &str) -> Option<Self> {
+        let module = crate::module::get_effective_module(state, bot_id, "llm")?;
+        let cfg = module.config.get("cache")?;
+        if cfg.get("enabled").and_then(|v| v.as_bool()) != Some(true) {
+            return None;
+        }
+        let get =
+            |key: &str, default: u64| cfg.get(key).and_then(|v| v.as_u64()).unwrap_or(default);
+        Some(Self {
+            ttl_secs: get("ttl_secs", DEFAULT_TTL_SECS).clamp(60, 30 * 24 * 3600),
+            max_entries: get("max_entries", DEFAULT_MAX_ENTRIES).clamp(10, 1_000_000),
+            max_bytes: get("max_mb", DEFAULT_MAX_MB).clamp(1, 10_000) * 1024 * 1024,
+        })
+    }
+}
+
+fn init_db(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "PRAGMA journal_mode = WAL;
+         PRAGMA synchronous = NORMAL;
+         CREATE TABLE IF NOT EXISTS llm_cache (
+             key TEXT PRIMARY KEY,
+             model TEXT NOT NULL,
+             content TEXT NOT NULL,
+             bytes INTEGER NOT NULL,
+             created_at INTEGER NOT NULL,
+             last_hit INTEGER NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS idx_llm_cache_last_hit ON llm_cache(last_hit);",
+    )
+    .map_err(|e| format!("初始化 LLM 缓存数据库失败: {e}"))
+}
+
+/// 缓存键：依次加入的各部分（先写入长度，避免拼接后产生歧义）的 sha256。
+#[derive(Clone)]
+struct CacheKey(Sha256);
+
+impl CacheKey {
+    fn new(parts: &[&str]) -> Self {
+        let mut key = Self(Sha256::new());
+        for part in parts {
+            key.add(part.as_bytes());
+        }
+        key
+    }
+
+    fn add(&mut self, part: &[u8]) {
+        self.0.update((part.len() as u64).to_le_bytes());
+        self.0.update(part);
+    }
+
+    fn hex(&self) -> String {
+        format!("{:x}", self.0.clone().finalize())
+    }
+}
+
+/// 对象的键按字典序重排：同样的内容不因字段顺序不同而得到不同的键
+fn sorted_keys(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k.clone(), sorted_keys(v)))
+                    .collect(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.iter().map(sorted_keys).collect()),
+        other => other.clone(),
+    }
+}
+
+fn file_digest(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hasher.finalize().to_vec())
+}
+
+fn lookup(
+    conn: &Connection,
+    key: &str,
+    min_created: i64,
+) -> Result<Option<String>, rusqlite::Error> {
+    let content: Option<String> = conn
+        .query_row(
+            "SELECT content FROM llm_cache WHERE key = ?1 AND created_at >= ?2",
+            params![key, min_created],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if content.is_some() {
+        conn.execute(
+            "UPDATE llm_cache SET last_hit = ?2 WHERE key = ?1",
+            params![key, now_secs() as i64],
+        )?;
+    }
+    Ok(content)
+}
+
+/// 写入一条回复，并按 TTL 与容量上限淘汰旧条目；返回淘汰数量。
+fn store(
+    conn: &mut Connection,
+    settings: &CacheSettings,
+    key: &str,
+    model: &str,
+    content: &str,
+) -> Result<usize, rusqlite::Error> {
+    let now = now_secs() as i64;
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT OR REPLACE INTO llm_cache (key, model, content, bytes, created_at, last_hit)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+        params![key, model, content, content.len() as i64, now],
+    )?;
+    tx.execute(
+        "DELETE FROM llm_cache WHERE created_at < ?1",
+        params![now - settings.ttl_secs as i64],
+    )?;
+    let (count, bytes): (i64, i64) = tx.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(bytes), 0) FROM llm_cache",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let mut evicted = 0usize;
+    if count as u64 > settings.max_entries || bytes as u64 > settings.max_bytes {
+        // 按最近使用时间从旧到新淘汰，直到回到上限以内
+        let mut stmt = tx.prepare("SELECT key, bytes FROM llm_cache ORDER BY last_hit")?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        drop(stmt);
+        let (mut count, mut bytes) = (count as u64, bytes as u64);
+        for (old_key, old_bytes) in rows {
+            if count <= settings.max_entries && bytes <= settings.max_bytes {
+                break;
+            }
+            tx.execute("DELETE FROM llm_cache WHERE key = ?1", params![old_key])?;
+            count -= 1;
+            bytes = bytes.saturating_sub(old_bytes as u64);
+            evicted += 1;
+        }
+    }
+    tx.commit()?;
+    Ok(evicted)
+}
+
+/// 一次可缓存的 LLM 调用。键由任务类型、模型映射与依次加入的各部分内容组成；
+/// 未开启缓存时所有操作都是空操作。
+pub(super) struct LlmResponseCache {
+    settings: Option<CacheSettings>,
+    key: CacheKey,
+    task: String,
+    origin: UsageOrigin,
+    provider_id: String,
+    model: String,
+}
+
+impl LlmResponseCache {
+    pub(super) fn new(state: &SharedState, bot_id: &str, llm: &LlmConfig, task: &str) -> Self {
+        let settings = CacheSettings::load(state, bot_id);
+        let key = CacheKey::new(&[task, llm.route.mapping.as_str(), llm.model_name.as_str()]);
+        Self {
+            settings,
+            key,
+            task: task.to_string(),
+            origin: llm.origin.clone(),
+            provider_id: llm
+                .targets
+                .first()
+                .map(|t| t.provider_id.clone())
+                .unwrap_or_default(),
+            model: llm.model_name.clone(),
+        }
+    }
+
+    pub(super) fn add_text(&mut self, text: &str) {
+        if self.settings.is_some() {
+            self.key.add(text.as_bytes());
+        }
+    }
+
+    pub(super) fn add_json(&mut self, value: &Value) {
+        if self.settings.is_some() {
+            self.key.add(sorted_keys(value).to_string().as_bytes());
+        }
+    }
+
+    /// 加入文件内容的 sha256（读取失败时放弃缓存）。
+    pub(super) async fn add_file(&mut self, path: &Path) {
+        if self.settings.is_none() {
+            return;
+        }
+        let path = path.to_path_buf();
+        let digest = tokio::task::spawn_blocking(move || file_digest(&path)).await;
+        match digest {
+            Ok(Ok(digest)) => self.key.add(&digest),
+            Ok(Err(e)) => {
+                warn!("LLM 缓存：计算文件哈希失败，本次不使用缓存: {}", e);
+                self.settings = None;
+            }
+            Err(e) => {
+                warn!("LLM 缓存：计算文件哈希失败，本次不使用缓存: {}", e);
+                self.settings = None;
+            }
+        }
+    }
+
+    /// 查询缓存；命中与未命中都会写日志并计入用量统计。
+    pub(super) async fn get(&self) -> Option<String> {
+        let settings = self.settings.as_ref()?;
+        let key = self.key.hex();
+        let lookup_key = key.clone();
+        let min_created = now_secs().saturating_sub(settings.ttl_secs) as i64;
+        let found = with_db(&DB, move |conn| lookup(conn, &lookup_key, min_created))
+            .await
+            .unwrap_or_else(|e| {
+                warn!("{}", e);
+                None
+            });
+
+        let hit = found.is_some();
+        info!(
+            "LLM 缓存{}：{} {} ({})",
+            if hit { "命中" } else { "未命中" },
+            self.task,
+            self.model,
+            &key[..12]
+        );
+        record_llm_cache_event(&self.origin, &self.provider_id, &self.model, hit);
+        found
+    }
+
+    /// 保存回复，并按 TTL 与容量上限淘汰旧条目。
+    pub(super) async fn put(&self, content: &str) {
+        let Some(settings) = self.settings.clone() else {
+            return;
+        };
+        if content.trim().is_empty() {
+            return;
+        }
+        let key = self.key.hex();
+        let model = self.model.clone();
+        let content = content.to_string();
+        let result = with_db(&DB, move |conn| {
+            store(conn, &settings, &key, &model, &content)
+        })
+        .await;
+        match result {
+            Ok(evicted) if evicted > 0 => info!("LLM 缓存已淘汰 {} 条旧回复", evicted),
+            Ok(_) => {}
+            Err(e) => warn!("写入 LLM 缓存失败: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn key_of(parts: &[&str]) -> String {
+        CacheKey::new(parts).hex()
+    }
+
+    fn settings(max_entries: u64, max_bytes: u64) -> CacheSettings {
+        CacheSettings {
+            ttl_secs: 3600,
+            max_entries,
+            max_bytes,
+        }
+    }
+
+    fn open() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn key_is_stable_and_depends_on_every_part() {
+        let key = key_of(&["text", "default", "gpt-x", "提示词"]);
+        assert_eq!(key, key_of(&["text", "default", "gpt-x", "提示词"]));
+        assert_eq!(key.len(), 64);
+        assert_ne!(key, key_of(&["image", "default", "gpt-x", "提示词"]));
+        assert_ne!(key, key_of(&["text", "default", "gpt-y", "提示词"]));
+        assert_ne!(key, key_of(&["text", "default", "gpt-x", "提示词 "]));
+    }
+
+    #[test]
+    fn key_parts_do_not_run_together() {
+        assert_ne!(key_of(&["ab", "c"]), key_of(&["a", "bc"]));
+        assert_ne!(key_of(&["a", ""]), key_of(&["a"]));
+    }
+
+    #[test]
+    fn json_parts_ignore_field_order() {
+        let a = json!({ "model": "m", "messages": [{ "role": "user", "content": "hi" }] });
+        let b: Value =
+            serde_json::from_str(r#"{"messages":[{"content":"hi","role":"user"}],"model":"m"}"#)
+                .unwrap();
+        assert_eq!(sorted_keys(&a).to_string(), sorted_keys(&b).to_string());
+
+        // 数组顺序仍然有意义
+        let c = json!({ "messages": [1, 2] });
+        let d = json!({ "messages": [2, 1] });
+        assert_ne!(sorted_keys(&c).to_string(), sorted_keys(&d).to_string());
+    }
+
+    #[test]
+    fn file_digest_depends_on_content_not_path() {
+        let dir = std::env::temp_dir().join(format!("nbot-cache-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (a, b, c) = (dir.join("a.bin"), dir.join("b.bin"), dir.join("c.bin"));
+        std::fs::write(&a, b"same").unwrap();
+        std::fs::write(&b, b"same").unwrap();
+        std::fs::write(&c, b"other").unwrap();
+        let (da, db, dc) = (
+            file_digest(&a).unwrap(),
+            file_digest(&b).unwrap(),
+            file_digest(&c).unwrap(),
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(da, db);
+        assert_ne!(da, dc);
+        assert!(file_digest(&a).is_err());
+    }
+
+    #[test]
+    fn stored_reply_found_until_expired() {
+        let mut conn = open();
+        store(&mut conn, &settings(10, 1 << 20), "k", "m", "回复").unwrap();
+        let now = now_secs() as i64;
+        assert_eq!(
+            lookup(&conn, "k", now - 60).unwrap().as_deref(),
+            Some("回复")
+        );
+        assert_eq!(lookup(&conn, "k", now + 60).unwrap(), None);
+        assert_eq!(lookup(&conn, "other", 0).unwrap(), None);
+    }
+
+    #[test]
+    fn least_recently_hit_entries_evicted_over_limits() {
+        let mut conn = open();
+        let limits = settings(2, 1 << 20);
+        store(&mut conn, &limits, "a", "m", "A").unwrap();
+        store(&mut conn, &limits, "b", "m", "B").unwrap();
+        conn.execute(
+            "UPDATE llm_cache SET last_hit = last_hit - 10 WHERE key = 'b'",
+            [],
+        )
+        .unwrap();
+        assert_eq!(store(&mut conn, &limits, "c", "m", "C").unwrap(), 1);
+        assert!(lookup(&conn, "a", 0).unwrap().is_some());
+        assert!(lookup(&conn, "b", 0).unwrap().is_none());
+
+        let mut conn = open();
+        let limits = settings(100, 10);
+        store(&mut conn, &limits, "a", "m", "123456").unwrap();
+        conn.execute("UPDATE llm_cache SET last_hit = last_hit - 10", [])
+            .unwrap();
+        assert_eq!(store(&mut conn, &limits, "b", "m", "123456").unwrap(), 1);
+        assert!(lookup(&conn, "a", 0).unwrap().is_none());
+        assert!(lookup(&conn, "b", 0).unwrap().is_some());
+    }
+}
@@ -1,17 +1,19 @@
 use crate::models::SharedState;
-use crate::plugin::runtime::MediaBundleItem;
+use crate::plugin::runtime::{ForwardTts, MediaBundleItem};
 use serde_json::json;
 use std::sync::Arc;
 
 use super::super::connection::BotRuntime;
+use super::llm_cache::LlmResponseCache;
 
 mod download;
 pub(super) mod multimodal;
 mod archive;
+mod extract;
 mod output_extract;
 mod redact;
 
-use archive::download_archive_text;
+use archive::{download_archive_text, is_supported_archive, ArchiveLimits};
 use download::{download_document_text, DocumentMeta};
 use multimodal::common::{
     call_chat_completions, log_llm_error, log_llm_len, reply_err, resolve_llm_config_by_name,
@@ -55,6 +57,7 @@ pub(super) enum LlmForwardSource<'a> {
         max_extract_bytes: u64,
         max_file_bytes: u64,
         max_files: u32,
+        bundle_files: u32,
         keywords: &'a [String],
     },
 }
@@ -67,6 +70,7 @@ pub(super) struct LlmForwardInput<'a> {
     pub(super) prompt: &'a str,
     pub(super) title: &'a str,
     pub(super) source: LlmForwardSource<'a>,
+    pub(super) tts: Option<&'a ForwardTts>,
 }
 
 pub(super) struct LlmForwardImageFromUrlInput<'a> {
@@ -84,6 +88,7 @@ pub(super) struct LlmForwardImageFromUrlInput<'a> {
     pub(super) max_height: u32,
     pub(super) jpeg_quality: u8,
     pub(super) max_output_bytes: u64,
+    pub(super) tts: Option<&'a ForwardTts>,
 }
 
 pub(super) struct LlmForwardVideoFromUrlInput<'a> {
@@ -103,10 +108,13 @@ pub(super) struct LlmForwardVideoFromUrlInput<'a> {
     pub(super) frame_max_height: u32,
     pub(super) frame_jpeg_quality: u8,
     pub(super) frame_max_output_bytes: u64,
+    /// 抽帧方式：even / scene
+    pub(super) frame_selection: &'a str,
     pub(super) transcribe_audio: bool,
     pub(super) transcription_model: Option<&'a str>,
     pub(super) max_audio_seconds: u32,
     pub(super) require_transcript: bool,
+    pub(super) tts: Option<&'a ForwardTts>,
 }
 
 pub(super) struct LlmForwardAudioFromUrlInput<'a> {
@@ -123,6 +131,7 @@ pub(super) struct LlmForwardAudioFromUrlInput<'a> {
     pub(super) max_bytes: u64,
     pub(super) max_audio_seconds: u32,
     pub(super) require_transcript: bool,
+    pub(super) tts: Option<&'a ForwardTts>,
 }
 
 pub(super) struct LlmForwardMediaBundleInput<'a> {
@@ -144,6 +153,27 @@ pub(super) struct LlmForwardMediaBundleInput<'a> {
     // Video / Audio options
     pub(super) video_max_bytes: u64,
     pub(super) audio_max_bytes: u64,
+    pub(super) tts: Option<&'a ForwardTts>,
+}
+
+/// 下载文档或压缩包并提取文本（知识库导入用），按文件名判断是否为压缩包。
+pub(super) async fn download_text_for_index(
+    url: &str,
+    file_name: Option<&str>,
+) -> Result<String, String> {
+    let (_guard, text, _meta) = if is_supported_archive(url, file_name) {
+        let limits = ArchiveLimits {
+            max_download_bytes: 50_000_000,
+            max_extract_bytes: 200_000_000,
+            max_file_bytes: 20_000_000,
+            max_files: 50,
+            bundle_files: 5,
+        };
+        download_archive_text(url, file_name, 60_000, limits, &[]).await?
+    } else {
+        download_document_text(url, file_name, 60_000, 20_000_000, 200_000).await?
+    };
+    Ok(text)
 }
 
 pub(super) async fn process_llm_forward(
@@ -158,6 +188,7 @@ pub(super) async fn process_llm_forward(
     let system_prompt = input.system_prompt;
     let prompt = input.prompt;
     let title = input.title;
+    let tts = input.tts;
 
     let (temp_file_guard, content, document_meta) = match input.source {
         LlmForwardSource::Content(content) => {
@@ -200,15 +231,19 @@ pub(super) async fn process_llm_forward(
             max_extract_bytes,
             max_file_bytes,
             max_files,
+            bundle_files,
             keywords,
         } => match download_archive_text(
             url,
             file_name,
             timeout_ms,
-            max_download_bytes,
-            max_extract_bytes,
-            max_file_bytes,
-            max_files,
+            ArchiveLimits {
+                max_download_bytes,
+                max_extract_bytes,
+                max_file_bytes,
+                max_files,
+                bundle_files,
+            },
             keywords,
         )
         .await
@@ -232,6 +267,27 @@ pub(super) async fn process_llm_forward(
         }
     };
 
+    let mut cache = LlmResponseCache::new(state, bot_id, &llm, "text");
+    cache.add_text(system_prompt);
+    cache.add_text(prompt);
+    cache.add_text(&content);
+    if let Some(cached) = cache.get().await {
+        send_llm_markdown_as_forward_image(
+            state,
+            runtime,
+            bot_id,
+            SendForwardImageInput {
+                user_id,
+                group_id,
+                title,
+                tts,
+                markdown: &cached,
+            },
+        )
+        .await;
+        return;
+    }
+
     let bot_name = state
         .bots
         .get(bot_id)
@@ -291,14 +347,7 @@ pub(super) async fn process_llm_forward(
         "max_tokens": 4096
     });
 
-    let reply_content = match call_chat_completions(
-        &llm.base_url,
-        &llm.api_key,
-        &request_body,
-        llm.max_request_bytes,
-    )
-    .await
-    {
+    let reply_content = match call_chat_completions(&llm, &request_body).await {
         Ok(t) => t,
         Err(e) => {
             log_llm_error("文本分析", &e.to_string());
@@ -315,6 +364,7 @@ pub(super) async fn process_llm_forward(
     };
 
     log_llm_len("文本分析", reply_content.len());
+    cache.put(&reply_content).await;
     send_llm_markdown_as_forward_image(
         state,
         runtime,
@@ -323,6 +373,7 @@ pub(super) async fn process_llm_forward(
             user_id,
             group_id,
             title,
+            tts,
             markdown: &reply_content,
         },
     )
@@ -1,26 +1,45 @@
+//! 压缩包分析：支持 zip / tar / tar.gz / gz 及其互相嵌套（如 zip 里的 zip、logs/*.log.gz），
+//! 按相关度挑选多个日志/文本文件合并输出，各文件分摊字符预算。
+//! 解压有深度、条目数、总字节数与压缩比上限；7z / rar 只做识别并明确提示。
+
 use super::download::{DocumentMeta, TempFileGuard};
+use super::extract::{extract_document_text, unsupported_archive_format, DOCUMENT_EXTENSIONS};
 use super::multimodal::common::download_binary_to_temp;
+use super::super::media_cache::evict_url;
 use flate2::read::GzDecoder;
-use std::io::Read;
+use std::io::{Cursor, Read};
 use std::path::Path;
+use std::rc::Rc;
 use tar::Archive as TarArchive;
 use zip::ZipArchive;
 
+/// 嵌套压缩包的最大层数（顶层为第 0 层）
+const MAX_NESTED_DEPTH: usize = 3;
+/// 最多展开的嵌套压缩包数量
+const MAX_NESTED_ARCHIVES: usize = 32;
+/// 所有层级合计最多遍历的条目数
+const MAX_ENTRIES: usize = 20_000;
+/// 输出文本的总字符上限，由各文件分摊
+const MAX_TOTAL_CHARS: usize = 200_000;
+/// 解压后与压缩前的大小之比上限，超过的条目按压缩炸弹跳过
+const MAX_COMPRESSION_RATIO: u64 = 200;
+/// 小于此大小的条目不检查压缩比（小文件的压缩比没有意义）
+const RATIO_CHECK_MIN_BYTES: u64 = 1_000_000;
+/// 合并输出时，次要文件的文件名得分至少为此值（.log、crash 或命中关键词）
+const MIN_SECONDARY_SCORE: i64 = 40;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ArchiveKind {
     Zip,
     Tar,
     TarGz,
     Gz,
+    SevenZip,
+    Rar,
 }
 
-fn guess_kind(url: &str, file_name: Option<&str>) -> Option<ArchiveKind> {
-    let name = file_name
-        .or_else(|| url.split('/').last())
-        .unwrap_or("")
-        .trim()
-        .to_lowercase();
-
+fn kind_from_name(name: &str) -> Option<ArchiveKind> {
+    let name = name.trim().to_lowercase();
     if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
         return Some(ArchiveKind::TarGz);
     }
@@ -33,9 +52,70 @@ fn guess_kind(url: &str, file_name: Option<&str>) -> Option<ArchiveKind> {
     if name.ends_with(".gz") {
         return Some(ArchiveKind::Gz);
     }
+    if name.ends_with(".7z") {
+        return Some(ArchiveKind::SevenZip);
+    }
+    if name.ends_with(".rar") {
+        return Some(ArchiveKind::Rar);
+    }
     None
 }
 
+fn guess_kind(url: &str, file_name: Option<&str>) -> Option<ArchiveKind> {
+    let name = file_name
+        .or_else(|| url.split('/').next_back())
+        .unwrap_or("");
+    kind_from_name(name)
+}
+
+/// 按文件头识别格式；gzip 需要看解压后的内容才能区分 tar.gz
+fn sniff_kind(bytes: &[u8]) -> Option<ArchiveKind> {
+    if bytes.starts_with(b"PK\x03\x04") || bytes.starts_with(b"PK\x05\x06") {
+        return Some(ArchiveKind::Zip);
+    }
+    if bytes.starts_with(b"\x1f\x8b") {
+        return Some(ArchiveKind::Gz);
+    }
+    if bytes.get(257..262) == Some(b"ustar") {
+        return Some(ArchiveKind::Tar);
+    }
+    match unsupported_archive_format(bytes) {
+        Some("7z") => Some(ArchiveKind::SevenZip),
+        Some(_) => Some(ArchiveKind::Rar),
+        None => None,
+    }
+}
+
+/// 文件名（或 URL）是否是压缩包（含只做识别的 7z / rar）
+pub(super) fn is_supported_archive(url: &str, file_name: Option<&str>) -> bool {
+    guess_kind(url, file_name).is_some()
+}
+
+fn unsupported_message(kind: ArchiveKind) -> Option<String> {
+    let format = match kind {
+        ArchiveKind::SevenZip => "7z",
+        ArchiveKind::Rar => "rar",
+        _ => return None,
+    };
+    Some(format!(
+        "暂不支持 {format} 压缩包，请改用 zip 或 tar.gz 重新打包，或解压后直接上传日志文件"
+    ))
+}
+
+/// 压缩包解压与选取文件的上限
+#[derive(Debug, Clone, Copy)]
+pub(super) struct ArchiveLimits {
+    pub(super) max_download_bytes: u64,
+    /// 所有层级合计最多解压的字节数
+    pub(super) max_extract_bytes: u64,
+    /// 单个文件（含嵌套压缩包）最多读取的字节数
+    pub(super) max_file_bytes: u64,
+    /// 最多收集的候选文件数
+    pub(super) max_files: u32,
+    /// 合并输出的文件数（按相关度取前 N 个）
+    pub(super) bundle_files: u32,
+}
+
 fn normalize_keywords(keywords: &[String]) -> Vec<String> {
     keywords
         .iter()
@@ -44,311 +124,586 @@ fn normalize_keywords(keywords: &[String]) -> Vec<String> {
         .collect()
 }
 
-fn choose_best_file<'a>(candidates: &'a [ExtractCandidate], keywords: &[String]) -> Option<&'a ExtractCandidate> {
-    if candidates.is_empty() {
-        return None;
-    }
-
-    let kw = normalize_keywords(keywords);
-
-    let mut best: Option<(&ExtractCandidate, i64)> = None;
-    for c in candidates {
-        let mut score: i64 = 0;
-        let name = c.name.to_lowercase();
-
-        if name.ends_with("latest.log") {
-            score += 200;
-        }
-        if name.contains("crash") || name.contains("hs_err") {
-            score += 150;
-        }
-        if name.ends_with(".log") {
-            score += 40;
-        }
-        if name.ends_with(".txt") {
-            score += 20;
-        }
-
-        for k in &kw {
-            if name == *k {
-                score += 500;
-            } else if name.contains(k) {
-                score += 80;
-            }
-        }
+/// 仅按路径计算的相关度
+fn name_score(path: &str, keywords: &[String]) -> i64 {
+    let name = path.to_lowercase();
+    let base = name.rsplit('/').next().unwrap_or(&name);
+    let mut score: i64 = 0;
 
-        // Prefer larger files if scores tie (more context), but keep stable.
-        score += (c.size_bytes.min(5_000_000) as i64) / 50_000;
+    if base == "latest.log" {
+        score += 200;
+    }
+    if name.contains("crash") || name.contains("hs_err") {
+        score += 150;
+    }
+    if base == "debug.log" {
+        score += 60;
+    }
+    if name.ends_with(".log") {
+        score += 40;
+    }
+    if name.ends_with(".txt") {
+        score += 20;
+    }
 
-        match best {
-            None => best = Some((c, score)),
-            Some((_, s)) if score > s => best = Some((c, score)),
-            _ => {}
+    for k in keywords {
+        if name == *k || base == k {
+            score += 500;
+        } else if name.contains(k) {
+            score += 80;
         }
     }
-
-    best.map(|(c, _)| c)
+    score
 }
 
-fn truncate_to_chars(s: String, max_chars: usize) -> (String, bool) {
-    if s.chars().count() <= max_chars {
-        return (s, false);
-    }
-    let out: String = s.chars().take(max_chars).collect();
-    (out, true)
+#[derive(Debug, Clone, Copy)]
+enum Location {
+    /// zip 条目（按序号）
+    ZipEntry(usize),
+    /// 容器数据中的一段（tar 条目或解压后的 gz）
+    Range(usize, usize),
 }
 
 #[derive(Debug, Clone)]
 struct ExtractCandidate {
-    name: String,
+    container: usize,
+    location: Location,
+    path: String,
     size_bytes: u64,
+    score: i64,
+}
+
+/// 已载入内存的一层压缩包（tar.gz / gz 保存解压后的数据）
+struct Container {
+    kind: ArchiveKind,
+    data: Rc<Vec<u8>>,
 }
 
 fn is_text_candidate_name(name: &str) -> bool {
     let lower = name.to_lowercase();
-    lower.ends_with(".log") || lower.ends_with(".txt")
+    if lower.ends_with(".log") || lower.ends_with(".txt") {
+        return true;
+    }
+    Path::new(&lower)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|ext| DOCUMENT_EXTENSIONS.contains(&ext))
 }
 
-fn read_limited_to_vec<R: Read>(mut r: R, max_bytes: u64) -> Result<(Vec<u8>, bool), String> {
+fn read_limited_to_vec<R: Read>(r: R, max_bytes: u64) -> Result<(Vec<u8>, bool), String> {
     let mut buf: Vec<u8> = Vec::new();
-    let mut truncated = false;
-    let max = max_bytes as usize;
-    let mut chunk = [0u8; 8192];
-    loop {
-        let n = r.read(&mut chunk).map_err(|e| format!("read failed: {e}"))?;
-        if n == 0 {
-            break;
+    let mut limited = r.take(max_bytes.saturating_add(1));
+    limited
+        .read_to_end(&mut buf)
+        .map_err(|e| format!("read failed: {e}"))?;
+    let truncated = buf.len() as u64 > max_bytes;
+    buf.truncate(max_bytes as usize);
+    Ok((buf, truncated))
+}
+
+/// 解压后大小相对压缩前是否异常
+fn exceeds_ratio(compressed: u64, size: u64) -> bool {
+    size > RATIO_CHECK_MIN_BYTES && size / compressed.max(1) > MAX_COMPRESSION_RATIO
+}
+
+fn zip_error(e: zip::result::ZipError) -> String {
+    let msg = e.to_string().to_lowercase();
+    if msg.contains("eocd") {
+        format!("zip 文件不完整或损坏（EOCD 缺失）：{e}")
+    } else {
+        format!("parse zip failed: {e}")
+    }
+}
+
+struct Walker {
+    limits: ArchiveLimits,
+    keywords: Vec<String>,
+    containers: Vec<Container>,
+    candidates: Vec<ExtractCandidate>,
+    /// 已解压的字节数（嵌套压缩包与选中的文件）
+    extracted: u64,
+    entries: usize,
+    /// 预算或数量上限导致内容不完整
+    truncated: bool,
+    /// 未能读取的条目及原因
+    skipped: Vec<String>,
+}
+
+impl Walker {
+    fn remaining(&self) -> u64 {
+        self.limits.max_extract_bytes.saturating_sub(self.extracted)
+    }
+
+    fn file_limit(&self) -> u64 {
+        self.limits.max_file_bytes.min(self.remaining())
+    }
+
+    /// 按剩余预算读取一个条目并计入解压量；超过上限时返回 None 并记录原因
+    fn read_entry(
+        &mut self,
+        path: &str,
+        read: impl FnOnce(u64) -> Result<(Vec<u8>, bool), String>,
+    ) -> Result<Option<Vec<u8>>, String> {
+        let limit = self.file_limit();
+        if limit == 0 {
+            self.truncated = true;
+            self.skipped.push(format!("{path}（超出解压总量上限）"));
+            return Ok(None);
         }
-        if buf.len() + n > max {
-            let remain = max.saturating_sub(buf.len());
-            buf.extend_from_slice(&chunk[..remain]);
-            truncated = true;
-            break;
+        let (bytes, truncated) = read(limit)?;
+        self.extracted += bytes.len() as u64;
+        if truncated {
+            self.truncated = true;
+            let reason = if limit < self.limits.max_file_bytes {
+                "超出解压总量上限"
+            } else {
+                "超过单文件大小上限"
+            };
+            self.skipped.push(format!("{path}（{reason}）"));
+            return Ok(None);
         }
-        buf.extend_from_slice(&chunk[..n]);
+        Ok(Some(bytes))
     }
-    Ok((buf, truncated))
-}
 
-fn extract_best_text_from_zip(
-    path: &Path,
-    max_extract_bytes: u64,
-    max_file_bytes: u64,
-    max_files: u32,
-    keywords: &[String],
-) -> Result<(String, Option<String>, u64, bool), String> {
-    let file = std::fs::File::open(path).map_err(|e| format!("open zip failed: {e}"))?;
-    let mut archive = ZipArchive::new(file).map_err(|e| {
-        let msg = e.to_string().to_lowercase();
-        if msg.contains("eocd") {
-            format!("zip 文件不完整或损坏（EOCD 缺失）：{e}")
-        } else {
-            format!("parse zip failed: {e}")
+    /// 载入一层压缩包并遍历其中的条目
+    fn open(
+        &mut self,
+        kind: ArchiveKind,
+        bytes: Vec<u8>,
+        path: &str,
+        depth: usize,
+    ) -> Result<(), String> {
+        let (kind, data) = match kind {
+            ArchiveKind::Zip | ArchiveKind::Tar => (kind, bytes),
+            ArchiveKind::TarGz | ArchiveKind::Gz => {
+                // gz 不记录原始大小，按压缩比上限限制读取量
+                let ratio_limit = (bytes.len() as u64)
+                    .saturating_mul(MAX_COMPRESSION_RATIO)
+                    .max(RATIO_CHECK_MIN_BYTES);
+                let limit = self.remaining().min(ratio_limit);
+                let (data, truncated) = read_limited_to_vec(GzDecoder::new(&bytes[..]), limit)?;
+                self.extracted += data.len() as u64;
+                if truncated {
+                    self.truncated = true;
+                    if limit == ratio_limit {
+                        return Err("压缩比异常，疑似压缩炸弹".to_string());
+                    }
+                }
+                // .gz 可能其实是 tar.gz
+                let kind = if data.get(257..262) == Some(b"ustar") {
+                    ArchiveKind::Tar
+                } else if kind == ArchiveKind::TarGz {
+                    return Err(format!("{path} 不是有效的 tar.gz 文件"));
+                } else {
+                    ArchiveKind::Gz
+                };
+                (kind, data)
+            }
+            ArchiveKind::SevenZip | ArchiveKind::Rar => {
+                return Err(unsupported_message(kind).unwrap_or_default());
+            }
+        };
+
+        // 顶层压缩包的条目不带压缩包自身的文件名
+        let prefix = if depth == 0 { "" } else { path };
+        let index = self.containers.len();
+        let data = Rc::new(data);
+        self.containers.push(Container {
+            kind,
+            data: data.clone(),
+        });
+        match kind {
+            ArchiveKind::Zip => self.scan_zip(index, &data, prefix, depth),
+            ArchiveKind::Tar => self.scan_tar(index, &data, prefix, depth),
+            _ => {
+                // 单文件 gz：去掉 .gz 后缀作为文件名
+                let lower = path.to_lowercase();
+                let name = if lower.ends_with(".gz") {
+                    &path[..path.len() - 3]
+                } else {
+                    path
+                };
+                let name = if name.is_empty() { "archive" } else { name };
+                self.add_entry(
+                    index,
+                    Location::Range(0, data.len()),
+                    name,
+                    data.len() as u64,
+                    depth,
+                    |limit| read_limited_to_vec(&data[..], limit),
+                );
+                Ok(())
+            }
         }
-    })?;
-
-    let mut candidates: Vec<ExtractCandidate> = Vec::new();
-    for i in 0..archive.len() {
-        let f = archive
-            .by_index(i)
-            .map_err(|e| format!("read zip entry failed: {e}"))?;
-        if f.is_dir() {
-            continue;
+    }
+
+    fn scan_zip(
+        &mut self,
+        index: usize,
+        data: &[u8],
+        prefix: &str,
+        depth: usize,
+    ) -> Result<(), String> {
+        let mut archive = ZipArchive::new(Cursor::new(data)).map_err(zip_error)?;
+        for i in 0..archive.len() {
+            let (name, size, compressed, is_dir) = {
+                let f = archive
+                    .by_index_raw(i)
+                    .map_err(|e| format!("read zip entry failed: {e}"))?;
+                (
+                    f.name().to_string(),
+                    f.size(),
+                    f.compressed_size(),
+                    f.is_dir(),
+                )
+            };
+            if is_dir {
+                continue;
+            }
+            let path = join_path(prefix, &name);
+            if exceeds_ratio(compressed, size) {
+                self.truncated = true;
+                self.skipped
+                    .push(format!("{path}（压缩比异常，疑似压缩炸弹）"));
+                continue;
+            }
+            self.add_entry(index, Location::ZipEntry(i), &path, size, depth, |limit| {
+                let f = archive
+                    .by_index(i)
+                    .map_err(|e| format!("open zip entry failed: {e}"))?;
+                read_limited_to_vec(f, limit)
+            });
+            if self.should_stop() {
+                break;
+            }
         }
-        let name = f.name().to_string();
-        if !is_text_candidate_name(&name) {
-            continue;
+        Ok(())
+    }
+
+    fn scan_tar(
+        &mut self,
+        index: usize,
+        data: &[u8],
+        prefix: &str,
+        depth: usize,
+    ) -> Result<(), String> {
+        let mut archive = TarArchive::new(Cursor::new(data));
+        let entries = archive
+            .entries()
+            .map_err(|e| format!("parse tar failed: {e}"))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("read tar entry failed: {e}"))?;
+            let header = entry.header();
+            if !header.entry_type().is_file() {
+                continue;
+            }
+            let name = entry
+                .path()
+                .map_err(|e| format!("read tar entry path failed: {e}"))?
+                .to_string_lossy()
+                .to_string();
+            let path = join_path(prefix, &name);
+            let start = entry.raw_file_position() as usize;
+            let size = entry.size();
+            let end = start.saturating_add(size as usize).min(data.len());
+            let start = start.min(end);
+            self.add_entry(
+                index,
+                Location::Range(start, end),
+                &path,
+                size,
+                depth,
+                |limit| read_limited_to_vec(&data[start..end], limit),
+            );
+            if self.should_stop() {
+                break;
+            }
         }
-        candidates.push(ExtractCandidate {
-            name,
-            size_bytes: f.size(),
+        Ok(())
+    }
+
+    /// 达到条目数或解压总量上限时停止遍历
+    fn should_stop(&self) -> bool {
+        self.entries >= MAX_ENTRIES || self.remaining() == 0
+    }
+
+    /// 登记一个条目：嵌套压缩包立即展开（失败只记录，不中断外层），文本文件记为候选
+    fn add_entry(
+        &mut self,
+        container: usize,
+        location: Location,
+        path: &str,
+        size: u64,
+        depth: usize,
+        read: impl FnOnce(u64) -> Result<(Vec<u8>, bool), String>,
+    ) {
+        self.entries += 1;
+        if self.entries >= MAX_ENTRIES {
+            self.truncated = true;
+        }
+
+        if let Some(kind) = kind_from_name(path) {
+            if let Some(msg) = unsupported_message(kind) {
+                self.skipped.push(format!("{path}（{msg}）"));
+                return;
+            }
+            if depth >= MAX_NESTED_DEPTH {
+                self.skipped.push(format!("{path}（嵌套层数过多）"));
+                return;
+            }
+            if self.containers.len() >= MAX_NESTED_ARCHIVES {
+                self.truncated = true;
+                self.skipped.push(format!("{path}（嵌套压缩包数量过多）"));
+                return;
+            }
+            let result = match self.read_entry(path, read) {
+                Ok(Some(bytes)) => {
+                    let kind = sniff_kind(&bytes).unwrap_or(kind);
+                    self.open(kind, bytes, path, depth + 1)
+                }
+                Ok(None) => Ok(()),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                self.skipped.push(format!("{path}（{e}）"));
+            }
+            return;
+        }
+
+        if !is_text_candidate_name(path) {
+            return;
+        }
+        if self.candidates.len() as u32 >= self.limits.max_files {
+            self.truncated = true;
+            return;
+        }
+        self.candidates.push(ExtractCandidate {
+            container,
+            location,
+            path: path.to_string(),
+            size_bytes: size,
+            score: name_score(path, &self.keywords),
         });
-        if candidates.len() as u32 >= max_files {
-            break;
+    }
+
+    /// 按相关度挑选文件：第一名总会选中，其余需达到 MIN_SECONDARY_SCORE
+    fn select(&self) -> Vec<ExtractCandidate> {
+        let mut ranked = self.candidates.clone();
+        // 得分相同时偏向更大的文件（上下文更多），保持稳定排序
+        ranked.sort_by_key(|c| {
+            std::cmp::Reverse(c.score + (c.size_bytes.min(5_000_000) as i64) / 50_000)
+        });
+        let limit = self.limits.bundle_files.clamp(1, 10) as usize;
+        ranked
+            .into_iter()
+            .enumerate()
+            .filter(|(i, c)| *i == 0 || c.score >= MIN_SECONDARY_SCORE)
+            .map(|(_, c)| c)
+            .take(limit)
+            .collect()
+    }
+
+    fn read_candidate(&mut self, c: &ExtractCandidate) -> Result<Option<Vec<u8>>, String> {
+        let (kind, data) = {
+            let container = &self.containers[c.container];
+            (container.kind, container.data.clone())
+        };
+        match (kind, c.location) {
+            (ArchiveKind::Zip, Location::ZipEntry(i)) => self.read_entry(&c.path, |limit| {
+                let mut archive = ZipArchive::new(Cursor::new(&data[..])).map_err(zip_error)?;
+                let f = archive
+                    .by_index(i)
+                    .map_err(|e| format!("open zip entry failed: {e}"))?;
+                read_limited_to_vec(f, limit)
+            }),
+            (_, Location::Range(start, end)) => {
+                let bytes = &data[start..end];
+                // tar / gz 的数据已计入容器解压量，这里只检查单文件上限
+                if bytes.len() as u64 > self.limits.max_file_bytes {
+                    self.truncated = true;
+                    self.skipped
+                        .push(format!("{}（超过单文件大小上限）", c.path));
+                    return Ok(None);
+                }
+                Ok(Some(bytes.to_vec()))
+            }
+            _ => Err("压缩包条目位置无效".to_string()),
         }
     }
+}
 
-    let Some(best) = choose_best_file(&candidates, keywords) else {
-        return Err("压缩包内未找到 .log/.txt 文件".to_string());
-    };
+fn join_path(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{prefix}/{name}")
+    }
+}
 
-    let mut total_extracted: u64 = 0;
-    let mut file = archive
-        .by_name(&best.name)
-        .map_err(|e| format!("open zip entry failed: {e}"))?;
-    let allowed = max_file_bytes.min(max_extract_bytes);
-    let (bytes, truncated_bytes) = read_limited_to_vec(&mut file, allowed)?;
-    total_extracted += bytes.len() as u64;
-
-    let text = String::from_utf8_lossy(&bytes).to_string();
-    // Keep some extra headroom for prompt wrapper and metadata; plugin can further truncate.
-    let (text, truncated_chars) = truncate_to_chars(text, 200_000);
-    let truncated = truncated_bytes || truncated_chars || total_extracted >= max_extract_bytes;
-    let ext = Path::new(&best.name)
-        .extension()
-        .and_then(|e| e.to_str())
-        .map(|s| s.to_lowercase());
-    Ok((text, ext, total_extracted, truncated))
+/// 按字符数截断；日志保留开头与结尾（结尾通常是报错所在）
+fn truncate_to_chars(text: String, max_chars: usize, keep_tail: bool) -> (String, bool) {
+    let total = text.chars().count();
+    if total <= max_chars {
+        return (text, false);
+    }
+    if !keep_tail {
+        return (text.chars().take(max_chars).collect(), true);
+    }
+    let head = max_chars / 3;
+    let tail = max_chars - head;
+    let mut out: String = text.chars().take(head).collect();
+    out.push_str(&format!("\n……（中间省略 {} 字）……\n", total - head - tail));
+    out.extend(text.chars().skip(total - tail));
+    (out, true)
 }
 
-fn extract_best_text_from_tar_path(
-    path: &Path,
-    gz: bool,
-    max_extract_bytes: u64,
-    max_file_bytes: u64,
-    max_files: u32,
+/// 把总字符预算平均分给各文件，短文件用不完的部分留给其余文件
+fn split_budget(lengths: &[usize], total: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..lengths.len()).collect();
+    order.sort_by_key(|&i| lengths[i]);
+    let mut budgets = vec![0; lengths.len()];
+    let mut remaining = total;
+    for (n, &i) in order.iter().enumerate() {
+        let share = remaining / (lengths.len() - n);
+        budgets[i] = lengths[i].min(share);
+        remaining -= budgets[i];
+    }
+    budgets
+}
+
+fn extract_archive_text(
+    kind: ArchiveKind,
+    bytes: Vec<u8>,
+    root_name: &str,
+    limits: ArchiveLimits,
     keywords: &[String],
 ) -> Result<(String, Option<String>, u64, bool), String> {
-    // First pass: collect candidates.
-    let file = std::fs::File::open(path).map_err(|e| format!("open tar failed: {e}"))?;
-    let reader: Box<dyn Read> = if gz {
-        Box::new(GzDecoder::new(file))
-    } else {
-        Box::new(file)
+    let mut walker = Walker {
+        limits,
+        keywords: normalize_keywords(keywords),
+        containers: Vec::new(),
+        candidates: Vec::new(),
+        extracted: 0,
+        entries: 0,
+        truncated: false,
+        skipped: Vec::new(),
     };
-    let mut archive = TarArchive::new(reader);
-
-    let mut candidates: Vec<ExtractCandidate> = Vec::new();
-    let entries = archive.entries().map_err(|e| format!("parse tar failed: {e}"))?;
-    for entry in entries {
-        let entry = entry.map_err(|e| format!("read tar entry failed: {e}"))?;
-        let header = entry.header();
-        if header.entry_type().is_dir() {
-            continue;
+    walker.open(kind, bytes, root_name, 0)?;
+
+    let selected = walker.select();
+    if selected.is_empty() {
+        let mut msg = "压缩包内未找到 .log/.txt 或可读取的文档文件".to_string();
+        if !walker.skipped.is_empty() {
+            msg.push_str(&format!("；未能读取：{}", walker.skipped.join("、")));
         }
-        let p = entry
-            .path()
-            .map_err(|e| format!("read tar entry path failed: {e}"))?;
-        let name = p.to_string_lossy().to_string();
-        if !is_text_candidate_name(&name) {
+        return Err(msg);
+    }
+
+    let mut files: Vec<(String, String)> = Vec::new();
+    let mut first_err = None;
+    for c in &selected {
+        let Some(bytes) = walker.read_candidate(c)? else {
             continue;
-        }
-        candidates.push(ExtractCandidate {
-            name,
-            size_bytes: header.size().unwrap_or(0),
-        });
-        if candidates.len() as u32 >= max_files {
-            break;
+        };
+        match extract_document_text(&bytes, Some(&c.path)) {
+            Ok(text) if !text.trim().is_empty() => files.push((c.path.clone(), text)),
+            Ok(_) => {}
+            Err(e) => {
+                walker.skipped.push(format!("{}（{e}）", c.path));
+                first_err.get_or_insert(e);
+            }
         }
     }
+    if files.is_empty() {
+        return Err(first_err.unwrap_or_else(|| "压缩包内选中的文件没有文字内容".to_string()));
+    }
 
-    let Some(best) = choose_best_file(&candidates, keywords) else {
-        return Err("压缩包内未找到 .log/.txt 文件".to_string());
-    };
-
-    // Second pass: read chosen entry.
-    let file = std::fs::File::open(path).map_err(|e| format!("open tar failed: {e}"))?;
-    let reader: Box<dyn Read> = if gz {
-        Box::new(GzDecoder::new(file))
-    } else {
-        Box::new(file)
-    };
-    let mut archive = TarArchive::new(reader);
-
-    let mut total_extracted: u64 = 0;
-    let entries = archive.entries().map_err(|e| format!("parse tar failed: {e}"))?;
-    for entry in entries {
-        let mut entry = entry.map_err(|e| format!("read tar entry failed: {e}"))?;
-        let header = entry.header();
-        if header.entry_type().is_dir() {
-            continue;
+    let lengths: Vec<usize> = files.iter().map(|(_, t)| t.chars().count()).collect();
+    let budgets = split_budget(&lengths, MAX_TOTAL_CHARS);
+    let single = files.len() == 1 && walker.skipped.is_empty();
+    let mut out = String::new();
+    for ((path, text), budget) in files.iter().zip(budgets) {
+        let lower = path.to_lowercase();
+        let keep_tail = lower.ends_with(".log") || lower.contains("crash");
+        let (text, truncated) = truncate_to_chars(text.clone(), budget, keep_tail);
+        walker.truncated |= truncated;
+        if single {
+            out = text;
+        } else {
+            out.push_str(&format!("===== 文件：{path} =====\n{text}\n\n"));
         }
-        let p = entry
-            .path()
-            .map_err(|e| format!("read tar entry path failed: {e}"))?;
-        let name = p.to_string_lossy().to_string();
-        if name != best.name {
-            continue;
+    }
+    if !walker.skipped.is_empty() {
+        out.push_str("===== 未能读取的文件 =====\n");
+        for s in &walker.skipped {
+            out.push_str(&format!("- {s}\n"));
         }
-
-        let allowed = max_file_bytes.min(max_extract_bytes);
-        let (bytes, truncated_bytes) = read_limited_to_vec(&mut entry, allowed)?;
-        total_extracted += bytes.len() as u64;
-        let text = String::from_utf8_lossy(&bytes).to_string();
-        let (text, truncated_chars) = truncate_to_chars(text, 200_000);
-        let truncated = truncated_bytes || truncated_chars || total_extracted >= max_extract_bytes;
-        let ext = Path::new(&best.name)
-            .extension()
-            .and_then(|e| e.to_str())
-            .map(|s| s.to_lowercase());
-        return Ok((text, ext, total_extracted, truncated));
     }
 
-    Err("压缩包内未找到可用日志文件".to_string())
-}
-
-fn extract_best_text_from_gz_path(
-    path: &Path,
-    max_extract_bytes: u64,
-    max_file_bytes: u64,
-) -> Result<(String, Option<String>, u64, bool), String> {
-    let file = std::fs::File::open(path).map_err(|e| format!("open gz failed: {e}"))?;
-    let mut decoder = GzDecoder::new(file);
-
-    let allowed = max_file_bytes.min(max_extract_bytes);
-    let (bytes, truncated_bytes) = read_limited_to_vec(&mut decoder, allowed)?;
-    let text = String::from_utf8_lossy(&bytes).to_string();
-    let (text, truncated_chars) = truncate_to_chars(text, 200_000);
-    let truncated = truncated_bytes || truncated_chars;
-    Ok((text, Some("gz".to_string()), bytes.len() as u64, truncated))
+    let ext = Path::new(&files[0].0)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|s| s.to_lowercase());
+    Ok((out, ext, walker.extracted, walker.truncated))
 }
 
 pub(super) async fn download_archive_text(
     url: &str,
     file_name: Option<&str>,
     timeout_ms: u64,
-    max_download_bytes: u64,
-    max_extract_bytes: u64,
-    max_file_bytes: u64,
-    max_files: u32,
+    limits: ArchiveLimits,
     keywords: &[String],
 ) -> Result<(TempFileGuard, String, DocumentMeta), String> {
-    let max_extract_bytes = max_extract_bytes.clamp(1_000_000, 1_000_000_000);
-    let max_file_bytes = max_file_bytes.clamp(100_000, 200_000_000);
-    let max_files = max_files.clamp(1, 500);
+    let limits = ArchiveLimits {
+        max_download_bytes: limits.max_download_bytes,
+        max_extract_bytes: limits.max_extract_bytes.clamp(1_000_000, 1_000_000_000),
+        max_file_bytes: limits.max_file_bytes.clamp(100_000, 200_000_000),
+        max_files: limits.max_files.clamp(1, 500),
+        bundle_files: limits.bundle_files.clamp(1, 10),
+    };
 
     let mut last_err: Option<String> = None;
     for attempt in 0..2 {
-        let (guard, bin_meta) = download_binary_to_temp(url, file_name, timeout_ms, max_download_bytes).await?;
+        let (guard, bin_meta) =
+            download_binary_to_temp(url, file_name, timeout_ms, limits.max_download_bytes).await?;
         if bin_meta.truncated {
             return Err(format!(
                 "压缩包下载被截断（已下载 {} bytes，达到上限 {} bytes）。请提高 max_download_bytes 或上传更小的压缩包。",
-                bin_meta.size_bytes, max_download_bytes
+                bin_meta.size_bytes, limits.max_download_bytes
             ));
         }
 
-        let kind = guess_kind(url, bin_meta.file_name.as_deref().or(file_name)).ok_or_else(|| {
-            "不支持的压缩格式（仅支持 .zip / .tar / .tar.gz(.tgz) / .gz）".to_string()
-        })?;
-
+        let name_kind = guess_kind(url, bin_meta.file_name.as_deref().or(file_name));
+        let root_name = bin_meta
+            .file_name
+            .as_deref()
+            .or(file_name)
+            .and_then(|s| Path::new(s).file_name().and_then(|n| n.to_str()))
+            .unwrap_or("archive")
+            .to_string();
         let path = guard.path.clone();
         let keywords = keywords.to_vec();
 
-        let extracted = tokio::task::spawn_blocking(move || match kind {
-            ArchiveKind::Zip => extract_best_text_from_zip(
-                &path,
-                max_extract_bytes,
-                max_file_bytes,
-                max_files,
-                &keywords,
-            ),
-            ArchiveKind::Tar => extract_best_text_from_tar_path(
-                &path,
-                false,
-                max_extract_bytes,
-                max_file_bytes,
-                max_files,
-                &keywords,
-            ),
-            ArchiveKind::TarGz => extract_best_text_from_tar_path(
-                &path,
-                true,
-                max_extract_bytes,
-                max_file_bytes,
-                max_files,
-                &keywords,
-            ),
-            ArchiveKind::Gz => extract_best_text_from_gz_path(&path, max_extract_bytes, max_file_bytes),
+        let extracted = tokio::task::spawn_blocking(move || {
+            let bytes = std::fs::read(&path).map_err(|e| format!("open archive failed: {e}"))?;
+            // 以文件头为准，扩展名只作兜底（tar.gz 与 gz 的区别在解压后判断）
+            let kind = match (sniff_kind(&bytes), name_kind) {
+                (Some(ArchiveKind::Gz), Some(ArchiveKind::TarGz)) => ArchiveKind::TarGz,
+                (Some(kind), _) => kind,
+                (None, Some(ArchiveKind::Tar)) => ArchiveKind::Tar,
+                (None, Some(ArchiveKind::Zip)) => {
+                    return Err("zip 文件不完整或损坏（EOCD 缺失）：文件头无效".to_string())
+                }
+                _ => {
+                    return Err(
+                        "不支持的压缩格式（仅支持 .zip / .tar / .tar.gz(.tgz) / .gz）".to_string(),
+                    )
+                }
+            };
+            if let Some(msg) = unsupported_message(kind) {
+                return Err(msg);
+            }
+            extract_archive_text(kind, bytes, &root_name, limits, &keywords)
         })
         .await
         .map_err(|e| format!("解压任务失败: {e}"))?;
@@ -366,6 +721,8 @@ pub(super) async fn download_archive_text(
             Err(e) => {
                 // Some zip downloads can be cut short by network, producing "EOCD" errors.
                 // Retry once to reduce flakiness; if it still fails, surface the error.
+                // 缓存里的可能就是损坏的文件，先移除再重试
+                evict_url(url).await;
                 let lower = e.to_lowercase();
                 last_err = Some(e);
                 if attempt == 0 && lower.contains("eocd") {
@@ -378,3 +735,144 @@ pub(super) async fn download_archive_text(
 
     Err(last_err.unwrap_or_else(|| "解压失败".to_string()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    fn zip_bytes(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut w = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in entries {
+            w.start_file(*name, SimpleFileOptions::default()).unwrap();
+            w.write_all(data).unwrap();
+        }
+        w.finish().unwrap().into_inner()
+    }
+
+    fn gz_bytes(data: &[u8]) -> Vec<u8> {
+        let mut enc = GzEncoder::new(Vec::new(), Compression::best());
+        enc.write_all(data).unwrap();
+        enc.finish().unwrap()
+    }
+
+    fn limits() -> ArchiveLimits {
+        ArchiveLimits {
+            max_download_bytes: 50_000_000,
+            max_extract_bytes: 100_000_000,
+            max_file_bytes: 20_000_000,
+            max_files: 50,
+            bundle_files: 5,
+        }
+    }
+
+    fn extract(
+        kind: ArchiveKind,
+        bytes: Vec<u8>,
+        limits: ArchiveLimits,
+    ) -> Result<(String, bool), String> {
+        extract_archive_text(kind, bytes, "root", limits, &[])
+            .map(|(text, _, _, truncated)| (text, truncated))
+    }
+
+    /// 每层一个压缩包，最内层放日志
+    fn nested_zip(levels: usize, extra: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut inner = zip_bytes(&[("deep.log", b"deep content")]);
+        for level in 1..levels {
+            inner = zip_bytes(&[(&format!("level{level}.zip"), &inner)]);
+        }
+        let mut top: Vec<(&str, &[u8])> = vec![("nested.zip", &inner)];
+        top.extend_from_slice(extra);
+        zip_bytes(&top)
+    }
+
+    #[test]
+    fn reads_nested_archives_within_depth() {
+        let (text, _) = extract(
+            ArchiveKind::Zip,
+            nested_zip(MAX_NESTED_DEPTH, &[]),
+            limits(),
+        )
+        .unwrap();
+        assert!(text.contains("deep content"));
+    }
+
+    #[test]
+    fn stops_at_max_nesting_depth() {
+        let bytes = nested_zip(MAX_NESTED_DEPTH + 2, &[("top.log", b"top content")]);
+        let (text, _) = extract(ArchiveKind::Zip, bytes, limits()).unwrap();
+        assert!(text.contains("top content"));
+        assert!(text.contains("嵌套层数过多"));
+        assert!(!text.contains("deep content"));
+
+        let err = extract(
+            ArchiveKind::Zip,
+            nested_zip(MAX_NESTED_DEPTH + 2, &[]),
+            limits(),
+        )
+        .unwrap_err();
+        assert!(err.contains("嵌套层数过多"), "{err}");
+    }
+
+    #[test]
+    fn skips_high_ratio_zip_entry() {
+        let zeros = vec![0u8; 20_000_000];
+        let bytes = zip_bytes(&[("bomb.log", &zeros), ("ok.log", b"fine")]);
+        let (text, truncated) = extract(ArchiveKind::Zip, bytes, limits()).unwrap();
+        assert!(text.contains("fine"));
+        assert!(text.contains("bomb.log（压缩比异常"));
+        assert!(truncated);
+    }
+
+    #[test]
+    fn rejects_high_ratio_gz() {
+        let bytes = gz_bytes(&vec![b'a'; 20_000_000]);
+        let err = extract(ArchiveKind::Gz, bytes, limits()).unwrap_err();
+        assert!(err.contains("压缩比异常"), "{err}");
+
+        // 嵌套的 gz 只跳过该条目
+        let nested = gz_bytes(&vec![b'a'; 20_000_000]);
+        let bytes = zip_bytes(&[("app.log.gz", &nested), ("ok.log", b"fine")]);
+        let (text, _) = extract(ArchiveKind::Zip, bytes, limits()).unwrap();
+        assert!(text.contains("fine"));
+        assert!(text.contains("app.log.gz（压缩比异常"));
+    }
+
+    #[test]
+    fn stops_at_total_extract_limit() {
+        let chunk = "error: something failed\n".repeat(25_000);
+        let bytes = zip_bytes(&[
+            ("a.log", chunk.as_bytes()),
+            ("b.log", chunk.as_bytes()),
+            ("c.log", chunk.as_bytes()),
+        ]);
+        let limits = ArchiveLimits {
+            max_extract_bytes: 1_000_000,
+            ..limits()
+        };
+        let (text, truncated) = extract(ArchiveKind::Zip, bytes, limits).unwrap();
+        assert!(truncated);
+        assert!(text.contains("超出解压总量上限"));
+    }
+
+    #[test]
+    fn stops_at_max_entries() {
+        let names: Vec<String> = (0..MAX_ENTRIES + 10).map(|i| format!("f{i}.bin")).collect();
+        let mut entries: Vec<(&str, &[u8])> = vec![("first.log", b"first")];
+        entries.extend(names.iter().map(|n| (n.as_str(), &b""[..])));
+        let (text, truncated) = extract(ArchiveKind::Zip, zip_bytes(&entries), limits()).unwrap();
+        assert!(text.contains("first"));
+        assert!(truncated);
+    }
+
+    #[test]
+    fn reports_unsupported_nested_formats() {
+        let bytes = zip_bytes(&[("inner.7z", b"7z\xbc\xaf\x27\x1c"), ("ok.log", b"fine")]);
+        let (text, _) = extract(ArchiveKind::Zip, bytes, limits()).unwrap();
+        assert!(text.contains("inner.7z"));
+    }
+}
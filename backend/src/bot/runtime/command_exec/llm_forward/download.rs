@@ -1,5 +1,6 @@
 use std::path::{Path, PathBuf};
-use tokio::io::AsyncWriteExt;
+
+use super::super::media_cache::download_cached;
 
 pub(super) struct TempFileGuard {
     pub(super) path: PathBuf,
@@ -95,59 +96,20 @@ pub(super) async fn download_document_text(
 ) -> Result<(TempFileGuard, String, DocumentMeta), String> {
     let guard = TempFileGuard::new("download", file_name).await?;
 
-    let timeout = std::time::Duration::from_millis(timeout_ms.clamp(1000, 120000));
     let max_bytes = max_bytes.clamp(1024, 50_000_000);
     let max_chars = max_chars.clamp(1000, 200_000) as usize;
 
-    let client = reqwest::Client::new();
-    let resp = client
-        .get(url)
-        .timeout(timeout)
-        .send()
-        .await
-        .map_err(|e| format!("Download failed: {e}"))?;
-
-    if !resp.status().is_success() {
-        return Err(format!("Download failed: HTTP {}", resp.status()));
-    }
-
-    let mut file = tokio::fs::File::create(&guard.path)
+    let downloaded = download_cached(url, &guard.path, timeout_ms, max_bytes, false).await?;
+    let buf = tokio::fs::read(&guard.path)
         .await
-        .map_err(|e| format!("Create temp file failed: {e}"))?;
-
-    let mut stream = resp.bytes_stream();
-    let mut downloaded: u64 = 0;
-    let mut truncated_by_bytes = false;
-    let mut buf: Vec<u8> = Vec::new();
-
-    use futures_util::StreamExt;
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk.map_err(|e| format!("Read download stream failed: {e}"))?;
-        if downloaded >= max_bytes {
-            truncated_by_bytes = true;
-            break;
-        }
-
-        let remaining = (max_bytes - downloaded) as usize;
-        let slice: &[u8] = if chunk.len() > remaining {
-            truncated_by_bytes = true;
-            &chunk[..remaining]
-        } else {
-            &chunk
-        };
-
-        file.write_all(slice)
-            .await
-            .map_err(|e| format!("Write temp file failed: {e}"))?;
-        buf.extend_from_slice(slice);
-        downloaded += slice.len() as u64;
-
-        if truncated_by_bytes {
-            break;
-        }
-    }
-
-    let mut text = String::from_utf8_lossy(&buf).to_string();
+        .map_err(|e| format!("Read temp file failed: {e}"))?;
+
+    let name_hint = file_name.map(|s| s.to_string());
+    let mut text = tokio::task::spawn_blocking(move || {
+        super::extract::extract_document_text(&buf, name_hint.as_deref())
+    })
+    .await
+    .map_err(|e| format!("Extract document text failed: {e}"))??;
     let truncated_by_chars = truncate_to_chars(&mut text, max_chars);
 
     let meta = DocumentMeta {
@@ -155,8 +117,8 @@ pub(super) async fn download_document_text(
         file_ext: file_name
             .and_then(|s| Path::new(s).extension().and_then(|e| e.to_str()))
             .map(|s| s.to_lowercase()),
-        size_bytes: Some(downloaded),
-        truncated: truncated_by_bytes || truncated_by_chars,
+        size_bytes: Some(downloaded.size_bytes),
+        truncated: downloaded.truncated || truncated_by_chars,
     };
 
     Ok((guard, text, meta))
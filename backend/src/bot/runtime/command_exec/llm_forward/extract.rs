@@ -0,0 +1,293 @@
+//! 文档文本提取：按内容嗅探格式（PDF、DOCX/XLSX/PPTX、HTML、纯文本），
+//! 纯文本按 BOM / UTF-8 / UTF-16 / GB18030 检测编码。文档下载与压缩包分析共用。
+
+mod ooxml;
+mod pdf;
+
+use encoding_rs::{Encoding, GB18030, UTF_16BE, UTF_16LE};
+use std::path::Path;
+
+/// 除 .log/.txt 外可提取文字的文档扩展名（压缩包内挑选文件时使用）
+pub(super) const DOCUMENT_EXTENSIONS: &[&str] = &[
+    "pdf", "docx", "xlsx", "pptx", "html", "htm", "md", "csv", "json",
+];
+
+/// 提取文档文字；无法识别的二进制文件与解析失败的文档返回 Err。
+pub(super) fn extract_document_text(bytes: &[u8], name: Option<&str>) -> Result<String, String> {
+    if bytes.starts_with(b"%PDF-") {
+        return pdf::extract_text(bytes).map_err(|e| format!("PDF 解析失败：{e}"));
+    }
+    if bytes.starts_with(b"PK\x03\x04") {
+        return ooxml::extract_text(bytes).map_err(|e| format!("Office 文档解析失败：{e}"));
+    }
+    if let Some(format) = unsupported_archive_format(bytes) {
+        return Err(format!(
+            "文件是 {format} 压缩包，暂不支持，请改用 zip 或 tar.gz 重新打包"
+        ));
+    }
+    if bytes.starts_with(b"\xD0\xCF\x11\xE0") {
+        return Err(
+            "暂不支持旧版 Office 格式（.doc/.xls/.ppt），请另存为 docx/xlsx/pptx".to_string(),
+        );
+    }
+    if looks_binary(bytes) {
+        return Err("文件不是文本或受支持的文档格式".to_string());
+    }
+
+    let text = decode_text(bytes);
+    if looks_like_html(&text, name) {
+        return Ok(html_to_text(&text));
+    }
+    Ok(text)
+}
+
+/// 识别只能提示、无法解压的压缩格式（7z / rar）
+pub(super) fn unsupported_archive_format(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"7z\xBC\xAF\x27\x1C") {
+        Some("7z")
+    } else if bytes.starts_with(b"Rar!\x1A\x07") {
+        Some("rar")
+    } else {
+        None
+    }
+}
+
+/// 检测编码并解码：BOM 优先，其次无 BOM 的 UTF-16、UTF-8，最后按 GB18030（兼容 GBK）。
+pub(super) fn decode_text(bytes: &[u8]) -> String {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        return encoding
+            .decode_without_bom_handling(&bytes[bom_len..])
+            .0
+            .into_owned();
+    }
+    if let Some(encoding) = sniff_utf16(bytes) {
+        return encoding.decode_without_bom_handling(bytes).0.into_owned();
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(s) => return s.to_string(),
+        // 只是在多字节字符中间被截断
+        Err(e) if e.error_len().is_none() => {
+            return String::from_utf8_lossy(&bytes[..e.valid_up_to()]).into_owned()
+        }
+        Err(_) => {}
+    }
+    let utf8 = String::from_utf8_lossy(bytes);
+    let (gb, _) = GB18030.decode_without_bom_handling(bytes);
+    if count_replacements(&gb) < count_replacements(&utf8) {
+        gb.into_owned()
+    } else {
+        utf8.into_owned()
+    }
+}
+
+fn count_replacements(s: &str) -> usize {
+    s.chars()
+        .filter(|&c| c == char::REPLACEMENT_CHARACTER)
+        .count()
+}
+
+/// 无 BOM 的 UTF-16：ASCII 字符的高字节为 0，集中出现在奇数位（LE）或偶数位（BE）
+fn sniff_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(4096) & !1];
+    if sample.len() < 4 {
+        return None;
+    }
+    let pairs = sample.len() / 2;
+    let even = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd = sample
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .filter(|&&b| b == 0)
+        .count();
+    if odd * 10 >= pairs * 3 && even * 20 < pairs {
+        Some(UTF_16LE)
+    } else if even * 10 >= pairs * 3 && odd * 20 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+fn looks_binary(bytes: &[u8]) -> bool {
+    if Encoding::for_bom(bytes).is_some() || sniff_utf16(bytes).is_some() {
+        return false;
+    }
+    let sample = &bytes[..bytes.len().min(8192)];
+    sample.contains(&0)
+}
+
+fn looks_like_html(text: &str, name: Option<&str>) -> bool {
+    let by_ext = name
+        .and_then(|n| Path::new(n).extension().and_then(|e| e.to_str()))
+        .is_some_and(|e| matches!(e.to_lowercase().as_str(), "html" | "htm" | "xhtml"));
+    if by_ext {
+        return true;
+    }
+    let head: String = text.trim_start().chars().take(64).collect();
+    let head = head.to_ascii_lowercase();
+    head.starts_with("<!doctype html") || head.starts_with("<html")
+}
+
+const BLOCK_TAGS: &[&str] = &[
+    "p",
+    "div",
+    "br",
+    "li",
+    "tr",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "table",
+    "section",
+    "article",
+    "header",
+    "footer",
+    "ul",
+    "ol",
+    "pre",
+    "blockquote",
+    "hr",
+    "title",
+];
+const SKIPPED_TAGS: &[&str] = &["script", "style", "noscript", "template", "svg"];
+
+/// HTML 转纯文本：去掉脚本与样式，块级标签换行，解码实体，压缩空白。
+pub(super) fn html_to_text(html: &str) -> String {
+    let mut out = String::with_capacity(html.len() / 2);
+    let lower = html.to_ascii_lowercase();
+    let mut pos = 0;
+    while pos < html.len() {
+        let Some(rel) = html[pos..].find('<') else {
+            push_html_text(&mut out, &html[pos..]);
+            break;
+        };
+        push_html_text(&mut out, &html[pos..pos + rel]);
+        pos += rel;
+
+        if lower[pos..].starts_with("<!--") {
+            pos = lower[pos..].find("-->").map_or(html.len(), |i| pos + i + 3);
+            continue;
+        }
+        let Some(end) = html[pos..].find('>') else {
+            break;
+        };
+        let tag = &lower[pos + 1..pos + end];
+        pos += end + 1;
+
+        let closing = tag.starts_with('/');
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect();
+        if !closing && SKIPPED_TAGS.contains(&name.as_str()) && !tag.ends_with('/') {
+            let close = format!("</{name}");
+            pos = lower[pos..].find(&close).map_or(html.len(), |i| {
+                let after = pos + i;
+                lower[after..]
+                    .find('>')
+                    .map_or(html.len(), |j| after + j + 1)
+            });
+            continue;
+        }
+        if BLOCK_TAGS.contains(&name.as_str()) {
+            out.push('\n');
+        } else if !closing && (name == "td" || name == "th") {
+            out.push('\t');
+        }
+    }
+
+    let mut lines: Vec<&str> = Vec::new();
+    for line in out.lines().map(str::trim) {
+        if line.is_empty() && lines.last().is_none_or(|l| l.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    lines.join("\n").trim().to_string()
+}
+
+fn push_html_text(out: &mut String, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    let decoded = decode_entities(text);
+    let mut last_space = out.ends_with([' ', '\n', '\t']);
+    for c in decoded.chars() {
+        if c.is_whitespace() && c != '\u{a0}' {
+            if !last_space {
+                out.push(' ');
+                last_space = true;
+            }
+        } else {
+            out.push(if c == '\u{a0}' { ' ' } else { c });
+            last_space = false;
+        }
+    }
+}
+
+/// 解码 XML/HTML 实体（数字实体与常用命名实体）
+pub(super) fn decode_entities(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&end| end > 0 && end <= 10)
+            .and_then(|end| Some((decode_entity(&rest[1..=end])?, end + 2)));
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    if let Some(num) = name.strip_prefix('#') {
+        let code = match num.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => num.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "copy" => '©',
+        "reg" => '®',
+        "hellip" => '…',
+        "mdash" => '—',
+        "ndash" => '–',
+        "middot" => '·',
+        "laquo" => '«',
+        "raquo" => '»',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "times" => '×',
+        "yen" => '¥',
+        _ => return None,
+    })
+}
@@ -0,0 +1,413 @@
+//! DOCX / XLSX / PPTX：读取 zip 包内的 XML，按段落、行、幻灯片提取文字。
+
+use std::io::{Cursor, Read};
+use zip::ZipArchive;
+
+use super::decode_entities;
+
+/// 单个 XML 部件最多读取的字节数
+const MAX_PART_BYTES: u64 = 64 * 1024 * 1024;
+
+type Package<'a> = ZipArchive<Cursor<&'a [u8]>>;
+
+pub(super) fn extract_text(bytes: &[u8]) -> Result<String, String> {
+    let mut zip = ZipArchive::new(Cursor::new(bytes)).map_err(|e| format!("无法打开 zip：{e}"))?;
+    let names: Vec<String> = zip.file_names().map(|s| s.to_string()).collect();
+    let has = |name: &str| names.iter().any(|n| n == name);
+    let text = if has("word/document.xml") {
+        docx(&mut zip)?
+    } else if has("xl/workbook.xml") {
+        xlsx(&mut zip, &names)?
+    } else if has("ppt/presentation.xml") {
+        pptx(&mut zip, &names)?
+    } else {
+        return Err("文件是 zip 压缩包而不是 Word/Excel/PowerPoint 文档".to_string());
+    };
+    if text.trim().is_empty() {
+        return Err("文档中没有文字内容".to_string());
+    }
+    Ok(text)
+}
+
+fn read_part(zip: &mut Package, name: &str) -> Result<String, String> {
+    let file = zip
+        .by_name(name)
+        .map_err(|e| format!("读取 {name} 失败：{e}"))?;
+    let mut buf = Vec::new();
+    file.take(MAX_PART_BYTES)
+        .read_to_end(&mut buf)
+        .map_err(|e| format!("读取 {name} 失败：{e}"))?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// 按 `prefix` + 数字 + `.xml` 的编号排序，如 ppt/slides/slide2.xml
+fn numbered_parts(names: &[String], prefix: &str) -> Vec<(u32, String)> {
+    let mut parts: Vec<(u32, String)> = names
+        .iter()
+        .filter_map(|n| {
+            let num = n.strip_prefix(prefix)?.strip_suffix(".xml")?.parse().ok()?;
+            Some((num, n.clone()))
+        })
+        .collect();
+    parts.sort();
+    parts
+}
+
+enum Tag<'a> {
+    Open {
+        name: &'a str,
+        attrs: &'a str,
+        empty: bool,
+    },
+    Close(&'a str),
+    Text(&'a str),
+}
+
+/// 极简 XML 扫描：只区分开始/结束标签与文本，标签名去掉命名空间前缀
+fn for_each_tag<'a>(xml: &'a str, mut f: impl FnMut(Tag<'a>)) {
+    let mut rest = xml;
+    while let Some(i) = rest.find('<') {
+        if i > 0 {
+            f(Tag::Text(&rest[..i]));
+        }
+        let Some(end) = rest[i..].find('>') else {
+            return;
+        };
+        let inner = &rest[i + 1..i + end];
+        rest = &rest[i + end + 1..];
+        if inner.starts_with(['?', '!']) {
+            continue;
+        }
+        if let Some(name) = inner.strip_prefix('/') {
+            f(Tag::Close(local_name(name.trim())));
+            continue;
+        }
+        let empty = inner.ends_with('/');
+        let inner = inner.trim_end_matches('/');
+        let (name, attrs) = inner.split_once(char::is_whitespace).unwrap_or((inner, ""));
+        f(Tag::Open {
+            name: local_name(name),
+            attrs,
+            empty,
+        });
+    }
+    if !rest.is_empty() {
+        f(Tag::Text(rest));
+    }
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn attr<'a>(attrs: &'a str, key: &str) -> Option<&'a str> {
+    let mut rest = attrs;
+    while let Some(i) = rest.find(key) {
+        let before_ok = i == 0 || rest[..i].ends_with(char::is_whitespace);
+        let after = rest[i + key.len()..].trim_start();
+        if before_ok {
+            if let Some(value) = after.strip_prefix('=') {
+                let value = value.trim_start();
+                let quote = value.chars().next()?;
+                if quote == '"' || quote == '\'' {
+                    let value = &value[1..];
+                    return value.find(quote).map(|end| &value[..end]);
+                }
+            }
+        }
+        rest = &rest[i + key.len()..];
+    }
+    None
+}
+
+fn docx(zip: &mut Package) -> Result<String, String> {
+    let xml = read_part(zip, "word/document.xml")?;
+    let mut out = String::new();
+    let mut in_text = false;
+    // 段落属性里的 <w:tabs> 是制表位定义，不是文字
+    let mut in_tabs = false;
+    let mut cell_depth = 0usize;
+    for_each_tag(&xml, |tag| match tag {
+        Tag::Open {
+            name: "t",
+            empty: false,
+            ..
+        } => in_text = true,
+        Tag::Close("t") => in_text = false,
+        Tag::Open {
+            name: "tabs",
+            empty: false,
+            ..
+        } => in_tabs = true,
+        Tag::Close("tabs") => in_tabs = false,
+        Tag::Open { name: "tab", .. } if !in_tabs => out.push('\t'),
+        Tag::Open {
+            name: "br" | "cr", ..
+        } => out.push('\n'),
+        Tag::Open {
+            name: "tc",
+            empty: false,
+            ..
+        } => cell_depth += 1,
+        Tag::Close("tc") => {
+            cell_depth = cell_depth.saturating_sub(1);
+            out.push('\t');
+        }
+        Tag::Close("tr") => out.push('\n'),
+        Tag::Close("p") => out.push(if cell_depth > 0 { ' ' } else { '\n' }),
+        Tag::Text(t) if in_text => out.push_str(&decode_entities(t)),
+        _ => {}
+    });
+    Ok(out)
+}
+
+fn pptx(zip: &mut Package, names: &[String]) -> Result<String, String> {
+    let mut out = String::new();
+    for (num, name) in numbered_parts(names, "ppt/slides/slide") {
+        let xml = read_part(zip, &name)?;
+        let mut slide = String::new();
+        let mut in_text = false;
+        for_each_tag(&xml, |tag| match tag {
+            Tag::Open {
+                name: "t",
+                empty: false,
+                ..
+            } => in_text = true,
+            Tag::Close("t") => in_text = false,
+            Tag::Open { name: "br", .. } => slide.push('\n'),
+            Tag::Close("p") => slide.push('\n'),
+            Tag::Text(t) if in_text => slide.push_str(&decode_entities(t)),
+            _ => {}
+        });
+        if slide.trim().is_empty() {
+            continue;
+        }
+        out.push_str(&format!("--- 第 {num} 页 ---\n{}\n\n", slide.trim()));
+    }
+    Ok(out)
+}
+
+fn xlsx(zip: &mut Package, names: &[String]) -> Result<String, String> {
+    let shared = if names.iter().any(|n| n == "xl/sharedStrings.xml") {
+        shared_strings(&read_part(zip, "xl/sharedStrings.xml")?)
+    } else {
+        Vec::new()
+    };
+
+    let mut sheet_names = Vec::new();
+    for_each_tag(&read_part(zip, "xl/workbook.xml")?, |tag| {
+        if let Tag::Open {
+            name: "sheet",
+            attrs,
+            ..
+        } = tag
+        {
+            sheet_names.push(decode_entities(attr(attrs, "name").unwrap_or_default()));
+        }
+    });
+
+    let mut out = String::new();
+    for (num, name) in numbered_parts(names, "xl/worksheets/sheet") {
+        let xml = read_part(zip, &name)?;
+        let title = sheet_names
+            .get(num.saturating_sub(1) as usize)
+            .filter(|s| !s.is_empty())
+            .cloned()
+            .unwrap_or_else(|| format!("Sheet{num}"));
+        let rows = sheet_rows(&xml, &shared);
+        if rows.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("--- 工作表 {title} ---\n{}\n\n", rows.join("\n")));
+    }
+    Ok(out)
+}
+
+fn shared_strings(xml: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut in_text = false;
+    // 注音（<rPh>）不计入单元格文字
+    let mut in_phonetic = false;
+    for_each_tag(xml, |tag| match tag {
+        Tag::Open {
+            name: "si", empty, ..
+        } => {
+            current.clear();
+            if empty {
+                items.push(String::new());
+            }
+        }
+        Tag::Close("si") => items.push(std::mem::take(&mut current)),
+        Tag::Open {
+            name: "rPh",
+            empty: false,
+            ..
+        } => in_phonetic = true,
+        Tag::Close("rPh") => in_phonetic = false,
+        Tag::Open {
+            name: "t",
+            empty: false,
+            ..
+        } => in_text = true,
+        Tag::Close("t") => in_text = false,
+        Tag::Text(t) if in_text && !in_phonetic => current.push_str(&decode_entities(t)),
+        _ => {}
+    });
+    items
+}
+
+fn sheet_rows(xml: &str, shared: &[String]) -> Vec<String> {
+    let mut rows = Vec::new();
+    let mut cells: Vec<String> = Vec::new();
+    let mut cell_type = String::new();
+    let mut value = String::new();
+    let mut in_value = false;
+    for_each_tag(xml, |tag| match tag {
+        Tag::Open {
+            name: "c",
+            attrs,
+            empty,
+        } => {
+            cell_type = attr(attrs, "t").unwrap_or_default().to_string();
+            value.clear();
+            if empty {
+                cells.push(String::new());
+            }
+        }
+        Tag::Open {
+            name: "v" | "t",
+            empty: false,
+            ..
+        } => in_value = true,
+        Tag::Close("v" | "t") => in_value = false,
+        Tag::Text(t) if in_value => value.push_str(&decode_entities(t)),
+        Tag::Close("c") => {
+            let text = match cell_type.as_str() {
+                "s" => value
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| shared.get(i).cloned())
+                    .unwrap_or_default(),
+                "b" => if value.trim() == "1" { "TRUE" } else { "FALSE" }.to_string(),
+                _ => std::mem::take(&mut value),
+            };
+            cells.push(text);
+        }
+        Tag::Close("row") => {
+            while cells.last().is_some_and(|c| c.is_empty()) {
+                cells.pop();
+            }
+            if !cells.is_empty() {
+                rows.push(cells.join("\t"));
+            }
+            cells.clear();
+        }
+        _ => {}
+    });
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    fn build_zip(parts: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, body) in parts {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(body.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn scans_tags_and_attrs() {
+        let mut seen = Vec::new();
+        for_each_tag(
+            r#"<?xml?><w:p a="1"><w:t>hi</w:t><w:br/></w:p>tail"#,
+            |tag| {
+                seen.push(match tag {
+                    Tag::Open { name, empty, .. } => format!("open:{name}:{empty}"),
+                    Tag::Close(name) => format!("close:{name}"),
+                    Tag::Text(t) => format!("text:{t}"),
+                })
+            },
+        );
+        assert_eq!(
+            seen,
+            [
+                "open:p:false",
+                "open:t:false",
+                "text:hi",
+                "close:t",
+                "open:br:true",
+                "close:p",
+                "text:tail"
+            ]
+        );
+        assert_eq!(attr(r#"r="A1" t='s'"#, "t"), Some("s"));
+        assert_eq!(attr(r#"rt="x""#, "t"), None);
+    }
+
+    #[test]
+    fn extracts_docx() {
+        let doc = build_zip(&[(
+            "word/document.xml",
+            r#"<w:document><w:body>
+                <w:p><w:pPr><w:tabs><w:tab w:val="left"/></w:tabs></w:pPr><w:r><w:t>A &amp; B</w:t><w:tab/><w:t>C</w:t></w:r></w:p>
+                <w:tbl><w:tr><w:tc><w:p><w:r><w:t>x</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>y</w:t></w:r></w:p></w:tc></w:tr></w:tbl>
+            </w:body></w:document>"#,
+        )]);
+        let text = extract_text(&doc).unwrap();
+        assert!(text.contains("A & B\tC\n"), "{text:?}");
+        assert!(text.contains("x \ty \t\n"), "{text:?}");
+    }
+
+    #[test]
+    fn extracts_xlsx_with_shared_strings() {
+        let book = build_zip(&[
+            (
+                "xl/workbook.xml",
+                r#"<workbook><sheets><sheet name="数据" sheetId="1"/></sheets></workbook>"#,
+            ),
+            (
+                "xl/sharedStrings.xml",
+                r#"<sst><si><t>名称</t></si><si><r><t>ab</t></r><rPh><t>x</t></rPh></si></sst>"#,
+            ),
+            (
+                "xl/worksheets/sheet1.xml",
+                r#"<worksheet><sheetData>
+                    <row><c t="s"><v>0</v></c><c t="s"><v>1</v></c></row>
+                    <row><c><v>42</v></c><c t="b"><v>1</v></c><c/></row>
+                </sheetData></worksheet>"#,
+            ),
+        ]);
+        let text = extract_text(&book).unwrap();
+        assert_eq!(text, "--- 工作表 数据 ---\n名称\tab\n42\tTRUE\n\n");
+    }
+
+    #[test]
+    fn extracts_pptx_in_slide_order() {
+        let deck = build_zip(&[
+            ("ppt/presentation.xml", "<p:presentation/>"),
+            ("ppt/slides/slide10.xml", "<a:p><a:t>ten</a:t></a:p>"),
+            ("ppt/slides/slide2.xml", "<a:p><a:t>two</a:t></a:p>"),
+        ]);
+        let text = extract_text(&deck).unwrap();
+        assert_eq!(text, "--- 第 2 页 ---\ntwo\n\n--- 第 10 页 ---\nten\n\n");
+    }
+
+    #[test]
+    fn rejects_other_zips_and_garbage() {
+        let other = build_zip(&[("readme.txt", "hello")]);
+        assert!(extract_text(&other).unwrap_err().contains("zip"));
+        assert!(extract_text(b"PK\x03\x04broken").is_err());
+        let empty = build_zip(&[("word/document.xml", "<w:document/>")]);
+        assert!(extract_text(&empty).is_err());
+    }
+}
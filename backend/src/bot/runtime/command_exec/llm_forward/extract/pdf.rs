@@ -0,0 +1,1035 @@
+//! 简易 PDF 文字提取：扫描对象（含对象流）、按页面解码内容流（FlateDecode），
+//! 借助字体的 ToUnicode CMap 还原文字；没有 CMap 的单字节字体按 Latin-1 处理。
+//! 扫描件（只有图片）与加密文档提取不到文字。
+
+use flate2::read::ZlibDecoder;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::rc::Rc;
+
+/// 单个流解压后的上限
+const MAX_STREAM_BYTES: u64 = 64 * 1024 * 1024;
+const MAX_PAGES: usize = 2000;
+/// 引用链与页面树的最大深度（防止循环引用）
+const MAX_DEPTH: usize = 32;
+
+type Dict = HashMap<String, Obj>;
+
+#[derive(Debug, Clone)]
+enum Obj {
+    Null,
+    Bool,
+    Num(f64),
+    Name(String),
+    Str(Vec<u8>),
+    Array(Vec<Obj>),
+    Dict(HashMap<String, Obj>),
+    Ref(u32),
+    Op(String),
+}
+
+impl Obj {
+    fn as_dict(&self) -> Option<&HashMap<String, Obj>> {
+        match self {
+            Obj::Dict(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    fn as_num(&self) -> Option<f64> {
+        match self {
+            Obj::Num(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn as_name(&self) -> Option<&str> {
+        match self {
+            Obj::Name(n) => Some(n),
+            _ => None,
+        }
+    }
+}
+
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, 0 | 9 | 10 | 12 | 13 | 32)
+}
+
+fn is_delimiter(b: u8) -> bool {
+    matches!(
+        b,
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+    )
+}
+
+fn is_regular(b: u8) -> bool {
+    !is_whitespace(b) && !is_delimiter(b)
+}
+
+struct Lexer<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        while let Some(b) = self.peek() {
+            if is_whitespace(b) {
+                self.pos += 1;
+            } else if b == b'%' {
+                while let Some(b) = self.peek() {
+                    if b == b'\n' || b == b'\r' {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn starts_with(&self, s: &[u8]) -> bool {
+        self.data[self.pos.min(self.data.len())..].starts_with(s)
+    }
+
+    fn read_regular(&mut self) -> &'a [u8] {
+        let start = self.pos;
+        while self.peek().is_some_and(is_regular) {
+            self.pos += 1;
+        }
+        &self.data[start..self.pos]
+    }
+
+    fn next(&mut self) -> Option<Obj> {
+        self.next_depth(0)
+    }
+
+    fn next_depth(&mut self, depth: usize) -> Option<Obj> {
+        self.skip_ws();
+        let b = self.peek()?;
+        if depth > MAX_DEPTH {
+            self.pos += 1;
+            return Some(Obj::Null);
+        }
+        match b {
+            b'/' => {
+                self.pos += 1;
+                Some(Obj::Name(decode_name(self.read_regular())))
+            }
+            b'(' => Some(Obj::Str(self.read_literal())),
+            b'<' if self.starts_with(b"<<") => {
+                self.pos += 2;
+                let mut dict = HashMap::new();
+                loop {
+                    self.skip_ws();
+                    if self.peek().is_none() {
+                        break;
+                    }
+                    if self.starts_with(b">>") {
+                        self.pos += 2;
+                        break;
+                    }
+                    match self.next_depth(depth + 1)? {
+                        Obj::Name(key) => {
+                            let value = self.next_depth(depth + 1).unwrap_or(Obj::Null);
+                            dict.insert(key, value);
+                        }
+                        // 跳过无法识别的键
+                        _ => continue,
+                    }
+                }
+                Some(Obj::Dict(dict))
+            }
+            b'<' => Some(Obj::Str(self.read_hex())),
+            b'[' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_ws();
+                    match self.peek() {
+                        None => break,
+                        Some(b']') => {
+                            self.pos += 1;
+                            break;
+                        }
+                        Some(_) => items.push(self.next_depth(depth + 1)?),
+                    }
+                }
+                Some(Obj::Array(items))
+            }
+            b'0'..=b'9' | b'+' | b'-' | b'.' => Some(self.read_number()),
+            _ => {
+                let word = self.read_regular();
+                if word.is_empty() {
+                    // 孤立的分隔符
+                    self.pos += 1;
+                    return Some(Obj::Op((b as char).to_string()));
+                }
+                Some(match word {
+                    b"true" | b"false" => Obj::Bool,
+                    b"null" => Obj::Null,
+                    _ => Obj::Op(String::from_utf8_lossy(word).into_owned()),
+                })
+            }
+        }
+    }
+
+    fn read_number(&mut self) -> Obj {
+        let word = self.read_regular();
+        let text = std::str::from_utf8(word).unwrap_or("0");
+        let n = text.parse::<f64>().unwrap_or(0.0);
+        // `n g R` 形式的间接引用
+        if !text.contains(['.', '-', '+']) {
+            let save = self.pos;
+            self.skip_ws();
+            let gen = self.read_regular();
+            if !gen.is_empty() && gen.iter().all(u8::is_ascii_digit) {
+                self.skip_ws();
+                if self.peek() == Some(b'R')
+                    && self.data.get(self.pos + 1).is_none_or(|&b| !is_regular(b))
+                {
+                    self.pos += 1;
+                    return Obj::Ref(n as u32);
+                }
+            }
+            self.pos = save;
+        }
+        Obj::Num(n)
+    }
+
+    fn read_literal(&mut self) -> Vec<u8> {
+        self.pos += 1;
+        let mut out = Vec::new();
+        let mut depth = 1;
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            match b {
+                b'(' => {
+                    depth += 1;
+                    out.push(b);
+                }
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                    out.push(b);
+                }
+                b'\\' => {
+                    let Some(e) = self.peek() else { break };
+                    self.pos += 1;
+                    match e {
+                        b'n' => out.push(b'\n'),
+                        b'r' => out.push(b'\r'),
+                        b't' => out.push(b'\t'),
+                        b'b' => out.push(8),
+                        b'f' => out.push(12),
+                        b'\r' => {
+                            if self.peek() == Some(b'\n') {
+                                self.pos += 1;
+                            }
+                        }
+                        b'\n' => {}
+                        b'0'..=b'7' => {
+                            let mut v = (e - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.peek() {
+                                    Some(d @ b'0'..=b'7') => {
+                                        v = v * 8 + (d - b'0') as u32;
+                                        self.pos += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            out.push(v as u8);
+                        }
+                        other => out.push(other),
+                    }
+                }
+                _ => out.push(b),
+            }
+        }
+        out
+    }
+
+    fn read_hex(&mut self) -> Vec<u8> {
+        self.pos += 1;
+        let mut digits = Vec::new();
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            if b == b'>' {
+                break;
+            }
+            if let Some(d) = (b as char).to_digit(16) {
+                digits.push(d as u8);
+            }
+        }
+        if digits.len() % 2 == 1 {
+            digits.push(0);
+        }
+        digits.chunks(2).map(|p| p[0] << 4 | p[1]).collect()
+    }
+
+    /// 内联图片 `ID ... EI` 的二进制数据直接跳过
+    fn skip_inline_image(&mut self) {
+        while self.pos + 2 < self.data.len() {
+            if is_whitespace(self.data[self.pos])
+                && self.data[self.pos + 1..].starts_with(b"EI")
+                && self.data.get(self.pos + 3).is_none_or(|&b| !is_regular(b))
+            {
+                self.pos += 3;
+                return;
+            }
+            self.pos += 1;
+        }
+        self.pos = self.data.len();
+    }
+}
+
+fn decode_name(raw: &[u8]) -> String {
+    let mut out = Vec::with_capacity(raw.len());
+    let mut i = 0;
+    while i < raw.len() {
+        if raw[i] == b'#' && i + 2 < raw.len() {
+            if let Ok(v) = u8::from_str_radix(&String::from_utf8_lossy(&raw[i + 1..i + 3]), 16) {
+                out.push(v);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(raw[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// 页面字典与（可继承的）资源字典
+type Page<'a> = (&'a Dict, Option<&'a Dict>);
+
+struct PdfObject {
+    value: Obj,
+    stream: Option<Vec<u8>>,
+}
+
+struct Document {
+    objects: HashMap<u32, PdfObject>,
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    if from >= haystack.len() {
+        return None;
+    }
+    haystack[from..]
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|i| from + i)
+}
+
+/// `obj` 关键字前是否为 `num gen `，是则返回对象号
+fn object_number_before(data: &[u8], obj_pos: usize) -> Option<u32> {
+    let mut i = obj_pos;
+    let skip_ws_back = |i: &mut usize| {
+        while *i > 0 && is_whitespace(data[*i - 1]) {
+            *i -= 1;
+        }
+    };
+    let digits_back = |i: &mut usize| -> usize {
+        let end = *i;
+        while *i > 0 && data[*i - 1].is_ascii_digit() {
+            *i -= 1;
+        }
+        end - *i
+    };
+    skip_ws_back(&mut i);
+    if i == obj_pos || digits_back(&mut i) == 0 {
+        return None;
+    }
+    let gen_start = i;
+    skip_ws_back(&mut i);
+    if i == gen_start {
+        return None;
+    }
+    let num_end = i;
+    if digits_back(&mut i) == 0 || (i > 0 && is_regular(data[i - 1])) {
+        return None;
+    }
+    std::str::from_utf8(&data[i..num_end]).ok()?.parse().ok()
+}
+
+impl Document {
+    fn parse(data: &[u8]) -> Self {
+        let mut objects = HashMap::new();
+        let mut pos = 0;
+        while let Some(at) = find(data, b"obj", pos) {
+            pos = at + 3;
+            if data.get(at + 3).is_some_and(|&b| is_regular(b)) {
+                continue;
+            }
+            let Some(num) = object_number_before(data, at) else {
+                continue;
+            };
+            let mut lexer = Lexer::new(data, at + 3);
+            let Some(value) = lexer.next() else {
+                break;
+            };
+            lexer.skip_ws();
+            let mut stream = None;
+            if lexer.starts_with(b"stream") {
+                let mut start = lexer.pos + 6;
+                if data.get(start) == Some(&b'\r') {
+                    start += 1;
+                }
+                if data.get(start) == Some(&b'\n') {
+                    start += 1;
+                }
+                let declared = value
+                    .as_dict()
+                    .and_then(|d| d.get("Length"))
+                    .and_then(Obj::as_num)
+                    .and_then(|n| start.checked_add(n as usize))
+                    .filter(|&end| {
+                        end <= data.len()
+                            && find(data, b"endstream", end).is_some_and(|e| e - end < 16)
+                    });
+                let end = declared
+                    .or_else(|| find(data, b"endstream", start))
+                    .unwrap_or(data.len());
+                let mut body_end = end;
+                while body_end > start
+                    && matches!(data[body_end - 1], b'\r' | b'\n')
+                    && declared.is_none()
+                {
+                    body_end -= 1;
+                }
+                stream = data.get(start..body_end).map(<[u8]>::to_vec);
+                pos = end;
+            } else {
+                pos = pos.max(lexer.pos);
+            }
+            // 增量更新时后出现的同号对象覆盖前面的
+            objects.insert(num, PdfObject { value, stream });
+        }
+
+        let mut doc = Self { objects };
+        doc.expand_object_streams();
+        doc
+    }
+
+    /// PDF 1.5+ 把字体等对象压缩在对象流（/Type /ObjStm）里
+    fn expand_object_streams(&mut self) {
+        let mut found = Vec::new();
+        for obj in self.objects.values() {
+            let Some(dict) = obj.value.as_dict() else {
+                continue;
+            };
+            if dict.get("Type").and_then(Obj::as_name) != Some("ObjStm") {
+                continue;
+            }
+            let Some(data) = obj.stream.as_ref().and_then(|s| decode_stream(dict, s)) else {
+                continue;
+            };
+            let n = dict.get("N").and_then(Obj::as_num).unwrap_or(0.0) as usize;
+            let first = dict.get("First").and_then(Obj::as_num).unwrap_or(0.0) as usize;
+            let mut header = Lexer::new(&data, 0);
+            for _ in 0..n {
+                let (Some(Obj::Num(num)), Some(Obj::Num(offset))) = (header.next(), header.next())
+                else {
+                    break;
+                };
+                let Some(at) = first.checked_add(offset as usize) else {
+                    continue;
+                };
+                let mut lexer = Lexer::new(&data, at);
+                if let Some(value) = lexer.next() {
+                    found.push((num as u32, value));
+                }
+            }
+        }
+        for (num, value) in found {
+            self.objects.entry(num).or_insert(PdfObject {
+                value,
+                stream: None,
+            });
+        }
+    }
+
+    fn resolve<'b>(&'b self, obj: &'b Obj) -> &'b Obj {
+        let mut current = obj;
+        for _ in 0..MAX_DEPTH {
+            match current {
+                Obj::Ref(num) => match self.objects.get(num) {
+                    Some(o) => current = &o.value,
+                    None => return &Obj::Null,
+                },
+                _ => return current,
+            }
+        }
+        &Obj::Null
+    }
+
+    fn get<'b>(&'b self, dict: &'b HashMap<String, Obj>, key: &str) -> Option<&'b Obj> {
+        dict.get(key).map(|v| self.resolve(v))
+    }
+
+    /// 引用指向的流（解码后）
+    fn stream_of(&self, obj: &Obj) -> Option<Vec<u8>> {
+        let Obj::Ref(num) = obj else {
+            return None;
+        };
+        let target = self.objects.get(num)?;
+        if let Obj::Ref(_) = target.value {
+            return self.stream_of(&target.value);
+        }
+        decode_stream(target.value.as_dict()?, target.stream.as_ref()?)
+    }
+
+    /// 按页面树顺序列出页面及其（可继承的）资源字典
+    fn pages(&self) -> Vec<Page<'_>> {
+        let mut out = Vec::new();
+        let root = self.objects.values().find_map(|o| {
+            let dict = o.value.as_dict()?;
+            (dict.get("Type").and_then(Obj::as_name) == Some("Catalog"))
+                .then(|| dict.get("Pages"))
+                .flatten()
+        });
+        if let Some(root) = root {
+            let mut visited = HashSet::new();
+            self.walk_pages(root, None, &mut out, &mut visited, 0);
+        }
+        if out.is_empty() {
+            let mut nums: Vec<&u32> = self.objects.keys().collect();
+            nums.sort();
+            for num in nums {
+                let Some(dict) = self.objects[num].value.as_dict() else {
+                    continue;
+                };
+                if dict.get("Type").and_then(Obj::as_name) == Some("Page") {
+                    let resources = self.get(dict, "Resources").and_then(Obj::as_dict);
+                    out.push((dict, resources));
+                }
+            }
+        }
+        out.truncate(MAX_PAGES);
+        out
+    }
+
+    fn walk_pages<'b>(
+        &'b self,
+        node: &'b Obj,
+        inherited: Option<&'b HashMap<String, Obj>>,
+        out: &mut Vec<Page<'b>>,
+        visited: &mut HashSet<u32>,
+        depth: usize,
+    ) {
+        if depth > MAX_DEPTH || out.len() >= MAX_PAGES {
+            return;
+        }
+        if let Obj::Ref(num) = node {
+            if !visited.insert(*num) {
+                return;
+            }
+        }
+        let Some(dict) = self.resolve(node).as_dict() else {
+            return;
+        };
+        let resources = self
+            .get(dict, "Resources")
+            .and_then(Obj::as_dict)
+            .or(inherited);
+        match self.get(dict, "Kids") {
+            Some(Obj::Array(kids)) => {
+                for kid in kids {
+                    self.walk_pages(kid, resources, out, visited, depth + 1);
+                }
+            }
+            _ => out.push((dict, resources)),
+        }
+    }
+
+    fn page_content(&self, page: &HashMap<String, Obj>) -> Vec<u8> {
+        let mut content = Vec::new();
+        let refs: Vec<&Obj> = match page.get("Contents") {
+            Some(Obj::Array(items)) => items.iter().collect(),
+            Some(r @ Obj::Ref(_)) => match self.resolve(r) {
+                Obj::Array(items) => items.iter().collect(),
+                _ => vec![r],
+            },
+            _ => Vec::new(),
+        };
+        for r in refs {
+            if let Some(data) = self.stream_of(r) {
+                content.extend_from_slice(&data);
+                content.push(b'\n');
+            }
+        }
+        content
+    }
+
+    fn page_fonts(
+        &self,
+        resources: Option<&HashMap<String, Obj>>,
+        cache: &mut HashMap<u32, Rc<Font>>,
+    ) -> HashMap<String, Rc<Font>> {
+        let mut fonts = HashMap::new();
+        let Some(font_dict) = resources
+            .and_then(|r| self.get(r, "Font"))
+            .and_then(Obj::as_dict)
+        else {
+            return fonts;
+        };
+        for (name, font_ref) in font_dict {
+            let font = match font_ref {
+                Obj::Ref(num) => cache
+                    .entry(*num)
+                    .or_insert_with(|| Rc::new(self.load_font(font_ref)))
+                    .clone(),
+                _ => Rc::new(self.load_font(font_ref)),
+            };
+            fonts.insert(name.clone(), font);
+        }
+        fonts
+    }
+
+    fn load_font(&self, font: &Obj) -> Font {
+        let Some(dict) = self.resolve(font).as_dict() else {
+            return Font::default();
+        };
+        let two_byte = dict.get("Subtype").and_then(Obj::as_name) == Some("Type0");
+        let cmap = dict
+            .get("ToUnicode")
+            .and_then(|r| self.stream_of(r))
+            .map(|data| parse_cmap(&data, if two_byte { 2 } else { 1 }))
+            .filter(|c| !c.map.is_empty());
+        Font { two_byte, cmap }
+    }
+}
+
+fn decode_stream(dict: &HashMap<String, Obj>, raw: &[u8]) -> Option<Vec<u8>> {
+    let filters: Vec<&str> = match dict.get("Filter") {
+        None => Vec::new(),
+        Some(Obj::Name(n)) => vec![n.as_str()],
+        Some(Obj::Array(items)) => items.iter().filter_map(Obj::as_name).collect(),
+        Some(_) => return None,
+    };
+    let mut data = raw.to_vec();
+    for filter in filters {
+        if filter != "FlateDecode" && filter != "Fl" {
+            return None;
+        }
+        let mut out = Vec::new();
+        // 损坏的流尽量保留已解压的部分
+        let result = ZlibDecoder::new(&data[..])
+            .take(MAX_STREAM_BYTES)
+            .read_to_end(&mut out);
+        if result.is_err() && out.is_empty() {
+            return None;
+        }
+        data = out;
+    }
+    Some(data)
+}
+
+#[derive(Default)]
+struct CMap {
+    map: HashMap<u32, String>,
+    code_len: usize,
+}
+
+fn code_of(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0u32, |acc, &b| acc << 8 | b as u32)
+}
+
+fn utf16be(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn parse_cmap(data: &[u8], default_len: usize) -> CMap {
+    let mut cmap = CMap {
+        map: HashMap::new(),
+        code_len: 0,
+    };
+    let mut lexer = Lexer::new(data, 0);
+    let mut section = "";
+    let mut pending: Vec<Obj> = Vec::new();
+    while let Some(tok) = lexer.next() {
+        match tok {
+            Obj::Op(op) => {
+                match op.as_str() {
+                    "begincodespacerange" => section = "codespace",
+                    "beginbfchar" => section = "bfchar",
+                    "beginbfrange" => section = "bfrange",
+                    op if op.starts_with("end") => section = "",
+                    _ => {}
+                }
+                pending.clear();
+            }
+            tok if !section.is_empty() => {
+                pending.push(tok);
+                match (section, pending.as_slice()) {
+                    ("codespace", [Obj::Str(lo), _]) => {
+                        if cmap.code_len == 0 {
+                            cmap.code_len = lo.len();
+                        }
+                        pending.clear();
+                    }
+                    ("bfchar", [Obj::Str(src), dst]) => {
+                        if let Obj::Str(dst) = dst {
+                            cmap.map.insert(code_of(src), utf16be(dst));
+                        }
+                        pending.clear();
+                    }
+                    ("bfrange", [Obj::Str(lo), Obj::Str(hi), dst]) => {
+                        let (lo_code, hi_code) = (code_of(lo), code_of(hi));
+                        if hi_code >= lo_code && hi_code - lo_code <= 0xFFFF {
+                            for (i, code) in (lo_code..=hi_code).enumerate() {
+                                let text = match dst {
+                                    Obj::Str(base) if !base.is_empty() => {
+                                        let mut bytes = base.clone();
+                                        let last = bytes.len() - 1;
+                                        let low = bytes[last] as usize + i;
+                                        bytes[last] = low as u8;
+                                        if last > 0 {
+                                            bytes[last - 1] =
+                                                bytes[last - 1].wrapping_add((low >> 8) as u8);
+                                        }
+                                        utf16be(&bytes)
+                                    }
+                                    Obj::Array(items) => match items.get(i) {
+                                        Some(Obj::Str(s)) => utf16be(s),
+                                        _ => continue,
+                                    },
+                                    _ => continue,
+                                };
+                                cmap.map.insert(code, text);
+                            }
+                        }
+                        pending.clear();
+                    }
+                    (_, items) if items.len() >= 3 => pending.clear(),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    if cmap.code_len == 0 {
+        cmap.code_len = default_len;
+    }
+    cmap
+}
+
+#[derive(Default)]
+struct Font {
+    two_byte: bool,
+    cmap: Option<CMap>,
+}
+
+impl Font {
+    fn decode(&self, bytes: &[u8]) -> String {
+        match &self.cmap {
+            Some(cmap) => {
+                let len = cmap.code_len.clamp(1, 4);
+                bytes
+                    .chunks(len)
+                    .filter_map(|code| match cmap.map.get(&code_of(code)) {
+                        Some(s) => Some(s.clone()),
+                        None if len == 1 => Some(latin1(code[0]).to_string()),
+                        None => None,
+                    })
+                    .collect()
+            }
+            // 没有 ToUnicode 的 CID 字体无法还原文字
+            None if self.two_byte => String::new(),
+            None => bytes.iter().map(|&b| latin1(b)).collect(),
+        }
+    }
+}
+
+fn latin1(b: u8) -> char {
+    match b {
+        b'\t' | b'\n' | b'\r' | 0x20..=0x7E | 0xA0..=0xFF => b as char,
+        _ => ' ',
+    }
+}
+
+fn push_newline(out: &mut String) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+fn push_space(out: &mut String) {
+    if !out.is_empty() && !out.ends_with([' ', '\n']) {
+        out.push(' ');
+    }
+}
+
+/// 解释内容流中的文字操作符（Tf / Tj / TJ / ' / " 与换行相关的 Td / TD / T* / Tm）
+fn content_text(content: &[u8], fonts: &HashMap<String, Rc<Font>>, out: &mut String) {
+    let fallback = Font::default();
+    let mut font: &Font = &fallback;
+    let mut lexer = Lexer::new(content, 0);
+    let mut operands: Vec<Obj> = Vec::new();
+    let mut last_y: Option<f64> = None;
+    while let Some(tok) = lexer.next() {
+        let Obj::Op(op) = tok else {
+            operands.push(tok);
+            if operands.len() > 32 {
+                operands.remove(0);
+            }
+            continue;
+        };
+        let num = |i: usize| operands.get(i).and_then(Obj::as_num).unwrap_or(0.0);
+        match op.as_str() {
+            "Tf" => {
+                if let Some(Obj::Name(name)) = operands.first() {
+                    font = fonts.get(name).map_or(&fallback, |f| f.as_ref());
+                }
+            }
+            "Tj" | "'" | "\"" => {
+                if op != "Tj" {
+                    push_newline(out);
+                }
+                if let Some(Obj::Str(s)) = operands.last() {
+                    out.push_str(&font.decode(s));
+                }
+            }
+            "TJ" => {
+                if let Some(Obj::Array(items)) = operands.last() {
+                    for item in items {
+                        match item {
+                            Obj::Str(s) => out.push_str(&font.decode(s)),
+                            // 较大的负字距通常是词间空格
+                            Obj::Num(n) if *n < -200.0 => push_space(out),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            "Td" | "TD" => {
+                if num(1).abs() > 0.01 {
+                    push_newline(out);
+                } else if num(0) > 0.0 {
+                    push_space(out);
+                }
+            }
+            "T*" => push_newline(out),
+            "Tm" => {
+                let y = num(5);
+                if last_y.is_some_and(|last| (last - y).abs() > 0.01) {
+                    push_newline(out);
+                }
+                last_y = Some(y);
+            }
+            "ET" => push_space(out),
+            "ID" => lexer.skip_inline_image(),
+            _ => {}
+        }
+        operands.clear();
+    }
+}
+
+pub(super) fn extract_text(data: &[u8]) -> Result<String, String> {
+    let doc = Document::parse(data);
+    if find(data, b"/Encrypt", 0).is_some() {
+        return Err("文档已加密，无法提取文字".to_string());
+    }
+    let pages = doc.pages();
+    if pages.is_empty() {
+        return Err("未找到页面".to_string());
+    }
+
+    let mut out = String::new();
+    let mut font_cache = HashMap::new();
+    for (page, resources) in pages {
+        let fonts = doc.page_fonts(resources, &mut font_cache);
+        let mut text = String::new();
+        content_text(&doc.page_content(page), &fonts, &mut text);
+        let text: Vec<&str> = text
+            .lines()
+            .map(str::trim_end)
+            .filter(|l| !l.trim().is_empty())
+            .collect();
+        if !text.is_empty() {
+            out.push_str(&text.join("\n"));
+            out.push_str("\n\n");
+        }
+    }
+    if out.trim().is_empty() {
+        return Err("没有可提取的文字（可能是扫描件或图片型 PDF）".to_string());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn tokens(data: &[u8]) -> Vec<Obj> {
+        let mut lexer = Lexer::new(data, 0);
+        std::iter::from_fn(|| lexer.next()).collect()
+    }
+
+    /// 按对象列表拼出一份 PDF（不带 xref，解析器本来就不依赖它）
+    fn build_pdf(objects: &[(u32, &str, Option<&[u8]>)]) -> Vec<u8> {
+        let mut out = b"%PDF-1.4\n".to_vec();
+        for (num, dict, stream) in objects {
+            out.extend_from_slice(format!("{num} 0 obj\n{dict}\n").as_bytes());
+            if let Some(stream) = stream {
+                out.extend_from_slice(b"stream\n");
+                out.extend_from_slice(stream);
+                out.extend_from_slice(b"\nendstream\n");
+            }
+            out.extend_from_slice(b"endobj\n");
+        }
+        out.extend_from_slice(b"trailer\n<< /Root 1 0 R >>\n%%EOF\n");
+        out
+    }
+
+    fn single_page(content: &[u8], extra: &str) -> Vec<u8> {
+        let length = format!("<< /Length {} {extra} >>", content.len());
+        build_pdf(&[
+            (1, "<< /Type /Catalog /Pages 2 0 R >>", None),
+            (2, "<< /Type /Pages /Kids [3 0 R] /Count 1 >>", None),
+            (
+                3,
+                "<< /Type /Page /Parent 2 0 R /Contents 4 0 R /Resources << /Font << /F1 5 0 R >> >> >>",
+                None,
+            ),
+            (4, &length, Some(content)),
+            (5, "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>", None),
+        ])
+    }
+
+    #[test]
+    fn lexes_literal_strings() {
+        let got = tokens(
+            br"(a\(b\)c (nested) \n\101\7x\
+line)",
+        );
+        let [Obj::Str(s)] = got.as_slice() else {
+            panic!("unexpected tokens: {got:?}");
+        };
+        assert_eq!(s, b"a(b)c (nested) \nA\x07xline");
+    }
+
+    #[test]
+    fn lexes_hex_strings_and_names() {
+        let got = tokens(b"<48 65 6C6c6F> <414> /A#20B");
+        assert!(matches!(&got[0], Obj::Str(s) if s == b"Hello"));
+        // 奇数个十六进制位时末尾补 0
+        assert!(matches!(&got[1], Obj::Str(s) if s == &[0x41, 0x40]));
+        assert!(matches!(&got[2], Obj::Name(n) if n == "A B"));
+    }
+
+    #[test]
+    fn lexes_refs_dicts_and_arrays() {
+        let got = tokens(b"<< /Kids [3 0 R 4 0 R] /Count 2 /Scale -1.5 >> 12 Tf");
+        let Obj::Dict(dict) = &got[0] else {
+            panic!("expected dict: {got:?}");
+        };
+        assert!(matches!(
+            dict.get("Kids"),
+            Some(Obj::Array(kids)) if matches!(kids.as_slice(), [Obj::Ref(3), Obj::Ref(4)])
+        ));
+        assert_eq!(dict.get("Count").and_then(Obj::as_num), Some(2.0));
+        assert_eq!(dict.get("Scale").and_then(Obj::as_num), Some(-1.5));
+        assert!(matches!(&got[1], Obj::Num(n) if *n == 12.0));
+        assert!(matches!(&got[2], Obj::Op(op) if op == "Tf"));
+    }
+
+    #[test]
+    fn parses_cmap_bfchar_and_bfrange() {
+        let cmap = parse_cmap(
+            b"1 begincodespacerange <0000> <FFFF> endcodespacerange
+              2 beginbfchar <0001> <4F60> <0002> <597D> endbfchar
+              2 beginbfrange <0010> <0012> <0041> <0020> <0021> [<0031> <0032>] endbfrange",
+            1,
+        );
+        assert_eq!(cmap.code_len, 2);
+        assert_eq!(cmap.map[&1], "你");
+        assert_eq!(cmap.map[&2], "好");
+        assert_eq!(cmap.map[&0x12], "C");
+        assert_eq!(cmap.map[&0x21], "2");
+
+        let font = Font {
+            two_byte: true,
+            cmap: Some(cmap),
+        };
+        assert_eq!(font.decode(&[0, 1, 0, 2, 0, 0x10]), "你好A");
+    }
+
+    #[test]
+    fn extracts_minimal_pdf() {
+        let pdf = single_page(
+            b"BT /F1 12 Tf 72 720 Td (Hello) Tj 0 -14 Td [(Wor) -300 (ld)] TJ ET",
+            "",
+        );
+        assert_eq!(extract_text(&pdf).unwrap().trim(), "Hello\nWor ld");
+    }
+
+    #[test]
+    fn extracts_flate_stream() {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(b"BT /F1 12 Tf (Compressed) Tj ET")
+            .unwrap();
+        let pdf = single_page(&encoder.finish().unwrap(), "/Filter /FlateDecode");
+        assert_eq!(extract_text(&pdf).unwrap().trim(), "Compressed");
+    }
+
+    #[test]
+    fn rejects_empty_and_encrypted_documents() {
+        assert!(extract_text(b"").is_err());
+        assert!(extract_text(b"not a pdf at all").is_err());
+        let mut pdf = single_page(b"BT (x) Tj ET", "");
+        pdf.extend_from_slice(b"<< /Encrypt 9 0 R >>");
+        assert!(extract_text(&pdf).unwrap_err().contains("加密"));
+    }
+
+    #[test]
+    fn survives_malformed_lengths_and_offsets() {
+        // 超大或负的 /Length 不应溢出，退回按 endstream 定位
+        for length in ["99999999999999999999999", "-5", "1e400"] {
+            let content = b"BT (Still here) Tj ET";
+            let mut pdf = single_page(content, "");
+            let declared = format!("/Length {}", content.len());
+            let at = find(&pdf, declared.as_bytes(), 0).unwrap();
+            pdf.splice(
+                at..at + declared.len(),
+                format!("/Length {length}").into_bytes(),
+            );
+            assert_eq!(extract_text(&pdf).unwrap().trim(), "Still here");
+        }
+
+        // 对象流里越界的偏移量
+        let objstm = build_pdf(&[(
+            7,
+            "<< /Type /ObjStm /N 2 /First 10 /Length 40 >>",
+            Some(b"8 99999999999999999999999 9 0 << >>"),
+        )]);
+        let _ = extract_text(&objstm);
+
+        // 截断在流中间
+        let truncated = b"1 0 obj << /Length 100 >> stream\nBT (abc";
+        assert!(extract_text(truncated).is_err());
+        let _ = Document::parse(b"obj");
+        let _ = Document::parse(b"1 0 obj << /Length 5 >> stream");
+    }
+}
@@ -4,12 +4,14 @@ use std::sync::Arc;
 use crate::bot::runtime::BotRuntime;
 use crate::models::SharedState;
 
+use super::super::super::llm_cache::LlmResponseCache;
 use super::super::LlmForwardAudioFromUrlInput;
 use super::common::{
     call_chat_completions, download_binary_to_temp, get_record_base64_as_temp, log_llm_error,
     log_llm_len, read_file_as_data_url, reply_err, resolve_llm_config_by_name,
     send_llm_markdown_as_forward_image, SendForwardImageInput,
 };
+use super::degrade::transcribe_audio;
 
 fn is_http_url(url: &str) -> bool {
     let u = url.trim();
@@ -45,6 +47,7 @@ pub(in super::super::super) async fn process_llm_forward_audio_from_url(
     let system_prompt = input.system_prompt;
     let prompt = input.prompt;
     let title = input.title;
+    let tts = input.tts;
 
     // Prefer OneBot `get_record` when available (handles silk/amr and returns a standard format).
     let (guard, bin_meta) = if let Some(record_file) = input.record_file {
@@ -112,6 +115,32 @@ pub(in super::super::super) async fn process_llm_forward_audio_from_url(
         }
     };
 
+    let mut cache = LlmResponseCache::new(state, bot_id, &llm, "audio");
+    cache.add_text(system_prompt);
+    cache.add_text(prompt);
+    cache.add_text(if input.require_transcript {
+        "transcript"
+    } else {
+        ""
+    });
+    cache.add_file(&guard.path).await;
+    if let Some(cached) = cache.get().await {
+        send_llm_markdown_as_forward_image(
+            state,
+            runtime,
+            bot_id,
+            SendForwardImageInput {
+                user_id,
+                group_id,
+                title,
+                tts,
+                markdown: &cached,
+            },
+        )
+        .await;
+        return;
+    }
+
     let effective_prompt = if input.require_transcript {
         format!(
             "请先输出该音频的逐字转写内容（逐字、不翻译）。然后完成任务：{}",
@@ -133,18 +162,28 @@ pub(in super::super::super) async fn process_llm_forward_audio_from_url(
             .unwrap_or_else(|| "audio.wav".to_string())
     };
 
-    let data_url = match read_file_as_data_url(&guard.path, &mime_name).await {
-        Ok(v) => v,
-        Err(e) => {
-            reply_err(
-                runtime,
-                bot_id,
-                user_id,
-                group_id,
-                &format!("读取音频失败：{e}"),
-            )
-            .await;
-            return;
+    let audio_part = if llm.capabilities.audio {
+        match read_file_as_data_url(&guard.path, &mime_name).await {
+            Ok(url) => json!({"type": "image_url", "image_url": {"url": url}}),
+            Err(e) => {
+                reply_err(
+                    runtime,
+                    bot_id,
+                    user_id,
+                    group_id,
+                    &format!("读取音频失败：{e}"),
+                )
+                .await;
+                return;
+            }
+        }
+    } else {
+        match transcribe_audio(state, bot_id, &llm, &guard.path, &mime_name).await {
+            Ok(text) => json!({"type": "text", "text": text}),
+            Err(e) => {
+                reply_err(runtime, bot_id, user_id, group_id, &e).await;
+                return;
+            }
         }
     };
 
@@ -172,7 +211,7 @@ pub(in super::super::super) async fn process_llm_forward_audio_from_url(
             "truncated": bin_meta.truncated,
             "max_audio_seconds": input.max_audio_seconds,
             "require_transcript": input.require_transcript,
-            "input_mode": "image_url_data"
+            "input_mode": if llm.capabilities.audio { "image_url_data" } else { "transcript" }
         },
         "environment": {
             "bot_id": bot_id,
@@ -191,20 +230,13 @@ pub(in super::super::super) async fn process_llm_forward_audio_from_url(
             {"role": "system", "content": super::super::build_prompt_injection_guard()},
             {"role": "user", "content": [
                 {"type": "text", "text": format!("上下文信息（JSON）：\n{}", ctx_pretty)},
-                {"type": "image_url", "image_url": {"url": data_url}}
+                audio_part
             ]}
         ],
         "max_tokens": 4096
     });
 
-    let reply_content = match call_chat_completions(
-        &llm.base_url,
-        &llm.api_key,
-        &request_body,
-        llm.max_request_bytes,
-    )
-    .await
-    {
+    let reply_content = match call_chat_completions(&llm, &request_body).await {
         Ok(t) => t,
         Err(e) => {
             log_llm_error("语音分析", &e.to_string());
@@ -221,6 +253,7 @@ pub(in super::super::super) async fn process_llm_forward_audio_from_url(
     };
 
     log_llm_len("语音分析", reply_content.len());
+    cache.put(&reply_content).await;
     send_llm_markdown_as_forward_image(
         state,
         runtime,
@@ -229,6 +262,7 @@ pub(in super::super::super) async fn process_llm_forward_audio_from_url(
             user_id,
             group_id,
             title,
+            tts,
             markdown: &reply_content,
         },
     )
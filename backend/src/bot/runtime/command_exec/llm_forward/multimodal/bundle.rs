@@ -6,12 +6,14 @@ use tracing::warn;
 use crate::bot::runtime::BotRuntime;
 use crate::models::SharedState;
 
+use super::super::super::llm_cache::LlmResponseCache;
 use super::super::LlmForwardMediaBundleInput;
 use super::common::{
     call_chat_completions, download_binary_to_temp, get_record_base64_as_temp, log_llm_error,
     log_llm_len, nonce12, read_file_as_data_url, reply_err, resolve_llm_config_by_name,
     send_llm_markdown_as_forward_image, BinaryMeta, SendForwardImageInput,
 };
+use super::degrade::{image_part, transcribe_audio};
 use super::image::{prepare_image_data_url, PreparedImageMeta};
 
 fn best_file_name(meta: &BinaryMeta, fallback: &str) -> String {
@@ -43,6 +45,7 @@ pub(in super::super::super) async fn process_llm_forward_media_bundle(
     let system_prompt = input.system_prompt;
     let prompt = input.prompt;
     let title = input.title;
+    let tts = input.tts;
 
     if input.items.is_empty() && input.text.map(|s| s.trim().is_empty()).unwrap_or(true) {
         reply_err(
@@ -64,6 +67,12 @@ pub(in super::super::super) async fn process_llm_forward_media_bundle(
         }
     };
 
+    // 附件按下载后的内容哈希计入缓存键，命中时仍需下载，但省去模型调用
+    let mut cache = LlmResponseCache::new(state, bot_id, &llm, "bundle");
+    cache.add_text(system_prompt);
+    cache.add_text(prompt);
+    cache.add_text(input.text.unwrap_or(""));
+
     let mut media_meta: Vec<serde_json::Value> = Vec::new();
     let mut file_meta: Vec<serde_json::Value> = Vec::new();
     let mut attachment_parts: Vec<serde_json::Value> = Vec::new();
@@ -95,6 +104,9 @@ pub(in super::super::super) async fn process_llm_forward_media_bundle(
                     }
                 };
 
+                cache.add_text(&kind);
+                cache.add_file(&guard.path).await;
+
                 let (data_url, prepared_meta) = match prepare_image_data_url(
                     &guard.path,
                     input.image_max_width,
@@ -111,6 +123,14 @@ pub(in super::super::super) async fn process_llm_forward_media_bundle(
                         continue;
                     }
                 };
+                let part = match image_part(state, bot_id, &llm, &data_url).await {
+                    Ok(v) => v,
+                    Err(e) => {
+                        failures.push(json!({"index": idx1, "type": kind, "error": e}));
+                        drop(guard);
+                        continue;
+                    }
+                };
 
                 media_meta.push(json!({
                     "index": idx1,
@@ -119,19 +139,25 @@ pub(in super::super::super) async fn process_llm_forward_media_bundle(
                     "size_bytes": meta.size_bytes,
                     "truncated": meta.truncated,
                     "prepared": prepared_image_meta_json(&prepared_meta),
+                    "mode": if llm.capabilities.vision { "direct" } else { "transcribed_text" },
                 }));
 
                 attachment_parts.push(json!({
                     "type": "text",
                     "text": format!("附件 #{idx1}: 图片")
                 }));
-                attachment_parts.push(json!({
-                    "type": "image_url",
-                    "image_url": { "url": data_url }
-                }));
+                attachment_parts.push(part);
                 drop(guard);
             }
             "video" => {
+                if !llm.capabilities.video {
+                    failures.push(json!({
+                        "index": idx1,
+                        "type": kind,
+                        "error": "当前模型不支持视频输入（可改用视频分析的抽帧模式）"
+                    }));
+                    continue;
+                }
                 let Some(url) = item.url.as_deref() else {
                     failures.push(json!({"index": idx1, "type": kind, "error": "missing url"}));
                     continue;
@@ -152,6 +178,9 @@ pub(in super::super::super) async fn process_llm_forward_media_bundle(
                     }
                 };
 
+                cache.add_text(&kind);
+                cache.add_file(&guard.path).await;
+
                 let mime_name = best_file_name(&meta, "video.mp4");
                 let data_url = match read_file_as_data_url(&guard.path, &mime_name).await {
                     Ok(v) => v,
@@ -222,12 +251,24 @@ pub(in super::super::super) async fn process_llm_forward_media_bundle(
                     continue;
                 };
 
+                cache.add_text(&kind);
+                cache.add_file(&guard.path).await;
+
                 let mime_name = if record_file.is_some() {
                     "record.wav".to_string()
                 } else {
                     best_file_name(&meta, "record.wav")
                 };
-                let data_url = match read_file_as_data_url(&guard.path, &mime_name).await {
+                let part = if llm.capabilities.audio {
+                    read_file_as_data_url(&guard.path, &mime_name)
+                        .await
+                        .map(|url| json!({ "type": "image_url", "image_url": { "url": url } }))
+                } else {
+                    transcribe_audio(state, bot_id, &llm, &guard.path, &mime_name)
+                        .await
+                        .map(|text| json!({ "type": "text", "text": text }))
+                };
+                let part = match part {
                     Ok(v) => v,
                     Err(e) => {
                         failures.push(json!({"index": idx1, "type": kind, "error": e}));
@@ -242,17 +283,14 @@ pub(in super::super::super) async fn process_llm_forward_media_bundle(
                     "file_ext": meta.file_ext,
                     "size_bytes": meta.size_bytes,
                     "truncated": meta.truncated,
-                    "mode": "direct",
+                    "mode": if llm.capabilities.audio { "direct" } else { "transcript" },
                 }));
 
                 attachment_parts.push(json!({
                     "type": "text",
                     "text": format!("附件 #{idx1}: 语音/音频")
                 }));
-                attachment_parts.push(json!({
-                    "type": "image_url",
-                    "image_url": { "url": data_url }
-                }));
+                attachment_parts.push(part);
 
                 drop(guard);
             }
@@ -293,6 +331,28 @@ pub(in super::super::super) async fn process_llm_forward_media_bundle(
         return;
     }
 
+    cache.add_json(&json!(file_meta));
+    cache.add_json(&json!(failures
+        .iter()
+        .map(|f| f["index"].clone())
+        .collect::<Vec<_>>()));
+    if let Some(cached) = cache.get().await {
+        send_llm_markdown_as_forward_image(
+            state,
+            runtime,
+            bot_id,
+            SendForwardImageInput {
+                user_id,
+                group_id,
+                title,
+                tts,
+                markdown: &cached,
+            },
+        )
+        .await;
+        return;
+    }
+
     let group_id_opt = (group_id != 0).then_some(group_id);
     let bot_name = state
         .bots
@@ -377,14 +437,7 @@ pub(in super::super::super) async fn process_llm_forward_media_bundle(
     });
 
     let reply_content = loop {
-        match call_chat_completions(
-            &llm.base_url,
-            &llm.api_key,
-            &request_body,
-            llm.max_request_bytes,
-        )
-        .await
-        {
+        match call_chat_completions(&llm, &request_body).await {
             Ok(t) => break t,
             Err(e) => {
                 let retryable = matches!(&e, super::common::LlmCallError::RequestTooLarge { .. })
@@ -433,6 +486,9 @@ pub(in super::super::super) async fn process_llm_forward_media_bundle(
     };
 
     log_llm_len("多媒体分析", reply_content.len());
+    if dropped.is_empty() {
+        cache.put(&reply_content).await;
+    }
     send_llm_markdown_as_forward_image(
         state,
         runtime,
@@ -441,6 +497,7 @@ pub(in super::super::super) async fn process_llm_forward_media_bundle(
             user_id,
             group_id,
             title,
+            tts,
             markdown: &reply_content,
         },
     )
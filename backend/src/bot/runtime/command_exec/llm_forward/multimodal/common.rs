@@ -1,30 +1,77 @@
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
-use futures_util::StreamExt;
 use serde_json::json;
 use std::path::Path;
 use std::sync::Arc;
 use std::sync::OnceLock;
-use tokio::io::AsyncWriteExt;
 use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use tokio::time::sleep;
 use tracing::{error, info, warn};
 
 use crate::bot::runtime::api::send_reply;
+use crate::bot::runtime::llm_usage::{record_llm_usage, TokenUsage, UsageOrigin};
 use crate::bot::runtime::BotRuntime;
 use crate::models::SharedState;
 
+use super::super::super::media_cache::{download_cached, evict_url, fetch_cached};
 use super::super::download::TempFileGuard;
 
+mod embeddings;
 mod forward;
+mod images;
+mod provider;
+mod routing;
+mod stream;
+mod tools;
 
+pub(in super::super::super) use embeddings::call_embeddings;
 pub(in super::super) use forward::{send_llm_markdown_as_forward_image, SendForwardImageInput};
-
+pub(in super::super::super) use images::{call_image_generation, ImageGenerationRequest};
+use provider::{build_chat_request, normalize_chat_response};
+use routing::{resolve_target, LlmRoute, LlmRouteConfig, LlmTarget};
+pub(in super::super::super) use stream::call_chat_completions_stream;
+pub(in super::super::super) use tools::{
+    is_builtin_call, run_builtin_tool, web_search_tool, LlmTool, LlmToolCall, ToolLoopLimits,
+    BUILTIN_WEB_SEARCH,
+};
+
+/// 解析后的模型映射。顶层字段对应首选目标；`targets` 含首选目标与备用目标，
+/// 实际调用时按 `route` 的策略选择。
 #[derive(Debug, Clone)]
 pub(in super::super::super) struct LlmConfig {
     pub(in super::super::super) base_url: String,
     pub(in super::super::super) api_key: String,
     pub(in super::super::super) model_name: String,
     pub(in super::super::super) max_request_bytes: u64,
+    pub(in super::super::super) targets: Vec<LlmTarget>,
+    pub(in super::super::super) route: LlmRouteConfig,
+    /// 用量归属（bot / 触发者 / 来源插件）
+    pub(in super::super::super) origin: UsageOrigin,
+    pub(in super::super::super) capabilities: ModelCapabilities,
+}
+
+/// 模型映射的 `capabilities`：是否接受图片 / 音频 / 视频输入，未配置时视为支持。
+/// 不支持时由宿主降级（OCR + 识图模型描述、语音转写、视频抽帧）。
+#[derive(Debug, Clone, Copy)]
+pub(in super::super::super) struct ModelCapabilities {
+    pub(in super::super::super) vision: bool,
+    pub(in super::super::super) audio: bool,
+    pub(in super::super::super) video: bool,
+}
+
+impl ModelCapabilities {
+    fn from_mapping(model_config: &serde_json::Value) -> Self {
+        let caps = model_config.get("capabilities");
+        let flag = |key: &str| {
+            caps.and_then(|c| c.get(key))
+                .and_then(|v| v.as_bool())
+                .unwrap_or(true)
+        };
+        Self {
+            vision: flag("vision"),
+            audio: flag("audio"),
+            video: flag("video"),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -141,80 +188,16 @@ pub(in super::super) async fn download_binary_to_temp(
     let safe_name = file_name.unwrap_or("download.bin");
     let guard = TempFileGuard::new("download", Some(safe_name)).await?;
 
-    let timeout = std::time::Duration::from_millis(timeout_ms.clamp(1000, 120000));
     let max_bytes = max_bytes.clamp(10_000, 200_000_000);
-
-    let client = reqwest::Client::new();
-    let resp = client
-        .get(url)
-        .timeout(timeout)
-        .send()
-        .await
-        .map_err(|e| format!("Download failed: {e}"))?;
-
-    if !resp.status().is_success() {
-        return Err(format!("Download failed: HTTP {}", resp.status()));
-    }
-
-    let content_length = resp.content_length();
-    if let Some(len) = content_length {
-        if len > max_bytes {
-            return Err(format!(
-                "下载文件过大：Content-Length={} bytes，超过限制 {} bytes",
-                len, max_bytes
-            ));
-        }
-    }
-
-    let mut file = tokio::fs::File::create(&guard.path)
-        .await
-        .map_err(|e| format!("Create temp file failed: {e}"))?;
-
-    let mut downloaded: u64 = 0;
-    let mut truncated_by_bytes = false;
-
-    let mut stream = resp.bytes_stream();
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk.map_err(|e| format!("Read download stream failed: {e}"))?;
-        if downloaded >= max_bytes {
-            truncated_by_bytes = true;
-            break;
-        }
-
-        let remaining = (max_bytes - downloaded) as usize;
-        let slice: &[u8] = if chunk.len() > remaining {
-            truncated_by_bytes = true;
-            &chunk[..remaining]
-        } else {
-            &chunk
-        };
-
-        file.write_all(slice)
-            .await
-            .map_err(|e| format!("Write temp file failed: {e}"))?;
-        downloaded += slice.len() as u64;
-
-        if truncated_by_bytes {
-            break;
-        }
-    }
-
-    if let Some(len) = content_length {
-        if !truncated_by_bytes && downloaded < len {
-            return Err(format!(
-                "下载不完整：期望 {} bytes，实际 {} bytes（可能链接过期或网络中断）",
-                len, downloaded
-            ));
-        }
-    }
+    let downloaded = download_cached(url, &guard.path, timeout_ms, max_bytes, true).await?;
 
     let meta = BinaryMeta {
         file_name: file_name.map(|s| s.to_string()),
         file_ext: file_name
             .and_then(|s| Path::new(s).extension().and_then(|e| e.to_str()))
             .map(|s| s.to_lowercase()),
-        size_bytes: downloaded,
-        truncated: truncated_by_bytes,
+        size_bytes: downloaded.size_bytes,
+        truncated: downloaded.truncated,
     };
 
     Ok((guard, meta))
@@ -223,7 +206,6 @@ pub(in super::super) async fn download_binary_to_temp(
 /// Download a remote image and convert it into a compact `data:` URL for multimodal chat.
 /// This is used by plugin `callLlmChat` so text-only plugins can still attach images reliably
 /// without depending on the provider to fetch remote URLs.
-#[allow(dead_code)]
 pub(in super::super::super) async fn download_and_prepare_image_data_url(
     url: &str,
     timeout_ms: u64,
@@ -234,15 +216,19 @@ pub(in super::super::super) async fn download_and_prepare_image_data_url(
     max_output_bytes: u64,
 ) -> Result<String, String> {
     let (guard, _meta) = download_binary_to_temp(url, None, timeout_ms, max_bytes).await?;
-    let (data_url, _prepared) = super::image::prepare_image_data_url(
+    let prepared = super::image::prepare_image_data_url(
         &guard.path,
         max_width,
         max_height,
         jpeg_quality,
         max_output_bytes,
     )
-    .await?;
-    Ok(data_url)
+    .await;
+    if prepared.is_err() {
+        // 缓存的可能是不完整的图片
+        evict_url(url).await;
+    }
+    Ok(prepared?.0)
 }
 
 fn guess_file_name_from_url(url: &str) -> Option<String> {
@@ -358,57 +344,54 @@ pub(super) async fn get_record_base64_as_temp(
     file_name: Option<&str>,
     max_bytes: u64,
 ) -> Result<(TempFileGuard, BinaryMeta), String> {
-    let resp = runtime
-        .call_api(
-            bot_id,
-            "get_record",
-            json!({
-                "file": record_file,
-                "out_format": "wav",
-            }),
-        )
-        .await
-        .ok_or_else(|| "调用 get_record 失败：无响应".to_string())?;
+    let max_bytes = max_bytes.clamp(10_000, 200_000_000);
+    let safe_name = file_name.unwrap_or("record.wav");
+    let guard = TempFileGuard::new("record", Some(safe_name)).await?;
 
-    let data = resp.get("data").unwrap_or(&resp);
-    let base64_str = data
-        .get("base64")
-        .and_then(|v| v.as_str())
-        .ok_or_else(|| "get_record 失败：响应缺少 base64 字段".to_string())?;
+    // 同一条语音被多个插件处理时只调用一次 get_record
+    let key = format!("record:{bot_id}:{record_file}");
+    let size = fetch_cached(&key, &guard.path, || async {
+        let resp = runtime
+            .call_api(
+                bot_id,
+                "get_record",
+                json!({
+                    "file": record_file,
+                    "out_format": "wav",
+                }),
+            )
+            .await
+            .ok_or_else(|| "调用 get_record 失败：无响应".to_string())?;
 
-    let b64 = base64_str
-        .trim()
-        .strip_prefix("base64://")
-        .unwrap_or(base64_str);
-    let bytes = BASE64
-        .decode(b64.as_bytes())
-        .map_err(|e| format!("解析语音 base64 失败: {e}"))?;
+        let data = resp.get("data").unwrap_or(&resp);
+        let base64_str = data
+            .get("base64")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| "get_record 失败：响应缺少 base64 字段".to_string())?;
+
+        let b64 = base64_str
+            .trim()
+            .strip_prefix("base64://")
+            .unwrap_or(base64_str);
+        BASE64
+            .decode(b64.as_bytes())
+            .map_err(|e| format!("解析语音 base64 失败: {e}"))
+    })
+    .await?;
 
-    let max_bytes = max_bytes.clamp(10_000, 200_000_000);
-    if (bytes.len() as u64) > max_bytes {
+    if size > max_bytes {
         return Err(format!(
             "语音过大：{} bytes，超过限制 {} bytes",
-            bytes.len(),
-            max_bytes
+            size, max_bytes
         ));
     }
 
-    let safe_name = file_name.unwrap_or("record.wav");
-    let guard = TempFileGuard::new("record", Some(safe_name)).await?;
-
-    let mut file = tokio::fs::File::create(&guard.path)
-        .await
-        .map_err(|e| format!("Create temp file failed: {e}"))?;
-    file.write_all(&bytes)
-        .await
-        .map_err(|e| format!("Write temp file failed: {e}"))?;
-
     let meta = BinaryMeta {
         file_name: file_name.map(|s| s.to_string()),
         file_ext: file_name
             .and_then(|s| Path::new(s).extension().and_then(|e| e.to_str()))
             .map(|s| s.to_lowercase()),
-        size_bytes: bytes.len() as u64,
+        size_bytes: size,
         truncated: false,
     };
 
@@ -474,59 +457,169 @@ pub(in super::super::super) fn resolve_llm_config_by_name(
         .get(&target_model_name)
         .ok_or_else(|| format!("LLM 模块配置错误：未找到模型映射 '{}'", target_model_name))?;
 
-    let provider_id = model_config
-        .get("provider")
-        .and_then(|v| v.as_str())
-        .unwrap_or("");
-    let model_name = model_config
-        .get("model")
-        .and_then(|v| v.as_str())
-        .unwrap_or("");
+    let find_provider = |provider_id: &str| {
+        providers
+            .iter()
+            .find(|p| p.get("id").and_then(|v| v.as_str()) == Some(provider_id))
+            .ok_or_else(|| format!("LLM 模块配置错误：未找到提供商 '{}'", provider_id))
+    };
+    let target_fields = |v: &serde_json::Value| {
+        let provider_id = v.get("provider").and_then(|v| v.as_str()).unwrap_or("");
+        let model_name = v.get("model").and_then(|v| v.as_str()).unwrap_or("");
+        let weight = v.get("weight").and_then(|v| v.as_u64()).unwrap_or(1) as u32;
+        (provider_id.to_string(), model_name.to_string(), weight)
+    };
+
+    let (provider_id, model_name, weight) = target_fields(model_config);
     if provider_id.is_empty() || model_name.is_empty() {
         return Err(format!(
             "LLM 模块配置错误：模型 '{}' 字段不完整",
             target_model_name
         ));
     }
+    let primary = resolve_target(
+        find_provider(&provider_id)?,
+        &provider_id,
+        &model_name,
+        weight,
+        &llm_module.config,
+    )?;
+
+    // 备用目标配置有误时只跳过该目标，不影响首选目标
+    let mut targets = vec![primary.clone()];
+    for fallback in model_config
+        .get("fallbacks")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+    {
+        let (provider_id, model_name, weight) = target_fields(fallback);
+        if provider_id.is_empty() || model_name.is_empty() {
+            warn!(
+                "LLM 模型映射 '{}' 的备用目标字段不完整，已忽略",
+                target_model_name
+            );
+            continue;
+        }
+        match find_provider(&provider_id)
+            .and_then(|p| resolve_target(p, &provider_id, &model_name, weight, &llm_module.config))
+        {
+            Ok(target) => targets.push(target),
+            Err(e) => warn!(
+                "LLM 模型映射 '{}' 的备用目标已忽略: {}",
+                target_model_name, e
+            ),
+        }
+    }
 
-    let provider = providers
-        .iter()
-        .find(|p| p.get("id").and_then(|v| v.as_str()) == Some(provider_id))
-        .ok_or_else(|| format!("LLM 模块配置错误：未找到提供商 '{}'", provider_id))?;
+    Ok(LlmConfig {
+        base_url: primary.base_url,
+        api_key: primary.api_key,
+        model_name: primary.model_name,
+        max_request_bytes: primary.max_request_bytes,
+        targets,
+        route: LlmRouteConfig::new(&target_model_name, model_config, &llm_module.config),
+        origin: UsageOrigin::current(bot_id),
+        capabilities: ModelCapabilities::from_mapping(model_config),
+    })
+}
 
-    let api_key = provider
-        .get("api_key")
-        .and_then(|v| v.as_str())
-        .unwrap_or("")
-        .to_string();
-    if api_key.is_empty() {
-        return Err("LLM 模块配置错误：API Key 未设置".to_string());
+/// 记录一次响应的 token 用量（响应已统一为 OpenAI 格式）。
+fn record_response_usage(origin: &UsageOrigin, llm: &LlmTarget, v: &serde_json::Value) {
+    if let Some(usage) = v.get("usage").and_then(TokenUsage::from_openai) {
+        record_llm_usage(origin, &llm.provider_id, &llm.model_name, llm.price, usage);
     }
+}
 
-    let base_url = provider
-        .get("base_url")
-        .and_then(|v| v.as_str())
-        .unwrap_or("https://api.openai.com/v1")
-        .to_string();
-    let max_request_bytes = provider
-        .get("max_request_bytes")
-        .and_then(|v| v.as_u64())
-        .unwrap_or(4_000_000)
-        .clamp(200_000, 200_000_000);
+fn extract_content_from_choice(choice: &serde_json::Value) -> Option<String> {
+    let message = choice.get("message")?;
+    let content = message.get("content")?;
+    match content {
+        serde_json::Value::String(s) => Some(s.to_string()),
+        serde_json::Value::Array(parts) => {
+            let mut out = String::new();
+            for part in parts {
+                match part {
+                    serde_json::Value::String(s) => out.push_str(s),
+                    serde_json::Value::Object(map) => {
+                        if let Some(t) = map.get("text").and_then(|v| v.as_str()) {
+                            out.push_str(t);
+                        } else if let Some(t) = map.get("content").and_then(|v| v.as_str()) {
+                            out.push_str(t);
+                        } else if let Some(t) = map.get("value").and_then(|v| v.as_str()) {
+                            out.push_str(t);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if out.trim().is_empty() {
+                None
+            } else {
+                Some(out)
+            }
+        }
+        _ => None,
+    }
+}
 
-    Ok(LlmConfig {
-        base_url,
-        api_key,
-        model_name: model_name.to_string(),
-        max_request_bytes,
-    })
+fn extract_chat_content(v: &serde_json::Value) -> Option<String> {
+    // OpenAI Chat Completions format
+    if let Some(choice) = v.get("choices").and_then(|c| c.get(0)) {
+        if let Some(content) = extract_content_from_choice(choice) {
+            return Some(content);
+        }
+        // Legacy (some gateways still return choices[0].text)
+        if let Some(text) = choice.get("text").and_then(|t| t.as_str()) {
+            if !text.trim().is_empty() {
+                return Some(text.to_string());
+            }
+        }
+    }
+
+    // OpenAI Responses API-like format fallback
+    if let Some(output) = v.get("output").and_then(|o| o.get(0)) {
+        if let Some(content) = output
+            .get("content")
+            .and_then(|c| c.get(0))
+            .and_then(|c| c.get("text"))
+            .and_then(|t| t.as_str())
+        {
+            if !content.trim().is_empty() {
+                return Some(content.to_string());
+            }
+        }
+    }
+    if let Some(text) = v.get("output_text").and_then(|t| t.as_str()) {
+        if !text.trim().is_empty() {
+            return Some(text.to_string());
+        }
+    }
+
+    None
 }
 
+/// 调用 LLM 并返回回复文本。`request_body` 使用 OpenAI Chat Completions 格式，
+/// Anthropic / Gemini 提供商由 `provider` 负责翻译；映射有多个目标时按策略故障转移。
 pub(in super::super::super) async fn call_chat_completions(
-    base_url: &str,
-    api_key: &str,
+    llm: &LlmConfig,
+    request_body: &serde_json::Value,
+) -> Result<String, LlmCallError> {
+    let mut route = LlmRoute::new(&llm.route, &llm.targets);
+    while let Some(target) = route.next_target() {
+        let body = target.request_body(request_body);
+        let result = call_chat_completions_target(target, &body, &llm.origin).await;
+        if let Some(result) = route.settle(target, result) {
+            return result;
+        }
+    }
+    Err(LlmCallError::Transport("没有可用的 LLM 目标".to_string()))
+}
+
+async fn call_chat_completions_target(
+    llm: &LlmTarget,
     request_body: &serde_json::Value,
-    max_request_bytes: u64,
+    origin: &UsageOrigin,
 ) -> Result<String, LlmCallError> {
     fn mask_long_digits_for_log(input: &str) -> String {
         let mut out = String::with_capacity(input.len());
@@ -570,85 +663,15 @@ pub(in super::super::super) async fn call_chat_completions(
         masked.chars().take(max_len).collect::<String>() + "..."
     }
 
-    fn extract_content_from_choice(choice: &serde_json::Value) -> Option<String> {
-        let message = choice.get("message")?;
-        let content = message.get("content")?;
-        match content {
-            serde_json::Value::String(s) => Some(s.to_string()),
-            serde_json::Value::Array(parts) => {
-                let mut out = String::new();
-                for part in parts {
-                    match part {
-                        serde_json::Value::String(s) => out.push_str(s),
-                        serde_json::Value::Object(map) => {
-                            if let Some(t) = map.get("text").and_then(|v| v.as_str()) {
-                                out.push_str(t);
-                            } else if let Some(t) = map.get("content").and_then(|v| v.as_str()) {
-                                out.push_str(t);
-                            } else if let Some(t) = map.get("value").and_then(|v| v.as_str()) {
-                                out.push_str(t);
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-                if out.trim().is_empty() {
-                    None
-                } else {
-                    Some(out)
-                }
-            }
-            _ => None,
-        }
-    }
-
-    fn extract_chat_content(v: &serde_json::Value) -> Option<String> {
-        // OpenAI Chat Completions format
-        if let Some(choice) = v.get("choices").and_then(|c| c.get(0)) {
-            if let Some(content) = extract_content_from_choice(choice) {
-                return Some(content);
-            }
-            // Legacy (some gateways still return choices[0].text)
-            if let Some(text) = choice.get("text").and_then(|t| t.as_str()) {
-                if !text.trim().is_empty() {
-                    return Some(text.to_string());
-                }
-            }
-        }
-
-        // OpenAI Responses API-like format fallback
-        if let Some(output) = v.get("output").and_then(|o| o.get(0)) {
-            if let Some(content) = output
-                .get("content")
-                .and_then(|c| c.get(0))
-                .and_then(|c| c.get("text"))
-                .and_then(|t| t.as_str())
-            {
-                if !content.trim().is_empty() {
-                    return Some(content.to_string());
-                }
-            }
-        }
-        if let Some(text) = v.get("output_text").and_then(|t| t.as_str()) {
-            if !text.trim().is_empty() {
-                return Some(text.to_string());
-            }
-        }
-
-        None
-    }
-
     let client = reqwest::Client::new();
-    let url = format!("{}/chat/completions", base_url.trim_end_matches('/'));
-
-    let request_bytes = serde_json::to_vec(request_body)
-        .map_err(|e| LlmCallError::Parse(format!("序列化请求失败: {e}")))?;
-    if (request_bytes.len() as u64) > max_request_bytes {
-        return Err(LlmCallError::RequestTooLarge {
-            request_bytes: request_bytes.len() as u64,
-            limit_bytes: max_request_bytes,
-        });
-    }
+    let request = build_chat_request(
+        llm.kind,
+        &llm.base_url,
+        &llm.api_key,
+        request_body,
+        false,
+        llm.max_request_bytes,
+    )?;
 
     let timeout = std::time::Duration::from_secs(180);
     let max_attempts: usize = 3;
@@ -657,11 +680,8 @@ pub(in super::super::super) async fn call_chat_completions(
         let attempt_result: Result<(reqwest::StatusCode, reqwest::header::HeaderMap, String), LlmCallError> =
             {
                 let _permit = acquire_llm_http_permit().await?;
-                let resp = client
-                    .post(&url)
-                    .header("Authorization", format!("Bearer {}", api_key))
-                    .header("Content-Type", "application/json")
-                    .body(request_bytes.clone())
+                let resp = request
+                    .builder(&client)
                     .timeout(timeout)
                     .send()
                     .await
@@ -734,6 +754,8 @@ pub(in super::super::super) async fn call_chat_completions(
 
         let v: serde_json::Value =
             serde_json::from_str(&text).map_err(|e| LlmCallError::Parse(e.to_string()))?;
+        let v = normalize_chat_response(llm.kind, v);
+        record_response_usage(origin, llm, &v);
         let content = extract_chat_content(&v).ok_or(LlmCallError::MissingContent)?;
 
         // Debug suspiciously short outputs: print a compact preview of the raw response (redacted).
@@ -775,124 +797,48 @@ pub(in super::super::super) async fn call_chat_completions(
     Err(LlmCallError::Transport("LLM 重试失败".to_string()))
 }
 
-/// Tavily 搜索工具定义
-fn tavily_tool_definition() -> serde_json::Value {
-    json!({
-        "type": "function",
-        "function": {
-            "name": "tavily_search",
-            "description": "Search the web for current information using Tavily. Use this when you need to find up-to-date information, facts, news, or any information that might have changed after your knowledge cutoff.",
-            "parameters": {
-                "type": "object",
-                "properties": {
-                    "query": {
-                        "type": "string",
-                        "description": "The search query to look up on the web"
-                    }
-                },
-                "required": ["query"]
-            }
-        }
-    })
-}
-
-/// 调用 Tavily 搜索 API
-async fn call_tavily_search(tavily_api_key: &str, query: &str) -> Result<String, String> {
-    let client = reqwest::Client::new();
-    let resp = client
-        .post("https://api.tavily.com/search")
-        .header("Content-Type", "application/json")
-        .json(&json!({
-            "api_key": tavily_api_key,
-            "query": query,
-            "search_depth": "basic",
-            "include_answer": true,
-            "include_raw_content": false,
-            "max_results": 5
-        }))
-        .timeout(std::time::Duration::from_secs(30))
-        .send()
-        .await
-        .map_err(|e| format!("Tavily 请求失败: {e}"))?;
-
-    let status = resp.status();
-    let text = resp
-        .text()
-        .await
-        .map_err(|e| format!("读取 Tavily 响应失败: {e}"))?;
-
-    if !status.is_success() {
-        return Err(format!("Tavily API 错误 (HTTP {}): {}", status, text));
-    }
-
-    let v: serde_json::Value =
-        serde_json::from_str(&text).map_err(|e| format!("解析 Tavily 响应失败: {e}"))?;
-
-    // 构建搜索结果摘要
-    let mut result = String::new();
-
-    // 如果有 answer，优先使用
-    if let Some(answer) = v.get("answer").and_then(|a| a.as_str()) {
-        result.push_str("## 搜索摘要\n");
-        result.push_str(answer);
-        result.push_str("\n\n");
-    }
-
-    // 添加搜索结果
-    if let Some(results) = v.get("results").and_then(|r| r.as_array()) {
-        result.push_str("## 搜索结果\n\n");
-        for (i, item) in results.iter().take(5).enumerate() {
-            let title = item
-                .get("title")
-                .and_then(|t| t.as_str())
-                .unwrap_or("无标题");
-            let url = item.get("url").and_then(|u| u.as_str()).unwrap_or("");
-            let content = item
-                .get("content")
-                .and_then(|c| c.as_str())
-                .unwrap_or("无内容");
-
-            result.push_str(&format!("### {}. {}\n", i + 1, title));
-            result.push_str(&format!("链接: {}\n", url));
-            result.push_str(&format!("{}\n\n", content));
-        }
-    }
-
-    if result.is_empty() {
-        result = "未找到相关搜索结果".to_string();
-    }
-
-    Ok(result)
-}
-
 /// 调用支持 Tavily 搜索的 LLM
-/// 如果提供了 tavily_api_key，则使用函数调用模式
+/// 如果提供了 tavily_api_key，则把 Tavily 作为内置工具走工具调用循环
 /// 否则回退到简单的搜索参数模式
 pub(in super::super::super) async fn call_chat_completions_with_tavily(
-    base_url: &str,
-    api_key: &str,
+    llm: &LlmConfig,
     request_body: &serde_json::Value,
-    max_request_bytes: u64,
     enable_search: bool,
     tavily_api_key: Option<&str>,
 ) -> Result<String, LlmCallError> {
-    let client = reqwest::Client::new();
-    let url = format!("{}/chat/completions", base_url.trim_end_matches('/'));
-
-    // 如果有 Tavily API key 且启用搜索，使用函数调用模式
-    if enable_search && tavily_api_key.is_some() && !tavily_api_key.unwrap().is_empty() {
-        return call_with_tavily_tool_loop(
-            &client,
-            &url,
-            api_key,
+    if let Some(key) = tavily_api_key.filter(|k| enable_search && !k.is_empty()) {
+        return call_chat_completions_with_tools(
+            llm,
             request_body,
-            max_request_bytes,
-            tavily_api_key.unwrap(),
+            &[web_search_tool()],
+            &ToolLoopLimits::default(),
+            |call| async move { run_builtin_tool(&call, Some(key)).await },
         )
         .await;
     }
 
-    // 否则使用简单搜索参数模式
+    let mut route = LlmRoute::new(&llm.route, &llm.targets);
+    while let Some(target) = route.next_target() {
+        let body = target.request_body(request_body);
+        let result =
+            call_chat_completions_with_search_params(target, &body, enable_search, &llm.origin)
+                .await;
+        if let Some(result) = route.settle(target, result) {
+            return result;
+        }
+    }
+    Err(LlmCallError::Transport("没有可用的 LLM 目标".to_string()))
+}
+
+async fn call_chat_completions_with_search_params(
+    llm: &LlmTarget,
+    request_body: &serde_json::Value,
+    enable_search: bool,
+    origin: &UsageOrigin,
+) -> Result<String, LlmCallError> {
+    let client = reqwest::Client::new();
+
+    // 没有 Tavily 时使用简单搜索参数模式（只对 OpenAI 兼容网关有意义，原生适配会丢弃这些字段）
     let mut body = request_body.clone();
     if enable_search {
         if let Some(obj) = body.as_object_mut() {
@@ -902,14 +848,14 @@ pub(in super::super::super) async fn call_chat_completions_with_tavily(
         }
     }
 
-    let request_bytes = serde_json::to_vec(&body)
-        .map_err(|e| LlmCallError::Parse(format!("序列化请求失败: {e}")))?;
-    if (request_bytes.len() as u64) > max_request_bytes {
-        return Err(LlmCallError::RequestTooLarge {
-            request_bytes: request_bytes.len() as u64,
-            limit_bytes: max_request_bytes,
-        });
-    }
+    let request = build_chat_request(
+        llm.kind,
+        &llm.base_url,
+        &llm.api_key,
+        &body,
+        false,
+        llm.max_request_bytes,
+    )?;
 
     let timeout = std::time::Duration::from_secs(300);
     let max_attempts: usize = 3;
@@ -918,11 +864,8 @@ pub(in super::super::super) async fn call_chat_completions_with_tavily(
         let attempt_result: Result<(reqwest::StatusCode, reqwest::header::HeaderMap, String), LlmCallError> =
             {
                 let _permit = acquire_llm_http_permit().await?;
-                let resp = client
-                    .post(&url)
-                    .header("Authorization", format!("Bearer {}", api_key))
-                    .header("Content-Type", "application/json")
-                    .body(request_bytes.clone())
+                let resp = request
+                    .builder(&client)
                     .timeout(timeout)
                     .send()
                     .await
@@ -995,6 +938,8 @@ pub(in super::super::super) async fn call_chat_completions_with_tavily(
 
         let v: serde_json::Value =
             serde_json::from_str(&text).map_err(|e| LlmCallError::Parse(e.to_string()))?;
+        let v = normalize_chat_response(llm.kind, v);
+        record_response_usage(origin, llm, &v);
         return v
             .get("choices")
             .and_then(|c| c.get(0))
@@ -1008,16 +953,59 @@ pub(in super::super::super) async fn call_chat_completions_with_tavily(
     Err(LlmCallError::Transport("LLM 重试失败".to_string()))
 }
 
-/// 使用 Tavily 工具的循环调用
-/// 处理 LLM 的 tool_calls，调用 Tavily，然后继续对话直到获得最终回复
-async fn call_with_tavily_tool_loop(
-    client: &reqwest::Client,
-    url: &str,
-    api_key: &str,
+/// 带工具调用的 LLM 请求：模型返回 tool_calls 时交给 `handler` 执行（受 `limits` 的单次超时约束），
+/// 把结果追加到对话后继续，直到模型给出最终回复；轮数用完后最后一轮禁止再调用工具。
+/// 已经执行过工具后不再切换备用目标，避免有副作用的工具被重复执行。
+pub(in super::super::super) async fn call_chat_completions_with_tools<H, Fut>(
+    llm: &LlmConfig,
     request_body: &serde_json::Value,
-    max_request_bytes: u64,
-    tavily_api_key: &str,
-) -> Result<String, LlmCallError> {
+    tools: &[LlmTool],
+    limits: &ToolLoopLimits,
+    handler: H,
+) -> Result<String, LlmCallError>
+where
+    H: Fn(LlmToolCall) -> Fut,
+    Fut: std::future::Future<Output = String>,
+{
+    let mut route = LlmRoute::new(&llm.route, &llm.targets);
+    while let Some(target) = route.next_target() {
+        let body = target.request_body(request_body);
+        let mut tools_called = false;
+        let result = call_with_tool_loop(
+            target,
+            &body,
+            tools,
+            limits,
+            &handler,
+            &llm.origin,
+            &mut tools_called,
+        )
+        .await;
+        if tools_called {
+            route.stop();
+        }
+        if let Some(result) = route.settle(target, result) {
+            return result;
+        }
+    }
+    Err(LlmCallError::Transport("没有可用的 LLM 目标".to_string()))
+}
+
+async fn call_with_tool_loop<H, Fut>(
+    llm: &LlmTarget,
+    request_body: &serde_json::Value,
+    tools: &[LlmTool],
+    limits: &ToolLoopLimits,
+    handler: &H,
+    origin: &UsageOrigin,
+    tools_called: &mut bool,
+) -> Result<String, LlmCallError>
+where
+    H: Fn(LlmToolCall) -> Fut,
+    Fut: std::future::Future<Output = String>,
+{
+    let client = reqwest::Client::new();
+    let definitions: Vec<serde_json::Value> = tools.iter().map(|t| t.definition()).collect();
     let mut messages = request_body
         .get("messages")
         .and_then(|m| m.as_array())
@@ -1031,26 +1019,26 @@ async fn call_with_tavily_tool_loop(
 
     let max_tokens = request_body.get("max_tokens").cloned();
 
-    // 最多循环 5 次（防止无限循环）
-    for _ in 0..5 {
+    for round in 0..=limits.max_rounds {
+        let tool_choice = limits.tool_choice_for_round(round);
         let mut body = json!({
             "model": model,
             "messages": messages,
-            "tools": [tavily_tool_definition()],
-            "tool_choice": "auto"
+            "tools": definitions,
+            "tool_choice": tool_choice
         });
         if let Some(max_tok) = &max_tokens {
             body["max_tokens"] = max_tok.clone();
         }
 
-        let request_bytes = serde_json::to_vec(&body)
-            .map_err(|e| LlmCallError::Parse(format!("序列化请求失败: {e}")))?;
-        if (request_bytes.len() as u64) > max_request_bytes {
-            return Err(LlmCallError::RequestTooLarge {
-                request_bytes: request_bytes.len() as u64,
-                limit_bytes: max_request_bytes,
-            });
-        }
+        let request = build_chat_request(
+            llm.kind,
+            &llm.base_url,
+            &llm.api_key,
+            &body,
+            false,
+            llm.max_request_bytes,
+        )?;
 
         let timeout = std::time::Duration::from_secs(180);
         let max_attempts: usize = 3;
@@ -1060,11 +1048,8 @@ async fn call_with_tavily_tool_loop(
             let attempt_result: Result<(reqwest::StatusCode, reqwest::header::HeaderMap, String), LlmCallError> =
                 {
                     let _permit = acquire_llm_http_permit().await?;
-                    let resp = client
-                        .post(url)
-                        .header("Authorization", format!("Bearer {}", api_key))
-                        .header("Content-Type", "application/json")
-                        .body(request_bytes.clone())
+                    let resp = request
+                        .builder(&client)
                         .timeout(timeout)
                         .send()
                         .await
@@ -1143,6 +1128,8 @@ async fn call_with_tavily_tool_loop(
 
         let v: serde_json::Value =
             serde_json::from_str(&text).map_err(|e| LlmCallError::Parse(e.to_string()))?;
+        let v = normalize_chat_response(llm.kind, v);
+        record_response_usage(origin, llm, &v);
 
         let choice = v
             .get("choices")
@@ -1161,49 +1148,33 @@ async fn call_with_tavily_tool_loop(
                 // 添加助手消息（包含 tool_calls）
                 messages.push(message.clone());
 
-                // 处理每个 tool call
+                // 处理每个 tool call；每个调用都要有对应的 tool 消息，否则下一轮请求会被拒绝
                 for tool_call in tool_calls {
-                    let tool_call_id = tool_call
-                        .get("id")
-                        .and_then(|i| i.as_str())
-                        .unwrap_or("unknown");
-                    let function = tool_call.get("function");
-                    let function_name = function
-                        .and_then(|f| f.get("name"))
-                        .and_then(|n| n.as_str())
-                        .unwrap_or("");
-
-                    if function_name == "tavily_search" {
-                        let arguments = function
-                            .and_then(|f| f.get("arguments"))
-                            .and_then(|a| a.as_str())
-                            .unwrap_or("{}");
-
-                        let args: serde_json::Value =
-                            serde_json::from_str(arguments).unwrap_or(json!({}));
-                        let query = args.get("query").and_then(|q| q.as_str()).unwrap_or("");
-
-                        info!("Tavily 搜索: {}", query);
-
-                        // 调用 Tavily
-                        let search_result = match call_tavily_search(tavily_api_key, query).await {
-                            Ok(result) => result,
-                            Err(e) => {
-                                error!("Tavily 搜索失败: {}", e);
-                                format!("搜索失败: {}", e)
+                    let call = LlmToolCall::from_openai(tool_call);
+                    let call_id = call.id.clone();
+                    let content = if tools.iter().any(|t| t.name == call.name) {
+                        *tools_called = true;
+                        let name = call.name.clone();
+                        match tokio::time::timeout(limits.call_timeout, handler(call)).await {
+                            Ok(content) => content,
+                            Err(_) => {
+                                warn!("LLM 工具 {} 执行超时", name);
+                                format!("工具 {} 执行超时", name)
                             }
-                        };
-
-                        // 添加工具响应消息
-                        messages.push(json!({
-                            "role": "tool",
-                            "tool_call_id": tool_call_id,
-                            "content": search_result
-                        }));
-                    }
+                        }
+                    } else {
+                        format!("未知工具: {}", call.name)
+                    };
+
+                    // 添加工具响应消息
+                    messages.push(json!({
+                        "role": "tool",
+                        "tool_call_id": call_id,
+                        "content": content
+                    }));
                 }
 
-                // 继续循环，让 LLM 处理搜索结果
+                // 继续循环，让 LLM 处理工具结果
                 continue;
             }
         }
@@ -1250,7 +1221,7 @@ async fn call_with_tavily_tool_loop(
     Err(LlmCallError::MissingContent)
 }
 
-fn guess_transcription_mime(file_name: &str) -> &'static str {
+pub(super) fn guess_transcription_mime(file_name: &str) -> &'static str {
     let ext = Path::new(file_name)
         .extension()
         .and_then(|e| e.to_str())
@@ -1270,12 +1241,14 @@ fn guess_transcription_mime(file_name: &str) -> &'static str {
     }
 }
 
+/// 调用 OpenAI 兼容的 `/audio/transcriptions`；`language` 为 None 时由服务端自动识别。
 pub(super) async fn call_audio_transcription(
     base_url: &str,
     api_key: &str,
     model: &str,
     file_path: &Path,
     file_name: &str,
+    language: Option<&str>,
 ) -> Result<String, String> {
     let bytes = tokio::fs::read(file_path)
         .await
@@ -1286,10 +1259,12 @@ pub(super) async fn call_audio_transcription(
         .mime_str(guess_transcription_mime(file_name))
         .map_err(|e| format!("构造音频 multipart 失败: {e}"))?;
 
-    let form = reqwest::multipart::Form::new()
+    let mut form = reqwest::multipart::Form::new()
         .text("model", model.to_string())
-        .text("language", "zh".to_string())
         .part("file", part);
+    if let Some(language) = language {
+        form = form.text("language", language.to_string());
+    }
 
     let client = reqwest::Client::new();
     let url = format!("{}/audio/transcriptions", base_url.trim_end_matches('/'));
@@ -1329,12 +1304,74 @@ pub(super) async fn call_audio_transcription(
 
     let v: serde_json::Value =
         serde_json::from_str(&text).map_err(|e| format!("解析转写响应失败: {e}"))?;
+    transcription_text(&v).ok_or_else(|| "音频转写失败：无法获取文本".to_string())
+}
+
+/// 调用 OpenAI 兼容的 `/audio/speech`，返回 mp3 音频（本地服务时 `url` 为完整地址）。
+pub(super) async fn call_audio_speech(
+    url: &str,
+    api_key: &str,
+    model: &str,
+    input: &str,
+    voice: &str,
+) -> Result<Vec<u8>, String> {
+    let body = json!({
+        "model": model,
+        "input": input,
+        "voice": voice,
+        "response_format": "mp3",
+    });
+    let client = reqwest::Client::new();
+    let (status, bytes) = {
+        let _permit = acquire_llm_http_permit()
+            .await
+            .map_err(|e| format!("语音合成并发控制失败: {e}"))?;
+        let mut req = client
+            .post(url)
+            .json(&body)
+            .timeout(std::time::Duration::from_secs(120));
+        if !api_key.is_empty() {
+            req = req.header("Authorization", format!("Bearer {}", api_key));
+        }
+        let resp = req
+            .send()
+            .await
+            .map_err(|e| format!("语音合成请求失败: {}", e))?;
+
+        let status = resp.status();
+        let bytes = resp
+            .bytes()
+            .await
+            .map_err(|e| format!("读取语音合成响应失败: {}", e))?;
+        (status, bytes)
+    };
+
+    if !status.is_success() {
+        let text = String::from_utf8_lossy(&bytes);
+        let msg = serde_json::from_str::<serde_json::Value>(&text)
+            .ok()
+            .and_then(|v| {
+                v.get("error")
+                    .and_then(|e| e.get("message").or(Some(e)))
+                    .cloned()
+                    .or_else(|| v.get("message").cloned())
+            })
+            .and_then(|v| v.as_str().map(|s| s.to_string()))
+            .unwrap_or_else(|| text.chars().take(400).collect());
+        return Err(format!("语音合成失败 (HTTP {}): {}", status, msg));
+    }
+    if bytes.is_empty() {
+        return Err("语音合成失败：返回的音频为空".to_string());
+    }
+    Ok(bytes.to_vec())
+}
+
+pub(super) fn transcription_text(v: &serde_json::Value) -> Option<String> {
     v.get("text")
         .or_else(|| v.get("transcript"))
         .or_else(|| v.get("data").and_then(|d| d.get("text")))
         .and_then(|v| v.as_str())
         .map(|s| s.to_string())
-        .ok_or_else(|| "音频转写失败：无法获取文本".to_string())
 }
 
 pub(super) fn nonce12() -> String {
@@ -1371,3 +1408,23 @@ pub(in super::super) fn log_llm_len(kind: &str, len: usize) {
 pub(in super::super) fn log_llm_error(kind: &str, err: &str) {
     error!("LLM {}失败: {}", kind, err);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn capabilities_default_to_supported() {
+        let caps = ModelCapabilities::from_mapping(&json!({ "model": "m" }));
+        assert!(caps.vision && caps.audio && caps.video);
+
+        let caps = ModelCapabilities::from_mapping(&json!({
+            "capabilities": { "vision": false, "video": "no" }
+        }));
+        assert!(!caps.vision);
+        assert!(caps.audio);
+        // 非布尔值按未配置处理
+        assert!(caps.video);
+    }
+}
@@ -0,0 +1,131 @@
+//! OpenAI 兼容的 `/embeddings` 调用（知识库检索用），与对话调用共用模型映射的路由与熔断。
+
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::provider::ProviderKind;
+use super::routing::{LlmRoute, LlmTarget};
+use super::{acquire_llm_http_permit, record_response_usage, LlmCallError, LlmConfig};
+use crate::bot::runtime::llm_usage::UsageOrigin;
+
+/// 每次请求最多携带的文本条数
+const EMBEDDING_BATCH: usize = 64;
+const EMBEDDING_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 计算一组文本的向量，顺序与输入一致。
+pub(in super::super::super::super) async fn call_embeddings(
+    llm: &LlmConfig,
+    inputs: &[String],
+) -> Result<Vec<Vec<f32>>, LlmCallError> {
+    let mut out = Vec::with_capacity(inputs.len());
+    for batch in inputs.chunks(EMBEDDING_BATCH) {
+        let mut route = LlmRoute::new(&llm.route, &llm.targets);
+        let mut vectors = None;
+        while let Some(target) = route.next_target() {
+            let result = call_embeddings_target(target, batch, &llm.origin).await;
+            if let Some(result) = route.settle(target, result) {
+                vectors = Some(result?);
+                break;
+            }
+        }
+        let vectors =
+            vectors.ok_or_else(|| LlmCallError::Transport("没有可用的 LLM 目标".to_string()))?;
+        out.extend(vectors);
+    }
+    Ok(out)
+}
+
+async fn call_embeddings_target(
+    llm: &LlmTarget,
+    inputs: &[String],
+    origin: &UsageOrigin,
+) -> Result<Vec<Vec<f32>>, LlmCallError> {
+    if llm.kind != ProviderKind::OpenAi {
+        return Err(LlmCallError::Http {
+            status: 400,
+            message: format!(
+                "提供商 {} 不是 OpenAI 兼容接口，无法计算向量",
+                llm.provider_id
+            ),
+        });
+    }
+
+    let url = format!("{}/embeddings", llm.base_url.trim_end_matches('/'));
+    let body = json!({ "model": llm.model_name, "input": inputs });
+    let (status, text) = {
+        let _permit = acquire_llm_http_permit().await?;
+        let resp = reqwest::Client::new()
+            .post(&url)
+            .bearer_auth(&llm.api_key)
+            .json(&body)
+            .timeout(EMBEDDING_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| LlmCallError::Transport(e.to_string()))?;
+        let status = resp.status();
+        let text = resp
+            .text()
+            .await
+            .map_err(|e| LlmCallError::Decode(e.to_string()))?;
+        (status, text)
+    };
+
+    let v: Value = match serde_json::from_str(&text) {
+        Ok(v) => v,
+        Err(_) if !status.is_success() => {
+            return Err(LlmCallError::Http {
+                status: status.as_u16(),
+                message: text.chars().take(400).collect(),
+            })
+        }
+        Err(e) => return Err(LlmCallError::Parse(e.to_string())),
+    };
+    if !status.is_success() {
+        let message = v
+            .pointer("/error/message")
+            .or_else(|| v.get("error"))
+            .or_else(|| v.get("message"))
+            .and_then(|m| m.as_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| text.chars().take(400).collect());
+        return Err(LlmCallError::Http {
+            status: status.as_u16(),
+            message,
+        });
+    }
+    record_response_usage(origin, llm, &v);
+
+    let mut items: Vec<(usize, Vec<f32>)> = v
+        .get("data")
+        .and_then(|d| d.as_array())
+        .ok_or(LlmCallError::MissingContent)?
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let index = item
+                .get("index")
+                .and_then(|n| n.as_u64())
+                .map_or(i, |n| n as usize);
+            let vector = item
+                .get("embedding")
+                .and_then(|e| e.as_array())
+                .map(|e| {
+                    e.iter()
+                        .filter_map(|x| x.as_f64())
+                        .map(|x| x as f32)
+                        .collect()
+                })
+                .unwrap_or_default();
+            (index, vector)
+        })
+        .collect();
+    items.sort_by_key(|(index, _)| *index);
+    if items.len() != inputs.len() || items.iter().any(|(_, v)| v.is_empty()) {
+        return Err(LlmCallError::Parse(format!(
+            "向量数量不符：请求 {} 条，返回 {} 条",
+            inputs.len(),
+            items.len()
+        )));
+    }
+    Ok(items.into_iter().map(|(_, v)| v).collect())
+}
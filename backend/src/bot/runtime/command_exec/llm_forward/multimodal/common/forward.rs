@@ -1,16 +1,21 @@
 use crate::bot::runtime::api::{send_api, send_reply};
 use crate::bot::runtime::BotRuntime;
 use crate::models::SharedState;
+use crate::plugin::runtime::ForwardTts;
 use crate::render_image::render_markdown_image;
 use serde_json::json;
 use std::sync::Arc;
-use tracing::error;
+use tracing::{error, warn};
+
+use super::super::{synthesize_voice, VoicePlatform};
 
 pub(in super::super::super) struct SendForwardImageInput<'a> {
     pub(in super::super::super) user_id: u64,
     pub(in super::super::super) group_id: u64,
     pub(in super::super::super) title: &'a str,
     pub(in super::super::super) markdown: &'a str,
+    /// 非空时合并转发之后再把结果朗读为语音发送
+    pub(in super::super::super) tts: Option<&'a ForwardTts>,
 }
 
 pub(in super::super::super) async fn send_llm_markdown_as_forward_image(
@@ -113,4 +118,15 @@ pub(in super::super::super) async fn send_llm_markdown_as_forward_image(
         )
         .await;
     }
+
+    if let Some(tts) = input.tts {
+        let platform = VoicePlatform::of_bot(runtime, bot_id).await;
+        match synthesize_voice(state, bot_id, &markdown, tts.voice.as_deref(), platform).await {
+            Ok(clip) => {
+                send_reply(runtime, bot_id, user_id, group_id_opt, &clip.cq_segment()).await;
+            }
+            // 文字结果已经发出，合成失败时只记录日志
+            Err(e) => warn!("[{}] 分析结果语音合成失败: {}", bot_id, e),
+        }
+    }
 }
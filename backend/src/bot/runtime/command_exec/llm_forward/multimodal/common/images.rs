@@ -0,0 +1,271 @@
+//! OpenAI 兼容的 `/images/generations` 与 `/images/edits` 调用（插件 nbot.llm.image），
+//! 与对话调用共用模型映射的路由与熔断。
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::super::super::super::media_cache::download_cached;
+use super::super::super::download::TempFileGuard;
+use super::provider::ProviderKind;
+use super::routing::{LlmRoute, LlmTarget};
+use super::{acquire_llm_http_permit, LlmCallError, LlmConfig};
+use crate::bot::runtime::llm_usage::{record_image_usage, TokenUsage, UsageOrigin};
+
+const IMAGE_TIMEOUT: Duration = Duration::from_secs(180);
+const MAX_IMAGE_BYTES: usize = 30 * 1024 * 1024;
+
+/// 一次图片生成请求；`input_image` 非空时走编辑接口。
+pub(in super::super::super::super) struct ImageGenerationRequest<'a> {
+    pub(in super::super::super::super) prompt: &'a str,
+    pub(in super::super::super::super) size: &'a str,
+    pub(in super::super::super::super) quality: Option<&'a str>,
+    pub(in super::super::super::super) input_image: Option<&'a [u8]>,
+}
+
+pub(in super::super::super::super) struct GeneratedImage {
+    pub(in super::super::super::super) bytes: Vec<u8>,
+    pub(in super::super::super::super) mime: &'static str,
+    /// 模型改写后的提示词（dall-e-3 等会返回）
+    pub(in super::super::super::super) revised_prompt: Option<String>,
+}
+
+/// 生成（或编辑）一张图片。
+pub(in super::super::super::super) async fn call_image_generation(
+    llm: &LlmConfig,
+    request: &ImageGenerationRequest<'_>,
+) -> Result<GeneratedImage, LlmCallError> {
+    let mut route = LlmRoute::new(&llm.route, &llm.targets);
+    while let Some(target) = route.next_target() {
+        let result = call_image_target(target, request, &llm.origin).await;
+        if let Some(result) = route.settle(target, result) {
+            return result;
+        }
+    }
+    Err(LlmCallError::Transport("没有可用的 LLM 目标".to_string()))
+}
+
+/// gpt-image 系列总是返回 base64，且不接受 response_format 参数
+fn wants_response_format(model: &str) -> bool {
+    !model.starts_with("gpt-image")
+}
+
+/// `/images/generations` 的 JSON 请求体
+fn generation_body(model: &str, request: &ImageGenerationRequest<'_>) -> Value {
+    let mut body = json!({
+        "model": model,
+        "prompt": request.prompt,
+        "n": 1,
+        "size": request.size,
+    });
+    if let Some(quality) = request.quality {
+        body["quality"] = json!(quality);
+    }
+    if wants_response_format(model) {
+        body["response_format"] = json!("b64_json");
+    }
+    body
+}
+
+async fn call_image_target(
+    llm: &LlmTarget,
+    request: &ImageGenerationRequest<'_>,
+    origin: &UsageOrigin,
+) -> Result<GeneratedImage, LlmCallError> {
+    if llm.kind != ProviderKind::OpenAi {
+        return Err(LlmCallError::Http {
+            status: 400,
+            message: format!(
+                "提供商 {} 不是 OpenAI 兼容接口，无法生成图片",
+                llm.provider_id
+            ),
+        });
+    }
+
+    let base = llm.base_url.trim_end_matches('/');
+    let client = reqwest::Client::new();
+    let builder = match request.input_image {
+        None => client
+            .post(format!("{base}/images/generations"))
+            .json(&generation_body(&llm.model_name, request)),
+        Some(image) => {
+            let part = reqwest::multipart::Part::bytes(image.to_vec())
+                .file_name(format!("image.{}", image_ext(image)))
+                .mime_str(image_mime(image))
+                .map_err(|e| LlmCallError::Transport(e.to_string()))?;
+            let mut form = reqwest::multipart::Form::new()
+                .text("model", llm.model_name.clone())
+                .text("prompt", request.prompt.to_string())
+                .text("n", "1")
+                .text("size", request.size.to_string())
+                .part("image", part);
+            if let Some(quality) = request.quality {
+                form = form.text("quality", quality.to_string());
+            }
+            if wants_response_format(&llm.model_name) {
+                form = form.text("response_format", "b64_json");
+            }
+            client.post(format!("{base}/images/edits")).multipart(form)
+        }
+    };
+
+    let (status, text) = {
+        let _permit = acquire_llm_http_permit().await?;
+        let resp = builder
+            .bearer_auth(&llm.api_key)
+            .timeout(IMAGE_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| LlmCallError::Transport(e.to_string()))?;
+        let status = resp.status();
+        let text = resp
+            .text()
+            .await
+            .map_err(|e| LlmCallError::Decode(e.to_string()))?;
+        (status, text)
+    };
+
+    let v: Value = match serde_json::from_str(&text) {
+        Ok(v) => v,
+        Err(_) if !status.is_success() => {
+            return Err(LlmCallError::Http {
+                status: status.as_u16(),
+                message: text.chars().take(400).collect(),
+            })
+        }
+        Err(e) => return Err(LlmCallError::Parse(e.to_string())),
+    };
+    if !status.is_success() {
+        let message = v
+            .pointer("/error/message")
+            .or_else(|| v.get("error"))
+            .or_else(|| v.get("message"))
+            .and_then(|m| m.as_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| text.chars().take(400).collect());
+        return Err(LlmCallError::Http {
+            status: status.as_u16(),
+            message,
+        });
+    }
+
+    // 图片已生成即计费，之后下载失败也不影响记账
+    let usage = v
+        .get("usage")
+        .and_then(TokenUsage::from_openai)
+        .unwrap_or_default();
+    record_image_usage(
+        origin,
+        &llm.provider_id,
+        &llm.model_name,
+        llm.price,
+        usage,
+        1,
+    );
+
+    let item = v
+        .get("data")
+        .and_then(|d| d.as_array())
+        .and_then(|d| d.first())
+        .ok_or(LlmCallError::MissingContent)?;
+    let bytes = match (
+        item.get("b64_json").and_then(|b| b.as_str()),
+        item.get("url").and_then(|u| u.as_str()),
+    ) {
+        (Some(b64), _) => BASE64
+            .decode(b64.trim())
+            .map_err(|e| LlmCallError::Parse(format!("图片 base64 解码失败: {e}")))?,
+        (None, Some(url)) => download_image(url).await?,
+        (None, None) => return Err(LlmCallError::MissingContent),
+    };
+
+    Ok(GeneratedImage {
+        mime: image_mime(&bytes),
+        bytes,
+        revised_prompt: item
+            .get("revised_prompt")
+            .and_then(|p| p.as_str())
+            .map(|s| s.to_string()),
+    })
+}
+
+async fn download_image(url: &str) -> Result<Vec<u8>, LlmCallError> {
+    let guard = TempFileGuard::new("image", Some("generated.bin"))
+        .await
+        .map_err(LlmCallError::Transport)?;
+    download_cached(
+        url,
+        &guard.path,
+        IMAGE_TIMEOUT.as_millis() as u64,
+        MAX_IMAGE_BYTES as u64,
+        true,
+    )
+    .await
+    .map_err(|e| LlmCallError::Transport(format!("下载生成的图片失败: {e}")))?;
+    tokio::fs::read(&guard.path)
+        .await
+        .map_err(|e| LlmCallError::Decode(e.to_string()))
+}
+
+fn image_ext(data: &[u8]) -> &'static str {
+    if data.starts_with(&[0x89, b'P', b'N', b'G']) {
+        "png"
+    } else if data.len() > 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        "webp"
+    } else {
+        "jpg"
+    }
+}
+
+fn image_mime(data: &[u8]) -> &'static str {
+    match image_ext(data) {
+        "png" => "image/png",
+        "webp" => "image/webp",
+        _ => "image/jpeg",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request<'a>(quality: Option<&'a str>) -> ImageGenerationRequest<'a> {
+        ImageGenerationRequest {
+            prompt: "一只猫",
+            size: "512x512",
+            quality,
+            input_image: None,
+        }
+    }
+
+    #[test]
+    fn dall_e_requests_base64_response() {
+        let body = generation_body("dall-e-3", &request(Some("hd")));
+        assert_eq!(
+            body,
+            json!({
+                "model": "dall-e-3",
+                "prompt": "一只猫",
+                "n": 1,
+                "size": "512x512",
+                "quality": "hd",
+                "response_format": "b64_json"
+            })
+        );
+    }
+
+    #[test]
+    fn gpt_image_omits_response_format() {
+        let body = generation_body("gpt-image-1", &request(None));
+        assert!(body.get("response_format").is_none());
+        assert!(body.get("quality").is_none());
+    }
+
+    #[test]
+    fn image_type_sniffed_from_magic_bytes() {
+        assert_eq!(image_mime(b"\x89PNG\r\n\x1a\n"), "image/png");
+        assert_eq!(image_mime(b"RIFF\x00\x00\x00\x00WEBPVP8 "), "image/webp");
+        assert_eq!(image_mime(b"\xff\xd8\xff\xe0"), "image/jpeg");
+        assert_eq!(image_ext(b"RIFF\x00\x00\x00\x00WEBP"), "jpg");
+    }
+}
@@ -0,0 +1,1076 @@
+//! LLM 提供商适配：内部统一使用 OpenAI Chat Completions 的请求/响应格式，
+//! 发请求前翻译成 Anthropic Messages / Gemini generateContent，收到响应后再翻译回来，
+//! 这样 Tavily 工具循环、多模态素材等上层逻辑不需要关心提供商。
+
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+use super::LlmCallError;
+use crate::bot::runtime::llm_usage::TokenUsage;
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const ANTHROPIC_DEFAULT_MAX_TOKENS: u64 = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(in super::super::super::super) enum ProviderKind {
+    #[default]
+    OpenAi,
+    Anthropic,
+    Gemini,
+}
+
+impl ProviderKind {
+    /// 读取提供商条目的 `kind`；旧配置只有 `type`（如 WebUI 写入的 `claude`）时按其推断。
+    pub(in super::super::super::super) fn from_provider(provider: &Value) -> Self {
+        let raw = provider
+            .get("kind")
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.trim().is_empty())
+            .or_else(|| provider.get("type").and_then(|v| v.as_str()))
+            .unwrap_or("openai")
+            .trim()
+            .to_ascii_lowercase();
+        match raw.as_str() {
+            "anthropic" | "claude" => ProviderKind::Anthropic,
+            "gemini" | "google" => ProviderKind::Gemini,
+            _ => ProviderKind::OpenAi,
+        }
+    }
+
+    pub(in super::super::super::super) fn default_base_url(self) -> &'static str {
+        match self {
+            ProviderKind::OpenAi => "https://api.openai.com/v1",
+            ProviderKind::Anthropic => "https://api.anthropic.com",
+            ProviderKind::Gemini => "https://generativelanguage.googleapis.com/v1beta",
+        }
+    }
+}
+
+/// 已翻译好的请求：URL、认证头与序列化后的请求体。
+pub(super) struct ChatRequest {
+    url: String,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+}
+
+impl ChatRequest {
+    pub(super) fn builder(&self, client: &reqwest::Client) -> reqwest::RequestBuilder {
+        let mut req = client
+            .post(&self.url)
+            .header("Content-Type", "application/json");
+        for (name, value) in &self.headers {
+            req = req.header(*name, value);
+        }
+        req.body(self.body.clone())
+    }
+}
+
+/// 把 OpenAI 格式的请求体翻译成目标提供商的请求，并检查请求体大小上限。
+pub(super) fn build_chat_request(
+    kind: ProviderKind,
+    base_url: &str,
+    api_key: &str,
+    body: &Value,
+    stream: bool,
+    max_request_bytes: u64,
+) -> Result<ChatRequest, LlmCallError> {
+    let base = base_url.trim_end_matches('/');
+    let (url, headers, body) = match kind {
+        ProviderKind::OpenAi => {
+            let mut body = body.clone();
+            if stream {
+                body["stream"] = json!(true);
+                body["stream_options"] = json!({ "include_usage": true });
+            }
+            (
+                format!("{}/chat/completions", base),
+                vec![("Authorization", format!("Bearer {}", api_key))],
+                body,
+            )
+        }
+        ProviderKind::Anthropic => {
+            let url = if base.ends_with("/v1") {
+                format!("{}/messages", base)
+            } else {
+                format!("{}/v1/messages", base)
+            };
+            (
+                url,
+                vec![
+                    ("x-api-key", api_key.to_string()),
+                    ("anthropic-version", ANTHROPIC_VERSION.to_string()),
+                ],
+                to_anthropic_request(body, stream),
+            )
+        }
+        ProviderKind::Gemini => {
+            let model = body.get("model").and_then(|m| m.as_str()).unwrap_or("");
+            let model = model.strip_prefix("models/").unwrap_or(model);
+            let url = if stream {
+                format!("{}/models/{}:streamGenerateContent?alt=sse", base, model)
+            } else {
+                format!("{}/models/{}:generateContent", base, model)
+            };
+            (
+                url,
+                vec![("x-goog-api-key", api_key.to_string())],
+                to_gemini_request(body),
+            )
+        }
+    };
+
+    let body = serde_json::to_vec(&body)
+        .map_err(|e| LlmCallError::Parse(format!("序列化请求失败: {e}")))?;
+    if (body.len() as u64) > max_request_bytes {
+        return Err(LlmCallError::RequestTooLarge {
+            request_bytes: body.len() as u64,
+            limit_bytes: max_request_bytes,
+        });
+    }
+    Ok(ChatRequest { url, headers, body })
+}
+
+/// 把提供商的非流式响应翻译成 OpenAI Chat Completions 响应（`choices[0].message`）。
+pub(super) fn normalize_chat_response(kind: ProviderKind, v: Value) -> Value {
+    match kind {
+        ProviderKind::OpenAi => v,
+        ProviderKind::Anthropic => from_anthropic_response(&v),
+        ProviderKind::Gemini => from_gemini_response(&v),
+    }
+}
+
+/// 从一个 SSE 事件中取出增量文本。
+pub(super) fn stream_delta(kind: ProviderKind, v: &Value) -> Option<String> {
+    match kind {
+        ProviderKind::OpenAi => {
+            let delta = v.get("choices")?.get(0)?.get("delta")?;
+            match delta.get("content")? {
+                Value::String(s) => Some(s.clone()),
+                Value::Array(parts) => Some(
+                    parts
+                        .iter()
+                        .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                        .collect(),
+                ),
+                _ => None,
+            }
+        }
+        ProviderKind::Anthropic => {
+            if v.get("type").and_then(|t| t.as_str()) != Some("content_block_delta") {
+                return None;
+            }
+            let delta = v.get("delta")?;
+            if delta.get("type").and_then(|t| t.as_str()) != Some("text_delta") {
+                return None;
+            }
+            delta.get("text")?.as_str().map(|s| s.to_string())
+        }
+        ProviderKind::Gemini => {
+            let parts = v
+                .get("candidates")?
+                .get(0)?
+                .get("content")?
+                .get("parts")?
+                .as_array()?;
+            Some(gemini_parts_text(parts))
+        }
+    }
+}
+
+/// 流式事件里携带的用量（OpenAI 需在请求里开启 `stream_options.include_usage`）。
+pub(super) fn stream_usage(kind: ProviderKind, v: &Value) -> Option<TokenUsage> {
+    match kind {
+        ProviderKind::OpenAi => TokenUsage::from_openai(v.get("usage")?),
+        ProviderKind::Anthropic => match v.get("type").and_then(|t| t.as_str())? {
+            "message_start" => Some(anthropic_usage(v.get("message")?.get("usage"))),
+            "message_delta" => Some(anthropic_usage(v.get("usage"))),
+            _ => None,
+        },
+        ProviderKind::Gemini => v.get("usageMetadata").map(gemini_usage),
+    }
+}
+
+/// 流式事件里的错误（三家都放在 `error` 字段里）。
+pub(super) fn stream_error(v: &Value) -> Option<String> {
+    let err = v.get("error")?;
+    Some(
+        err.get("message")
+            .and_then(|m| m.as_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| err.to_string()),
+    )
+}
+
+/// `data:<mime>;base64,<data>` -> (mime, data)
+fn parse_data_url(url: &str) -> Option<(&str, &str)> {
+    let rest = url.strip_prefix("data:")?;
+    let (meta, data) = rest.split_once(',')?;
+    let mime = meta.strip_suffix(";base64")?;
+    Some((mime, data))
+}
+
+fn content_text(content: Option<&Value>) -> String {
+    match content {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|p| match p {
+                Value::String(s) => Some(s.as_str()),
+                _ => p.get("text").and_then(|t| t.as_str()),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+fn tool_call_parts(msg: &Value) -> Vec<(String, String, Value)> {
+    msg.get("tool_calls")
+        .and_then(|t| t.as_array())
+        .map(|calls| {
+            calls
+                .iter()
+                .map(|tc| {
+                    let id = tc.get("id").and_then(|v| v.as_str()).unwrap_or("");
+                    let func = tc.get("function");
+                    let name = func
+                        .and_then(|f| f.get("name"))
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("");
+                    let args = func
+                        .and_then(|f| f.get("arguments"))
+                        .map(|a| match a {
+                            Value::String(s) => serde_json::from_str(s).unwrap_or(json!({})),
+                            other => other.clone(),
+                        })
+                        .unwrap_or(json!({}));
+                    (id.to_string(), name.to_string(), args)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn openai_tools(body: &Value) -> Vec<(String, String, Value)> {
+    body.get("tools")
+        .and_then(|t| t.as_array())
+        .map(|tools| {
+            tools
+                .iter()
+                .filter_map(|t| {
+                    let f = t.get("function")?;
+                    let name = f.get("name")?.as_str()?.to_string();
+                    let desc = f
+                        .get("description")
+                        .and_then(|d| d.as_str())
+                        .unwrap_or("")
+                        .to_string();
+                    let params = f
+                        .get("parameters")
+                        .cloned()
+                        .unwrap_or_else(|| json!({ "type": "object", "properties": {} }));
+                    Some((name, desc, params))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn stop_sequences(body: &Value) -> Option<Value> {
+    match body.get("stop")? {
+        Value::String(s) => Some(json!([s])),
+        Value::Array(a) if !a.is_empty() => Some(Value::Array(a.clone())),
+        _ => None,
+    }
+}
+
+/// OpenAI 的 `response_format` 里的 JSON Schema；`json_object` 时为 Some(None)
+fn response_json_schema(body: &Value) -> Option<Option<&Value>> {
+    let format = body.get("response_format")?;
+    match format.get("type").and_then(|t| t.as_str()) {
+        Some("json_schema") => Some(format.pointer("/json_schema/schema")),
+        Some("json_object") => Some(None),
+        _ => None,
+    }
+}
+
+/// Anthropic 没有结构化输出参数，改为追加一段系统提示
+fn response_format_instruction(body: &Value) -> Option<String> {
+    Some(match response_json_schema(body)? {
+        Some(schema) => format!(
+            "只输出一个符合以下 JSON Schema 的 JSON 值，不要输出代码块或其它文字：\n{}",
+            schema
+        ),
+        None => "只输出一个 JSON 对象，不要输出代码块或其它文字。".to_string(),
+    })
+}
+
+fn max_tokens(body: &Value) -> Option<u64> {
+    body.get("max_tokens")
+        .or_else(|| body.get("max_completion_tokens"))
+        .and_then(|v| v.as_u64())
+}
+
+/// 相邻同角色的消息合并（Anthropic/Gemini 要求 user/assistant 交替）。
+fn push_merged(out: &mut Vec<(String, Vec<Value>)>, role: &str, blocks: Vec<Value>) {
+    if blocks.is_empty() {
+        return;
+    }
+    match out.last_mut() {
+        Some((last_role, last_blocks)) if last_role == role => last_blocks.extend(blocks),
+        _ => out.push((role.to_string(), blocks)),
+    }
+}
+
+fn unsupported_attachment_note(mime: &str) -> String {
+    format!("[附件（{}）未发送：当前模型不支持该类型]", mime)
+}
+
+// ---------------- Anthropic Messages ----------------
+
+fn anthropic_blocks(content: Option<&Value>) -> Vec<Value> {
+    let mut blocks = Vec::new();
+    match content {
+        Some(Value::String(s)) if !s.is_empty() => {
+            blocks.push(json!({ "type": "text", "text": s }));
+        }
+        Some(Value::Array(parts)) => {
+            for part in parts {
+                let ty = part.get("type").and_then(|t| t.as_str()).unwrap_or("");
+                match ty {
+                    "text" => {
+                        if let Some(t) = part.get("text").and_then(|t| t.as_str()) {
+                            if !t.is_empty() {
+                                blocks.push(json!({ "type": "text", "text": t }));
+                            }
+                        }
+                    }
+                    "image_url" => {
+                        let url = part
+                            .get("image_url")
+                            .and_then(|i| i.get("url"))
+                            .and_then(|u| u.as_str())
+                            .unwrap_or("");
+                        match parse_data_url(url) {
+                            Some((mime, data))
+                                if matches!(
+                                    mime,
+                                    "image/jpeg" | "image/png" | "image/gif" | "image/webp"
+                                ) =>
+                            {
+                                blocks.push(json!({
+                                    "type": "image",
+                                    "source": { "type": "base64", "media_type": mime, "data": data }
+                                }));
+                            }
+                            Some((mime, _)) => {
+                                blocks.push(json!({
+                                    "type": "text",
+                                    "text": unsupported_attachment_note(mime)
+                                }));
+                            }
+                            None if url.starts_with("http") => {
+                                blocks.push(json!({
+                                    "type": "image",
+                                    "source": { "type": "url", "url": url }
+                                }));
+                            }
+                            None => {}
+                        }
+                    }
+                    "input_audio" => {
+                        let format = part
+                            .get("input_audio")
+                            .and_then(|a| a.get("format"))
+                            .and_then(|f| f.as_str())
+                            .unwrap_or("audio");
+                        blocks.push(json!({
+                            "type": "text",
+                            "text": unsupported_attachment_note(&format!("audio/{format}"))
+                        }));
+                    }
+                    _ => {
+                        if let Some(s) = part.as_str() {
+                            blocks.push(json!({ "type": "text", "text": s }));
+                        }
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+    blocks
+}
+
+fn to_anthropic_request(body: &Value, stream: bool) -> Value {
+    let mut system: Vec<String> = Vec::new();
+    let mut messages: Vec<(String, Vec<Value>)> = Vec::new();
+
+    for msg in body
+        .get("messages")
+        .and_then(|m| m.as_array())
+        .into_iter()
+        .flatten()
+    {
+        let role = msg.get("role").and_then(|r| r.as_str()).unwrap_or("user");
+        match role {
+            "system" | "developer" => {
+                let text = content_text(msg.get("content"));
+                if !text.trim().is_empty() {
+                    system.push(text);
+                }
+            }
+            "tool" => {
+                let id = msg
+                    .get("tool_call_id")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("");
+                let block = json!({
+                    "type": "tool_result",
+                    "tool_use_id": id,
+                    "content": content_text(msg.get("content"))
+                });
+                push_merged(&mut messages, "user", vec![block]);
+            }
+            "assistant" => {
+                let mut blocks = anthropic_blocks(msg.get("content"));
+                for (id, name, input) in tool_call_parts(msg) {
+                    blocks.push(
+                        json!({ "type": "tool_use", "id": id, "name": name, "input": input }),
+                    );
+                }
+                push_merged(&mut messages, "assistant", blocks);
+            }
+            _ => push_merged(&mut messages, "user", anthropic_blocks(msg.get("content"))),
+        }
+    }
+
+    if let Some(instruction) = response_format_instruction(body) {
+        system.push(instruction);
+    }
+
+    let messages: Vec<Value> = messages
+        .into_iter()
+        .map(|(role, content)| json!({ "role": role, "content": content }))
+        .collect();
+
+    let mut out = json!({
+        "model": body.get("model").cloned().unwrap_or(Value::Null),
+        "max_tokens": max_tokens(body).unwrap_or(ANTHROPIC_DEFAULT_MAX_TOKENS),
+        "messages": messages,
+    });
+    if !system.is_empty() {
+        out["system"] = json!(system.join("\n\n"));
+    }
+    // Anthropic 的 temperature 只接受 0~1，OpenAI 口径的 0~2 需要截断
+    if let Some(t) = body.get("temperature").and_then(|v| v.as_f64()) {
+        out["temperature"] = json!(t.clamp(0.0, 1.0));
+    }
+    if let Some(v) = body.get("top_p") {
+        out["top_p"] = v.clone();
+    }
+    if let Some(stop) = stop_sequences(body) {
+        out["stop_sequences"] = stop;
+    }
+
+    // 历史里有 tool_use/tool_result 时必须带上 tools，"none" 用 tool_choice 禁止再调用
+    let tool_choice = body.get("tool_choice");
+    let tools = openai_tools(body);
+    if !tools.is_empty() {
+        out["tools"] = Value::Array(
+            tools
+                .into_iter()
+                .map(|(name, desc, params)| {
+                    json!({ "name": name, "description": desc, "input_schema": params })
+                })
+                .collect(),
+        );
+        let choice = match tool_choice {
+            Some(Value::String(s)) if s == "required" => json!({ "type": "any" }),
+            Some(Value::String(s)) if s == "none" => json!({ "type": "none" }),
+            Some(Value::Object(o)) => match o
+                .get("function")
+                .and_then(|f| f.get("name"))
+                .and_then(|n| n.as_str())
+            {
+                Some(name) => json!({ "type": "tool", "name": name }),
+                None => json!({ "type": "auto" }),
+            },
+            _ => json!({ "type": "auto" }),
+        };
+        out["tool_choice"] = choice;
+    }
+    if stream {
+        out["stream"] = json!(true);
+    }
+    out
+}
+
+fn from_anthropic_response(v: &Value) -> Value {
+    let mut text = String::new();
+    let mut tool_calls: Vec<Value> = Vec::new();
+    for block in v
+        .get("content")
+        .and_then(|c| c.as_array())
+        .into_iter()
+        .flatten()
+    {
+        match block.get("type").and_then(|t| t.as_str()) {
+            Some("text") => {
+                if let Some(t) = block.get("text").and_then(|t| t.as_str()) {
+                    text.push_str(t);
+                }
+            }
+            Some("tool_use") => {
+                let input = block.get("input").cloned().unwrap_or(json!({}));
+                tool_calls.push(json!({
+                    "id": block.get("id").cloned().unwrap_or(Value::Null),
+                    "type": "function",
+                    "function": {
+                        "name": block.get("name").cloned().unwrap_or(Value::Null),
+                        "arguments": input.to_string()
+                    }
+                }));
+            }
+            _ => {}
+        }
+    }
+
+    let finish_reason = match v.get("stop_reason").and_then(|s| s.as_str()) {
+        Some("max_tokens") => "length",
+        Some("tool_use") => "tool_calls",
+        _ => "stop",
+    };
+    json!({
+        "id": v.get("id").cloned().unwrap_or(Value::Null),
+        "model": v.get("model").cloned().unwrap_or(Value::Null),
+        "choices": [{
+            "index": 0,
+            "message": openai_message(text, tool_calls),
+            "finish_reason": finish_reason
+        }],
+        "usage": openai_usage(anthropic_usage(v.get("usage")))
+    })
+}
+
+/// Anthropic 的 `input_tokens` 不含缓存读写部分，这里合并成 OpenAI 口径的输入总数。
+fn anthropic_usage(usage: Option<&Value>) -> TokenUsage {
+    let count = |key: &str| {
+        usage
+            .and_then(|u| u.get(key))
+            .and_then(|t| t.as_u64())
+            .unwrap_or(0)
+    };
+    let cached_tokens = count("cache_read_input_tokens");
+    TokenUsage {
+        prompt_tokens: count("input_tokens") + cached_tokens + count("cache_creation_input_tokens"),
+        completion_tokens: count("output_tokens"),
+        cached_tokens,
+    }
+}
+
+fn gemini_usage(usage: &Value) -> TokenUsage {
+    let count = |key: &str| usage.get(key).and_then(|t| t.as_u64()).unwrap_or(0);
+    TokenUsage {
+        prompt_tokens: count("promptTokenCount"),
+        completion_tokens: count("candidatesTokenCount") + count("thoughtsTokenCount"),
+        cached_tokens: count("cachedContentTokenCount"),
+    }
+}
+
+fn openai_usage(usage: TokenUsage) -> Value {
+    json!({
+        "prompt_tokens": usage.prompt_tokens,
+        "completion_tokens": usage.completion_tokens,
+        "total_tokens": usage.prompt_tokens + usage.completion_tokens,
+        "prompt_tokens_details": { "cached_tokens": usage.cached_tokens }
+    })
+}
+
+fn openai_message(text: String, tool_calls: Vec<Value>) -> Value {
+    let mut message = Map::new();
+    message.insert("role".to_string(), json!("assistant"));
+    if text.is_empty() && !tool_calls.is_empty() {
+        message.insert("content".to_string(), Value::Null);
+    } else {
+        message.insert("content".to_string(), json!(text));
+    }
+    if !tool_calls.is_empty() {
+        message.insert("tool_calls".to_string(), Value::Array(tool_calls));
+    }
+    Value::Object(message)
+}
+
+// ---------------- Gemini generateContent ----------------
+
+fn gemini_parts(content: Option<&Value>) -> Vec<Value> {
+    let mut parts = Vec::new();
+    match content {
+        Some(Value::String(s)) if !s.is_empty() => parts.push(json!({ "text": s })),
+        Some(Value::Array(items)) => {
+            for item in items {
+                let ty = item.get("type").and_then(|t| t.as_str()).unwrap_or("");
+                match ty {
+                    "text" => {
+                        if let Some(t) = item.get("text").and_then(|t| t.as_str()) {
+                            if !t.is_empty() {
+                                parts.push(json!({ "text": t }));
+                            }
+                        }
+                    }
+                    "image_url" => {
+                        let url = item
+                            .get("image_url")
+                            .and_then(|i| i.get("url"))
+                            .and_then(|u| u.as_str())
+                            .unwrap_or("");
+                        match parse_data_url(url) {
+                            Some((mime, data)) => parts
+                                .push(json!({ "inlineData": { "mimeType": mime, "data": data } })),
+                            // generateContent 不会替我们抓取任意 URL，只能把链接作为文本交给模型
+                            None if !url.is_empty() => {
+                                parts.push(json!({ "text": format!("[图片链接: {}]", url) }))
+                            }
+                            None => {}
+                        }
+                    }
+                    "input_audio" => {
+                        let audio = item.get("input_audio");
+                        let data = audio
+                            .and_then(|a| a.get("data"))
+                            .and_then(|d| d.as_str())
+                            .unwrap_or("");
+                        let format = audio
+                            .and_then(|a| a.get("format"))
+                            .and_then(|f| f.as_str())
+                            .unwrap_or("wav");
+                        if !data.is_empty() {
+                            parts.push(json!({
+                                "inlineData": { "mimeType": format!("audio/{format}"), "data": data }
+                            }));
+                        }
+                    }
+                    _ => {
+                        if let Some(s) = item.as_str() {
+                            parts.push(json!({ "text": s }));
+                        }
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+    parts
+}
+
+fn gemini_parts_text(parts: &[Value]) -> String {
+    parts
+        .iter()
+        .filter(|p| p.get("thought").and_then(|t| t.as_bool()) != Some(true))
+        .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+        .collect()
+}
+
+/// Gemini 的函数参数只接受 OpenAPI 子集，去掉它不认识的 JSON Schema 关键字。
+fn gemini_schema(schema: &Value) -> Value {
+    match schema {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .filter(|(k, _)| {
+                    !matches!(k.as_str(), "additionalProperties" | "$schema" | "strict")
+                })
+                .map(|(k, v)| (k.clone(), gemini_schema(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(gemini_schema).collect()),
+        other => other.clone(),
+    }
+}
+
+fn to_gemini_request(body: &Value) -> Value {
+    let mut system: Vec<String> = Vec::new();
+    let mut contents: Vec<(String, Vec<Value>)> = Vec::new();
+    let mut tool_names: HashMap<String, String> = HashMap::new();
+
+    for msg in body
+        .get("messages")
+        .and_then(|m| m.as_array())
+        .into_iter()
+        .flatten()
+    {
+        let role = msg.get("role").and_then(|r| r.as_str()).unwrap_or("user");
+        match role {
+            "system" | "developer" => {
+                let text = content_text(msg.get("content"));
+                if !text.trim().is_empty() {
+                    system.push(text);
+                }
+            }
+            "tool" => {
+                let id = msg
+                    .get("tool_call_id")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("");
+                let name = tool_names
+                    .get(id)
+                    .cloned()
+                    .unwrap_or_else(|| "tool".to_string());
+                let part = json!({
+                    "functionResponse": {
+                        "name": name,
+                        "response": { "content": content_text(msg.get("content")) }
+                    }
+                });
+                push_merged(&mut contents, "user", vec![part]);
+            }
+            "assistant" => {
+                let mut parts = gemini_parts(msg.get("content"));
+                for (id, name, args) in tool_call_parts(msg) {
+                    tool_names.insert(id, name.clone());
+                    parts.push(json!({ "functionCall": { "name": name, "args": args } }));
+                }
+                push_merged(&mut contents, "model", parts);
+            }
+            _ => push_merged(&mut contents, "user", gemini_parts(msg.get("content"))),
+        }
+    }
+
+    let contents: Vec<Value> = contents
+        .into_iter()
+        .map(|(role, parts)| json!({ "role": role, "parts": parts }))
+        .collect();
+    let mut out = json!({ "contents": contents });
+    if !system.is_empty() {
+        out["systemInstruction"] = json!({ "parts": [{ "text": system.join("\n\n") }] });
+    }
+
+    let mut generation = Map::new();
+    if let Some(n) = max_tokens(body) {
+        generation.insert("maxOutputTokens".to_string(), json!(n));
+    }
+    if let Some(v) = body.get("temperature") {
+        generation.insert("temperature".to_string(), v.clone());
+    }
+    if let Some(v) = body.get("top_p") {
+        generation.insert("topP".to_string(), v.clone());
+    }
+    if let Some(stop) = stop_sequences(body) {
+        generation.insert("stopSequences".to_string(), stop);
+    }
+    if let Some(schema) = response_json_schema(body) {
+        generation.insert("responseMimeType".to_string(), json!("application/json"));
+        if let Some(schema) = schema {
+            generation.insert("responseSchema".to_string(), gemini_schema(schema));
+        }
+    }
+    if !generation.is_empty() {
+        out["generationConfig"] = Value::Object(generation);
+    }
+
+    let tools = openai_tools(body);
+    if !tools.is_empty() {
+        let declarations: Vec<Value> = tools
+            .into_iter()
+            .map(|(name, desc, params)| {
+                json!({ "name": name, "description": desc, "parameters": gemini_schema(&params) })
+            })
+            .collect();
+        out["tools"] = json!([{ "functionDeclarations": declarations }]);
+        let mode = match body.get("tool_choice").and_then(|c| c.as_str()) {
+            Some("none") => "NONE",
+            Some("required") => "ANY",
+            _ => "AUTO",
+        };
+        out["toolConfig"] = json!({ "functionCallingConfig": { "mode": mode } });
+    }
+    out
+}
+
+fn from_gemini_response(v: &Value) -> Value {
+    let candidate = v.get("candidates").and_then(|c| c.get(0));
+    let parts: Vec<Value> = candidate
+        .and_then(|c| c.get("content"))
+        .and_then(|c| c.get("parts"))
+        .and_then(|p| p.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    let text = gemini_parts_text(&parts);
+    let tool_calls: Vec<Value> = parts
+        .iter()
+        .filter_map(|p| p.get("functionCall"))
+        .enumerate()
+        .map(|(i, call)| {
+            json!({
+                "id": format!("call_{}", i),
+                "type": "function",
+                "function": {
+                    "name": call.get("name").cloned().unwrap_or(Value::Null),
+                    "arguments": call.get("args").cloned().unwrap_or(json!({})).to_string()
+                }
+            })
+        })
+        .collect();
+
+    let finish_reason = if !tool_calls.is_empty() {
+        "tool_calls"
+    } else {
+        match candidate
+            .and_then(|c| c.get("finishReason"))
+            .and_then(|f| f.as_str())
+        {
+            Some("MAX_TOKENS") => "length",
+            Some("SAFETY")
+            | Some("RECITATION")
+            | Some("PROHIBITED_CONTENT")
+            | Some("BLOCKLIST") => "content_filter",
+            _ => "stop",
+        }
+    };
+    // 没有候选（如提示词被拦截）时不生成 choices，上层会按“无法获取回复内容”处理
+    let choices = if candidate.is_some() {
+        json!([{
+            "index": 0,
+            "message": openai_message(text, tool_calls),
+            "finish_reason": finish_reason
+        }])
+    } else {
+        json!([])
+    };
+
+    json!({
+        "model": v.get("modelVersion").cloned().unwrap_or(Value::Null),
+        "choices": choices,
+        "usage": openai_usage(gemini_usage(v.get("usageMetadata").unwrap_or(&Value::Null)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool_body() -> Value {
+        json!({
+            "model": "m",
+            "messages": [
+                { "role": "system", "content": "你是助手" },
+                { "role": "developer", "content": "简短回答" },
+                { "role": "user", "content": "查天气" },
+                { "role": "user", "content": [{ "type": "text", "text": "北京" }] },
+                {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "toolu_1",
+                        "type": "function",
+                        "function": { "name": "search", "arguments": "{\"q\":\"北京天气\"}" }
+                    }]
+                },
+                { "role": "tool", "tool_call_id": "toolu_1", "content": "晴" },
+                { "role": "user", "content": "谢谢" }
+            ],
+            "tools": [{
+                "type": "function",
+                "function": {
+                    "name": "search",
+                    "description": "搜索",
+                    "parameters": {
+                        "type": "object",
+                        "additionalProperties": false,
+                        "properties": { "q": { "type": "string" } }
+                    }
+                }
+            }],
+            "tool_choice": "required"
+        })
+    }
+
+    #[test]
+    fn anthropic_request_maps_system_roles_and_tools() {
+        let out = to_anthropic_request(&tool_body(), true);
+        assert_eq!(out["system"], "你是助手\n\n简短回答");
+        assert_eq!(out["max_tokens"], ANTHROPIC_DEFAULT_MAX_TOKENS);
+        assert_eq!(out["stream"], true);
+
+        let messages = out["messages"].as_array().unwrap();
+        let roles: Vec<&str> = messages
+            .iter()
+            .map(|m| m["role"].as_str().unwrap())
+            .collect();
+        assert_eq!(roles, ["user", "assistant", "user"]);
+        assert_eq!(messages[0]["content"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            messages[1]["content"][0],
+            json!({ "type": "tool_use", "id": "toolu_1", "name": "search", "input": { "q": "北京天气" } })
+        );
+        // tool_result 与随后的用户消息合并进同一条 user 消息
+        assert_eq!(messages[2]["content"][0]["type"], "tool_result");
+        assert_eq!(messages[2]["content"][0]["tool_use_id"], "toolu_1");
+        assert_eq!(messages[2]["content"][0]["content"], "晴");
+        assert_eq!(messages[2]["content"][1]["text"], "谢谢");
+
+        assert_eq!(out["tools"][0]["name"], "search");
+        assert_eq!(out["tools"][0]["input_schema"]["type"], "object");
+        assert_eq!(out["tool_choice"], json!({ "type": "any" }));
+    }
+
+    #[test]
+    fn anthropic_request_clamps_temperature() {
+        let body = |t: f64| json!({ "messages": [], "temperature": t, "top_p": 0.9 });
+        assert_eq!(to_anthropic_request(&body(1.7), false)["temperature"], 1.0);
+        assert_eq!(to_anthropic_request(&body(0.3), false)["temperature"], 0.3);
+        assert_eq!(to_anthropic_request(&body(-1.0), false)["temperature"], 0.0);
+        assert_eq!(to_anthropic_request(&body(0.3), false)["top_p"], 0.9);
+        assert!(to_anthropic_request(&json!({ "messages": [] }), false)
+            .get("temperature")
+            .is_none());
+    }
+
+    #[test]
+    fn anthropic_response_maps_text_tool_use_and_usage() {
+        let resp = json!({
+            "id": "msg_1",
+            "model": "claude",
+            "content": [
+                { "type": "text", "text": "我来查" },
+                { "type": "tool_use", "id": "toolu_9", "name": "search", "input": { "q": "x" } }
+            ],
+            "stop_reason": "tool_use",
+            "usage": {
+                "input_tokens": 10,
+                "cache_read_input_tokens": 5,
+                "cache_creation_input_tokens": 2,
+                "output_tokens": 7
+            }
+        });
+        let out = from_anthropic_response(&resp);
+        let choice = &out["choices"][0];
+        assert_eq!(choice["finish_reason"], "tool_calls");
+        assert_eq!(choice["message"]["role"], "assistant");
+        assert_eq!(choice["message"]["content"], "我来查");
+        let call = &choice["message"]["tool_calls"][0];
+        assert_eq!(call["id"], "toolu_9");
+        assert_eq!(call["function"]["name"], "search");
+        let args: Value =
+            serde_json::from_str(call["function"]["arguments"].as_str().unwrap()).unwrap();
+        assert_eq!(args, json!({ "q": "x" }));
+        assert_eq!(out["usage"]["prompt_tokens"], 17);
+        assert_eq!(out["usage"]["completion_tokens"], 7);
+        assert_eq!(out["usage"]["prompt_tokens_details"]["cached_tokens"], 5);
+
+        let only_tool = json!({
+            "content": [{ "type": "tool_use", "id": "t", "name": "n", "input": {} }],
+            "stop_reason": "tool_use"
+        });
+        assert!(from_anthropic_response(&only_tool)["choices"][0]["message"]["content"].is_null());
+        let truncated = json!({ "content": [], "stop_reason": "max_tokens" });
+        assert_eq!(
+            from_anthropic_response(&truncated)["choices"][0]["finish_reason"],
+            "length"
+        );
+    }
+
+    #[test]
+    fn gemini_request_maps_system_roles_and_tools() {
+        let mut body = tool_body();
+        body["temperature"] = json!(1.5);
+        body["max_tokens"] = json!(256);
+        let out = to_gemini_request(&body);
+        assert_eq!(
+            out["systemInstruction"]["parts"][0]["text"],
+            "你是助手\n\n简短回答"
+        );
+
+        let contents = out["contents"].as_array().unwrap();
+        let roles: Vec<&str> = contents
+            .iter()
+            .map(|c| c["role"].as_str().unwrap())
+            .collect();
+        assert_eq!(roles, ["user", "model", "user"]);
+        assert_eq!(
+            contents[1]["parts"][0]["functionCall"],
+            json!({ "name": "search", "args": { "q": "北京天气" } })
+        );
+        // functionResponse 按 tool_call_id 找回函数名
+        assert_eq!(
+            contents[2]["parts"][0]["functionResponse"],
+            json!({ "name": "search", "response": { "content": "晴" } })
+        );
+        assert_eq!(contents[2]["parts"][1]["text"], "谢谢");
+
+        assert_eq!(out["generationConfig"]["temperature"], 1.5);
+        assert_eq!(out["generationConfig"]["maxOutputTokens"], 256);
+        let decl = &out["tools"][0]["functionDeclarations"][0];
+        assert_eq!(decl["name"], "search");
+        assert!(decl["parameters"].get("additionalProperties").is_none());
+        assert_eq!(out["toolConfig"]["functionCallingConfig"]["mode"], "ANY");
+    }
+
+    #[test]
+    fn gemini_response_assigns_sequential_call_ids() {
+        let resp = json!({
+            "modelVersion": "gemini",
+            "candidates": [{
+                "content": {
+                    "role": "model",
+                    "parts": [
+                        { "text": "思考中", "thought": true },
+                        { "text": "好的" },
+                        { "functionCall": { "name": "a", "args": { "x": 1 } } },
+                        { "functionCall": { "name": "b", "args": {} } }
+                    ]
+                },
+                "finishReason": "STOP"
+            }],
+            "usageMetadata": {
+                "promptTokenCount": 12,
+                "candidatesTokenCount": 3,
+                "thoughtsTokenCount": 4,
+                "cachedContentTokenCount": 6
+            }
+        });
+        let out = from_gemini_response(&resp);
+        let message = &out["choices"][0]["message"];
+        assert_eq!(out["choices"][0]["finish_reason"], "tool_calls");
+        assert_eq!(message["content"], "好的");
+        let calls = message["tool_calls"].as_array().unwrap();
+        assert_eq!(calls[0]["id"], "call_0");
+        assert_eq!(calls[0]["function"]["name"], "a");
+        assert_eq!(calls[0]["function"]["arguments"], "{\"x\":1}");
+        assert_eq!(calls[1]["id"], "call_1");
+        assert_eq!(out["usage"]["prompt_tokens"], 12);
+        assert_eq!(out["usage"]["completion_tokens"], 7);
+        assert_eq!(out["usage"]["prompt_tokens_details"]["cached_tokens"], 6);
+
+        let blocked = json!({ "promptFeedback": { "blockReason": "SAFETY" } });
+        assert_eq!(from_gemini_response(&blocked)["choices"], json!([]));
+        let filtered = json!({ "candidates": [{ "finishReason": "SAFETY" }] });
+        assert_eq!(
+            from_gemini_response(&filtered)["choices"][0]["finish_reason"],
+            "content_filter"
+        );
+    }
+
+    #[test]
+    fn gemini_call_ids_round_trip_to_function_names() {
+        // 上一轮 from_gemini_response 生成的 call_{i} 在下一轮请求里要能找回函数名
+        let reply = from_gemini_response(&json!({
+            "candidates": [{
+                "content": { "parts": [{ "functionCall": { "name": "lookup", "args": {} } }] }
+            }]
+        }));
+        let assistant = reply["choices"][0]["message"].clone();
+        let body = json!({
+            "messages": [
+                { "role": "user", "content": "hi" },
+                assistant,
+                { "role": "tool", "tool_call_id": "call_0", "content": "ok" },
+                { "role": "tool", "tool_call_id": "call_7", "content": "?" }
+            ]
+        });
+        let out = to_gemini_request(&body);
+        let parts = out["contents"][2]["parts"].as_array().unwrap();
+        assert_eq!(parts[0]["functionResponse"]["name"], "lookup");
+        assert_eq!(parts[1]["functionResponse"]["name"], "tool");
+    }
+}
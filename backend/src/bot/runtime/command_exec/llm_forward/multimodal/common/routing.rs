@@ -0,0 +1,460 @@
+//! 模型映射的多目标路由：按策略（顺序故障转移 / 加权轮询 / 最低延迟）挑选提供商，
+//! 对连续失败的提供商熔断一段时间，并在日志中记录每次请求实际由哪个目标完成。
+
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use super::provider::ProviderKind;
+use super::LlmCallError;
+use crate::bot::runtime::llm_usage::ModelPrice;
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+const DEFAULT_COOLDOWN_SECS: u64 = 30;
+const MAX_COOLDOWN_SECS: u64 = 600;
+/// 延迟的指数滑动平均系数
+const LATENCY_EWMA_ALPHA: f64 = 0.3;
+
+/// 映射中的一个提供商/模型目标。
+#[derive(Debug, Clone)]
+pub(in super::super::super::super) struct LlmTarget {
+    pub(in super::super::super::super) provider_id: String,
+    pub(in super::super::super::super) kind: ProviderKind,
+    pub(in super::super::super::super) base_url: String,
+    pub(in super::super::super::super) api_key: String,
+    pub(in super::super::super::super) model_name: String,
+    pub(in super::super::super::super) max_request_bytes: u64,
+    pub(in super::super::super::super) weight: u32,
+    /// 用于计算费用的单价，未配置时只记 token
+    pub(in super::super::super::super) price: Option<ModelPrice>,
+}
+
+impl LlmTarget {
+    fn label(&self) -> String {
+        format!("{}/{}", self.provider_id, self.model_name)
+    }
+
+    /// 调用方按首选目标填写 `model`；切换到其他目标时替换成该目标的模型名。
+    pub(super) fn request_body<'a>(&self, body: &'a Value) -> Cow<'a, Value> {
+        if body.get("model").and_then(|m| m.as_str()) == Some(self.model_name.as_str()) {
+            return Cow::Borrowed(body);
+        }
+        let mut body = body.clone();
+        body["model"] = Value::String(self.model_name.clone());
+        Cow::Owned(body)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(in super::super::super::super) enum RouteStrategy {
+    /// 按配置顺序，失败时切换到下一个
+    #[default]
+    Failover,
+    /// 按权重平滑轮询，失败时按配置顺序尝试其余目标
+    Weighted,
+    /// 优先选择近期平均延迟最低的目标（没有数据的目标先试）
+    Latency,
+}
+
+impl RouteStrategy {
+    fn parse(raw: Option<&str>) -> Self {
+        match raw.map(|s| s.trim().to_ascii_lowercase()).as_deref() {
+            Some("weighted") | Some("round_robin") => RouteStrategy::Weighted,
+            Some("latency") | Some("lowest_latency") => RouteStrategy::Latency,
+            _ => RouteStrategy::Failover,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(in super::super::super::super) struct LlmRouteConfig {
+    pub(in super::super::super::super) mapping: String,
+    pub(in super::super::super::super) strategy: RouteStrategy,
+    failure_threshold: u32,
+    cooldown: Duration,
+}
+
+impl LlmRouteConfig {
+    /// `strategy` 来自模型映射，熔断参数来自 llm 模块配置的 `circuit_breaker`。
+    pub(super) fn new(mapping: &str, model_config: &Value, module_config: &Value) -> Self {
+        let breaker = module_config.get("circuit_breaker");
+        let failure_threshold = breaker
+            .and_then(|b| b.get("failure_threshold"))
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_FAILURE_THRESHOLD as u64)
+            .clamp(1, 100) as u32;
+        let cooldown_secs = breaker
+            .and_then(|b| b.get("cooldown_secs"))
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_COOLDOWN_SECS)
+            .clamp(1, MAX_COOLDOWN_SECS);
+        Self {
+            mapping: mapping.to_string(),
+            strategy: RouteStrategy::parse(model_config.get("strategy").and_then(|v| v.as_str())),
+            failure_threshold,
+            cooldown: Duration::from_secs(cooldown_secs),
+        }
+    }
+}
+
+/// 解析提供商条目得到一个目标；API Key 缺失时报错。单价取自 llm 模块配置的 `prices`。
+pub(super) fn resolve_target(
+    provider: &Value,
+    provider_id: &str,
+    model_name: &str,
+    weight: u32,
+    module_config: &Value,
+) -> Result<LlmTarget, String> {
+    let api_key = provider
+        .get("api_key")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+    if api_key.is_empty() {
+        return Err(format!(
+            "LLM 模块配置错误：提供商 '{}' 的 API Key 未设置",
+            provider_id
+        ));
+    }
+
+    let kind = ProviderKind::from_provider(provider);
+    let base_url = provider
+        .get("base_url")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or(kind.default_base_url())
+        .to_string();
+    let max_request_bytes = provider
+        .get("max_request_bytes")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(4_000_000)
+        .clamp(200_000, 200_000_000);
+
+    Ok(LlmTarget {
+        provider_id: provider_id.to_string(),
+        kind,
+        base_url,
+        api_key,
+        model_name: model_name.to_string(),
+        max_request_bytes,
+        weight: weight.max(1),
+        price: ModelPrice::lookup(module_config, provider_id, model_name),
+    })
+}
+
+#[derive(Default)]
+struct ProviderHealth {
+    consecutive_failures: u32,
+    /// 连续熔断次数，每次熔断冷却时间翻倍
+    trips: u32,
+    open_until: Option<Instant>,
+}
+
+#[derive(Default)]
+struct RouterState {
+    /// 按提供商 id 统计的健康状态
+    health: HashMap<String, ProviderHealth>,
+    /// 按 provider/model 统计的成功请求平均耗时（毫秒）
+    latency_ms: HashMap<String, f64>,
+    /// 按映射名保存的平滑加权轮询当前权重
+    weighted: HashMap<String, Vec<i64>>,
+}
+
+static ROUTER_STATE: OnceLock<Mutex<RouterState>> = OnceLock::new();
+
+fn router_state() -> std::sync::MutexGuard<'static, RouterState> {
+    ROUTER_STATE
+        .get_or_init(|| Mutex::new(RouterState::default()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// 提供商不健康的信号：传输错误、超时、限流与 5xx。其余错误（请求本身有问题）不切换目标。
+fn is_failover_error(err: &LlmCallError) -> bool {
+    match err {
+        LlmCallError::Transport(_) | LlmCallError::Decode(_) => true,
+        LlmCallError::Http { status, .. } => *status == 408 || *status == 429 || *status >= 500,
+        _ => false,
+    }
+}
+
+/// 一次 LLM 调用的目标顺序。用法：循环 `next_target`，把结果交给 `settle`，
+/// 返回 `Some` 即为最终结果，`None` 表示继续尝试下一个目标。
+pub(super) struct LlmRoute<'a> {
+    route: &'a LlmRouteConfig,
+    targets: &'a [LlmTarget],
+    queue: VecDeque<usize>,
+    started: Instant,
+    attempts: usize,
+}
+
+impl<'a> LlmRoute<'a> {
+    pub(super) fn new(route: &'a LlmRouteConfig, targets: &'a [LlmTarget]) -> Self {
+        let mut state = router_state();
+        let now = Instant::now();
+
+        let mut order: Vec<usize> = (0..targets.len()).collect();
+        match route.strategy {
+            RouteStrategy::Failover => {}
+            RouteStrategy::Weighted if targets.len() > 1 => {
+                let current = state.weighted.entry(route.mapping.clone()).or_default();
+                current.resize(targets.len(), 0);
+                let total: i64 = targets.iter().map(|t| t.weight as i64).sum();
+                for (w, t) in current.iter_mut().zip(targets) {
+                    *w += t.weight as i64;
+                }
+                let picked = (0..targets.len())
+                    .max_by_key(|&i| (current[i], std::cmp::Reverse(i)))
+                    .unwrap_or(0);
+                current[picked] -= total;
+                order.retain(|&i| i != picked);
+                order.insert(0, picked);
+            }
+            RouteStrategy::Weighted => {}
+            RouteStrategy::Latency => {
+                let latency = |i: usize| {
+                    state
+                        .latency_ms
+                        .get(&targets[i].label())
+                        .copied()
+                        .unwrap_or(0.0)
+                };
+                order.sort_by(|&a, &b| latency(a).total_cmp(&latency(b)));
+            }
+        }
+
+        // 熔断中的提供商跳过；全部熔断时仍按原顺序尝试，避免映射整体不可用
+        let is_open = |i: &usize| {
+            state
+                .health
+                .get(&targets[*i].provider_id)
+                .and_then(|h| h.open_until)
+                .is_some_and(|until| until > now)
+        };
+        let (closed, open): (Vec<usize>, Vec<usize>) = order.into_iter().partition(|i| !is_open(i));
+        let queue = if closed.is_empty() { open } else { closed };
+
+        Self {
+            route,
+            targets,
+            queue: queue.into(),
+            started: now,
+            attempts: 0,
+        }
+    }
+
+    pub(super) fn next_target(&mut self) -> Option<&'a LlmTarget> {
+        let idx = self.queue.pop_front()?;
+        self.started = Instant::now();
+        self.attempts += 1;
+        Some(&self.targets[idx])
+    }
+
+    /// 不再切换目标（例如流式输出已经开始），下一次 `settle` 直接返回结果。
+    pub(super) fn stop(&mut self) {
+        self.queue.clear();
+    }
+
+    pub(super) fn settle<T>(
+        &mut self,
+        target: &LlmTarget,
+        result: Result<T, LlmCallError>,
+    ) -> Option<Result<T, LlmCallError>> {
+        let elapsed = self.started.elapsed();
+        match result {
+            Ok(v) => {
+                self.record_success(target, elapsed);
+                info!(
+                    "LLM 映射 {} 由 {} 完成（耗时 {}ms，第 {} 个目标）",
+                    self.route.mapping,
+                    target.label(),
+                    elapsed.as_millis(),
+                    self.attempts
+                );
+                Some(Ok(v))
+            }
+            Err(e) if is_failover_error(&e) => {
+                self.record_failure(target);
+                if self.queue.is_empty() {
+                    return Some(Err(e));
+                }
+                warn!(
+                    "LLM 映射 {} 的目标 {} 失败，切换到下一个目标: {}",
+                    self.route.mapping,
+                    target.label(),
+                    e
+                );
+                None
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    fn record_success(&self, target: &LlmTarget, elapsed: Duration) {
+        let mut state = router_state();
+        if let Some(h) = state.health.get_mut(&target.provider_id) {
+            if h.trips > 0 {
+                info!("LLM 提供商 {} 已恢复", target.provider_id);
+            }
+            *h = ProviderHealth::default();
+        }
+        let ms = elapsed.as_millis() as f64;
+        state
+            .latency_ms
+            .entry(target.label())
+            .and_modify(|avg| *avg = *avg * (1.0 - LATENCY_EWMA_ALPHA) + ms * LATENCY_EWMA_ALPHA)
+            .or_insert(ms);
+    }
+
+    fn record_failure(&self, target: &LlmTarget) {
+        let mut state = router_state();
+        let h = state.health.entry(target.provider_id.clone()).or_default();
+        h.consecutive_failures += 1;
+        // 达到阈值后熔断；冷却结束后的试探请求再失败会立即重新熔断
+        if h.consecutive_failures >= self.route.failure_threshold {
+            h.trips += 1;
+            let cooldown = self
+                .route
+                .cooldown
+                .saturating_mul(1 << (h.trips - 1).min(6))
+                .min(Duration::from_secs(MAX_COOLDOWN_SECS));
+            h.open_until = Some(Instant::now() + cooldown);
+            warn!(
+                "LLM 提供商 {} 连续失败 {} 次，熔断 {} 秒",
+                target.provider_id,
+                h.consecutive_failures,
+                cooldown.as_secs()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// 路由状态是全局的，每个测试用各自的映射名与提供商 id
+    fn targets(prefix: &str, weights: &[u32]) -> Vec<LlmTarget> {
+        weights
+            .iter()
+            .enumerate()
+            .map(|(i, &weight)| {
+                let provider = json!({ "api_key": "k" });
+                resolve_target(&provider, &format!("{prefix}-{i}"), "m", weight, &json!({}))
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    fn route(mapping: &str, strategy: &str, module_config: Value) -> LlmRouteConfig {
+        LlmRouteConfig::new(mapping, &json!({ "strategy": strategy }), &module_config)
+    }
+
+    fn order(route: &LlmRouteConfig, targets: &[LlmTarget]) -> Vec<String> {
+        let mut r = LlmRoute::new(route, targets);
+        std::iter::from_fn(|| r.next_target().map(|t| t.provider_id.clone())).collect()
+    }
+
+    #[test]
+    fn failover_keeps_config_order() {
+        let targets = targets("failover", &[1, 1, 1]);
+        let route = route("failover", "", json!({}));
+        assert_eq!(
+            order(&route, &targets),
+            ["failover-0", "failover-1", "failover-2"]
+        );
+    }
+
+    #[test]
+    fn weighted_follows_smooth_round_robin() {
+        let targets = targets("weighted", &[5, 1, 1]);
+        let route = route("weighted", "weighted", json!({}));
+        let firsts: Vec<String> = (0..7).map(|_| order(&route, &targets).remove(0)).collect();
+        assert_eq!(
+            firsts,
+            [
+                "weighted-0",
+                "weighted-0",
+                "weighted-1",
+                "weighted-0",
+                "weighted-2",
+                "weighted-0",
+                "weighted-0"
+            ]
+        );
+        // 其余目标仍按配置顺序排在后面
+        assert_eq!(
+            order(&route, &targets),
+            ["weighted-0", "weighted-1", "weighted-2"]
+        );
+    }
+
+    #[test]
+    fn latency_prefers_fastest_and_untried() {
+        let targets = targets("latency", &[1, 1, 1]);
+        {
+            let mut state = router_state();
+            state.latency_ms.insert(targets[0].label(), 300.0);
+            state.latency_ms.insert(targets[1].label(), 50.0);
+        }
+        let route = route("latency", "latency", json!({}));
+        assert_eq!(
+            order(&route, &targets),
+            ["latency-2", "latency-1", "latency-0"]
+        );
+    }
+
+    #[test]
+    fn open_circuit_is_skipped_until_all_open() {
+        let targets = targets("breaker", &[1, 1]);
+        let route = route(
+            "breaker",
+            "failover",
+            json!({ "circuit_breaker": { "failure_threshold": 1 } }),
+        );
+
+        let mut r = LlmRoute::new(&route, &targets);
+        let first = r.next_target().unwrap();
+        let settled = r.settle::<()>(first, Err(LlmCallError::Transport("down".into())));
+        assert!(
+            settled.is_none(),
+            "failover error should try the next target"
+        );
+        assert_eq!(order(&route, &targets), ["breaker-1"]);
+
+        let mut r = LlmRoute::new(&route, &targets);
+        let second = r.next_target().unwrap();
+        let settled = r.settle::<()>(
+            second,
+            Err(LlmCallError::Http {
+                status: 503,
+                message: String::new(),
+            }),
+        );
+        assert!(settled.is_some(), "last target returns its error");
+        assert_eq!(order(&route, &targets), ["breaker-0", "breaker-1"]);
+    }
+
+    #[test]
+    fn request_errors_do_not_fail_over() {
+        let targets = targets("request-error", &[1, 1]);
+        let route = route("request-error", "failover", json!({}));
+        let mut r = LlmRoute::new(&route, &targets);
+        let first = r.next_target().unwrap();
+        let settled = r.settle::<()>(
+            first,
+            Err(LlmCallError::Http {
+                status: 400,
+                message: String::new(),
+            }),
+        );
+        assert!(matches!(
+            settled,
+            Some(Err(LlmCallError::Http { status: 400, .. }))
+        ));
+    }
+}
@@ -0,0 +1,222 @@
+//! LLM 的 SSE 流式调用：边收边把增量文本推给调用方，最后返回完整内容。
+
+use futures_util::StreamExt;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout, Duration};
+use tracing::warn;
+
+use super::provider::{
+    build_chat_request, normalize_chat_response, stream_delta, stream_error, stream_usage,
+};
+use super::{
+    acquire_llm_http_permit, extract_chat_content, parse_retry_after_seconds_from_headers,
+    parse_retry_after_seconds_from_message, record_response_usage, should_retry_llm_http_status,
+    LlmCallError, LlmConfig, LlmRoute, LlmTarget,
+};
+use crate::bot::runtime::llm_usage::{record_llm_usage, TokenUsage, UsageOrigin};
+
+/// 建立连接（拿到响应头）的超时
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+/// 两次收到数据之间的最长间隔；流式响应没有整体超时，只看是否“卡住”
+const IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+/// 整个流的硬上限，防止上游无限输出
+const TOTAL_TIMEOUT: Duration = Duration::from_secs(900);
+
+/// 流式调用 LLM（按提供商翻译请求与事件）。每收到一段增量就发到 `deltas`（接收端关闭不影响调用），
+/// 返回拼接后的完整内容。网关忽略 `stream` 直接返回 JSON 时，按非流式解析并一次性推送。
+/// 只在开始接收正文之前重试或切换备用目标；正文中途断开直接返回错误。
+pub(in super::super::super::super) async fn call_chat_completions_stream(
+    llm: &LlmConfig,
+    request_body: &serde_json::Value,
+    deltas: mpsc::UnboundedSender<String>,
+) -> Result<String, LlmCallError> {
+    let mut route = LlmRoute::new(&llm.route, &llm.targets);
+    while let Some(target) = route.next_target() {
+        let body = target.request_body(request_body);
+        let mut streamed = false;
+        let result = stream_target(target, &body, &llm.origin, &deltas, &mut streamed).await;
+        if streamed {
+            route.stop();
+        }
+        if let Some(result) = route.settle(target, result) {
+            return result;
+        }
+    }
+    Err(LlmCallError::Transport("没有可用的 LLM 目标".to_string()))
+}
+
+async fn stream_target(
+    llm: &LlmTarget,
+    request_body: &serde_json::Value,
+    origin: &UsageOrigin,
+    deltas: &mpsc::UnboundedSender<String>,
+    streamed: &mut bool,
+) -> Result<String, LlmCallError> {
+    let client = reqwest::Client::new();
+    let request = build_chat_request(
+        llm.kind,
+        &llm.base_url,
+        &llm.api_key,
+        request_body,
+        true,
+        llm.max_request_bytes,
+    )?;
+
+    let max_attempts: usize = 3;
+    let _permit = acquire_llm_http_permit().await?;
+
+    let mut attempt = 0usize;
+    let resp = loop {
+        let sent = timeout(
+            CONNECT_TIMEOUT,
+            request
+                .builder(&client)
+                .header("Accept", "text/event-stream")
+                .send(),
+        )
+        .await
+        .map_err(|_| LlmCallError::Transport("连接超时".to_string()))
+        .and_then(|r| r.map_err(|e| LlmCallError::Transport(e.to_string())));
+
+        let resp = match sent {
+            Ok(resp) => resp,
+            Err(err) => {
+                attempt += 1;
+                if attempt >= max_attempts {
+                    return Err(err);
+                }
+                let delay_ms = 300_u64
+                    .saturating_mul(2_u64.saturating_pow(attempt as u32 - 1))
+                    .min(3000);
+                warn!(
+                    "LLM stream request failed, retrying in {}ms: {}",
+                    delay_ms, err
+                );
+                sleep(Duration::from_millis(delay_ms)).await;
+                continue;
+            }
+        };
+
+        let status = resp.status();
+        if status.is_success() {
+            break resp;
+        }
+
+        let headers = resp.headers().clone();
+        let text = resp
+            .text()
+            .await
+            .map_err(|e| LlmCallError::Decode(e.to_string()))?;
+        let msg = serde_json::from_str::<serde_json::Value>(&text)
+            .ok()
+            .and_then(|v| {
+                stream_error(&v).or_else(|| {
+                    v.get("message")
+                        .and_then(|m| m.as_str())
+                        .map(|m| m.to_string())
+                })
+            })
+            .unwrap_or_else(|| text.chars().take(400).collect());
+        let http_status = status.as_u16();
+
+        attempt += 1;
+        if attempt >= max_attempts || !should_retry_llm_http_status(http_status) {
+            return Err(LlmCallError::Http {
+                status: http_status,
+                message: msg,
+            });
+        }
+        let delay_ms = if http_status == 429 {
+            parse_retry_after_seconds_from_headers(&headers)
+                .or_else(|| parse_retry_after_seconds_from_message(&msg))
+                .unwrap_or(1)
+                .clamp(1, 60)
+                * 1000
+        } else {
+            500_u64
+                .saturating_mul(2_u64.saturating_pow(attempt as u32 - 1))
+                .min(5000)
+        };
+        warn!(
+            "LLM stream HTTP {}, retrying in {}ms",
+            http_status, delay_ms
+        );
+        sleep(Duration::from_millis(delay_ms)).await;
+    };
+
+    let is_sse = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.contains("text/event-stream"));
+    if !is_sse {
+        let text = resp
+            .text()
+            .await
+            .map_err(|e| LlmCallError::Decode(e.to_string()))?;
+        let v: serde_json::Value =
+            serde_json::from_str(&text).map_err(|e| LlmCallError::Parse(e.to_string()))?;
+        let v = normalize_chat_response(llm.kind, v);
+        record_response_usage(origin, llm, &v);
+        let content = extract_chat_content(&v).ok_or(LlmCallError::MissingContent)?;
+        *streamed = true;
+        let _ = deltas.send(content.clone());
+        return Ok(content);
+    }
+
+    let started = tokio::time::Instant::now();
+    let mut stream = resp.bytes_stream();
+    let mut buf: Vec<u8> = Vec::new();
+    let mut content = String::new();
+    let mut usage: Option<TokenUsage> = None;
+
+    'outer: loop {
+        if started.elapsed() >= TOTAL_TIMEOUT {
+            return Err(LlmCallError::Transport("流式响应超过最长时长".to_string()));
+        }
+        let chunk = match timeout(IDLE_TIMEOUT, stream.next()).await {
+            Err(_) => return Err(LlmCallError::Transport("流式响应长时间无数据".to_string())),
+            Ok(None) => break,
+            Ok(Some(Err(e))) => return Err(LlmCallError::Decode(e.to_string())),
+            Ok(Some(Ok(chunk))) => chunk,
+        };
+        buf.extend_from_slice(&chunk);
+
+        while let Some(pos) = buf.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buf.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let Some(data) = line.trim().strip_prefix("data:") else {
+                continue;
+            };
+            let data = data.trim();
+            if data == "[DONE]" {
+                break 'outer;
+            }
+            let Ok(v) = serde_json::from_str::<serde_json::Value>(data) else {
+                continue;
+            };
+            if let Some(err) = stream_error(&v) {
+                return Err(LlmCallError::Http {
+                    status: 200,
+                    message: err,
+                });
+            }
+            if let Some(u) = stream_usage(llm.kind, &v) {
+                usage.get_or_insert_with(TokenUsage::default).merge(u);
+            }
+            if let Some(delta) = stream_delta(llm.kind, &v).filter(|d| !d.is_empty()) {
+                content.push_str(&delta);
+                *streamed = true;
+                let _ = deltas.send(delta);
+            }
+        }
+    }
+
+    if let Some(usage) = usage {
+        record_llm_usage(origin, &llm.provider_id, &llm.model_name, llm.price, usage);
+    }
+    if content.trim().is_empty() {
+        return Err(LlmCallError::MissingContent);
+    }
+    Ok(content)
+}
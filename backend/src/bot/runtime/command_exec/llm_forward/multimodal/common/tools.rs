@@ -0,0 +1,323 @@
+//! LLM 工具调用（function calling）：插件声明的工具与内置工具共用同一个调用循环，
+//! 这里定义工具描述、循环的限制，以及内置的 Tavily 联网搜索。
+
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// 内置联网搜索工具在插件 `tools` 里的名字
+pub(in super::super::super::super) const BUILTIN_WEB_SEARCH: &str = "web_search";
+/// 提供给模型的工具名（沿用原来的名字）
+const TAVILY_TOOL_NAME: &str = "tavily_search";
+
+const DEFAULT_MAX_ROUNDS: usize = 5;
+const MAX_ROUNDS_LIMIT: usize = 10;
+const DEFAULT_CALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub(in super::super::super::super) struct LlmTool {
+    pub(in super::super::super::super) name: String,
+    pub(in super::super::super::super) description: String,
+    /// JSON Schema，为空时按无参数处理
+    pub(in super::super::super::super) parameters: Value,
+}
+
+impl LlmTool {
+    pub(super) fn definition(&self) -> Value {
+        let parameters = if self.parameters.is_object() {
+            self.parameters.clone()
+        } else {
+            json!({ "type": "object", "properties": {} })
+        };
+        json!({
+            "type": "function",
+            "function": {
+                "name": self.name,
+                "description": self.description,
+                "parameters": parameters
+            }
+        })
+    }
+}
+
+/// 模型发起的一次工具调用
+#[derive(Debug, Clone)]
+pub(in super::super::super::super) struct LlmToolCall {
+    pub(in super::super::super::super) id: String,
+    pub(in super::super::super::super) name: String,
+    pub(in super::super::super::super) arguments: Value,
+}
+
+impl LlmToolCall {
+    /// 解析 OpenAI 格式的 `tool_calls[i]`；参数不是合法 JSON 时保留原始字符串。
+    pub(super) fn from_openai(tool_call: &Value) -> Self {
+        let function = tool_call.get("function");
+        let arguments = match function.and_then(|f| f.get("arguments")) {
+            Some(Value::String(s)) if s.trim().is_empty() => json!({}),
+            Some(Value::String(s)) => {
+                serde_json::from_str(s).unwrap_or_else(|_| Value::String(s.clone()))
+            }
+            Some(v) => v.clone(),
+            None => json!({}),
+        };
+        Self {
+            id: tool_call
+                .get("id")
+                .and_then(|i| i.as_str())
+                .unwrap_or("unknown")
+                .to_string(),
+            name: function
+                .and_then(|f| f.get("name"))
+                .and_then(|n| n.as_str())
+                .unwrap_or("")
+                .to_string(),
+            arguments,
+        }
+    }
+}
+
+/// 工具循环的限制
+#[derive(Debug, Clone)]
+pub(in super::super::super::super) struct ToolLoopLimits {
+    /// 最多执行几轮工具调用；用完后最后一轮禁止调用工具，要求模型直接回答
+    pub(in super::super::super::super) max_rounds: usize,
+    /// 单次工具执行的超时，超时后把错误信息作为工具结果交给模型
+    pub(in super::super::super::super) call_timeout: Duration,
+    /// 第一轮的 tool_choice（"auto" / "required" / 指定工具），之后的轮次都是 auto
+    pub(in super::super::super::super) tool_choice: Option<Value>,
+}
+
+impl Default for ToolLoopLimits {
+    fn default() -> Self {
+        Self {
+            max_rounds: DEFAULT_MAX_ROUNDS,
+            call_timeout: DEFAULT_CALL_TIMEOUT,
+            tool_choice: None,
+        }
+    }
+}
+
+impl ToolLoopLimits {
+    pub(in super::super::super::super) fn new(
+        max_rounds: Option<u32>,
+        timeout_ms: Option<u64>,
+        tool_choice: Option<Value>,
+    ) -> Self {
+        Self {
+            max_rounds: max_rounds
+                .map(|n| (n as usize).clamp(1, MAX_ROUNDS_LIMIT))
+                .unwrap_or(DEFAULT_MAX_ROUNDS),
+            call_timeout: timeout_ms
+                .map(|ms| Duration::from_millis(ms.clamp(1000, 120_000)))
+                .unwrap_or(DEFAULT_CALL_TIMEOUT),
+            tool_choice: tool_choice.filter(|c| !c.is_null()),
+        }
+    }
+
+    /// 第一轮使用调用方指定的 tool_choice，最后一轮禁止调用工具
+    pub(super) fn tool_choice_for_round(&self, round: usize) -> Value {
+        if round >= self.max_rounds {
+            json!("none")
+        } else if round == 0 {
+            self.tool_choice.clone().unwrap_or(json!("auto"))
+        } else {
+            json!("auto")
+        }
+    }
+}
+
+/// 内置的 Tavily 搜索工具
+pub(in super::super::super::super) fn web_search_tool() -> LlmTool {
+    LlmTool {
+        name: TAVILY_TOOL_NAME.to_string(),
+        description: "Search the web for current information using Tavily. Use this when you need to find up-to-date information, facts, news, or any information that might have changed after your knowledge cutoff.".to_string(),
+        parameters: json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "The search query to look up on the web"
+                }
+            },
+            "required": ["query"]
+        }),
+    }
+}
+
+/// 是否是内置工具的调用
+pub(in super::super::super::super) fn is_builtin_call(call: &LlmToolCall) -> bool {
+    call.name == TAVILY_TOOL_NAME
+}
+
+/// 执行内置工具，返回交给模型的结果文本。
+pub(in super::super::super::super) async fn run_builtin_tool(
+    call: &LlmToolCall,
+    tavily_api_key: Option<&str>,
+) -> String {
+    let query = call
+        .arguments
+        .get("query")
+        .and_then(|q| q.as_str())
+        .unwrap_or("");
+    let Some(key) = tavily_api_key.filter(|k| !k.is_empty()) else {
+        return "搜索失败: 未配置 Tavily API Key".to_string();
+    };
+
+    tracing::info!("Tavily 搜索: {}", query);
+    match call_tavily_search(key, query).await {
+        Ok(result) => result,
+        Err(e) => {
+            tracing::error!("Tavily 搜索失败: {}", e);
+            format!("搜索失败: {}", e)
+        }
+    }
+}
+
+/// 调用 Tavily 搜索 API
+async fn call_tavily_search(tavily_api_key: &str, query: &str) -> Result<String, String> {
+    let client = reqwest::Client::new();
+    let resp = client
+        .post("https://api.tavily.com/search")
+        .header("Content-Type", "application/json")
+        .json(&json!({
+            "api_key": tavily_api_key,
+            "query": query,
+            "search_depth": "basic",
+            "include_answer": true,
+            "include_raw_content": false,
+            "max_results": 5
+        }))
+        .timeout(std::time::Duration::from_secs(30))
+        .send()
+        .await
+        .map_err(|e| format!("Tavily 请求失败: {e}"))?;
+
+    let status = resp.status();
+    let text = resp
+        .text()
+        .await
+        .map_err(|e| format!("读取 Tavily 响应失败: {e}"))?;
+
+    if !status.is_success() {
+        return Err(format!("Tavily API 错误 (HTTP {}): {}", status, text));
+    }
+
+    let v: Value = serde_json::from_str(&text).map_err(|e| format!("解析 Tavily 响应失败: {e}"))?;
+
+    // 构建搜索结果摘要
+    let mut result = String::new();
+
+    // 如果有 answer，优先使用
+    if let Some(answer) = v.get("answer").and_then(|a| a.as_str()) {
+        result.push_str("## 搜索摘要\n");
+        result.push_str(answer);
+        result.push_str("\n\n");
+    }
+
+    // 添加搜索结果
+    if let Some(results) = v.get("results").and_then(|r| r.as_array()) {
+        result.push_str("## 搜索结果\n\n");
+        for (i, item) in results.iter().take(5).enumerate() {
+            let title = item
+                .get("title")
+                .and_then(|t| t.as_str())
+                .unwrap_or("无标题");
+            let url = item.get("url").and_then(|u| u.as_str()).unwrap_or("");
+            let content = item
+                .get("content")
+                .and_then(|c| c.as_str())
+                .unwrap_or("无内容");
+
+            result.push_str(&format!("### {}. {}\n", i + 1, title));
+            result.push_str(&format!("链接: {}\n", url));
+            result.push_str(&format!("{}\n\n", content));
+        }
+    }
+
+    if result.is_empty() {
+        result = "未找到相关搜索结果".to_string();
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tool_definition_defaults_to_empty_object_schema() {
+        let tool = LlmTool {
+            name: "roll".to_string(),
+            description: "掷骰子".to_string(),
+            parameters: Value::Null,
+        };
+        let def = tool.definition();
+        assert_eq!(def["type"], "function");
+        assert_eq!(def["function"]["name"], "roll");
+        assert_eq!(
+            def["function"]["parameters"],
+            json!({ "type": "object", "properties": {} })
+        );
+    }
+
+    #[test]
+    fn tool_call_arguments_parsed_from_openai() {
+        let call = LlmToolCall::from_openai(&json!({
+            "id": "call_1",
+            "function": { "name": "roll", "arguments": "{\"sides\":6}" }
+        }));
+        assert_eq!(call.id, "call_1");
+        assert_eq!(call.name, "roll");
+        assert_eq!(call.arguments, json!({ "sides": 6 }));
+
+        let args = |arguments: Value| {
+            LlmToolCall::from_openai(
+                &json!({ "function": { "name": "f", "arguments": arguments } }),
+            )
+            .arguments
+        };
+        assert_eq!(args(json!("  ")), json!({}));
+        assert_eq!(args(json!("not json")), json!("not json"));
+        assert_eq!(args(json!({ "a": 1 })), json!({ "a": 1 }));
+        assert_eq!(LlmToolCall::from_openai(&json!({})).id, "unknown");
+    }
+
+    #[test]
+    fn loop_limits_are_clamped() {
+        let limits = ToolLoopLimits::new(Some(100), Some(10), Some(Value::Null));
+        assert_eq!(limits.max_rounds, MAX_ROUNDS_LIMIT);
+        assert_eq!(limits.call_timeout, Duration::from_secs(1));
+        assert!(limits.tool_choice.is_none());
+
+        let limits = ToolLoopLimits::new(Some(0), Some(600_000), None);
+        assert_eq!(limits.max_rounds, 1);
+        assert_eq!(limits.call_timeout, Duration::from_secs(120));
+
+        let limits = ToolLoopLimits::new(None, None, None);
+        assert_eq!(limits.max_rounds, DEFAULT_MAX_ROUNDS);
+        assert_eq!(limits.call_timeout, DEFAULT_CALL_TIMEOUT);
+    }
+
+    #[test]
+    fn last_round_forbids_tool_calls() {
+        let limits = ToolLoopLimits::new(Some(2), None, Some(json!("required")));
+        assert_eq!(limits.tool_choice_for_round(0), json!("required"));
+        assert_eq!(limits.tool_choice_for_round(1), json!("auto"));
+        assert_eq!(limits.tool_choice_for_round(2), json!("none"));
+
+        let limits = ToolLoopLimits::new(Some(2), None, None);
+        assert_eq!(limits.tool_choice_for_round(0), json!("auto"));
+    }
+
+    #[test]
+    fn only_tavily_calls_are_builtin() {
+        let call = |name: &str| LlmToolCall {
+            id: "1".to_string(),
+            name: name.to_string(),
+            arguments: json!({}),
+        };
+        assert!(is_builtin_call(&call(&web_search_tool().name)));
+        assert!(!is_builtin_call(&call(BUILTIN_WEB_SEARCH)));
+        assert!(!is_builtin_call(&call("roll")));
+    }
+}
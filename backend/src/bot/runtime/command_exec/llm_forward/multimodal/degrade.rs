@@ -0,0 +1,244 @@
+//! 模型能力降级：模型映射的 `capabilities` 声明不支持图片/音频时，宿主先把媒体转成文字再交给模型。
+//! llm 模块配置：`vision_model`（用于描述图片的模型映射名，需支持识图）、
+//! `ocr`（`enabled` 默认开启，`lang` 默认 chi_sim+eng）；语音转写见 `transcribe`。
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde_json::{json, Value};
+use std::path::Path;
+use tracing::warn;
+
+use crate::models::SharedState;
+
+use super::common::{call_chat_completions, nonce12, resolve_llm_config_by_name, LlmConfig};
+use super::ocr::run_ocr;
+use super::transcribe::transcribe_audio_file;
+
+const DEFAULT_OCR_LANG: &str = "chi_sim+eng";
+const DESCRIBE_PROMPT: &str =
+    "请客观、详细地描述这张图片的内容：主体、场景、人物动作、图表数据，以及图中出现的文字。只输出描述。";
+
+struct DegradeSettings {
+    vision_model: Option<String>,
+    ocr_enabled: bool,
+    ocr_lang: String,
+}
+
+impl DegradeSettings {
+    fn load(state: &SharedState, bot_id: &str) -> Self {
+        let config = crate::module::get_effective_module(state, bot_id, "llm")
+            .map(|m| m.config)
+            .unwrap_or(Value::Null);
+        Self::from_config(&config)
+    }
+
+    fn from_config(config: &Value) -> Self {
+        let non_empty = |v: Option<&Value>| {
+            v.and_then(|v| v.as_str())
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        let ocr = config.get("ocr");
+        Self {
+            vision_model: non_empty(config.get("vision_model")),
+            ocr_enabled: ocr
+                .and_then(|o| o.get("enabled"))
+                .and_then(|v| v.as_bool())
+                .unwrap_or(true),
+            ocr_lang: non_empty(ocr.and_then(|o| o.get("lang")))
+                .unwrap_or_else(|| DEFAULT_OCR_LANG.to_string()),
+        }
+    }
+}
+
+/// 图片内容部件：模型支持识图时原样发送，否则替换为识图模型描述 + OCR 文字。
+pub(super) async fn image_part(
+    state: &SharedState,
+    bot_id: &str,
+    llm: &LlmConfig,
+    data_url: &str,
+) -> Result<Value, String> {
+    if llm.capabilities.vision {
+        return Ok(json!({ "type": "image_url", "image_url": { "url": data_url } }));
+    }
+    let text = describe_image(state, bot_id, data_url).await?;
+    Ok(json!({ "type": "text", "text": text }))
+}
+
+/// 把图片转写为文字（供不支持识图的模型使用）；描述与 OCR 都失败时返回 Err。
+pub(super) async fn describe_image(
+    state: &SharedState,
+    bot_id: &str,
+    data_url: &str,
+) -> Result<String, String> {
+    let settings = DegradeSettings::load(state, bot_id);
+    let mut errors: Vec<String> = Vec::new();
+
+    let description = match settings.vision_model.as_deref() {
+        Some(name) => match describe_with_vision_model(state, bot_id, name, data_url).await {
+            Ok(text) => Some(text),
+            Err(e) => {
+                warn!("识图模型描述图片失败: {}", e);
+                errors.push(format!("识图模型：{e}"));
+                None
+            }
+        },
+        None => {
+            errors.push("未配置识图模型（vision_model）".to_string());
+            None
+        }
+    };
+
+    let ocr_text = if settings.ocr_enabled {
+        match ocr_data_url(data_url, &settings.ocr_lang).await {
+            Ok(text) => Some(text),
+            Err(e) => {
+                warn!("OCR 识别失败: {}", e);
+                errors.push(format!("OCR：{e}"));
+                None
+            }
+        }
+    } else {
+        errors.push("OCR 已关闭".to_string());
+        None
+    };
+
+    if description.is_none() && ocr_text.is_none() {
+        return Err(format!(
+            "当前模型不支持识图，且无法转写图片（{}）",
+            errors.join("；")
+        ));
+    }
+
+    Ok(image_text(
+        description.as_deref(),
+        ocr_text.as_deref(),
+        &nonce12(),
+    ))
+}
+
+/// 转写结果包在带随机标记的不可信区块里，避免图中文字被当作指令
+fn image_text(description: Option<&str>, ocr_text: Option<&str>, nonce: &str) -> String {
+    let mut out = String::from("[图片内容（当前模型不支持识图，已自动转写）]\n");
+    out.push_str(&format!("<<BEGIN_UNTRUSTED_IMAGE_TEXT:{nonce}>>\n"));
+    if let Some(d) = description {
+        out.push_str(&format!("图片描述：\n{}\n", d.trim()));
+    }
+    match ocr_text {
+        Some("") => out.push_str("图中文字（OCR）：（未识别到文字）\n"),
+        Some(t) => out.push_str(&format!("图中文字（OCR）：\n{t}\n")),
+        None => {}
+    }
+    out.push_str(&format!("<<END_UNTRUSTED_IMAGE_TEXT:{nonce}>>"));
+    out
+}
+
+async fn describe_with_vision_model(
+    state: &SharedState,
+    bot_id: &str,
+    mapping: &str,
+    data_url: &str,
+) -> Result<String, String> {
+    let vision = resolve_llm_config_by_name(state, bot_id, Some(mapping))?;
+    if !vision.capabilities.vision {
+        return Err(format!("模型映射 '{mapping}' 未声明识图能力"));
+    }
+    let request_body = json!({
+        "model": vision.model_name,
+        "messages": [
+            {"role": "user", "content": [
+                {"type": "text", "text": DESCRIBE_PROMPT},
+                {"type": "image_url", "image_url": {"url": data_url}}
+            ]}
+        ],
+        "max_tokens": 1024
+    });
+    call_chat_completions(&vision, &request_body)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+async fn ocr_data_url(data_url: &str, lang: &str) -> Result<String, String> {
+    let (_, b64) = data_url
+        .split_once(";base64,")
+        .ok_or_else(|| "图片不是 base64 data URL".to_string())?;
+    let bytes = BASE64
+        .decode(b64)
+        .map_err(|e| format!("图片 base64 解码失败：{e}"))?;
+    run_ocr(&bytes, lang).await
+}
+
+/// 模型不支持音频输入时，把音频转写为文字。
+pub(super) async fn transcribe_audio(
+    state: &SharedState,
+    bot_id: &str,
+    llm: &LlmConfig,
+    path: &Path,
+    file_name: &str,
+) -> Result<String, String> {
+    let transcript = transcribe_audio_file(state, bot_id, llm, path, file_name, None)
+        .await
+        .map_err(|e| format!("当前模型不支持音频，语音转写失败：{e}"))?;
+
+    let nonce = nonce12();
+    Ok(format!(
+        "[语音内容（当前模型不支持音频，已自动转写）]\n<<BEGIN_UNTRUSTED_AUDIO_TRANSCRIPT:{nonce}>>\n{}\n<<END_UNTRUSTED_AUDIO_TRANSCRIPT:{nonce}>>",
+        transcript.trim()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_default_to_ocr_without_vision_model() {
+        let settings = DegradeSettings::from_config(&Value::Null);
+        assert!(settings.vision_model.is_none());
+        assert!(settings.ocr_enabled);
+        assert_eq!(settings.ocr_lang, DEFAULT_OCR_LANG);
+
+        let settings = DegradeSettings::from_config(&json!({
+            "vision_model": " vision ",
+            "ocr": { "enabled": false, "lang": "  " }
+        }));
+        assert_eq!(settings.vision_model.as_deref(), Some("vision"));
+        assert!(!settings.ocr_enabled);
+        assert_eq!(settings.ocr_lang, DEFAULT_OCR_LANG);
+    }
+
+    #[test]
+    fn image_text_wraps_description_and_ocr() {
+        let text = image_text(Some(" 一只猫 \n"), Some("HELLO"), "n1");
+        assert_eq!(
+            text,
+            "[图片内容（当前模型不支持识图，已自动转写）]\n\
+             <<BEGIN_UNTRUSTED_IMAGE_TEXT:n1>>\n\
+             图片描述：\n一只猫\n\
+             图中文字（OCR）：\nHELLO\n\
+             <<END_UNTRUSTED_IMAGE_TEXT:n1>>"
+        );
+    }
+
+    #[test]
+    fn image_text_notes_empty_ocr_and_skips_missing_parts() {
+        let text = image_text(None, Some(""), "n2");
+        assert!(text.contains("（未识别到文字）"));
+        assert!(!text.contains("图片描述"));
+
+        let text = image_text(Some("描述"), None, "n3");
+        assert!(!text.contains("OCR"));
+        assert!(text.ends_with("<<END_UNTRUSTED_IMAGE_TEXT:n3>>"));
+    }
+
+    #[tokio::test]
+    async fn ocr_rejects_non_base64_images() {
+        assert!(ocr_data_url("https://example.com/a.png", "eng")
+            .await
+            .unwrap_err()
+            .contains("data URL"));
+        assert!(ocr_data_url("data:image/png;base64,@@@", "eng")
+            .await
+            .unwrap_err()
+            .contains("解码失败"));
+    }
+}
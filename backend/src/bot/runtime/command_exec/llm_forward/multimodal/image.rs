@@ -8,11 +8,13 @@ use std::sync::Arc;
 use crate::bot::runtime::BotRuntime;
 use crate::models::SharedState;
 
+use super::super::super::llm_cache::LlmResponseCache;
 use super::super::LlmForwardImageFromUrlInput;
 use super::common::{
     call_chat_completions, download_binary_to_temp, log_llm_error, log_llm_len, reply_err,
     resolve_llm_config_by_name, send_llm_markdown_as_forward_image, SendForwardImageInput,
 };
+use super::degrade::image_part;
 
 #[derive(Debug, Clone)]
 pub(super) struct PreparedImageMeta {
@@ -135,6 +137,7 @@ pub(in super::super::super) async fn process_llm_forward_image_from_url(
     let system_prompt = input.system_prompt;
     let prompt = input.prompt;
     let title = input.title;
+    let tts = input.tts;
 
     let (guard, bin_meta) = match download_binary_to_temp(
         input.url,
@@ -166,6 +169,27 @@ pub(in super::super::super) async fn process_llm_forward_image_from_url(
         }
     };
 
+    let mut cache = LlmResponseCache::new(state, bot_id, &llm, "image");
+    cache.add_text(system_prompt);
+    cache.add_text(prompt);
+    cache.add_file(&guard.path).await;
+    if let Some(cached) = cache.get().await {
+        send_llm_markdown_as_forward_image(
+            state,
+            runtime,
+            bot_id,
+            SendForwardImageInput {
+                user_id,
+                group_id,
+                title,
+                tts,
+                markdown: &cached,
+            },
+        )
+        .await;
+        return;
+    }
+
     let (data_url, prepared_meta) = match prepare_image_data_url(
         &guard.path,
         input.max_width,
@@ -216,7 +240,8 @@ pub(in super::super::super) async fn process_llm_forward_image_from_url(
                 "height": prepared_meta.height,
                 "bytes": prepared_meta.output_bytes,
                 "jpeg_quality": prepared_meta.quality
-            }
+            },
+            "input_mode": if llm.capabilities.vision { "image_url_data" } else { "transcribed_text" }
         },
         "environment": {
             "bot_id": bot_id,
@@ -228,6 +253,14 @@ pub(in super::super::super) async fn process_llm_forward_image_from_url(
     });
     let ctx_pretty = serde_json::to_string_pretty(&ctx).unwrap_or_else(|_| ctx.to_string());
 
+    let image = match image_part(state, bot_id, &llm, &data_url).await {
+        Ok(v) => v,
+        Err(e) => {
+            reply_err(runtime, bot_id, user_id, group_id, &e).await;
+            return;
+        }
+    };
+
     let request_body = json!({
         "model": llm.model_name,
         "messages": [
@@ -235,20 +268,13 @@ pub(in super::super::super) async fn process_llm_forward_image_from_url(
             {"role": "system", "content": super::super::build_prompt_injection_guard()},
             {"role": "user", "content": [
                 {"type": "text", "text": format!("上下文信息（JSON）：\n{}", ctx_pretty)},
-                {"type": "image_url", "image_url": {"url": data_url}}
+                image
             ]}
         ],
         "max_tokens": 4096
     });
 
-    let reply_content = match call_chat_completions(
-        &llm.base_url,
-        &llm.api_key,
-        &request_body,
-        llm.max_request_bytes,
-    )
-    .await
-    {
+    let reply_content = match call_chat_completions(&llm, &request_body).await {
         Ok(t) => t,
         Err(e) => {
             log_llm_error("图片分析", &e.to_string());
@@ -265,6 +291,7 @@ pub(in super::super::super) async fn process_llm_forward_image_from_url(
     };
 
     log_llm_len("图片分析", reply_content.len());
+    cache.put(&reply_content).await;
     send_llm_markdown_as_forward_image(
         state,
         runtime,
@@ -273,6 +300,7 @@ pub(in super::super::super) async fn process_llm_forward_image_from_url(
             user_id,
             group_id,
             title,
+            tts,
             markdown: &reply_content,
         },
     )
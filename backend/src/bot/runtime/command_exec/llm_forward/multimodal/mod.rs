@@ -1,10 +1,15 @@
 mod audio;
 mod bundle;
 pub(in super::super) mod common;
+mod degrade;
 mod image;
+mod ocr;
+mod transcribe;
+mod tts;
 mod video;
 
 pub(in super::super) use audio::process_llm_forward_audio_from_url;
 pub(in super::super) use bundle::process_llm_forward_media_bundle;
 pub(in super::super) use image::process_llm_forward_image_from_url;
+pub(in super::super) use tts::{synthesize_voice, VoicePlatform};
 pub(in super::super) use video::process_llm_forward_video_from_url;
@@ -0,0 +1,107 @@
+//! 本地 OCR：优先运行本地 tesseract，缺失时以工具镜像 `docker run --rm` 临时运行。
+//! 图片经 stdin 传入，不需要挂载目录。
+
+use std::io::ErrorKind;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+use tracing::warn;
+
+const OCR_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// 识别图片中的文字；指定语言包缺失时回退到 eng。
+pub(super) async fn run_ocr(image: &[u8], lang: &str) -> Result<String, String> {
+    match run_tesseract(image, lang).await {
+        Err(e) if lang != "eng" && e.contains("Failed loading language") => {
+            warn!("OCR 语言包 {} 不可用，改用 eng：{}", lang, e);
+            run_tesseract(image, "eng").await
+        }
+        other => other,
+    }
+}
+
+async fn run_tesseract(image: &[u8], lang: &str) -> Result<String, String> {
+    let program = crate::tool::tesseract_program();
+    let mut local = Command::new(&program);
+    local.args(["stdin", "stdout", "-l", lang]);
+    match run_with_stdin(local, image).await {
+        Err(RunError::NotFound) => {}
+        other => return other.map_err(|e| e.describe(&program)),
+    }
+
+    let image_name = crate::tool::runner_image("tesseract")
+        .ok_or_else(|| "未注册 tesseract 工具".to_string())?;
+    let mut docker = Command::new("docker");
+    docker.args([
+        "run",
+        "--rm",
+        "-i",
+        "--network",
+        "none",
+        "--entrypoint",
+        "tesseract",
+        &image_name,
+        "stdin",
+        "stdout",
+        "-l",
+        lang,
+    ]);
+    run_with_stdin(docker, image).await.map_err(|e| match e {
+        RunError::NotFound => {
+            "tesseract 不存在：请安装 tesseract，或安装 docker 并在工具页拉取 tesseract 镜像"
+                .to_string()
+        }
+        e => e.describe("docker run tesseract"),
+    })
+}
+
+enum RunError {
+    NotFound,
+    Failed(String),
+}
+
+impl RunError {
+    fn describe(self, program: &str) -> String {
+        match self {
+            RunError::NotFound => format!("{program} 不存在"),
+            RunError::Failed(e) => format!("{program} 失败：{e}"),
+        }
+    }
+}
+
+async fn run_with_stdin(mut cmd: Command, input: &[u8]) -> Result<String, RunError> {
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| {
+            if e.kind() == ErrorKind::NotFound {
+                RunError::NotFound
+            } else {
+                RunError::Failed(e.to_string())
+            }
+        })?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input)
+            .await
+            .map_err(|e| RunError::Failed(format!("写入图片失败：{e}")))?;
+    }
+
+    let out = timeout(OCR_TIMEOUT, child.wait_with_output())
+        .await
+        .map_err(|_| RunError::Failed("识别超时".to_string()))?
+        .map_err(|e| RunError::Failed(e.to_string()))?;
+    if !out.status.success() {
+        return Err(RunError::Failed(format!(
+            "exit={}: {}",
+            out.status.code().unwrap_or(-1),
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
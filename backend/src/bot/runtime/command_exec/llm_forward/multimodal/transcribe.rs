@@ -0,0 +1,408 @@
+//! 语音转文字：按 bot 的 llm 模块配置 `stt` 选择后端。
+//! `backend`：`cloud`（默认，OpenAI 兼容 `/audio/transcriptions`）或 `local`（whisper.cpp）；
+//! `model`：云端模型（默认 whisper-1）；`language`：语言提示（默认 zh，`auto` 为自动识别）。
+//! 本地后端优先使用 `NBOT_WHISPER_URL`（whisper.cpp server 的 /inference 等），否则运行
+//! `NBOT_WHISPER_BIN`（默认 whisper-cli）并加载 `NBOT_WHISPER_MODEL` 模型。
+//! silk / amr 语音先转为 16k 单声道 wav（silk 需要 `NBOT_SILK_DECODER_BIN`）。
+
+use serde_json::Value;
+use std::io::ErrorKind;
+use std::path::Path;
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+use tracing::info;
+
+use crate::models::SharedState;
+
+use super::super::download::TempFileGuard;
+use super::common::{
+    call_audio_transcription, guess_transcription_mime, transcription_text, LlmConfig,
+};
+
+const DEFAULT_CLOUD_MODEL: &str = "whisper-1";
+const DEFAULT_LANGUAGE: &str = "zh";
+const LOCAL_TIMEOUT: Duration = Duration::from_secs(300);
+/// QQ silk 语音的采样率
+const SILK_SAMPLE_RATE: &str = "24000";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SttBackend {
+    Cloud,
+    Local,
+}
+
+struct SttSettings {
+    backend: SttBackend,
+    model: String,
+    language: Option<String>,
+}
+
+impl SttSettings {
+    fn load(state: &SharedState, bot_id: &str) -> Self {
+        let stt = crate::module::get_effective_module(state, bot_id, "llm")
+            .and_then(|m| m.config.get("stt").cloned())
+            .unwrap_or(Value::Null);
+        Self::from_config(&stt)
+    }
+
+    fn from_config(stt: &Value) -> Self {
+        let get = |key: &str| {
+            stt.get(key)
+                .and_then(|v| v.as_str())
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        let backend = match get("backend").as_deref() {
+            Some("local") => SttBackend::Local,
+            _ => SttBackend::Cloud,
+        };
+        let language = get("language").unwrap_or_else(|| DEFAULT_LANGUAGE.to_string());
+        Self {
+            backend,
+            model: get("model").unwrap_or_else(|| DEFAULT_CLOUD_MODEL.to_string()),
+            language: (!language.eq_ignore_ascii_case("auto")).then_some(language),
+        }
+    }
+}
+
+/// 转写音频文件。`model` 仅覆盖云端模型；本地后端始终使用本地模型。
+pub(super) async fn transcribe_audio_file(
+    state: &SharedState,
+    bot_id: &str,
+    llm: &LlmConfig,
+    path: &Path,
+    file_name: &str,
+    model: Option<&str>,
+) -> Result<String, String> {
+    let settings = SttSettings::load(state, bot_id);
+    let language = settings.language.as_deref();
+    match settings.backend {
+        SttBackend::Cloud => {
+            let model = model.unwrap_or(&settings.model);
+            let converted = if needs_conversion(path, file_name).await {
+                Some(convert_to_wav(path).await?)
+            } else {
+                None
+            };
+            let (path, file_name) = match &converted {
+                Some(wav) => (wav.path.as_path(), "audio.wav"),
+                None => (path, file_name),
+            };
+            call_audio_transcription(
+                &llm.base_url,
+                &llm.api_key,
+                model,
+                path,
+                file_name,
+                language,
+            )
+            .await
+        }
+        SttBackend::Local => {
+            let wav = convert_to_wav(path).await?;
+            let text = match crate::tool::whisper_url() {
+                Some(url) => transcribe_local_http(&url, &wav.path, language).await?,
+                None => transcribe_local_cli(&wav.path, language).await?,
+            };
+            info!("本地语音转写完成，文本长度: {}", text.len());
+            Ok(text)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AudioFormat {
+    Silk,
+    Amr,
+    Other,
+}
+
+async fn sniff_audio_format(path: &Path) -> Result<AudioFormat, String> {
+    use tokio::io::AsyncReadExt;
+    let mut head = [0u8; 16];
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| format!("读取音频失败: {e}"))?;
+    let n = file
+        .read(&mut head)
+        .await
+        .map_err(|e| format!("读取音频失败: {e}"))?;
+    let head = &head[..n];
+    // QQ 的 silk 语音在标准头前多一个 0x02
+    let silk = head.strip_prefix(b"\x02").unwrap_or(head);
+    Ok(if silk.starts_with(b"#!SILK_V3") {
+        AudioFormat::Silk
+    } else if head.starts_with(b"#!AMR") {
+        AudioFormat::Amr
+    } else {
+        AudioFormat::Other
+    })
+}
+
+/// 云端只接受常见格式：silk/amr 或无法识别扩展名时先转码
+async fn needs_conversion(path: &Path, file_name: &str) -> bool {
+    match sniff_audio_format(path).await {
+        Ok(AudioFormat::Other) => guess_transcription_mime(file_name) == "application/octet-stream",
+        _ => true,
+    }
+}
+
+/// 转为 whisper 需要的 16k 单声道 PCM wav
+async fn convert_to_wav(path: &Path) -> Result<TempFileGuard, String> {
+    let out = TempFileGuard::new("stt", Some("audio.wav")).await?;
+    let out_path = out.path.to_string_lossy().to_string();
+
+    let pcm = if sniff_audio_format(path).await? == AudioFormat::Silk {
+        Some(decode_silk(path).await?)
+    } else {
+        None
+    };
+    let mut args: Vec<String> = ["-hide_banner", "-loglevel", "error", "-y"]
+        .map(String::from)
+        .to_vec();
+    match &pcm {
+        Some(pcm) => {
+            args.extend(
+                ["-f", "s16le", "-ar", SILK_SAMPLE_RATE, "-ac", "1", "-i"].map(String::from),
+            );
+            args.push(pcm.path.to_string_lossy().to_string());
+        }
+        None => {
+            args.push("-i".to_string());
+            args.push(path.to_string_lossy().to_string());
+        }
+    }
+    args.extend(["-vn", "-ac", "1", "-ar", "16000", "-c:a", "pcm_s16le"].map(String::from));
+    args.push(out_path);
+
+    let program = crate::tool::ffmpeg_program();
+    run(&program, &args).await.map_err(|e| {
+        if e.kind == ErrorKind::NotFound {
+            format!("{program} 不存在：语音转码需要 ffmpeg")
+        } else {
+            format!("语音转码失败：{}", e.message)
+        }
+    })?;
+    Ok(out)
+}
+
+/// silk → 24k 单声道 s16le PCM
+async fn decode_silk(path: &Path) -> Result<TempFileGuard, String> {
+    let bytes = tokio::fs::read(path)
+        .await
+        .map_err(|e| format!("读取音频失败: {e}"))?;
+    let input = TempFileGuard::new("stt", Some("audio.silk")).await?;
+    tokio::fs::write(&input.path, bytes.strip_prefix(b"\x02").unwrap_or(&bytes))
+        .await
+        .map_err(|e| format!("Write temp file failed: {e}"))?;
+    let pcm = TempFileGuard::new("stt", Some("audio.pcm")).await?;
+
+    let program = crate::tool::silk_decoder_program();
+    let args = [
+        input.path.to_string_lossy().to_string(),
+        pcm.path.to_string_lossy().to_string(),
+        "-Fs_API".to_string(),
+        SILK_SAMPLE_RATE.to_string(),
+    ];
+    run(&program, &args).await.map_err(|e| {
+        if e.kind == ErrorKind::NotFound {
+            "语音为 silk 格式，需要 silk 解码器：请安装 silk_v3_decoder 或设置 NBOT_SILK_DECODER_BIN"
+                .to_string()
+        } else {
+            format!("silk 解码失败：{}", e.message)
+        }
+    })?;
+    Ok(pcm)
+}
+
+async fn transcribe_local_cli(wav: &Path, language: Option<&str>) -> Result<String, String> {
+    let model = crate::tool::whisper_model_path().ok_or_else(|| {
+        "本地语音转写未配置模型：请设置 NBOT_WHISPER_MODEL（ggml 模型文件路径）或 NBOT_WHISPER_URL"
+            .to_string()
+    })?;
+    let args = [
+        "-m".to_string(),
+        model,
+        "-f".to_string(),
+        wav.to_string_lossy().to_string(),
+        "-l".to_string(),
+        language.unwrap_or("auto").to_string(),
+        "-nt".to_string(),
+        "-np".to_string(),
+    ];
+    let program = crate::tool::whisper_program();
+    let stdout = run(&program, &args).await.map_err(|e| {
+        if e.kind == ErrorKind::NotFound {
+            format!(
+                "{program} 不存在：请安装 whisper.cpp 或设置 NBOT_WHISPER_BIN / NBOT_WHISPER_URL"
+            )
+        } else {
+            format!("本地语音转写失败：{}", e.message)
+        }
+    })?;
+    cli_transcript(&stdout).ok_or_else(|| "本地语音转写失败：未识别到内容".to_string())
+}
+
+/// whisper-cli（`-nt`）的输出：去掉空行与行首尾空白
+fn cli_transcript(stdout: &str) -> Option<String> {
+    let text = stdout
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    (!text.is_empty()).then_some(text)
+}
+
+/// whisper.cpp server（`/inference`）或其他接受 multipart `file` 的本地服务
+async fn transcribe_local_http(
+    url: &str,
+    wav: &Path,
+    language: Option<&str>,
+) -> Result<String, String> {
+    let bytes = tokio::fs::read(wav)
+        .await
+        .map_err(|e| format!("读取音频失败: {e}"))?;
+    let part = reqwest::multipart::Part::bytes(bytes)
+        .file_name("audio.wav")
+        .mime_str("audio/wav")
+        .map_err(|e| format!("构造音频 multipart 失败: {e}"))?;
+    let form = reqwest::multipart::Form::new()
+        .part("file", part)
+        .text("response_format", "json")
+        .text("temperature", "0")
+        .text("language", language.unwrap_or("auto").to_string());
+
+    let resp = reqwest::Client::new()
+        .post(url)
+        .multipart(form)
+        .timeout(LOCAL_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| format!("本地语音转写请求失败: {e}"))?;
+    let status = resp.status();
+    let text = resp
+        .text()
+        .await
+        .map_err(|e| format!("读取转写响应失败: {e}"))?;
+    if !status.is_success() {
+        let snippet: String = text.chars().take(400).collect();
+        return Err(format!("本地语音转写失败 (HTTP {status}): {snippet}"));
+    }
+    let v: Value = serde_json::from_str(&text).map_err(|e| format!("解析转写响应失败: {e}"))?;
+    transcription_text(&v)
+        .map(|s| s.trim().to_string())
+        .ok_or_else(|| "本地语音转写失败：无法获取文本".to_string())
+}
+
+struct RunError {
+    kind: ErrorKind,
+    message: String,
+}
+
+async fn run(program: &str, args: &[String]) -> Result<String, RunError> {
+    let out = timeout(
+        LOCAL_TIMEOUT,
+        Command::new(program).args(args).kill_on_drop(true).output(),
+    )
+    .await
+    .map_err(|_| RunError {
+        kind: ErrorKind::TimedOut,
+        message: "超时".to_string(),
+    })?
+    .map_err(|e| RunError {
+        kind: e.kind(),
+        message: e.to_string(),
+    })?;
+    if !out.status.success() {
+        return Err(RunError {
+            kind: ErrorKind::Other,
+            message: format!(
+                "exit={}: {}",
+                out.status.code().unwrap_or(-1),
+                String::from_utf8_lossy(&out.stderr).trim()
+            ),
+        });
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn settings_default_to_cloud_whisper_in_chinese() {
+        let settings = SttSettings::from_config(&Value::Null);
+        assert_eq!(settings.backend, SttBackend::Cloud);
+        assert_eq!(settings.model, DEFAULT_CLOUD_MODEL);
+        assert_eq!(settings.language.as_deref(), Some(DEFAULT_LANGUAGE));
+
+        let settings = SttSettings::from_config(&json!({
+            "backend": "local",
+            "model": " ",
+            "language": "AUTO"
+        }));
+        assert_eq!(settings.backend, SttBackend::Local);
+        assert_eq!(settings.model, DEFAULT_CLOUD_MODEL);
+        assert_eq!(settings.language, None);
+
+        let settings = SttSettings::from_config(&json!({ "backend": "bogus", "language": "en" }));
+        assert_eq!(settings.backend, SttBackend::Cloud);
+        assert_eq!(settings.language.as_deref(), Some("en"));
+    }
+
+    #[test]
+    fn cli_output_drops_blank_lines() {
+        assert_eq!(
+            cli_transcript("\n  你好 \n\n 世界\n").as_deref(),
+            Some("你好\n世界")
+        );
+        assert_eq!(cli_transcript(" \n\n"), None);
+    }
+
+    fn write_audio(name: &str, bytes: &[u8]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("nbot-stt-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    async fn sniff(name: &str, bytes: &[u8]) -> AudioFormat {
+        let path = write_audio(name, bytes);
+        let format = sniff_audio_format(&path).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        format
+    }
+
+    async fn convert(name: &str, bytes: &[u8]) -> bool {
+        let path = write_audio(name, bytes);
+        let needed = needs_conversion(&path, name).await;
+        std::fs::remove_file(&path).unwrap();
+        needed
+    }
+
+    #[tokio::test]
+    async fn sniffs_qq_silk_and_amr_headers() {
+        assert_eq!(
+            sniff("a.silk", b"\x02#!SILK_V3\x0c\x00").await,
+            AudioFormat::Silk
+        );
+        assert_eq!(sniff("b.silk", b"#!SILK_V3").await, AudioFormat::Silk);
+        assert_eq!(sniff("c.amr", b"#!AMR\n").await, AudioFormat::Amr);
+        assert_eq!(sniff("d.mp3", b"ID3\x04").await, AudioFormat::Other);
+        assert!(sniff_audio_format(Path::new("/nonexistent/audio"))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn cloud_conversion_only_for_unsupported_audio() {
+        assert!(!convert("e.mp3", b"ID3\x04").await);
+        assert!(convert("f.bin", b"ID3\x04").await);
+        assert!(convert("g.mp3", b"#!AMR\n").await);
+        assert!(convert("h.ogg", b"\x02#!SILK_V3").await);
+    }
+}
@@ -0,0 +1,364 @@
+//! 语音合成：按 bot 的 llm 模块配置 `tts` 选择后端。
+//! `backend`：`cloud`（默认，OpenAI 兼容 `/audio/speech`，使用 `model_mapping` 模型映射的地址与密钥，
+//! 默认 default_model）或 `local`；`model`：默认 tts-1；`voice`：默认 alloy；`max_chars`：朗读字数上限（默认 300）。
+//! 本地后端优先请求 `NBOT_TTS_URL`（OpenAI 兼容的 /audio/speech 完整地址），否则运行 `NBOT_TTS_BIN`
+//! （默认 piper）并加载 `NBOT_TTS_MODEL` 模型。
+//! 输出按平台编码：QQ 为 silk（需要 `NBOT_SILK_ENCODER_BIN`），缺少编码器时为 amr；Discord 为 ogg/opus。
+//! 转码失败时发送原始音频，由协议端自行处理。
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde_json::Value;
+use std::ffi::OsStr;
+use std::io::ErrorKind;
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+use tracing::{info, warn};
+
+use crate::bot::runtime::api::is_discord_bot;
+use crate::bot::runtime::BotRuntime;
+use crate::models::SharedState;
+
+use super::super::download::TempFileGuard;
+use super::common::{call_audio_speech, resolve_llm_config_by_name};
+
+const DEFAULT_MODEL: &str = "tts-1";
+const DEFAULT_VOICE: &str = "alloy";
+const DEFAULT_MAX_CHARS: u64 = 300;
+const LOCAL_TIMEOUT: Duration = Duration::from_secs(120);
+/// QQ silk 语音的采样率
+const SILK_SAMPLE_RATE: &str = "24000";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TtsBackend {
+    Cloud,
+    Local,
+}
+
+struct TtsSettings {
+    backend: TtsBackend,
+    model_mapping: Option<String>,
+    model: String,
+    voice: String,
+    max_chars: usize,
+}
+
+impl TtsSettings {
+    fn load(state: &SharedState, bot_id: &str) -> Self {
+        let tts = crate::module::get_effective_module(state, bot_id, "llm")
+            .and_then(|m| m.config.get("tts").cloned())
+            .unwrap_or(Value::Null);
+        let get = |key: &str| {
+            tts.get(key)
+                .and_then(|v| v.as_str())
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        let backend = match get("backend").as_deref() {
+            Some("local") => TtsBackend::Local,
+            _ => TtsBackend::Cloud,
+        };
+        let max_chars = tts
+            .get("max_chars")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_MAX_CHARS)
+            .clamp(10, 4000);
+        Self {
+            backend,
+            model_mapping: get("model_mapping"),
+            model: get("model").unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+            voice: get("voice").unwrap_or_else(|| DEFAULT_VOICE.to_string()),
+            max_chars: max_chars as usize,
+        }
+    }
+}
+
+/// 语音消息的目标平台
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(in super::super::super) enum VoicePlatform {
+    /// OneBot / Satori：silk，退而求其次 amr
+    Qq,
+    /// Discord：ogg/opus
+    Discord,
+}
+
+impl VoicePlatform {
+    pub(in super::super::super) async fn of_bot(runtime: &Arc<BotRuntime>, bot_id: &str) -> Self {
+        if is_discord_bot(runtime, bot_id).await {
+            Self::Discord
+        } else {
+            Self::Qq
+        }
+    }
+}
+
+/// 编码好的语音
+pub(in super::super::super) struct VoiceClip {
+    pub(in super::super::super) bytes: Vec<u8>,
+    /// silk | amr | ogg | mp3 | wav
+    pub(in super::super::super) format: &'static str,
+}
+
+impl VoiceClip {
+    /// 内联 base64 的 `[CQ:record]` 消息段
+    pub(in super::super::super) fn cq_segment(&self) -> String {
+        format!("[CQ:record,file=base64://{}]", BASE64.encode(&self.bytes))
+    }
+}
+
+/// 把文本合成为语音并按平台编码；`voice` 为空时使用配置的音色。
+pub(in super::super::super) async fn synthesize_voice(
+    state: &SharedState,
+    bot_id: &str,
+    text: &str,
+    voice: Option<&str>,
+    platform: VoicePlatform,
+) -> Result<VoiceClip, String> {
+    let settings = TtsSettings::load(state, bot_id);
+    let text = speakable_text(text, settings.max_chars);
+    if text.is_empty() {
+        return Err("没有可朗读的文字".to_string());
+    }
+    let voice = voice
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .unwrap_or(&settings.voice);
+
+    let (audio, format) = match settings.backend {
+        TtsBackend::Cloud => {
+            let llm = resolve_llm_config_by_name(state, bot_id, settings.model_mapping.as_deref())?;
+            let url = format!("{}/audio/speech", llm.base_url.trim_end_matches('/'));
+            let audio =
+                call_audio_speech(&url, &llm.api_key, &settings.model, &text, voice).await?;
+            (audio, "mp3")
+        }
+        TtsBackend::Local => match crate::tool::tts_url() {
+            Some(url) => (
+                call_audio_speech(&url, "", &settings.model, &text, voice).await?,
+                "mp3",
+            ),
+            None => (synthesize_local_cli(&text).await?, "wav"),
+        },
+    };
+    info!(
+        "语音合成完成：{} 字，{} bytes",
+        text.chars().count(),
+        audio.len()
+    );
+    Ok(encode_for_platform(audio, format, platform).await)
+}
+
+/// 去掉 CQ 码与 Markdown 标记，合并空白并截断到 `max_chars`
+fn speakable_text(text: &str, max_chars: usize) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("[CQ:") {
+        plain.push_str(&rest[..start]);
+        rest = match rest[start..].find(']') {
+            Some(end) => &rest[start + end + 1..],
+            None => "",
+        };
+    }
+    plain.push_str(rest);
+    let plain = plain
+        .replace("&#91;", "[")
+        .replace("&#93;", "]")
+        .replace("&#44;", ",")
+        .replace("&amp;", "&");
+    let plain: String = plain
+        .chars()
+        .filter(|c| !matches!(c, '*' | '#' | '`' | '~' | '>' | '|'))
+        .collect();
+    plain
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(max_chars)
+        .collect()
+}
+
+async fn synthesize_local_cli(text: &str) -> Result<Vec<u8>, String> {
+    let model = crate::tool::tts_model_path().ok_or_else(|| {
+        "本地语音合成未配置模型：请设置 NBOT_TTS_MODEL（piper 模型文件路径）或 NBOT_TTS_URL"
+            .to_string()
+    })?;
+    let out = TempFileGuard::new("tts", Some("speech.wav")).await?;
+    let args = [
+        "--model".to_string(),
+        model,
+        "--output_file".to_string(),
+        out.path.to_string_lossy().to_string(),
+    ];
+    let program = crate::tool::tts_program();
+    run(&program, &args, Some(text.as_bytes()))
+        .await
+        .map_err(|e| match e {
+            RunError::NotFound => {
+                format!("{program} 不存在：请安装 piper 或设置 NBOT_TTS_BIN / NBOT_TTS_URL")
+            }
+            RunError::Failed(e) => format!("本地语音合成失败：{e}"),
+        })?;
+    tokio::fs::read(&out.path)
+        .await
+        .map_err(|e| format!("读取合成语音失败: {e}"))
+}
+
+async fn encode_for_platform(
+    audio: Vec<u8>,
+    format: &'static str,
+    platform: VoicePlatform,
+) -> VoiceClip {
+    let encoded = match platform {
+        VoicePlatform::Discord => encode_ogg(&audio).await.map(|bytes| (bytes, "ogg")),
+        VoicePlatform::Qq => match encode_silk(&audio).await {
+            Ok(bytes) => Ok((bytes, "silk")),
+            Err(e) => {
+                info!("silk 编码不可用，改用 amr：{}", e);
+                encode_amr(&audio).await.map(|bytes| (bytes, "amr"))
+            }
+        },
+    };
+    match encoded {
+        Ok((bytes, format)) => VoiceClip { bytes, format },
+        Err(e) => {
+            warn!("语音转码失败，发送原始 {} 音频：{}", format, e);
+            VoiceClip {
+                bytes: audio,
+                format,
+            }
+        }
+    }
+}
+
+async fn encode_ogg(audio: &[u8]) -> Result<Vec<u8>, String> {
+    ffmpeg_pipe(
+        audio,
+        &[
+            "-ar", "48000", "-c:a", "libopus", "-b:a", "32k", "-f", "ogg",
+        ],
+    )
+    .await
+}
+
+async fn encode_amr(audio: &[u8]) -> Result<Vec<u8>, String> {
+    ffmpeg_pipe(
+        audio,
+        &[
+            "-ar",
+            "8000",
+            "-c:a",
+            "libopencore_amrnb",
+            "-b:a",
+            "12.2k",
+            "-f",
+            "amr",
+        ],
+    )
+    .await
+}
+
+/// 音频 → 24k 单声道 PCM → silk（`-tencent` 输出 QQ 使用的带 0x02 前缀格式）
+async fn encode_silk(audio: &[u8]) -> Result<Vec<u8>, String> {
+    let pcm = ffmpeg_pipe(audio, &["-ar", SILK_SAMPLE_RATE, "-f", "s16le"]).await?;
+    let input = TempFileGuard::new("tts", Some("voice.pcm")).await?;
+    tokio::fs::write(&input.path, &pcm)
+        .await
+        .map_err(|e| format!("Write temp file failed: {e}"))?;
+    let output = TempFileGuard::new("tts", Some("voice.silk")).await?;
+
+    let program = crate::tool::silk_encoder_program();
+    let args = [
+        input.path.to_string_lossy().to_string(),
+        output.path.to_string_lossy().to_string(),
+        "-Fs_API".to_string(),
+        SILK_SAMPLE_RATE.to_string(),
+        "-tencent".to_string(),
+    ];
+    run(&program, &args, None).await.map_err(|e| match e {
+        RunError::NotFound => {
+            format!("{program} 不存在：请安装 silk_v3_encoder 或设置 NBOT_SILK_ENCODER_BIN")
+        }
+        RunError::Failed(e) => format!("silk 编码失败：{e}"),
+    })?;
+    tokio::fs::read(&output.path)
+        .await
+        .map_err(|e| format!("读取 silk 语音失败: {e}"))
+}
+
+/// 经 stdin/stdout 用 ffmpeg 转码为单声道音频
+async fn ffmpeg_pipe(input: &[u8], output_args: &[&str]) -> Result<Vec<u8>, String> {
+    let mut args = vec![
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-i",
+        "pipe:0",
+        "-vn",
+        "-ac",
+        "1",
+    ];
+    args.extend_from_slice(output_args);
+    args.push("pipe:1");
+
+    let program = crate::tool::ffmpeg_program();
+    run(&program, &args, Some(input))
+        .await
+        .map_err(|e| match e {
+            RunError::NotFound => format!("{program} 不存在：语音转码需要 ffmpeg"),
+            RunError::Failed(e) => format!("语音转码失败：{e}"),
+        })
+}
+
+enum RunError {
+    NotFound,
+    Failed(String),
+}
+
+async fn run<S: AsRef<OsStr>>(
+    program: &str,
+    args: &[S],
+    input: Option<&[u8]>,
+) -> Result<Vec<u8>, RunError> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| {
+            if e.kind() == ErrorKind::NotFound {
+                RunError::NotFound
+            } else {
+                RunError::Failed(e.to_string())
+            }
+        })?;
+
+    // 边写边读，避免输出填满管道时互相等待
+    if let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) {
+        let input = input.to_vec();
+        tokio::spawn(async move {
+            let _ = stdin.write_all(&input).await;
+        });
+    }
+
+    let out = timeout(LOCAL_TIMEOUT, child.wait_with_output())
+        .await
+        .map_err(|_| RunError::Failed("超时".to_string()))?
+        .map_err(|e| RunError::Failed(e.to_string()))?;
+    if !out.status.success() {
+        return Err(RunError::Failed(format!(
+            "exit={}: {}",
+            out.status.code().unwrap_or(-1),
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+    Ok(out.stdout)
+}
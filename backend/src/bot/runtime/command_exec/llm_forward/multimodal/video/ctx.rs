@@ -4,6 +4,7 @@ use crate::models::SharedState;
 
 use super::super::common::BinaryMeta;
 use super::super::image::PreparedImageMeta;
+use super::frames::format_timestamp;
 
 pub(super) struct VideoCtxInput<'a> {
     pub(super) state: &'a SharedState,
@@ -16,6 +17,7 @@ pub(super) struct VideoCtxInput<'a> {
     pub(super) frames: &'a [(u64, PreparedImageMeta)],
     pub(super) frames_total: usize,
     pub(super) frames_selected: usize,
+    pub(super) frame_selection: &'a str,
     pub(super) transcript_included: bool,
 }
 
@@ -46,9 +48,12 @@ pub(super) fn build_video_ctx(input: VideoCtxInput<'_>) -> serde_json::Value {
             "duration_seconds": input.duration_seconds,
             "frames_total": input.frames_total,
             "frames_selected": input.frames_selected,
+            "frame_selection": input.frame_selection,
+            "timestamp_note": "每帧标注了视频内时间（m:ss），回答时可引用，如“在 0:42”",
             "frames": input.frames.iter().map(|(ts_ms, meta)| {
                 json!({
                     "timestamp_ms": ts_ms,
+                    "timestamp": format_timestamp(*ts_ms),
                     "prepared": {
                         "mime": meta.mime,
                         "width": meta.width,
@@ -21,6 +21,152 @@ pub(super) fn evenly_spaced_indices(total: usize, keep: usize) -> Vec<usize> {
         .collect()
 }
 
+/// 抽帧方式：`even` 按时长均匀取帧；`scene` 优先取画面切换处（ffmpeg 场景检测），剩余名额均匀补齐
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum FrameSelection {
+    Even,
+    Scene,
+}
+
+impl FrameSelection {
+    pub(super) fn parse(s: &str) -> Self {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "scene" => Self::Scene,
+            _ => Self::Even,
+        }
+    }
+
+    pub(super) fn as_str(self) -> &'static str {
+        match self {
+            Self::Even => "even",
+            Self::Scene => "scene",
+        }
+    }
+}
+
+/// 场景检测的候选阈值（ffmpeg scene 分数 0~1）
+const SCENE_THRESHOLD: f64 = 0.1;
+/// 场景切换后稍等再取帧，避开转场过渡
+const SCENE_FRAME_OFFSET: f64 = 0.2;
+
+/// 用 ffmpeg 的 scene 滤镜给画面切换打分，返回 (时间秒, 分数)
+async fn detect_scene_changes(video_path: &Path) -> Result<Vec<(f64, f64)>, String> {
+    let work_dir = video_path
+        .parent()
+        .ok_or_else(|| "视频路径无父目录".to_string())?;
+    let input_name = video_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| "视频文件名无效".to_string())?;
+    let filter =
+        format!("fps=5,scale=160:-2,select='gt(scene,{SCENE_THRESHOLD})',metadata=print:file=-");
+
+    let out = run_program(
+        "ffmpeg",
+        work_dir,
+        &[
+            "-hide_banner",
+            "-loglevel",
+            "error",
+            "-i",
+            input_name,
+            "-an",
+            "-vf",
+            &filter,
+            "-f",
+            "null",
+            "-",
+        ],
+    )
+    .await?;
+    if !out.status.success() {
+        return Err(format!(
+            "场景检测失败: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+    Ok(parse_scene_scores(&String::from_utf8_lossy(&out.stdout)))
+}
+
+/// 解析 `metadata=print` 输出：`frame:N pts:X pts_time:T` 后跟 `lavfi.scene_score=S`
+fn parse_scene_scores(output: &str) -> Vec<(f64, f64)> {
+    let mut scores = Vec::new();
+    let mut current: Option<f64> = None;
+    for line in output.lines() {
+        if let Some(pos) = line.find("pts_time:") {
+            current = line[pos + "pts_time:".len()..]
+                .split_whitespace()
+                .next()
+                .and_then(|t| t.parse().ok());
+        } else if let Some(score) = line.trim().strip_prefix("lavfi.scene_score=") {
+            if let (Some(t), Ok(score)) = (current.take(), score.parse::<f64>()) {
+                scores.push((t, score));
+            }
+        }
+    }
+    scores
+}
+
+/// 先按分数从高到低选场景切换点（彼此至少相隔 `min_spacing` 秒），再用最远点补齐剩余名额。
+fn select_keyframe_timestamps(
+    duration: f64,
+    scenes: &[(f64, f64)],
+    max_frames: usize,
+    min_spacing: f64,
+) -> Vec<f64> {
+    let far_enough = |picked: &[f64], t: f64| picked.iter().all(|&p| (p - t).abs() >= min_spacing);
+
+    let mut ranked: Vec<(f64, f64)> = scenes.to_vec();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let mut picked: Vec<f64> = Vec::with_capacity(max_frames);
+    // 给均匀补帧至少留一半名额，避免整段视频只剩切换处
+    let scene_budget = max_frames.saturating_sub(max_frames / 2).max(1);
+    for (t, _score) in ranked {
+        if picked.len() >= scene_budget {
+            break;
+        }
+        let t = (t + SCENE_FRAME_OFFSET).clamp(0.0, duration - 0.05);
+        if far_enough(&picked, t) {
+            picked.push(t);
+        }
+    }
+
+    let grid_len = max_frames * 4;
+    let grid: Vec<f64> = (0..grid_len)
+        .map(|i| ((i as f64 + 0.5) / grid_len as f64) * duration)
+        .collect();
+    while picked.len() < max_frames {
+        let best = grid
+            .iter()
+            .map(|&t| {
+                let gap = picked
+                    .iter()
+                    .map(|&p| (p - t).abs())
+                    .fold(f64::INFINITY, f64::min);
+                (t, gap)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        match best {
+            Some((t, gap)) if gap >= min_spacing => picked.push(t),
+            _ => break,
+        }
+    }
+
+    picked.sort_by(f64::total_cmp);
+    picked
+}
+
+/// 时间戳格式化为 m:ss（超过一小时为 h:mm:ss），便于模型在回答中引用
+pub(super) fn format_timestamp(ts_ms: u64) -> String {
+    let secs = ts_ms / 1000;
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{h}:{m:02}:{s:02}")
+    } else {
+        format!("{m}:{s:02}")
+    }
+}
+
 pub(super) async fn probe_video_duration_seconds(video_path: &Path) -> Option<f64> {
     let work_dir = video_path.parent()?;
     let file_name = video_path.file_name()?.to_str()?;
@@ -55,6 +201,7 @@ pub(super) async fn extract_video_frames_as_data_urls(
     max_height: u32,
     jpeg_quality: u8,
     frame_max_output_bytes: u64,
+    selection: FrameSelection,
 ) -> Result<Vec<(u64, String, PreparedImageMeta)>, String> {
     let work_dir = video_path
         .parent()
@@ -69,7 +216,22 @@ pub(super) async fn extract_video_frames_as_data_urls(
         .unwrap_or(0.0);
     let max_frames = max_frames.clamp(1, 24);
 
-    let timestamps: Vec<f64> = if duration > 0.1 {
+    let scenes = if selection == FrameSelection::Scene && duration > 0.1 {
+        match detect_scene_changes(video_path).await {
+            Ok(v) => Some(v),
+            Err(e) => {
+                warn!("场景检测失败，改为均匀抽帧: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let timestamps: Vec<f64> = if let Some(scenes) = scenes {
+        let min_spacing = (duration / max_frames as f64 / 2.0).clamp(0.3, 3.0);
+        select_keyframe_timestamps(duration, &scenes, max_frames as usize, min_spacing)
+    } else if duration > 0.1 {
         (0..max_frames)
             .map(|i| ((i as f64 + 0.5) / max_frames as f64) * duration)
             .collect()
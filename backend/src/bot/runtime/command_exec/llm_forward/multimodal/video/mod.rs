@@ -5,23 +5,25 @@ mod frames;
 
 use serde_json::json;
 use std::sync::Arc;
-use tracing::warn;
+use tracing::{info, warn};
 
 use crate::bot::runtime::BotRuntime;
 use crate::models::SharedState;
 
+use super::super::super::llm_cache::LlmResponseCache;
 use super::super::LlmForwardVideoFromUrlInput;
 use super::common::{
-    call_audio_transcription, call_chat_completions, download_binary_to_temp, log_llm_error,
-    log_llm_len, reply_err, resolve_llm_config_by_name, send_llm_markdown_as_forward_image,
-    SendForwardImageInput,
+    call_chat_completions, download_binary_to_temp, log_llm_error, log_llm_len, reply_err,
+    resolve_llm_config_by_name, send_llm_markdown_as_forward_image, SendForwardImageInput,
 };
+use super::degrade::image_part;
+use super::transcribe::transcribe_audio_file;
 
 use ctx::{build_video_ctx, VideoCtxInput};
 use direct::prepare_video_data_url_with_budget;
 use frames::{
-    evenly_spaced_indices, extract_audio_wav, extract_video_frames_as_data_urls,
-    probe_video_duration_seconds,
+    evenly_spaced_indices, extract_audio_wav, extract_video_frames_as_data_urls, format_timestamp,
+    probe_video_duration_seconds, FrameSelection,
 };
 
 pub(in super::super::super) async fn process_llm_forward_video_from_url(
@@ -35,6 +37,7 @@ pub(in super::super::super) async fn process_llm_forward_video_from_url(
     let system_prompt = input.system_prompt;
     let prompt = input.prompt;
     let title = input.title;
+    let tts = input.tts;
     let mode = input.mode.trim().to_ascii_lowercase();
 
     let (guard, bin_meta) = match download_binary_to_temp(
@@ -67,7 +70,41 @@ pub(in super::super::super) async fn process_llm_forward_video_from_url(
         }
     };
 
-    if mode == "direct" {
+    let mut cache = LlmResponseCache::new(state, bot_id, &llm, "video");
+    cache.add_text(system_prompt);
+    cache.add_text(prompt);
+    cache.add_text(&mode);
+    cache.add_text(input.frame_selection);
+    cache.add_text(&format!(
+        "{}/{}",
+        input.transcribe_audio, input.require_transcript
+    ));
+    cache.add_file(&guard.path).await;
+    if let Some(cached) = cache.get().await {
+        send_llm_markdown_as_forward_image(
+            state,
+            runtime,
+            bot_id,
+            SendForwardImageInput {
+                user_id,
+                group_id,
+                title,
+                tts,
+                markdown: &cached,
+            },
+        )
+        .await;
+        return;
+    }
+
+    // 模型不支持视频输入时改用抽帧模式
+    if mode == "direct" && !llm.capabilities.video {
+        info!(
+            "模型映射 {} 不支持视频输入，改用抽帧模式",
+            llm.route.mapping
+        );
+    }
+    if mode == "direct" && llm.capabilities.video {
         let mime_name = bin_meta
             .file_name
             .as_deref()
@@ -196,14 +233,7 @@ pub(in super::super::super) async fn process_llm_forward_video_from_url(
                 "max_tokens": 4096
             });
 
-            let reply_content = match call_chat_completions(
-                &llm.base_url,
-                &llm.api_key,
-                &request_body,
-                llm.max_request_bytes,
-            )
-            .await
-            {
+            let reply_content = match call_chat_completions(&llm, &request_body).await {
                 Ok(t) => t,
                 Err(e) => {
                     let retryable =
@@ -229,6 +259,7 @@ pub(in super::super::super) async fn process_llm_forward_video_from_url(
             };
 
             log_llm_len("视频分析", reply_content.len());
+            cache.put(&reply_content).await;
             send_llm_markdown_as_forward_image(
                 state,
                 runtime,
@@ -237,6 +268,7 @@ pub(in super::super::super) async fn process_llm_forward_video_from_url(
                     user_id,
                     group_id,
                     title,
+                    tts,
                     markdown: &reply_content,
                 },
             )
@@ -251,6 +283,7 @@ pub(in super::super::super) async fn process_llm_forward_video_from_url(
         return;
     }
 
+    let selection = FrameSelection::parse(input.frame_selection);
     let frames = match extract_video_frames_as_data_urls(
         &guard.path,
         input.max_frames,
@@ -258,6 +291,7 @@ pub(in super::super::super) async fn process_llm_forward_video_from_url(
         input.frame_max_height,
         input.frame_jpeg_quality,
         input.frame_max_output_bytes,
+        selection,
     )
     .await
     {
@@ -286,15 +320,26 @@ pub(in super::super::super) async fn process_llm_forward_video_from_url(
         }
     };
 
+    let mut frame_parts = Vec::with_capacity(frames.len());
+    for (_ts_ms, data_url, _meta) in &frames {
+        match image_part(state, bot_id, &llm, data_url).await {
+            Ok(part) => frame_parts.push(part),
+            Err(e) => {
+                reply_err(runtime, bot_id, user_id, group_id, &e).await;
+                return;
+            }
+        }
+    }
+
     let transcript = if input.transcribe_audio {
-        let transcription_model = input.transcription_model.unwrap_or("whisper-1");
         match extract_audio_wav(&guard.path, input.max_audio_seconds).await {
-            Ok(audio) => match call_audio_transcription(
-                &llm.base_url,
-                &llm.api_key,
-                transcription_model,
+            Ok(audio) => match transcribe_audio_file(
+                state,
+                bot_id,
+                &llm,
                 &audio.path,
                 "audio.wav",
+                input.transcription_model,
             )
             .await
             {
@@ -368,6 +413,7 @@ pub(in super::super::super) async fn process_llm_forward_video_from_url(
             frames: &ctx_frames,
             frames_total,
             frames_selected: ctx_frames.len(),
+            frame_selection: selection.as_str(),
             transcript_included: transcript.is_some(),
         });
         let ctx_pretty = serde_json::to_string_pretty(&ctx).unwrap_or_else(|_| ctx.to_string());
@@ -384,14 +430,16 @@ pub(in super::super::super) async fn process_llm_forward_video_from_url(
             content_parts.push(json!({ "type": "text", "text": format!("{begin}\n{t}\n{end}") }));
         }
         for (pos, idx) in indices.iter().enumerate() {
-            let Some((ts_ms, data_url, _meta)) = frames.get(*idx) else {
+            let (Some((ts_ms, _url, _meta)), Some(part)) =
+                (frames.get(*idx), frame_parts.get(*idx))
+            else {
                 continue;
             };
             content_parts.push(json!({
                 "type": "text",
-                "text": format!("Frame {} @ {}ms", pos + 1, ts_ms)
+                "text": format!("Frame {} @ {}", pos + 1, format_timestamp(*ts_ms))
             }));
-            content_parts.push(json!({ "type": "image_url", "image_url": { "url": data_url } }));
+            content_parts.push(part.clone());
         }
 
         let request_body = json!({
@@ -404,14 +452,7 @@ pub(in super::super::super) async fn process_llm_forward_video_from_url(
             "max_tokens": 4096
         });
 
-        let reply_content = match call_chat_completions(
-            &llm.base_url,
-            &llm.api_key,
-            &request_body,
-            llm.max_request_bytes,
-        )
-        .await
-        {
+        let reply_content = match call_chat_completions(&llm, &request_body).await {
             Ok(t) => t,
             Err(e) => {
                 let retryable = matches!(&e, super::common::LlmCallError::RequestTooLarge { .. })
@@ -436,6 +477,7 @@ pub(in super::super::super) async fn process_llm_forward_video_from_url(
         };
 
         log_llm_len("视频分析", reply_content.len());
+        cache.put(&reply_content).await;
         send_llm_markdown_as_forward_image(
             state,
             runtime,
@@ -444,6 +486,7 @@ pub(in super::super::super) async fn process_llm_forward_video_from_url(
                 user_id,
                 group_id,
                 title,
+                tts,
                 markdown: &reply_content,
             },
         )
@@ -0,0 +1,168 @@
+//! 插件图片生成（`nbot.llm.image`）。配置位于 llm 模块的 `images`：
+//! `model_mapping`（图片模型的映射名，未配置时不可用）、`size`（默认 1024x1024）与 `quality`（可选）。
+//! 提供 `inputImageUrl` 时改走 `/images/edits` 编辑该图片。
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde_json::{json, Value};
+use tracing::info;
+
+use crate::models::SharedState;
+
+use super::llm_forward::multimodal::common::{
+    call_image_generation, download_and_prepare_image_data_url, ImageGenerationRequest, LlmConfig,
+};
+
+const DEFAULT_SIZE: &str = "1024x1024";
+const INPUT_TIMEOUT_MS: u64 = 30_000;
+const INPUT_MAX_BYTES: u64 = 20 * 1024 * 1024;
+const INPUT_MAX_SIDE: u32 = 2048;
+
+pub(super) struct ImageSettings {
+    pub(super) model_mapping: Option<String>,
+    size: String,
+    quality: Option<String>,
+}
+
+impl ImageSettings {
+    pub(super) fn load(state: &SharedState, bot_id: &str) -> Self {
+        let images = crate::module::get_effective_module(state, bot_id, "llm")
+            .and_then(|m| m.config.get("images").cloned())
+            .unwrap_or(Value::Null);
+        Self::from_config(&images)
+    }
+
+    fn from_config(images: &Value) -> Self {
+        let get = |key: &str| {
+            images
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        Self {
+            model_mapping: get("model_mapping"),
+            size: get("size").unwrap_or_else(|| DEFAULT_SIZE.to_string()),
+            quality: get("quality"),
+        }
+    }
+}
+
+/// 生成图片，返回给插件的 JSON：`base64`、`mime`、`segment`（可直接发送的 `[CQ:image]`）与 `revisedPrompt`
+pub(super) async fn generate_image(
+    llm: &LlmConfig,
+    settings: &ImageSettings,
+    prompt: &str,
+    size: Option<&str>,
+    quality: Option<&str>,
+    input_image_url: Option<&str>,
+) -> Result<Value, String> {
+    let prompt = prompt.trim();
+    if prompt.is_empty() {
+        return Err("提示词不能为空".to_string());
+    }
+    let input_image = match input_image_url {
+        Some(url) => Some(load_input_image(url).await?),
+        None => None,
+    };
+    let request = ImageGenerationRequest {
+        prompt,
+        size: size.unwrap_or(&settings.size),
+        quality: quality.or(settings.quality.as_deref()),
+        input_image: input_image.as_deref(),
+    };
+    let image = call_image_generation(llm, &request)
+        .await
+        .map_err(|e| format!("图片生成失败: {e}"))?;
+    info!(
+        "图片生成完成：{} bytes{}",
+        image.bytes.len(),
+        if input_image.is_some() {
+            "（编辑）"
+        } else {
+            ""
+        }
+    );
+
+    let base64 = BASE64.encode(&image.bytes);
+    Ok(json!({
+        "segment": format!("[CQ:image,file=base64://{base64}]"),
+        "base64": base64,
+        "mime": image.mime,
+        "revisedPrompt": image.revised_prompt,
+    }))
+}
+
+/// 读取待编辑的图片：支持 data URL、`base64://` 与 http(s) 地址
+async fn load_input_image(url: &str) -> Result<Vec<u8>, String> {
+    let url = url.trim();
+    let data_url = if url.starts_with("data:") || url.starts_with("base64://") {
+        url.to_string()
+    } else if url.starts_with("http://") || url.starts_with("https://") {
+        download_and_prepare_image_data_url(
+            url,
+            INPUT_TIMEOUT_MS,
+            INPUT_MAX_BYTES,
+            INPUT_MAX_SIDE,
+            INPUT_MAX_SIDE,
+            90,
+            INPUT_MAX_BYTES,
+        )
+        .await
+        .map_err(|e| format!("下载待编辑图片失败: {e}"))?
+    } else {
+        return Err("inputImageUrl 需为 http(s) 地址、data URL 或 base64://".to_string());
+    };
+    let encoded = data_url
+        .strip_prefix("base64://")
+        .or_else(|| data_url.split_once(";base64,").map(|(_, b)| b))
+        .ok_or_else(|| "无法解析待编辑图片的 data URL".to_string())?;
+    BASE64
+        .decode(encoded.trim())
+        .map_err(|e| format!("待编辑图片 base64 解码失败: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_require_model_mapping() {
+        let settings = ImageSettings::from_config(&Value::Null);
+        assert!(settings.model_mapping.is_none());
+        assert_eq!(settings.size, DEFAULT_SIZE);
+        assert!(settings.quality.is_none());
+
+        let settings = ImageSettings::from_config(&json!({
+            "model_mapping": " image ",
+            "size": "1792x1024",
+            "quality": ""
+        }));
+        assert_eq!(settings.model_mapping.as_deref(), Some("image"));
+        assert_eq!(settings.size, "1792x1024");
+        assert!(settings.quality.is_none());
+    }
+
+    #[tokio::test]
+    async fn input_image_accepts_inline_base64() {
+        let encoded = BASE64.encode(b"\x89PNG");
+        assert_eq!(
+            load_input_image(&format!("base64://{encoded}"))
+                .await
+                .unwrap(),
+            b"\x89PNG"
+        );
+        assert_eq!(
+            load_input_image(&format!(" data:image/png;base64,{encoded} "))
+                .await
+                .unwrap(),
+            b"\x89PNG"
+        );
+    }
+
+    #[tokio::test]
+    async fn input_image_rejects_other_sources() {
+        assert!(load_input_image("file:///etc/passwd").await.is_err());
+        assert!(load_input_image("data:image/png,raw").await.is_err());
+        assert!(load_input_image("base64://@@@").await.is_err());
+    }
+}
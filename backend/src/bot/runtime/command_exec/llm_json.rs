@@ -0,0 +1,382 @@
+//! 插件 callLlmChat 的结构化输出：按 response_format 要求模型输出 JSON（提供商支持时透传），
+//! 宿主再按 JSON Schema 校验，不通过时带着错误列表让模型修正，成功后把解析好的 JSON 交给插件。
+
+use crate::models::SharedState;
+use crate::plugin::runtime::LlmResponseFormat;
+use serde_json::{json, Value};
+use tracing::warn;
+
+use super::llm_cache::LlmResponseCache;
+use super::llm_forward::multimodal::common::{call_chat_completions, LlmConfig};
+
+const DEFAULT_MAX_REPAIRS: u32 = 2;
+const MAX_REPAIRS: u32 = 5;
+/// 单次校验最多报告的错误条数（避免修复提示过长）
+const MAX_ERRORS: usize = 8;
+
+/// 以结构化输出调用 LLM；返回 (原文, 解析后的 JSON)。
+pub(super) async fn call_llm_chat_json(
+    state: &SharedState,
+    bot_id: &str,
+    llm: &LlmConfig,
+    request_body: &Value,
+    format: &LlmResponseFormat,
+) -> Result<(String, Value), String> {
+    let schema = format.schema.as_ref().filter(|s| s.is_object());
+    let mut body = request_body.clone();
+    body["response_format"] = match schema {
+        Some(schema) => json!({
+            "type": "json_schema",
+            "json_schema": {
+                "name": schema_name(&format.name),
+                "schema": schema,
+                "strict": format.strict,
+            }
+        }),
+        None => json!({ "type": "json_object" }),
+    };
+
+    // 只缓存通过校验的回复，键为首次请求体
+    let mut cache = LlmResponseCache::new(state, bot_id, llm, "chat");
+    cache.add_json(&body);
+    if let Some(cached) = cache.get().await {
+        if let Ok(value) = check_reply(&cached, schema) {
+            return Ok((cached, value));
+        }
+    }
+
+    let max_repairs = format
+        .max_repairs
+        .unwrap_or(DEFAULT_MAX_REPAIRS)
+        .min(MAX_REPAIRS);
+    let mut repairs = 0;
+    loop {
+        let content = call_chat_completions(llm, &body)
+            .await
+            .map_err(|e| e.to_string())?;
+        let errors = match check_reply(&content, schema) {
+            Ok(value) => {
+                cache.put(&content).await;
+                return Ok((content, value));
+            }
+            Err(errors) => errors,
+        };
+        if repairs >= max_repairs {
+            return Err(format!("模型输出的 JSON 未通过校验：{}", errors.join("；")));
+        }
+        repairs += 1;
+        warn!(
+            "[{}] 模型输出的 JSON 未通过校验，要求修正（第 {} 次）：{}",
+            bot_id,
+            repairs,
+            errors.join("；")
+        );
+        if let Some(messages) = body["messages"].as_array_mut() {
+            messages.push(json!({ "role": "assistant", "content": content }));
+            messages.push(json!({ "role": "user", "content": repair_prompt(&errors) }));
+        }
+    }
+}
+
+fn schema_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+        .take(64)
+        .collect();
+    if name.is_empty() {
+        "response".to_string()
+    } else {
+        name
+    }
+}
+
+fn repair_prompt(errors: &[String]) -> String {
+    let list: Vec<String> = errors.iter().map(|e| format!("- {e}")).collect();
+    format!(
+        "上面的输出不符合要求：\n{}\n请修正后重新输出完整的 JSON，只输出 JSON 本身，不要代码块或其它文字。",
+        list.join("\n")
+    )
+}
+
+fn check_reply(content: &str, schema: Option<&Value>) -> Result<Value, Vec<String>> {
+    let value = parse_json_reply(content).map_err(|e| vec![format!("不是合法的 JSON：{e}")])?;
+    let mut errors = Vec::new();
+    if let Some(schema) = schema {
+        validate(schema, &value, "$", &mut errors);
+    }
+    if errors.is_empty() {
+        Ok(value)
+    } else {
+        Err(errors)
+    }
+}
+
+/// 解析模型输出的 JSON：容忍 ```json 代码块与前后的多余文字
+fn parse_json_reply(content: &str) -> Result<Value, String> {
+    let mut text = content.trim();
+    if let Some(rest) = text.strip_prefix("```") {
+        let rest = rest.split_once('\n').map_or("", |(_, body)| body);
+        text = rest.trim_end().strip_suffix("```").unwrap_or(rest).trim();
+    }
+    let err = match serde_json::from_str::<Value>(text) {
+        Ok(v) => return Ok(v),
+        Err(e) => e.to_string(),
+    };
+    let start = text.find(['{', '[']);
+    let end = text.rfind(['}', ']']);
+    if let (Some(start), Some(end)) = (start, end) {
+        if start < end {
+            if let Ok(v) = serde_json::from_str::<Value>(&text[start..=end]) {
+                return Ok(v);
+            }
+        }
+    }
+    Err(err)
+}
+
+fn type_matches(ty: &str, value: &Value) -> bool {
+    match ty {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|f| f.fract() == 0.0)
+        }
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+/// JSON Schema 的常用子集：type / enum / const / properties / required /
+/// additionalProperties / items / 长度与数值范围 / anyOf / oneOf（按 anyOf 处理）/ allOf。
+fn validate(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    if errors.len() >= MAX_ERRORS {
+        return;
+    }
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+
+    if let Some(ty) = schema.get("type") {
+        let types: Vec<&str> = match ty {
+            Value::String(s) => vec![s.as_str()],
+            Value::Array(items) => items.iter().filter_map(|t| t.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|t| type_matches(t, value)) {
+            errors.push(format!("{path}：类型应为 {}", types.join(" 或 ")));
+            return;
+        }
+    }
+    if let Some(options) = schema.get("enum").and_then(|e| e.as_array()) {
+        if !options.contains(value) {
+            errors.push(format!(
+                "{path}：取值应为 {} 之一",
+                Value::Array(options.clone())
+            ));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            errors.push(format!("{path}：取值应为 {expected}"));
+        }
+    }
+
+    for key in ["anyOf", "oneOf"] {
+        if let Some(branches) = schema.get(key).and_then(|b| b.as_array()) {
+            let ok = branches.iter().any(|branch| {
+                let mut branch_errors = Vec::new();
+                validate(branch, value, path, &mut branch_errors);
+                branch_errors.is_empty()
+            });
+            if !ok {
+                errors.push(format!("{path}：不符合 {key} 中的任何一种结构"));
+            }
+        }
+    }
+    if let Some(branches) = schema.get("allOf").and_then(|b| b.as_array()) {
+        for branch in branches {
+            validate(branch, value, path, errors);
+        }
+    }
+
+    match value {
+        Value::Object(map) => {
+            for name in schema
+                .get("required")
+                .and_then(|r| r.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|n| n.as_str())
+            {
+                if !map.contains_key(name) {
+                    errors.push(format!("{path}：缺少必需字段 {name}"));
+                }
+            }
+            let properties = schema.get("properties").and_then(|p| p.as_object());
+            for (key, item) in map {
+                let child = format!("{path}.{key}");
+                match properties.and_then(|p| p.get(key)) {
+                    Some(sub) => validate(sub, item, &child, errors),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            errors.push(format!("{path}：不允许出现字段 {key}"))
+                        }
+                        Some(sub @ Value::Object(_)) => validate(sub, item, &child, errors),
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            let len = items.len() as u64;
+            if let Some(min) = schema.get("minItems").and_then(|n| n.as_u64()) {
+                if len < min {
+                    errors.push(format!("{path}：至少需要 {min} 个元素"));
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(|n| n.as_u64()) {
+                if len > max {
+                    errors.push(format!("{path}：最多只能有 {max} 个元素"));
+                }
+            }
+            if let Some(sub) = schema.get("items").filter(|s| s.is_object()) {
+                for (i, item) in items.iter().enumerate() {
+                    validate(sub, item, &format!("{path}[{i}]"), errors);
+                }
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(|n| n.as_u64()) {
+                if len < min {
+                    errors.push(format!("{path}：长度至少为 {min}"));
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(|n| n.as_u64()) {
+                if len > max {
+                    errors.push(format!("{path}：长度最多为 {max}"));
+                }
+            }
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or(0.0);
+            if let Some(min) = schema.get("minimum").and_then(|m| m.as_f64()) {
+                if n < min {
+                    errors.push(format!("{path}：不能小于 {min}"));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(|m| m.as_f64()) {
+                if n > max {
+                    errors.push(format!("{path}：不能大于 {max}"));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors_for(schema: Value, value: Value) -> Vec<String> {
+        let mut errors = Vec::new();
+        validate(&schema, &value, "$", &mut errors);
+        errors
+    }
+
+    #[test]
+    fn accepts_matching_object() {
+        let schema = json!({
+            "type": "object",
+            "required": ["name", "tags"],
+            "additionalProperties": false,
+            "properties": {
+                "name": { "type": "string", "minLength": 1 },
+                "age": { "type": ["integer", "null"], "minimum": 0 },
+                "tags": { "type": "array", "items": { "enum": ["a", "b"] }, "maxItems": 2 }
+            }
+        });
+        let value = json!({ "name": "x", "age": 3.0, "tags": ["a"] });
+        assert!(errors_for(schema, value).is_empty());
+    }
+
+    #[test]
+    fn reports_nested_errors_with_paths() {
+        let schema = json!({
+            "type": "object",
+            "required": ["name"],
+            "additionalProperties": false,
+            "properties": {
+                "tags": { "type": "array", "items": { "type": "string" }, "minItems": 2 }
+            }
+        });
+        let errors = errors_for(schema, json!({ "tags": [1], "extra": true }));
+        assert!(
+            errors.contains(&"$：缺少必需字段 name".to_string()),
+            "{errors:?}"
+        );
+        assert!(
+            errors.contains(&"$：不允许出现字段 extra".to_string()),
+            "{errors:?}"
+        );
+        assert!(
+            errors.contains(&"$.tags：至少需要 2 个元素".to_string()),
+            "{errors:?}"
+        );
+        assert!(
+            errors.contains(&"$.tags[0]：类型应为 string".to_string()),
+            "{errors:?}"
+        );
+    }
+
+    #[test]
+    fn checks_ranges_and_constants() {
+        let schema = json!({ "type": "number", "minimum": 1, "maximum": 5 });
+        assert_eq!(errors_for(schema.clone(), json!(0)).len(), 1);
+        assert_eq!(errors_for(schema, json!(6.5)).len(), 1);
+        assert_eq!(
+            errors_for(json!({ "type": "integer" }), json!(1.5)).len(),
+            1
+        );
+        assert_eq!(errors_for(json!({ "const": "ok" }), json!("no")).len(), 1);
+        assert_eq!(
+            errors_for(json!({ "maxLength": 2 }), json!("你好啊")).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn combines_branches() {
+        let any_of = json!({ "anyOf": [{ "type": "string" }, { "type": "integer" }] });
+        assert!(errors_for(any_of.clone(), json!(1)).is_empty());
+        assert_eq!(errors_for(any_of, json!(true)).len(), 1);
+        let all_of = json!({ "allOf": [{ "minimum": 1 }, { "maximum": 2 }] });
+        assert!(errors_for(all_of.clone(), json!(2)).is_empty());
+        assert_eq!(errors_for(all_of, json!(3)).len(), 1);
+    }
+
+    #[test]
+    fn caps_error_count() {
+        let schema = json!({ "items": { "type": "string" } });
+        let errors = errors_for(schema, json!([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]));
+        assert_eq!(errors.len(), MAX_ERRORS);
+    }
+
+    #[test]
+    fn parses_fenced_and_wrapped_replies() {
+        assert_eq!(
+            parse_json_reply("```json\n{\"a\": 1}\n```").unwrap(),
+            json!({ "a": 1 })
+        );
+        assert_eq!(
+            parse_json_reply("结果如下：[1, 2] 以上").unwrap(),
+            json!([1, 2])
+        );
+        assert!(parse_json_reply("没有 JSON").is_err());
+    }
+}
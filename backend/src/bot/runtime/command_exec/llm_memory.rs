@@ -0,0 +1,750 @@
+//! 宿主侧的 LLM 对话记忆：插件用 `conversationId` 调用 callLlmChat 时，由这里拼接历史。
+//!
+//! 对话按 bot/插件/conversationId 保存在 `data/llm/conversations.db`，记录触发者的群与用户。
+//! 发送时按 token 预算截取最近的消息，更早的内容由摘要代替；历史超出预算后，
+//! 在后台用配置的模型（llm 模块 `memory.summary_model`）把较早的轮次合并进摘要。
+
+use crate::models::SharedState;
+use crate::plugin::runtime::LlmConversationOptions;
+use once_cell::sync::Lazy;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use super::super::llm_usage::UsageScope;
+use super::super::state_db::{now_secs, with_db, SharedDb};
+use super::llm_forward::multimodal::common::{call_chat_completions, resolve_llm_config_by_name};
+
+const MEMORY_DB: &str = "data/llm/conversations.db";
+const DEFAULT_MAX_CONTEXT_TOKENS: u64 = 4000;
+/// 超过这么久没有更新的对话在启动后清理
+const IDLE_RETENTION: Duration = Duration::from_secs(30 * 24 * 3600);
+const SUMMARY_MAX_TOKENS: u32 = 800;
+const SUMMARY_PROMPT: &str = "你负责维护一段对话的记忆摘要。请把“已有摘要”和“新增对话”合并成一份新的摘要：\
+保留人物、事实、偏好、约定和未完成的事项，省略寒暄与重复内容，使用对话中的语言，只输出摘要正文，不超过 500 字。";
+
+static DB: SharedDb = SharedDb::new(MEMORY_DB, "对话记忆数据库", init_db);
+/// 正在后台摘要的对话，避免同一对话并发摘要
+static SUMMARIZING: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// llm 模块配置 `memory` 中的设置（插件可按调用覆盖预算与超时）
+#[derive(Debug, Clone)]
+struct MemorySettings {
+    /// 历史消息（含摘要）的 token 预算
+    max_context_tokens: u64,
+    /// 用于生成摘要的模型映射，为空时使用默认模型
+    summary_model: Option<String>,
+    /// 超过这么久没有对话则重新开始，0 表示不超时
+    session_timeout: Duration,
+}
+
+impl MemorySettings {
+    fn from_state(state: &SharedState, bot_id: &str, options: &LlmConversationOptions) -> Self {
+        let module = crate::module::get_effective_module(state, bot_id, "llm");
+        let memory = module
+            .as_ref()
+            .and_then(|m| m.config.get("memory"))
+            .unwrap_or(&Value::Null);
+
+        let max_context_tokens = options
+            .max_context_tokens
+            .or_else(|| memory.get("max_context_tokens").and_then(|v| v.as_u64()))
+            .unwrap_or(DEFAULT_MAX_CONTEXT_TOKENS)
+            .clamp(200, 200_000);
+        let session_timeout_minutes = options
+            .session_timeout_minutes
+            .or_else(|| {
+                memory
+                    .get("session_timeout_minutes")
+                    .and_then(|v| v.as_u64())
+            })
+            .unwrap_or(0);
+        Self {
+            max_context_tokens,
+            summary_model: memory
+                .get("summary_model")
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string()),
+            session_timeout: Duration::from_secs(session_timeout_minutes.saturating_mul(60)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ConversationKey {
+    bot_id: String,
+    plugin_id: String,
+    conversation_id: String,
+}
+
+impl ConversationKey {
+    fn label(&self) -> String {
+        format!(
+            "{}/{}/{}",
+            self.bot_id, self.plugin_id, self.conversation_id
+        )
+    }
+}
+
+struct StoredMessage {
+    id: i64,
+    role: String,
+    content: String,
+    tokens: u64,
+}
+
+/// 粗略估算 token 数：CJK 字符按 1 个，其余按 4 个字符 1 个。
+fn estimate_tokens(text: &str) -> u64 {
+    let mut cjk = 0u64;
+    let mut other = 0u64;
+    for c in text.chars() {
+        if matches!(c as u32, 0x2E80..=0x9FFF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF | 0xFF00..=0xFFEF)
+        {
+            cjk += 1;
+        } else {
+            other += 1;
+        }
+    }
+    cjk + other.div_ceil(4) + 4
+}
+
+/// 历史里只保存文本；图片等附件换成占位符，避免保存大体积的 data URL。
+fn content_for_history(content: &Value) -> String {
+    match content {
+        Value::String(s) => s.clone(),
+        Value::Array(parts) => {
+            let mut out = String::new();
+            for part in parts {
+                let text = match part.get("type").and_then(|t| t.as_str()) {
+                    Some("text") => part
+                        .get("text")
+                        .and_then(|t| t.as_str())
+                        .unwrap_or("")
+                        .to_string(),
+                    Some("image_url") => "[图片]".to_string(),
+                    Some(other) => format!("[{}]", other),
+                    None => part.as_str().unwrap_or("").to_string(),
+                };
+                if !text.is_empty() {
+                    if !out.is_empty() {
+                        out.push('\n');
+                    }
+                    out.push_str(&text);
+                }
+            }
+            out
+        }
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn init_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "PRAGMA journal_mode = WAL;
+         PRAGMA synchronous = NORMAL;
+         CREATE TABLE IF NOT EXISTS conversations (
+             bot_id TEXT NOT NULL,
+             plugin_id TEXT NOT NULL,
+             conversation_id TEXT NOT NULL,
+             group_id INTEGER,
+             user_id INTEGER,
+             summary TEXT NOT NULL DEFAULT '',
+             updated_at INTEGER NOT NULL,
+             PRIMARY KEY (bot_id, plugin_id, conversation_id)
+         );
+         CREATE TABLE IF NOT EXISTS conversation_messages (
+             id INTEGER PRIMARY KEY AUTOINCREMENT,
+             bot_id TEXT NOT NULL,
+             plugin_id TEXT NOT NULL,
+             conversation_id TEXT NOT NULL,
+             role TEXT NOT NULL,
+             content TEXT NOT NULL,
+             tokens INTEGER NOT NULL,
+             time INTEGER NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS idx_conversation_messages_key
+             ON conversation_messages(bot_id, plugin_id, conversation_id, id);",
+    )
+    .map_err(|e| format!("初始化对话记忆数据库失败: {e}"))
+}
+
+fn purge_idle(conn: &Connection) -> Result<(), rusqlite::Error> {
+    let cutoff = now_secs().saturating_sub(IDLE_RETENTION.as_secs()) as i64;
+    let removed = conn.execute(
+        "DELETE FROM conversation_messages WHERE (bot_id, plugin_id, conversation_id) IN
+             (SELECT bot_id, plugin_id, conversation_id FROM conversations WHERE updated_at < ?1)",
+        params![cutoff],
+    )?;
+    conn.execute(
+        "DELETE FROM conversations WHERE updated_at < ?1",
+        params![cutoff],
+    )?;
+    if removed > 0 {
+        info!("已清理 {} 条长期未使用的对话记忆", removed);
+    }
+    Ok(())
+}
+
+/// 首次打开时建表并清理长期未使用的对话
+fn init_db(conn: &Connection) -> Result<(), String> {
+    init_schema(conn)?;
+    if let Err(e) = purge_idle(conn) {
+        warn!("清理对话记忆失败: {}", e);
+    }
+    Ok(())
+}
+
+fn delete_conversation(conn: &Connection, key: &ConversationKey) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "DELETE FROM conversation_messages
+         WHERE bot_id = ?1 AND plugin_id = ?2 AND conversation_id = ?3",
+        params![key.bot_id, key.plugin_id, key.conversation_id],
+    )?;
+    conn.execute(
+        "DELETE FROM conversations WHERE bot_id = ?1 AND plugin_id = ?2 AND conversation_id = ?3",
+        params![key.bot_id, key.plugin_id, key.conversation_id],
+    )?;
+    Ok(())
+}
+
+/// 读取摘要与全部历史（按时间顺序）；对话已超时则先清空。
+fn load_history(
+    conn: &Connection,
+    key: &ConversationKey,
+    session_timeout: Duration,
+) -> Result<(String, Vec<StoredMessage>), rusqlite::Error> {
+    let row: Option<(String, i64)> = conn
+        .query_row(
+            "SELECT summary, updated_at FROM conversations
+             WHERE bot_id = ?1 AND plugin_id = ?2 AND conversation_id = ?3",
+            params![key.bot_id, key.plugin_id, key.conversation_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let Some((summary, updated_at)) = row else {
+        return Ok((String::new(), Vec::new()));
+    };
+    if !session_timeout.is_zero()
+        && now_secs().saturating_sub(updated_at.max(0) as u64) > session_timeout.as_secs()
+    {
+        delete_conversation(conn, key)?;
+        return Ok((String::new(), Vec::new()));
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT id, role, content, tokens FROM conversation_messages
+         WHERE bot_id = ?1 AND plugin_id = ?2 AND conversation_id = ?3 ORDER BY id",
+    )?;
+    let messages = stmt
+        .query_map(
+            params![key.bot_id, key.plugin_id, key.conversation_id],
+            |row| {
+                Ok(StoredMessage {
+                    id: row.get(0)?,
+                    role: row.get(1)?,
+                    content: row.get(2)?,
+                    tokens: row.get::<_, i64>(3)?.max(0) as u64,
+                })
+            },
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((summary, messages))
+}
+
+fn is_system(message: &Value) -> bool {
+    message.get("role").and_then(|r| r.as_str()) == Some("system")
+}
+
+/// 调用方的 system 消息 → 摘要 → 预算内的最近历史 → 本次新消息。
+fn compose_prompt(
+    messages: &[Value],
+    summary: &str,
+    history: &[StoredMessage],
+    max_context_tokens: u64,
+) -> Vec<Value> {
+    let (system, rest): (Vec<&Value>, Vec<&Value>) = messages.iter().partition(|m| is_system(m));
+
+    let mut prompt: Vec<Value> = system.into_iter().cloned().collect();
+    let mut budget = max_context_tokens;
+    if !summary.trim().is_empty() {
+        budget = budget.saturating_sub(estimate_tokens(summary));
+        prompt.push(json!({
+            "role": "system",
+            "content": format!("以下是之前对话的摘要：\n{}", summary)
+        }));
+    }
+    let mut window: Vec<&StoredMessage> = Vec::new();
+    for m in history.iter().rev() {
+        if m.tokens > budget {
+            break;
+        }
+        budget -= m.tokens;
+        window.push(m);
+    }
+    prompt.extend(
+        window
+            .into_iter()
+            .rev()
+            .map(|m| json!({ "role": m.role, "content": m.content })),
+    );
+    prompt.extend(rest.into_iter().cloned());
+    prompt
+}
+
+/// 写入一轮消息并更新对话，返回历史的 token 总数。
+fn append_messages(
+    conn: &mut Connection,
+    key: &ConversationKey,
+    group_id: Option<u64>,
+    user_id: Option<u64>,
+    rows: &[(String, String)],
+) -> Result<u64, rusqlite::Error> {
+    let now = now_secs() as i64;
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT INTO conversations
+             (bot_id, plugin_id, conversation_id, group_id, user_id, summary, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, '', ?6)
+         ON CONFLICT(bot_id, plugin_id, conversation_id) DO UPDATE SET
+             group_id = COALESCE(excluded.group_id, group_id),
+             user_id = COALESCE(excluded.user_id, user_id),
+             updated_at = excluded.updated_at",
+        params![
+            key.bot_id,
+            key.plugin_id,
+            key.conversation_id,
+            group_id.map(|g| g as i64),
+            user_id.map(|u| u as i64),
+            now
+        ],
+    )?;
+    for (role, content) in rows {
+        tx.execute(
+            "INSERT INTO conversation_messages
+                 (bot_id, plugin_id, conversation_id, role, content, tokens, time)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                key.bot_id,
+                key.plugin_id,
+                key.conversation_id,
+                role,
+                content,
+                estimate_tokens(content) as i64,
+                now
+            ],
+        )?;
+    }
+    let total: i64 = tx.query_row(
+        "SELECT COALESCE(SUM(tokens), 0) FROM conversation_messages
+         WHERE bot_id = ?1 AND plugin_id = ?2 AND conversation_id = ?3",
+        params![key.bot_id, key.plugin_id, key.conversation_id],
+        |row| row.get(0),
+    )?;
+    tx.commit()?;
+    Ok(total.max(0) as u64)
+}
+
+/// 保留预算内的最近消息，返回需要合并进摘要的消息数。
+fn summary_split(history: &[StoredMessage], keep_budget: u64) -> usize {
+    let mut kept = 0u64;
+    let mut split = history.len();
+    for (i, m) in history.iter().enumerate().rev() {
+        if kept + m.tokens > keep_budget {
+            break;
+        }
+        kept += m.tokens;
+        split = i;
+    }
+    split
+}
+
+/// 一次带记忆的调用：`prompt` 是发给模型的完整消息，回复成功后用 `finish` 写回历史。
+pub(super) struct ConversationTurn {
+    key: ConversationKey,
+    settings: MemorySettings,
+    group_id: Option<u64>,
+    user_id: Option<u64>,
+    /// 本次新增、需要写入历史的消息 (role, content)
+    new_messages: Vec<(String, String)>,
+    pub(super) prompt: Vec<Value>,
+}
+
+impl ConversationTurn {
+    /// 拼接发给模型的消息（见 `compose_prompt`）。
+    /// 读取历史失败时只记录警告，按没有历史处理。
+    pub(super) async fn begin(
+        state: &SharedState,
+        bot_id: &str,
+        plugin_id: &str,
+        options: &LlmConversationOptions,
+        messages: &[Value],
+    ) -> Self {
+        let key = ConversationKey {
+            bot_id: bot_id.to_string(),
+            plugin_id: plugin_id.to_string(),
+            conversation_id: options.id.clone(),
+        };
+        let settings = MemorySettings::from_state(state, bot_id, options);
+        let scope = UsageScope::current();
+
+        let load_key = key.clone();
+        let timeout = settings.session_timeout;
+        let (summary, history) =
+            match with_db(&DB, move |conn| load_history(conn, &load_key, timeout)).await {
+                Ok(v) => v,
+                Err(e) => {
+                    warn!("[{}] 读取对话记忆 {} 失败: {}", bot_id, key.label(), e);
+                    (String::new(), Vec::new())
+                }
+            };
+
+        let prompt = compose_prompt(messages, &summary, &history, settings.max_context_tokens);
+        let new_messages = messages
+            .iter()
+            .filter(|m| !is_system(m))
+            .filter_map(|m| {
+                let role = m.get("role").and_then(|r| r.as_str())?;
+                let content = content_for_history(m.get("content").unwrap_or(&Value::Null));
+                (!content.trim().is_empty()).then(|| (role.to_string(), content))
+            })
+            .collect();
+
+        Self {
+            key,
+            settings,
+            group_id: options.group_id().or(scope.group_id),
+            user_id: options.user_id().or(scope.user_id),
+            new_messages,
+            prompt,
+        }
+    }
+
+    /// 写回本轮消息与回复；历史超出预算时在后台更新摘要。
+    pub(super) async fn finish(self, state: &SharedState, reply: &str) {
+        let mut rows = self.new_messages;
+        rows.push(("assistant".to_string(), reply.to_string()));
+        let key = self.key.clone();
+        let (group_id, user_id) = (self.group_id, self.user_id);
+        let result = with_db(&DB, move |conn| {
+            append_messages(conn, &key, group_id, user_id, &rows)
+        })
+        .await;
+
+        match result {
+            Ok(total) if total > self.settings.max_context_tokens => {
+                let state = state.clone();
+                tokio::spawn(summarize(state, self.key, self.settings));
+            }
+            Ok(_) => {}
+            Err(e) => warn!("写入对话记忆 {} 失败: {}", self.key.label(), e),
+        }
+    }
+}
+
+/// 把较早的消息合并进摘要，只保留约一半预算的最近消息。
+async fn summarize(state: SharedState, key: ConversationKey, settings: MemorySettings) {
+    let label = key.label();
+    {
+        let Ok(mut running) = SUMMARIZING.lock() else {
+            return;
+        };
+        if !running.insert(label.clone()) {
+            return;
+        }
+    }
+
+    if let Err(e) = summarize_inner(&state, &key, &settings).await {
+        warn!("更新对话记忆 {} 的摘要失败: {}", label, e);
+    }
+
+    if let Ok(mut running) = SUMMARIZING.lock() {
+        running.remove(&label);
+    }
+}
+
+async fn summarize_inner(
+    state: &SharedState,
+    key: &ConversationKey,
+    settings: &MemorySettings,
+) -> Result<(), String> {
+    let load_key = key.clone();
+    let (summary, history) = with_db(&DB, move |conn| {
+        load_history(conn, &load_key, Duration::ZERO)
+    })
+    .await?;
+
+    let older = &history[..summary_split(&history, settings.max_context_tokens / 2)];
+    let Some(last_id) = older.last().map(|m| m.id) else {
+        return Ok(());
+    };
+
+    let mut transcript = String::new();
+    for m in older {
+        let speaker = if m.role == "assistant" {
+            "助手"
+        } else {
+            "用户"
+        };
+        transcript.push_str(&format!("{}：{}\n", speaker, m.content));
+    }
+    let user_prompt = format!(
+        "已有摘要：\n{}\n\n新增对话：\n{}",
+        if summary.trim().is_empty() {
+            "（无）"
+        } else {
+            summary.as_str()
+        },
+        transcript
+    );
+
+    let mut llm =
+        resolve_llm_config_by_name(state, &key.bot_id, settings.summary_model.as_deref())?;
+    llm.origin.plugin_id = Some(key.plugin_id.clone());
+    let body = json!({
+        "model": llm.model_name,
+        "messages": [
+            { "role": "system", "content": SUMMARY_PROMPT },
+            { "role": "user", "content": user_prompt }
+        ],
+        "max_tokens": SUMMARY_MAX_TOKENS
+    });
+    let new_summary = call_chat_completions(&llm, &body)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let label = key.label();
+    let key = key.clone();
+    let summarized = older.len();
+    with_db(&DB, move |conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE conversations SET summary = ?4
+             WHERE bot_id = ?1 AND plugin_id = ?2 AND conversation_id = ?3",
+            params![
+                key.bot_id,
+                key.plugin_id,
+                key.conversation_id,
+                new_summary.trim()
+            ],
+        )?;
+        tx.execute(
+            "DELETE FROM conversation_messages
+             WHERE bot_id = ?1 AND plugin_id = ?2 AND conversation_id = ?3 AND id <= ?4",
+            params![key.bot_id, key.plugin_id, key.conversation_id, last_id],
+        )?;
+        tx.commit()
+    })
+    .await?;
+    info!("对话记忆 {} 已将 {} 条消息合并进摘要", label, summarized);
+    Ok(())
+}
+
+/// 清空一段对话（插件调用 clearConversation）
+pub(super) async fn clear_conversation(bot_id: &str, plugin_id: &str, conversation_id: &str) {
+    let key = ConversationKey {
+        bot_id: bot_id.to_string(),
+        plugin_id: plugin_id.to_string(),
+        conversation_id: conversation_id.to_string(),
+    };
+    let label = key.label();
+    if let Err(e) = with_db(&DB, move |conn| delete_conversation(conn, &key)).await {
+        warn!("清空对话记忆 {} 失败: {}", label, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(conversation_id: &str) -> ConversationKey {
+        ConversationKey {
+            bot_id: "bot".to_string(),
+            plugin_id: "plugin".to_string(),
+            conversation_id: conversation_id.to_string(),
+        }
+    }
+
+    fn open() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_schema(&conn).unwrap();
+        conn
+    }
+
+    fn rows(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(r, c)| (r.to_string(), c.to_string()))
+            .collect()
+    }
+
+    fn stored(tokens: &[u64]) -> Vec<StoredMessage> {
+        tokens
+            .iter()
+            .enumerate()
+            .map(|(i, t)| StoredMessage {
+                id: i as i64 + 1,
+                role: if i % 2 == 0 { "user" } else { "assistant" }.to_string(),
+                content: format!("m{}", i + 1),
+                tokens: *t,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stored_turns_load_in_order_per_conversation() {
+        let mut conn = open();
+        let total = append_messages(
+            &mut conn,
+            &key("a"),
+            Some(10),
+            Some(20),
+            &rows(&[("user", "你好"), ("assistant", "你好！")]),
+        )
+        .unwrap();
+        append_messages(&mut conn, &key("a"), None, None, &rows(&[("user", "再见")])).unwrap();
+        append_messages(
+            &mut conn,
+            &key("b"),
+            None,
+            None,
+            &rows(&[("user", "别的对话")]),
+        )
+        .unwrap();
+        assert_eq!(total, estimate_tokens("你好") + estimate_tokens("你好！"));
+
+        let (summary, history) = load_history(&conn, &key("a"), Duration::ZERO).unwrap();
+        assert!(summary.is_empty());
+        let contents: Vec<&str> = history.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["你好", "你好！", "再见"]);
+        assert_eq!(history[1].role, "assistant");
+
+        // 后续没带群/用户的写入不会覆盖已记录的触发者
+        let (group_id, user_id): (Option<i64>, Option<i64>) = conn
+            .query_row(
+                "SELECT group_id, user_id FROM conversations WHERE conversation_id = 'a'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((group_id, user_id), (Some(10), Some(20)));
+    }
+
+    #[test]
+    fn expired_session_is_cleared_on_load() {
+        let mut conn = open();
+        append_messages(
+            &mut conn,
+            &key("a"),
+            None,
+            None,
+            &rows(&[("user", "旧消息")]),
+        )
+        .unwrap();
+        conn.execute(
+            "UPDATE conversations SET updated_at = updated_at - 7200",
+            [],
+        )
+        .unwrap();
+
+        let (_, history) = load_history(&conn, &key("a"), Duration::ZERO).unwrap();
+        assert_eq!(history.len(), 1);
+
+        let (_, history) = load_history(&conn, &key("a"), Duration::from_secs(3600)).unwrap();
+        assert!(history.is_empty());
+        let remaining: i64 = conn
+            .query_row("SELECT COUNT(*) FROM conversation_messages", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(remaining, 0);
+    }
+
+    #[test]
+    fn delete_only_removes_one_conversation() {
+        let mut conn = open();
+        append_messages(&mut conn, &key("a"), None, None, &rows(&[("user", "a")])).unwrap();
+        append_messages(&mut conn, &key("b"), None, None, &rows(&[("user", "b")])).unwrap();
+        delete_conversation(&conn, &key("a")).unwrap();
+        assert!(load_history(&conn, &key("a"), Duration::ZERO)
+            .unwrap()
+            .1
+            .is_empty());
+        assert_eq!(
+            load_history(&conn, &key("b"), Duration::ZERO)
+                .unwrap()
+                .1
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn prompt_keeps_most_recent_history_within_budget() {
+        let messages = vec![
+            json!({ "role": "system", "content": "你是助手" }),
+            json!({ "role": "user", "content": "新问题" }),
+        ];
+        let prompt = compose_prompt(&messages, "", &stored(&[30, 30, 30, 30]), 70);
+        let contents: Vec<&str> = prompt
+            .iter()
+            .filter_map(|m| m["content"].as_str())
+            .collect();
+        assert_eq!(contents, vec!["你是助手", "m3", "m4", "新问题"]);
+        assert_eq!(prompt[1]["role"], "user");
+        assert_eq!(prompt[2]["role"], "assistant");
+    }
+
+    #[test]
+    fn prompt_summary_counts_against_budget() {
+        let messages = vec![json!({ "role": "user", "content": "新问题" })];
+        let summary = "摘".repeat(40);
+        let budget = estimate_tokens(&summary) + 30;
+        let prompt = compose_prompt(&messages, &summary, &stored(&[30, 30]), budget);
+        assert_eq!(prompt.len(), 3);
+        assert_eq!(prompt[0]["role"], "system");
+        assert!(prompt[0]["content"].as_str().unwrap().ends_with(&summary));
+        assert_eq!(prompt[1]["content"], "m2");
+
+        // 窗口遇到放不下的消息就停止，不会跳过它去取更早的消息
+        let prompt = compose_prompt(&messages, "", &stored(&[10, 100, 10]), 50);
+        let contents: Vec<&str> = prompt
+            .iter()
+            .filter_map(|m| m["content"].as_str())
+            .collect();
+        assert_eq!(contents, vec!["m3", "新问题"]);
+    }
+
+    #[test]
+    fn summary_keeps_recent_half_of_budget() {
+        assert_eq!(summary_split(&stored(&[40, 40, 40, 40]), 100), 2);
+        assert_eq!(summary_split(&stored(&[10, 10]), 100), 0);
+        assert_eq!(summary_split(&stored(&[10, 500]), 100), 2);
+    }
+
+    #[test]
+    fn history_content_replaces_attachments() {
+        let content = json!([
+            { "type": "text", "text": "看看这张图" },
+            { "type": "image_url", "image_url": { "url": "data:image/png;base64,AAAA" } },
+            { "type": "input_audio" }
+        ]);
+        assert_eq!(
+            content_for_history(&content),
+            "看看这张图\n[图片]\n[input_audio]"
+        );
+        assert_eq!(content_for_history(&Value::Null), "");
+    }
+
+    #[test]
+    fn token_estimate_counts_cjk_per_character() {
+        assert_eq!(estimate_tokens(""), 4);
+        assert_eq!(estimate_tokens("你好世界"), 8);
+        assert_eq!(estimate_tokens("abcdefgh"), 6);
+    }
+}
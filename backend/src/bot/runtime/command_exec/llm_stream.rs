@@ -0,0 +1,393 @@
+//! 流式 LLM 调用：增量文本按节流间隔交给插件（onLlmChunk），或边生成边发到会话。
+//! Discord 上原地编辑同一条消息，QQ 上按段落/句子切分后分段发送。
+
+use crate::models::SharedState;
+use crate::plugin::runtime::LlmStreamOptions;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::MissedTickBehavior;
+use tracing::warn;
+
+use super::super::api::{
+    discord_upsert_stream_message, is_discord_bot, send_reply, DiscordStreamMessage,
+};
+use super::super::connection::BotRuntime;
+use super::llm_forward::multimodal::common::{call_chat_completions_stream, LlmConfig};
+use super::plugin_outputs::process_plugin_outputs_with_llm_response;
+
+const DEFAULT_INTERVAL_MS: u64 = 1500;
+const DEFAULT_MIN_CHARS: usize = 200;
+/// Discord 单条 2000 字符，留余量给脱敏替换与生成中的省略号
+const DISCORD_SEGMENT_CHARS: usize = 1900;
+const GENERATING_SUFFIX: &str = " …";
+
+pub(super) struct LlmStreamCall<'a> {
+    pub(super) plugin_id: &'a str,
+    pub(super) request_id: &'a str,
+    pub(super) llm: &'a LlmConfig,
+    pub(super) request_body: &'a serde_json::Value,
+    pub(super) options: &'a LlmStreamOptions,
+}
+
+/// 第 `n` 个字符的字节下标（不足 `n` 个字符时为长度）。
+fn char_boundary(text: &str, n: usize) -> usize {
+    text.char_indices()
+        .nth(n)
+        .map(|(i, _)| i)
+        .unwrap_or(text.len())
+}
+
+/// 找一个自然的切分点（段落 > 换行 > 句末标点），返回切分后的字节下标。
+/// 切分点太靠前（不足三分之一）时视为没有。
+fn find_cut(text: &str) -> Option<usize> {
+    let min = text.len() / 3;
+    let cut = text
+        .rfind("\n\n")
+        .map(|i| i + 2)
+        .or_else(|| text.rfind('\n').map(|i| i + 1))
+        .or_else(|| {
+            text.char_indices()
+                .rev()
+                .find(|(_, c)| matches!(c, '。' | '！' | '？' | '；' | '!' | '?' | ';'))
+                .map(|(i, c)| i + c.len_utf8())
+        })?;
+    (cut > min).then_some(cut)
+}
+
+/// QQ 分段发送：未发出的内容够长时返回这次发送到的字节下标。
+fn paced_cut(pending: &str, min_chars: usize) -> Option<usize> {
+    let pending_chars = pending.chars().count();
+    if pending_chars < min_chars {
+        return None;
+    }
+    // 找不到自然断点时等积累得足够多再整段发出
+    match find_cut(pending) {
+        Some(cut) => Some(cut),
+        None if pending_chars >= min_chars * 4 => Some(pending.len()),
+        None => None,
+    }
+}
+
+struct ReplyTarget {
+    runtime: Arc<BotRuntime>,
+    bot_id: String,
+    user_id: u64,
+    group_id: Option<u64>,
+}
+
+impl ReplyTarget {
+    async fn send(&self, content: &str) {
+        send_reply(
+            &self.runtime,
+            &self.bot_id,
+            self.user_id,
+            self.group_id,
+            content,
+        )
+        .await;
+    }
+
+    /// 新建或编辑 Discord 消息；失败时保留原来的消息句柄，下次再试。
+    async fn upsert(
+        &self,
+        message: Option<DiscordStreamMessage>,
+        content: &str,
+    ) -> Option<DiscordStreamMessage> {
+        match discord_upsert_stream_message(
+            &self.runtime,
+            &self.bot_id,
+            self.user_id,
+            self.group_id,
+            message,
+            content,
+        )
+        .await
+        {
+            Ok(m) => Some(m),
+            Err(e) => {
+                warn!("[{}] 流式回复更新 Discord 消息失败: {}", self.bot_id, e);
+                message
+            }
+        }
+    }
+}
+
+enum ReplySink {
+    /// Discord：当前消息对应 `text[base..]`，超长时定稿并另起一条
+    Discord {
+        message: Option<DiscordStreamMessage>,
+        base: usize,
+        last: String,
+    },
+    /// QQ：`text[..sent]` 已发出
+    Paced { sent: usize },
+}
+
+struct ProgressiveReply {
+    target: ReplyTarget,
+    min_chars: usize,
+    sink: ReplySink,
+}
+
+impl ProgressiveReply {
+    async fn new(target: ReplyTarget, min_chars: usize) -> Self {
+        let sink = if is_discord_bot(&target.runtime, &target.bot_id).await {
+            ReplySink::Discord {
+                message: None,
+                base: 0,
+                last: String::new(),
+            }
+        } else {
+            ReplySink::Paced { sent: 0 }
+        };
+        Self {
+            target,
+            min_chars,
+            sink,
+        }
+    }
+
+    /// 把目前的 `text` 推送出去；`done` 为真时发送剩余全部内容。
+    async fn flush(&mut self, text: &str, done: bool) {
+        let target = &self.target;
+        match &mut self.sink {
+            ReplySink::Discord {
+                message,
+                base,
+                last,
+            } => {
+                loop {
+                    let body = &text[*base..];
+                    if body.chars().count() <= DISCORD_SEGMENT_CHARS {
+                        break;
+                    }
+                    let limit = char_boundary(body, DISCORD_SEGMENT_CHARS);
+                    let cut = find_cut(&body[..limit]).unwrap_or(limit);
+                    target.upsert(*message, body[..cut].trim_end()).await;
+                    *message = None;
+                    *base += cut;
+                    last.clear();
+                }
+                let body = text[*base..].trim_end();
+                if body.trim().is_empty() {
+                    return;
+                }
+                let content = if done {
+                    body.to_string()
+                } else {
+                    format!("{body}{GENERATING_SUFFIX}")
+                };
+                if content != *last {
+                    *message = target.upsert(*message, &content).await;
+                    *last = content;
+                }
+            }
+            ReplySink::Paced { sent } => {
+                let pending = &text[*sent..];
+                if done {
+                    if !pending.trim().is_empty() {
+                        target.send(pending.trim()).await;
+                    }
+                    *sent = text.len();
+                    return;
+                }
+                let Some(cut) = paced_cut(pending, self.min_chars) else {
+                    return;
+                };
+                let segment = pending[..cut].trim();
+                if !segment.is_empty() {
+                    target.send(segment).await;
+                }
+                *sent += cut;
+            }
+        }
+    }
+}
+
+/// 把累积的增量交给插件的 onLlmChunk，并处理回调产生的输出。
+async fn emit_chunk(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    call: &LlmStreamCall<'_>,
+    pending: &mut String,
+    text: &str,
+    index: &mut u32,
+) {
+    if pending.is_empty() {
+        return;
+    }
+    let delta = std::mem::take(pending);
+    match state
+        .plugin_manager
+        .on_llm_chunk(call.plugin_id, call.request_id, &delta, text, *index)
+        .await
+    {
+        Ok(new_outputs) => {
+            Box::pin(process_plugin_outputs_with_llm_response(
+                state,
+                runtime,
+                bot_id,
+                call.plugin_id,
+                &new_outputs,
+            ))
+            .await;
+        }
+        Err(e) => {
+            warn!(
+                "[{}] 插件 {} onLlmChunk 失败: {}",
+                bot_id, call.plugin_id, e
+            );
+        }
+    }
+    *index += 1;
+}
+
+/// 流式调用 LLM；返回完整内容（与非流式调用一致，供 onLlmResponse 使用）。
+pub(super) async fn call_llm_chat_streaming(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    call: LlmStreamCall<'_>,
+) -> Result<String, String> {
+    let options = call.options;
+    let interval = Duration::from_millis(
+        options
+            .interval_ms
+            .unwrap_or(DEFAULT_INTERVAL_MS)
+            .clamp(200, 60_000),
+    );
+    let min_chars = options
+        .min_chars
+        .unwrap_or(DEFAULT_MIN_CHARS)
+        .clamp(20, 4000);
+
+    let mut delivery = match options.deliver_target() {
+        Some((user_id, group_id)) => {
+            let target = ReplyTarget {
+                runtime: runtime.clone(),
+                bot_id: bot_id.to_string(),
+                user_id,
+                group_id,
+            };
+            Some(ProgressiveReply::new(target, min_chars).await)
+        }
+        None => None,
+    };
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    let request = call_chat_completions_stream(call.llm, call.request_body, tx);
+    tokio::pin!(request);
+
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut text = String::new();
+    let mut pending = String::new();
+    let mut index = 0u32;
+
+    let result = loop {
+        tokio::select! {
+            res = &mut request => break res,
+            Some(delta) = rx.recv() => {
+                text.push_str(&delta);
+                pending.push_str(&delta);
+            }
+            _ = ticker.tick() => {
+                if options.chunk_events {
+                    emit_chunk(state, runtime, bot_id, &call, &mut pending, &text, &mut index)
+                        .await;
+                }
+                if let Some(delivery) = delivery.as_mut() {
+                    delivery.flush(&text, false).await;
+                }
+            }
+        }
+    };
+    while let Ok(delta) = rx.try_recv() {
+        text.push_str(&delta);
+        pending.push_str(&delta);
+    }
+
+    if options.chunk_events {
+        emit_chunk(
+            state,
+            runtime,
+            bot_id,
+            &call,
+            &mut pending,
+            &text,
+            &mut index,
+        )
+        .await;
+    }
+    match result {
+        Ok(content) => {
+            if let Some(delivery) = delivery.as_mut() {
+                delivery.flush(&content, true).await;
+            }
+            Ok(content)
+        }
+        Err(e) => {
+            // 已经发出的部分保持原样，只去掉 Discord 上的“生成中”标记
+            if let Some(delivery) = delivery.as_mut() {
+                if matches!(delivery.sink, ReplySink::Discord { .. }) {
+                    delivery.flush(&text, true).await;
+                }
+            }
+            Err(e.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn char_boundary_counts_characters() {
+        assert_eq!(char_boundary("你好世界", 2), 6);
+        assert_eq!(char_boundary("abc", 5), 3);
+    }
+
+    #[test]
+    fn find_cut_prefers_paragraph_then_line_then_sentence() {
+        let text = "第一段内容。\n\n第二段第一行\n第二段第二行";
+        assert_eq!(&text[..find_cut(text).unwrap()], "第一段内容。\n\n");
+
+        let text = "第一行内容较长\n第二行";
+        assert_eq!(&text[..find_cut(text).unwrap()], "第一行内容较长\n");
+
+        let text = "这是第一句话！这是第二句";
+        assert_eq!(&text[..find_cut(text).unwrap()], "这是第一句话！");
+    }
+
+    #[test]
+    fn find_cut_ignores_cuts_near_the_start() {
+        assert_eq!(
+            find_cut("好。后面还有很长很长很长很长的一段没有断点的内容"),
+            None
+        );
+        assert_eq!(find_cut("没有任何断点的内容"), None);
+    }
+
+    #[test]
+    fn paced_cut_waits_for_min_chars() {
+        assert_eq!(paced_cut("短句。", 10), None);
+
+        let text = "这是一段足够长的内容。后面还在生成";
+        assert_eq!(
+            &text[..paced_cut(text, 10).unwrap()],
+            "这是一段足够长的内容。"
+        );
+    }
+
+    #[test]
+    fn paced_cut_flushes_long_text_without_break() {
+        let text = "没".repeat(39);
+        assert_eq!(paced_cut(&text, 10), None);
+        let text = "没".repeat(40);
+        assert_eq!(paced_cut(&text, 10), Some(text.len()));
+    }
+}
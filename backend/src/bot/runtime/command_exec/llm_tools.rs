@@ -0,0 +1,118 @@
+//! 插件 callLlmChat 的工具调用：插件声明的工具经 onLlmToolCall 交给插件执行，
+//! 内置工具（联网搜索）由宿主执行，循环本身在 `multimodal::common` 里。
+
+use crate::models::SharedState;
+use crate::plugin::runtime::LlmToolOptions;
+use std::sync::Arc;
+use tracing::warn;
+
+use super::super::connection::BotRuntime;
+use super::llm_forward::multimodal::common::{
+    call_chat_completions_with_tools, is_builtin_call, run_builtin_tool, web_search_tool,
+    LlmConfig, LlmTool, LlmToolCall, ToolLoopLimits, BUILTIN_WEB_SEARCH,
+};
+use super::plugin_outputs::{get_tavily_api_key, process_plugin_outputs_with_llm_response};
+
+pub(super) struct LlmToolsCall<'a> {
+    pub(super) plugin_id: &'a str,
+    pub(super) request_id: &'a str,
+    pub(super) llm: &'a LlmConfig,
+    pub(super) request_body: &'a serde_json::Value,
+    pub(super) options: &'a LlmToolOptions,
+}
+
+/// 带工具调用 LLM；返回最终回复内容（与非流式调用一致，供 onLlmResponse 使用）。
+pub(super) async fn call_llm_chat_with_tools(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    call: LlmToolsCall<'_>,
+) -> Result<String, String> {
+    let options = call.options;
+    let web_search = options.builtin.iter().any(|b| b == BUILTIN_WEB_SEARCH);
+    for name in options.builtin.iter().filter(|b| *b != BUILTIN_WEB_SEARCH) {
+        warn!(
+            "[{}] 插件 {} 请求了未知的内置工具 {}，已忽略",
+            bot_id, call.plugin_id, name
+        );
+    }
+
+    let builtin = web_search.then(web_search_tool);
+    let mut tools: Vec<LlmTool> = Vec::new();
+    for f in &options.functions {
+        let name = f.name.trim();
+        // 与内置工具重名时以内置工具为准
+        if name.is_empty() || builtin.as_ref().is_some_and(|b| b.name == name) {
+            continue;
+        }
+        tools.push(LlmTool {
+            name: name.to_string(),
+            description: f.description.clone(),
+            parameters: f.parameters.clone(),
+        });
+    }
+    tools.extend(builtin);
+    if tools.is_empty() {
+        return Err("callLlmChat 的 tools 中没有可用的工具".to_string());
+    }
+
+    let tavily_key = if web_search {
+        get_tavily_api_key(state, bot_id)
+    } else {
+        None
+    };
+    // 指定内置工具时换成提供给模型的工具名
+    let mut tool_choice = options.tool_choice.clone();
+    if let Some(name) = tool_choice
+        .as_mut()
+        .and_then(|c| c.pointer_mut("/function/name"))
+    {
+        if name.as_str() == Some(BUILTIN_WEB_SEARCH) {
+            *name = serde_json::Value::String(web_search_tool().name);
+        }
+    }
+    let limits = ToolLoopLimits::new(options.max_rounds, options.timeout_ms, tool_choice);
+
+    let handler = |tool_call: LlmToolCall| {
+        let tavily_key = tavily_key.clone();
+        async move {
+            if web_search && is_builtin_call(&tool_call) {
+                return run_builtin_tool(&tool_call, tavily_key.as_deref()).await;
+            }
+            match state
+                .plugin_manager
+                .on_llm_tool_call(
+                    call.plugin_id,
+                    call.request_id,
+                    &tool_call.id,
+                    &tool_call.name,
+                    &tool_call.arguments,
+                )
+                .await
+            {
+                Ok((result, outputs)) => {
+                    Box::pin(process_plugin_outputs_with_llm_response(
+                        state,
+                        runtime,
+                        bot_id,
+                        call.plugin_id,
+                        &outputs,
+                    ))
+                    .await;
+                    result
+                }
+                Err(e) => {
+                    warn!(
+                        "[{}] 插件 {} onLlmToolCall({}) 失败: {}",
+                        bot_id, call.plugin_id, tool_call.name, e
+                    );
+                    format!("工具执行失败: {}", e)
+                }
+            }
+        }
+    };
+
+    call_chat_completions_with_tools(call.llm, call.request_body, &tools, &limits, handler)
+        .await
+        .map_err(|e| e.to_string())
+}
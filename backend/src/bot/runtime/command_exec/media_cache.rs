@@ -0,0 +1,535 @@
+//! 媒体下载缓存：llm_forward 的图片/视频/语音/文档/压缩包下载共用，按 URL（QQ 文件取 `fileid`）
+//! 与内容 sha256 去重，多个插件同时处理同一文件时只下载一次，并发请求等待同一次下载完成。
+//! 文件保存在 `data/cache/media/<sha256>`，索引为 `data/cache/media.db`。环境变量
+//! `NBOT_MEDIA_CACHE_TTL_SECS`（默认 86400）与 `NBOT_MEDIA_CACHE_MAX_MB`（默认 1024，0 为关闭缓存）。
+//! 插件的 `downloadFile`（由协议端下载）同样合并并发请求，并在短时间内复用协议端返回的文件。
+//! 协议端 `get_record` 取回的语音也按文件名缓存。调用方解析失败时用 `evict_url` 丢掉可能损坏的缓存。
+
+use futures_util::StreamExt;
+use once_cell::sync::Lazy;
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::OwnedMutexGuard;
+use tracing::{info, warn};
+
+use super::super::state_db::{now_secs, with_db, SharedDb};
+
+const MEDIA_DIR: &str = "data/cache/media";
+const CACHE_DB: &str = "data/cache/media.db";
+const DEFAULT_TTL_SECS: u64 = 24 * 3600;
+const DEFAULT_MAX_MB: u64 = 1024;
+/// 协议端 download_file 结果的复用时长
+const DOWNLOAD_FILE_REUSE: Duration = Duration::from_secs(600);
+
+static DB: SharedDb = SharedDb::new(CACHE_DB, "媒体缓存数据库", init_db);
+/// 正在下载的键，同一键的请求串行执行，后到的直接命中缓存
+static INFLIGHT: Lazy<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+static RECENT_DOWNLOAD_FILES: Lazy<Mutex<HashMap<String, (Instant, String)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone)]
+struct CacheSettings {
+    ttl_secs: u64,
+    max_bytes: u64,
+}
+
+impl CacheSettings {
+    /// 关闭缓存时返回 None
+    fn load() -> Option<Self> {
+        let get = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        let max_mb = get("NBOT_MEDIA_CACHE_MAX_MB", DEFAULT_MAX_MB);
+        if max_mb == 0 {
+            return None;
+        }
+        Some(Self {
+            ttl_secs: get("NBOT_MEDIA_CACHE_TTL_SECS", DEFAULT_TTL_SECS).clamp(60, 30 * 24 * 3600),
+            max_bytes: max_mb.min(1_000_000) * 1024 * 1024,
+        })
+    }
+}
+
+/// 一次下载（或缓存命中）的结果，文件写在调用方给出的路径
+pub(super) struct Downloaded {
+    pub(super) size_bytes: u64,
+    /// 超过 `max_bytes` 被截断
+    pub(super) truncated: bool,
+}
+
+/// 缓存键：QQ 的文件链接每次带不同的 rkey，按其中的 fileid 识别同一文件
+pub(super) fn cache_key(url: &str) -> String {
+    let url = url.trim();
+    if let Ok(parsed) = reqwest::Url::parse(url) {
+        if let Some((_, file_id)) = parsed
+            .query_pairs()
+            .find(|(k, v)| k.eq_ignore_ascii_case("fileid") && !v.is_empty())
+        {
+            return format!("qq-file:{file_id}");
+        }
+    }
+    format!("url:{url}")
+}
+
+/// 同一键的独占锁，释放时若没有其他等待者则清理
+pub(super) struct KeyLock {
+    key: String,
+    guard: OwnedMutexGuard<()>,
+}
+
+pub(super) async fn lock_key(key: &str) -> KeyLock {
+    let lock = {
+        let mut map = INFLIGHT.lock().unwrap_or_else(|e| e.into_inner());
+        map.entry(key.to_string()).or_default().clone()
+    };
+    KeyLock {
+        key: key.to_string(),
+        guard: lock.lock_owned().await,
+    }
+}
+
+impl Drop for KeyLock {
+    fn drop(&mut self) {
+        let mut map = INFLIGHT.lock().unwrap_or_else(|e| e.into_inner());
+        // 只剩表里与本锁持有的引用
+        if Arc::strong_count(OwnedMutexGuard::mutex(&self.guard)) <= 2 {
+            map.remove(&self.key);
+        }
+    }
+}
+
+fn init_db(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "PRAGMA journal_mode = WAL;
+         PRAGMA synchronous = NORMAL;
+         CREATE TABLE IF NOT EXISTS media_keys (
+             key TEXT PRIMARY KEY,
+             sha256 TEXT NOT NULL,
+             created_at INTEGER NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS media_blobs (
+             sha256 TEXT PRIMARY KEY,
+             bytes INTEGER NOT NULL,
+             created_at INTEGER NOT NULL,
+             last_hit INTEGER NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS idx_media_blobs_last_hit ON media_blobs(last_hit);",
+    )
+    .map_err(|e| format!("初始化媒体缓存数据库失败: {e}"))
+}
+
+fn blob_path(sha256: &str) -> PathBuf {
+    Path::new(MEDIA_DIR).join(sha256)
+}
+
+/// 下载 `url` 到 `dest`（不得已存在），优先使用缓存。`max_bytes` 以内的完整文件才会写入缓存；
+/// `reject_oversized` 为真时超过上限直接报错，否则截断到上限。
+pub(super) async fn download_cached(
+    url: &str,
+    dest: &Path,
+    timeout_ms: u64,
+    max_bytes: u64,
+    reject_oversized: bool,
+) -> Result<Downloaded, String> {
+    let Some(settings) = CacheSettings::load() else {
+        return download(url, dest, timeout_ms, max_bytes, reject_oversized)
+            .await
+            .map(|(downloaded, _)| downloaded);
+    };
+
+    let key = cache_key(url);
+    let _lock = lock_key(&key).await;
+
+    if let Some((sha256, size)) = lookup(&settings, &key).await {
+        if size > max_bytes && reject_oversized {
+            return Err(format!(
+                "下载文件过大：{} bytes，超过限制 {} bytes",
+                size, max_bytes
+            ));
+        }
+        match materialize(&sha256, size, dest, max_bytes).await {
+            Ok(downloaded) => {
+                info!("媒体缓存命中：{} ({} bytes)", &sha256[..12], size);
+                return Ok(downloaded);
+            }
+            Err(e) => warn!("读取媒体缓存失败，重新下载: {}", e),
+        }
+    }
+
+    let (downloaded, sha256) = download(url, dest, timeout_ms, max_bytes, reject_oversized).await?;
+    if !downloaded.truncated {
+        if let Err(e) = store(&settings, &key, dest, &sha256, downloaded.size_bytes).await {
+            warn!("写入媒体缓存失败: {}", e);
+        }
+    }
+    Ok(downloaded)
+}
+
+async fn download(
+    url: &str,
+    dest: &Path,
+    timeout_ms: u64,
+    max_bytes: u64,
+    reject_oversized: bool,
+) -> Result<(Downloaded, String), String> {
+    let timeout = Duration::from_millis(timeout_ms.clamp(1000, 120000));
+
+    let client = reqwest::Client::new();
+    let resp = client
+        .get(url)
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|e| format!("Download failed: {e}"))?;
+
+    if !resp.status().is_success() {
+        return Err(format!("Download failed: HTTP {}", resp.status()));
+    }
+
+    let content_length = resp.content_length();
+    if let Some(len) = content_length {
+        if reject_oversized && len > max_bytes {
+            return Err(format!(
+                "下载文件过大：Content-Length={} bytes，超过限制 {} bytes",
+                len, max_bytes
+            ));
+        }
+    }
+
+    let mut file = tokio::fs::File::create(dest)
+        .await
+        .map_err(|e| format!("Create temp file failed: {e}"))?;
+
+    let mut hasher = Sha256::new();
+    let mut downloaded: u64 = 0;
+    let mut truncated_by_bytes = false;
+
+    let mut stream = resp.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| format!("Read download stream failed: {e}"))?;
+        if downloaded >= max_bytes {
+            truncated_by_bytes = true;
+            break;
+        }
+
+        let remaining = (max_bytes - downloaded) as usize;
+        let slice: &[u8] = if chunk.len() > remaining {
+            truncated_by_bytes = true;
+            &chunk[..remaining]
+        } else {
+            &chunk
+        };
+
+        file.write_all(slice)
+            .await
+            .map_err(|e| format!("Write temp file failed: {e}"))?;
+        hasher.update(slice);
+        downloaded += slice.len() as u64;
+
+        if truncated_by_bytes {
+            break;
+        }
+    }
+    file.flush()
+        .await
+        .map_err(|e| format!("Write temp file failed: {e}"))?;
+
+    if let Some(len) = content_length {
+        if !truncated_by_bytes && downloaded < len {
+            return Err(format!(
+                "下载不完整：期望 {} bytes，实际 {} bytes（可能链接过期或网络中断）",
+                len, downloaded
+            ));
+        }
+    }
+
+    Ok((
+        Downloaded {
+            size_bytes: downloaded,
+            truncated: truncated_by_bytes,
+        },
+        format!("{:x}", hasher.finalize()),
+    ))
+}
+
+/// 查询缓存，命中时更新最近使用时间；文件已丢失时清掉索引
+async fn lookup(settings: &CacheSettings, key: &str) -> Option<(String, u64)> {
+    let lookup_key = key.to_string();
+    let min_created = now_secs().saturating_sub(settings.ttl_secs) as i64;
+    let found = with_db(&DB, move |conn| {
+        conn.query_row(
+            "SELECT b.sha256, b.bytes FROM media_keys k
+             JOIN media_blobs b ON b.sha256 = k.sha256
+             WHERE k.key = ?1 AND k.created_at >= ?2",
+            params![lookup_key, min_created],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+        )
+        .optional()
+    })
+    .await
+    .unwrap_or_else(|e| {
+        warn!("{}", e);
+        None
+    })?;
+
+    let (sha256, size) = (found.0, found.1.max(0) as u64);
+    let exists = tokio::fs::metadata(blob_path(&sha256)).await.is_ok();
+    let hit_sha = sha256.clone();
+    let result = with_db(&DB, move |conn| {
+        if exists {
+            conn.execute(
+                "UPDATE media_blobs SET last_hit = ?2 WHERE sha256 = ?1",
+                params![hit_sha, now_secs() as i64],
+            )?;
+        } else {
+            conn.execute("DELETE FROM media_keys WHERE sha256 = ?1", params![hit_sha])?;
+            conn.execute(
+                "DELETE FROM media_blobs WHERE sha256 = ?1",
+                params![hit_sha],
+            )?;
+        }
+        Ok(())
+    })
+    .await;
+    if let Err(e) = result {
+        warn!("{}", e);
+    }
+    exists.then_some((sha256, size))
+}
+
+/// 把缓存文件复制到 `dest`；超过上限时只复制前 `max_bytes` 字节
+async fn materialize(
+    sha256: &str,
+    size: u64,
+    dest: &Path,
+    max_bytes: u64,
+) -> Result<Downloaded, String> {
+    let blob = blob_path(sha256);
+    if size > max_bytes {
+        let mut src = tokio::fs::File::open(&blob)
+            .await
+            .map_err(|e| format!("打开缓存文件失败: {e}"))?
+            .take(max_bytes);
+        let mut out = tokio::fs::File::create(dest)
+            .await
+            .map_err(|e| format!("Create temp file failed: {e}"))?;
+        tokio::io::copy(&mut src, &mut out)
+            .await
+            .map_err(|e| format!("复制缓存文件失败: {e}"))?;
+        return Ok(Downloaded {
+            size_bytes: max_bytes,
+            truncated: true,
+        });
+    }
+
+    // 不用硬链接：调用方可能原地改写自己的文件
+    tokio::fs::copy(&blob, dest)
+        .await
+        .map_err(|e| format!("复制缓存文件失败: {e}"))?;
+    Ok(Downloaded {
+        size_bytes: size,
+        truncated: false,
+    })
+}
+
+/// 写入缓存，并按 TTL 与容量上限淘汰旧文件
+async fn store(
+    settings: &CacheSettings,
+    key: &str,
+    path: &Path,
+    sha256: &str,
+    size: u64,
+) -> Result<(), String> {
+    if size > settings.max_bytes {
+        return Ok(());
+    }
+    tokio::fs::create_dir_all(MEDIA_DIR)
+        .await
+        .map_err(|e| format!("创建目录失败: {e}"))?;
+    let blob = blob_path(sha256);
+    if tokio::fs::metadata(&blob).await.is_err() {
+        // 先复制到临时名再改名，避免其他请求读到半个文件
+        let tmp = blob.with_extension("part");
+        tokio::fs::copy(path, &tmp)
+            .await
+            .map_err(|e| format!("复制到缓存失败: {e}"))?;
+        tokio::fs::rename(&tmp, &blob)
+            .await
+            .map_err(|e| format!("复制到缓存失败: {e}"))?;
+    }
+
+    let settings = settings.clone();
+    let key = key.to_string();
+    let sha256 = sha256.to_string();
+    let evicted = with_db(&DB, move |conn| {
+        let now = now_secs() as i64;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO media_blobs (sha256, bytes, created_at, last_hit) VALUES (?1, ?2, ?3, ?3)
+             ON CONFLICT(sha256) DO UPDATE SET last_hit = excluded.last_hit",
+            params![sha256, size as i64, now],
+        )?;
+        tx.execute(
+            "INSERT OR REPLACE INTO media_keys (key, sha256, created_at) VALUES (?1, ?2, ?3)",
+            params![key, sha256, now],
+        )?;
+
+        let min_time = now - settings.ttl_secs as i64;
+        tx.execute(
+            "DELETE FROM media_keys WHERE created_at < ?1",
+            params![min_time],
+        )?;
+        let mut evicted: Vec<String> = Vec::new();
+        {
+            // 过期且不再被任何键引用的文件，以及超出容量时最久未用的文件
+            let mut stmt = tx.prepare(
+                "SELECT sha256, bytes, last_hit,
+                        EXISTS(SELECT 1 FROM media_keys k WHERE k.sha256 = b.sha256)
+                 FROM media_blobs b ORDER BY last_hit",
+            )?;
+            let rows = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, i64>(2)?,
+                        row.get::<_, bool>(3)?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut total: u64 = rows.iter().map(|r| r.1.max(0) as u64).sum();
+            for (old_sha, bytes, last_hit, referenced) in rows {
+                let expired = last_hit < min_time && !referenced;
+                if !expired && total <= settings.max_bytes {
+                    continue;
+                }
+                total = total.saturating_sub(bytes.max(0) as u64);
+                evicted.push(old_sha);
+            }
+        }
+        for old_sha in &evicted {
+            tx.execute("DELETE FROM media_keys WHERE sha256 = ?1", params![old_sha])?;
+            tx.execute(
+                "DELETE FROM media_blobs WHERE sha256 = ?1",
+                params![old_sha],
+            )?;
+        }
+        tx.commit()?;
+        Ok(evicted)
+    })
+    .await?;
+
+    for old_sha in &evicted {
+        let _ = tokio::fs::remove_file(blob_path(old_sha)).await;
+    }
+    if !evicted.is_empty() {
+        info!("媒体缓存已淘汰 {} 个文件", evicted.len());
+    }
+    Ok(())
+}
+
+/// 调用方发现内容无法解析（如 zip 缺少 EOCD）时移除该 URL 的缓存，下次重新下载
+pub(super) async fn evict_url(url: &str) {
+    if CacheSettings::load().is_none() {
+        return;
+    }
+    let key = cache_key(url);
+    let _lock = lock_key(&key).await;
+    let result = with_db(&DB, move |conn| {
+        conn.execute("DELETE FROM media_keys WHERE key = ?1", params![key])
+            .map(|_| ())
+    })
+    .await;
+    if let Err(e) = result {
+        warn!("{}", e);
+    }
+}
+
+/// 非 URL 来源的缓存：`key` 由调用方给出，`fetch` 取回完整内容，结果写到 `dest`，返回字节数
+pub(super) async fn fetch_cached<F, Fut>(key: &str, dest: &Path, fetch: F) -> Result<u64, String>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<Vec<u8>, String>>,
+{
+    let settings = CacheSettings::load();
+    let _lock = match settings {
+        Some(_) => Some(lock_key(key).await),
+        None => None,
+    };
+
+    if let Some(settings) = &settings {
+        if let Some((sha256, size)) = lookup(settings, key).await {
+            match materialize(&sha256, size, dest, u64::MAX).await {
+                Ok(downloaded) => {
+                    info!("媒体缓存命中：{} ({} bytes)", &sha256[..12], size);
+                    return Ok(downloaded.size_bytes);
+                }
+                Err(e) => warn!("读取媒体缓存失败，重新获取: {}", e),
+            }
+        }
+    }
+
+    let bytes = fetch().await?;
+    tokio::fs::write(dest, &bytes)
+        .await
+        .map_err(|e| format!("Write temp file failed: {e}"))?;
+    if let Some(settings) = &settings {
+        let sha256 = format!("{:x}", Sha256::digest(&bytes));
+        if let Err(e) = store(settings, key, dest, &sha256, bytes.len() as u64).await {
+            warn!("写入媒体缓存失败: {}", e);
+        }
+    }
+    Ok(bytes.len() as u64)
+}
+
+/// 协议端下载（插件 downloadFile）：同一 bot 的同一文件合并为一次，成功结果在一段时间内直接复用
+pub(super) async fn coalesce_download_file<F, Fut>(
+    bot_id: &str,
+    url: &str,
+    headers: Option<&str>,
+    fetch: F,
+) -> (bool, String)
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = (bool, String)>,
+{
+    let key = format!(
+        "download_file:{}:{}:{}",
+        bot_id,
+        cache_key(url),
+        headers.unwrap_or_default()
+    );
+    let _lock = lock_key(&key).await;
+
+    let recent = {
+        let mut map = RECENT_DOWNLOAD_FILES
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        map.retain(|_, (at, _)| at.elapsed() < DOWNLOAD_FILE_REUSE);
+        map.get(&key).map(|(_, data)| data.clone())
+    };
+    if let Some(data) = recent {
+        info!("[{}] 复用协议端已下载的文件", bot_id);
+        return (true, data);
+    }
+
+    let (success, data) = fetch().await;
+    if success {
+        RECENT_DOWNLOAD_FILES
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, (Instant::now(), data.clone()));
+    }
+    (success, data)
+}
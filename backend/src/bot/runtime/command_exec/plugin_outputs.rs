@@ -1,22 +1,40 @@
 use crate::models::SharedState;
 use crate::plugin::runtime::{ForwardNode, PluginOutput};
 use crate::plugin::PluginOutputWithSource;
+use once_cell::sync::Lazy;
 use serde_json::json;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tracing::warn;
 
-use super::super::api::{send_api, send_reply};
+use super::super::api::{send_api, send_api_tracked, send_reply, send_reply_tracked};
+use super::super::chat_archive::{search_chat_archive, ChatArchiveQuery};
 use super::super::connection::{BotRuntime, GroupSendStatus};
-use super::llm_abuse::{try_begin_llm_task, LlmAbuseConfig, LlmTaskGuard};
+use super::super::llm_usage::{self, UsageScope};
+use super::super::message::build_conversation_context;
+use super::knowledge_base;
+use super::llm_abuse::{check_llm_budget, try_begin_llm_task, LlmAbuseConfig, LlmTaskGuard};
+use super::llm_cache::LlmResponseCache;
+use super::llm_forward::multimodal::common::{
+    call_chat_completions, resolve_llm_config_by_name, LlmConfig,
+};
+use super::llm_forward::multimodal::{synthesize_voice, VoicePlatform};
 use super::llm_forward::{
     process_llm_forward, process_llm_forward_audio_from_url, process_llm_forward_image_from_url,
     process_llm_forward_media_bundle, process_llm_forward_video_from_url,
     LlmForwardAudioFromUrlInput, LlmForwardImageFromUrlInput, LlmForwardInput,
     LlmForwardMediaBundleInput, LlmForwardSource, LlmForwardVideoFromUrlInput,
 };
+use super::llm_image::{generate_image, ImageSettings};
+use super::llm_json::call_llm_chat_json;
+use super::llm_memory::{clear_conversation, ConversationTurn};
+use super::llm_stream::{call_llm_chat_streaming, LlmStreamCall};
+use super::llm_tools::{call_llm_chat_with_tools, LlmToolsCall};
+use super::media_cache::coalesce_download_file;
 
 /// 从 LLM 模块配置中获取 Tavily API key
-fn get_tavily_api_key(state: &SharedState, bot_id: &str) -> Option<String> {
+pub(super) fn get_tavily_api_key(state: &SharedState, bot_id: &str) -> Option<String> {
     crate::module::get_effective_module(state, bot_id, "llm").and_then(|m| {
         m.config
             .get("tavily_api_key")
@@ -26,6 +44,25 @@ fn get_tavily_api_key(state: &SharedState, bot_id: &str) -> Option<String> {
     })
 }
 
+/// 普通（非流式、无工具）对话调用，开启缓存时按完整请求体复用回复
+async fn call_llm_chat_cached(
+    state: &SharedState,
+    bot_id: &str,
+    llm: &LlmConfig,
+    request_body: &serde_json::Value,
+) -> Result<String, String> {
+    let mut cache = LlmResponseCache::new(state, bot_id, llm, "chat");
+    cache.add_json(request_body);
+    if let Some(cached) = cache.get().await {
+        return Ok(cached);
+    }
+    let content = call_chat_completions(llm, request_body)
+        .await
+        .map_err(|e| e.to_string())?;
+    cache.put(&content).await;
+    Ok(content)
+}
+
 async fn begin_llm_task_guard(
     runtime: &Arc<BotRuntime>,
     bot_id: &str,
@@ -46,7 +83,9 @@ async fn begin_llm_task_guard(
         return None;
     }
 
-    match try_begin_llm_task(abuse_cfg, user_id, group_id) {
+    let begin = check_llm_budget(&abuse_cfg, bot_id, Some(user_id), Some(group_id))
+        .and_then(|_| try_begin_llm_task(abuse_cfg, user_id, group_id));
+    match begin {
         Ok(g) => Some(g),
         Err(block) => {
             send_reply(
@@ -62,6 +101,22 @@ async fn begin_llm_task_guard(
     }
 }
 
+/// 插件直接调用 LLM：先按当前事件的用户/群检查当日预算，再解析配置并记上来源插件。
+fn resolve_plugin_llm_config(
+    state: &SharedState,
+    bot_id: &str,
+    plugin_id: &str,
+    model_name: Option<&str>,
+) -> Result<LlmConfig, String> {
+    let scope = UsageScope::current();
+    let abuse_cfg = LlmAbuseConfig::from_state(state, bot_id);
+    check_llm_budget(&abuse_cfg, bot_id, scope.user_id, scope.group_id)
+        .map_err(|block| block.message)?;
+    let mut llm = resolve_llm_config_by_name(state, bot_id, model_name)?;
+    llm.origin.plugin_id = Some(plugin_id.to_string());
+    Ok(llm)
+}
+
 async fn inline_multimodal_media_in_messages(
     messages: &mut Vec<serde_json::Value>,
     timeout_ms: u64,
@@ -171,10 +226,21 @@ pub(super) async fn process_plugin_outputs(
                 user_id,
                 group_id,
                 content,
+                ..
             } => {
                 send_reply(runtime, bot_id, *user_id, *group_id, content).await;
             }
-            PluginOutput::CallApi { action, params } => {
+            PluginOutput::SendVoiceReply {
+                user_id,
+                group_id,
+                text,
+                voice,
+                ..
+            } => {
+                let content = voice_reply_content(state, runtime, bot_id, text, voice).await;
+                send_reply(runtime, bot_id, *user_id, *group_id, &content).await;
+            }
+            PluginOutput::CallApi { action, params, .. } => {
                 send_api(runtime, bot_id, action, params.clone()).await;
             }
             PluginOutput::CallLlmAndForward {
@@ -185,6 +251,7 @@ pub(super) async fn process_plugin_outputs(
                 prompt,
                 content,
                 title,
+                tts,
             } => {
                 let Some(_guard) =
                     begin_llm_task_guard(runtime, bot_id, abuse_cfg, *user_id, *group_id).await
@@ -204,6 +271,7 @@ pub(super) async fn process_plugin_outputs(
                         prompt,
                         title,
                         source: LlmForwardSource::Content(content),
+                        tts: tts.as_ref(),
                     },
                 )
                 .await;
@@ -220,6 +288,7 @@ pub(super) async fn process_plugin_outputs(
                 timeout_ms,
                 max_bytes,
                 max_chars,
+                tts,
             } => {
                 let Some(_guard) =
                     begin_llm_task_guard(runtime, bot_id, abuse_cfg, *user_id, *group_id).await
@@ -245,6 +314,7 @@ pub(super) async fn process_plugin_outputs(
                             max_bytes: *max_bytes,
                             max_chars: *max_chars,
                         },
+                        tts: tts.as_ref(),
                     },
                 )
                 .await;
@@ -263,7 +333,9 @@ pub(super) async fn process_plugin_outputs(
                 max_extract_bytes,
                 max_file_bytes,
                 max_files,
+                bundle_files,
                 keywords,
+                tts,
             } => {
                 let Some(_guard) =
                     begin_llm_task_guard(runtime, bot_id, abuse_cfg, *user_id, *group_id).await
@@ -290,8 +362,10 @@ pub(super) async fn process_plugin_outputs(
                             max_extract_bytes: *max_extract_bytes,
                             max_file_bytes: *max_file_bytes,
                             max_files: *max_files,
+                            bundle_files: *bundle_files,
                             keywords,
                         },
+                        tts: tts.as_ref(),
                     },
                 )
                 .await;
@@ -311,6 +385,7 @@ pub(super) async fn process_plugin_outputs(
                 max_height,
                 jpeg_quality,
                 max_output_bytes,
+                tts,
             } => {
                 let Some(_guard) =
                     begin_llm_task_guard(runtime, bot_id, abuse_cfg, *user_id, *group_id).await
@@ -337,6 +412,7 @@ pub(super) async fn process_plugin_outputs(
                         max_height: *max_height,
                         jpeg_quality: *jpeg_quality,
                         max_output_bytes: *max_output_bytes,
+                        tts: tts.as_ref(),
                     },
                 )
                 .await;
@@ -358,10 +434,12 @@ pub(super) async fn process_plugin_outputs(
                 frame_max_height,
                 frame_jpeg_quality,
                 frame_max_output_bytes,
+                frame_selection,
                 transcribe_audio,
                 transcription_model,
                 max_audio_seconds,
                 require_transcript,
+                tts,
             } => {
                 let Some(_guard) =
                     begin_llm_task_guard(runtime, bot_id, abuse_cfg, *user_id, *group_id).await
@@ -390,10 +468,12 @@ pub(super) async fn process_plugin_outputs(
                         frame_max_height: *frame_max_height,
                         frame_jpeg_quality: *frame_jpeg_quality,
                         frame_max_output_bytes: *frame_max_output_bytes,
+                        frame_selection,
                         transcribe_audio: *transcribe_audio,
                         transcription_model: transcription_model.as_deref(),
                         max_audio_seconds: *max_audio_seconds,
                         require_transcript: *require_transcript,
+                        tts: tts.as_ref(),
                     },
                 )
                 .await;
@@ -412,6 +492,7 @@ pub(super) async fn process_plugin_outputs(
                 max_bytes,
                 max_audio_seconds,
                 require_transcript,
+                tts,
             } => {
                 let Some(_guard) =
                     begin_llm_task_guard(runtime, bot_id, abuse_cfg, *user_id, *group_id).await
@@ -437,6 +518,7 @@ pub(super) async fn process_plugin_outputs(
                         max_bytes: *max_bytes,
                         max_audio_seconds: *max_audio_seconds,
                         require_transcript: *require_transcript,
+                        tts: tts.as_ref(),
                     },
                 )
                 .await;
@@ -458,6 +540,7 @@ pub(super) async fn process_plugin_outputs(
                 image_max_output_bytes,
                 video_max_bytes,
                 audio_max_bytes,
+                tts,
             } => {
                 let Some(_guard) =
                     begin_llm_task_guard(runtime, bot_id, abuse_cfg, *user_id, *group_id).await
@@ -486,6 +569,7 @@ pub(super) async fn process_plugin_outputs(
                         image_max_output_bytes: *image_max_output_bytes,
                         video_max_bytes: *video_max_bytes,
                         audio_max_bytes: *audio_max_bytes,
+                        tts: tts.as_ref(),
                     },
                 )
                 .await;
@@ -494,6 +578,8 @@ pub(super) async fn process_plugin_outputs(
             PluginOutput::CallLlmChat { .. } => {}
             // CallLlmChatWithSearch is handled in process_plugin_outputs_with_llm_response
             PluginOutput::CallLlmChatWithSearch { .. } => {}
+            // ClearConversation needs the plugin id, handled in the processors that know it
+            PluginOutput::ClearConversation { .. } => {}
             // Group info fetch outputs are handled in process_plugin_outputs_with_group_info_response
             PluginOutput::FetchGroupNotice { .. } => {}
             PluginOutput::FetchGroupMsgHistory { .. } => {}
@@ -503,6 +589,14 @@ pub(super) async fn process_plugin_outputs(
             PluginOutput::FetchGroupList { .. } => {}
             PluginOutput::FetchGroupMemberList { .. } => {}
             PluginOutput::DownloadFile { .. } => {}
+            PluginOutput::SearchChatArchive { .. } => {}
+            PluginOutput::KbSearch { .. }
+            | PluginOutput::KbIngest { .. }
+            | PluginOutput::KbRemove { .. } => {}
+            // Tts / GenerateImage / BuildConversationContext need the plugin id to deliver the result
+            PluginOutput::Tts { .. }
+            | PluginOutput::GenerateImage { .. }
+            | PluginOutput::BuildConversationContext { .. } => {}
             // SendForwardMessage sends merged forward message
             PluginOutput::SendForwardMessage {
                 user_id,
@@ -586,9 +680,7 @@ pub(super) async fn process_plugin_outputs_with_llm_response(
     plugin_id: &str,
     outputs: &[PluginOutput],
 ) {
-    use super::llm_forward::multimodal::common::{
-        call_chat_completions, call_chat_completions_with_tavily, resolve_llm_config_by_name,
-    };
+    use super::llm_forward::multimodal::common::call_chat_completions_with_tavily;
 
     for output in outputs {
         match output {
@@ -597,53 +689,123 @@ pub(super) async fn process_plugin_outputs_with_llm_response(
                 model_name,
                 messages,
                 max_tokens,
+                stream,
+                tools,
+                conversation,
+                response_format,
             } => {
                 // 解析 LLM 配置
-                let (success, content) =
-                    match resolve_llm_config_by_name(state, bot_id, model_name.as_deref()) {
-                        Ok(llm) => {
-                            // If the plugin provided multimodal image_url parts, inline them as data URLs.
-                            let mut prepared_messages = messages.clone();
-                            let _ = inline_multimodal_media_in_messages(
-                                &mut prepared_messages,
-                                30_000,
-                                15_000_000,
-                                1024,
-                                1024,
-                                80,
-                                600_000,
-                                2,
-                            )
-                            .await;
-                            // 构建请求
-                            let mut request_body = json!({
-                                "model": llm.model_name,
-                                "messages": prepared_messages,
-                            });
-                            if let Some(max_tok) = max_tokens {
-                                request_body["max_tokens"] = json!(max_tok);
-                            }
+                let (success, content, parsed) = match resolve_plugin_llm_config(
+                    state,
+                    bot_id,
+                    plugin_id,
+                    model_name.as_deref(),
+                ) {
+                    Ok(llm) => {
+                        // 指定 conversationId 时由宿主拼接历史
+                        let turn = match conversation {
+                            Some(options) => Some(
+                                ConversationTurn::begin(
+                                    state, bot_id, plugin_id, options, messages,
+                                )
+                                .await,
+                            ),
+                            None => None,
+                        };
+                        // If the plugin provided multimodal image_url parts, inline them as data URLs.
+                        let mut prepared_messages = turn
+                            .as_ref()
+                            .map_or_else(|| messages.clone(), |t| t.prompt.clone());
+                        let _ = inline_multimodal_media_in_messages(
+                            &mut prepared_messages,
+                            30_000,
+                            15_000_000,
+                            1024,
+                            1024,
+                            80,
+                            600_000,
+                            2,
+                        )
+                        .await;
+                        // 构建请求
+                        let mut request_body = json!({
+                            "model": llm.model_name,
+                            "messages": prepared_messages,
+                        });
+                        if let Some(max_tok) = max_tokens {
+                            request_body["max_tokens"] = json!(max_tok);
+                        }
 
-                            // 调用 LLM
-                            match call_chat_completions(
-                                &llm.base_url,
-                                &llm.api_key,
-                                &request_body,
-                                llm.max_request_bytes,
-                            )
-                            .await
-                            {
-                                Ok(content) => (true, content),
-                                Err(e) => (false, e.to_string()),
+                        // 调用 LLM（指定 stream 时边生成边回调/发送，指定 response_format 时校验 JSON）
+                        let mut parsed = None;
+                        let result = match (tools, stream, response_format) {
+                            (Some(options), stream, format) => {
+                                if stream.is_some() {
+                                    warn!(
+                                        "[{}] 插件 {} 的工具调用暂不支持流式输出，按非流式处理",
+                                        bot_id, plugin_id
+                                    );
+                                }
+                                if format.is_some() {
+                                    warn!(
+                                        "[{}] 插件 {} 的工具调用暂不支持 response_format，已忽略",
+                                        bot_id, plugin_id
+                                    );
+                                }
+                                let call = LlmToolsCall {
+                                    plugin_id,
+                                    request_id,
+                                    llm: &llm,
+                                    request_body: &request_body,
+                                    options,
+                                };
+                                call_llm_chat_with_tools(state, runtime, bot_id, call).await
+                            }
+                            (None, stream, Some(format)) => {
+                                if stream.is_some() {
+                                    warn!(
+                                        "[{}] 插件 {} 的结构化输出不支持流式输出，按非流式处理",
+                                        bot_id, plugin_id
+                                    );
+                                }
+                                call_llm_chat_json(state, bot_id, &llm, &request_body, format)
+                                    .await
+                                    .map(|(content, value)| {
+                                        parsed = Some(value);
+                                        content
+                                    })
                             }
+                            (None, Some(options), None) => {
+                                let call = LlmStreamCall {
+                                    plugin_id,
+                                    request_id,
+                                    llm: &llm,
+                                    request_body: &request_body,
+                                    options,
+                                };
+                                call_llm_chat_streaming(state, runtime, bot_id, call).await
+                            }
+                            (None, None, None) => {
+                                call_llm_chat_cached(state, bot_id, &llm, &request_body).await
+                            }
+                        };
+                        match result {
+                            Ok(content) => {
+                                if let Some(turn) = turn {
+                                    turn.finish(state, &content).await;
+                                }
+                                (true, content, parsed)
+                            }
+                            Err(e) => (false, e, None),
                         }
-                        Err(e) => (false, e),
-                    };
+                    }
+                    Err(e) => (false, e, None),
+                };
 
                 // 回调插件
                 match state
                     .plugin_manager
-                    .on_llm_response(plugin_id, request_id, success, &content)
+                    .on_llm_response(plugin_id, request_id, success, &content, parsed.as_ref())
                     .await
                 {
                     Ok(new_outputs) => {
@@ -673,7 +835,7 @@ pub(super) async fn process_plugin_outputs_with_llm_response(
                 let model_to_use = model_name.as_deref().or(Some("websearch"));
                 let tavily_key = get_tavily_api_key(state, bot_id);
                 let (success, content) =
-                    match resolve_llm_config_by_name(state, bot_id, model_to_use) {
+                    match resolve_plugin_llm_config(state, bot_id, plugin_id, model_to_use) {
                         Ok(llm) => {
                             let mut prepared_messages = messages.clone();
                             let _ = inline_multimodal_media_in_messages(
@@ -699,10 +861,8 @@ pub(super) async fn process_plugin_outputs_with_llm_response(
                             // 调用支持搜索的 LLM（使用 Tavily 函数调用）
                             let search_enabled = enable_search.unwrap_or(true);
                             match call_chat_completions_with_tavily(
-                                &llm.base_url,
-                                &llm.api_key,
+                                &llm,
                                 &request_body,
-                                llm.max_request_bytes,
                                 search_enabled,
                                 tavily_key.as_deref(),
                             )
@@ -718,7 +878,7 @@ pub(super) async fn process_plugin_outputs_with_llm_response(
                 // 回调插件
                 match state
                     .plugin_manager
-                    .on_llm_response(plugin_id, request_id, success, &content)
+                    .on_llm_response(plugin_id, request_id, success, &content, None)
                     .await
                 {
                     Ok(new_outputs) => {
@@ -877,21 +1037,146 @@ pub(super) async fn process_plugin_outputs_with_llm_response(
                 if let Some(h) = headers {
                     params["headers"] = json!(h);
                 }
-                process_group_info_request(
+                let headers_key = headers.as_ref().map(|h| json!(h).to_string());
+                let (success, data) =
+                    coalesce_download_file(bot_id, url, headers_key.as_deref(), || {
+                        call_info_api(runtime, bot_id, "download_file", params)
+                    })
+                    .await;
+                deliver_group_info_response(
+                    state, runtime, bot_id, plugin_id, request_id, "download", success, &data,
+                )
+                .await;
+            }
+            PluginOutput::SearchChatArchive {
+                request_id,
+                query,
+                group_id,
+                user_id,
+                since,
+                limit,
+            } => {
+                let query = ChatArchiveQuery {
+                    bot_id: Some(bot_id.to_string()),
+                    q: Some(query.clone()),
+                    group_id: *group_id,
+                    user_id: *user_id,
+                    since: *since,
+                    limit: *limit,
+                };
+                let (success, data) = match search_chat_archive(query).await {
+                    Ok(list) => (
+                        true,
+                        serde_json::to_string(&list).unwrap_or_else(|_| "[]".to_string()),
+                    ),
+                    Err(e) => (false, e),
+                };
+                deliver_group_info_response(
                     state,
                     runtime,
                     bot_id,
                     plugin_id,
                     request_id,
-                    "download",
-                    "download_file",
-                    params,
+                    "archive_search",
+                    success,
+                    &data,
+                )
+                .await;
+            }
+            PluginOutput::SendReply {
+                user_id,
+                group_id,
+                content,
+                request_id: Some(request_id),
+            } => {
+                let action = if group_id.is_some() {
+                    "send_group_msg"
+                } else {
+                    "send_private_msg"
+                };
+                let response =
+                    send_reply_tracked(runtime, bot_id, *user_id, *group_id, content).await;
+                deliver_api_response(
+                    state, runtime, bot_id, plugin_id, request_id, action, response,
+                )
+                .await;
+            }
+            PluginOutput::SendVoiceReply {
+                user_id,
+                group_id,
+                text,
+                voice,
+                request_id: Some(request_id),
+            } => {
+                let action = if group_id.is_some() {
+                    "send_group_msg"
+                } else {
+                    "send_private_msg"
+                };
+                let content = voice_reply_content(state, runtime, bot_id, text, voice).await;
+                let response =
+                    send_reply_tracked(runtime, bot_id, *user_id, *group_id, &content).await;
+                deliver_api_response(
+                    state, runtime, bot_id, plugin_id, request_id, action, response,
+                )
+                .await;
+            }
+            PluginOutput::CallApi {
+                action,
+                params,
+                request_id: Some(request_id),
+            } => {
+                let response = send_api_tracked(runtime, bot_id, action, params.clone()).await;
+                deliver_api_response(
+                    state, runtime, bot_id, plugin_id, request_id, action, response,
+                )
+                .await;
+            }
+            PluginOutput::ClearConversation { conversation_id } => {
+                clear_conversation(bot_id, plugin_id, conversation_id).await;
+            }
+            PluginOutput::KbSearch { .. }
+            | PluginOutput::KbIngest { .. }
+            | PluginOutput::KbRemove { .. } => {
+                process_kb_request(state, runtime, bot_id, plugin_id, output).await;
+            }
+            PluginOutput::Tts {
+                request_id,
+                text,
+                voice,
+            } => {
+                process_tts_request(state, runtime, bot_id, plugin_id, request_id, text, voice)
+                    .await;
+            }
+            PluginOutput::GenerateImage { .. } => {
+                process_image_request(state, runtime, bot_id, plugin_id, output).await;
+            }
+            PluginOutput::BuildConversationContext {
+                request_id,
+                options,
+            } => {
+                let (success, data) =
+                    match build_conversation_context(runtime, bot_id, options).await {
+                        Ok(data) => (true, data.to_string()),
+                        Err(e) => (false, e),
+                    };
+                deliver_group_info_response(
+                    state,
+                    runtime,
+                    bot_id,
+                    plugin_id,
+                    request_id,
+                    "conversation_context",
+                    success,
+                    &data,
                 )
                 .await;
             }
             // 其他输出类型委托给普通处理函数
             _ => {
-                process_plugin_outputs(state, runtime, bot_id, std::slice::from_ref(output)).await;
+                let handle =
+                    process_plugin_outputs(state, runtime, bot_id, std::slice::from_ref(output));
+                llm_usage::with_plugin_scope(plugin_id, handle).await;
             }
         }
     }
@@ -905,9 +1190,7 @@ pub(super) async fn process_plugin_outputs_with_source(
     bot_id: &str,
     outputs: &[PluginOutputWithSource],
 ) {
-    use super::llm_forward::multimodal::common::{
-        call_chat_completions, call_chat_completions_with_tavily, resolve_llm_config_by_name,
-    };
+    use super::llm_forward::multimodal::common::call_chat_completions_with_tavily;
 
     for output_with_source in outputs {
         let plugin_id = &output_with_source.plugin_id;
@@ -919,40 +1202,108 @@ pub(super) async fn process_plugin_outputs_with_source(
                 model_name,
                 messages,
                 max_tokens,
+                stream,
+                tools,
+                conversation,
+                response_format,
             } => {
                 // 解析 LLM 配置
-                let (success, content) =
-                    match resolve_llm_config_by_name(state, bot_id, model_name.as_deref()) {
-                        Ok(llm) => {
-                            // 构建请求
-                            let mut request_body = json!({
-                                "model": llm.model_name,
-                                "messages": messages,
-                            });
-                            if let Some(max_tok) = max_tokens {
-                                request_body["max_tokens"] = json!(max_tok);
-                            }
+                let (success, content, parsed) = match resolve_plugin_llm_config(
+                    state,
+                    bot_id,
+                    plugin_id,
+                    model_name.as_deref(),
+                ) {
+                    Ok(llm) => {
+                        // 指定 conversationId 时由宿主拼接历史
+                        let turn = match conversation {
+                            Some(options) => Some(
+                                ConversationTurn::begin(
+                                    state, bot_id, plugin_id, options, messages,
+                                )
+                                .await,
+                            ),
+                            None => None,
+                        };
+                        // 构建请求
+                        let mut request_body = json!({
+                            "model": llm.model_name,
+                            "messages": turn.as_ref().map_or(messages, |t| &t.prompt),
+                        });
+                        if let Some(max_tok) = max_tokens {
+                            request_body["max_tokens"] = json!(max_tok);
+                        }
 
-                            // 调用 LLM
-                            match call_chat_completions(
-                                &llm.base_url,
-                                &llm.api_key,
-                                &request_body,
-                                llm.max_request_bytes,
-                            )
-                            .await
-                            {
-                                Ok(content) => (true, content),
-                                Err(e) => (false, e.to_string()),
+                        // 调用 LLM（指定 stream 时边生成边回调/发送，指定 response_format 时校验 JSON）
+                        let mut parsed = None;
+                        let result = match (tools, stream, response_format) {
+                            (Some(options), stream, format) => {
+                                if stream.is_some() {
+                                    warn!(
+                                        "[{}] 插件 {} 的工具调用暂不支持流式输出，按非流式处理",
+                                        bot_id, plugin_id
+                                    );
+                                }
+                                if format.is_some() {
+                                    warn!(
+                                        "[{}] 插件 {} 的工具调用暂不支持 response_format，已忽略",
+                                        bot_id, plugin_id
+                                    );
+                                }
+                                let call = LlmToolsCall {
+                                    plugin_id,
+                                    request_id,
+                                    llm: &llm,
+                                    request_body: &request_body,
+                                    options,
+                                };
+                                call_llm_chat_with_tools(state, runtime, bot_id, call).await
+                            }
+                            (None, stream, Some(format)) => {
+                                if stream.is_some() {
+                                    warn!(
+                                        "[{}] 插件 {} 的结构化输出不支持流式输出，按非流式处理",
+                                        bot_id, plugin_id
+                                    );
+                                }
+                                call_llm_chat_json(state, bot_id, &llm, &request_body, format)
+                                    .await
+                                    .map(|(content, value)| {
+                                        parsed = Some(value);
+                                        content
+                                    })
                             }
+                            (None, Some(options), None) => {
+                                let call = LlmStreamCall {
+                                    plugin_id,
+                                    request_id,
+                                    llm: &llm,
+                                    request_body: &request_body,
+                                    options,
+                                };
+                                call_llm_chat_streaming(state, runtime, bot_id, call).await
+                            }
+                            (None, None, None) => {
+                                call_llm_chat_cached(state, bot_id, &llm, &request_body).await
+                            }
+                        };
+                        match result {
+                            Ok(content) => {
+                                if let Some(turn) = turn {
+                                    turn.finish(state, &content).await;
+                                }
+                                (true, content, parsed)
+                            }
+                            Err(e) => (false, e, None),
                         }
-                        Err(e) => (false, e),
-                    };
+                    }
+                    Err(e) => (false, e, None),
+                };
 
                 // 回调插件
                 match state
                     .plugin_manager
-                    .on_llm_response(plugin_id, request_id, success, &content)
+                    .on_llm_response(plugin_id, request_id, success, &content, parsed.as_ref())
                     .await
                 {
                     Ok(new_outputs) => {
@@ -982,7 +1333,7 @@ pub(super) async fn process_plugin_outputs_with_source(
                 let model_to_use = model_name.as_deref().or(Some("websearch"));
                 let tavily_key = get_tavily_api_key(state, bot_id);
                 let (success, content) =
-                    match resolve_llm_config_by_name(state, bot_id, model_to_use) {
+                    match resolve_plugin_llm_config(state, bot_id, plugin_id, model_to_use) {
                         Ok(llm) => {
                             // 构建请求
                             let mut request_body = json!({
@@ -996,10 +1347,8 @@ pub(super) async fn process_plugin_outputs_with_source(
                             // 调用支持搜索的 LLM（使用 Tavily 函数调用）
                             let search_enabled = enable_search.unwrap_or(true);
                             match call_chat_completions_with_tavily(
-                                &llm.base_url,
-                                &llm.api_key,
+                                &llm,
                                 &request_body,
-                                llm.max_request_bytes,
                                 search_enabled,
                                 tavily_key.as_deref(),
                             )
@@ -1015,7 +1364,7 @@ pub(super) async fn process_plugin_outputs_with_source(
                 // 回调插件
                 match state
                     .plugin_manager
-                    .on_llm_response(plugin_id, request_id, success, &content)
+                    .on_llm_response(plugin_id, request_id, success, &content, None)
                     .await
                 {
                     Ok(new_outputs) => {
@@ -1119,6 +1468,48 @@ pub(super) async fn process_plugin_outputs_with_source(
                 )
                 .await;
             }
+            PluginOutput::FetchFriendList { request_id } => {
+                process_group_info_request(
+                    state,
+                    runtime,
+                    bot_id,
+                    plugin_id,
+                    request_id,
+                    "friend_list",
+                    "get_friend_list",
+                    json!({}),
+                )
+                .await;
+            }
+            PluginOutput::FetchGroupList { request_id } => {
+                process_group_info_request(
+                    state,
+                    runtime,
+                    bot_id,
+                    plugin_id,
+                    request_id,
+                    "group_list",
+                    "get_group_list",
+                    json!({}),
+                )
+                .await;
+            }
+            PluginOutput::FetchGroupMemberList {
+                request_id,
+                group_id,
+            } => {
+                process_group_info_request(
+                    state,
+                    runtime,
+                    bot_id,
+                    plugin_id,
+                    request_id,
+                    "group_member_list",
+                    "get_group_member_list",
+                    json!({ "group_id": group_id }),
+                )
+                .await;
+            }
             PluginOutput::DownloadFile {
                 request_id,
                 url,
@@ -1132,26 +1523,342 @@ pub(super) async fn process_plugin_outputs_with_source(
                 if let Some(h) = headers {
                     params["headers"] = json!(h);
                 }
-                process_group_info_request(
+                let headers_key = headers.as_ref().map(|h| json!(h).to_string());
+                let (success, data) =
+                    coalesce_download_file(bot_id, url, headers_key.as_deref(), || {
+                        call_info_api(runtime, bot_id, "download_file", params)
+                    })
+                    .await;
+                deliver_group_info_response(
+                    state, runtime, bot_id, plugin_id, request_id, "download", success, &data,
+                )
+                .await;
+            }
+            PluginOutput::SearchChatArchive {
+                request_id,
+                query,
+                group_id,
+                user_id,
+                since,
+                limit,
+            } => {
+                let query = ChatArchiveQuery {
+                    bot_id: Some(bot_id.to_string()),
+                    q: Some(query.clone()),
+                    group_id: *group_id,
+                    user_id: *user_id,
+                    since: *since,
+                    limit: *limit,
+                };
+                let (success, data) = match search_chat_archive(query).await {
+                    Ok(list) => (
+                        true,
+                        serde_json::to_string(&list).unwrap_or_else(|_| "[]".to_string()),
+                    ),
+                    Err(e) => (false, e),
+                };
+                deliver_group_info_response(
                     state,
                     runtime,
                     bot_id,
                     plugin_id,
                     request_id,
-                    "download",
-                    "download_file",
-                    params,
+                    "archive_search",
+                    success,
+                    &data,
+                )
+                .await;
+            }
+            PluginOutput::SendReply {
+                user_id,
+                group_id,
+                content,
+                request_id: Some(request_id),
+            } => {
+                let action = if group_id.is_some() {
+                    "send_group_msg"
+                } else {
+                    "send_private_msg"
+                };
+                let response =
+                    send_reply_tracked(runtime, bot_id, *user_id, *group_id, content).await;
+                deliver_api_response(
+                    state, runtime, bot_id, plugin_id, request_id, action, response,
+                )
+                .await;
+            }
+            PluginOutput::SendVoiceReply {
+                user_id,
+                group_id,
+                text,
+                voice,
+                request_id: Some(request_id),
+            } => {
+                let action = if group_id.is_some() {
+                    "send_group_msg"
+                } else {
+                    "send_private_msg"
+                };
+                let content = voice_reply_content(state, runtime, bot_id, text, voice).await;
+                let response =
+                    send_reply_tracked(runtime, bot_id, *user_id, *group_id, &content).await;
+                deliver_api_response(
+                    state, runtime, bot_id, plugin_id, request_id, action, response,
+                )
+                .await;
+            }
+            PluginOutput::CallApi {
+                action,
+                params,
+                request_id: Some(request_id),
+            } => {
+                let response = send_api_tracked(runtime, bot_id, action, params.clone()).await;
+                deliver_api_response(
+                    state, runtime, bot_id, plugin_id, request_id, action, response,
+                )
+                .await;
+            }
+            PluginOutput::ClearConversation { conversation_id } => {
+                clear_conversation(bot_id, plugin_id, conversation_id).await;
+            }
+            PluginOutput::KbSearch { .. }
+            | PluginOutput::KbIngest { .. }
+            | PluginOutput::KbRemove { .. } => {
+                process_kb_request(state, runtime, bot_id, plugin_id, output).await;
+            }
+            PluginOutput::Tts {
+                request_id,
+                text,
+                voice,
+            } => {
+                process_tts_request(state, runtime, bot_id, plugin_id, request_id, text, voice)
+                    .await;
+            }
+            PluginOutput::GenerateImage { .. } => {
+                process_image_request(state, runtime, bot_id, plugin_id, output).await;
+            }
+            PluginOutput::BuildConversationContext {
+                request_id,
+                options,
+            } => {
+                let (success, data) =
+                    match build_conversation_context(runtime, bot_id, options).await {
+                        Ok(data) => (true, data.to_string()),
+                        Err(e) => (false, e),
+                    };
+                deliver_group_info_response(
+                    state,
+                    runtime,
+                    bot_id,
+                    plugin_id,
+                    request_id,
+                    "conversation_context",
+                    success,
+                    &data,
                 )
                 .await;
             }
             // 其他输出类型委托给普通处理函数
             _ => {
-                process_plugin_outputs(state, runtime, bot_id, std::slice::from_ref(output)).await;
+                let handle =
+                    process_plugin_outputs(state, runtime, bot_id, std::slice::from_ref(output));
+                llm_usage::with_plugin_scope(plugin_id, handle).await;
             }
         }
     }
 }
 
+/// 知识库请求，结果通过 onGroupInfoResponse 返回（infoType 为 kb_search / kb_ingest / kb_remove）
+async fn process_kb_request(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    plugin_id: &str,
+    output: &PluginOutput,
+) {
+    // 未指定群时使用当前事件的群，私聊等场景落到公共知识库（0）
+    let resolve_group = |group_id: &Option<u64>| {
+        group_id
+            .or_else(|| UsageScope::current().group_id)
+            .unwrap_or(0)
+    };
+    let (request_id, info_type, result) = match output {
+        PluginOutput::KbSearch {
+            request_id,
+            query,
+            k,
+            group_id,
+        } => {
+            let group_id = resolve_group(group_id);
+            let result = knowledge_base::search(state, bot_id, group_id, query, *k as usize)
+                .await
+                .map(|hits| serde_json::to_string(&hits).unwrap_or_else(|_| "[]".to_string()));
+            (request_id, "kb_search", result)
+        }
+        PluginOutput::KbIngest {
+            request_id,
+            group_id,
+            source,
+            title,
+            text,
+            url,
+            file_name,
+        } => {
+            let group_id = resolve_group(group_id);
+            let title = if title.is_empty() { source } else { title };
+            let result = match (text, url) {
+                (Some(text), _) => {
+                    knowledge_base::ingest_text(state, bot_id, group_id, source, title, text).await
+                }
+                (None, Some(url)) => {
+                    knowledge_base::ingest_url(
+                        state,
+                        bot_id,
+                        group_id,
+                        source,
+                        title,
+                        url,
+                        file_name.as_deref(),
+                    )
+                    .await
+                }
+                (None, None) => Err("需要提供 text 或 url".to_string()),
+            };
+            let result = result.map(|chunks| json!({ "chunks": chunks }).to_string());
+            (request_id, "kb_ingest", result)
+        }
+        PluginOutput::KbRemove {
+            request_id,
+            group_id,
+            source,
+        } => {
+            let group_id = resolve_group(group_id);
+            let result = knowledge_base::remove_source(bot_id, group_id, source)
+                .await
+                .map(|removed| json!({ "removed": removed }).to_string());
+            (request_id, "kb_remove", result)
+        }
+        _ => return,
+    };
+    let (success, data) = match result {
+        Ok(data) => (true, data),
+        Err(e) => (false, e),
+    };
+    deliver_group_info_response(
+        state, runtime, bot_id, plugin_id, request_id, info_type, success, &data,
+    )
+    .await;
+}
+
+/// 语音回复的消息内容：合成成功时为语音消息段，失败时退回原文字
+async fn voice_reply_content(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    text: &str,
+    voice: &Option<String>,
+) -> String {
+    let platform = VoicePlatform::of_bot(runtime, bot_id).await;
+    match synthesize_voice(state, bot_id, text, voice.as_deref(), platform).await {
+        Ok(clip) => clip.cq_segment(),
+        Err(e) => {
+            warn!("[{}] 语音合成失败，改发文字: {}", bot_id, e);
+            text.to_string()
+        }
+    }
+}
+
+/// 语音合成请求，结果通过 onGroupInfoResponse 返回（infoType 为 tts）
+async fn process_tts_request(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    plugin_id: &str,
+    request_id: &str,
+    text: &str,
+    voice: &Option<String>,
+) {
+    let platform = VoicePlatform::of_bot(runtime, bot_id).await;
+    let (success, data) =
+        match synthesize_voice(state, bot_id, text, voice.as_deref(), platform).await {
+            Ok(clip) => (
+                true,
+                json!({
+                    "segment": clip.cq_segment(),
+                    "format": clip.format,
+                    "bytes": clip.bytes.len(),
+                })
+                .to_string(),
+            ),
+            Err(e) => (false, e),
+        };
+    deliver_group_info_response(
+        state, runtime, bot_id, plugin_id, request_id, "tts", success, &data,
+    )
+    .await;
+}
+
+/// 图片生成请求，结果通过 onGroupInfoResponse 返回（infoType 为 llm_image）
+async fn process_image_request(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    plugin_id: &str,
+    output: &PluginOutput,
+) {
+    let PluginOutput::GenerateImage {
+        request_id,
+        prompt,
+        size,
+        quality,
+        input_image_url,
+        model_name,
+    } = output
+    else {
+        return;
+    };
+    let settings = ImageSettings::load(state, bot_id);
+    let result = async {
+        let mapping = model_name
+            .as_deref()
+            .or(settings.model_mapping.as_deref())
+            .ok_or_else(|| "未配置图片生成模型（llm.images.model_mapping）".to_string())?;
+        let llm = resolve_plugin_llm_config(state, bot_id, plugin_id, Some(mapping))?;
+        let scope = UsageScope::current();
+        let _guard = try_begin_llm_task(
+            LlmAbuseConfig::from_state(state, bot_id),
+            scope.user_id.unwrap_or(0),
+            scope.group_id.unwrap_or(0),
+        )
+        .map_err(|block| block.message)?;
+        generate_image(
+            &llm,
+            &settings,
+            prompt,
+            size.as_deref(),
+            quality.as_deref(),
+            input_image_url.as_deref(),
+        )
+        .await
+    }
+    .await;
+    let (success, data) = match result {
+        Ok(data) => (true, data.to_string()),
+        Err(e) => (false, e),
+    };
+    deliver_group_info_response(
+        state,
+        runtime,
+        bot_id,
+        plugin_id,
+        request_id,
+        "llm_image",
+        success,
+        &data,
+    )
+    .await;
+}
+
 /// Helper function to process group info requests
 async fn process_group_info_request(
     state: &SharedState,
@@ -1163,10 +1870,25 @@ async fn process_group_info_request(
     action: &str,
     params: serde_json::Value,
 ) {
+    let (success, data) = call_info_api(runtime, bot_id, action, params).await;
+
+    deliver_group_info_response(
+        state, runtime, bot_id, plugin_id, request_id, info_type, success, &data,
+    )
+    .await;
+}
+
+/// 调用信息类 API，返回（是否成功, data 的 JSON 或错误信息）
+async fn call_info_api(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    action: &str,
+    params: serde_json::Value,
+) -> (bool, String) {
     // Call the API
     let result = runtime.call_api(bot_id, action, params).await;
 
-    let (success, data) = match result {
+    match result {
         Some(resp) => {
             if resp.get("status").and_then(|s| s.as_str()) == Some("ok") {
                 let data = resp.get("data").cloned().unwrap_or(json!(null));
@@ -1183,12 +1905,24 @@ async fn process_group_info_request(
             }
         }
         None => (false, "API call failed".to_string()),
-    };
+    }
+}
 
-    // Callback to plugin
+/// 把结果通过 onGroupInfoResponse 回调给插件，并继续处理插件产生的新输出
+#[allow(clippy::too_many_arguments)]
+async fn deliver_group_info_response(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    plugin_id: &str,
+    request_id: &str,
+    info_type: &str,
+    success: bool,
+    data: &str,
+) {
     match state
         .plugin_manager
-        .on_group_info_response(plugin_id, request_id, info_type, success, &data)
+        .on_group_info_response(plugin_id, request_id, info_type, success, data)
         .await
     {
         Ok(new_outputs) => {
@@ -1210,3 +1944,151 @@ async fn process_group_info_request(
         }
     }
 }
+
+/// 把发送/API 调用结果通过 onApiResponse 回调给插件，并继续处理插件产生的新输出
+async fn deliver_api_response(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    plugin_id: &str,
+    request_id: &str,
+    action: &str,
+    response: serde_json::Value,
+) {
+    match state
+        .plugin_manager
+        .on_api_response(plugin_id, request_id, action, response)
+        .await
+    {
+        Ok(new_outputs) => {
+            Box::pin(process_plugin_outputs_with_llm_response(
+                state,
+                runtime,
+                bot_id,
+                plugin_id,
+                &new_outputs,
+            ))
+            .await;
+        }
+        Err(e) => {
+            warn!(
+                "[{}] Plugin {} onApiResponse failed: {}",
+                bot_id, plugin_id, e
+            );
+        }
+    }
+}
+
+type WatcherKey = (String, String);
+
+/// 正在检查超时请求的（bot, 插件）；值为检查期间是否又有新请求发出
+static PENDING_WATCHERS: Lazy<Mutex<HashMap<WatcherKey, bool>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+const PENDING_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 登记检查任务；已有任务在运行时只标记续期并返回 false
+fn claim_watcher(watchers: &mut HashMap<WatcherKey, bool>, key: &WatcherKey) -> bool {
+    if let Some(renewed) = watchers.get_mut(key) {
+        *renewed = true;
+        return false;
+    }
+    watchers.insert(key.clone(), false);
+    true
+}
+
+/// 插件已无等待中的请求：检查期间有过新请求就再跑一轮，否则注销任务
+fn keep_watching(watchers: &mut HashMap<WatcherKey, bool>, key: &WatcherKey) -> bool {
+    match watchers.get_mut(key) {
+        Some(renewed) if *renewed => {
+            *renewed = false;
+            true
+        }
+        _ => {
+            watchers.remove(key);
+            false
+        }
+    }
+}
+
+/// 插件仍有等待中的 Promise 请求时，定时让超时的请求失败，不依赖后续钩子调用
+pub(super) fn watch_pending_requests(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    plugin_id: &str,
+) {
+    let key = (bot_id.to_string(), plugin_id.to_string());
+    if !claim_watcher(
+        &mut PENDING_WATCHERS.lock().unwrap_or_else(|e| e.into_inner()),
+        &key,
+    ) {
+        return;
+    }
+
+    let state = state.clone();
+    let runtime = runtime.clone();
+    tokio::spawn(async move {
+        let (bot_id, plugin_id) = (key.0.as_str(), key.1.as_str());
+        loop {
+            tokio::time::sleep(PENDING_SWEEP_INTERVAL).await;
+            let pending = match state.plugin_manager.sweep_requests(plugin_id).await {
+                Ok((pending, outputs)) => {
+                    let handle = process_plugin_outputs_with_llm_response(
+                        &state, &runtime, bot_id, plugin_id, &outputs,
+                    );
+                    llm_usage::with_plugin_scope(plugin_id, handle).await;
+                    pending
+                }
+                Err(e) => {
+                    warn!("[{}] 插件 {} 请求超时检查失败: {}", bot_id, plugin_id, e);
+                    false
+                }
+            };
+            if pending {
+                continue;
+            }
+            let mut watchers = PENDING_WATCHERS.lock().unwrap_or_else(|e| e.into_inner());
+            if !keep_watching(&mut watchers, &key) {
+                break;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(plugin: &str) -> WatcherKey {
+        ("bot".to_string(), plugin.to_string())
+    }
+
+    #[test]
+    fn one_watcher_per_plugin() {
+        let mut watchers = HashMap::new();
+        assert!(claim_watcher(&mut watchers, &key("a")));
+        assert!(!claim_watcher(&mut watchers, &key("a")));
+        assert!(claim_watcher(&mut watchers, &key("b")));
+    }
+
+    #[test]
+    fn watcher_stops_once_idle() {
+        let mut watchers = HashMap::new();
+        claim_watcher(&mut watchers, &key("a"));
+        assert!(!keep_watching(&mut watchers, &key("a")));
+        assert!(watchers.is_empty());
+        // 注销后新请求重新启动检查任务
+        assert!(claim_watcher(&mut watchers, &key("a")));
+    }
+
+    #[test]
+    fn request_during_sweep_keeps_watcher_for_another_round() {
+        let mut watchers = HashMap::new();
+        claim_watcher(&mut watchers, &key("a"));
+        // 检查期间又发出请求
+        assert!(!claim_watcher(&mut watchers, &key("a")));
+        assert!(keep_watching(&mut watchers, &key("a")));
+        assert!(!keep_watching(&mut watchers, &key("a")));
+        assert!(watchers.is_empty());
+    }
+}
@@ -4,6 +4,7 @@ use reqwest::Client as HttpClient;
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
@@ -14,6 +15,9 @@ use tracing::{info, warn};
 
 use super::command_exec::process_plugin_outputs_with_source;
 use super::message::handle_event;
+use super::onebot_http::onebot_http_call_api;
+use super::outbound::OutboundQueues;
+use super::satori::{satori_call_api, SatoriIdState, SatoriLogin};
 
 pub type WsSender = mpsc::UnboundedSender<String>;
 pub type ResponseSender = oneshot::Sender<Value>;
@@ -21,6 +25,18 @@ pub type ResponseSender = oneshot::Sender<Value>;
 const GROUP_SEND_STATUS_TTL: Duration = Duration::from_secs(3);
 const DISCORD_MSG_INDEX_MAX: usize = 2048;
 
+static ECHO_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// 生成唯一的 echo：进程内自增序号 + 时间戳，同一毫秒内的多次调用也不会撞车。
+pub(super) fn next_echo(action: &str) -> String {
+    let seq = ECHO_SEQ.fetch_add(1, Ordering::Relaxed);
+    let now_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    format!("{}_{}_{}", action, now_ms, seq)
+}
+
 #[derive(Debug, Clone)]
 pub enum GroupSendStatus {
     Allowed,
@@ -41,10 +57,29 @@ pub struct DiscordConnection {
     pub shutdown: watch::Sender<bool>,
 }
 
+#[derive(Clone)]
+pub struct OneBotHttpConnection {
+    pub api_url: Arc<String>,
+    pub access_token: Arc<String>,
+    pub http: HttpClient,
+}
+
+#[derive(Clone)]
+pub struct SatoriConnection {
+    pub endpoint: Arc<String>,
+    pub token: Arc<String>,
+    pub login: Arc<std::sync::RwLock<SatoriLogin>>,
+    pub ids: Arc<std::sync::Mutex<SatoriIdState>>,
+    pub http: HttpClient,
+    pub shutdown: watch::Sender<bool>,
+}
+
 #[derive(Clone)]
 pub enum BotConnection {
     OneBot { sender: WsSender },
+    OneBotHttp(OneBotHttpConnection),
     Discord(DiscordConnection),
+    Satori(SatoriConnection),
 }
 
 #[derive(Debug, Clone)]
@@ -87,6 +122,7 @@ pub struct BotRuntime {
     pub connections: Arc<RwLock<HashMap<String, BotConnection>>>,
     pub pending_requests: Arc<RwLock<HashMap<String, ResponseSender>>>,
     pub message_dedup: Arc<Mutex<MessageDedup>>,
+    pub outbound: Arc<OutboundQueues>,
     self_id_cache: Arc<RwLock<HashMap<String, u64>>>,
     group_send_status_cache: Arc<Mutex<HashMap<(String, u64), CachedGroupSendStatus>>>,
     discord_msg_index: Arc<Mutex<HashMap<(String, u64), IndexedDiscordMessage>>>,
@@ -99,6 +135,7 @@ impl BotRuntime {
             connections: Arc::new(RwLock::new(HashMap::new())),
             pending_requests: Arc::new(RwLock::new(HashMap::new())),
             message_dedup: Arc::new(Mutex::new(MessageDedup::new(5))), // 5秒去重窗口
+            outbound: Arc::new(OutboundQueues::default()),
             self_id_cache: Arc::new(RwLock::new(HashMap::new())),
             group_send_status_cache: Arc::new(Mutex::new(HashMap::new())),
             discord_msg_index: Arc::new(Mutex::new(HashMap::new())),
@@ -113,6 +150,13 @@ impl BotRuntime {
             .insert(bot_id.to_string(), BotConnection::Discord(conn));
     }
 
+    pub async fn register_satori_connection(&self, bot_id: &str, conn: SatoriConnection) {
+        self.connections
+            .write()
+            .await
+            .insert(bot_id.to_string(), BotConnection::Satori(conn));
+    }
+
     pub async fn unregister_connection(&self, bot_id: &str) {
         self.connections.write().await.remove(bot_id);
     }
@@ -125,6 +169,14 @@ impl BotRuntime {
         self.unregister_connection(bot_id).await;
     }
 
+    pub async fn shutdown_satori_connection(&self, bot_id: &str) {
+        let conn = self.connections.read().await.get(bot_id).cloned();
+        if let Some(BotConnection::Satori(conn)) = conn {
+            let _ = conn.shutdown.send(true);
+        }
+        self.unregister_connection(bot_id).await;
+    }
+
     pub async fn set_self_id(&self, bot_id: &str, user_id: u64) {
         if user_id == 0 {
             return;
@@ -169,22 +221,24 @@ impl BotRuntime {
         }
     }
 
-    /// 调用 OneBot API 并等待响应
+    /// 调用 OneBot API 并等待响应（WS 走 echo 匹配，HTTP 直接 POST）
     pub async fn call_api(&self, bot_id: &str, action: &str, params: Value) -> Option<Value> {
         let conn = self.connections.read().await.get(bot_id).cloned();
         match conn {
             Some(BotConnection::Discord(_)) => {
                 return self.call_discord_api(bot_id, action, params).await;
             }
+            Some(BotConnection::Satori(conn)) => {
+                return satori_call_api(bot_id, &conn, action, params).await;
+            }
+            Some(BotConnection::OneBotHttp(conn)) => {
+                return onebot_http_call_api(bot_id, &conn, action, &params).await;
+            }
             Some(BotConnection::OneBot { .. }) => {}
             None => return None,
         }
 
-        let now_nanos = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .map(|d| d.as_nanos())
-            .unwrap_or(0);
-        let echo = format!("{}_{}", action, now_nanos);
+        let echo = next_echo(action);
 
         let msg = serde_json::json!({
             "action": action,
@@ -230,7 +284,7 @@ impl BotRuntime {
 
         if matches!(
             self.connections.read().await.get(bot_id),
-            Some(BotConnection::Discord(_))
+            Some(BotConnection::Discord(_)) | Some(BotConnection::Satori(_))
         ) {
             return None;
         }
@@ -260,11 +314,11 @@ impl BotRuntime {
             return GroupSendStatus::Allowed;
         }
 
-        // Discord: group_id 实际上是 channel_id，不强制调用 “群成员/群信息” API；
+        // Discord/Satori: group_id 实际上是 channel_id，不强制调用 “群成员/群信息” API；
         // 发送权限由 send_api 的错误回写缓存决定。
         if matches!(
             self.connections.read().await.get(bot_id),
-            Some(BotConnection::Discord(_))
+            Some(BotConnection::Discord(_)) | Some(BotConnection::Satori(_))
         ) {
             let key = (bot_id.to_string(), group_id);
             let cache = self.group_send_status_cache.lock().await;
@@ -0,0 +1,771 @@
+//! LLM 用量记账：记录每次调用的 token 数与费用，按 bot/群/用户/插件/模型归属，
+//! 提供按维度汇总的查询，以及当日累计（供 `LlmAbuseConfig` 的预算检查）。
+//!
+//! 归属信息通过 task-local 作用域传递：消息/通知处理时记录触发者，处理插件输出时记录来源插件。
+//! 写入走单独线程；当日累计常驻内存，启动后首次使用时从数据库恢复。
+//! 回复缓存的命中/未命中以 `cache` 列标记的零 token 记录写入，不计入调用次数。
+
+use once_cell::sync::Lazy;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, Connection};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use tokio::task_local;
+use tracing::warn;
+
+use super::state_db::{self, now_secs};
+
+const USAGE_DB: &str = "data/llm/usage.db";
+const WRITE_BATCH_MAX: usize = 256;
+const DEFAULT_RANGE_DAYS: i64 = 7;
+
+task_local! {
+    static USAGE_SCOPE: UsageScope;
+}
+
+/// 当前处理中的事件触发者与来源插件。
+#[derive(Debug, Clone, Default)]
+pub(super) struct UsageScope {
+    pub(super) user_id: Option<u64>,
+    pub(super) group_id: Option<u64>,
+    pub(super) plugin_id: Option<String>,
+}
+
+impl UsageScope {
+    pub(super) fn current() -> Self {
+        USAGE_SCOPE.try_with(|s| s.clone()).unwrap_or_default()
+    }
+}
+
+/// 在消息/通知的处理范围内记录触发者。
+pub(super) async fn with_event_scope<T>(
+    user_id: u64,
+    group_id: Option<u64>,
+    fut: impl std::future::Future<Output = T>,
+) -> T {
+    let scope = UsageScope {
+        user_id: (user_id != 0).then_some(user_id),
+        group_id,
+        plugin_id: None,
+    };
+    USAGE_SCOPE.scope(scope, fut).await
+}
+
+/// 处理某个插件的输出时记录来源插件，沿用外层的触发者。
+pub(super) async fn with_plugin_scope<T>(
+    plugin_id: &str,
+    fut: impl std::future::Future<Output = T>,
+) -> T {
+    let mut scope = UsageScope::current();
+    scope.plugin_id = Some(plugin_id.to_string());
+    USAGE_SCOPE.scope(scope, fut).await
+}
+
+/// 一次 LLM 调用的归属（在解析 LLM 配置时确定）。
+#[derive(Debug, Clone, Default)]
+pub(super) struct UsageOrigin {
+    pub(super) bot_id: String,
+    pub(super) user_id: Option<u64>,
+    pub(super) group_id: Option<u64>,
+    pub(super) plugin_id: Option<String>,
+}
+
+impl UsageOrigin {
+    pub(super) fn current(bot_id: &str) -> Self {
+        let scope = UsageScope::current();
+        Self {
+            bot_id: bot_id.to_string(),
+            user_id: scope.user_id,
+            group_id: scope.group_id,
+            plugin_id: scope.plugin_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) struct TokenUsage {
+    /// 输入 token 总数（含命中缓存的部分）
+    pub(super) prompt_tokens: u64,
+    pub(super) completion_tokens: u64,
+    pub(super) cached_tokens: u64,
+}
+
+impl TokenUsage {
+    /// 读取 OpenAI 格式的 `usage` 对象（图片接口为 `input_tokens` / `output_tokens`）。
+    pub(super) fn from_openai(usage: &Value) -> Option<Self> {
+        let prompt_tokens = usage
+            .get("prompt_tokens")
+            .or_else(|| usage.get("input_tokens"))?
+            .as_u64()?;
+        Some(Self {
+            prompt_tokens,
+            completion_tokens: usage
+                .get("completion_tokens")
+                .or_else(|| usage.get("output_tokens"))
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0),
+            cached_tokens: usage
+                .pointer("/prompt_tokens_details/cached_tokens")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0),
+        })
+    }
+
+    /// 合并流式事件中分批给出的用量（各字段都是累计值，取最大）。
+    pub(super) fn merge(&mut self, other: TokenUsage) {
+        self.prompt_tokens = self.prompt_tokens.max(other.prompt_tokens);
+        self.completion_tokens = self.completion_tokens.max(other.completion_tokens);
+        self.cached_tokens = self.cached_tokens.max(other.cached_tokens);
+    }
+
+    fn total(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+/// 模型单价（每百万 token），来自 llm 模块配置的 `prices`。
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct ModelPrice {
+    prompt: f64,
+    completion: f64,
+    /// 命中缓存的输入单价，未配置时按普通输入计
+    cached: Option<f64>,
+    /// 图片生成按张计费（每张价格）
+    image: f64,
+}
+
+impl ModelPrice {
+    /// 先按 `provider/model` 查找，再按 `model` 查找。
+    pub(super) fn lookup(llm_config: &Value, provider_id: &str, model: &str) -> Option<Self> {
+        let prices = llm_config.get("prices")?;
+        let entry = prices
+            .get(format!("{}/{}", provider_id, model))
+            .or_else(|| prices.get(model))?;
+        let price = |key: &str| entry.get(key).and_then(|v| v.as_f64());
+        Some(Self {
+            prompt: price("prompt").unwrap_or(0.0),
+            completion: price("completion").unwrap_or(0.0),
+            cached: price("cached"),
+            image: price("image").unwrap_or(0.0),
+        })
+    }
+
+    fn image_cost(&self, usage: &TokenUsage, images: u64) -> f64 {
+        self.cost(usage) + self.image * images as f64
+    }
+
+    fn cost(&self, usage: &TokenUsage) -> f64 {
+        let cached = usage.cached_tokens.min(usage.prompt_tokens);
+        let uncached = usage.prompt_tokens - cached;
+        (uncached as f64 * self.prompt
+            + cached as f64 * self.cached.unwrap_or(self.prompt)
+            + usage.completion_tokens as f64 * self.completion)
+            / 1_000_000.0
+    }
+}
+
+struct UsageRecord {
+    time: u64,
+    day: String,
+    origin: UsageOrigin,
+    provider_id: String,
+    model: String,
+    usage: TokenUsage,
+    cost: f64,
+    /// 缓存事件："hit" / "miss"；普通调用为 None
+    cache: Option<&'static str>,
+}
+
+static WRITER: Lazy<Mutex<Option<mpsc::Sender<UsageRecord>>>> = Lazy::new(|| Mutex::new(None));
+
+fn today() -> String {
+    chrono::Local::now().format("%Y-%m-%d").to_string()
+}
+
+fn open_db(read_only: bool) -> Result<Connection, String> {
+    state_db::open(USAGE_DB, read_only, "LLM 用量数据库")
+}
+
+fn init_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "PRAGMA journal_mode = WAL;
+         PRAGMA synchronous = NORMAL;
+         CREATE TABLE IF NOT EXISTS llm_usage (
+             id INTEGER PRIMARY KEY AUTOINCREMENT,
+             time INTEGER NOT NULL,
+             day TEXT NOT NULL,
+             bot_id TEXT NOT NULL,
+             group_id INTEGER,
+             user_id INTEGER,
+             plugin_id TEXT,
+             provider_id TEXT NOT NULL,
+             model TEXT NOT NULL,
+             prompt_tokens INTEGER NOT NULL,
+             completion_tokens INTEGER NOT NULL,
+             cached_tokens INTEGER NOT NULL,
+             cost REAL NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS idx_llm_usage_day ON llm_usage(day, bot_id);",
+    )
+    .map_err(|e| format!("初始化 LLM 用量数据库失败: {e}"))?;
+
+    // 旧数据库没有 cache 列
+    let has_cache_column = conn
+        .prepare("SELECT 1 FROM pragma_table_info('llm_usage') WHERE name = 'cache'")
+        .and_then(|mut stmt| stmt.exists([]))
+        .map_err(|e| format!("读取 LLM 用量表结构失败: {e}"))?;
+    if !has_cache_column {
+        conn.execute("ALTER TABLE llm_usage ADD COLUMN cache TEXT", [])
+            .map_err(|e| format!("升级 LLM 用量数据库失败: {e}"))?;
+    }
+    Ok(())
+}
+
+fn run_writer(rx: mpsc::Receiver<UsageRecord>) {
+    let mut conn = match open_db(false).and_then(|c| init_schema(&c).map(|_| c)) {
+        Ok(c) => c,
+        Err(e) => {
+            warn!("{}", e);
+            return;
+        }
+    };
+
+    while let Ok(first) = rx.recv() {
+        let mut batch = vec![first];
+        while batch.len() < WRITE_BATCH_MAX {
+            match rx.try_recv() {
+                Ok(r) => batch.push(r),
+                Err(_) => break,
+            }
+        }
+        let result = conn.transaction().and_then(|tx| {
+            for r in batch {
+                tx.execute(
+                    "INSERT INTO llm_usage
+                         (time, day, bot_id, group_id, user_id, plugin_id, provider_id, model,
+                          prompt_tokens, completion_tokens, cached_tokens, cost, cache)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                    params![
+                        r.time as i64,
+                        r.day,
+                        r.origin.bot_id,
+                        r.origin.group_id.map(|g| g as i64),
+                        r.origin.user_id.map(|u| u as i64),
+                        r.origin.plugin_id,
+                        r.provider_id,
+                        r.model,
+                        r.usage.prompt_tokens as i64,
+                        r.usage.completion_tokens as i64,
+                        r.usage.cached_tokens as i64,
+                        r.cost,
+                        r.cache,
+                    ],
+                )?;
+            }
+            tx.commit()
+        });
+        if let Err(e) = result {
+            warn!("写入 LLM 用量失败: {}", e);
+        }
+    }
+}
+
+fn submit(record: UsageRecord) {
+    let Ok(mut guard) = WRITER.lock() else {
+        return;
+    };
+    if guard.is_none() {
+        let (tx, rx) = mpsc::channel();
+        if let Err(e) = std::thread::Builder::new()
+            .name("llm-usage".to_string())
+            .spawn(move || run_writer(rx))
+        {
+            warn!("启动 LLM 用量写入线程失败: {}", e);
+            return;
+        }
+        *guard = Some(tx);
+    }
+    if let Some(tx) = guard.as_ref() {
+        if let Err(mpsc::SendError(_)) = tx.send(record) {
+            *guard = None;
+        }
+    }
+}
+
+/// 预算检查的对象
+#[derive(Debug, Clone, Copy)]
+pub(super) enum UsageSubject {
+    User(u64),
+    Group(u64),
+}
+
+fn subject_key(bot_id: &str, subject: UsageSubject) -> String {
+    match subject {
+        UsageSubject::User(id) => format!("{bot_id}|u|{id}"),
+        UsageSubject::Group(id) => format!("{bot_id}|g|{id}"),
+    }
+}
+
+/// 当日累计：key 为 `bot|u|id` / `bot|g|id`，值为 (tokens, cost)。
+struct DailyTotals {
+    day: String,
+    totals: HashMap<String, (u64, f64)>,
+}
+
+impl DailyTotals {
+    fn load() -> Self {
+        let day = today();
+        let totals = load_day_totals(&day).unwrap_or_else(|e| {
+            warn!("读取当日 LLM 用量失败: {}", e);
+            HashMap::new()
+        });
+        Self { day, totals }
+    }
+
+    fn roll(&mut self) {
+        let day = today();
+        if day != self.day {
+            self.day = day;
+            self.totals.clear();
+        }
+    }
+
+    fn add(&mut self, key: String, tokens: u64, cost: f64) {
+        let entry = self.totals.entry(key).or_default();
+        entry.0 += tokens;
+        entry.1 += cost;
+    }
+}
+
+static DAILY: Lazy<Mutex<DailyTotals>> = Lazy::new(|| Mutex::new(DailyTotals::load()));
+
+fn load_day_totals(day: &str) -> Result<HashMap<String, (u64, f64)>, String> {
+    let mut out: HashMap<String, (u64, f64)> = HashMap::new();
+    if !std::path::Path::new(USAGE_DB).exists() {
+        return Ok(out);
+    }
+    let conn = open_db(true)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT bot_id, user_id, group_id,
+                    SUM(prompt_tokens + completion_tokens), SUM(cost)
+             FROM llm_usage WHERE day = ?1 GROUP BY bot_id, user_id, group_id",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![day], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<i64>>(1)?,
+                row.get::<_, Option<i64>>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, f64>(4)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    for row in rows {
+        let (bot_id, user_id, group_id, tokens, cost) = row.map_err(|e| e.to_string())?;
+        let subjects = [
+            user_id.map(|u| UsageSubject::User(u as u64)),
+            group_id.map(|g| UsageSubject::Group(g as u64)),
+        ];
+        for subject in subjects.into_iter().flatten() {
+            let entry = out.entry(subject_key(&bot_id, subject)).or_default();
+            entry.0 += tokens as u64;
+            entry.1 += cost;
+        }
+    }
+    Ok(out)
+}
+
+/// 当日累计的 (tokens, cost)。
+pub(super) fn today_totals(bot_id: &str, subject: UsageSubject) -> (u64, f64) {
+    let Ok(mut daily) = DAILY.lock() else {
+        return (0, 0.0);
+    };
+    daily.roll();
+    daily
+        .totals
+        .get(&subject_key(bot_id, subject))
+        .copied()
+        .unwrap_or_default()
+}
+
+/// 记录一次成功调用的用量。
+pub(super) fn record_llm_usage(
+    origin: &UsageOrigin,
+    provider_id: &str,
+    model: &str,
+    price: Option<ModelPrice>,
+    usage: TokenUsage,
+) {
+    let cost = price.map(|p| p.cost(&usage)).unwrap_or(0.0);
+    record_usage(origin, provider_id, model, usage, cost);
+}
+
+/// 记录一次图片生成：token 用量（接口返回时）加上按张计的费用。
+pub(super) fn record_image_usage(
+    origin: &UsageOrigin,
+    provider_id: &str,
+    model: &str,
+    price: Option<ModelPrice>,
+    usage: TokenUsage,
+    images: u64,
+) {
+    let cost = price.map(|p| p.image_cost(&usage, images)).unwrap_or(0.0);
+    record_usage(origin, provider_id, model, usage, cost);
+}
+
+fn record_usage(
+    origin: &UsageOrigin,
+    provider_id: &str,
+    model: &str,
+    usage: TokenUsage,
+    cost: f64,
+) {
+    let day = match DAILY.lock() {
+        Ok(mut daily) => {
+            daily.roll();
+            let tokens = usage.total();
+            if let Some(uid) = origin.user_id {
+                daily.add(
+                    subject_key(&origin.bot_id, UsageSubject::User(uid)),
+                    tokens,
+                    cost,
+                );
+            }
+            if let Some(gid) = origin.group_id {
+                daily.add(
+                    subject_key(&origin.bot_id, UsageSubject::Group(gid)),
+                    tokens,
+                    cost,
+                );
+            }
+            daily.day.clone()
+        }
+        Err(_) => today(),
+    };
+
+    submit(UsageRecord {
+        time: now_secs(),
+        day,
+        origin: origin.clone(),
+        provider_id: provider_id.to_string(),
+        model: model.to_string(),
+        usage,
+        cost,
+        cache: None,
+    });
+}
+
+/// 记录一次回复缓存的命中或未命中（不计 token 与费用）。
+pub(super) fn record_llm_cache_event(
+    origin: &UsageOrigin,
+    provider_id: &str,
+    model: &str,
+    hit: bool,
+) {
+    submit(UsageRecord {
+        time: now_secs(),
+        day: today(),
+        origin: origin.clone(),
+        provider_id: provider_id.to_string(),
+        model: model.to_string(),
+        usage: TokenUsage::default(),
+        cost: 0.0,
+        cache: Some(if hit { "hit" } else { "miss" }),
+    });
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct LlmUsageQuery {
+    /// 起止日期（含），`YYYY-MM-DD`；默认最近 7 天
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub to: Option<String>,
+    /// 汇总维度：day / bot / group / user / plugin / provider / model（默认）
+    #[serde(default)]
+    pub group_by: Option<String>,
+    #[serde(default)]
+    pub bot_id: Option<String>,
+    #[serde(default)]
+    pub group_id: Option<u64>,
+    #[serde(default)]
+    pub user_id: Option<u64>,
+    #[serde(default)]
+    pub plugin_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct LlmUsageRow {
+    pub key: Value,
+    pub calls: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cached_tokens: u64,
+    pub total_tokens: u64,
+    pub cost: f64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LlmUsageSummary {
+    pub from: String,
+    pub to: String,
+    pub group_by: String,
+    pub total: LlmUsageRow,
+    pub rows: Vec<LlmUsageRow>,
+}
+
+fn summarize_blocking(query: &LlmUsageQuery) -> Result<LlmUsageSummary, String> {
+    let to = query
+        .to
+        .clone()
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(today);
+    let from = query
+        .from
+        .clone()
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| {
+            (chrono::Local::now() - chrono::Duration::days(DEFAULT_RANGE_DAYS - 1))
+                .format("%Y-%m-%d")
+                .to_string()
+        });
+    let group_by = query
+        .group_by
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .unwrap_or("model")
+        .to_string();
+    let column = match group_by.as_str() {
+        "day" => "day",
+        "bot" => "bot_id",
+        "group" => "group_id",
+        "user" => "user_id",
+        "plugin" => "plugin_id",
+        "provider" => "provider_id",
+        "model" => "provider_id || '/' || model",
+        other => return Err(format!("不支持的汇总维度: {}", other)),
+    };
+
+    let mut summary = LlmUsageSummary {
+        from: from.clone(),
+        to: to.clone(),
+        group_by,
+        total: LlmUsageRow {
+            key: Value::String("total".to_string()),
+            ..Default::default()
+        },
+        rows: Vec::new(),
+    };
+    if !std::path::Path::new(USAGE_DB).exists() {
+        return Ok(summary);
+    }
+    // 可写打开，以便旧数据库先补上 cache 列
+    let conn = open_db(false)?;
+    init_schema(&conn)?;
+
+    let mut sql = format!(
+        "SELECT {column}, SUM(CASE WHEN cache IS NULL THEN 1 ELSE 0 END),
+                SUM(prompt_tokens), SUM(completion_tokens), SUM(cached_tokens), SUM(cost),
+                SUM(CASE WHEN cache = 'hit' THEN 1 ELSE 0 END),
+                SUM(CASE WHEN cache = 'miss' THEN 1 ELSE 0 END)
+         FROM llm_usage WHERE day >= ? AND day <= ?"
+    );
+    let mut args: Vec<SqlValue> = vec![SqlValue::Text(from), SqlValue::Text(to)];
+    if let Some(bot_id) = query.bot_id.as_deref().filter(|s| !s.is_empty()) {
+        sql.push_str(" AND bot_id = ?");
+        args.push(SqlValue::Text(bot_id.to_string()));
+    }
+    if let Some(gid) = query.group_id {
+        sql.push_str(" AND group_id = ?");
+        args.push(SqlValue::Integer(gid as i64));
+    }
+    if let Some(uid) = query.user_id {
+        sql.push_str(" AND user_id = ?");
+        args.push(SqlValue::Integer(uid as i64));
+    }
+    if let Some(plugin_id) = query.plugin_id.as_deref().filter(|s| !s.is_empty()) {
+        sql.push_str(" AND plugin_id = ?");
+        args.push(SqlValue::Text(plugin_id.to_string()));
+    }
+    sql.push_str(&format!(" GROUP BY {column}"));
+
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| format!("查询 LLM 用量失败: {e}"))?;
+    let rows = stmt
+        .query_map(rusqlite::params_from_iter(args), |row| {
+            let key = match row.get::<_, SqlValue>(0)? {
+                SqlValue::Integer(i) => Value::from(i),
+                SqlValue::Text(s) => Value::String(s),
+                _ => Value::Null,
+            };
+            let prompt_tokens = row.get::<_, i64>(2)? as u64;
+            let completion_tokens = row.get::<_, i64>(3)? as u64;
+            Ok(LlmUsageRow {
+                key,
+                calls: row.get::<_, i64>(1)? as u64,
+                prompt_tokens,
+                completion_tokens,
+                cached_tokens: row.get::<_, i64>(4)? as u64,
+                total_tokens: prompt_tokens + completion_tokens,
+                cost: row.get(5)?,
+                cache_hits: row.get::<_, i64>(6)? as u64,
+                cache_misses: row.get::<_, i64>(7)? as u64,
+            })
+        })
+        .map_err(|e| format!("查询 LLM 用量失败: {e}"))?;
+    for row in rows {
+        let row = row.map_err(|e| format!("读取 LLM 用量失败: {e}"))?;
+        let total = &mut summary.total;
+        total.calls += row.calls;
+        total.prompt_tokens += row.prompt_tokens;
+        total.completion_tokens += row.completion_tokens;
+        total.cached_tokens += row.cached_tokens;
+        total.total_tokens += row.total_tokens;
+        total.cost += row.cost;
+        total.cache_hits += row.cache_hits;
+        total.cache_misses += row.cache_misses;
+        summary.rows.push(row);
+    }
+    summary.rows.sort_by(|a, b| {
+        b.cost
+            .total_cmp(&a.cost)
+            .then(b.total_tokens.cmp(&a.total_tokens))
+    });
+    Ok(summary)
+}
+
+/// 按维度汇总 LLM 用量。
+pub async fn summarize_llm_usage(query: LlmUsageQuery) -> Result<LlmUsageSummary, String> {
+    tokio::task::spawn_blocking(move || summarize_blocking(&query))
+        .await
+        .map_err(|e| format!("查询任务失败: {e}"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn usage_reads_openai_and_image_shapes() {
+        let usage = TokenUsage::from_openai(&json!({
+            "prompt_tokens": 120,
+            "completion_tokens": 30,
+            "prompt_tokens_details": { "cached_tokens": 100 }
+        }))
+        .unwrap();
+        assert_eq!(
+            usage,
+            TokenUsage {
+                prompt_tokens: 120,
+                completion_tokens: 30,
+                cached_tokens: 100
+            }
+        );
+
+        let image = TokenUsage::from_openai(&json!({ "input_tokens": 50, "output_tokens": 8 }));
+        assert_eq!(image.map(|u| u.total()), Some(58));
+        assert_eq!(TokenUsage::from_openai(&json!({})), None);
+    }
+
+    #[test]
+    fn streamed_usage_merges_cumulative_values() {
+        let mut usage = TokenUsage {
+            prompt_tokens: 100,
+            ..Default::default()
+        };
+        usage.merge(TokenUsage {
+            completion_tokens: 40,
+            ..Default::default()
+        });
+        usage.merge(TokenUsage {
+            prompt_tokens: 100,
+            completion_tokens: 25,
+            cached_tokens: 0,
+        });
+        assert_eq!(usage.prompt_tokens, 100);
+        assert_eq!(usage.completion_tokens, 40);
+    }
+
+    #[test]
+    fn price_lookup_prefers_provider_qualified_model() {
+        let cfg = json!({ "prices": {
+            "openai/gpt-x": { "prompt": 2.0, "completion": 8.0 },
+            "gpt-x": { "prompt": 1.0, "completion": 4.0 }
+        }});
+        let usage = TokenUsage {
+            prompt_tokens: 1_000_000,
+            completion_tokens: 0,
+            cached_tokens: 0,
+        };
+        let cost = |provider: &str| {
+            ModelPrice::lookup(&cfg, provider, "gpt-x")
+                .unwrap()
+                .cost(&usage)
+        };
+        assert_eq!(cost("openai"), 2.0);
+        assert_eq!(cost("other"), 1.0);
+        assert!(ModelPrice::lookup(&cfg, "openai", "unknown").is_none());
+    }
+
+    #[test]
+    fn cached_tokens_use_cached_price() {
+        let cfg =
+            json!({ "prices": { "m": { "prompt": 4.0, "completion": 10.0, "cached": 1.0 } } });
+        let price = ModelPrice::lookup(&cfg, "p", "m").unwrap();
+        let usage = TokenUsage {
+            prompt_tokens: 1_000_000,
+            completion_tokens: 500_000,
+            cached_tokens: 750_000,
+        };
+        // 25 万未命中 * 4 + 75 万命中 * 1 + 50 万输出 * 10
+        assert!((price.cost(&usage) - 6.75).abs() < 1e-9);
+
+        let no_cached_price =
+            ModelPrice::lookup(&json!({ "prices": { "m": { "prompt": 4.0 } } }), "p", "m").unwrap();
+        assert!((no_cached_price.cost(&usage) - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn image_cost_adds_per_image_price_to_tokens() {
+        let cfg = json!({ "prices": { "img": { "prompt": 5.0, "image": 0.04 } } });
+        let price = ModelPrice::lookup(&cfg, "p", "img").unwrap();
+        let usage = TokenUsage {
+            prompt_tokens: 200_000,
+            ..Default::default()
+        };
+        assert!((price.image_cost(&usage, 2) - 1.08).abs() < 1e-9);
+        assert!((price.image_cost(&TokenUsage::default(), 1) - 0.04).abs() < 1e-9);
+    }
+
+    #[test]
+    fn daily_totals_accumulate_per_subject() {
+        let mut daily = DailyTotals {
+            day: today(),
+            totals: HashMap::new(),
+        };
+        let user = subject_key("bot", UsageSubject::User(1));
+        let group = subject_key("bot", UsageSubject::Group(1));
+        assert_ne!(user, group);
+
+        daily.add(user.clone(), 100, 0.25);
+        daily.add(user.clone(), 50, 0.25);
+        daily.add(group.clone(), 10, 0.0);
+        daily.roll();
+        assert_eq!(daily.totals.get(&user), Some(&(150, 0.5)));
+        assert_eq!(daily.totals.get(&group), Some(&(10, 0.0)));
+
+        daily.day = "2000-01-01".to_string();
+        daily.roll();
+        assert!(daily.totals.is_empty());
+    }
+}
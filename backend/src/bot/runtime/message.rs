@@ -10,12 +10,19 @@ use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::info;
 
-use super::command_exec::{execute_command, process_plugin_outputs_with_source, CommandExecInput};
+use super::command_exec::{
+    execute_command, index_group_upload, process_plugin_outputs_with_source, CommandExecInput,
+};
+use super::chat_archive;
 use super::connection::{BotRuntime, GroupSendStatus};
+use super::llm_usage;
+use super::outbound::{self, OutboundPriority};
 use super::privacy;
 
 mod reply;
 
+pub(super) use reply::build_conversation_context;
+
 const FILE_EVENT_DEDUPE_MS: u64 = 5_000;
 static FILE_EVENT_DEDUPE: Lazy<DashMap<String, u64>> = Lazy::new(DashMap::new);
 static FILE_EVENT_DEDUPE_LAST_CLEANUP_MS: AtomicU64 = AtomicU64::new(0);
@@ -151,7 +158,7 @@ fn collect_cq_at_ids_from_raw(raw_message: &str, out: &mut HashSet<String>) {
     }
 }
 
-fn decode_basic_html_entities(s: &str) -> String {
+pub(super) fn decode_basic_html_entities(s: &str) -> String {
     // Minimal decoding for URLs and CQ segment fields (NapCat sometimes returns &amp; in url).
     s.replace("&amp;", "&")
         .replace("&lt;", "<")
@@ -180,6 +187,8 @@ fn parse_cq_field(raw: &str, key: &str) -> Option<String> {
     }
 }
 
+/// 为插件补充消息段信息：face 段加上 `data.name`，表情类段（face/mface/dice/rps/poke、图片表情）
+/// 加上可直接放进 LLM 提示词的 `data.desc`（如 `[表情:doge]`）。
 fn decorate_message_segments_for_plugins(message: Option<&Value>) -> Value {
     let Some(Value::Array(segments)) = message else {
         return message.cloned().unwrap_or(Value::Null);
@@ -188,33 +197,24 @@ fn decorate_message_segments_for_plugins(message: Option<&Value>) -> Value {
     let mut out: Vec<Value> = Vec::with_capacity(segments.len());
     for seg in segments {
         let seg_type = seg.get("type").and_then(|t| t.as_str()).unwrap_or("");
-        if seg_type != "face" {
-            out.push(seg.clone());
-            continue;
-        }
-
-        let id = seg
-            .get("data")
-            .and_then(|d| d.get("id"))
-            .and_then(|v| {
-                if let Some(s) = v.as_str() {
-                    Some(s.trim().to_string())
-                } else if let Some(n) = v.as_i64() {
-                    Some(n.to_string())
-                } else {
-                    None
-                }
-            })
-            .filter(|s| !s.is_empty());
-
-        let Some(id) = id else {
+        let data = seg.get("data").unwrap_or(&Value::Null);
+        let Some(desc) = qq_face::describe_segment(seg_type, data) else {
             out.push(seg.clone());
             continue;
         };
 
-        let Some(name) = qq_face::name_for_id(&id) else {
-            out.push(seg.clone());
-            continue;
+        let name = if seg_type == "face" {
+            data.get("id")
+                .and_then(|v| {
+                    if let Some(s) = v.as_str() {
+                        Some(s.trim().to_string())
+                    } else {
+                        v.as_i64().map(|n| n.to_string())
+                    }
+                })
+                .and_then(|id| qq_face::name_for_id(&id))
+        } else {
+            None
         };
 
         let mut next = seg.clone();
@@ -222,7 +222,10 @@ fn decorate_message_segments_for_plugins(message: Option<&Value>) -> Value {
             .get_mut("data")
             .and_then(|d| d.as_object_mut())
         {
-            obj.insert("name".to_string(), Value::String(name.to_string()));
+            if let Some(name) = name {
+                obj.insert("name".to_string(), Value::String(name.to_string()));
+            }
+            obj.insert("desc".to_string(), Value::String(desc));
         }
         out.push(next);
     }
@@ -370,6 +373,9 @@ async fn handle_message(
     state.message_stats.check_reset().await;
     state.message_stats.inc_message();
 
+    // 本地消息存档（模块未启用时直接跳过）
+    chat_archive::record_incoming(state, bot_id, &event);
+
     let mut sensitive_ids: HashSet<String> = HashSet::new();
     if user_id > 0 {
         sensitive_ids.insert(user_id.to_string());
@@ -396,7 +402,14 @@ async fn handle_message(
         }
     }
 
-    privacy::with_sensitive_ids(sensitive_ids, async {
+    // 管理员消息触发的回复在发送队列中优先发送
+    let priority = if is_admin(state, bot_id, user_id) || is_super_admin(state, bot_id, user_id) {
+        OutboundPriority::High
+    } else {
+        OutboundPriority::Normal
+    };
+
+    let handle = privacy::with_sensitive_ids(sensitive_ids, async {
         info!(
             "[{}] 收到消息 ({}) from {}: {}",
             bot_id,
@@ -530,8 +543,9 @@ async fn handle_message(
             )
             .await;
         }
-    })
-    .await;
+    });
+    let handle = llm_usage::with_event_scope(user_id, group_id, handle);
+    outbound::with_priority(priority, handle).await;
 }
 
 /// 检查是否为管理员
@@ -742,6 +756,10 @@ async fn handle_notice(
                 }
             }
 
+            if let Some(gid) = group_id {
+                index_group_upload(state, runtime, bot_id, gid, &file);
+            }
+
             json!({
                 "notice_type": notice_type,
                 "sub_type": sub_type,
@@ -848,12 +866,12 @@ async fn handle_notice(
         }
     };
 
-    privacy::with_sensitive_ids(sensitive_ids, async {
+    let handle = privacy::with_sensitive_ids(sensitive_ids, async {
         // 调用插件 onNotice 钩子
         let notice_result = state.plugin_manager.on_notice(notice_ctx).await;
 
         // 处理插件输出（支持 LLM 回调）
         process_plugin_outputs_with_source(state, runtime, bot_id, &notice_result.outputs).await;
-    })
-    .await;
+    });
+    llm_usage::with_event_scope(user_id, group_id, handle).await;
 }
@@ -7,21 +7,19 @@ const FORWARD_TEXT_MAX_CHARS: usize = 50_000;
 const FORWARD_MAX_DEPTH: usize = 3;
 const FORWARD_MEDIA_MAX_ITEMS: usize = 20;
 
+mod context;
 mod forward;
 
-/// 获取被回复消息的内容（如果有回复）
-pub(super) async fn get_reply_message_content(
-    runtime: &Arc<BotRuntime>,
-    bot_id: &str,
-    group_id: Option<u64>,
-    event: &Value,
-) -> Option<Value> {
-    let raw_event_message = event
+pub(in super::super) use context::build_conversation_context;
+
+/// 从消息（事件或 get_msg 的结果）中取出被回复消息的 ID
+fn extract_reply_id(msg: &Value) -> Option<u64> {
+    let raw_message = msg
         .get("raw_message")
         .and_then(|v| v.as_str())
         .unwrap_or("");
 
-    let reply_id: u64 = if let Some(message) = event.get("message").and_then(|m| m.as_array()) {
+    if let Some(message) = msg.get("message").and_then(|m| m.as_array()) {
         // 从消息中查找 reply 段；若数组格式里缺失 reply，则回退到 raw_message 解析。
         let reply_seg = message
             .iter()
@@ -32,16 +30,26 @@ pub(super) async fn get_reply_message_content(
                 .get("data")
                 .and_then(|d| d.get("id").or_else(|| d.get("message_id")))?;
             match reply_id_val {
-                Value::String(s) => s.trim().parse().ok()?,
-                Value::Number(n) => n.as_u64()?,
-                _ => super::parse_reply_id_from_raw(raw_event_message)?,
+                Value::String(s) => s.trim().parse().ok(),
+                Value::Number(n) => n.as_u64(),
+                _ => super::parse_reply_id_from_raw(raw_message),
             }
         } else {
-            super::parse_reply_id_from_raw(raw_event_message)?
+            super::parse_reply_id_from_raw(raw_message)
         }
     } else {
-        super::parse_reply_id_from_raw(raw_event_message)?
-    };
+        super::parse_reply_id_from_raw(raw_message)
+    }
+}
+
+/// 获取被回复消息的内容（如果有回复）
+pub(super) async fn get_reply_message_content(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    group_id: Option<u64>,
+    event: &Value,
+) -> Option<Value> {
+    let reply_id = extract_reply_id(event)?;
 
     // 调用 get_msg API 获取被回复消息
     let msg_data = runtime
@@ -0,0 +1,324 @@
+//! 对话上下文（插件 `nbot.context.build`）：从一条消息出发沿回复链向上追溯，
+//! 展开其中的合并转发并收集媒体，在字数预算内整理为可直接交给 LLM 的 messages。
+//! 较新的消息优先保留，预算不足时舍弃较早的消息。
+
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::bot::runtime::BotRuntime;
+use crate::plugin::runtime::ConversationContextOptions;
+
+use super::forward;
+
+const DEFAULT_REPLY_DEPTH: u32 = 3;
+const MAX_REPLY_DEPTH: u32 = 10;
+const DEFAULT_FORWARD_DEPTH: u32 = 3;
+const MAX_FORWARD_DEPTH: u32 = 5;
+const DEFAULT_MAX_CHARS: u32 = 8000;
+const DEFAULT_MAX_MEDIA: u32 = 20;
+
+/// 回复链上的一条消息
+struct ChainMessage {
+    message_id: Value,
+    msg: Value,
+}
+
+fn message_id_value(id: &str) -> Value {
+    match id.trim().parse::<u64>() {
+        Ok(n) => json!(n),
+        Err(_) => json!(id.trim()),
+    }
+}
+
+async fn fetch_message(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    message_id: &Value,
+) -> Option<Value> {
+    let resp = runtime
+        .call_api(bot_id, "get_msg", json!({ "message_id": message_id }))
+        .await?;
+    if resp.get("status").and_then(|s| s.as_str()) == Some("failed") {
+        return None;
+    }
+    let msg = resp.get("data").unwrap_or(&resp);
+    msg.get("message").is_some().then(|| msg.clone())
+}
+
+async fn fetch_forward(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    forward_id: &str,
+) -> Option<Vec<Value>> {
+    let resp = runtime
+        .call_api(
+            bot_id,
+            "get_forward_msg",
+            json!({ "message_id": forward_id }),
+        )
+        .await?;
+    let data = resp.get("data").unwrap_or(&resp);
+    data.get("messages").and_then(|v| v.as_array()).cloned()
+}
+
+fn sender_name(msg: &Value) -> String {
+    let sender = msg.get("sender").unwrap_or(&Value::Null);
+    ["card", "nickname"]
+        .iter()
+        .filter_map(|k| sender.get(*k).and_then(|v| v.as_str()))
+        .map(str::trim)
+        .find(|s| !s.is_empty())
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+fn sender_user_id(msg: &Value) -> Option<u64> {
+    msg.get("sender")
+        .and_then(|s| s.get("user_id"))
+        .and_then(|v| v.as_u64().or_else(|| v.as_str()?.parse().ok()))
+}
+
+/// 转发内容已内嵌在消息段里时无需再调用 get_forward_msg
+fn has_inline_forward(message_arr: &Value) -> bool {
+    message_arr.as_array().is_some_and(|segments| {
+        segments.iter().any(|seg| {
+            seg.get("type").and_then(|t| t.as_str()) == Some("forward")
+                && seg.pointer("/data/content").is_some_and(|c| c.is_array())
+        })
+    })
+}
+
+/// 按剩余字数预算截断一条消息文本并扣减预算，返回文本及是否发生截断
+fn fit_budget(text: String, remaining: &mut usize) -> (String, bool) {
+    let (text, truncated) = if text.chars().count() > *remaining {
+        (forward::truncate_snippet(&text, *remaining), true)
+    } else {
+        (text, false)
+    };
+    *remaining = remaining.saturating_sub(text.chars().count());
+    (text, truncated)
+}
+
+/// 构建 `options.message_id` 的对话上下文，返回
+/// `{ messages, media, chain, truncated, chainTruncated, mediaTruncated }`。
+pub(in super::super::super) async fn build_conversation_context(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    options: &ConversationContextOptions,
+) -> Result<Value, String> {
+    let reply_depth = options
+        .reply_depth
+        .unwrap_or(DEFAULT_REPLY_DEPTH)
+        .min(MAX_REPLY_DEPTH) as usize;
+    let forward_depth = options
+        .forward_depth
+        .unwrap_or(DEFAULT_FORWARD_DEPTH)
+        .min(MAX_FORWARD_DEPTH) as usize;
+    let max_chars = options
+        .max_chars
+        .unwrap_or(DEFAULT_MAX_CHARS)
+        .min(super::FORWARD_TEXT_MAX_CHARS as u32) as usize;
+    let max_media = options.max_media.unwrap_or(DEFAULT_MAX_MEDIA) as usize;
+
+    if options.message_id.trim().is_empty() {
+        return Err("需要提供 messageId".to_string());
+    }
+
+    // 回复链，从起点消息开始由新到旧
+    let mut chain: Vec<ChainMessage> = Vec::new();
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut next = Some(message_id_value(&options.message_id));
+    let mut chain_truncated = false;
+    while let Some(message_id) = next.take() {
+        if chain.len() > reply_depth {
+            chain_truncated = true;
+            break;
+        }
+        if !visited.insert(message_id.to_string()) {
+            break;
+        }
+        let Some(msg) = fetch_message(runtime, bot_id, &message_id).await else {
+            if chain.is_empty() {
+                return Err(format!("获取消息 {} 失败", message_id));
+            }
+            // 更早的消息可能已过期或被撤回
+            chain_truncated = true;
+            break;
+        };
+        next = super::extract_reply_id(&msg).map(|id| json!(id));
+        chain.push(ChainMessage { message_id, msg });
+    }
+
+    let self_id = runtime.get_self_id(bot_id).await;
+    let mut remaining = max_chars;
+    let mut truncated = false;
+    let mut media: Vec<Value> = Vec::new();
+    let mut seen_media: HashSet<String> = HashSet::new();
+    let mut media_truncated = false;
+    let mut messages: Vec<Value> = Vec::new();
+    let mut chain_info: Vec<Value> = Vec::new();
+
+    for item in &chain {
+        if remaining == 0 {
+            truncated = true;
+            break;
+        }
+        let msg = &item.msg;
+        let user_id = sender_user_id(msg);
+        let is_bot = matches!((user_id, self_id), (Some(u), Some(s)) if u == s);
+        let nickname = sender_name(msg);
+        let time = msg.get("time").and_then(|v| v.as_i64()).unwrap_or(0);
+
+        let mut body = forward::render_message_text(msg, 0, forward_depth);
+        let media_start = media.len();
+        // 起点消息本身算第 0 层，内嵌的转发从第 1 层开始
+        media_truncated |= forward::collect_media(
+            std::slice::from_ref(msg),
+            forward_depth + 1,
+            max_media,
+            &mut media,
+            &mut seen_media,
+        );
+
+        // get_msg 返回的合并转发通常只有 ID，需要单独拉取内容
+        let raw_message = msg
+            .get("raw_message")
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+        let message_arr = msg.get("message").unwrap_or(&Value::Null);
+        if forward_depth > 0 && !has_inline_forward(message_arr) {
+            if let Some(fid) = forward::extract_forward_id(message_arr, raw_message) {
+                if let Some(fwd) = fetch_forward(runtime, bot_id, &fid).await {
+                    let (txt, fwd_truncated) =
+                        forward::render_forward_messages(&fwd, remaining, 1, forward_depth);
+                    truncated |= fwd_truncated;
+                    body.push_str(&format!("\n[转发消息内容]\n{}", txt.trim_end()));
+                    media_truncated |= forward::collect_media(
+                        &fwd,
+                        forward_depth,
+                        max_media,
+                        &mut media,
+                        &mut seen_media,
+                    );
+                }
+            }
+        }
+
+        let text = match (is_bot, time > 0) {
+            (true, _) => body,
+            (false, true) => format!("{} {}:\n{}", nickname, forward::format_ts(time), body),
+            (false, false) => format!("{}:\n{}", nickname, body),
+        };
+        let (text, text_truncated) = fit_budget(text, &mut remaining);
+        truncated |= text_truncated;
+
+        for m in &mut media[media_start..] {
+            if let Some(obj) = m.as_object_mut() {
+                obj.insert("messageId".to_string(), item.message_id.clone());
+                obj.insert("sender".to_string(), json!(nickname));
+            }
+        }
+
+        let images: Vec<&str> = if options.attach_images {
+            media[media_start..]
+                .iter()
+                .filter(|m| m.get("type").and_then(|t| t.as_str()) == Some("image"))
+                .filter_map(|m| m.get("url").and_then(|u| u.as_str()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let content = if images.is_empty() {
+            json!(text)
+        } else {
+            let mut parts = vec![json!({ "type": "text", "text": text })];
+            parts.extend(
+                images
+                    .iter()
+                    .map(|url| json!({ "type": "image_url", "image_url": { "url": url } })),
+            );
+            Value::Array(parts)
+        };
+        messages.push(json!({
+            "role": if is_bot { "assistant" } else { "user" },
+            "content": content,
+        }));
+        chain_info.push(json!({
+            "messageId": item.message_id,
+            "userId": user_id.map(|u| u.to_string()),
+            "nickname": nickname,
+            "time": time,
+            "isBot": is_bot,
+        }));
+    }
+
+    // 按时间先后返回
+    messages.reverse();
+    chain_info.reverse();
+    Ok(json!({
+        "messages": messages,
+        "media": media,
+        "chain": chain_info,
+        "truncated": truncated,
+        "chainTruncated": chain_truncated,
+        "mediaTruncated": media_truncated,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budget_is_consumed_until_exhausted() {
+        let mut remaining = 10;
+        assert_eq!(
+            fit_budget("你好世界".to_string(), &mut remaining),
+            ("你好世界".to_string(), false)
+        );
+        assert_eq!(remaining, 6);
+
+        let (text, truncated) = fit_budget("abcdefghij".to_string(), &mut remaining);
+        assert!(truncated);
+        assert_eq!(text, "abcdef…");
+        assert_eq!(remaining, 0);
+
+        assert_eq!(
+            fit_budget("后续".to_string(), &mut remaining),
+            (String::new(), true)
+        );
+    }
+
+    #[test]
+    fn exact_fit_is_not_truncated() {
+        let mut remaining = 3;
+        assert_eq!(
+            fit_budget("abc".to_string(), &mut remaining),
+            ("abc".to_string(), false)
+        );
+        assert_eq!(remaining, 0);
+    }
+
+    #[test]
+    fn message_ids_keep_numeric_form() {
+        assert_eq!(message_id_value(" 12345 "), json!(12345));
+        assert_eq!(message_id_value("abc-1"), json!("abc-1"));
+    }
+
+    #[test]
+    fn sender_prefers_card_over_nickname() {
+        let msg = json!({ "sender": { "card": " ", "nickname": "小明", "user_id": "42" } });
+        assert_eq!(sender_name(&msg), "小明");
+        assert_eq!(sender_user_id(&msg), Some(42));
+        assert_eq!(sender_name(&json!({})), "unknown");
+    }
+
+    #[test]
+    fn inline_forward_requires_content_array() {
+        let inline = json!([{ "type": "forward", "data": { "id": "f1", "content": [] } }]);
+        let by_id = json!([{ "type": "forward", "data": { "id": "f1" } }]);
+        assert!(has_inline_forward(&inline));
+        assert!(!has_inline_forward(&by_id));
+    }
+}
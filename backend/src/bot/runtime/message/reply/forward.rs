@@ -9,7 +9,7 @@ fn take_str(v: &Value) -> Option<String> {
     }
 }
 
-fn format_ts(ts: i64) -> String {
+pub(super) fn format_ts(ts: i64) -> String {
     use chrono::TimeZone;
     chrono::Local
         .timestamp_opt(ts.max(0), 0)
@@ -18,7 +18,7 @@ fn format_ts(ts: i64) -> String {
         .unwrap_or_else(|| ts.to_string())
 }
 
-fn truncate_snippet(s: &str, max_chars: usize) -> String {
+pub(super) fn truncate_snippet(s: &str, max_chars: usize) -> String {
     if max_chars == 0 {
         return String::new();
     }
@@ -43,6 +43,10 @@ fn render_segment(seg: &Value, depth: usize, max_depth: usize) -> String {
     let seg_type = seg.get("type").and_then(|t| t.as_str()).unwrap_or("");
     let data = seg.get("data").unwrap_or(&Value::Null);
 
+    if let Some(desc) = crate::qq_face::describe_segment(seg_type, data) {
+        return desc;
+    }
+
     match seg_type {
         "text" => data
             .get("text")
@@ -161,7 +165,7 @@ fn render_segment(seg: &Value, depth: usize, max_depth: usize) -> String {
     }
 }
 
-fn render_message_text(msg: &Value, depth: usize, max_depth: usize) -> String {
+pub(super) fn render_message_text(msg: &Value, depth: usize, max_depth: usize) -> String {
     let message = msg.get("message").unwrap_or(&Value::Null);
     if let Some(s) = message.as_str() {
         return s.to_string();
@@ -274,19 +278,37 @@ fn collect_forward_media_from_messages(
 pub(super) fn collect_forward_media(messages: &[Value]) -> (Vec<Value>, bool) {
     let mut out: Vec<Value> = Vec::new();
     let mut seen: HashSet<String> = HashSet::new();
-    let mut truncated = false;
-    collect_forward_media_from_messages(
+    let truncated = collect_media(
         messages,
-        0,
         super::FORWARD_MAX_DEPTH,
+        super::FORWARD_MEDIA_MAX_ITEMS,
         &mut out,
         &mut seen,
-        super::FORWARD_MEDIA_MAX_ITEMS,
-        &mut truncated,
     );
     (out, truncated)
 }
 
+/// 收集消息（含内嵌的转发内容）中的媒体，按 `seen` 去重；返回是否因 `max_items` 截断
+pub(super) fn collect_media(
+    messages: &[Value],
+    max_depth: usize,
+    max_items: usize,
+    out: &mut Vec<Value>,
+    seen: &mut HashSet<String>,
+) -> bool {
+    let mut truncated = false;
+    collect_forward_media_from_messages(
+        messages,
+        0,
+        max_depth,
+        out,
+        seen,
+        max_items,
+        &mut truncated,
+    );
+    truncated
+}
+
 fn truncate_to_chars(s: &mut String, max_chars: usize) -> bool {
     if max_chars == 0 {
         s.clear();
@@ -1,10 +1,21 @@
 mod api;
+mod chat_archive;
 mod command_exec;
 mod connection;
 mod discord;
 mod help_image;
+mod llm_usage;
 mod message;
+mod onebot_http;
+mod outbound;
 mod privacy;
+mod satori;
+mod state_db;
 
+pub use chat_archive::{search_chat_archive, start_chat_archive_maintenance, ChatArchiveQuery};
 pub use connection::{start_bot_connections, BotRuntime, GroupSendStatus};
 pub use discord::start_discord_connections;
+pub use llm_usage::{summarize_llm_usage, LlmUsageQuery};
+pub use onebot_http::{onebot_http_event_handler, start_onebot_http_connections};
+pub use outbound::start_outbound_config_sync;
+pub use satori::start_satori_connections;
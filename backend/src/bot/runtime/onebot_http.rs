@@ -0,0 +1,505 @@
+use crate::models::SharedState;
+use crate::persistence::save_bots;
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use hmac::{Hmac, Mac};
+use reqwest::Client as HttpClient;
+use serde_json::{json, Value};
+use sha1::Sha1;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+use tokio::task_local;
+use tokio::time::{sleep, Duration};
+use tracing::{info, warn};
+
+use super::connection::{BotConnection, BotRuntime, OneBotHttpConnection};
+use super::message::handle_event;
+
+/// 上报请求最多等待多久以便把第一条回复塞进快速操作响应。
+const QUICK_OPERATION_WAIT: Duration = Duration::from_millis(1500);
+const ONEBOT_HTTP_API_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone)]
+struct OneBotHttpConfig {
+    api_url: String,
+    access_token: String,
+    secret: String,
+}
+
+fn get_onebot_http_config(bot: &crate::models::BotInstance) -> Option<OneBotHttpConfig> {
+    let cfg = bot.metadata.get("onebot_http")?;
+    let get = |key: &str| {
+        cfg.get(key)
+            .and_then(|v| v.as_str())
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default()
+    };
+    Some(OneBotHttpConfig {
+        api_url: get("api_url").trim_end_matches('/').to_string(),
+        access_token: get("access_token"),
+        secret: get("secret"),
+    })
+}
+
+// ===== Quick operation =====
+
+struct QuickReplySlot {
+    user_id: u64,
+    group_id: Option<u64>,
+    tx: Option<oneshot::Sender<String>>,
+}
+
+task_local! {
+    static QUICK_REPLY: Arc<Mutex<QuickReplySlot>>;
+}
+
+/// 在 HTTP 上报的处理上下文中，把发往同一会话的第一条回复交给快速操作响应。
+/// 返回 `None` 表示已被快速操作接管；否则原样返回消息，调用方继续走 API 发送。
+///
+/// 上下文是 task-local 的，只有在 `handle_event` 任务内直接 await 的发送（指令回复、
+/// 插件 onMessage 输出、LLM 转发）才能被接管；`tokio::spawn` 出去的后台任务
+/// （插件请求超时检查、记忆摘要等）以及超过 `QUICK_OPERATION_WAIT` 的回复都走 API。
+pub(super) fn try_quick_reply(
+    user_id: u64,
+    group_id: Option<u64>,
+    message: String,
+) -> Option<String> {
+    let taken = QUICK_REPLY.try_with(|slot| {
+        let mut slot = slot.lock().ok()?;
+        if slot.group_id != group_id || (group_id.is_none() && slot.user_id != user_id) {
+            return None;
+        }
+        slot.tx.take()
+    });
+    let Ok(Some(tx)) = taken else {
+        return Some(message);
+    };
+    // 接收端已超时放弃时 send 会失败，消息退回普通发送路径。
+    tx.send(message).err()
+}
+
+fn parse_u64(v: Option<&Value>) -> Option<u64> {
+    match v? {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.trim().parse::<u64>().ok(),
+        _ => None,
+    }
+}
+
+// ===== Signature =====
+
+fn verify_signature(secret: &str, headers: &HeaderMap, body: &[u8]) -> bool {
+    let Some(sig) = headers
+        .get("x-signature")
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+    else {
+        return false;
+    };
+    let Some(hex_sig) = sig.strip_prefix("sha1=") else {
+        return false;
+    };
+    let Some(expected) = decode_hex(hex_sig) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha1>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// ===== Event endpoint =====
+
+/// OneBot HTTP POST 上报入口：校验 `X-Signature` 后交给 `handle_event`，
+/// 并在短时间内产生的第一条回复以快速操作（`{"reply": ...}`）形式返回。
+pub async fn onebot_http_event_handler(
+    State(state): State<SharedState>,
+    Extension(runtime): Extension<Arc<BotRuntime>>,
+    Path(bot_id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let cfg = state
+        .bots
+        .get(&bot_id)
+        .filter(|b| b.platform.eq_ignore_ascii_case("onebot"))
+        .and_then(|b| get_onebot_http_config(b.value()));
+    let Some(cfg) = cfg else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    // 没有 secret 时无法确认上报来源，直接拒绝。
+    if cfg.secret.is_empty() {
+        warn!("[{}] OneBot HTTP 上报被拒绝：未配置 secret", bot_id);
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if !verify_signature(&cfg.secret, &headers, &body) {
+        warn!("[{}] OneBot HTTP 上报签名校验失败", bot_id);
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let event: Value = match serde_json::from_slice(&body) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("[{}] OneBot HTTP 上报解析失败: {}", bot_id, e);
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+    };
+
+    if let Some(self_id) = parse_u64(event.get("self_id")) {
+        runtime.set_self_id(&bot_id, self_id).await;
+    }
+
+    let is_message = event.get("post_type").and_then(|v| v.as_str()) == Some("message");
+    let user_id = parse_u64(event.get("user_id")).unwrap_or(0);
+    let group_id = (event.get("message_type").and_then(|v| v.as_str()) == Some("group"))
+        .then(|| parse_u64(event.get("group_id")))
+        .flatten();
+
+    if !is_message {
+        tokio::spawn(async move {
+            handle_event(&state, &runtime, &bot_id, event).await;
+        });
+        return StatusCode::NO_CONTENT.into_response();
+    }
+
+    let (tx, rx) = oneshot::channel::<String>();
+    let slot = Arc::new(Mutex::new(QuickReplySlot {
+        user_id,
+        group_id,
+        tx: Some(tx),
+    }));
+    let slot_cl = slot.clone();
+    let mut task = tokio::spawn(async move {
+        QUICK_REPLY
+            .scope(slot_cl, handle_event(&state, &runtime, &bot_id, event))
+            .await;
+    });
+
+    let mut rx = rx;
+    let reply = tokio::select! {
+        r = &mut rx => r.ok(),
+        _ = &mut task => rx.try_recv().ok(),
+        _ = sleep(QUICK_OPERATION_WAIT) => None,
+    };
+    // 超时后关闭快速回复通道，后续回复都走普通 API 发送。
+    if let Ok(mut slot) = slot.lock() {
+        slot.tx = None;
+    }
+    // 等待超时与回复写入可能同时发生，通道关闭后再取一次，避免回复丢失
+    let reply = reply.or_else(|| rx.try_recv().ok());
+
+    match reply {
+        Some(message) => {
+            let mut op = json!({ "reply": message, "auto_escape": false });
+            if group_id.is_some() {
+                op["at_sender"] = json!(false);
+            }
+            Json(op).into_response()
+        }
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+// ===== API =====
+
+async fn onebot_http_post(
+    conn: &OneBotHttpConnection,
+    action: &str,
+    params: &Value,
+) -> Result<Value, String> {
+    let url = format!("{}/{}", conn.api_url, action);
+    let mut req = conn.http.post(&url).json(params);
+    if !conn.access_token.is_empty() {
+        req = req.header(
+            reqwest::header::AUTHORIZATION,
+            format!("Bearer {}", conn.access_token),
+        );
+    }
+    let resp = req
+        .send()
+        .await
+        .map_err(|e| format!("HTTP request failed: {e}"))?;
+    let status = resp.status();
+    let text = resp
+        .text()
+        .await
+        .map_err(|e| format!("read response failed: {e}"))?;
+    if !status.is_success() {
+        return Err(format!("HTTP {status}: {text}"));
+    }
+    serde_json::from_str(&text).map_err(|e| format!("parse response failed: {e}"))
+}
+
+/// OneBot HTTP API：POST `{api_url}/{action}`，返回与 WS 响应相同结构的 JSON。
+pub(super) async fn onebot_http_call_api(
+    bot_id: &str,
+    conn: &OneBotHttpConnection,
+    action: &str,
+    params: &Value,
+) -> Option<Value> {
+    info!("[{}] 发送 HTTP API 请求: action={}", bot_id, action);
+    match onebot_http_post(conn, action, params).await {
+        Ok(v) => Some(v),
+        Err(e) => {
+            warn!("[{}] OneBot HTTP API {} 失败: {}", bot_id, action, e);
+            None
+        }
+    }
+}
+
+// ===== Connection manager =====
+
+async fn probe_login(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    conn: &OneBotHttpConnection,
+) {
+    let resp = onebot_http_post(conn, "get_login_info", &json!({})).await;
+    let self_id = resp
+        .as_ref()
+        .ok()
+        .filter(|v| v.get("status").and_then(|s| s.as_str()) == Some("ok"))
+        .and_then(|v| parse_u64(v.get("data").and_then(|d| d.get("user_id"))));
+    let connected = self_id.is_some();
+    if let Some(self_id) = self_id {
+        runtime.set_self_id(bot_id, self_id).await;
+    }
+
+    let changed = match state.bots.get_mut(bot_id) {
+        Some(mut bot) if bot.is_connected != connected => {
+            bot.is_connected = connected;
+            if let Some(self_id) = self_id {
+                bot.qq_id = Some(self_id.to_string());
+            }
+            true
+        }
+        _ => false,
+    };
+    if changed {
+        save_bots(&state.bots);
+        if connected {
+            info!("[{}] OneBot HTTP 已连接", bot_id);
+        } else {
+            warn!("[{}] OneBot HTTP 不可达", bot_id);
+        }
+    }
+}
+
+pub async fn start_onebot_http_connections(state: SharedState, runtime: Arc<BotRuntime>) {
+    info!("启动 OneBot HTTP 连接管理循环...");
+
+    let http = match HttpClient::builder()
+        .timeout(ONEBOT_HTTP_API_TIMEOUT)
+        .build()
+    {
+        Ok(c) => c,
+        Err(e) => {
+            warn!("OneBot HTTP client 初始化失败: {}", e);
+            return;
+        }
+    };
+
+    let mut probe_tick: u32 = 0;
+    loop {
+        let bots: Vec<(String, bool, Option<OneBotHttpConfig>)> = state
+            .bots
+            .iter()
+            .filter(|b| b.platform.eq_ignore_ascii_case("onebot"))
+            .map(|b| {
+                (
+                    b.id.clone(),
+                    b.is_running,
+                    get_onebot_http_config(b.value()).filter(|c| !c.api_url.is_empty()),
+                )
+            })
+            .collect();
+
+        for (bot_id, is_running, cfg) in bots {
+            let current = match runtime.connections.read().await.get(&bot_id) {
+                Some(BotConnection::OneBotHttp(c)) => Some(c.clone()),
+                _ => None,
+            };
+
+            let Some(cfg) = cfg.filter(|_| is_running) else {
+                if current.is_some() {
+                    runtime.unregister_connection(&bot_id).await;
+                    if let Some(mut bot) = state.bots.get_mut(&bot_id) {
+                        bot.is_connected = false;
+                    }
+                    save_bots(&state.bots);
+                    info!("[{}] OneBot HTTP 已停止", bot_id);
+                }
+                continue;
+            };
+
+            let stale = current.as_ref().is_some_and(|c| {
+                c.api_url.as_str() != cfg.api_url || c.access_token.as_str() != cfg.access_token
+            });
+            let conn = match current {
+                Some(c) if !stale => c,
+                _ => {
+                    let conn = OneBotHttpConnection {
+                        api_url: Arc::new(cfg.api_url.clone()),
+                        access_token: Arc::new(cfg.access_token.clone()),
+                        http: http.clone(),
+                    };
+                    runtime
+                        .connections
+                        .write()
+                        .await
+                        .insert(bot_id.clone(), BotConnection::OneBotHttp(conn.clone()));
+                    info!("[{}] 注册 OneBot HTTP 连接: {}", bot_id, cfg.api_url);
+                    probe_login(&state, &runtime, &bot_id, &conn).await;
+                    continue;
+                }
+            };
+
+            // 每 30 秒探测一次在线状态。
+            if probe_tick.is_multiple_of(15) {
+                probe_login(&state, &runtime, &bot_id, &conn).await;
+            }
+        }
+
+        probe_tick = probe_tick.wrapping_add(1);
+        sleep(Duration::from_secs(2)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = b"The quick brown fox jumps over the lazy dog";
+    const SIGNATURE: &str = "sha1=de7c9b85b8b78aa6bc8a7a36f70a90701c9db4d9";
+
+    fn headers(sig: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-signature", sig.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn decodes_hex() {
+        assert_eq!(decode_hex("00ff7F"), Some(vec![0x00, 0xff, 0x7f]));
+        assert_eq!(decode_hex(""), Some(vec![]));
+        assert_eq!(decode_hex("abc"), None);
+        assert_eq!(decode_hex("zz"), None);
+        assert_eq!(decode_hex("é0"), None);
+    }
+
+    #[test]
+    fn accepts_valid_signature() {
+        assert!(verify_signature("key", &headers(SIGNATURE), BODY));
+        assert!(verify_signature(
+            "key",
+            &headers(&SIGNATURE.to_ascii_uppercase().replace("SHA1=", "sha1=")),
+            BODY
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_signatures() {
+        assert!(!verify_signature("other", &headers(SIGNATURE), BODY));
+        assert!(!verify_signature("key", &headers(SIGNATURE), b"tampered"));
+        assert!(!verify_signature("key", &headers(&SIGNATURE[5..]), BODY));
+        assert!(!verify_signature("key", &headers("sha1=de7c"), BODY));
+        assert!(!verify_signature("key", &HeaderMap::new(), BODY));
+    }
+
+    fn slot(
+        user_id: u64,
+        group_id: Option<u64>,
+    ) -> (Arc<Mutex<QuickReplySlot>>, oneshot::Receiver<String>) {
+        let (tx, rx) = oneshot::channel();
+        let slot = QuickReplySlot {
+            user_id,
+            group_id,
+            tx: Some(tx),
+        };
+        (Arc::new(Mutex::new(slot)), rx)
+    }
+
+    #[tokio::test]
+    async fn quick_reply_needs_event_context() {
+        assert_eq!(try_quick_reply(1, Some(2), "hi".into()), Some("hi".into()));
+    }
+
+    #[tokio::test]
+    async fn quick_reply_takes_only_first_reply_to_same_session() {
+        let (slot, mut rx) = slot(1, Some(2));
+        QUICK_REPLY
+            .scope(slot, async {
+                assert_eq!(
+                    try_quick_reply(1, Some(3), "other".into()),
+                    Some("other".into())
+                );
+                assert_eq!(
+                    try_quick_reply(1, None, "private".into()),
+                    Some("private".into())
+                );
+                // 群聊中发给其他成员的回复同样属于这个会话
+                assert_eq!(try_quick_reply(9, Some(2), "first".into()), None);
+                assert_eq!(
+                    try_quick_reply(1, Some(2), "second".into()),
+                    Some("second".into())
+                );
+            })
+            .await;
+        assert_eq!(rx.try_recv().ok().as_deref(), Some("first"));
+    }
+
+    #[tokio::test]
+    async fn private_quick_reply_matches_user() {
+        let (slot, mut rx) = slot(1, None);
+        QUICK_REPLY
+            .scope(slot, async {
+                assert_eq!(
+                    try_quick_reply(2, None, "other".into()),
+                    Some("other".into())
+                );
+                assert_eq!(try_quick_reply(1, None, "hi".into()), None);
+            })
+            .await;
+        assert_eq!(rx.try_recv().ok().as_deref(), Some("hi"));
+    }
+
+    #[tokio::test]
+    async fn spawned_tasks_do_not_inherit_quick_reply() {
+        let (slot, mut rx) = slot(1, Some(2));
+        let spawned = QUICK_REPLY
+            .scope(slot, async {
+                tokio::spawn(async { try_quick_reply(1, Some(2), "late".into()) })
+                    .await
+                    .unwrap()
+            })
+            .await;
+        assert_eq!(spawned, Some("late".into()));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn quick_reply_falls_back_after_receiver_gave_up() {
+        let (slot, rx) = slot(1, Some(2));
+        drop(rx);
+        let reply = QUICK_REPLY
+            .scope(slot, async { try_quick_reply(1, Some(2), "hi".into()) })
+            .await;
+        assert_eq!(reply, Some("hi".into()));
+    }
+}
@@ -0,0 +1,658 @@
+//! 发送队列：每个 bot 一条出站队列，按群/全局限速、管理员优先、失败重试，
+//! 超过重试次数的动作进入死信列表（可通过 API 查看）。
+//! 不同会话的发送互不等待，同一会话同时只有一条在发送以保证顺序。
+//! `outbound` 模块默认关闭，此时不限速、不切块，只保留失败重试。
+
+use crate::models::SharedState;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{oneshot, Mutex, Notify};
+use tokio::task_local;
+use tracing::{info, warn};
+
+use super::connection::BotRuntime;
+
+const OUTBOUND_MODULE_ID: &str = "outbound";
+const DEAD_LETTER_MAX: usize = 200;
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+const IDLE_POLL: Duration = Duration::from_millis(500);
+
+/// 长消息切块后各块共享的编号
+static NEXT_SPLIT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// 等待发送结果的一方（插件 onApiResponse），收到 OneBot 风格响应。
+pub(super) type ApiResultSender = oneshot::Sender<Value>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum OutboundPriority {
+    High,
+    Normal,
+}
+
+task_local! {
+    static PRIORITY: OutboundPriority;
+}
+
+/// 在该作用域内入队的发送动作使用指定优先级（管理员消息的回复走 High）。
+pub(super) async fn with_priority<T>(
+    priority: OutboundPriority,
+    fut: impl std::future::Future<Output = T>,
+) -> T {
+    PRIORITY.scope(priority, fut).await
+}
+
+fn current_priority() -> OutboundPriority {
+    PRIORITY
+        .try_with(|p| *p)
+        .unwrap_or(OutboundPriority::Normal)
+}
+
+/// 发送失败的类型：可重试（超时/风控/服务端错误）或直接放弃（参数错误、无权限等）。
+pub(super) enum SendFailure {
+    Retry(String),
+    Fatal(String),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct OutboundConfig {
+    /// 每分钟全局最多发送条数（0 = 不限制）
+    global_per_minute: usize,
+    /// 每分钟单个会话最多发送条数（0 = 不限制）
+    target_per_minute: usize,
+    /// 同一会话两条消息之间的最小间隔
+    target_min_interval: Duration,
+    max_retries: u32,
+    retry_delay: Duration,
+    /// 超长文本按此字符数切块分批发送（0 = 不切分）
+    chunk_max_chars: usize,
+}
+
+/// 未配置时不限速、不切块
+impl Default for OutboundConfig {
+    fn default() -> Self {
+        Self {
+            global_per_minute: 0,
+            target_per_minute: 0,
+            target_min_interval: Duration::ZERO,
+            max_retries: 2,
+            retry_delay: Duration::from_millis(2000),
+            chunk_max_chars: 0,
+        }
+    }
+}
+
+impl OutboundConfig {
+    fn from_state(state: &SharedState, bot_id: &str) -> Self {
+        let mut cfg = Self::default();
+        let Some(module) = crate::module::get_effective_module(state, bot_id, OUTBOUND_MODULE_ID)
+        else {
+            return cfg;
+        };
+        // 模块关闭时不限速、不切块，只保留失败重试。
+        if !module.enabled {
+            cfg.global_per_minute = 0;
+            cfg.target_per_minute = 0;
+            cfg.target_min_interval = Duration::ZERO;
+            cfg.chunk_max_chars = 0;
+            return cfg;
+        }
+        let c = &module.config;
+        let get = |key: &str| c.get(key).and_then(|v| v.as_u64());
+        if let Some(v) = get("global_per_minute") {
+            cfg.global_per_minute = v as usize;
+        }
+        if let Some(v) = get("group_per_minute") {
+            cfg.target_per_minute = v as usize;
+        }
+        if let Some(v) = get("min_interval_ms") {
+            cfg.target_min_interval = Duration::from_millis(v);
+        }
+        if let Some(v) = get("max_retries") {
+            cfg.max_retries = v.min(10) as u32;
+        }
+        if let Some(v) = get("retry_delay_ms") {
+            cfg.retry_delay = Duration::from_millis(v);
+        }
+        if let Some(v) = get("chunk_max_chars") {
+            cfg.chunk_max_chars = v as usize;
+        }
+        cfg
+    }
+}
+
+struct OutboundItem {
+    action: String,
+    params: Value,
+    target: String,
+    attempts: u32,
+    not_before: Instant,
+    /// 切块发送时只跟随第一块
+    result: Option<ApiResultSender>,
+    /// 同一条长消息切出的各块编号相同
+    split_id: Option<u64>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DeadLetter {
+    pub bot_id: String,
+    pub action: String,
+    pub params: Value,
+    pub attempts: u32,
+    pub error: String,
+    pub time: u64,
+}
+
+#[derive(Default)]
+struct BotQueue {
+    high: VecDeque<OutboundItem>,
+    normal: VecDeque<OutboundItem>,
+    worker_running: bool,
+    /// 正在发送的会话，发送完成前不取该会话的下一条
+    in_flight: HashSet<String>,
+    wake: Arc<Notify>,
+    global_sent: VecDeque<Instant>,
+    target_sent: HashMap<String, VecDeque<Instant>>,
+}
+
+impl BotQueue {
+    fn len(&self) -> usize {
+        self.high.len() + self.normal.len()
+    }
+
+    fn queue_mut(&mut self, priority: OutboundPriority) -> &mut VecDeque<OutboundItem> {
+        match priority {
+            OutboundPriority::High => &mut self.high,
+            OutboundPriority::Normal => &mut self.normal,
+        }
+    }
+
+    /// 返回本会话下一条允许发送的时间。
+    fn target_ready_at(&self, target: &str, cfg: &OutboundConfig, now: Instant) -> Instant {
+        let Some(sent) = self.target_sent.get(target) else {
+            return now;
+        };
+        let mut ready = now;
+        if let Some(last) = sent.back() {
+            ready = ready.max(*last + cfg.target_min_interval);
+        }
+        if cfg.target_per_minute > 0 && sent.len() >= cfg.target_per_minute {
+            if let Some(first) = sent.get(sent.len() - cfg.target_per_minute) {
+                ready = ready.max(*first + RATE_WINDOW);
+            }
+        }
+        ready
+    }
+
+    fn global_ready_at(&self, cfg: &OutboundConfig, now: Instant) -> Instant {
+        if cfg.global_per_minute == 0 || self.global_sent.len() < cfg.global_per_minute {
+            return now;
+        }
+        self.global_sent
+            .get(self.global_sent.len() - cfg.global_per_minute)
+            .map(|first| *first + RATE_WINDOW)
+            .unwrap_or(now)
+    }
+
+    fn prune(&mut self, now: Instant) {
+        let expired = |t: &Instant| now.duration_since(*t) >= RATE_WINDOW;
+        while self.global_sent.front().is_some_and(expired) {
+            self.global_sent.pop_front();
+        }
+        self.target_sent.retain(|_, sent| {
+            while sent.front().is_some_and(expired) {
+                sent.pop_front();
+            }
+            !sent.is_empty()
+        });
+    }
+
+    /// 取出下一条可发送的动作；同一会话只看队首，保证会话内顺序不乱。
+    /// 没有可发送的动作时返回最早的可发送时间。
+    fn take_next(
+        &mut self,
+        cfg: &OutboundConfig,
+        now: Instant,
+    ) -> Result<(OutboundPriority, OutboundItem), Option<Instant>> {
+        self.prune(now);
+        let global_ready = self.global_ready_at(cfg, now);
+        if global_ready > now {
+            return Err(Some(global_ready));
+        }
+
+        let mut earliest: Option<Instant> = None;
+        for priority in [OutboundPriority::High, OutboundPriority::Normal] {
+            let mut seen: Vec<&str> = Vec::new();
+            let mut pick: Option<usize> = None;
+            let queue = match priority {
+                OutboundPriority::High => &self.high,
+                OutboundPriority::Normal => &self.normal,
+            };
+            for (idx, item) in queue.iter().enumerate() {
+                if seen.contains(&item.target.as_str()) {
+                    continue;
+                }
+                seen.push(item.target.as_str());
+                if self.in_flight.contains(&item.target) {
+                    continue;
+                }
+                let ready = self
+                    .target_ready_at(&item.target, cfg, now)
+                    .max(item.not_before);
+                if ready <= now {
+                    pick = Some(idx);
+                    break;
+                }
+                earliest = Some(earliest.map_or(ready, |e| e.min(ready)));
+            }
+            if let Some(idx) = pick {
+                let item = self.queue_mut(priority).remove(idx);
+                return item.map(|item| (priority, item)).ok_or(earliest);
+            }
+        }
+        Err(earliest)
+    }
+
+    /// 丢弃同一条长消息中尚未发送的块
+    fn drop_split(&mut self, split_id: u64) -> usize {
+        let before = self.len();
+        for queue in [&mut self.high, &mut self.normal] {
+            queue.retain(|item| item.split_id != Some(split_id));
+        }
+        before - self.len()
+    }
+
+    /// 标记需要发送任务；返回是否需要新启动一个
+    fn claim_worker(&mut self) -> bool {
+        !std::mem::replace(&mut self.worker_running, true)
+    }
+
+    fn mark_sent(&mut self, target: &str, now: Instant) {
+        self.global_sent.push_back(now);
+        self.target_sent
+            .entry(target.to_string())
+            .or_default()
+            .push_back(now);
+    }
+}
+
+/// 所有 bot 的出站队列与死信列表
+#[derive(Default)]
+pub struct OutboundQueues {
+    queues: Mutex<HashMap<String, BotQueue>>,
+    configs: std::sync::RwLock<HashMap<String, OutboundConfig>>,
+    dead_letters: Mutex<VecDeque<DeadLetter>>,
+}
+
+impl OutboundQueues {
+    fn config_for(&self, bot_id: &str) -> OutboundConfig {
+        self.configs
+            .read()
+            .ok()
+            .and_then(|m| m.get(bot_id).copied())
+            .unwrap_or_default()
+    }
+
+    pub async fn pending_count(&self, bot_id: Option<&str>) -> usize {
+        let queues = self.queues.lock().await;
+        match bot_id {
+            Some(id) => queues.get(id).map(BotQueue::len).unwrap_or(0),
+            None => queues.values().map(BotQueue::len).sum(),
+        }
+    }
+
+    pub async fn dead_letters(&self, bot_id: Option<&str>) -> Vec<DeadLetter> {
+        self.dead_letters
+            .lock()
+            .await
+            .iter()
+            .filter(|d| bot_id.is_none_or(|id| d.bot_id == id))
+            .cloned()
+            .collect()
+    }
+
+    pub async fn clear_dead_letters(&self, bot_id: Option<&str>) -> usize {
+        let mut list = self.dead_letters.lock().await;
+        let before = list.len();
+        list.retain(|d| bot_id.is_some_and(|id| d.bot_id != id));
+        before - list.len()
+    }
+
+    async fn push_dead_letter(&self, bot_id: &str, item: OutboundItem, error: String) {
+        warn!(
+            "[{}] 发送 {} 失败（已尝试 {} 次），进入死信列表: {}",
+            bot_id, item.action, item.attempts, error
+        );
+        let mut list = self.dead_letters.lock().await;
+        list.push_back(DeadLetter {
+            bot_id: bot_id.to_string(),
+            action: item.action,
+            params: item.params,
+            attempts: item.attempts,
+            error,
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        });
+        while list.len() > DEAD_LETTER_MAX {
+            list.pop_front();
+        }
+    }
+}
+
+fn target_key(action: &str, params: &Value) -> String {
+    let id = |key: &str| {
+        params
+            .get(key)
+            .map(|v| match v {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })
+            .unwrap_or_default()
+    };
+    let is_private = match action {
+        "send_private_msg" | "send_private_forward_msg" => true,
+        "send_msg" => params.get("message_type").and_then(|v| v.as_str()) == Some("private"),
+        "send_forward_msg" => params.get("group_id").is_none(),
+        _ => false,
+    };
+    if is_private {
+        format!("u:{}", id("user_id"))
+    } else {
+        format!("g:{}", id("group_id"))
+    }
+}
+
+/// 需要排队限速的发送类动作
+pub(super) fn is_queued_action(action: &str) -> bool {
+    matches!(
+        action,
+        "send_group_msg"
+            | "send_private_msg"
+            | "send_msg"
+            | "send_forward_msg"
+            | "send_group_forward_msg"
+            | "send_private_forward_msg"
+    )
+}
+
+/// 按当前切块配置，这段文本能否一条发完（OneBot HTTP 快速回复只接管这种消息）。
+pub(super) fn fits_one_chunk(runtime: &BotRuntime, bot_id: &str, text: &str) -> bool {
+    let max_chars = runtime.outbound.config_for(bot_id).chunk_max_chars;
+    max_chars == 0 || text.chars().count() <= max_chars
+}
+
+/// 把超长文本按行切块；单行超长时按字符硬切。不会切开 CQ 码。
+fn split_text_chunks(text: &str, max_chars: usize) -> Vec<String> {
+    if max_chars == 0 || text.chars().count() <= max_chars {
+        return vec![text.to_string()];
+    }
+
+    let mut chunks: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0usize;
+    for line in text.split_inclusive('\n') {
+        let line_len = line.chars().count();
+        if current_len + line_len > max_chars && !current.is_empty() {
+            chunks.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+        if line_len <= max_chars {
+            current.push_str(line);
+            current_len += line_len;
+            continue;
+        }
+        // 单行超长：按字符切，但遇到 CQ 码时整段保留。
+        let mut in_cq = false;
+        for (idx, ch) in line.char_indices() {
+            if ch == '[' && line[idx..].starts_with("[CQ:") {
+                in_cq = true;
+            }
+            if current_len >= max_chars && !in_cq {
+                chunks.push(std::mem::take(&mut current));
+                current_len = 0;
+            }
+            if ch == ']' {
+                in_cq = false;
+            }
+            current.push(ch);
+            current_len += 1;
+        }
+    }
+    if !current.trim().is_empty() {
+        chunks.push(current);
+    }
+    chunks
+        .into_iter()
+        .map(|c| c.trim_end_matches('\n').to_string())
+        .filter(|c| !c.trim().is_empty())
+        .collect()
+}
+
+/// 发送动作入队；该 bot 没有运行中的发送任务时启动一个。
+pub(super) async fn enqueue(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    action: &str,
+    params: Value,
+    result: Option<ApiResultSender>,
+) {
+    let priority = current_priority();
+    let item = OutboundItem {
+        target: target_key(action, &params),
+        action: action.to_string(),
+        params,
+        attempts: 0,
+        not_before: Instant::now(),
+        result,
+        split_id: None,
+    };
+
+    let spawn_worker = {
+        let mut queues = runtime.outbound.queues.lock().await;
+        let queue = queues.entry(bot_id.to_string()).or_default();
+        queue.queue_mut(priority).push_back(item);
+        queue.wake.notify_one();
+        queue.claim_worker()
+    };
+
+    if spawn_worker {
+        spawn_worker_task(runtime, bot_id);
+    }
+}
+
+fn spawn_worker_task(runtime: &Arc<BotRuntime>, bot_id: &str) {
+    let runtime = runtime.clone();
+    let bot_id = bot_id.to_string();
+    tokio::spawn(async move {
+        run_worker(runtime, bot_id).await;
+    });
+}
+
+/// 取出可发送的动作交给独立任务发送；队列空时退出。
+async fn run_worker(runtime: Arc<BotRuntime>, bot_id: String) {
+    loop {
+        let cfg = runtime.outbound.config_for(&bot_id);
+        let now = Instant::now();
+        let (next, wake) = {
+            let mut queues = runtime.outbound.queues.lock().await;
+            let Some(queue) = queues.get_mut(&bot_id) else {
+                return;
+            };
+            if queue.len() == 0 {
+                queue.worker_running = false;
+                return;
+            }
+            let next = match queue.take_next(&cfg, now) {
+                Ok((priority, mut item)) => {
+                    // 超长文本切块：先发第一块，其余块按原顺序放回队首，受同一会话限速约束。
+                    if let Some(Value::String(text)) = item.params.get("message") {
+                        let chunks = split_text_chunks(text, cfg.chunk_max_chars);
+                        if chunks.len() > 1 {
+                            info!("[{}] 长消息切分为 {} 段分批发送", bot_id, chunks.len());
+                            let split_id = NEXT_SPLIT_ID.fetch_add(1, Ordering::Relaxed);
+                            let q = queue.queue_mut(priority);
+                            for chunk in chunks.iter().skip(1).rev() {
+                                let mut params = item.params.clone();
+                                params["message"] = Value::String(chunk.clone());
+                                q.push_front(OutboundItem {
+                                    action: item.action.clone(),
+                                    params,
+                                    target: item.target.clone(),
+                                    attempts: 0,
+                                    not_before: now,
+                                    result: None,
+                                    split_id: Some(split_id),
+                                });
+                            }
+                            item.params["message"] = Value::String(chunks[0].clone());
+                            item.split_id = Some(split_id);
+                        }
+                    }
+                    queue.mark_sent(&item.target, now);
+                    queue.in_flight.insert(item.target.clone());
+                    Ok((priority, item))
+                }
+                Err(wait_until) => Err(wait_until),
+            };
+            (next, queue.wake.clone())
+        };
+
+        match next {
+            Ok((priority, item)) => {
+                let runtime = runtime.clone();
+                let bot_id = bot_id.clone();
+                tokio::spawn(async move {
+                    send_item(runtime, bot_id, priority, item, cfg).await;
+                });
+            }
+            Err(wait_until) => {
+                let wait = wait_until
+                    .map(|t| t.saturating_duration_since(now))
+                    .unwrap_or(IDLE_POLL)
+                    .clamp(Duration::from_millis(20), IDLE_POLL * 4);
+                // 有新动作入队或某个会话发送完成时提前醒来
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => {}
+                    _ = wake.notified() => {}
+                }
+            }
+        }
+    }
+}
+
+/// 发送一条动作；失败时重试或进入死信，完成后释放该会话。
+async fn send_item(
+    runtime: Arc<BotRuntime>,
+    bot_id: String,
+    priority: OutboundPriority,
+    mut item: OutboundItem,
+    cfg: OutboundConfig,
+) {
+    item.attempts += 1;
+    let target = item.target.clone();
+    let result =
+        super::api::dispatch_api(&runtime, &bot_id, &item.action, &item.params, true).await;
+
+    let mut retry = None;
+    let mut dead = None;
+    match result {
+        Ok(resp) => super::api::respond(item.result.take(), resp),
+        Err(SendFailure::Retry(e)) if item.attempts <= cfg.max_retries => {
+            warn!(
+                "[{}] 发送 {} 失败，第 {} 次重试: {}",
+                bot_id, item.action, item.attempts, e
+            );
+            item.not_before = Instant::now() + cfg.retry_delay * item.attempts;
+            retry = Some(item);
+        }
+        Err(SendFailure::Retry(e)) | Err(SendFailure::Fatal(e)) => {
+            super::api::respond(item.result.take(), super::api::api_failed(&e));
+            dead = Some((item, e));
+        }
+    }
+
+    let spawn_worker = {
+        let mut queues = runtime.outbound.queues.lock().await;
+        let queue = queues.entry(bot_id.clone()).or_default();
+        queue.in_flight.remove(&target);
+        if let Some(item) = retry {
+            queue.queue_mut(priority).push_front(item);
+        }
+        // 长消息的某一块发不出去时，其余块也不再发送
+        if let Some(split_id) = dead.as_ref().and_then(|(item, _)| item.split_id) {
+            let dropped = queue.drop_split(split_id);
+            if dropped > 0 {
+                warn!("[{}] 长消息发送失败，丢弃剩余 {} 段", bot_id, dropped);
+            }
+        }
+        queue.wake.notify_one();
+        queue.len() > 0 && queue.claim_worker()
+    };
+    if spawn_worker {
+        spawn_worker_task(&runtime, &bot_id);
+    }
+    if let Some((item, e)) = dead {
+        runtime.outbound.push_dead_letter(&bot_id, item, e).await;
+    }
+}
+
+/// 定期从模块配置同步各 bot 的限速参数（发送路径拿不到 `SharedState`）。
+pub async fn start_outbound_config_sync(state: SharedState, runtime: Arc<BotRuntime>) {
+    loop {
+        let bot_ids: Vec<String> = state.bots.iter().map(|b| b.id.clone()).collect();
+        let configs: HashMap<String, OutboundConfig> = bot_ids
+            .into_iter()
+            .map(|id| {
+                let cfg = OutboundConfig::from_state(&state, &id);
+                (id, cfg)
+            })
+            .collect();
+        if let Ok(mut map) = runtime.outbound.configs.write() {
+            *map = configs;
+        }
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_text_is_one_chunk() {
+        assert_eq!(split_text_chunks("hello", 10), ["hello"]);
+        assert_eq!(split_text_chunks("no limit", 0), ["no limit"]);
+    }
+
+    #[test]
+    fn splits_on_line_boundaries() {
+        let chunks = split_text_chunks("aaa\nbbb\nccc\n", 8);
+        assert_eq!(chunks, ["aaa\nbbb", "ccc"]);
+    }
+
+    #[test]
+    fn splits_long_lines_by_chars() {
+        let chunks = split_text_chunks("一二三四五六七", 3);
+        assert_eq!(chunks, ["一二三", "四五六", "七"]);
+    }
+
+    #[test]
+    fn keeps_cq_codes_whole() {
+        let chunks = split_text_chunks("ab[CQ:face,id=14]cd", 3);
+        assert_eq!(chunks, ["ab[CQ:face,id=14]", "cd"]);
+        assert!(chunks
+            .iter()
+            .all(|c| c.matches('[').count() == c.matches(']').count()));
+    }
+
+    #[test]
+    fn drops_blank_chunks() {
+        let chunks = split_text_chunks("abc\n\n\n\ndef", 4);
+        assert!(chunks.iter().all(|c| !c.trim().is_empty()));
+        assert_eq!(chunks.concat().replace('\n', ""), "abcdef");
+    }
+}
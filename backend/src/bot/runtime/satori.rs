@@ -0,0 +1,1580 @@
+use crate::models::SharedState;
+use crate::persistence::save_bots;
+use base64::Engine;
+use futures_util::{SinkExt, StreamExt};
+use reqwest::Client as HttpClient;
+use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::time::{sleep, Duration};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use tracing::{error, info, warn};
+
+use super::connection::{BotConnection, BotRuntime, SatoriConnection};
+use super::message::handle_event;
+
+// Satori signaling opcodes.
+const OP_EVENT: i64 = 0;
+const OP_PING: i64 = 1;
+const OP_PONG: i64 = 2;
+const OP_IDENTIFY: i64 = 3;
+const OP_READY: i64 = 4;
+
+const SATORI_PING_INTERVAL: Duration = Duration::from_secs(10);
+const SATORI_ID_MAP_MAX: usize = 8192;
+const SATORI_MEMBER_LIST_MAX_PAGES: usize = 50;
+
+/// Satori 侧的 ID 都是字符串；内部事件/插件输出沿用 u64，这里维护双向映射。
+#[derive(Default)]
+pub struct SatoriIdState {
+    to_str: RecentMap<u64, String>,
+    msg_channel: RecentMap<u64, String>,
+    channel_guild: RecentMap<String, String>,
+}
+
+/// 超过 `SATORI_ID_MAP_MAX` 时按写入顺序淘汰最早的条目
+struct RecentMap<K, V> {
+    map: HashMap<K, V>,
+    order: VecDeque<K>,
+}
+
+impl<K, V> Default for RecentMap<K, V> {
+    fn default() -> Self {
+        Self {
+            map: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V> RecentMap<K, V> {
+    fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.get(key)
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        if self.map.insert(key.clone(), value).is_none() {
+            self.order.push_back(key);
+        }
+        while self.map.len() > SATORI_ID_MAP_MAX {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            self.map.remove(&oldest);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SatoriLogin {
+    pub platform: String,
+    pub self_id: String,
+}
+
+#[derive(Debug, Clone)]
+struct SatoriBotConfig {
+    endpoint: String,
+    token: String,
+    platform: String,
+}
+
+fn get_satori_config_from_bot(bot: &crate::models::BotInstance) -> Option<SatoriBotConfig> {
+    let cfg = bot.metadata.get("satori")?;
+    let endpoint = cfg
+        .get("endpoint")
+        .and_then(|v| v.as_str())
+        .map(normalize_endpoint)
+        .filter(|s| !s.is_empty())?;
+    let token = cfg
+        .get("token")
+        .and_then(|v| v.as_str())
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default();
+    let platform = cfg
+        .get("platform")
+        .and_then(|v| v.as_str())
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default();
+    Some(SatoriBotConfig {
+        endpoint,
+        token,
+        platform,
+    })
+}
+
+fn normalize_endpoint(endpoint: &str) -> String {
+    let s = endpoint.trim().trim_end_matches('/');
+    s.strip_suffix("/v1").unwrap_or(s).to_string()
+}
+
+fn events_url(endpoint: &str) -> String {
+    let ws = if let Some(rest) = endpoint.strip_prefix("https://") {
+        format!("wss://{rest}")
+    } else if let Some(rest) = endpoint.strip_prefix("http://") {
+        format!("ws://{rest}")
+    } else {
+        endpoint.to_string()
+    };
+    format!("{ws}/v1/events")
+}
+
+fn take_id(v: Option<&Value>) -> Option<String> {
+    match v? {
+        Value::String(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// 纯数字 ID 直接使用；其它 ID 取稳定哈希（最高位置 1，避免与真实数字 ID 冲突）。
+pub(super) fn satori_id_to_u64(conn: &SatoriConnection, id: &str) -> u64 {
+    if let Ok(n) = id.parse::<u64>() {
+        if n.to_string() == id {
+            return n;
+        }
+    }
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    let n = hasher.finish() | (1u64 << 63);
+    if let Ok(mut ids) = conn.ids.lock() {
+        ids.to_str.insert(n, id.to_string());
+    }
+    n
+}
+
+pub(super) fn satori_id_from_u64(conn: &SatoriConnection, id: u64) -> String {
+    conn.ids
+        .lock()
+        .ok()
+        .and_then(|ids| ids.to_str.get(&id).cloned())
+        .unwrap_or_else(|| id.to_string())
+}
+
+fn satori_id_from_value(conn: &SatoriConnection, v: Option<&Value>) -> Option<String> {
+    match v? {
+        Value::Number(n) => n.as_u64().map(|n| satori_id_from_u64(conn, n)),
+        Value::String(s) => {
+            let s = s.trim();
+            if s.is_empty() {
+                return None;
+            }
+            match s.parse::<u64>() {
+                Ok(n) => Some(satori_id_from_u64(conn, n)),
+                Err(_) => Some(s.to_string()),
+            }
+        }
+        _ => None,
+    }
+}
+
+fn remember_message_channel(conn: &SatoriConnection, message_id: u64, channel_id: &str) {
+    if let Ok(mut ids) = conn.ids.lock() {
+        ids.msg_channel.insert(message_id, channel_id.to_string());
+    }
+}
+
+fn remember_channel_guild(conn: &SatoriConnection, channel_id: &str, guild_id: &str) {
+    if let Ok(mut ids) = conn.ids.lock() {
+        ids.channel_guild
+            .insert(channel_id.to_string(), guild_id.to_string());
+    }
+}
+
+fn guild_for_channel(conn: &SatoriConnection, channel_id: &str) -> String {
+    conn.ids
+        .lock()
+        .ok()
+        .and_then(|ids| ids.channel_guild.get(channel_id).cloned())
+        .unwrap_or_else(|| channel_id.to_string())
+}
+
+// ===== Element <-> segment =====
+
+fn unescape_element_text(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(pos) = rest.find('&') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        let Some(end) = rest.find(';').filter(|e| *e <= 10) else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|h| u32::from_str_radix(h, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse::<u32>().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn escape_element_text(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ElementToken {
+    Text(String),
+    Open {
+        name: String,
+        attrs: HashMap<String, String>,
+        self_closing: bool,
+    },
+    Close(String),
+}
+
+fn parse_element_attrs(s: &str) -> HashMap<String, String> {
+    let mut attrs = HashMap::new();
+    let bytes = s.as_bytes();
+    let mut i = 0usize;
+    while i < bytes.len() {
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        let start = i;
+        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'=' {
+            i += 1;
+        }
+        if start == i {
+            i += 1;
+            continue;
+        }
+        let key = s[start..i].to_string();
+        if i < bytes.len() && bytes[i] == b'=' {
+            i += 1;
+            let quote = bytes.get(i).copied();
+            if quote == Some(b'"') || quote == Some(b'\'') {
+                let q = quote.unwrap_or(b'"');
+                i += 1;
+                let vstart = i;
+                while i < bytes.len() && bytes[i] != q {
+                    i += 1;
+                }
+                attrs.insert(key, unescape_element_text(&s[vstart..i]));
+                i += 1;
+            } else {
+                let vstart = i;
+                while i < bytes.len() && !bytes[i].is_ascii_whitespace() {
+                    i += 1;
+                }
+                attrs.insert(key, unescape_element_text(&s[vstart..i]));
+            }
+        } else {
+            attrs.insert(key, "true".to_string());
+        }
+    }
+    attrs
+}
+
+fn tokenize_elements(content: &str) -> Vec<ElementToken> {
+    let mut out = Vec::new();
+    let mut rest = content;
+    while let Some(lt) = rest.find('<') {
+        let Some(gt_rel) = rest[lt..].find('>') else {
+            break;
+        };
+        let inner = &rest[lt + 1..lt + gt_rel];
+        let name_ok = inner
+            .trim_start_matches('/')
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic());
+        if !name_ok {
+            // Not a tag (e.g. "a < b"); keep as text.
+            out.push(ElementToken::Text(unescape_element_text(&rest[..lt + 1])));
+            rest = &rest[lt + 1..];
+            continue;
+        }
+        if lt > 0 {
+            out.push(ElementToken::Text(unescape_element_text(&rest[..lt])));
+        }
+        rest = &rest[lt + gt_rel + 1..];
+
+        if let Some(name) = inner.strip_prefix('/') {
+            out.push(ElementToken::Close(name.trim().to_ascii_lowercase()));
+            continue;
+        }
+        let self_closing = inner.ends_with('/');
+        let inner = inner.trim_end_matches('/');
+        let (name, attrs) = match inner.find(|c: char| c.is_ascii_whitespace()) {
+            Some(pos) => (&inner[..pos], parse_element_attrs(&inner[pos..])),
+            None => (inner, HashMap::new()),
+        };
+        out.push(ElementToken::Open {
+            name: name.to_ascii_lowercase(),
+            attrs,
+            self_closing,
+        });
+    }
+    if !rest.is_empty() {
+        out.push(ElementToken::Text(unescape_element_text(rest)));
+    }
+    out
+}
+
+fn push_text_segment(segments: &mut Vec<Value>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    if let Some(last) = segments.last_mut() {
+        if last.get("type").and_then(|t| t.as_str()) == Some("text") {
+            let prev = last["data"]["text"].as_str().unwrap_or("").to_string();
+            last["data"]["text"] = Value::String(format!("{prev}{text}"));
+            return;
+        }
+    }
+    segments.push(json!({ "type": "text", "data": { "text": text } }));
+}
+
+fn media_segment(ty: &str, attrs: &HashMap<String, String>) -> Option<Value> {
+    let src = attrs.get("src").or_else(|| attrs.get("url"))?;
+    let name = attrs
+        .get("title")
+        .or_else(|| attrs.get("name"))
+        .cloned()
+        .unwrap_or_else(|| {
+            src.rsplit('/')
+                .next()
+                .and_then(|s| s.split('?').next())
+                .filter(|s| !s.is_empty() && !s.starts_with("data:"))
+                .unwrap_or(ty)
+                .to_string()
+        });
+    Some(json!({
+        "type": ty,
+        "data": {
+            "url": src,
+            "file": name,
+            "name": name,
+        }
+    }))
+}
+
+/// 将 Satori 消息元素转换为 OneBot 消息段（未知元素保留其文本内容）。
+fn satori_content_to_segments(conn: &SatoriConnection, content: &str) -> Vec<Value> {
+    let mut segments: Vec<Value> = Vec::new();
+    // Children of these elements are not part of the visible message body.
+    let mut skip_stack: Vec<String> = Vec::new();
+
+    for token in tokenize_elements(content) {
+        if let Some(skipping) = skip_stack.last() {
+            match &token {
+                ElementToken::Open {
+                    name,
+                    self_closing: false,
+                    ..
+                } if name == skipping => skip_stack.push(name.clone()),
+                ElementToken::Close(name) if name == skipping => {
+                    skip_stack.pop();
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        match token {
+            ElementToken::Text(text) => push_text_segment(&mut segments, &text),
+            ElementToken::Close(name) => {
+                if name == "p" {
+                    push_text_segment(&mut segments, "\n");
+                }
+            }
+            ElementToken::Open {
+                name,
+                attrs,
+                self_closing,
+            } => {
+                let seg = match name.as_str() {
+                    "at" => {
+                        if attrs.get("type").map(|s| s.as_str()) == Some("all") {
+                            Some(json!({ "type": "at", "data": { "qq": "all" } }))
+                        } else {
+                            attrs.get("id").map(|id| {
+                                json!({
+                                    "type": "at",
+                                    "data": {
+                                        "qq": satori_id_to_u64(conn, id).to_string(),
+                                        "name": attrs.get("name"),
+                                    }
+                                })
+                            })
+                        }
+                    }
+                    "quote" => attrs.get("id").map(|id| {
+                        json!({
+                            "type": "reply",
+                            "data": { "id": satori_id_to_u64(conn, id).to_string() }
+                        })
+                    }),
+                    "img" | "image" => media_segment("image", &attrs),
+                    "audio" => media_segment("record", &attrs),
+                    "video" => media_segment("video", &attrs),
+                    "file" => media_segment("file", &attrs),
+                    "face" => attrs.get("id").map(|id| {
+                        json!({ "type": "face", "data": { "id": id, "name": attrs.get("name") } })
+                    }),
+                    "br" => {
+                        push_text_segment(&mut segments, "\n");
+                        None
+                    }
+                    "sharp" => {
+                        let label = attrs.get("name").or_else(|| attrs.get("id"));
+                        if let Some(label) = label {
+                            push_text_segment(&mut segments, &format!("#{label}"));
+                        }
+                        None
+                    }
+                    _ => None,
+                };
+
+                let skip_children = matches!(name.as_str(), "quote" | "author" | "message");
+                if skip_children && !self_closing {
+                    skip_stack.push(name.clone());
+                }
+
+                if let Some(seg) = seg {
+                    segments.push(seg);
+                }
+            }
+        }
+    }
+
+    segments
+}
+
+fn cq_unescape(s: &str) -> String {
+    s.replace("&#91;", "[")
+        .replace("&#93;", "]")
+        .replace("&#44;", ",")
+        .replace("&amp;", "&")
+}
+
+fn cq_escape(s: &str, in_param: bool) -> String {
+    let out = s
+        .replace('&', "&amp;")
+        .replace('[', "&#91;")
+        .replace(']', "&#93;");
+    if in_param {
+        out.replace(',', "&#44;")
+    } else {
+        out
+    }
+}
+
+/// 解析 CQ 码字符串为消息段数组。
+fn cq_string_to_segments(message: &str) -> Vec<Value> {
+    let mut segments: Vec<Value> = Vec::new();
+    let mut rest = message;
+    while let Some(start) = rest.find("[CQ:") {
+        let Some(end_rel) = rest[start..].find(']') else {
+            break;
+        };
+        if start > 0 {
+            push_text_segment(&mut segments, &cq_unescape(&rest[..start]));
+        }
+        let body = &rest[start + 4..start + end_rel];
+        rest = &rest[start + end_rel + 1..];
+
+        let mut parts = body.split(',');
+        let ty = parts.next().unwrap_or("").trim().to_string();
+        let mut data = serde_json::Map::new();
+        for kv in parts {
+            if let Some((k, v)) = kv.split_once('=') {
+                data.insert(k.trim().to_string(), Value::String(cq_unescape(v)));
+            }
+        }
+        segments.push(json!({ "type": ty, "data": Value::Object(data) }));
+    }
+    if !rest.is_empty() {
+        push_text_segment(&mut segments, &cq_unescape(rest));
+    }
+    segments
+}
+
+/// 消息段转回 CQ 码字符串（用于 raw_message）。
+fn segments_to_cq_string(segments: &[Value]) -> String {
+    let mut out = String::new();
+    for seg in segments {
+        let ty = seg.get("type").and_then(|t| t.as_str()).unwrap_or("");
+        let data = seg.get("data").and_then(|d| d.as_object());
+        if ty == "text" {
+            let text = data
+                .and_then(|d| d.get("text"))
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            out.push_str(&cq_escape(text, false));
+            continue;
+        }
+        out.push_str("[CQ:");
+        out.push_str(ty);
+        if let Some(data) = data {
+            for (k, v) in data {
+                let v = match v {
+                    Value::String(s) => s.clone(),
+                    Value::Null => continue,
+                    other => other.to_string(),
+                };
+                out.push(',');
+                out.push_str(k);
+                out.push('=');
+                out.push_str(&cq_escape(&v, true));
+            }
+        }
+        out.push(']');
+    }
+    out
+}
+
+fn guess_base64_mime(b64: &str, fallback: &str) -> String {
+    let head = b64.trim_start();
+    let mime = if head.starts_with("iVBOR") {
+        "image/png"
+    } else if head.starts_with("/9j/") {
+        "image/jpeg"
+    } else if head.starts_with("R0lG") {
+        "image/gif"
+    } else if head.starts_with("UklG") {
+        "image/webp"
+    } else if head.starts_with("T2dnUw") {
+        "audio/ogg"
+    } else if head.starts_with("SUQz") {
+        "audio/mpeg"
+    } else if head.starts_with("IyFBTVI") {
+        "audio/amr"
+    } else if head.starts_with("AiMhU0lMS") || head.starts_with("IyFTSUxL") {
+        "audio/silk"
+    } else {
+        fallback
+    };
+    mime.to_string()
+}
+
+fn segment_media_src(data: &Value, fallback_mime: &str) -> Option<String> {
+    let file = data.get("file").and_then(|v| v.as_str()).unwrap_or("");
+    if let Some(b64) = file
+        .strip_prefix("base64://")
+        .or_else(|| file.strip_prefix("base64:"))
+    {
+        let b64 = b64.trim();
+        let mime = guess_base64_mime(b64, fallback_mime);
+        return Some(format!("data:{mime};base64,{b64}"));
+    }
+    if file.starts_with("http://") || file.starts_with("https://") || file.starts_with("data:") {
+        return Some(file.to_string());
+    }
+    // file:// 已由 inline_local_files 在发送前换成 base64，剩下的是不允许读取的路径
+    data.get("url")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| s.to_string())
+}
+
+/// 允许作为 `file://` 发送的目录：数据目录与系统临时目录
+async fn local_media_roots() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    for dir in [PathBuf::from("data"), std::env::temp_dir()] {
+        if let Ok(dir) = tokio::fs::canonicalize(&dir).await {
+            roots.push(dir);
+        }
+    }
+    roots
+}
+
+async fn read_local_media(path: &str, roots: &[PathBuf]) -> Result<Vec<u8>, String> {
+    let path = tokio::fs::canonicalize(Path::new(path))
+        .await
+        .map_err(|e| format!("{path}: {e}"))?;
+    if !roots.iter().any(|root| path.starts_with(root)) {
+        return Err(format!("{} 不在允许的目录内", path.display()));
+    }
+    tokio::fs::read(&path)
+        .await
+        .map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// 把消息（含转发节点）里的 `file://` 媒体读成 `base64://`，Satori 网关通常无法访问本机文件。
+/// 只允许读取数据目录与临时目录，读取失败的段保持原样，转换时会被跳过。
+async fn inline_local_files(message: &mut Value) {
+    let roots = local_media_roots().await;
+    let mut stack: Vec<&mut Value> = vec![message];
+    while let Some(v) = stack.pop() {
+        // CQ 字符串形式的消息（顶层或转发节点的 content）先拆成消息段
+        if let Value::String(s) = v {
+            if s.contains("file://") {
+                *v = Value::Array(cq_string_to_segments(s));
+            }
+        }
+        match v {
+            Value::Array(items) => stack.extend(items.iter_mut()),
+            Value::Object(obj) => {
+                let local = obj
+                    .get("file")
+                    .and_then(|f| f.as_str())
+                    .and_then(|f| f.strip_prefix("file://"))
+                    .map(|p| p.to_string());
+                if let Some(path) = local {
+                    match read_local_media(&path, &roots).await {
+                        Ok(bytes) => {
+                            let b64 = base64::engine::general_purpose::STANDARD.encode(bytes);
+                            obj.insert("file".to_string(), json!(format!("base64://{b64}")));
+                        }
+                        Err(e) => warn!("Satori 无法发送本地文件 {}", e),
+                    }
+                }
+                stack.extend(
+                    obj.iter_mut()
+                        .filter(|(key, child)| *key == "content" || !child.is_string())
+                        .map(|(_, child)| child),
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 将 OneBot 消息（CQ 字符串或消息段数组）转换为 Satori 消息元素。
+pub(super) fn onebot_message_to_satori_content(conn: &SatoriConnection, message: &Value) -> String {
+    let segments = match message {
+        Value::String(s) => cq_string_to_segments(s),
+        Value::Array(arr) => arr.clone(),
+        _ => return String::new(),
+    };
+
+    let mut out = String::new();
+    for seg in &segments {
+        let ty = seg.get("type").and_then(|t| t.as_str()).unwrap_or("");
+        let data = seg.get("data").cloned().unwrap_or(Value::Null);
+        match ty {
+            "text" => {
+                let text = data.get("text").and_then(|v| v.as_str()).unwrap_or("");
+                out.push_str(&escape_element_text(text));
+            }
+            "at" => {
+                let qq = data.get("qq");
+                if qq.and_then(|v| v.as_str()) == Some("all") {
+                    out.push_str("<at type=\"all\"/>");
+                } else if let Some(id) = satori_id_from_value(conn, qq) {
+                    out.push_str(&format!("<at id=\"{}\"/>", escape_element_text(&id)));
+                }
+            }
+            "reply" => {
+                if let Some(id) =
+                    satori_id_from_value(conn, data.get("id").or_else(|| data.get("message_id")))
+                {
+                    out.push_str(&format!("<quote id=\"{}\"/>", escape_element_text(&id)));
+                }
+            }
+            "image" | "record" | "video" | "file" => {
+                let (tag, mime) = match ty {
+                    "image" => ("img", "image/png"),
+                    "record" => ("audio", "audio/amr"),
+                    "video" => ("video", "video/mp4"),
+                    _ => ("file", "application/octet-stream"),
+                };
+                if let Some(src) = segment_media_src(&data, mime) {
+                    out.push_str(&format!("<{tag} src=\"{}\"/>", escape_element_text(&src)));
+                }
+            }
+            "face" => {
+                if let Some(id) = take_id(data.get("id")) {
+                    out.push_str(&format!("<face id=\"{}\"/>", escape_element_text(&id)));
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+fn forward_nodes_to_satori_content(conn: &SatoriConnection, nodes: &[Value]) -> String {
+    let mut out = String::from("<message forward>");
+    for node in nodes {
+        let data = node.get("data").unwrap_or(&Value::Null);
+        let name = data
+            .get("name")
+            .or_else(|| data.get("nickname"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+        let uin = satori_id_from_value(conn, data.get("uin").or_else(|| data.get("user_id")));
+        let content = data.get("content").cloned().unwrap_or(Value::Null);
+
+        out.push_str("<message>");
+        out.push_str("<author");
+        if let Some(uin) = uin {
+            out.push_str(&format!(" id=\"{}\"", escape_element_text(&uin)));
+        }
+        if !name.is_empty() {
+            out.push_str(&format!(" name=\"{}\"", escape_element_text(name)));
+        }
+        out.push_str("/>");
+        out.push_str(&onebot_message_to_satori_content(conn, &content));
+        out.push_str("</message>");
+    }
+    out.push_str("</message>");
+    out
+}
+
+// ===== Events =====
+
+fn build_onebot_like_event(conn: &SatoriConnection, bot_id: &str, body: &Value) -> Option<Value> {
+    let channel = body.get("channel")?;
+    let channel_id = take_id(channel.get("id"))?;
+    let guild_id = take_id(body.get("guild").and_then(|g| g.get("id")));
+    let user = body.get("user")?;
+    let user_id = take_id(user.get("id"))?;
+    let message = body.get("message")?;
+    let message_id = take_id(message.get("id"));
+    let content = message
+        .get("content")
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+
+    let is_direct = channel.get("type").and_then(|v| v.as_i64()) == Some(1)
+        || channel_id.starts_with("private:")
+        || guild_id.is_none();
+    let message_type = if is_direct { "private" } else { "group" };
+
+    if let Some(gid) = guild_id.as_deref() {
+        remember_channel_guild(conn, &channel_id, gid);
+    }
+
+    let mut segments = satori_content_to_segments(conn, content);
+    let has_reply = segments
+        .iter()
+        .any(|s| s.get("type").and_then(|t| t.as_str()) == Some("reply"));
+    if !has_reply {
+        if let Some(quote_id) = take_id(message.get("quote").and_then(|q| q.get("id"))) {
+            let id = satori_id_to_u64(conn, &quote_id);
+            remember_message_channel(conn, id, &channel_id);
+            segments.insert(
+                0,
+                json!({ "type": "reply", "data": { "id": id.to_string() } }),
+            );
+        }
+    }
+
+    let message_id_u64 = message_id.as_deref().map(|id| satori_id_to_u64(conn, id));
+    if let Some(mid) = message_id_u64 {
+        remember_message_channel(conn, mid, &channel_id);
+    }
+
+    let user_id_u64 = satori_id_to_u64(conn, &user_id);
+    let group_id_u64 = (!is_direct).then(|| satori_id_to_u64(conn, &channel_id));
+    let nickname = user
+        .get("name")
+        .or_else(|| user.get("nick"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+    let card = body
+        .get("member")
+        .and_then(|m| m.get("nick").or_else(|| m.get("name")))
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+
+    Some(json!({
+        "post_type": "message",
+        "message_type": message_type,
+        // Use string IDs to avoid JS precision loss in plugins.
+        "user_id": user_id_u64.to_string(),
+        "group_id": group_id_u64.map(|g| g.to_string()),
+        "message_id": message_id_u64.map(|m| m.to_string()),
+        "raw_message": segments_to_cq_string(&segments),
+        "message": segments,
+        "sender": {
+            "user_id": user_id_u64.to_string(),
+            "nickname": nickname,
+            "card": card,
+        },
+        "platform": "Satori",
+        "bot_id": bot_id,
+        "satori": {
+            "platform": body.get("platform").cloned().unwrap_or(Value::Null),
+            "channel_id": channel_id,
+            "guild_id": guild_id,
+            "user_id": user_id,
+            "message_id": message_id,
+        }
+    }))
+}
+
+fn build_onebot_like_message_data(conn: &SatoriConnection, msg: &Value) -> Value {
+    let content = msg.get("content").and_then(|v| v.as_str()).unwrap_or("");
+    let segments = satori_content_to_segments(conn, content);
+    let user = msg.get("user").unwrap_or(&Value::Null);
+    let user_id = take_id(user.get("id"))
+        .map(|id| satori_id_to_u64(conn, &id).to_string())
+        .unwrap_or_default();
+    let nickname = msg
+        .get("member")
+        .and_then(|m| m.get("nick"))
+        .or_else(|| user.get("name"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("unknown");
+    let message_id = take_id(msg.get("id"))
+        .map(|id| satori_id_to_u64(conn, &id).to_string())
+        .unwrap_or_default();
+
+    json!({
+        "message_id": message_id,
+        "raw_message": segments_to_cq_string(&segments),
+        "message": segments,
+        "time": msg.get("created_at").and_then(|v| v.as_i64()).map(|ms| ms / 1000),
+        "sender": {
+            "user_id": user_id,
+            "nickname": nickname,
+        }
+    })
+}
+
+// ===== HTTP API =====
+
+async fn satori_post(conn: &SatoriConnection, method: &str, body: &Value) -> Result<Value, String> {
+    let url = format!("{}/v1/{}", conn.endpoint, method);
+    let login = conn.login.read().map(|l| l.clone()).unwrap_or_default();
+
+    let mut req = conn
+        .http
+        .post(&url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Platform", login.platform.as_str())
+        .header("X-Self-ID", login.self_id.as_str())
+        .header("Satori-Platform", login.platform.as_str())
+        .header("Satori-User-ID", login.self_id.as_str());
+    if !conn.token.is_empty() {
+        req = req.header(
+            reqwest::header::AUTHORIZATION,
+            format!("Bearer {}", conn.token),
+        );
+    }
+
+    let resp = req
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| format!("HTTP request failed: {e}"))?;
+    let status = resp.status();
+    let text = resp
+        .text()
+        .await
+        .map_err(|e| format!("read response failed: {e}"))?;
+    if !status.is_success() {
+        return Err(format!("HTTP {status}: {text}"));
+    }
+    if text.trim().is_empty() {
+        return Ok(Value::Null);
+    }
+    serde_json::from_str(&text).map_err(|e| format!("parse response failed: {e}"))
+}
+
+async fn satori_create_message(
+    conn: &SatoriConnection,
+    channel_id: &str,
+    content: &str,
+) -> Result<Vec<String>, String> {
+    if content.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let resp = satori_post(
+        conn,
+        "message.create",
+        &json!({ "channel_id": channel_id, "content": content }),
+    )
+    .await?;
+    Ok(resp
+        .as_array()
+        .map(|arr| arr.iter().filter_map(|m| take_id(m.get("id"))).collect())
+        .unwrap_or_default())
+}
+
+async fn satori_direct_channel(conn: &SatoriConnection, user_id: &str) -> Result<String, String> {
+    let resp = satori_post(conn, "user.channel.create", &json!({ "user_id": user_id })).await?;
+    take_id(resp.get("id")).ok_or_else(|| "missing direct channel id".to_string())
+}
+
+async fn satori_resolve_target(
+    conn: &SatoriConnection,
+    action: &str,
+    params: &Value,
+) -> Result<String, String> {
+    let is_group = match action {
+        "send_group_msg" | "send_group_forward_msg" => true,
+        "send_private_msg" | "send_private_forward_msg" => false,
+        _ => {
+            params.get("message_type").and_then(|v| v.as_str()) == Some("group")
+                || (params.get("message_type").is_none()
+                    && params.get("group_id").is_some_and(|v| !v.is_null()))
+        }
+    };
+    if is_group {
+        satori_id_from_value(conn, params.get("group_id"))
+            .ok_or_else(|| "missing group_id".to_string())
+    } else {
+        let user_id = satori_id_from_value(conn, params.get("user_id"))
+            .ok_or_else(|| "missing user_id".to_string())?;
+        satori_direct_channel(conn, &user_id).await
+    }
+}
+
+/// 发送类 OneBot action → Satori message.create，返回新消息 ID。
+pub(super) async fn satori_send_api(
+    conn: &SatoriConnection,
+    action: &str,
+    params: &Value,
+) -> Result<Vec<u64>, String> {
+    let content = match action {
+        "send_group_msg" | "send_private_msg" | "send_msg" => {
+            let mut message = params.get("message").cloned().unwrap_or(Value::Null);
+            inline_local_files(&mut message).await;
+            onebot_message_to_satori_content(conn, &message)
+        }
+        "send_group_forward_msg" | "send_private_forward_msg" | "send_forward_msg" => {
+            let mut nodes = params
+                .get("messages")
+                .filter(|v| v.is_array())
+                .cloned()
+                .ok_or_else(|| "missing messages".to_string())?;
+            inline_local_files(&mut nodes).await;
+            forward_nodes_to_satori_content(conn, nodes.as_array().map_or(&[], |n| n))
+        }
+        _ => return Err(format!("unsupported action: {}", action)),
+    };
+
+    let channel_id = satori_resolve_target(conn, action, params).await?;
+    let ids = satori_create_message(conn, &channel_id, &content).await?;
+    Ok(ids
+        .iter()
+        .map(|id| {
+            let n = satori_id_to_u64(conn, id);
+            remember_message_channel(conn, n, &channel_id);
+            n
+        })
+        .collect())
+}
+
+fn ok_response(data: Value) -> Value {
+    json!({ "status": "ok", "retcode": 0, "data": data })
+}
+
+fn member_to_onebot(conn: &SatoriConnection, group_id: u64, m: &Value) -> Value {
+    let user = m.get("user").unwrap_or(&Value::Null);
+    let user_id = take_id(user.get("id"))
+        .map(|id| satori_id_to_u64(conn, &id))
+        .unwrap_or(0);
+    let nickname = user.get("name").and_then(|v| v.as_str()).unwrap_or("");
+    let card = m.get("nick").and_then(|v| v.as_str()).unwrap_or("");
+    json!({
+        "group_id": group_id,
+        "user_id": user_id,
+        "nickname": nickname,
+        "card": card,
+        "role": "member",
+        "join_time": m.get("joined_at").and_then(|v| v.as_i64()).map(|ms| ms / 1000),
+    })
+}
+
+/// 查询类 OneBot action → Satori HTTP API，返回 OneBot 风格的响应体。
+pub(super) async fn satori_call_api(
+    bot_id: &str,
+    conn: &SatoriConnection,
+    action: &str,
+    params: Value,
+) -> Option<Value> {
+    let result: Result<Value, String> = match action {
+        "get_login_info" => {
+            let login = conn.login.read().ok()?.clone();
+            let resp = satori_post(conn, "login.get", &json!({})).await.ok();
+            let user = resp.as_ref().and_then(|r| r.get("user"));
+            let nickname = user
+                .and_then(|u| u.get("name"))
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            Ok(json!({
+                "user_id": satori_id_to_u64(conn, &login.self_id),
+                "nickname": nickname,
+            }))
+        }
+        "get_msg" => {
+            let mid = params
+                .get("message_id")
+                .and_then(|v| v.as_u64().or_else(|| v.as_str()?.parse::<u64>().ok()))?;
+            let channel_id = conn.ids.lock().ok()?.msg_channel.get(&mid).cloned()?;
+            let message_id = satori_id_from_u64(conn, mid);
+            satori_post(
+                conn,
+                "message.get",
+                &json!({ "channel_id": channel_id, "message_id": message_id }),
+            )
+            .await
+            .map(|m| build_onebot_like_message_data(conn, &m))
+        }
+        "get_group_member_list" => {
+            let group_id = params
+                .get("group_id")
+                .and_then(|v| v.as_u64().or_else(|| v.as_str()?.parse::<u64>().ok()))?;
+            let channel_id = satori_id_from_u64(conn, group_id);
+            let guild_id = guild_for_channel(conn, &channel_id);
+            let mut members: Vec<Value> = Vec::new();
+            let mut next: Option<String> = None;
+            let mut err: Option<String> = None;
+            for _ in 0..SATORI_MEMBER_LIST_MAX_PAGES {
+                let mut body = json!({ "guild_id": guild_id });
+                if let Some(n) = next.as_deref() {
+                    body["next"] = json!(n);
+                }
+                match satori_post(conn, "guild.member.list", &body).await {
+                    Ok(page) => {
+                        if let Some(data) = page.get("data").and_then(|v| v.as_array()) {
+                            members
+                                .extend(data.iter().map(|m| member_to_onebot(conn, group_id, m)));
+                        }
+                        next = take_id(page.get("next"));
+                        if next.is_none() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        err = Some(e);
+                        break;
+                    }
+                }
+            }
+            match err {
+                Some(e) if members.is_empty() => Err(e),
+                _ => Ok(Value::Array(members)),
+            }
+        }
+        "get_group_member_info" => {
+            let group_id = params
+                .get("group_id")
+                .and_then(|v| v.as_u64().or_else(|| v.as_str()?.parse::<u64>().ok()))?;
+            let user_id = satori_id_from_value(conn, params.get("user_id"))?;
+            let guild_id = guild_for_channel(conn, &satori_id_from_u64(conn, group_id));
+            satori_post(
+                conn,
+                "guild.member.get",
+                &json!({ "guild_id": guild_id, "user_id": user_id }),
+            )
+            .await
+            .map(|m| member_to_onebot(conn, group_id, &m))
+        }
+        "get_stranger_info" => {
+            let user_id = satori_id_from_value(conn, params.get("user_id"))?;
+            satori_post(conn, "user.get", &json!({ "user_id": user_id }))
+                .await
+                .map(|u| {
+                    json!({
+                        "user_id": satori_id_to_u64(conn, &user_id),
+                        "nickname": u.get("name").and_then(|v| v.as_str()).unwrap_or(""),
+                    })
+                })
+        }
+        "get_group_list" => satori_post(conn, "guild.list", &json!({}))
+            .await
+            .map(|page| {
+                let list = page
+                    .get("data")
+                    .and_then(|v| v.as_array())
+                    .cloned()
+                    .unwrap_or_default();
+                Value::Array(
+                    list.iter()
+                        .filter_map(|g| {
+                            let id = take_id(g.get("id"))?;
+                            Some(json!({
+                                "group_id": satori_id_to_u64(conn, &id),
+                                "group_name": g.get("name").and_then(|v| v.as_str()).unwrap_or(""),
+                            }))
+                        })
+                        .collect(),
+                )
+            }),
+        "get_friend_list" => satori_post(conn, "friend.list", &json!({}))
+            .await
+            .map(|page| {
+                let list = page
+                    .get("data")
+                    .and_then(|v| v.as_array())
+                    .cloned()
+                    .unwrap_or_default();
+                Value::Array(
+                    list.iter()
+                        .filter_map(|u| {
+                            let id = take_id(u.get("id"))?;
+                            Some(json!({
+                                "user_id": satori_id_to_u64(conn, &id),
+                                "nickname": u.get("name").and_then(|v| v.as_str()).unwrap_or(""),
+                            }))
+                        })
+                        .collect(),
+                )
+            }),
+        "send_group_msg"
+        | "send_private_msg"
+        | "send_msg"
+        | "send_group_forward_msg"
+        | "send_private_forward_msg"
+        | "send_forward_msg" => satori_send_api(conn, action, &params)
+            .await
+            .map(|ids| json!({ "message_id": ids.first().copied() })),
+        "delete_msg" => {
+            let mid = params
+                .get("message_id")
+                .and_then(|v| v.as_u64().or_else(|| v.as_str()?.parse::<u64>().ok()))?;
+            let channel_id = conn.ids.lock().ok()?.msg_channel.get(&mid).cloned()?;
+            satori_post(
+                conn,
+                "message.delete",
+                &json!({ "channel_id": channel_id, "message_id": satori_id_from_u64(conn, mid) }),
+            )
+            .await
+        }
+        _ => return None,
+    };
+
+    match result {
+        Ok(data) => Some(ok_response(data)),
+        Err(e) => {
+            warn!("[{}] Satori API {} 失败: {}", bot_id, action, e);
+            Some(json!({ "status": "failed", "retcode": -1, "message": e, "data": Value::Null }))
+        }
+    }
+}
+
+// ===== Connection =====
+
+enum SatoriExit {
+    Shutdown,
+    Reconnect,
+}
+
+async fn satori_connect_and_run(
+    state: SharedState,
+    runtime: Arc<BotRuntime>,
+    bot_id: String,
+    conn: SatoriConnection,
+    preferred_platform: String,
+    sequence: Arc<std::sync::atomic::AtomicU64>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> Result<SatoriExit, String> {
+    use std::sync::atomic::Ordering;
+
+    let url = events_url(&conn.endpoint);
+    let (ws_stream, _resp) = connect_async(url.as_str())
+        .await
+        .map_err(|e| format!("connect events failed: {e}"))?;
+    let (mut write, mut read) = ws_stream.split();
+
+    let mut identify_body = json!({});
+    if !conn.token.is_empty() {
+        identify_body["token"] = json!(conn.token.as_str());
+    }
+    let seq = sequence.load(Ordering::Relaxed);
+    if seq > 0 {
+        identify_body["sequence"] = json!(seq);
+        identify_body["sn"] = json!(seq);
+    }
+    let identify = json!({ "op": OP_IDENTIFY, "body": identify_body });
+    write
+        .send(Message::Text(identify.to_string()))
+        .await
+        .map_err(|e| format!("send identify failed: {e}"))?;
+
+    let mut ping = tokio::time::interval(SATORI_PING_INTERVAL);
+    ping.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    ping.tick().await;
+
+    loop {
+        tokio::select! {
+            _ = shutdown_rx.changed() => {
+                if *shutdown_rx.borrow() {
+                    break;
+                }
+            }
+            _ = ping.tick() => {
+                let payload = json!({ "op": OP_PING });
+                if write.send(Message::Text(payload.to_string())).await.is_err() {
+                    return Err("send ping failed".to_string());
+                }
+            }
+            msg = read.next() => {
+                let Some(msg) = msg else {
+                    return Err("events closed".to_string());
+                };
+                let msg = msg.map_err(|e| format!("events read error: {e}"))?;
+                let text = match msg {
+                    Message::Text(t) => t,
+                    Message::Binary(b) => String::from_utf8(b).map_err(|e| format!("events binary utf8 error: {e}"))?,
+                    Message::Close(_) => return Err("events closed".to_string()),
+                    _ => continue,
+                };
+                let payload: Value = serde_json::from_str(&text).map_err(|e| format!("events json error: {e}"))?;
+                let op = payload.get("op").and_then(|v| v.as_i64()).unwrap_or(-1);
+                let body = payload.get("body").cloned().unwrap_or(Value::Null);
+
+                match op {
+                    OP_READY => {
+                        let logins = body.get("logins").and_then(|v| v.as_array()).cloned().unwrap_or_default();
+                        let login = logins
+                            .iter()
+                            .find(|l| {
+                                preferred_platform.is_empty()
+                                    || l.get("platform").and_then(|v| v.as_str()) == Some(preferred_platform.as_str())
+                            })
+                            .or_else(|| logins.first());
+                        let Some(login) = login else {
+                            return Err("READY without logins".to_string());
+                        };
+                        let platform = login.get("platform").and_then(|v| v.as_str()).unwrap_or("").to_string();
+                        let self_id = take_id(login.get("self_id"))
+                            .or_else(|| take_id(login.get("user").and_then(|u| u.get("id"))))
+                            .unwrap_or_default();
+                        if let Ok(mut l) = conn.login.write() {
+                            *l = SatoriLogin { platform: platform.clone(), self_id: self_id.clone() };
+                        }
+                        if !self_id.is_empty() {
+                            runtime.set_self_id(&bot_id, satori_id_to_u64(&conn, &self_id)).await;
+                        }
+
+                        if let Some(mut bot) = state.bots.get_mut(&bot_id) {
+                            bot.is_connected = true;
+                            bot.metadata["satori"]["self_id"] = json!(self_id);
+                            bot.metadata["satori"]["login_platform"] = json!(platform);
+                        }
+                        save_bots(&state.bots);
+                        info!("[{}] Satori 已连接 (platform={}, self_id={})", bot_id, platform, self_id);
+                    }
+                    OP_EVENT => {
+                        if let Some(sn) = body.get("sn").or_else(|| body.get("id")).and_then(|v| v.as_u64()) {
+                            sequence.store(sn, Ordering::Relaxed);
+                        }
+                        let ty = body.get("type").and_then(|v| v.as_str()).unwrap_or("");
+                        if ty != "message-created" {
+                            continue;
+                        }
+
+                        let self_id = conn.login.read().map(|l| l.self_id.clone()).unwrap_or_default();
+                        let author_id = take_id(body.get("user").and_then(|u| u.get("id"))).unwrap_or_default();
+                        let author_bot = body
+                            .get("user")
+                            .and_then(|u| u.get("is_bot"))
+                            .and_then(|v| v.as_bool())
+                            .unwrap_or(false);
+                        if author_bot || (!self_id.is_empty() && author_id == self_id) {
+                            continue;
+                        }
+
+                        if let Some(event) = build_onebot_like_event(&conn, &bot_id, &body) {
+                            let state_cl = state.clone();
+                            let runtime_cl = runtime.clone();
+                            let bot_id_cl = bot_id.clone();
+                            tokio::spawn(async move {
+                                handle_event(&state_cl, &runtime_cl, &bot_id_cl, event).await;
+                            });
+                        }
+                    }
+                    OP_PONG => {}
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    let _ = write.send(Message::Close(None)).await;
+
+    if *shutdown_rx.borrow() {
+        Ok(SatoriExit::Shutdown)
+    } else {
+        Ok(SatoriExit::Reconnect)
+    }
+}
+
+async fn start_satori_bot(
+    state: SharedState,
+    runtime: Arc<BotRuntime>,
+    bot_id: String,
+    cfg: SatoriBotConfig,
+) -> Result<(), String> {
+    let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
+
+    let http = HttpClient::builder()
+        .user_agent("nBot (satori backend)")
+        .timeout(std::time::Duration::from_secs(30))
+        .build()
+        .map_err(|e| format!("build http client failed: {e}"))?;
+
+    let conn = SatoriConnection {
+        endpoint: Arc::new(cfg.endpoint.clone()),
+        token: Arc::new(cfg.token.clone()),
+        login: Arc::new(std::sync::RwLock::new(SatoriLogin {
+            platform: cfg.platform.clone(),
+            self_id: String::new(),
+        })),
+        ids: Arc::new(std::sync::Mutex::new(SatoriIdState::default())),
+        http,
+        shutdown: shutdown_tx,
+    };
+    runtime
+        .register_satori_connection(&bot_id, conn.clone())
+        .await;
+
+    if let Some(mut bot) = state.bots.get_mut(&bot_id) {
+        bot.is_running = true;
+    }
+    save_bots(&state.bots);
+
+    tokio::spawn(async move {
+        let mut backoff = Duration::from_secs(1);
+        let sequence = Arc::new(std::sync::atomic::AtomicU64::new(0));
+        loop {
+            if *shutdown_rx.borrow() {
+                break;
+            }
+
+            match satori_connect_and_run(
+                state.clone(),
+                runtime.clone(),
+                bot_id.clone(),
+                conn.clone(),
+                cfg.platform.clone(),
+                sequence.clone(),
+                shutdown_rx.clone(),
+            )
+            .await
+            {
+                Ok(SatoriExit::Shutdown) => break,
+                Ok(SatoriExit::Reconnect) => {
+                    backoff = Duration::from_secs(1);
+                }
+                Err(e) => {
+                    warn!("[{}] Satori 连接错误: {}", bot_id, e);
+                }
+            }
+
+            if *shutdown_rx.borrow() {
+                break;
+            }
+
+            if let Some(mut bot) = state.bots.get_mut(&bot_id) {
+                if bot.is_connected {
+                    bot.is_connected = false;
+                    save_bots(&state.bots);
+                }
+            }
+
+            tokio::select! {
+                _ = sleep(backoff) => {},
+                _ = shutdown_rx.changed() => {},
+            }
+            backoff = (backoff * 2).min(Duration::from_secs(30));
+        }
+
+        runtime.unregister_connection(&bot_id).await;
+        if let Some(mut bot) = state.bots.get_mut(&bot_id) {
+            bot.is_connected = false;
+        }
+        save_bots(&state.bots);
+        info!("[{}] Satori 已停止", bot_id);
+    });
+
+    Ok(())
+}
+
+async fn stop_satori_bot(state: &SharedState, runtime: &Arc<BotRuntime>, bot_id: &str) {
+    let is_satori = matches!(
+        runtime.connections.read().await.get(bot_id),
+        Some(BotConnection::Satori(_))
+    );
+    if !is_satori {
+        return;
+    }
+
+    runtime.shutdown_satori_connection(bot_id).await;
+
+    if let Some(mut bot) = state.bots.get_mut(bot_id) {
+        bot.is_connected = false;
+        bot.is_running = false;
+    }
+    save_bots(&state.bots);
+}
+
+pub async fn start_satori_connections(state: SharedState, runtime: Arc<BotRuntime>) {
+    info!("启动 Satori 连接管理循环...");
+
+    loop {
+        let to_start: Vec<(String, SatoriBotConfig)> = state
+            .bots
+            .iter()
+            .filter(|b| b.platform.eq_ignore_ascii_case("satori"))
+            .filter(|b| b.is_running)
+            .filter_map(|b| {
+                let cfg = get_satori_config_from_bot(b.value())?;
+                Some((b.id.clone(), cfg))
+            })
+            .collect();
+
+        for (bot_id, cfg) in to_start {
+            let already = {
+                let conns = runtime.connections.read().await;
+                matches!(conns.get(&bot_id), Some(BotConnection::Satori(_)))
+            };
+            if already {
+                continue;
+            }
+
+            info!("[{}] 启动 Satori Bot: {}", bot_id, cfg.endpoint);
+            if let Err(e) =
+                start_satori_bot(state.clone(), runtime.clone(), bot_id.clone(), cfg).await
+            {
+                error!("[{}] 启动 Satori Bot 失败: {}", bot_id, e);
+                if let Some(mut bot) = state.bots.get_mut(&bot_id) {
+                    bot.is_connected = false;
+                    bot.is_running = false;
+                }
+                save_bots(&state.bots);
+            }
+        }
+
+        let to_stop: Vec<String> = {
+            let conns = runtime.connections.read().await;
+            state
+                .bots
+                .iter()
+                .filter(|b| b.platform.eq_ignore_ascii_case("satori"))
+                .filter(|b| !b.is_running)
+                .filter_map(|b| {
+                    matches!(conns.get(&b.id), Some(BotConnection::Satori(_)))
+                        .then_some(b.id.clone())
+                })
+                .collect()
+        };
+
+        for bot_id in to_stop {
+            info!("[{}] 停止 Satori Bot...", bot_id);
+            stop_satori_bot(&state, &runtime, &bot_id).await;
+        }
+
+        sleep(Duration::from_secs(2)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(name: &str, attrs: &[(&str, &str)], self_closing: bool) -> ElementToken {
+        ElementToken::Open {
+            name: name.to_string(),
+            attrs: attrs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            self_closing,
+        }
+    }
+
+    #[test]
+    fn tokenizes_elements_and_text() {
+        let tokens = tokenize_elements(r#"hi <at id="123"/> <B>x</b>"#);
+        assert_eq!(
+            tokens,
+            [
+                ElementToken::Text("hi ".to_string()),
+                open("at", &[("id", "123")], true),
+                ElementToken::Text(" ".to_string()),
+                open("b", &[], false),
+                ElementToken::Text("x".to_string()),
+                ElementToken::Close("b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_stray_angle_brackets_as_text() {
+        let text: String = tokenize_elements("a < b > c <")
+            .into_iter()
+            .map(|t| match t {
+                ElementToken::Text(s) => s,
+                other => panic!("unexpected token: {other:?}"),
+            })
+            .collect();
+        assert_eq!(text, "a < b > c <");
+    }
+
+    #[test]
+    fn parses_attrs() {
+        let attrs = parse_element_attrs(r#" src='a&amp;b' title="x &lt;y&gt;" width=10 flag"#);
+        assert_eq!(attrs["src"], "a&b");
+        assert_eq!(attrs["title"], "x <y>");
+        assert_eq!(attrs["width"], "10");
+        assert_eq!(attrs["flag"], "true");
+    }
+
+    #[test]
+    fn unescapes_entities() {
+        assert_eq!(
+            unescape_element_text("&lt;&#65;&#x42;&quot;&unknown; & &amp;"),
+            "<AB\"&unknown; & &"
+        );
+        let text = "a<b>&\"c";
+        assert_eq!(unescape_element_text(&escape_element_text(text)), text);
+    }
+
+    #[test]
+    fn round_trips_cq_strings() {
+        let message = "hi [CQ:at,qq=123] &#91;x&#93; [CQ:image,file=a&#44;b]";
+        let segments = cq_string_to_segments(message);
+        assert_eq!(segments[0]["data"]["text"], "hi ");
+        assert_eq!(segments[1]["type"], "at");
+        assert_eq!(segments[2]["data"]["text"], " [x] ");
+        assert_eq!(segments[3]["data"]["file"], "a,b");
+        assert_eq!(segments_to_cq_string(&segments), message);
+    }
+
+    #[test]
+    fn recent_map_evicts_oldest() {
+        let mut map = RecentMap::default();
+        for i in 0..SATORI_ID_MAP_MAX + 1 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.get(&0), None);
+        assert_eq!(map.get(&1), Some(&1));
+        assert_eq!(map.get(&SATORI_ID_MAP_MAX), Some(&SATORI_ID_MAP_MAX));
+    }
+}
@@ -0,0 +1,78 @@
+//! 运行时本地状态库（`data/` 下的 SQLite 文件）共用的连接与时间工具。
+//!
+//! 有单独写线程的库直接用 `open` 获取连接；其余库用 `SharedDb` 持有一条按需打开的共享连接。
+
+use rusqlite::{Connection, OpenFlags};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub(super) fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// 打开数据库（读写时先创建所在目录），`label` 用于错误信息
+pub(super) fn open(path: &str, read_only: bool, label: &str) -> Result<Connection, String> {
+    let conn = if read_only {
+        Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+    } else {
+        if let Some(dir) = Path::new(path).parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("创建目录失败: {e}"))?;
+        }
+        Connection::open(path)
+    }
+    .map_err(|e| format!("打开{label}失败: {e}"))?;
+    conn.busy_timeout(Duration::from_secs(5))
+        .map_err(|e| format!("设置 busy_timeout 失败: {e}"))?;
+    Ok(conn)
+}
+
+/// 首次使用时打开并用 `init` 建表的共享连接，通过 `with_db` 在阻塞线程里串行使用。
+pub(super) struct SharedDb {
+    path: &'static str,
+    label: &'static str,
+    init: fn(&Connection) -> Result<(), String>,
+    conn: Mutex<Option<Connection>>,
+}
+
+impl SharedDb {
+    pub(super) const fn new(
+        path: &'static str,
+        label: &'static str,
+        init: fn(&Connection) -> Result<(), String>,
+    ) -> Self {
+        Self {
+            path,
+            label,
+            init,
+            conn: Mutex::new(None),
+        }
+    }
+}
+
+/// 在阻塞线程里使用 `db` 的共享连接（首次使用时打开并初始化）。
+pub(super) async fn with_db<T, F>(db: &'static SharedDb, f: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut Connection) -> Result<T, rusqlite::Error> + Send + 'static,
+{
+    let label = db.label;
+    tokio::task::spawn_blocking(move || {
+        let mut guard = db.conn.lock().map_err(|_| format!("{label}锁已损坏"))?;
+        if guard.is_none() {
+            let conn = open(db.path, false, label)?;
+            (db.init)(&conn)?;
+            *guard = Some(conn);
+        }
+        let conn = guard.as_mut().ok_or_else(|| format!("{label}不可用"))?;
+        f(conn).map_err(|e| format!("{label}操作失败: {e}"))
+    })
+    .await
+    .map_err(|e| format!("{label}任务失败: {e}"))?
+}
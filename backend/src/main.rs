@@ -32,7 +32,8 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilte
 use crate::auth::{load_or_create_api_token, require_api_token, AuthState};
 use crate::bot::{
     docker_status_sync_loop, napcat_login_monitor, start_bot_connections,
-    start_discord_connections, BotRuntime,
+    start_chat_archive_maintenance, start_discord_connections, start_onebot_http_connections,
+    start_outbound_config_sync, start_satori_connections, BotRuntime,
 };
 use crate::command::CommandRegistry;
 use crate::models::{AppState, BotInstance, MessageStats, RuntimeState};
@@ -235,6 +236,33 @@ async fn main() {
         start_discord_connections(state_cl5, runtime_cl5).await;
     });
 
+    // Start Satori connection manager (external Satori-compatible gateways)
+    let state_cl6 = state.clone();
+    let runtime_cl6 = bot_runtime.clone();
+    tokio::spawn(async move {
+        start_satori_connections(state_cl6, runtime_cl6).await;
+    });
+
+    // Start OneBot HTTP connection manager (HTTP API + HTTP POST events)
+    let state_cl7 = state.clone();
+    let runtime_cl7 = bot_runtime.clone();
+    tokio::spawn(async move {
+        start_onebot_http_connections(state_cl7, runtime_cl7).await;
+    });
+
+    // Message archive retention (no-op unless the archive module is enabled)
+    let state_cl8 = state.clone();
+    tokio::spawn(async move {
+        start_chat_archive_maintenance(state_cl8).await;
+    });
+
+    // Outbound queue rate-limit config (read from the outbound module)
+    let state_cl9 = state.clone();
+    let runtime_cl9 = bot_runtime.clone();
+    tokio::spawn(async move {
+        start_outbound_config_sync(state_cl9, runtime_cl9).await;
+    });
+
     let allowed_origins = std::env::var("NBOT_ALLOWED_ORIGINS")
         .ok()
         .and_then(|v| {
@@ -279,6 +307,8 @@ async fn main() {
         .route("/bots/:id", delete(bot::delete_bot_handler))
         .route("/bots/:id", put(bot::update_bot_handler))
         .route("/bots/:id/discord", put(bot::update_discord_bot_handler))
+        .route("/bots/:id/satori", put(bot::update_satori_bot_handler))
+        .route("/bots/:id/onebot", put(bot::update_onebot_http_bot_handler))
         .route("/bots/:id/login", post(bot::login_trigger_handler))
         .route("/bots/:id/copy", post(bot::copy_bot_handler))
         .route(
@@ -359,6 +389,7 @@ async fn main() {
         .route("/llm/models", post(module::llm_models_handler))
         .route("/llm/chat", post(module::llm_chat_handler))
         .route("/llm/tavily/test", post(module::tavily_test_handler))
+        .route("/llm/usage", get(bot::get_llm_usage_handler))
         // Command routes
         .route("/commands", get(command::list_commands_handler))
         .route("/commands", post(command::create_command_handler))
@@ -380,6 +411,13 @@ async fn main() {
         // Chat routes
         .route("/chat/history", get(bot::get_chat_history_handler))
         .route("/chat/send", post(bot::send_chat_message_handler))
+        .route("/chat/search", get(bot::search_chat_archive_handler))
+        // Outbound queue routes
+        .route("/outbound", get(bot::get_outbound_status_handler))
+        .route(
+            "/outbound/dead-letters",
+            delete(bot::clear_dead_letters_handler),
+        )
         .layer(Extension(bot_runtime.clone()))
         .with_state(state.clone())
         .layer(middleware::from_fn_with_state(
@@ -387,8 +425,15 @@ async fn main() {
             require_api_token,
         ));
 
+    // OneBot HTTP POST 上报：不走 API token，由每个 bot 的 secret 做 HMAC 签名校验。
+    let onebot_events = Router::new()
+        .route("/onebot/:id/event", post(bot::onebot_http_event_handler))
+        .layer(Extension(bot_runtime.clone()))
+        .with_state(state.clone());
+
     let app = Router::new()
         .nest("/api", api)
+        .merge(onebot_events)
         .layer(cors)
         .fallback_service(tower_http::services::ServeDir::new("dist").precompressed_gzip())
         .layer(SetResponseHeaderLayer::overriding(
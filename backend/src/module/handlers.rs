@@ -56,6 +56,16 @@ pub async fn update_module_config_handler(
 
 // LLM specific handlers
 
+const GEMINI_DEFAULT_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+fn gemini_base(base_url: &str) -> &str {
+    if base_url.is_empty() {
+        GEMINI_DEFAULT_BASE_URL
+    } else {
+        base_url.trim_end_matches('/')
+    }
+}
+
 #[derive(serde::Deserialize)]
 pub struct LLMTestPayload {
     pub provider: String,
@@ -69,7 +79,7 @@ pub async fn llm_test_handler(Json(payload): Json<LLMTestPayload>) -> Json<serde
 
     // Use /models endpoint for health check instead of chat completion
     let req = match payload.provider.as_str() {
-        "claude" => {
+        "claude" | "anthropic" => {
             // Anthropic doesn't have a models endpoint, just verify the API key format
             if payload.api_key.is_empty() {
                 return Json(json!({ "status": "error", "message": "API Key 不能为空" }));
@@ -81,6 +91,9 @@ pub async fn llm_test_handler(Json(payload): Json<LLMTestPayload>) -> Json<serde
             }
             return Json(json!({ "status": "success", "message": "API Key 格式正确" }));
         }
+        "gemini" => client
+            .get(format!("{}/models", gemini_base(&payload.base_url)))
+            .header("x-goog-api-key", &payload.api_key),
         _ => {
             let base = if payload.base_url.is_empty() {
                 "https://api.openai.com/v1"
@@ -123,7 +136,7 @@ pub async fn llm_models_handler(Json(payload): Json<LLMModelsPayload>) -> Json<s
     let client = reqwest::Client::new();
 
     match payload.provider.as_str() {
-        "claude" => {
+        "claude" | "anthropic" => {
             // Anthropic doesn't have a models endpoint, return hardcoded list
             Json(json!({
                 "status": "success",
@@ -136,6 +149,48 @@ pub async fn llm_models_handler(Json(payload): Json<LLMModelsPayload>) -> Json<s
                 ]
             }))
         }
+        "gemini" => {
+            let url = format!("{}/models?pageSize=1000", gemini_base(&payload.base_url));
+            let resp = match client
+                .get(&url)
+                .header("x-goog-api-key", &payload.api_key)
+                .send()
+                .await
+            {
+                Ok(resp) => resp,
+                Err(e) => return Json(json!({ "status": "error", "message": e.to_string() })),
+            };
+            if !resp.status().is_success() {
+                let text = match resp.text().await {
+                    Ok(t) => t,
+                    Err(e) => format!("(无法读取响应正文: {e})"),
+                };
+                return Json(json!({ "status": "error", "message": text }));
+            }
+            let Ok(data) = resp.json::<serde_json::Value>().await else {
+                return Json(json!({ "status": "error", "message": "解析响应失败" }));
+            };
+            // 只列出支持 generateContent 的模型，去掉 "models/" 前缀
+            let mut models: Vec<String> = data
+                .get("models")
+                .and_then(|v| v.as_array())
+                .map(|arr| {
+                    arr.iter()
+                        .filter(|m| {
+                            m.get("supportedGenerationMethods")
+                                .and_then(|v| v.as_array())
+                                .is_some_and(|methods| {
+                                    methods.iter().any(|x| x.as_str() == Some("generateContent"))
+                                })
+                        })
+                        .filter_map(|m| m.get("name").and_then(|v| v.as_str()))
+                        .map(|name| name.trim_start_matches("models/").to_string())
+                        .collect()
+                })
+                .unwrap_or_default();
+            models.sort();
+            Json(json!({ "status": "success", "models": models }))
+        }
         _ => {
             let base = if payload.base_url.is_empty() {
                 "https://api.openai.com/v1"
@@ -207,7 +262,10 @@ pub async fn get_llm_config_handler(State(state): State<SharedState>) -> Json<se
                 "model_library": config.get("model_library").cloned().unwrap_or(json!([])),
                 "mappings": config.get("models").cloned().unwrap_or(json!({})),
                 "default_model": config.get("default_model").and_then(|v| v.as_str()).unwrap_or("default"),
-                "tavily_api_key": config.get("tavily_api_key").and_then(|v| v.as_str()).unwrap_or("")
+                "vision_model": config.get("vision_model").and_then(|v| v.as_str()).unwrap_or(""),
+                "images": config.get("images").cloned().unwrap_or(json!({})),
+                "tavily_api_key": config.get("tavily_api_key").and_then(|v| v.as_str()).unwrap_or(""),
+                "prices": config.get("prices").cloned().unwrap_or(json!({}))
             }))
         }
         None => Json(json!({
@@ -216,7 +274,10 @@ pub async fn get_llm_config_handler(State(state): State<SharedState>) -> Json<se
             "model_library": [],
             "mappings": {},
             "default_model": "default",
-            "tavily_api_key": ""
+            "vision_model": "",
+            "images": {},
+            "tavily_api_key": "",
+            "prices": {}
         })),
     }
 }
@@ -228,8 +289,17 @@ pub struct UpdateLLMConfigPayload {
     pub model_library: serde_json::Value,
     pub mappings: serde_json::Value,
     pub default_model: String,
+    /// 模型不支持识图时用于描述图片的模型映射名，不传则保留原值
+    #[serde(default)]
+    pub vision_model: Option<String>,
+    /// 图片生成设置（model_mapping / size），不传则保留原值
+    #[serde(default)]
+    pub images: Option<serde_json::Value>,
     #[serde(default)]
     pub tavily_api_key: String,
+    /// 模型单价（每百万 token），不传则保留原值
+    #[serde(default)]
+    pub prices: Option<serde_json::Value>,
 }
 
 /// Update LLM configuration
@@ -237,13 +307,27 @@ pub async fn update_llm_config_handler(
     State(state): State<SharedState>,
     Json(payload): Json<UpdateLLMConfigPayload>,
 ) -> Json<serde_json::Value> {
-    let new_config = json!({
-        "providers": payload.providers,
-        "model_library": payload.model_library,
-        "models": payload.mappings,
-        "default_model": payload.default_model,
-        "tavily_api_key": payload.tavily_api_key
-    });
+    // 保留页面上不编辑的字段（limits、circuit_breaker 等）
+    let mut new_config = state
+        .modules
+        .get("llm")
+        .map(|m| m.config)
+        .filter(|c| c.is_object())
+        .unwrap_or_else(|| json!({}));
+    new_config["providers"] = payload.providers;
+    new_config["model_library"] = payload.model_library;
+    new_config["models"] = payload.mappings;
+    new_config["default_model"] = json!(payload.default_model);
+    new_config["tavily_api_key"] = json!(payload.tavily_api_key);
+    if let Some(vision_model) = payload.vision_model {
+        new_config["vision_model"] = json!(vision_model.trim());
+    }
+    if let Some(images) = payload.images.filter(|v| v.is_object()) {
+        new_config["images"] = images;
+    }
+    if let Some(prices) = payload.prices {
+        new_config["prices"] = prices;
+    }
 
     match state.modules.update_config("llm", new_config) {
         Ok(_) => Json(json!({ "status": "success" })),
@@ -321,7 +405,7 @@ pub async fn llm_chat_handler(Json(payload): Json<LLMChatPayload>) -> Json<serde
     let client = reqwest::Client::new();
 
     match payload.provider.as_str() {
-        "claude" => {
+        "claude" | "anthropic" => {
             // Anthropic API
             let base = if payload.base_url.is_empty() {
                 "https://api.anthropic.com"
@@ -397,6 +481,84 @@ pub async fn llm_chat_handler(Json(payload): Json<LLMChatPayload>) -> Json<serde
                 Err(e) => Json(json!({ "status": "error", "message": e.to_string() })),
             }
         }
+        "gemini" => {
+            let url = format!(
+                "{}/models/{}:generateContent",
+                gemini_base(&payload.base_url),
+                payload.model.trim_start_matches("models/")
+            );
+
+            let mut system_prompt = String::new();
+            let mut contents: Vec<serde_json::Value> = Vec::new();
+            for msg in &payload.messages {
+                let role = msg.get("role").and_then(|r| r.as_str()).unwrap_or("user");
+                let content = msg.get("content").and_then(|c| c.as_str()).unwrap_or("");
+                match role {
+                    "system" => system_prompt = content.to_string(),
+                    "assistant" => {
+                        contents.push(json!({ "role": "model", "parts": [{ "text": content }] }))
+                    }
+                    _ => contents.push(json!({ "role": "user", "parts": [{ "text": content }] })),
+                }
+            }
+
+            let mut body = json!({
+                "contents": contents,
+                "generationConfig": { "maxOutputTokens": 4096 }
+            });
+            if !system_prompt.is_empty() {
+                body["systemInstruction"] = json!({ "parts": [{ "text": system_prompt }] });
+            }
+
+            match client
+                .post(&url)
+                .header("x-goog-api-key", &payload.api_key)
+                .json(&body)
+                .timeout(std::time::Duration::from_secs(120))
+                .send()
+                .await
+            {
+                Ok(resp) => {
+                    let status = resp.status();
+                    let text = match resp.text().await {
+                        Ok(t) => t,
+                        Err(e) => {
+                            return Json(
+                                json!({ "status": "error", "message": format!("读取响应失败: {e}") }),
+                            )
+                        }
+                    };
+                    let v = serde_json::from_str::<serde_json::Value>(&text).ok();
+                    if status.is_success() {
+                        let Some(v) = v else {
+                            return Json(json!({ "status": "error", "message": "解析响应失败" }));
+                        };
+                        let content: String = v
+                            .pointer("/candidates/0/content/parts")
+                            .and_then(|p| p.as_array())
+                            .map(|parts| {
+                                parts
+                                    .iter()
+                                    .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                                    .collect()
+                            })
+                            .unwrap_or_default();
+                        Json(json!({ "status": "success", "content": content }))
+                    } else {
+                        let msg = v
+                            .as_ref()
+                            .and_then(|v| v.pointer("/error/message"))
+                            .and_then(|m| m.as_str())
+                            .map(|s| s.to_string())
+                            .unwrap_or_else(|| text.chars().take(200).collect());
+                        Json(
+                            json!({ "status": "error", "message": format!("HTTP {}: {}", status, msg) }),
+                        )
+                    }
+                }
+                Err(e) => Json(json!({ "status": "error", "message": e.to_string() })),
+            }
+        }
         _ => {
             // OpenAI compatible API
             let base = if payload.base_url.is_empty() {
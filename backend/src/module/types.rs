@@ -78,6 +78,35 @@ impl ModuleRegistry {
                     "aliases": {}
                 }),
             },
+            BotModule {
+                id: "outbound".to_string(),
+                name: "发送队列".to_string(),
+                description: "出站消息限速、管理员优先、失败重试与长消息分段".to_string(),
+                icon: "send".to_string(),
+                enabled: false,
+                builtin: true,
+                config: serde_json::json!({
+                    "global_per_minute": 30,
+                    "group_per_minute": 12,
+                    "min_interval_ms": 1000,
+                    "max_retries": 2,
+                    "retry_delay_ms": 2000,
+                    "chunk_max_chars": 1500
+                }),
+            },
+            BotModule {
+                id: "archive".to_string(),
+                name: "消息存档".to_string(),
+                description: "在本地保存收发的消息，供 WebUI 与插件检索".to_string(),
+                icon: "archive".to_string(),
+                enabled: false,
+                builtin: true,
+                config: serde_json::json!({
+                    "retention_days": 30,
+                    "record_private": true,
+                    "record_outgoing": true
+                }),
+            },
         ];
 
         for module in defaults {
@@ -13,6 +13,118 @@ const toBigInt = (v) => {
   }
 };
 
+// options.tts for the callLlmForward* family: true or { voice } -> { voice } payload, otherwise null
+const forwardTts = (options) => {
+  const tts = options && options.tts;
+  if (!tts) return null;
+  return { voice: typeof tts === "object" && tts.voice ? String(tts.voice) : null };
+};
+
+// -1 lets the host pick the current event's group
+const kbGroupId = (options) =>
+  options.groupId === undefined || options.groupId === null ? -1n : toBigInt(options.groupId);
+
+// Promise layer over the requestId/callback APIs. Responses to requestIds issued here settle the waiting
+// promise instead of reaching onLlmResponse / onGroupInfoResponse / onApiResponse. Deadlines are checked
+// whenever the host calls into the plugin (tick/heartbeat meta events included), and by a host timer while
+// requests issued from onCommand are still pending.
+const LLM_TIMEOUT_MS = 300000;
+const REQUEST_TIMEOUT_MS = 60000;
+const pendingRequests = new Map();
+let asyncRequestSeq = 0;
+
+const request = (kind, options, send, settle, extra = {}) => {
+  const requestId = `nbot-${kind}-${Date.now().toString(36)}-${++asyncRequestSeq}`;
+  const timeoutMs =
+    Number(options && options.timeoutMs) > 0
+      ? Number(options.timeoutMs)
+      : kind === "llm"
+        ? LLM_TIMEOUT_MS
+        : REQUEST_TIMEOUT_MS;
+  const promise = new Promise((resolve, reject) => {
+    pendingRequests.set(requestId, {
+      ...extra,
+      kind,
+      settle,
+      resolve,
+      reject,
+      deadline: Date.now() + timeoutMs,
+    });
+  });
+  try {
+    send(requestId);
+  } catch (e) {
+    pendingRequests.delete(requestId);
+    return Promise.reject(e);
+  }
+  return promise;
+};
+
+const settleLlm = (r, resolve, reject) =>
+  r.success ? resolve(r.content) : reject(new Error(String(r.content)));
+const settleInfo = (r, resolve, reject) =>
+  r.success
+    ? resolve(r.data)
+    : reject(new Error(typeof r.data === "string" ? r.data : JSON.stringify(r.data)));
+const settleApi = (r, resolve, reject) =>
+  r.success ? resolve(r) : reject(Object.assign(new Error(r.message || r.status), { response: r }));
+
+globalThis.__nbotSweepRequests = () => {
+  const now = Date.now();
+  for (const [requestId, entry] of pendingRequests) {
+    if (now <= entry.deadline) continue;
+    pendingRequests.delete(requestId);
+    entry.reject(new Error(`${entry.kind} request timed out`));
+  }
+};
+
+globalThis.__nbotPendingRequestCount = () => pendingRequests.size;
+
+// Called by the host before the response hooks; true when the response belonged to a promise.
+globalThis.__nbotSettleRequest = (response) => {
+  globalThis.__nbotSweepRequests();
+  const requestId = String(response.requestId);
+  const entry = pendingRequests.get(requestId);
+  if (!entry) return false;
+  pendingRequests.delete(requestId);
+  entry.settle(response, entry.resolve, entry.reject);
+  return true;
+};
+
+globalThis.__nbotLlmChunk = (chunk) => {
+  const entry = pendingRequests.get(String(chunk.requestId));
+  if (!entry) return false;
+  if (typeof entry.onChunk === "function") entry.onChunk(chunk);
+  return true;
+};
+
+// Tool handlers passed to nbot.llm.chat; null falls back to onLlmToolCall.
+globalThis.__nbotLlmToolCall = (call) => {
+  const entry = pendingRequests.get(String(call.requestId));
+  const handler = entry && entry.toolHandlers ? entry.toolHandlers[call.name] : null;
+  if (typeof handler !== "function") return null;
+  return Promise.resolve().then(() => handler(call.arguments, call));
+};
+
+const infoPromise = (options, send) => request("info", options, send, settleInfo);
+
+// Callback mode when options.requestId is set (returns the requestId), otherwise a promise.
+const infoRequest = (options, send) => {
+  if (options.requestId) {
+    const requestId = String(options.requestId);
+    send(requestId);
+    return requestId;
+  }
+  return infoPromise(options, send);
+};
+
+const normalizeMessages = (messages) =>
+  Array.isArray(messages)
+    ? messages
+    : typeof messages === "string" && messages
+      ? [{ role: "user", content: messages }]
+      : [];
+
 globalThis.nbot = {
   // CQ helper: mention (at) a user
   at: (userId) => {
@@ -26,24 +138,50 @@ globalThis.nbot = {
   },
 
   // Send reply message
-  sendReply: (userId, groupId, content) => {
-    return core.ops.op_send_reply(toBigInt(userId), toBigInt(groupId || 0), content);
+  // options.requestId: when set, the send result is delivered via
+  // onApiResponse({ requestId, action, success, status, retcode, messageId, data, message })
+  // options.tts: speak the text and send it as a voice message instead (true or { voice });
+  // falls back to the text when synthesis fails (see llm module `tts` config)
+  sendReply: (userId, groupId, content, options = {}) => {
+    if (options && options.tts) {
+      return core.ops.op_send_voice_reply(
+        toBigInt(userId),
+        toBigInt(groupId || 0),
+        String(content || ""),
+        String((options.tts && options.tts.voice) || ""),
+        String(options.requestId || "")
+      );
+    }
+    return core.ops.op_send_reply(
+      toBigInt(userId),
+      toBigInt(groupId || 0),
+      content,
+      String((options && options.requestId) || "")
+    );
   },
 
-  // Call QQ API
-  callApi: (action, params = {}) => {
-    return core.ops.op_call_api(action, JSON.stringify(params));
+  // Call QQ API (options.requestId: same as sendReply)
+  callApi: (action, params = {}, options = {}) => {
+    return core.ops.op_call_api(
+      action,
+      JSON.stringify(params),
+      String((options && options.requestId) || "")
+    );
   },
 
   // Call LLM and send result as forward message
-  callLlmForward: (userId, groupId, systemPrompt, prompt, content, title) => {
+  // options.tts (all callLlmForward* functions): true or { voice } also reads the result out as a
+  // voice message after the forward message (see llm module `tts` config); synthesis failures are only logged
+  callLlmForward: (userId, groupId, systemPrompt, prompt, content, title, options = {}) => {
+    const tts = forwardTts(options);
     return core.ops.op_call_llm_forward(
       toBigInt(userId),
       toBigInt(groupId || 0),
       systemPrompt,
       prompt,
       content,
-      title
+      title,
+      tts ? JSON.stringify(tts) : ""
     );
   },
 
@@ -63,6 +201,7 @@ globalThis.nbot = {
   ) => {
     const payload = {
       model_name: options.modelName ? String(options.modelName) : null,
+      tts: forwardTts(options),
       url: String(url),
       title: String(title),
       file_name: fileName ? String(fileName) : null,
@@ -79,8 +218,9 @@ globalThis.nbot = {
     );
   },
 
-  // Call LLM by downloading an archive URL and extracting a log/text file (temp file, removed after processing)
-  // Supported: .zip / .tar / .tar.gz (.tgz) / .gz
+  // Call LLM by downloading an archive URL and extracting the most relevant log/text files (temp file, removed after processing)
+  // Supported: .zip / .tar / .tar.gz (.tgz) / .gz, including nested archives; 7z / rar are detected and rejected
+  // options.bundleFiles: how many relevant files to combine (default 3, max 10)
   callLlmForwardArchiveFromUrl: (
     userId,
     groupId,
@@ -99,6 +239,7 @@ globalThis.nbot = {
   ) => {
     const payload = {
       model_name: options.modelName ? String(options.modelName) : null,
+      tts: forwardTts(options),
       url: String(url),
       title: String(title),
       file_name: fileName ? String(fileName) : null,
@@ -107,6 +248,7 @@ globalThis.nbot = {
       max_extract_bytes: maxExtractBytes,
       max_file_bytes: maxFileBytes,
       max_files: maxFiles,
+      bundle_files: options.bundleFiles ? Number(options.bundleFiles) : null,
       keywords: Array.isArray(keywords) ? keywords.map((x) => String(x)) : [],
     };
     return core.ops.op_call_llm_forward_archive_from_url(
@@ -137,6 +279,7 @@ globalThis.nbot = {
   ) => {
     const payload = {
       model_name: options.modelName ? String(options.modelName) : null,
+      tts: forwardTts(options),
       url: String(url),
       title: String(title),
       file_name: fileName ? String(fileName) : null,
@@ -157,6 +300,7 @@ globalThis.nbot = {
   },
 
   // Call multimodal LLM with a video from URL (downloaded by core, frames extracted, temp files removed after processing)
+  // options.frameSelection: "even" (default) or "scene" (prefer scene changes, fill the rest evenly)
   callLlmForwardVideoFromUrl: (
     userId,
     groupId,
@@ -181,6 +325,7 @@ globalThis.nbot = {
   ) => {
     const payload = {
       model_name: options.modelName ? String(options.modelName) : null,
+      tts: forwardTts(options),
       url: String(url),
       title: String(title),
       file_name: fileName ? String(fileName) : null,
@@ -192,6 +337,7 @@ globalThis.nbot = {
       frame_max_height: frameMaxHeight,
       frame_jpeg_quality: frameJpegQuality,
       frame_max_output_bytes: frameMaxOutputBytes,
+      frame_selection: options.frameSelection ? String(options.frameSelection) : null,
       transcribe_audio: !!transcribeAudio,
       transcription_model: transcriptionModel ? String(transcriptionModel) : null,
       max_audio_seconds: maxAudioSeconds,
@@ -224,6 +370,7 @@ globalThis.nbot = {
   ) => {
     const payload = {
       model_name: options.modelName ? String(options.modelName) : null,
+      tts: forwardTts(options),
       url: String(url),
       title: String(title),
       file_name: fileName ? String(fileName) : null,
@@ -255,6 +402,7 @@ globalThis.nbot = {
   ) => {
     const payload = {
       model_name: options.modelName ? String(options.modelName) : null,
+      tts: forwardTts(options),
       title: String(title || "Multimodal Analysis"),
       text: text ? String(text) : null,
       items: Array.isArray(items) ? items : [],
@@ -279,18 +427,120 @@ globalThis.nbot = {
   // Call LLM for multi-turn chat (async, result returned via onLlmResponse hook)
   // requestId: unique identifier for matching response
   // messages: array of {role: "system"|"user"|"assistant", content: "..."}
-  // options: { modelName?: string, maxTokens?: number }
-  // Returns immediately; result delivered via onLlmResponse({ requestId, success, content })
+  // options: { modelName?: string, maxTokens?: number, stream?: boolean | StreamOptions, tools?: Tool[],
+  //            toolChoice?: "auto" | "required" | string, maxToolRounds?: number, toolTimeoutMs?: number }
+  // Returns immediately; result delivered via onLlmResponse({ requestId, success, content, raw? })
+  // StreamOptions: {
+  //   onChunk?: boolean,                       // onLlmChunk({ requestId, delta, text, index }), default true
+  //   deliverTo?: { userId, groupId },         // send partial output to this chat while generating
+  //   intervalMs?: number, minChars?: number,  // throttling (default 1500ms / 200 chars per QQ segment)
+  // }
+  // With deliverTo, Discord edits one message in place and QQ posts paced segments;
+  // onLlmResponse still receives the full content, so do not send it again.
+  // Tool: { name, description?, parameters?: JSONSchema } (OpenAI { type: "function", function } also accepted)
+  //   or a built-in tool name: "web_search" (Tavily, uses the LLM module's Tavily key).
+  // When the model calls a plugin tool the runtime invokes onLlmToolCall({ requestId, toolCallId, name, arguments })
+  // and feeds its return value (string, or JSON-serialized object) back to the model; the loop stops after
+  // maxToolRounds (default 5, max 10) and each call is limited by toolTimeoutMs (default 30000).
+  // Tool calls are not streamed: with tools, `stream` is ignored.
+  // Conversation memory: pass options.conversationId and only the new turn (a string is taken as one user message);
+  // the host prepends the stored summary and recent history within contextTokens (default: LLM module memory config),
+  // stores the reply, and starts over after sessionTimeoutMinutes of inactivity (0 = never).
+  // conversationGroupId / conversationUserId tag the conversation (default: the current event's source).
+  // Structured output: options.responseFormat = { json_schema: JSONSchema, name?, strict?, maxRepairs? }
+  //   (OpenAI's { type: "json_schema", json_schema: { name, schema, strict } } and { type: "json_object" } also accepted).
+  // The host asks the provider for JSON, validates it against the schema and asks the model to fix it
+  // up to maxRepairs times (default 2); onLlmResponse then gets the parsed value as `content` and the text as `raw`.
   callLlmChat: (requestId, messages, options = {}) => {
+    let stream = null;
+    if (options.stream) {
+      const s = typeof options.stream === "object" ? options.stream : {};
+      const to = s.deliverTo || null;
+      stream = {
+        chunk_events: s.onChunk !== false,
+        deliver_user_id: to ? toBigInt(to.userId).toString() : "",
+        deliver_group_id: to ? toBigInt(to.groupId).toString() : "",
+        interval_ms: s.intervalMs || null,
+        min_chars: s.minChars || null,
+      };
+    }
     const payload = {
       request_id: String(requestId),
       model_name: options.modelName ? String(options.modelName) : null,
-      messages: Array.isArray(messages) ? messages : [],
+      messages: normalizeMessages(messages),
       max_tokens: options.maxTokens || null,
+      stream,
+      tools: null,
+      conversation: null,
+      response_format: null,
     };
+    const rf = options.responseFormat || options.response_format;
+    if (rf && typeof rf === "object") {
+      let schema = rf.json_schema || rf.jsonSchema || null;
+      let name = rf.name || "";
+      let strict = rf.strict === true;
+      // OpenAI style: json_schema wraps { name, schema, strict }
+      if (schema && schema.schema && typeof schema.schema === "object") {
+        name = schema.name || name;
+        strict = schema.strict === true || strict;
+        schema = schema.schema;
+      }
+      payload.response_format = {
+        name: String(name),
+        schema: schema && typeof schema === "object" ? schema : null,
+        strict,
+        max_repairs:
+          rf.maxRepairs === undefined || rf.maxRepairs === null ? null : Number(rf.maxRepairs),
+      };
+    }
+    if (options.conversationId) {
+      payload.conversation = {
+        id: String(options.conversationId),
+        max_context_tokens: options.contextTokens || null,
+        session_timeout_minutes:
+          options.sessionTimeoutMinutes === undefined || options.sessionTimeoutMinutes === null
+            ? null
+            : Number(options.sessionTimeoutMinutes),
+        group_id: options.conversationGroupId ? toBigInt(options.conversationGroupId).toString() : "",
+        user_id: options.conversationUserId ? toBigInt(options.conversationUserId).toString() : "",
+      };
+    }
+    if (Array.isArray(options.tools) && options.tools.length > 0) {
+      const functions = [];
+      const builtin = [];
+      for (const t of options.tools) {
+        if (typeof t === "string") {
+          builtin.push(t);
+          continue;
+        }
+        const f = t && t.type === "function" && t.function ? t.function : t;
+        if (!f || !f.name) continue;
+        functions.push({
+          name: String(f.name),
+          description: f.description ? String(f.description) : "",
+          parameters: f.parameters || null,
+        });
+      }
+      let toolChoice = options.toolChoice || null;
+      if (typeof toolChoice === "string" && !["auto", "required", "none"].includes(toolChoice)) {
+        toolChoice = { type: "function", function: { name: toolChoice } };
+      }
+      payload.tools = {
+        functions,
+        builtin,
+        max_rounds: options.maxToolRounds || null,
+        timeout_ms: options.toolTimeoutMs || null,
+        tool_choice: toolChoice,
+      };
+    }
     return core.ops.op_call_llm_chat(JSON.stringify(payload));
   },
 
+  // Clear the stored memory of a conversation started with callLlmChat({ conversationId })
+  clearConversation: (conversationId) => {
+    return core.ops.op_clear_conversation(String(conversationId || ""));
+  },
+
   // Call LLM with web search capability (async, result returned via onLlmResponse hook)
   // requestId: unique identifier for matching response
   // messages: array of {role: "system"|"user"|"assistant", content: "..."}
@@ -479,6 +729,210 @@ globalThis.nbot = {
       headersJson
     );
   },
+
+  // Search the local message archive (requires the archive module to be enabled)
+  // requestId: unique identifier for matching response
+  // options: { query?: string, groupId?: number, userId?: number, since?: number (unix seconds), limit?: number }
+  // Response infoType is "archive_search", data is an array of archived messages (newest first).
+  searchChatArchive: (requestId, options = {}) => {
+    return core.ops.op_search_chat_archive(
+      String(requestId),
+      String(options.query || ""),
+      toBigInt(options.groupId || 0),
+      toBigInt(options.userId || 0),
+      toBigInt(options.since || 0),
+      options.limit || 0
+    );
+  },
+
+  // Group knowledge base (embeddings over group files / announcements / plugin-provided text).
+  // Each call returns a promise of `data`; with options.requestId the result goes to
+  // onGroupInfoResponse({ requestId, infoType, success, data }) instead and the requestId is returned.
+  // options.groupId defaults to the current event's group; 0 = bot-wide base.
+  kb: {
+    // infoType "kb_search", data: [{ source, title, text, score }] sorted by relevance
+    search: (query, k = 5, options = {}) =>
+      infoRequest(options, (requestId) =>
+        core.ops.op_kb_search(requestId, String(query || ""), Number(k) || 5, kbGroupId(options))
+      ),
+    // source: stable id (re-ingesting the same source replaces it); options: { title?, text?, url?, fileName? }
+    // infoType "kb_ingest", data: { chunks }
+    ingest: (source, options = {}) => {
+      const payload = {
+        source: String(source || ""),
+        title: options.title ? String(options.title) : "",
+        text: options.text ? String(options.text) : null,
+        url: options.url ? String(options.url) : null,
+        file_name: options.fileName ? String(options.fileName) : null,
+      };
+      return infoRequest(options, (requestId) =>
+        core.ops.op_kb_ingest(requestId, kbGroupId(options), JSON.stringify(payload))
+      );
+    },
+    // infoType "kb_remove", data: { removed }
+    remove: (source, options = {}) =>
+      infoRequest(options, (requestId) =>
+        core.ops.op_kb_remove(requestId, kbGroupId(options), String(source || ""))
+      ),
+  },
+
+  // Text-to-speech. Resolves with { segment, format, bytes }: segment is a ready-to-send
+  // `[CQ:record,file=base64://...]` encoded for the bot's platform (silk/amr for QQ, ogg/opus for Discord).
+  // voice: optional voice name (defaults to the llm module `tts.voice`); with options.requestId the result
+  // goes to onGroupInfoResponse({ requestId, infoType: "tts", success, data }) instead.
+  tts: (text, voice = "", options = {}) =>
+    infoRequest(options, (requestId) =>
+      core.ops.op_tts(requestId, String(text || ""), String(voice || ""))
+    ),
+
+  // Conversation context of a message: follows the reply chain up to options.replyDepth levels (default 3),
+  // expands merged forwards up to options.forwardDepth (default 3) and collects media, keeping the newest
+  // messages within options.maxChars (default 8000). message: a message id or a hook ctx (uses ctx.message_id).
+  // Resolves with { messages, media, chain, truncated, chainTruncated, mediaTruncated }: messages are
+  // chronological { role, content } entries ready for nbot.llm.chat; with options.attachImages images are
+  // added as image_url parts. With options.requestId the result goes to
+  // onGroupInfoResponse({ requestId, infoType: "conversation_context", success, data }) instead.
+  context: {
+    build: (message, options = {}) => {
+      const messageId =
+        message && typeof message === "object" ? message.message_id ?? message.messageId : message;
+      const payload = {
+        message_id: String(messageId ?? ""),
+        reply_depth: options.replyDepth != null ? Number(options.replyDepth) : null,
+        forward_depth: options.forwardDepth != null ? Number(options.forwardDepth) : null,
+        max_chars: options.maxChars != null ? Number(options.maxChars) : null,
+        max_media: options.maxMedia != null ? Number(options.maxMedia) : null,
+        attach_images: !!options.attachImages,
+      };
+      if (!payload.message_id.trim()) {
+        return Promise.reject(new Error("message id must not be empty"));
+      }
+      return infoRequest(options, (requestId) =>
+        core.ops.op_build_conversation_context(requestId, JSON.stringify(payload))
+      );
+    },
+  },
+
+  // Promise-based API: `await nbot.llm.chat(...)`, `await nbot.group.history(...)` etc. resolve inside the
+  // plugin's event loop and reject with an Error on failure or after options.timeoutMs
+  // (default 300s for LLM calls, 60s otherwise). The requestId/callback functions above keep working.
+  llm: {
+    // Same options as callLlmChat. Resolves with the reply text (the parsed JSON with responseFormat).
+    // options.onChunk(chunk) receives streaming chunks ({ delta, text, index }); tools may carry
+    // handler(arguments, call) to answer the model instead of onLlmToolCall (handlers cannot await
+    // other nbot calls while the model is waiting).
+    chat: (messages, options = {}) => {
+      if (normalizeMessages(messages).length === 0) {
+        return Promise.reject(new Error("messages must not be empty"));
+      }
+      const toolHandlers = {};
+      for (const t of Array.isArray(options.tools) ? options.tools : []) {
+        const f = t && t.type === "function" && t.function ? t.function : t;
+        if (f && f.name && typeof t.handler === "function") toolHandlers[String(f.name)] = t.handler;
+      }
+      const onChunk = typeof options.onChunk === "function" ? options.onChunk : null;
+      const chatOptions = onChunk && !options.stream ? { ...options, stream: { onChunk: true } } : options;
+      return request(
+        "llm",
+        options,
+        (requestId) => globalThis.nbot.callLlmChat(requestId, messages, chatOptions),
+        settleLlm,
+        { onChunk, toolHandlers }
+      );
+    },
+    // Same options as callLlmChatWithSearch; resolves with the reply text.
+    chatWithSearch: (messages, options = {}) => {
+      if (normalizeMessages(messages).length === 0) {
+        return Promise.reject(new Error("messages must not be empty"));
+      }
+      return request(
+        "llm",
+        options,
+        (requestId) =>
+          globalThis.nbot.callLlmChatWithSearch(requestId, normalizeMessages(messages), options),
+        settleLlm
+      );
+    },
+    // Image generation via the llm module `images` config (OpenAI-compatible /images/generations;
+    // inputImageUrl switches to /images/edits). options: { prompt, size?, quality?, inputImageUrl?,
+    // modelName? }. Resolves with { base64, mime, segment, revisedPrompt }: segment is a ready-to-send
+    // `[CQ:image,file=base64://...]`. With options.requestId the result goes to
+    // onGroupInfoResponse({ requestId, infoType: "llm_image", success, data }) instead.
+    image: (options = {}) => {
+      const opts = typeof options === "string" ? { prompt: options } : options || {};
+      const payload = {
+        prompt: String(opts.prompt || ""),
+        size: opts.size ? String(opts.size) : null,
+        quality: opts.quality ? String(opts.quality) : null,
+        input_image_url: opts.inputImageUrl ? String(opts.inputImageUrl) : null,
+        model_name: opts.modelName ? String(opts.modelName) : null,
+      };
+      if (!payload.prompt.trim()) {
+        return Promise.reject(new Error("prompt must not be empty"));
+      }
+      return infoRequest({ timeoutMs: LLM_TIMEOUT_MS, ...opts }, (requestId) =>
+        core.ops.op_llm_image(JSON.stringify({ ...payload, request_id: requestId }))
+      );
+    },
+  },
+
+  // Resolve with the same `data` that onGroupInfoResponse would receive; options.timeoutMs applies to all.
+  group: {
+    notice: (groupId, options = {}) =>
+      infoPromise(options, (requestId) => globalThis.nbot.fetchGroupNotice(requestId, groupId)),
+    // options: { count?, messageSeq? }
+    history: (groupId, options = {}) =>
+      infoPromise(options, (requestId) =>
+        globalThis.nbot.fetchGroupMsgHistory(requestId, groupId, options)
+      ),
+    files: (groupId, folderId = "", options = {}) =>
+      infoPromise(options, (requestId) =>
+        globalThis.nbot.fetchGroupFiles(requestId, groupId, folderId)
+      ),
+    fileUrl: (groupId, fileId, busid = 0, options = {}) =>
+      infoPromise(options, (requestId) =>
+        globalThis.nbot.fetchGroupFileUrl(requestId, groupId, fileId, busid)
+      ),
+    memberList: (groupId, options = {}) =>
+      infoPromise(options, (requestId) => globalThis.nbot.fetchGroupMemberList(requestId, groupId)),
+    list: (options = {}) =>
+      infoPromise(options, (requestId) => globalThis.nbot.fetchGroupList(requestId)),
+  },
+
+  friend: {
+    list: (options = {}) =>
+      infoPromise(options, (requestId) => globalThis.nbot.fetchFriendList(requestId)),
+  },
+
+  file: {
+    // options: same as downloadFile
+    download: (url, options = {}) =>
+      infoPromise(options, (requestId) => globalThis.nbot.downloadFile(requestId, url, options)),
+  },
+
+  archive: {
+    // options: same as searchChatArchive
+    search: (options = {}) =>
+      infoPromise(options, (requestId) => globalThis.nbot.searchChatArchive(requestId, options)),
+  },
+
+  // Resolve with the onApiResponse payload ({ action, success, status, retcode, messageId, data, message }).
+  api: {
+    call: (action, params = {}, options = {}) =>
+      request(
+        "api",
+        options,
+        (requestId) => globalThis.nbot.callApi(action, params, { requestId }),
+        settleApi
+      ),
+    sendReply: (userId, groupId, content, options = {}) =>
+      request(
+        "api",
+        options,
+        (requestId) => globalThis.nbot.sendReply(userId, groupId, content, { requestId }),
+        settleApi
+      ),
+  },
 };
 
 // Helper to define plugin
@@ -500,6 +954,7 @@ export const callLlmForwardAudioFromUrl = globalThis.nbot.callLlmForwardAudioFro
 export const callLlmForwardMediaBundle = globalThis.nbot.callLlmForwardMediaBundle;
 export const callLlmChat = globalThis.nbot.callLlmChat;
 export const callLlmChatWithSearch = globalThis.nbot.callLlmChatWithSearch;
+export const clearConversation = globalThis.nbot.clearConversation;
 export const sendForwardMessage = globalThis.nbot.sendForwardMessage;
 export const httpFetch = globalThis.nbot.httpFetch;
 export const renderMarkdownImage = globalThis.nbot.renderMarkdownImage;
@@ -518,4 +973,13 @@ export const fetchFriendList = globalThis.nbot.fetchFriendList;
 export const fetchGroupList = globalThis.nbot.fetchGroupList;
 export const fetchGroupMemberList = globalThis.nbot.fetchGroupMemberList;
 export const downloadFile = globalThis.nbot.downloadFile;
+export const searchChatArchive = globalThis.nbot.searchChatArchive;
+export const kb = globalThis.nbot.kb;
+export const context = globalThis.nbot.context;
+export const llm = globalThis.nbot.llm;
+export const group = globalThis.nbot.group;
+export const friend = globalThis.nbot.friend;
+export const file = globalThis.nbot.file;
+export const archive = globalThis.nbot.archive;
+export const api = globalThis.nbot.api;
 export const definePlugin = globalThis.definePlugin;
@@ -67,8 +67,25 @@ pub enum PluginRequest {
         request_id: String,
         success: bool,
         content: String,
+        parsed: Option<serde_json::Value>,
         respond: oneshot::Sender<Result<Vec<PluginOutput>, String>>,
     },
+    OnLlmChunk {
+        plugin_id: String,
+        request_id: String,
+        delta: String,
+        text: String,
+        index: u32,
+        respond: oneshot::Sender<Result<Vec<PluginOutput>, String>>,
+    },
+    OnLlmToolCall {
+        plugin_id: String,
+        request_id: String,
+        tool_call_id: String,
+        name: String,
+        arguments: serde_json::Value,
+        respond: oneshot::Sender<Result<(String, Vec<PluginOutput>), String>>,
+    },
     OnGroupInfoResponse {
         plugin_id: String,
         request_id: String,
@@ -77,6 +94,17 @@ pub enum PluginRequest {
         data: String,
         respond: oneshot::Sender<Result<Vec<PluginOutput>, String>>,
     },
+    OnApiResponse {
+        plugin_id: String,
+        request_id: String,
+        action: String,
+        response: serde_json::Value,
+        respond: oneshot::Sender<Result<Vec<PluginOutput>, String>>,
+    },
+    SweepRequests {
+        plugin_id: String,
+        respond: oneshot::Sender<Result<(bool, Vec<PluginOutput>), String>>,
+    },
 }
 
 /// 插件管理器 - 管理所有插件运行时
@@ -423,6 +451,7 @@ impl PluginManager {
         request_id: &str,
         success: bool,
         content: &str,
+        parsed: Option<&serde_json::Value>,
     ) -> Result<Vec<PluginOutput>, String> {
         let (respond, rx) = oneshot::channel();
         self.tx
@@ -431,6 +460,7 @@ impl PluginManager {
                 request_id: request_id.to_string(),
                 success,
                 content: content.to_string(),
+                parsed: parsed.cloned(),
                 respond,
             })
             .await
@@ -440,6 +470,58 @@ impl PluginManager {
             .map_err(|_| "接收插件 onLlmResponse 响应失败".to_string())?
     }
 
+    /// 调用 onLlmChunk 钩子 - 流式 LLM 调用的增量回调
+    pub async fn on_llm_chunk(
+        &self,
+        plugin_id: &str,
+        request_id: &str,
+        delta: &str,
+        text: &str,
+        index: u32,
+    ) -> Result<Vec<PluginOutput>, String> {
+        let (respond, rx) = oneshot::channel();
+        self.tx
+            .send(PluginRequest::OnLlmChunk {
+                plugin_id: plugin_id.to_string(),
+                request_id: request_id.to_string(),
+                delta: delta.to_string(),
+                text: text.to_string(),
+                index,
+                respond,
+            })
+            .await
+            .map_err(|e| format!("发送插件 onLlmChunk 请求失败: {}", e))?;
+
+        rx.await
+            .map_err(|_| "接收插件 onLlmChunk 响应失败".to_string())?
+    }
+
+    /// 调用 onLlmToolCall 钩子 - 模型调用插件声明的工具，返回 (结果文本, 钩子产生的输出)
+    pub async fn on_llm_tool_call(
+        &self,
+        plugin_id: &str,
+        request_id: &str,
+        tool_call_id: &str,
+        name: &str,
+        arguments: &serde_json::Value,
+    ) -> Result<(String, Vec<PluginOutput>), String> {
+        let (respond, rx) = oneshot::channel();
+        self.tx
+            .send(PluginRequest::OnLlmToolCall {
+                plugin_id: plugin_id.to_string(),
+                request_id: request_id.to_string(),
+                tool_call_id: tool_call_id.to_string(),
+                name: name.to_string(),
+                arguments: arguments.clone(),
+                respond,
+            })
+            .await
+            .map_err(|e| format!("发送插件 onLlmToolCall 请求失败: {}", e))?;
+
+        rx.await
+            .map_err(|_| "接收插件 onLlmToolCall 响应失败".to_string())?
+    }
+
     /// 调用 onGroupInfoResponse 钩子 - 群信息获取完成后的回调
     pub async fn on_group_info_response(
         &self,
@@ -466,6 +548,48 @@ impl PluginManager {
             .map_err(|_| "接收插件 onGroupInfoResponse 响应失败".to_string())?
     }
 
+    /// 调用 onApiResponse 钩子 - 带 requestId 的发送/API 调用完成后的回调
+    pub async fn on_api_response(
+        &self,
+        plugin_id: &str,
+        request_id: &str,
+        action: &str,
+        response: serde_json::Value,
+    ) -> Result<Vec<PluginOutput>, String> {
+        let (respond, rx) = oneshot::channel();
+        self.tx
+            .send(PluginRequest::OnApiResponse {
+                plugin_id: plugin_id.to_string(),
+                request_id: request_id.to_string(),
+                action: action.to_string(),
+                response,
+                respond,
+            })
+            .await
+            .map_err(|e| format!("发送插件 onApiResponse 请求失败: {}", e))?;
+
+        rx.await
+            .map_err(|_| "接收插件 onApiResponse 响应失败".to_string())?
+    }
+
+    /// 让插件中超时的 Promise 请求失败，返回（是否仍有等待中的请求, 新输出）
+    pub async fn sweep_requests(
+        &self,
+        plugin_id: &str,
+    ) -> Result<(bool, Vec<PluginOutput>), String> {
+        let (respond, rx) = oneshot::channel();
+        self.tx
+            .send(PluginRequest::SweepRequests {
+                plugin_id: plugin_id.to_string(),
+                respond,
+            })
+            .await
+            .map_err(|e| format!("发送插件 sweepRequests 请求失败: {}", e))?;
+
+        rx.await
+            .map_err(|_| "接收插件 sweepRequests 响应失败".to_string())?
+    }
+
     /// 检查插件是否已加载
     pub fn is_loaded(&self, plugin_id: &str) -> bool {
         self.loaded_plugins.contains_key(plugin_id)
@@ -823,11 +947,48 @@ async fn plugin_worker(
                 request_id,
                 success,
                 content,
+                parsed,
                 respond,
             } => {
                 let result = if let Some(entry) = runtimes.get_mut(&plugin_id) {
                     entry.runtime
-                        .on_llm_response(&request_id, success, &content)
+                        .on_llm_response(&request_id, success, &content, parsed.as_ref())
+                        .await
+                } else {
+                    Err(format!("插件 {} 未加载", plugin_id))
+                };
+                let _ = respond.send(result);
+            }
+            PluginRequest::OnLlmChunk {
+                plugin_id,
+                request_id,
+                delta,
+                text,
+                index,
+                respond,
+            } => {
+                let result = if let Some(entry) = runtimes.get_mut(&plugin_id) {
+                    entry
+                        .runtime
+                        .on_llm_chunk(&request_id, &delta, &text, index)
+                        .await
+                } else {
+                    Err(format!("插件 {} 未加载", plugin_id))
+                };
+                let _ = respond.send(result);
+            }
+            PluginRequest::OnLlmToolCall {
+                plugin_id,
+                request_id,
+                tool_call_id,
+                name,
+                arguments,
+                respond,
+            } => {
+                let result = if let Some(entry) = runtimes.get_mut(&plugin_id) {
+                    entry
+                        .runtime
+                        .on_llm_tool_call(&request_id, &tool_call_id, &name, &arguments)
                         .await
                 } else {
                     Err(format!("插件 {} 未加载", plugin_id))
@@ -851,6 +1012,31 @@ async fn plugin_worker(
                 };
                 let _ = respond.send(result);
             }
+            PluginRequest::OnApiResponse {
+                plugin_id,
+                request_id,
+                action,
+                response,
+                respond,
+            } => {
+                let result = if let Some(entry) = runtimes.get_mut(&plugin_id) {
+                    entry
+                        .runtime
+                        .on_api_response(&request_id, &action, &response)
+                        .await
+                } else {
+                    Err(format!("插件 {} 未加载", plugin_id))
+                };
+                let _ = respond.send(result);
+            }
+            PluginRequest::SweepRequests { plugin_id, respond } => {
+                let result = if let Some(entry) = runtimes.get_mut(&plugin_id) {
+                    entry.runtime.sweep_requests().await
+                } else {
+                    Err(format!("插件 {} 未加载", plugin_id))
+                };
+                let _ = respond.send(result);
+            }
         }
     }
 
@@ -7,15 +7,18 @@ mod ops;
 mod state;
 
 use ops::*;
-use state::{get_hook_result, reset_hook_state, take_outputs, PluginOpState};
+use state::{get_hook_result, reset_hook_state, take_outputs, take_tool_result, PluginOpState};
 
-pub use state::{ForwardNode, MediaBundleItem, PluginOutput};
+pub use state::{
+    ConversationContextOptions, ForwardNode, ForwardTts, LlmConversationOptions,
+    LlmResponseFormat, LlmStreamOptions, LlmToolOptions, MediaBundleItem, PluginOutput,
+};
 
 use super::types::PluginCodeType;
 
 extension!(
     nbot_plugin,
-    ops = [op_send_message, op_send_reply, op_call_api, op_log, op_set_hook_result, op_now, op_get_config, op_set_config, op_storage_set, op_storage_get, op_storage_delete, op_get_plugin_id, op_call_llm_forward, op_call_llm_forward_from_url, op_call_llm_forward_archive_from_url, op_call_llm_forward_image_from_url, op_call_llm_forward_video_from_url, op_call_llm_forward_audio_from_url, op_call_llm_forward_media_bundle, op_call_llm_chat, op_call_llm_chat_with_search, op_send_forward_message, op_http_fetch, op_render_markdown_image, op_render_html_image, op_fetch_group_notice, op_fetch_group_msg_history, op_fetch_group_files, op_fetch_group_file_url, op_fetch_friend_list, op_fetch_group_list, op_fetch_group_member_list, op_download_file],
+    ops = [op_send_message, op_send_reply, op_call_api, op_log, op_set_hook_result, op_set_tool_result, op_now, op_get_config, op_set_config, op_storage_set, op_storage_get, op_storage_delete, op_get_plugin_id, op_call_llm_forward, op_call_llm_forward_from_url, op_call_llm_forward_archive_from_url, op_call_llm_forward_image_from_url, op_call_llm_forward_video_from_url, op_call_llm_forward_audio_from_url, op_call_llm_forward_media_bundle, op_call_llm_chat, op_call_llm_chat_with_search, op_clear_conversation, op_send_forward_message, op_http_fetch, op_render_markdown_image, op_render_html_image, op_fetch_group_notice, op_fetch_group_msg_history, op_fetch_group_files, op_fetch_group_file_url, op_fetch_friend_list, op_fetch_group_list, op_fetch_group_member_list, op_download_file, op_search_chat_archive, op_kb_search, op_kb_ingest, op_kb_remove, op_tts, op_send_voice_reply, op_llm_image, op_build_conversation_context],
     esm_entry_point = "ext:nbot_plugin/runtime.js",
     esm = [dir "src/plugin/js", "runtime.js"],
 );
@@ -47,6 +50,7 @@ impl PluginRuntime {
                 config,
                 data_dir: data_dir.to_string(),
                 hook_result: None,
+                tool_result: None,
                 outputs: Vec::new(),
             });
         }
@@ -362,6 +366,10 @@ impl PluginRuntime {
         let code = format!(
             r#"
             (async () => {{
+                // 心跳/tick 顺带让超时的 Promise 请求失败
+                if (globalThis.__nbotSweepRequests) {{
+                    globalThis.__nbotSweepRequests();
+                }}
                 if (globalThis.__plugin && globalThis.__plugin.onMetaEvent) {{
                     const result = await globalThis.__plugin.onMetaEvent({});
                     Deno.core.ops.op_set_hook_result(result !== false);
@@ -391,32 +399,44 @@ impl PluginRuntime {
     /// request_id: 请求 ID（与 callLlmChat 时传入的一致）
     /// success: 是否成功
     /// content: 成功时为 LLM 回复内容，失败时为错误信息
+    /// parsed: 指定 response_format 时为校验通过的 JSON，此时 content 传给插件的是它，原文放在 raw
     pub async fn on_llm_response(
         &mut self,
         request_id: &str,
         success: bool,
         content: &str,
+        parsed: Option<&serde_json::Value>,
     ) -> Result<Vec<PluginOutput>, String> {
         take_outputs(&mut self.runtime);
 
         let request_id_json = serde_json::to_string(request_id)
             .map_err(|e| format!("Serialize request_id failed: {e}"))?;
-        let content_json =
+        let raw_json =
             serde_json::to_string(content).map_err(|e| format!("Serialize content failed: {e}"))?;
+        let (content_json, raw_json) = match parsed {
+            Some(value) => (value.to_string(), raw_json),
+            None => (raw_json, "undefined".to_string()),
+        };
 
         let code = format!(
             r#"
             (async () => {{
+                const response = {{
+                    requestId: {},
+                    success: {},
+                    content: {},
+                    raw: {}
+                }};
+                // nbot.llm.chat 发起的请求直接兑现对应的 Promise
+                if (globalThis.__nbotSettleRequest && globalThis.__nbotSettleRequest(response)) {{
+                    return;
+                }}
                 if (globalThis.__plugin && globalThis.__plugin.onLlmResponse) {{
-                    await globalThis.__plugin.onLlmResponse({{
-                        requestId: {},
-                        success: {},
-                        content: {}
-                    }});
+                    await globalThis.__plugin.onLlmResponse(response);
                 }}
             }})()
             "#,
-            request_id_json, success, content_json
+            request_id_json, success, content_json, raw_json
         );
 
         self.runtime
@@ -431,6 +451,124 @@ impl PluginRuntime {
         Ok(take_outputs(&mut self.runtime))
     }
 
+    /// onLlmChunk 钩子：流式 callLlmChat 的增量回调（按节流间隔合并后触发）
+    /// delta: 自上次回调以来的新增文本；text: 目前为止的完整文本；index: 回调序号（从 0 开始）
+    pub async fn on_llm_chunk(
+        &mut self,
+        request_id: &str,
+        delta: &str,
+        text: &str,
+        index: u32,
+    ) -> Result<Vec<PluginOutput>, String> {
+        take_outputs(&mut self.runtime);
+
+        let request_id_json = serde_json::to_string(request_id)
+            .map_err(|e| format!("Serialize request_id failed: {e}"))?;
+        let delta_json =
+            serde_json::to_string(delta).map_err(|e| format!("Serialize delta failed: {e}"))?;
+        let text_json =
+            serde_json::to_string(text).map_err(|e| format!("Serialize text failed: {e}"))?;
+
+        let code = format!(
+            r#"
+            (async () => {{
+                const chunk = {{
+                    requestId: {},
+                    delta: {},
+                    text: {},
+                    index: {}
+                }};
+                if (globalThis.__nbotLlmChunk && globalThis.__nbotLlmChunk(chunk)) {{
+                    return;
+                }}
+                if (globalThis.__plugin && globalThis.__plugin.onLlmChunk) {{
+                    await globalThis.__plugin.onLlmChunk(chunk);
+                }}
+            }})()
+            "#,
+            request_id_json, delta_json, text_json, index
+        );
+
+        self.runtime
+            .execute_script("<onLlmChunk>", code)
+            .map_err(|e| format!("onLlmChunk failed: {}", e))?;
+
+        self.runtime
+            .run_event_loop(Default::default())
+            .await
+            .map_err(|e| format!("onLlmChunk event loop failed: {}", e))?;
+
+        Ok(take_outputs(&mut self.runtime))
+    }
+
+    /// onLlmToolCall 钩子：模型调用插件声明的工具，返回交给模型的结果文本
+    /// arguments: 模型给出的参数（通常是对象）
+    pub async fn on_llm_tool_call(
+        &mut self,
+        request_id: &str,
+        tool_call_id: &str,
+        name: &str,
+        arguments: &serde_json::Value,
+    ) -> Result<(String, Vec<PluginOutput>), String> {
+        reset_hook_state(&mut self.runtime);
+
+        let request_id_json = serde_json::to_string(request_id)
+            .map_err(|e| format!("Serialize request_id failed: {e}"))?;
+        let tool_call_id_json = serde_json::to_string(tool_call_id)
+            .map_err(|e| format!("Serialize tool_call_id failed: {e}"))?;
+        let name_json =
+            serde_json::to_string(name).map_err(|e| format!("Serialize name failed: {e}"))?;
+        let arguments_json = serde_json::to_string(arguments)
+            .map_err(|e| format!("Serialize arguments failed: {e}"))?;
+
+        let code = format!(
+            r#"
+            (async () => {{
+                let result = null;
+                try {{
+                    const call = {{
+                        requestId: {},
+                        toolCallId: {},
+                        name: {},
+                        arguments: {}
+                    }};
+                    const routed = globalThis.__nbotLlmToolCall
+                        ? globalThis.__nbotLlmToolCall(call)
+                        : null;
+                    if (routed) {{
+                        result = await routed;
+                    }} else if (globalThis.__plugin && globalThis.__plugin.onLlmToolCall) {{
+                        result = await globalThis.__plugin.onLlmToolCall(call);
+                    }} else {{
+                        result = "插件未实现 onLlmToolCall";
+                    }}
+                }} catch (e) {{
+                    result = "工具执行出错: " + (e && e.message ? e.message : String(e));
+                }}
+                if (result === undefined || result === null) {{
+                    result = "";
+                }}
+                Deno.core.ops.op_set_tool_result(
+                    typeof result === "string" ? result : JSON.stringify(result)
+                );
+            }})()
+            "#,
+            request_id_json, tool_call_id_json, name_json, arguments_json
+        );
+
+        self.runtime
+            .execute_script("<onLlmToolCall>", code)
+            .map_err(|e| format!("onLlmToolCall failed: {}", e))?;
+
+        self.runtime
+            .run_event_loop(Default::default())
+            .await
+            .map_err(|e| format!("onLlmToolCall event loop failed: {}", e))?;
+
+        let result = take_tool_result(&mut self.runtime).unwrap_or_default();
+        Ok((result, take_outputs(&mut self.runtime)))
+    }
+
     /// onGroupInfoResponse hook: callback after group info fetch completes
     /// request_id: request ID (matches the one passed to fetchGroupNotice/fetchGroupMsgHistory/etc.)
     /// info_type: type of info ("notice", "msg_history", "files", "file_url", "download")
@@ -453,19 +591,23 @@ impl PluginRuntime {
         let code = format!(
             r#"
             (async () => {{
+                let parsedData = null;
+                try {{
+                    parsedData = JSON.parse({data});
+                }} catch (e) {{
+                    parsedData = {data};
+                }}
+                const response = {{
+                    requestId: {request_id},
+                    infoType: {info_type},
+                    success: {success},
+                    data: parsedData
+                }};
+                if (globalThis.__nbotSettleRequest && globalThis.__nbotSettleRequest(response)) {{
+                    return;
+                }}
                 if (globalThis.__plugin && globalThis.__plugin.onGroupInfoResponse) {{
-                    let parsedData = null;
-                    try {{
-                        parsedData = JSON.parse({data});
-                    }} catch (e) {{
-                        parsedData = {data};
-                    }}
-                    await globalThis.__plugin.onGroupInfoResponse({{
-                        requestId: {request_id},
-                        infoType: {info_type},
-                        success: {success},
-                        data: parsedData
-                    }});
+                    await globalThis.__plugin.onGroupInfoResponse(response);
                 }}
             }})()
             "#,
@@ -486,4 +628,91 @@ impl PluginRuntime {
 
         Ok(take_outputs(&mut self.runtime))
     }
+
+    /// 让超时的 Promise 请求失败，返回是否仍有等待中的请求
+    pub async fn sweep_requests(&mut self) -> Result<(bool, Vec<PluginOutput>), String> {
+        take_outputs(&mut self.runtime);
+
+        self.runtime
+            .execute_script(
+                "<sweepRequests>",
+                "if (globalThis.__nbotSweepRequests) { globalThis.__nbotSweepRequests(); }",
+            )
+            .map_err(|e| format!("sweepRequests failed: {}", e))?;
+
+        self.runtime
+            .run_event_loop(Default::default())
+            .await
+            .map_err(|e| format!("sweepRequests event loop failed: {}", e))?;
+
+        // 失败回调里可能又发起了新请求，事件循环结束后再统计
+        let outputs = take_outputs(&mut self.runtime);
+        reset_hook_state(&mut self.runtime);
+        self.runtime
+            .execute_script(
+                "<pendingRequests>",
+                "Deno.core.ops.op_set_hook_result(\
+                 (globalThis.__nbotPendingRequestCount?.() ?? 0) > 0);",
+            )
+            .map_err(|e| format!("sweepRequests failed: {}", e))?;
+        Ok((get_hook_result(&mut self.runtime), outputs))
+    }
+
+    /// onApiResponse hook: result of a sendReply/callApi issued with a requestId
+    /// response: OneBot-style response object ({status, retcode, data, message})
+    pub async fn on_api_response(
+        &mut self,
+        request_id: &str,
+        action: &str,
+        response: &serde_json::Value,
+    ) -> Result<Vec<PluginOutput>, String> {
+        take_outputs(&mut self.runtime);
+
+        let request_id_json = serde_json::to_string(request_id)
+            .map_err(|e| format!("Serialize request_id failed: {e}"))?;
+        let action_json =
+            serde_json::to_string(action).map_err(|e| format!("Serialize action failed: {e}"))?;
+        let response_json = serde_json::to_string(response)
+            .map_err(|e| format!("Serialize response failed: {e}"))?;
+
+        let code = format!(
+            r#"
+            (async () => {{
+                const resp = {response} || {{}};
+                const data = resp.data === undefined ? null : resp.data;
+                const messageId = data && data.message_id !== undefined ? data.message_id : null;
+                const response = {{
+                    requestId: {request_id},
+                    action: {action},
+                    success: resp.status === "ok" || resp.status === "async",
+                    status: resp.status || "failed",
+                    retcode: typeof resp.retcode === "number" ? resp.retcode : -1,
+                    messageId: messageId,
+                    data: data,
+                    message: resp.message || resp.wording || ""
+                }};
+                if (globalThis.__nbotSettleRequest && globalThis.__nbotSettleRequest(response)) {{
+                    return;
+                }}
+                if (globalThis.__plugin && globalThis.__plugin.onApiResponse) {{
+                    await globalThis.__plugin.onApiResponse(response);
+                }}
+            }})()
+            "#,
+            request_id = request_id_json,
+            action = action_json,
+            response = response_json,
+        );
+
+        self.runtime
+            .execute_script("<onApiResponse>", code)
+            .map_err(|e| format!("onApiResponse failed: {}", e))?;
+
+        self.runtime
+            .run_event_loop(Default::default())
+            .await
+            .map_err(|e| format!("onApiResponse event loop failed: {}", e))?;
+
+        Ok(take_outputs(&mut self.runtime))
+    }
 }
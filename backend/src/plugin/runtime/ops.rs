@@ -6,23 +6,32 @@ use tracing::error;
 use super::state::MediaBundleItem;
 use super::{PluginOpState, PluginOutput};
 
+mod context;
 mod core;
 mod group;
 mod http;
+mod kb;
 mod llm;
 mod render;
 mod storage;
+mod tts;
 
 pub(super) mod state {
-    pub use super::super::state::ForwardNode;
+    pub use super::super::state::{
+        ConversationContextOptions, ForwardNode, ForwardTts, LlmConversationOptions,
+        LlmResponseFormat, LlmStreamOptions, LlmToolOptions,
+    };
 }
 
+pub(super) use context::*;
 pub(super) use core::*;
 pub(super) use group::*;
 pub(super) use http::*;
+pub(super) use kb::*;
 pub(super) use llm::*;
 pub(super) use render::*;
 pub(super) use storage::*;
+pub(super) use tts::*;
 
 fn log_json_parse_error(state: &OpState, op_name: &str, err: &serde_json::Error) {
     let plugin_id = state.borrow::<PluginOpState>().plugin_id.clone();
@@ -44,6 +53,7 @@ fn push_reply(state: &mut OpState, user_id: i64, group_id: i64, content: &str) {
                 None
             },
             content: content.to_string(),
+            request_id: None,
         });
 }
 
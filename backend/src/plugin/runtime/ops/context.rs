@@ -0,0 +1,30 @@
+use deno_core::{op2, OpState};
+
+use super::state::ConversationContextOptions;
+use super::{PluginOpState, PluginOutput};
+
+/// Op: Build the reply-chain / forward context of a message (async, result via onGroupInfoResponse)
+#[op2(fast)]
+pub(in super::super) fn op_build_conversation_context(
+    state: &mut OpState,
+    #[string] request_id: &str,
+    #[string] options_json: &str,
+) {
+    let Some(options) = super::parse_payload_or_reply::<ConversationContextOptions>(
+        state,
+        0,
+        0,
+        "context.build",
+        options_json,
+    ) else {
+        return;
+    };
+
+    state
+        .borrow_mut::<PluginOpState>()
+        .outputs
+        .push(PluginOutput::BuildConversationContext {
+            request_id: request_id.to_string(),
+            options,
+        });
+}
@@ -21,16 +21,23 @@ pub(in super::super) fn op_send_message(
                 None
             },
             content: content.to_string(),
+            request_id: None,
         });
 }
 
-// Op: 发送回复消息
+fn non_empty(s: &str) -> Option<String> {
+    let s = s.trim();
+    (!s.is_empty()).then(|| s.to_string())
+}
+
+// Op: 发送回复消息（request_id 非空时结果经 onApiResponse 回调）
 #[op2(fast)]
 pub(in super::super) fn op_send_reply(
     state: &mut OpState,
     #[bigint] user_id: i64,
     #[bigint] group_id: i64,
     #[string] content: &str,
+    #[string] request_id: &str,
 ) {
     state
         .borrow_mut::<PluginOpState>()
@@ -43,15 +50,17 @@ pub(in super::super) fn op_send_reply(
                 None
             },
             content: content.to_string(),
+            request_id: non_empty(request_id),
         });
 }
 
-// Op: 调用 QQ API
+// Op: 调用 QQ API（request_id 非空时结果经 onApiResponse 回调）
 #[op2(fast)]
 pub(in super::super) fn op_call_api(
     state: &mut OpState,
     #[string] action: &str,
     #[string] params_json: &str,
+    #[string] request_id: &str,
 ) {
     let params: serde_json::Value = match serde_json::from_str(params_json) {
         Ok(v) => v,
@@ -66,6 +75,7 @@ pub(in super::super) fn op_call_api(
         .push(PluginOutput::CallApi {
             action: action.to_string(),
             params,
+            request_id: non_empty(request_id),
         });
 }
 
@@ -91,6 +101,12 @@ pub(in super::super) fn op_set_hook_result(state: &mut OpState, result: bool) {
     state.borrow_mut::<PluginOpState>().hook_result = Some(result);
 }
 
+// Op: 设置 onLlmToolCall 的返回值
+#[op2(fast)]
+pub(in super::super) fn op_set_tool_result(state: &mut OpState, #[string] result: &str) {
+    state.borrow_mut::<PluginOpState>().tool_result = Some(result.to_string());
+}
+
 // Op: 获取当前时间戳（毫秒）
 #[op2(fast)]
 pub(in super::super) fn op_now() -> f64 {
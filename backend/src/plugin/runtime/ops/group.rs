@@ -151,3 +151,27 @@ pub(in super::super) fn op_download_file(
             headers,
         });
 }
+
+/// Op: Search the local message archive (async, result returned via onGroupInfoResponse hook)
+#[op2(fast)]
+pub(in super::super) fn op_search_chat_archive(
+    state: &mut OpState,
+    #[string] request_id: &str,
+    #[string] query: &str,
+    #[bigint] group_id: i64,
+    #[bigint] user_id: i64,
+    #[bigint] since: i64,
+    limit: u32,
+) {
+    state
+        .borrow_mut::<PluginOpState>()
+        .outputs
+        .push(PluginOutput::SearchChatArchive {
+            request_id: request_id.to_string(),
+            query: query.to_string(),
+            group_id: if group_id > 0 { Some(group_id as u64) } else { None },
+            user_id: if user_id > 0 { Some(user_id as u64) } else { None },
+            since: if since > 0 { Some(since as u64) } else { None },
+            limit: if limit > 0 { Some(limit) } else { None },
+        });
+}
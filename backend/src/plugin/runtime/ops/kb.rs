@@ -0,0 +1,86 @@
+use deno_core::{op2, OpState};
+
+use super::{PluginOpState, PluginOutput};
+
+fn optional_group_id(group_id: i64) -> Option<u64> {
+    (group_id >= 0).then_some(group_id as u64)
+}
+
+/// Op: Search the group knowledge base (async, result returned via onGroupInfoResponse hook)
+#[op2(fast)]
+pub(in super::super) fn op_kb_search(
+    state: &mut OpState,
+    #[string] request_id: &str,
+    #[string] query: &str,
+    k: u32,
+    #[bigint] group_id: i64,
+) {
+    state
+        .borrow_mut::<PluginOpState>()
+        .outputs
+        .push(PluginOutput::KbSearch {
+            request_id: request_id.to_string(),
+            query: query.to_string(),
+            k,
+            group_id: optional_group_id(group_id),
+        });
+}
+
+#[derive(serde::Deserialize, Default)]
+struct KbIngestPayload {
+    source: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    file_name: Option<String>,
+}
+
+/// Op: Add text or a document URL to the group knowledge base (async, result via onGroupInfoResponse)
+#[op2(fast)]
+pub(in super::super) fn op_kb_ingest(
+    state: &mut OpState,
+    #[string] request_id: &str,
+    #[bigint] group_id: i64,
+    #[string] payload_json: &str,
+) {
+    let Some(payload) =
+        super::parse_payload_or_reply::<KbIngestPayload>(state, 0, 0, "kb.ingest", payload_json)
+    else {
+        return;
+    };
+
+    state
+        .borrow_mut::<PluginOpState>()
+        .outputs
+        .push(PluginOutput::KbIngest {
+            request_id: request_id.to_string(),
+            group_id: optional_group_id(group_id),
+            source: payload.source,
+            title: payload.title,
+            text: payload.text.filter(|t| !t.trim().is_empty()),
+            url: payload.url.filter(|u| !u.trim().is_empty()),
+            file_name: payload.file_name.filter(|f| !f.trim().is_empty()),
+        });
+}
+
+/// Op: Remove a source from the group knowledge base (async, result via onGroupInfoResponse)
+#[op2(fast)]
+pub(in super::super) fn op_kb_remove(
+    state: &mut OpState,
+    #[string] request_id: &str,
+    #[bigint] group_id: i64,
+    #[string] source: &str,
+) {
+    state
+        .borrow_mut::<PluginOpState>()
+        .outputs
+        .push(PluginOutput::KbRemove {
+            request_id: request_id.to_string(),
+            group_id: optional_group_id(group_id),
+            source: source.to_string(),
+        });
+}
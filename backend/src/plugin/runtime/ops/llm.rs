@@ -1,6 +1,9 @@
 use deno_core::{op2, OpState};
 
-use super::state::ForwardNode;
+use super::state::{
+    ForwardNode, ForwardTts, LlmConversationOptions, LlmResponseFormat, LlmStreamOptions,
+    LlmToolOptions,
+};
 use super::{MediaBundleItem, PluginOpState, PluginOutput};
 
 #[derive(serde::Deserialize, Default)]
@@ -11,6 +14,14 @@ struct CallLlmChatPayload {
     messages: Vec<serde_json::Value>,
     #[serde(default)]
     max_tokens: Option<u32>,
+    #[serde(default)]
+    stream: Option<LlmStreamOptions>,
+    #[serde(default)]
+    tools: Option<LlmToolOptions>,
+    #[serde(default)]
+    conversation: Option<LlmConversationOptions>,
+    #[serde(default)]
+    response_format: Option<Box<LlmResponseFormat>>,
 }
 
 // Op: 调用 LLM 进行多轮对话（异步返回结果）
@@ -45,6 +56,10 @@ pub(in super::super) fn op_call_llm_chat(
             model_name: payload.model_name,
             messages: payload.messages,
             max_tokens: payload.max_tokens,
+            stream: payload.stream,
+            tools: payload.tools,
+            conversation: payload.conversation,
+            response_format: payload.response_format,
         });
 }
 
@@ -96,6 +111,66 @@ pub(in super::super) fn op_call_llm_chat_with_search(
         });
 }
 
+#[derive(serde::Deserialize, Default)]
+struct LlmImagePayload {
+    request_id: String,
+    prompt: String,
+    #[serde(default)]
+    size: Option<String>,
+    #[serde(default)]
+    quality: Option<String>,
+    #[serde(default)]
+    input_image_url: Option<String>,
+    #[serde(default)]
+    model_name: Option<String>,
+}
+
+// Op: 生成或编辑图片（异步返回结果）
+#[op2(fast)]
+pub(in super::super) fn op_llm_image(state: &mut OpState, #[string] payload_json: &str) {
+    let Some(payload) =
+        super::parse_payload_or_reply::<LlmImagePayload>(state, 0, 0, "llm.image", payload_json)
+    else {
+        return;
+    };
+
+    if payload.request_id.trim().is_empty() {
+        return;
+    }
+
+    let non_empty = |s: Option<String>| s.filter(|s| !s.trim().is_empty());
+    state
+        .borrow_mut::<PluginOpState>()
+        .outputs
+        .push(PluginOutput::GenerateImage {
+            request_id: payload.request_id,
+            prompt: payload.prompt,
+            size: non_empty(payload.size),
+            quality: non_empty(payload.quality),
+            input_image_url: non_empty(payload.input_image_url),
+            model_name: non_empty(payload.model_name),
+        });
+}
+
+// Op: 清空一段对话记忆
+#[op2(fast)]
+pub(in super::super) fn op_clear_conversation(
+    state: &mut OpState,
+    #[string] conversation_id: &str,
+) {
+    let conversation_id = conversation_id.trim();
+    if conversation_id.is_empty() {
+        return;
+    }
+
+    state
+        .borrow_mut::<PluginOpState>()
+        .outputs
+        .push(PluginOutput::ClearConversation {
+            conversation_id: conversation_id.to_string(),
+        });
+}
+
 #[derive(serde::Deserialize, Default)]
 struct SendForwardMessagePayload {
     #[serde(default)]
@@ -136,6 +211,7 @@ pub(in super::super) fn op_send_forward_message(
 
 // Op: 调用 LLM 并发送合并转发消息
 #[op2(fast)]
+#[allow(clippy::too_many_arguments)]
 pub(in super::super) fn op_call_llm_forward(
     state: &mut OpState,
     #[bigint] user_id: i64,
@@ -144,7 +220,11 @@ pub(in super::super) fn op_call_llm_forward(
     #[string] prompt: &str,
     #[string] content: &str,
     #[string] title: &str,
+    #[string] tts_json: &str,
 ) {
+    // 为空表示不朗读；否则为 `{"voice": ...}`
+    let tts = (!tts_json.trim().is_empty())
+        .then(|| serde_json::from_str::<ForwardTts>(tts_json).unwrap_or_default());
     state
         .borrow_mut::<PluginOpState>()
         .outputs
@@ -156,6 +236,7 @@ pub(in super::super) fn op_call_llm_forward(
             prompt: prompt.to_string(),
             content: content.to_string(),
             title: title.to_string(),
+            tts,
         });
 }
 
@@ -173,6 +254,8 @@ struct CallLlmForwardFromUrlPayload {
     max_bytes: Option<u64>,
     #[serde(default)]
     max_chars: Option<u64>,
+    #[serde(default)]
+    tts: Option<ForwardTts>,
 }
 
 // Op: 从 URL 下载内容后调用 LLM 并发送合并转发消息（临时文件，处理完即删除）
@@ -218,6 +301,7 @@ pub(in super::super) fn op_call_llm_forward_from_url(
                 .unwrap_or(2_000_000)
                 .clamp(1024, 50_000_000),
             max_chars: payload.max_chars.unwrap_or(50_000).clamp(1000, 200_000),
+            tts: payload.tts,
         });
 }
 
@@ -240,7 +324,11 @@ struct CallLlmForwardArchiveFromUrlPayload {
     #[serde(default)]
     max_files: Option<u32>,
     #[serde(default)]
+    bundle_files: Option<u32>,
+    #[serde(default)]
     keywords: Option<Vec<String>>,
+    #[serde(default)]
+    tts: Option<ForwardTts>,
 }
 
 // Op: 从 URL 下载压缩包并提取日志后调用 LLM 并发送合并转发消息（临时文件，处理完即删除）
@@ -302,7 +390,9 @@ pub(in super::super) fn op_call_llm_forward_archive_from_url(
                 .unwrap_or(15_000_000)
                 .clamp(100_000, 200_000_000),
             max_files: payload.max_files.unwrap_or(50).clamp(1, 500),
+            bundle_files: payload.bundle_files.unwrap_or(3).clamp(1, 10),
             keywords,
+            tts: payload.tts,
         });
 }
 
@@ -326,6 +416,8 @@ struct CallLlmForwardImageFromUrlPayload {
     jpeg_quality: Option<u8>,
     #[serde(default)]
     max_output_bytes: Option<u64>,
+    #[serde(default)]
+    tts: Option<ForwardTts>,
 }
 
 // Op: 从 URL 下载图片后调用多模态 LLM（临时文件，处理完即删除），并发送结果（合并转发）
@@ -377,6 +469,7 @@ pub(in super::super) fn op_call_llm_forward_image_from_url(
                 .max_output_bytes
                 .unwrap_or(2_000_000)
                 .clamp(50_000, 10_000_000),
+            tts: payload.tts,
         });
 }
 
@@ -405,6 +498,8 @@ struct CallLlmForwardVideoFromUrlPayload {
     #[serde(default)]
     frame_max_output_bytes: Option<u64>,
     #[serde(default)]
+    frame_selection: Option<String>,
+    #[serde(default)]
     transcribe_audio: Option<bool>,
     #[serde(default)]
     transcription_model: Option<String>,
@@ -412,6 +507,8 @@ struct CallLlmForwardVideoFromUrlPayload {
     max_audio_seconds: Option<u32>,
     #[serde(default)]
     require_transcript: Option<bool>,
+    #[serde(default)]
+    tts: Option<ForwardTts>,
 }
 
 // Op: 从 URL 下载视频后抽帧（可选转写音频）并调用多模态 LLM（临时文件，处理完即删除），发送结果（合并转发）
@@ -465,10 +562,12 @@ pub(in super::super) fn op_call_llm_forward_video_from_url(
                 .frame_max_output_bytes
                 .unwrap_or(600_000)
                 .clamp(50_000, 10_000_000),
+            frame_selection: payload.frame_selection.unwrap_or_else(|| "even".to_string()),
             transcribe_audio: payload.transcribe_audio.unwrap_or(true),
             transcription_model: payload.transcription_model,
             max_audio_seconds: payload.max_audio_seconds.unwrap_or(180).clamp(10, 1800),
             require_transcript: payload.require_transcript.unwrap_or(false),
+            tts: payload.tts,
         });
 }
 
@@ -490,6 +589,8 @@ struct CallLlmForwardAudioFromUrlPayload {
     max_audio_seconds: Option<u32>,
     #[serde(default)]
     require_transcript: Option<bool>,
+    #[serde(default)]
+    tts: Option<ForwardTts>,
 }
 
 // Op: 从 URL 下载音频后调用多模态 LLM（临时文件，处理完即删除），发送结果（合并转发）
@@ -551,6 +652,7 @@ pub(in super::super) fn op_call_llm_forward_audio_from_url(
                 .clamp(10_000, 200_000_000),
             max_audio_seconds: payload.max_audio_seconds.unwrap_or(180).clamp(10, 1800),
             require_transcript: payload.require_transcript.unwrap_or(false),
+            tts: payload.tts,
         });
 }
 
@@ -581,6 +683,8 @@ struct CallLlmForwardMediaBundlePayload {
     video_max_bytes: Option<u64>,
     #[serde(default)]
     audio_max_bytes: Option<u64>,
+    #[serde(default)]
+    tts: Option<ForwardTts>,
 }
 
 // Op: 多媒体 bundle（文本 + 多个附件）调用多模态 LLM 并发送结果（合并转发）
@@ -645,5 +749,6 @@ pub(in super::super) fn op_call_llm_forward_media_bundle(
                 .audio_max_bytes
                 .unwrap_or(20_000_000)
                 .clamp(10_000, 200_000_000),
+            tts: payload.tts,
         });
 }
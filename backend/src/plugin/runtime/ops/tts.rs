@@ -0,0 +1,48 @@
+use deno_core::{op2, OpState};
+
+use super::{PluginOpState, PluginOutput};
+
+fn non_empty(s: &str) -> Option<String> {
+    let s = s.trim();
+    (!s.is_empty()).then(|| s.to_string())
+}
+
+/// Op: Synthesize speech (async, result returned via onGroupInfoResponse with infoType "tts")
+#[op2(fast)]
+pub(in super::super) fn op_tts(
+    state: &mut OpState,
+    #[string] request_id: &str,
+    #[string] text: &str,
+    #[string] voice: &str,
+) {
+    state
+        .borrow_mut::<PluginOpState>()
+        .outputs
+        .push(PluginOutput::Tts {
+            request_id: request_id.to_string(),
+            text: text.to_string(),
+            voice: non_empty(voice),
+        });
+}
+
+/// Op: Send a reply as a voice message (falls back to text when synthesis fails)
+#[op2(fast)]
+pub(in super::super) fn op_send_voice_reply(
+    state: &mut OpState,
+    #[bigint] user_id: i64,
+    #[bigint] group_id: i64,
+    #[string] text: &str,
+    #[string] voice: &str,
+    #[string] request_id: &str,
+) {
+    state
+        .borrow_mut::<PluginOpState>()
+        .outputs
+        .push(PluginOutput::SendVoiceReply {
+            user_id: user_id as u64,
+            group_id: (group_id > 0).then_some(group_id as u64),
+            text: text.to_string(),
+            voice: non_empty(voice),
+            request_id: non_empty(request_id),
+        });
+}
@@ -16,6 +16,14 @@ pub struct MediaBundleItem {
     pub file: Option<String>,
 }
 
+/// LLM 转发结果额外朗读为语音发送
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ForwardTts {
+    /// 音色，为空时使用 llm 模块 tts 配置
+    #[serde(default)]
+    pub voice: Option<String>,
+}
+
 /// 插件输出动作
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum PluginOutput {
@@ -29,11 +37,28 @@ pub enum PluginOutput {
         user_id: u64,
         group_id: Option<u64>,
         content: String,
+        /// 非空时发送结果通过 onApiResponse 回调给插件
+        #[serde(default)]
+        request_id: Option<String>,
+    },
+    /// 把文本合成为语音后发送（合成失败时改发文字）
+    SendVoiceReply {
+        user_id: u64,
+        group_id: Option<u64>,
+        text: String,
+        /// 音色，为空时使用 llm 模块 tts 配置
+        #[serde(default)]
+        voice: Option<String>,
+        /// 非空时发送结果通过 onApiResponse 回调给插件
+        #[serde(default)]
+        request_id: Option<String>,
     },
     /// 调用 QQ API
     CallApi {
         action: String,
         params: serde_json::Value,
+        #[serde(default)]
+        request_id: Option<String>,
     },
     /// 调用 LLM 并发送结果（合并转发）
     CallLlmAndForward {
@@ -46,6 +71,9 @@ pub enum PluginOutput {
         prompt: String,
         content: String,
         title: String,
+        /// 非空时把结果额外朗读为语音发送
+        #[serde(default)]
+        tts: Option<ForwardTts>,
     },
     /// 从 URL 下载内容后调用 LLM（临时文件，处理完即删除）并发送结果（合并转发）
     CallLlmAndForwardFromUrl {
@@ -66,6 +94,9 @@ pub enum PluginOutput {
         max_bytes: u64,
         #[serde(default)]
         max_chars: u64,
+        /// 非空时把结果额外朗读为语音发送
+        #[serde(default)]
+        tts: Option<ForwardTts>,
     },
     /// 从 URL 下载压缩包并提取日志后调用 LLM（临时文件，处理完即删除）并发送结果（合并转发）
     CallLlmAndForwardArchiveFromUrl {
@@ -90,8 +121,14 @@ pub enum PluginOutput {
         max_file_bytes: u64,
         #[serde(default)]
         max_files: u32,
+        /// 合并输出的相关文件数
+        #[serde(default)]
+        bundle_files: u32,
         #[serde(default)]
         keywords: Vec<String>,
+        /// 非空时把结果额外朗读为语音发送
+        #[serde(default)]
+        tts: Option<ForwardTts>,
     },
     /// 从 URL 下载图片后调用多模态 LLM，并发送结果（合并转发）
     CallLlmAndForwardImageFromUrl {
@@ -118,6 +155,9 @@ pub enum PluginOutput {
         jpeg_quality: u8,
         #[serde(default)]
         max_output_bytes: u64,
+        /// 非空时把结果额外朗读为语音发送
+        #[serde(default)]
+        tts: Option<ForwardTts>,
     },
     /// 从 URL 下载视频后抽帧（可选转写音频）并调用多模态 LLM，发送结果（合并转发）
     CallLlmAndForwardVideoFromUrl {
@@ -149,6 +189,9 @@ pub enum PluginOutput {
         frame_jpeg_quality: u8,
         #[serde(default)]
         frame_max_output_bytes: u64,
+        /// 抽帧方式：even（均匀）或 scene（优先画面切换处）
+        #[serde(default)]
+        frame_selection: String,
         #[serde(default)]
         transcribe_audio: bool,
         #[serde(default)]
@@ -157,6 +200,9 @@ pub enum PluginOutput {
         max_audio_seconds: u32,
         #[serde(default)]
         require_transcript: bool,
+        /// 非空时把结果额外朗读为语音发送
+        #[serde(default)]
+        tts: Option<ForwardTts>,
     },
     /// 从 URL 下载音频后调用多模态 LLM，发送结果（合并转发）
     CallLlmAndForwardAudioFromUrl {
@@ -182,6 +228,9 @@ pub enum PluginOutput {
         max_audio_seconds: u32,
         #[serde(default)]
         require_transcript: bool,
+        /// 非空时把结果额外朗读为语音发送
+        #[serde(default)]
+        tts: Option<ForwardTts>,
     },
     /// 多媒体 bundle：可包含文本 + 多个媒体附件（图片/视频/语音/文件），并调用多模态 LLM 后发送结果（合并转发）
     CallLlmAndForwardMediaBundle {
@@ -215,6 +264,9 @@ pub enum PluginOutput {
         video_max_bytes: u64,
         #[serde(default)]
         audio_max_bytes: u64,
+        /// 非空时把结果额外朗读为语音发送
+        #[serde(default)]
+        tts: Option<ForwardTts>,
     },
     /// 调用 LLM 进行多轮对话（异步返回结果，不直接发送）
     CallLlmChat {
@@ -228,7 +280,21 @@ pub enum PluginOutput {
         /// 最大 token 数
         #[serde(default)]
         max_tokens: Option<u32>,
+        /// 流式输出选项（为空则等待完整结果）
+        #[serde(default)]
+        stream: Option<LlmStreamOptions>,
+        /// 工具调用选项（为空则不提供工具）
+        #[serde(default)]
+        tools: Option<LlmToolOptions>,
+        /// 对话记忆选项（为空则只发送 messages）
+        #[serde(default)]
+        conversation: Option<LlmConversationOptions>,
+        /// 结构化输出选项（为空则返回原始文本）；装箱以免撑大 PluginOutput
+        #[serde(default)]
+        response_format: Option<Box<LlmResponseFormat>>,
     },
+    /// 清空插件的一段对话记忆
+    ClearConversation { conversation_id: String },
     /// 调用支持联网搜索的 LLM（异步返回结果）
     CallLlmChatWithSearch {
         /// 请求 ID，用于匹配响应
@@ -324,6 +390,218 @@ pub enum PluginOutput {
         #[serde(default)]
         headers: Option<Vec<String>>,
     },
+    /// 检索本地消息存档（异步返回结果）
+    SearchChatArchive {
+        /// 请求 ID，用于匹配响应
+        request_id: String,
+        /// 关键词（空字符串表示不按内容过滤）
+        query: String,
+        /// 群号
+        #[serde(default)]
+        group_id: Option<u64>,
+        /// 用户 QQ
+        #[serde(default)]
+        user_id: Option<u64>,
+        /// 起始时间（Unix 秒）
+        #[serde(default)]
+        since: Option<u64>,
+        /// 返回条数
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+    /// 检索群知识库（异步返回结果）
+    KbSearch {
+        /// 请求 ID，用于匹配响应
+        request_id: String,
+        query: String,
+        /// 返回片段数
+        k: u32,
+        /// 群号，为空时使用当前事件的群；0 表示 bot 级的公共知识库
+        #[serde(default)]
+        group_id: Option<u64>,
+    },
+    /// 向群知识库导入文本或文件（异步返回结果）
+    KbIngest {
+        request_id: String,
+        #[serde(default)]
+        group_id: Option<u64>,
+        /// 来源标识，同一来源再次导入时替换旧内容
+        source: String,
+        #[serde(default)]
+        title: String,
+        #[serde(default)]
+        text: Option<String>,
+        /// 文档或压缩包的下载地址（未提供 text 时使用）
+        #[serde(default)]
+        url: Option<String>,
+        #[serde(default)]
+        file_name: Option<String>,
+    },
+    /// 从群知识库删除某个来源（异步返回结果）
+    KbRemove {
+        request_id: String,
+        #[serde(default)]
+        group_id: Option<u64>,
+        source: String,
+    },
+    /// 语音合成（异步返回结果，infoType 为 tts）
+    Tts {
+        request_id: String,
+        text: String,
+        #[serde(default)]
+        voice: Option<String>,
+    },
+    /// 图片生成 / 编辑（异步返回结果，infoType 为 llm_image）
+    GenerateImage {
+        request_id: String,
+        prompt: String,
+        /// 为空时使用 llm 模块 images 配置
+        #[serde(default)]
+        size: Option<String>,
+        #[serde(default)]
+        quality: Option<String>,
+        /// 非空时编辑该图片
+        #[serde(default)]
+        input_image_url: Option<String>,
+        /// 模型映射名，为空时使用 images.model_mapping
+        #[serde(default)]
+        model_name: Option<String>,
+    },
+    /// 构建一条消息的对话上下文（异步返回结果，infoType 为 conversation_context）
+    BuildConversationContext {
+        request_id: String,
+        options: ConversationContextOptions,
+    },
+}
+
+/// 流式 LLM 调用选项
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct LlmStreamOptions {
+    /// 是否触发 onLlmChunk 增量回调
+    #[serde(default)]
+    pub chunk_events: bool,
+    /// 边生成边发送到该会话（均为空或 0 时不发送）；用字符串传递避免 JS 数字精度丢失
+    #[serde(default)]
+    pub deliver_user_id: String,
+    #[serde(default)]
+    pub deliver_group_id: String,
+    /// 回调/发送的节流间隔（毫秒）
+    #[serde(default)]
+    pub interval_ms: Option<u64>,
+    /// QQ 分段发送时每段至少累积的字符数
+    #[serde(default)]
+    pub min_chars: Option<usize>,
+}
+
+impl LlmStreamOptions {
+    /// 解析发送目标：返回 (user_id, group_id)，都没有时为 None
+    pub fn deliver_target(&self) -> Option<(u64, Option<u64>)> {
+        let user_id = self.deliver_user_id.trim().parse::<u64>().unwrap_or(0);
+        let group_id = self
+            .deliver_group_id
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .filter(|g| *g > 0);
+        (user_id > 0 || group_id.is_some()).then_some((user_id, group_id))
+    }
+}
+
+/// nbot.context.build 的选项：从一条消息出发沿回复链向上追溯、展开合并转发并收集媒体
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ConversationContextOptions {
+    /// 起点消息 ID（通常为 ctx.message_id）；用字符串传递避免 JS 数字精度丢失
+    pub message_id: String,
+    /// 沿回复链向上追溯的层数，默认 3
+    #[serde(default)]
+    pub reply_depth: Option<u32>,
+    /// 合并转发的展开层数，默认 3
+    #[serde(default)]
+    pub forward_depth: Option<u32>,
+    /// 全部文本的字数预算，默认 8000；超出时先舍弃较早的消息
+    #[serde(default)]
+    pub max_chars: Option<u32>,
+    /// 最多收集的媒体数，默认 20
+    #[serde(default)]
+    pub max_media: Option<u32>,
+    /// 是否把图片作为 image_url 附加到对应消息中（多模态模型）
+    #[serde(default)]
+    pub attach_images: bool,
+}
+
+/// callLlmChat 的对话记忆选项：宿主按 id 保存历史，插件只需传本轮的新消息
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct LlmConversationOptions {
+    /// 对话 ID（插件内唯一，如 "group:123"）
+    pub id: String,
+    /// 历史（含摘要）的 token 预算，为空则使用 llm 模块的 memory 配置
+    #[serde(default)]
+    pub max_context_tokens: Option<u64>,
+    /// 超过多少分钟没有对话则重新开始，0 表示不超时
+    #[serde(default)]
+    pub session_timeout_minutes: Option<u64>,
+    /// 对话所属的群/用户，为空时取当前事件的来源；用字符串传递避免 JS 数字精度丢失
+    #[serde(default)]
+    pub group_id: String,
+    #[serde(default)]
+    pub user_id: String,
+}
+
+impl LlmConversationOptions {
+    pub fn group_id(&self) -> Option<u64> {
+        self.group_id.trim().parse::<u64>().ok().filter(|g| *g > 0)
+    }
+
+    pub fn user_id(&self) -> Option<u64> {
+        self.user_id.trim().parse::<u64>().ok().filter(|u| *u > 0)
+    }
+}
+
+/// callLlmChat 的结构化输出选项：要求模型输出 JSON，宿主按 schema 校验并让模型修正
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct LlmResponseFormat {
+    /// schema 名称（OpenAI 要求只含字母、数字、下划线与连字符）
+    #[serde(default)]
+    pub name: String,
+    /// JSON Schema，为空时只要求输出合法 JSON
+    #[serde(default)]
+    pub schema: Option<serde_json::Value>,
+    /// 是否要求提供商严格遵循 schema（OpenAI strict 模式）
+    #[serde(default)]
+    pub strict: bool,
+    /// 校验失败后最多让模型修正几次（默认 2，最大 5）
+    #[serde(default)]
+    pub max_repairs: Option<u32>,
+}
+
+/// callLlmChat 的工具调用选项
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct LlmToolOptions {
+    /// 插件声明的工具，模型调用时触发插件的 onLlmToolCall
+    #[serde(default)]
+    pub functions: Vec<LlmToolFunction>,
+    /// 启用的内置工具（目前只有 "web_search"）
+    #[serde(default)]
+    pub builtin: Vec<String>,
+    /// 最多执行几轮工具调用（默认 5，最大 10）
+    #[serde(default)]
+    pub max_rounds: Option<u32>,
+    /// 单次工具执行的超时（毫秒，默认 30000）
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// 第一轮的 tool_choice："auto" / "required" / {"type":"function","function":{"name":...}}
+    #[serde(default)]
+    pub tool_choice: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct LlmToolFunction {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// 参数的 JSON Schema
+    #[serde(default)]
+    pub parameters: serde_json::Value,
 }
 
 /// 合并转发消息节点
@@ -342,6 +620,8 @@ pub(super) struct PluginOpState {
     pub(super) config: serde_json::Value,
     pub(super) data_dir: String,
     pub(super) hook_result: Option<bool>,
+    /// onLlmToolCall 的返回值
+    pub(super) tool_result: Option<String>,
     pub(super) outputs: Vec<PluginOutput>,
 }
 
@@ -357,9 +637,17 @@ pub(super) fn reset_hook_state(runtime: &mut JsRuntime) {
     let mut op_state = op_state.borrow_mut();
     let state = op_state.borrow_mut::<PluginOpState>();
     state.hook_result = None;
+    state.tool_result = None;
     state.outputs.clear();
 }
 
+pub(super) fn take_tool_result(runtime: &mut JsRuntime) -> Option<String> {
+    let op_state = runtime.op_state();
+    let mut op_state = op_state.borrow_mut();
+    let state = op_state.borrow_mut::<PluginOpState>();
+    state.tool_result.take()
+}
+
 pub(super) fn get_hook_result(runtime: &mut JsRuntime) -> bool {
     let op_state = runtime.op_state();
     let op_state = op_state.borrow();
@@ -1,122 +1,276 @@
+//! QQ 表情：系统表情 ID 与名称互查，以及把表情类消息段（face/mface/dice/rps/poke、图片表情）
+//! 转成 LLM 能理解的描述（如 `[表情:doge]`）；发送时再把 `[表情:名称]` 还原成 `[CQ:face]`。
+
+use serde_json::Value;
+
+/// 系统表情（ID, 名称）
+const FACES: &[(&str, &str)] = &[
+    ("4", "得意"),
+    ("5", "流泪"),
+    ("8", "睡"),
+    ("9", "大哭"),
+    ("10", "尴尬"),
+    ("12", "调皮"),
+    ("14", "微笑"),
+    ("16", "酷"),
+    ("21", "可爱"),
+    ("23", "傲慢"),
+    ("24", "饥饿"),
+    ("25", "困"),
+    ("26", "惊恐"),
+    ("27", "流汗"),
+    ("28", "憨笑"),
+    ("29", "悠闲"),
+    ("30", "奋斗"),
+    ("32", "疑问"),
+    ("33", "嘘"),
+    ("34", "晕"),
+    ("38", "敲打"),
+    ("39", "再见"),
+    ("41", "发抖"),
+    ("42", "爱情"),
+    ("43", "跳跳"),
+    ("49", "拥抱"),
+    ("53", "蛋糕"),
+    ("60", "咖啡"),
+    ("63", "玫瑰"),
+    ("66", "爱心"),
+    ("74", "太阳"),
+    ("75", "月亮"),
+    ("76", "赞"),
+    ("78", "握手"),
+    ("79", "胜利"),
+    ("85", "飞吻"),
+    ("89", "西瓜"),
+    ("96", "冷汗"),
+    ("97", "擦汗"),
+    ("98", "抠鼻"),
+    ("99", "鼓掌"),
+    ("100", "糗大了"),
+    ("101", "坏笑"),
+    ("102", "左哼哼"),
+    ("103", "右哼哼"),
+    ("104", "哈欠"),
+    ("106", "委屈"),
+    ("109", "左亲亲"),
+    ("111", "可怜"),
+    ("116", "示爱"),
+    ("118", "抱拳"),
+    ("120", "拳头"),
+    ("122", "爱你"),
+    ("123", "NO"),
+    ("124", "OK"),
+    ("125", "转圈"),
+    ("129", "挥手"),
+    ("144", "喝彩"),
+    ("147", "棒棒糖"),
+    ("171", "茶"),
+    ("173", "泪奔"),
+    ("174", "无奈"),
+    ("175", "卖萌"),
+    ("176", "小纠结"),
+    ("179", "doge"),
+    ("180", "惊喜"),
+    ("181", "骚扰"),
+    ("182", "笑哭"),
+    ("183", "我最美"),
+    ("201", "点赞"),
+    ("203", "托脸"),
+    ("212", "托腮"),
+    ("214", "啵啵"),
+    ("219", "蹭一蹭"),
+    ("222", "抱抱"),
+    ("227", "拍手"),
+    ("232", "佛系"),
+    ("240", "喷脸"),
+    ("243", "甩头"),
+    ("246", "加油抱抱"),
+    ("262", "脑阔疼"),
+    ("264", "捂脸"),
+    ("265", "辣眼睛"),
+    ("266", "哦哟"),
+    ("267", "头秃"),
+    ("268", "问号脸"),
+    ("269", "暗中观察"),
+    ("270", "emm"),
+    ("271", "吃瓜"),
+    ("272", "呵呵哒"),
+    ("273", "我酸了"),
+    ("277", "汪汪"),
+    ("278", "汗"),
+    ("281", "无眼笑"),
+    ("282", "敬礼"),
+    ("284", "面无表情"),
+    ("285", "摸鱼"),
+    ("287", "哦"),
+    ("289", "睁眼"),
+    ("290", "敲开心"),
+    ("293", "摸锦鲤"),
+    ("294", "期待"),
+    ("297", "拜谢"),
+    ("298", "元宝"),
+    ("299", "牛啊"),
+    ("305", "右亲亲"),
+    ("306", "牛气冲天"),
+    ("307", "喵喵"),
+    ("314", "仔细分析"),
+    ("315", "加油"),
+    ("318", "崇拜"),
+    ("319", "比心"),
+    ("320", "庆祝"),
+    ("322", "拒绝"),
+    ("324", "吃糖"),
+    ("326", "生气"),
+];
+
+/// 骰子与猜拳在部分协议端以 face 段上报
+const DICE_FACE_ID: &str = "358";
+const RPS_FACE_ID: &str = "359";
+
 pub fn name_for_id(id: &str) -> Option<&'static str> {
-    match id {
-        "4" => Some("得意"),
-        "5" => Some("流泪"),
-        "8" => Some("睡"),
-        "9" => Some("大哭"),
-        "10" => Some("尴尬"),
-        "12" => Some("调皮"),
-        "14" => Some("微笑"),
-        "16" => Some("酷"),
-        "21" => Some("可爱"),
-        "23" => Some("傲慢"),
-        "24" => Some("饥饿"),
-        "25" => Some("困"),
-        "26" => Some("惊恐"),
-        "27" => Some("流汗"),
-        "28" => Some("憨笑"),
-        "29" => Some("悠闲"),
-        "30" => Some("奋斗"),
-        "32" => Some("疑问"),
-        "33" => Some("嘘"),
-        "34" => Some("晕"),
-        "38" => Some("敲打"),
-        "39" => Some("再见"),
-        "41" => Some("发抖"),
-        "42" => Some("爱情"),
-        "43" => Some("跳跳"),
-        "49" => Some("拥抱"),
-        "53" => Some("蛋糕"),
-        "60" => Some("咖啡"),
-        "63" => Some("玫瑰"),
-        "66" => Some("爱心"),
-        "74" => Some("太阳"),
-        "75" => Some("月亮"),
-        "76" => Some("赞"),
-        "78" => Some("握手"),
-        "79" => Some("胜利"),
-        "85" => Some("飞吻"),
-        "89" => Some("西瓜"),
-        "96" => Some("冷汗"),
-        "97" => Some("擦汗"),
-        "98" => Some("抠鼻"),
-        "99" => Some("鼓掌"),
-        "100" => Some("糗大了"),
-        "101" => Some("坏笑"),
-        "102" => Some("左哼哼"),
-        "103" => Some("右哼哼"),
-        "104" => Some("哈欠"),
-        "106" => Some("委屈"),
-        "109" => Some("左亲亲"),
-        "111" => Some("可怜"),
-        "116" => Some("示爱"),
-        "118" => Some("抱拳"),
-        "120" => Some("拳头"),
-        "122" => Some("爱你"),
-        "123" => Some("NO"),
-        "124" => Some("OK"),
-        "125" => Some("转圈"),
-        "129" => Some("挥手"),
-        "144" => Some("喝彩"),
-        "147" => Some("棒棒糖"),
-        "171" => Some("茶"),
-        "173" => Some("泪奔"),
-        "174" => Some("无奈"),
-        "175" => Some("卖萌"),
-        "176" => Some("小纠结"),
-        "179" => Some("doge"),
-        "180" => Some("惊喜"),
-        "181" => Some("骚扰"),
-        "182" => Some("笑哭"),
-        "183" => Some("我最美"),
-        "201" => Some("点赞"),
-        "203" => Some("托脸"),
-        "212" => Some("托腮"),
-        "214" => Some("啵啵"),
-        "219" => Some("蹭一蹭"),
-        "222" => Some("抱抱"),
-        "227" => Some("拍手"),
-        "232" => Some("佛系"),
-        "240" => Some("喷脸"),
-        "243" => Some("甩头"),
-        "246" => Some("加油抱抱"),
-        "262" => Some("脑阔疼"),
-        "264" => Some("捂脸"),
-        "265" => Some("辣眼睛"),
-        "266" => Some("哦哟"),
-        "267" => Some("头秃"),
-        "268" => Some("问号脸"),
-        "269" => Some("暗中观察"),
-        "270" => Some("emm"),
-        "271" => Some("吃瓜"),
-        "272" => Some("呵呵哒"),
-        "273" => Some("我酸了"),
-        "277" => Some("汪汪"),
-        "278" => Some("汗"),
-        "281" => Some("无眼笑"),
-        "282" => Some("敬礼"),
-        "284" => Some("面无表情"),
-        "285" => Some("摸鱼"),
-        "287" => Some("哦"),
-        "289" => Some("睁眼"),
-        "290" => Some("敲开心"),
-        "293" => Some("摸锦鲤"),
-        "294" => Some("期待"),
-        "297" => Some("拜谢"),
-        "298" => Some("元宝"),
-        "299" => Some("牛啊"),
-        "305" => Some("右亲亲"),
-        "306" => Some("牛气冲天"),
-        "307" => Some("喵喵"),
-        "314" => Some("仔细分析"),
-        "315" => Some("加油"),
-        "318" => Some("崇拜"),
-        "319" => Some("比心"),
-        "320" => Some("庆祝"),
-        "322" => Some("拒绝"),
-        "324" => Some("吃糖"),
-        "326" => Some("生气"),
+    FACES.iter().find(|(i, _)| *i == id).map(|(_, name)| *name)
+}
+
+pub fn id_for_name(name: &str) -> Option<&'static str> {
+    let name = name.trim().trim_start_matches('/');
+    FACES
+        .iter()
+        .find(|(_, n)| n.eq_ignore_ascii_case(name))
+        .map(|(id, _)| *id)
+}
+
+fn field_str(data: &Value, key: &str) -> Option<String> {
+    match data.get(key)? {
+        Value::String(s) => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
         _ => None,
     }
+    .filter(|s| !s.is_empty())
 }
 
+/// 去掉 summary 两侧的方括号，如 "[动画表情]" → "动画表情"
+fn strip_brackets(s: &str) -> &str {
+    s.trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim()
+}
+
+fn rps_name(result: &str) -> &'static str {
+    match result {
+        "1" => "石头",
+        "2" => "剪刀",
+        "3" => "布",
+        _ => "?",
+    }
+}
+
+fn with_detail(label: &str, detail: Option<&str>) -> String {
+    match detail.filter(|d| !d.is_empty()) {
+        Some(d) => format!("[{}:{}]", label, d),
+        None => format!("[{}]", label),
+    }
+}
+
+/// 表情类消息段的描述文本；不是表情类的段返回 None
+pub fn describe_segment(seg_type: &str, data: &Value) -> Option<String> {
+    match seg_type {
+        "face" => {
+            let id = field_str(data, "id").unwrap_or_default();
+            let result = field_str(data, "resultId").or_else(|| field_str(data, "result"));
+            if id == DICE_FACE_ID {
+                return Some(with_detail("骰子", result.as_deref()));
+            }
+            if id == RPS_FACE_ID {
+                return Some(with_detail("猜拳", result.as_deref().map(rps_name)));
+            }
+            let name = name_for_id(&id).map(str::to_string).or_else(|| {
+                data.get("raw")
+                    .and_then(|raw| field_str(raw, "faceText"))
+                    .map(|t| t.trim_start_matches('/').to_string())
+            });
+            Some(with_detail("表情", name.as_deref()))
+        }
+        "mface" => {
+            let summary = field_str(data, "summary");
+            Some(with_detail("表情", summary.as_deref().map(strip_brackets)))
+        }
+        "dice" => Some(with_detail("骰子", field_str(data, "result").as_deref())),
+        "rps" => Some(with_detail(
+            "猜拳",
+            field_str(data, "result").as_deref().map(rps_name),
+        )),
+        "poke" => Some("[戳一戳]".to_string()),
+        "image" if is_sticker_image(data) => {
+            let summary = field_str(data, "summary");
+            let summary = summary.as_deref().map(strip_brackets);
+            Some(with_detail(
+                "表情",
+                Some(summary.filter(|s| !s.is_empty()).unwrap_or("动画表情")),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// 图片段是否为表情包（OneBot 的 sub_type=1，或 summary 标注为表情）
+pub fn is_sticker_image(data: &Value) -> bool {
+    field_str(data, "sub_type").as_deref() == Some("1")
+        || field_str(data, "summary").is_some_and(|s| s.contains("表情"))
+}
+
+/// 把文本中的 `[表情:名称]` 还原为 `[CQ:face,id=…]`；未知的名称保持原样
+pub fn face_tokens_to_cq(text: &str) -> String {
+    const TAG: &str = "[表情:";
+    if !text.contains(TAG) {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(TAG) {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + TAG.len()..];
+        let Some(end) = after.find(']') else {
+            rest = &rest[start..];
+            break;
+        };
+        match id_for_name(&after[..end]) {
+            Some(id) => out.push_str(&format!("[CQ:face,id={}]", id)),
+            None => out.push_str(&rest[start..start + TAG.len() + end + 1]),
+        }
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restores_known_faces() {
+        assert_eq!(face_tokens_to_cq("[表情:微笑]"), "[CQ:face,id=14]");
+        assert_eq!(
+            face_tokens_to_cq("早[表情:/赞]！[表情:微笑][表情:微笑]"),
+            "早[CQ:face,id=76]！[CQ:face,id=14][CQ:face,id=14]"
+        );
+    }
+
+    #[test]
+    fn keeps_unknown_and_unterminated_tokens() {
+        assert_eq!(face_tokens_to_cq("[表情:不存在]"), "[表情:不存在]");
+        assert_eq!(
+            face_tokens_to_cq("[表情:微笑] [表情:微"),
+            "[CQ:face,id=14] [表情:微"
+        );
+        assert_eq!(face_tokens_to_cq("plain [text]"), "plain [text]");
+    }
+
+    #[test]
+    fn looks_up_names_both_ways() {
+        assert_eq!(id_for_name(" /微笑 "), Some("14"));
+        assert_eq!(name_for_id("14"), Some("微笑"));
+        assert_eq!(id_for_name("不存在"), None);
+    }
+}
@@ -278,14 +278,13 @@ pub async fn recreate_tool_handler(
     }
 }
 
-/// 拉取工具镜像（用于非常驻工具，例如 ffmpeg）或 compose 工具的镜像
+/// 拉取工具镜像（用于非常驻工具，例如 ffmpeg / tesseract）或 compose 工具的镜像
 pub async fn pull_tool_handler(
     State(_state): State<SharedState>,
     axum::extract::Path(id): axum::extract::Path<String>,
 ) -> Json<serde_json::Value> {
-    if id == "ffmpeg" {
-        let image = super::runners::ffmpeg_image_for_pull();
-        info!("拉取工具镜像 (ffmpeg): {}", image);
+    if let Some(image) = super::runners::runner_image(&id) {
+        info!("拉取工具镜像 ({}): {}", id, image);
 
         let mut cmd = Command::new("docker");
         cmd.args(["pull", &image]);
@@ -305,7 +304,7 @@ pub async fn pull_tool_handler(
                     truncate_output(&stderr, 4000),
                     truncate_output(&stdout, 4000),
                 );
-                warn!("工具镜像 ffmpeg 拉取失败: {}", msg);
+                warn!("工具镜像 {} 拉取失败: {}", id, msg);
                 Json(serde_json::json!({ "status": "error", "message": msg }))
             }
             Ok(Err(e)) => Json(
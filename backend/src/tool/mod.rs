@@ -5,4 +5,5 @@ mod types;
 
 pub use handlers::*;
 pub(crate) use programs::*;
+pub(crate) use runners::runner_image;
 pub use types::*;
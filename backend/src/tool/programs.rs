@@ -17,6 +17,52 @@ pub(crate) fn ffmpeg_program() -> String {
         .unwrap_or_else(|| "ffmpeg".to_string())
 }
 
+pub(crate) fn tesseract_program() -> String {
+    normalize_env("NBOT_TESSERACT_BIN")
+        .or_else(|| normalize_env("TESSERACT_BIN"))
+        .unwrap_or_else(|| "tesseract".to_string())
+}
+
+/// whisper.cpp 命令行（本地语音转写）
+pub(crate) fn whisper_program() -> String {
+    normalize_env("NBOT_WHISPER_BIN").unwrap_or_else(|| "whisper-cli".to_string())
+}
+
+/// whisper.cpp 的 ggml 模型文件路径
+pub(crate) fn whisper_model_path() -> Option<String> {
+    normalize_env("NBOT_WHISPER_MODEL")
+}
+
+/// 本地 whisper HTTP 服务地址（如 whisper.cpp server 的 http://127.0.0.1:8080/inference），优先于命令行
+pub(crate) fn whisper_url() -> Option<String> {
+    normalize_env("NBOT_WHISPER_URL")
+}
+
+/// silk 解码器（QQ 语音转 PCM），如 silk-v3-decoder 的 decoder
+pub(crate) fn silk_decoder_program() -> String {
+    normalize_env("NBOT_SILK_DECODER_BIN").unwrap_or_else(|| "silk_v3_decoder".to_string())
+}
+
+/// silk 编码器（PCM 转 QQ 语音），如 silk-v3-decoder 的 encoder
+pub(crate) fn silk_encoder_program() -> String {
+    normalize_env("NBOT_SILK_ENCODER_BIN").unwrap_or_else(|| "silk_v3_encoder".to_string())
+}
+
+/// 本地语音合成命令行（piper 风格：stdin 读文本，`--output_file` 写 wav）
+pub(crate) fn tts_program() -> String {
+    normalize_env("NBOT_TTS_BIN").unwrap_or_else(|| "piper".to_string())
+}
+
+/// 本地语音合成的模型文件路径
+pub(crate) fn tts_model_path() -> Option<String> {
+    normalize_env("NBOT_TTS_MODEL")
+}
+
+/// 本地 OpenAI 兼容语音合成服务地址（完整的 /audio/speech 地址），优先于命令行
+pub(crate) fn tts_url() -> Option<String> {
+    normalize_env("NBOT_TTS_URL")
+}
+
 pub(crate) fn ffprobe_program() -> String {
     normalize_env("NBOT_FFPROBE_BIN")
         .or_else(|| normalize_env("FFPROBE_BIN"))
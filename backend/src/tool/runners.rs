@@ -3,40 +3,76 @@ use std::io::ErrorKind;
 use tokio::process::Command;
 use tokio::time::{timeout, Duration};
 
-const DEFAULT_FFMPEG_IMAGE: &str = "jrottenberg/ffmpeg:6.1-alpine";
+/// 非常驻工具：优先使用本地程序，缺失时以 `docker run --rm` 临时运行镜像
+struct Runner {
+    id: &'static str,
+    description: &'static str,
+    image_env: &'static str,
+    default_image: &'static str,
+    program: fn() -> String,
+    version_args: &'static [&'static str],
+}
+
+const RUNNERS: &[Runner] = &[
+    Runner {
+        id: "ffmpeg",
+        description: "视频/音频处理（转码/压缩/抽帧）",
+        image_env: "NBOT_FFMPEG_IMAGE",
+        default_image: "jrottenberg/ffmpeg:6.1-alpine",
+        program: super::ffmpeg_program,
+        version_args: &["-version"],
+    },
+    Runner {
+        id: "tesseract",
+        description: "图片文字识别（OCR，模型不支持识图时使用）",
+        image_env: "NBOT_OCR_IMAGE",
+        default_image: "jitesoft/tesseract-ocr:latest",
+        program: super::tesseract_program,
+        version_args: &["--version"],
+    },
+];
+
+fn find_runner(id: &str) -> Option<&'static Runner> {
+    RUNNERS.iter().find(|r| r.id == id)
+}
 
-fn configured_ffmpeg_image() -> String {
-    std::env::var("NBOT_FFMPEG_IMAGE")
+fn configured_image(runner: &Runner) -> String {
+    std::env::var(runner.image_env)
         .ok()
         .map(|s| s.trim().to_string())
         .filter(|s| !s.is_empty())
-        .unwrap_or_else(|| DEFAULT_FFMPEG_IMAGE.to_string())
+        .unwrap_or_else(|| runner.default_image.to_string())
 }
 
-async fn try_get_local_ffmpeg_version_line() -> Result<Option<String>, String> {
-    let program = super::ffmpeg_program();
+async fn try_get_local_version_line(runner: &Runner) -> Result<Option<String>, String> {
+    let program = (runner.program)();
     let out = timeout(
         Duration::from_secs(3),
-        Command::new(&program).args(["-version"]).output(),
+        Command::new(&program).args(runner.version_args).output(),
     )
     .await
-    .map_err(|_| format!("{program} -version 超时"))?;
+    .map_err(|_| format!("{program} {} 超时", runner.version_args.join(" ")))?;
 
     match out {
         Ok(out) => {
             if !out.status.success() {
                 return Err(format!(
-                    "{program} -version 失败 (exit={}): {}",
+                    "{program} {} 失败 (exit={}): {}",
+                    runner.version_args.join(" "),
                     out.status.code().unwrap_or(-1),
                     String::from_utf8_lossy(&out.stderr).trim()
                 ));
             }
 
-            let first_line = String::from_utf8_lossy(&out.stdout)
+            // 旧版 tesseract 把版本信息输出到 stderr
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            let stderr = String::from_utf8_lossy(&out.stderr);
+            let first_line = stdout
                 .lines()
-                .next()
+                .chain(stderr.lines())
+                .map(str::trim)
+                .find(|l| !l.is_empty())
                 .unwrap_or_default()
-                .trim()
                 .to_string();
             Ok(Some(if first_line.is_empty() {
                 format!("{program} (version unknown)")
@@ -73,9 +109,17 @@ async fn docker_image_exists(image: &str) -> Result<bool, String> {
 }
 
 pub async fn runner_tools() -> Vec<ToolContainer> {
-    let image = configured_ffmpeg_image();
+    let mut tools = Vec::with_capacity(RUNNERS.len());
+    for runner in RUNNERS {
+        tools.push(runner_tool(runner).await);
+    }
+    tools
+}
 
-    let local = try_get_local_ffmpeg_version_line().await;
+async fn runner_tool(runner: &Runner) -> ToolContainer {
+    let image = configured_image(runner);
+
+    let local = try_get_local_version_line(runner).await;
     let (status, local_ok, local_detail) = match local {
         Ok(Some(v)) => ("ready".to_string(), true, Some(format!("local={v}"))),
         Ok(None) => (
@@ -108,23 +152,24 @@ pub async fn runner_tools() -> Vec<ToolContainer> {
         detail_parts.push(d);
     }
 
-    vec![ToolContainer {
-        id: "ffmpeg".to_string(),
-        name: "ffmpeg".to_string(),
-        description: "视频/音频处理（转码/压缩/抽帧）".to_string(),
+    ToolContainer {
+        id: runner.id.to_string(),
+        name: runner.id.to_string(),
+        description: runner.description.to_string(),
         kind: "runner".to_string(),
         container_name: String::new(),
         status,
         ports: Vec::new(),
         detail: Some(detail_parts.join(" · ")),
         container_id: None,
-    }]
+    }
 }
 
-pub fn ffmpeg_image_for_pull() -> String {
-    configured_ffmpeg_image()
+/// 非常驻工具使用的 docker 镜像（可通过环境变量覆盖）
+pub(crate) fn runner_image(id: &str) -> Option<String> {
+    find_runner(id).map(configured_image)
 }
 
 pub fn is_runner_tool_id(id: &str) -> bool {
-    matches!(id, "ffmpeg")
+    find_runner(id).is_some()
 }
@@ -55,6 +55,7 @@ return {
     const videoMaxAudioSeconds = config.video_max_audio_seconds || 180;
     const videoRequireTranscript = config.video_require_transcript === true;
     const videoInputMode = config.video_input_mode || "direct";
+    const videoFrameSelection = config.video_frame_selection || "even";
 
     // Audio options (pure voice/record)
     const maxAudioBytes = config.max_audio_bytes || 20_000_000;
@@ -132,7 +133,7 @@ return {
           videoMaxAudioSeconds,
           videoRequireTranscript,
           videoInputMode,
-          { modelName: analysisModel }
+          { modelName: analysisModel, frameSelection: videoFrameSelection }
         );
         return;
       }
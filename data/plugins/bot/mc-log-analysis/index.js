@@ -47,9 +47,7 @@ const TEXT_EXTENSIONS = [".txt", ".log"];
 const ARCHIVE_EXTENSIONS = [".zip", ".tar", ".tar.gz", ".tgz", ".rar", ".7z", ".gz"];
 
 const sessions = new Map();
-const pendingFileUrlRequests = new Map();
 let lastCleanupAt = 0;
-let requestIdCounter = 0;
 
 function clampNumber(value, fallback, min, max) {
   const n = Number(value);
@@ -115,11 +113,6 @@ function buildSessionKey(userId, groupId) {
   return `${groupId || 0}:${userId}`;
 }
 
-function genRequestId(type) {
-  requestIdCounter += 1;
-  return `mc-log-${type}-${requestIdCounter}-${nbot.now()}`;
-}
-
 function looksLikeUrl(value) {
   if (typeof value !== "string") return false;
   const v = value.trim().toLowerCase();
@@ -751,33 +744,38 @@ function handleFileTarget(userId, groupId, target, config) {
   nbot.sendReply(userId, groupId || 0, "无法获取文件链接");
 }
 
-function requestGroupFileUrl(userId, groupId, fileId, busid, name, config) {
+async function requestGroupFileUrl(userId, groupId, fileId, busid, name, config) {
   if (!groupId) {
     nbot.sendReply(userId, 0, "仅支持群文件");
     return;
   }
-  if (typeof nbot.fetchGroupFileUrl !== "function") {
+  if (!nbot.group || typeof nbot.group.fileUrl !== "function") {
     nbot.sendReply(userId, groupId, "当前后端不支持拉取群文件链接");
     return;
   }
 
-  const requestId = genRequestId("file-url");
-  pendingFileUrlRequests.set(requestId, {
-    userId,
-    groupId,
-    fileId,
-    busid,
-    name,
-    createdAt: nbot.now(),
-  });
-
   sendProcessing(userId, groupId, config);
 
-  if (busid === undefined || busid === null || busid === "") {
-    nbot.fetchGroupFileUrl(requestId, Number(groupId), String(fileId));
-  } else {
-    nbot.fetchGroupFileUrl(requestId, Number(groupId), String(fileId), busid);
+  let data;
+  try {
+    data = await nbot.group.fileUrl(
+      Number(groupId),
+      String(fileId),
+      busid === undefined || busid === null || busid === "" ? 0 : busid,
+      { timeoutMs: config.file_url_timeout_seconds * 1000 }
+    );
+  } catch (e) {
+    const timedOut = e && /timed out/.test(e.message || "");
+    nbot.sendReply(userId, groupId, timedOut ? "获取文件链接超时" : "获取文件链接失败");
+    return;
   }
+
+  const info = extractFileUrlFromResponse(data);
+  if (!info || !info.url) {
+    nbot.sendReply(userId, groupId, "获取文件链接失败");
+    return;
+  }
+  analyzeFileFromUrl(userId, groupId, info.url, info.name || name, getConfig());
 }
 
 function extractFileUrlFromResponse(data) {
@@ -965,16 +963,6 @@ function analyzeArchiveFromUrl(userId, groupId, url, fileName, config) {
   );
 }
 
-function cleanupPendingFileUrlRequests(config) {
-  const now = nbot.now();
-  const timeoutMs = config.file_url_timeout_seconds * 1000;
-  for (const [requestId, req] of pendingFileUrlRequests.entries()) {
-    if (now - req.createdAt <= timeoutMs) continue;
-    pendingFileUrlRequests.delete(requestId);
-    nbot.sendReply(req.userId, req.groupId || 0, "获取文件链接超时");
-  }
-}
-
 function cleanupExpiredSessions(config) {
   const now = nbot.now();
   if (now - lastCleanupAt < 1000) return;
@@ -996,7 +984,6 @@ return {
 
   onDisable() {
     sessions.clear();
-    pendingFileUrlRequests.clear();
     nbot.log.info("我的世界日志分析插件已禁用");
   },
 
@@ -1046,7 +1033,6 @@ return {
   preMessage(ctx) {
     const config = getConfig();
     cleanupExpiredSessions(config);
-    cleanupPendingFileUrlRequests(config);
 
     const { user_id, group_id, raw_message } = ctx;
     if (!user_id) return true;
@@ -1120,7 +1106,6 @@ return {
   onNotice(ctx) {
     const config = getConfig();
     cleanupExpiredSessions(config);
-    cleanupPendingFileUrlRequests(config);
 
     if (!ctx || ctx.notice_type !== "group_upload") {
       return true;
@@ -1162,35 +1147,6 @@ return {
     return true;
   },
 
-  onGroupInfoResponse(response) {
-    if (!response || response.infoType !== "file_url") {
-      return true;
-    }
-
-    const requestId = response.requestId;
-    const req = pendingFileUrlRequests.get(requestId);
-    if (!req) {
-      return true;
-    }
-
-    pendingFileUrlRequests.delete(requestId);
-
-    if (!response.success) {
-      nbot.sendReply(req.userId, req.groupId || 0, "获取文件链接失败");
-      return true;
-    }
-
-    const info = extractFileUrlFromResponse(response.data);
-    if (!info || !info.url) {
-      nbot.sendReply(req.userId, req.groupId || 0, "获取文件链接失败");
-      return true;
-    }
-
-    const config = getConfig();
-    analyzeFileFromUrl(req.userId, req.groupId, info.url, info.name || req.name, config);
-    return true;
-  },
-
   onMetaEvent(ctx) {
     if (!ctx) return true;
     if (ctx.meta_event_type !== "tick" && ctx.meta_event_type !== "heartbeat") {
@@ -1198,7 +1154,6 @@ return {
     }
     const config = getConfig();
     cleanupExpiredSessions(config);
-    cleanupPendingFileUrlRequests(config);
     return true;
   }
 };
@@ -24,6 +24,8 @@ export function getConfig() {
   const mentionUserOnEveryReply = cfg.mention_user_on_every_reply === true;
   // Default: don't reply to every user turn in-session; still keep session state so screenshots/logs can be followed up.
   const alwaysReplyInSession = cfg.always_reply_in_session === true;
+  // Off by default: synthesis uses the llm module `tts` backend.
+  const voiceReply = cfg.voice_reply === true;
   const decisionSystemPrompt =
     cfg.decision_system_prompt ||
     [
@@ -135,6 +137,8 @@ export function getConfig() {
     mentionUserOnFirstReply,
     mentionUserOnEveryReply,
     alwaysReplyInSession,
+    voiceReply,
+    voiceReplyVoice: String(cfg.voice_reply_voice || "").trim(),
     mentionCooldownMs: (() => {
       const v = Number(cfg.mention_cooldown_seconds ?? 30);
       const secs = Number.isFinite(v) ? Math.max(0, Math.min(600, v)) : 30;
@@ -34,7 +34,8 @@ export function sanitizeMessageForLlm(text, ctx) {
 
   const selfId = ctx && ctx.self_id !== undefined && ctx.self_id !== null ? String(ctx.self_id) : "";
 
-  // Prefer structured segments from ctx.message (backend enriches face segments with `data.name`).
+  // Prefer structured segments from ctx.message (backend enriches face segments with `data.name`,
+  // and face/mface/dice/rps/poke/sticker segments with a ready-made `data.desc` like "[表情:doge]").
   if (ctx && Array.isArray(ctx.message) && ctx.message.length) {
     const parts = [];
     for (const seg of ctx.message) {
@@ -59,6 +60,11 @@ export function sanitizeMessageForLlm(text, ctx) {
         }
         continue;
       }
+      const desc = data.desc !== undefined ? String(data.desc).trim() : "";
+      if (desc) {
+        parts.push(desc);
+        continue;
+      }
       if (type === "face") {
         const name = data.name !== undefined ? String(data.name).trim() : "";
         const id = data.id !== undefined ? String(data.id).trim() : "";
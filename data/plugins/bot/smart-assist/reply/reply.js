@@ -536,8 +536,14 @@ export function handleReplyResult(requestInfo, success, content) {
   }
   // Send as a single message to avoid out-of-order delivery in some QQ setups.
   const combined = finalParts.join(" ").trim();
-  const msg = prefix ? `${prefix}${combined}` : combined;
-  if (msg) nbot.sendReply(session.userId, session.groupId || 0, msg);
+  const lastUserMsg = session.messages.slice().reverse().find((m) => m && m.role === "user")?.content || "";
+  if (config.voiceReply && combined && String(lastUserMsg).includes("[语音]")) {
+    // Voice questions get a voice answer; a record message cannot carry the @ prefix.
+    nbot.sendReply(session.userId, session.groupId || 0, combined, { tts: { voice: config.voiceReplyVoice } });
+  } else {
+    const msg = prefix ? `${prefix}${combined}` : combined;
+    if (msg) nbot.sendReply(session.userId, session.groupId || 0, msg);
+  }
   session.lastBotReplyAt = now;
   session.pendingUserInput = false;
 
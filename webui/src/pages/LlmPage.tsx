@@ -2,6 +2,7 @@ import { useEffect, useMemo, useState, type ReactNode } from 'react';
 import { useQuery } from '@tanstack/react-query';
 import toast from 'react-hot-toast';
 import {
+  BarChart3,
   ChevronDown,
   ChevronUp,
   Database,
@@ -34,9 +35,37 @@ type LibraryModel = {
   enabled?: boolean;
 };
 
-type ModelMapping = {
+type MappingTarget = {
   provider: string;
   model: string;
+  weight?: number;
+};
+
+type RouteStrategy = 'failover' | 'weighted' | 'latency';
+
+/** 未勾选的输入类型由后端降级处理（OCR/识图模型描述、语音转写、视频抽帧），未配置视为支持 */
+type ModelCapabilities = {
+  vision?: boolean;
+  audio?: boolean;
+  video?: boolean;
+};
+
+type ModelMapping = MappingTarget & {
+  strategy?: RouteStrategy;
+  fallbacks?: MappingTarget[];
+  capabilities?: ModelCapabilities;
+};
+
+const CAPABILITY_LABELS: Record<keyof ModelCapabilities, string> = {
+  vision: '识图',
+  audio: '音频',
+  video: '视频',
+};
+
+const STRATEGY_LABELS: Record<RouteStrategy, string> = {
+  failover: '顺序故障转移',
+  weighted: '加权轮询',
+  latency: '最低延迟',
 };
 
 type LlmConfigResponse = {
@@ -45,10 +74,61 @@ type LlmConfigResponse = {
   model_library: LibraryModel[];
   mappings: Record<string, ModelMapping>;
   default_model: string;
+  vision_model?: string;
+  images?: ImageSettings;
   tavily_api_key: string;
+  prices?: Record<string, ModelPrice>;
+};
+
+/** 图片生成：使用的别名与默认尺寸 */
+type ImageSettings = {
+  model_mapping?: string;
+  size?: string;
+};
+
+const IMAGE_SIZES = ['1024x1024', '1024x1536', '1536x1024', '1792x1024', '1024x1792', 'auto'];
+
+/** 每百万 token 的单价；cached 为命中缓存的输入单价；image 为图片生成每张的价格 */
+type ModelPrice = {
+  prompt?: number;
+  completion?: number;
+  cached?: number;
+  image?: number;
+};
+
+type UsageGroupBy = 'model' | 'provider' | 'bot' | 'group' | 'user' | 'plugin' | 'day';
+
+type UsageRow = {
+  key: string | number | null;
+  calls: number;
+  prompt_tokens: number;
+  completion_tokens: number;
+  cached_tokens: number;
+  total_tokens: number;
+  cost: number;
+  cache_hits?: number;
+  cache_misses?: number;
+};
+
+type UsageSummary = {
+  from: string;
+  to: string;
+  group_by: UsageGroupBy;
+  total: UsageRow;
+  rows: UsageRow[];
+};
+
+const GROUP_BY_LABELS: Record<UsageGroupBy, string> = {
+  model: '模型',
+  provider: '供应商',
+  bot: '机器人',
+  group: '群',
+  user: '用户',
+  plugin: '插件',
+  day: '日期',
 };
 
-type TabKey = 'providers' | 'library' | 'mapping' | 'websearch' | 'chat';
+type TabKey = 'providers' | 'library' | 'mapping' | 'websearch' | 'usage' | 'chat';
 
 export function LlmPage() {
   const [tab, setTab] = useState<TabKey>('providers');
@@ -56,7 +136,10 @@ export function LlmPage() {
   const [modelLibrary, setModelLibrary] = useState<LibraryModel[]>([]);
   const [mappings, setMappings] = useState<Record<string, ModelMapping>>({});
   const [defaultAlias, setDefaultAlias] = useState('default');
+  const [visionAlias, setVisionAlias] = useState('');
+  const [imageSettings, setImageSettings] = useState<ImageSettings>({});
   const [tavilyKey, setTavilyKey] = useState('');
+  const [prices, setPrices] = useState<Record<string, ModelPrice>>({});
   const [saving, setSaving] = useState(false);
   const [loadedOnce, setLoadedOnce] = useState(false);
 
@@ -73,7 +156,10 @@ export function LlmPage() {
     setModelLibrary(configQuery.data.model_library ?? []);
     setMappings(configQuery.data.mappings ?? {});
     setDefaultAlias(configQuery.data.default_model ?? 'default');
+    setVisionAlias(configQuery.data.vision_model ?? '');
+    setImageSettings(configQuery.data.images ?? {});
     setTavilyKey(configQuery.data.tavily_api_key ?? '');
+    setPrices(configQuery.data.prices ?? {});
     setLoadedOnce(true);
   }, [configQuery.data, loadedOnce]);
 
@@ -95,7 +181,10 @@ export function LlmPage() {
         model_library: modelLibrary,
         mappings,
         default_model: defaultAlias.trim(),
+        vision_model: visionAlias,
+        images: imageSettings,
         tavily_api_key: tavilyKey.trim(),
+        prices,
       });
       if (resp.data?.status === 'success') {
         toast.success('配置已保存');
@@ -174,6 +263,13 @@ export function LlmPage() {
               count={tavilyKey.trim() ? '1' : ''}
               onClick={() => setTab('websearch')}
             />
+            <TabButton
+              active={tab === 'usage'}
+              icon={<BarChart3 className="w-5 h-5" />}
+              label="用量与计费"
+              count={String(Object.keys(prices).length || '')}
+              onClick={() => setTab('usage')}
+            />
             <TabButton
               active={tab === 'chat'}
               icon={<MessageSquare className="w-5 h-5" />}
@@ -205,9 +301,15 @@ export function LlmPage() {
                 setMappings={setMappings}
                 defaultAlias={defaultAlias}
                 setDefaultAlias={setDefaultAlias}
+                visionAlias={visionAlias}
+                setVisionAlias={setVisionAlias}
+                imageSettings={imageSettings}
+                setImageSettings={setImageSettings}
               />
             ) : tab === 'websearch' ? (
               <WebSearchTab tavilyKey={tavilyKey} setTavilyKey={setTavilyKey} />
+            ) : tab === 'usage' ? (
+              <UsageTab enabledModels={enabledModels} prices={prices} setPrices={setPrices} />
             ) : (
               <ChatTestTab providers={providers} enabledModels={enabledModels} />
             )}
@@ -461,10 +563,12 @@ function ProviderCard({
                 onChange={(e) => update({ type: e.target.value })}
               >
                 <option value="openai">OpenAI 兼容</option>
-                <option value="claude">Claude</option>
+                <option value="claude">Claude (Anthropic)</option>
+                <option value="gemini">Gemini</option>
               </select>
               <div className="text-xs text-text-main/60 font-medium">
-                说明：当前后端仅对 <code className="font-mono">claude</code> 做专用适配，其余按 OpenAI 兼容处理。
+                说明：<code className="font-mono">claude</code> / <code className="font-mono">gemini</code>{' '}
+                直连官方原生接口（Messages / generateContent），无需 OpenAI 兼容代理。
               </div>
             </div>
             <div className="space-y-2">
@@ -475,7 +579,13 @@ function ProviderCard({
                 className="w-full px-5 py-3 rounded-2xl border border-brand-soft bg-white text-sm font-bold text-text-main focus:outline-none focus:ring-4 focus:ring-brand/10 transition-all"
                 value={provider.base_url ?? ''}
                 onChange={(e) => update({ base_url: e.target.value })}
-                placeholder={provider.type === 'claude' ? 'https://api.anthropic.com' : 'https://api.openai.com/v1'}
+                placeholder={
+                  provider.type === 'claude'
+                    ? 'https://api.anthropic.com'
+                    : provider.type === 'gemini'
+                      ? 'https://generativelanguage.googleapis.com/v1beta'
+                      : 'https://api.openai.com/v1'
+                }
               />
             </div>
           </div>
@@ -490,7 +600,7 @@ function ProviderCard({
                 type={showKey ? 'text' : 'password'}
                 value={provider.api_key ?? ''}
                 onChange={(e) => update({ api_key: e.target.value })}
-                placeholder={provider.type === 'claude' ? 'sk-ant-...' : 'sk-...'}
+                placeholder={provider.type === 'claude' ? 'sk-ant-...' : provider.type === 'gemini' ? 'AIza...' : 'sk-...'}
               />
               <button
                 className="absolute right-3 top-1/2 -translate-y-1/2 p-2 text-brand/30 hover:text-brand transition-colors"
@@ -690,6 +800,10 @@ function MappingTab({
   setMappings,
   defaultAlias,
   setDefaultAlias,
+  visionAlias,
+  setVisionAlias,
+  imageSettings,
+  setImageSettings,
 }: {
   providers: LLMProvider[];
   enabledModels: LibraryModel[];
@@ -697,6 +811,10 @@ function MappingTab({
   setMappings: (next: Record<string, ModelMapping>) => void;
   defaultAlias: string;
   setDefaultAlias: (next: string) => void;
+  visionAlias: string;
+  setVisionAlias: (next: string) => void;
+  imageSettings: ImageSettings;
+  setImageSettings: (next: ImageSettings) => void;
 }) {
   const [newAlias, setNewAlias] = useState('');
   const [newValue, setNewValue] = useState('');
@@ -728,7 +846,37 @@ function MappingTab({
   function updateMapping(alias: string, value: string) {
     const [pid, mid] = value.split('||');
     if (!pid || !mid) return;
-    setMappings({ ...mappings, [alias]: { provider: pid, model: mid } });
+    setMappings({ ...mappings, [alias]: { ...mappings[alias], provider: pid, model: mid } });
+  }
+
+  function patchMapping(alias: string, patch: Partial<ModelMapping>) {
+    setMappings({ ...mappings, [alias]: { ...mappings[alias], ...patch } });
+  }
+
+  function addFallback(alias: string, value: string) {
+    const [pid, mid] = value.split('||');
+    if (!pid || !mid) return;
+    const fallbacks = mappings[alias].fallbacks ?? [];
+    patchMapping(alias, { fallbacks: [...fallbacks, { provider: pid, model: mid }] });
+  }
+
+  function updateFallback(alias: string, index: number, patch: Partial<MappingTarget>) {
+    const fallbacks = (mappings[alias].fallbacks ?? []).map((t, i) => (i === index ? { ...t, ...patch } : t));
+    patchMapping(alias, { fallbacks });
+  }
+
+  function setCapability(alias: string, key: keyof ModelCapabilities, enabled: boolean) {
+    patchMapping(alias, { capabilities: { ...mappings[alias].capabilities, [key]: enabled } });
+  }
+
+  function removeFallback(alias: string, index: number) {
+    const fallbacks = (mappings[alias].fallbacks ?? []).filter((_, i) => i !== index);
+    patchMapping(alias, { fallbacks });
+  }
+
+  function labelOf(target: MappingTarget) {
+    const providerName = providers.find((p) => p.id === target.provider)?.name ?? target.provider;
+    return `${target.model} (${providerName})`;
   }
 
   function remove(alias: string) {
@@ -739,6 +887,8 @@ function MappingTab({
       const fallback = Object.keys(next)[0] ?? 'default';
       setDefaultAlias(fallback);
     }
+    if (visionAlias === alias) setVisionAlias('');
+    if (imageSettings.model_mapping === alias) setImageSettings({ ...imageSettings, model_mapping: '' });
   }
 
   return (
@@ -750,7 +900,9 @@ function MappingTab({
 
       <div className="bg-brand-soft/50 rounded-2xl p-5 border border-brand/10 text-xs text-text-main/70 font-medium">
         别名映射允许你用自定义名称（如 <span className="font-mono">default</span> /{' '}
-        <span className="font-mono">fast</span>）引用具体模型，便于随时切换。
+        <span className="font-mono">fast</span>）引用具体模型，便于随时切换。可为映射添加备用目标：首选提供商返回 5xx /
+        429 或连接失败时自动切换，连续失败的提供商会被暂时熔断。取消勾选模型不支持的输入类型后，图片会先经识图模型描述与
+        OCR 转为文字，语音会先转写，视频改为抽帧。
       </div>
 
       <div className="bg-white rounded-[28px] border border-brand-soft shadow-sm p-6 space-y-4">
@@ -758,12 +910,62 @@ function MappingTab({
           <div className="text-[10px] font-black text-brand/40 uppercase tracking-widest">默认别名</div>
           <div className="text-sm font-black text-text-main">{defaultAlias}</div>
         </div>
+        <div className="flex items-center justify-between gap-3">
+          <div className="text-[10px] font-black text-brand/40 uppercase tracking-widest">识图模型</div>
+          <select
+            className="px-3 py-1.5 rounded-xl border border-brand-soft bg-white text-xs font-bold text-text-main focus:outline-none"
+            value={visionAlias}
+            onChange={(e) => setVisionAlias(e.target.value)}
+            title="模型不支持识图时用于描述图片的别名"
+          >
+            <option value="">不使用（仅 OCR）</option>
+            {Object.keys(mappings)
+              .filter((a) => mappings[a].capabilities?.vision !== false)
+              .map((a) => (
+                <option key={a} value={a}>
+                  {a}
+                </option>
+              ))}
+          </select>
+        </div>
+        <div className="flex items-center justify-between gap-3">
+          <div className="text-[10px] font-black text-brand/40 uppercase tracking-widest">图片生成</div>
+          <div className="flex items-center gap-2">
+            <select
+              className="px-3 py-1.5 rounded-xl border border-brand-soft bg-white text-xs font-bold text-text-main focus:outline-none"
+              value={imageSettings.model_mapping ?? ''}
+              onChange={(e) => setImageSettings({ ...imageSettings, model_mapping: e.target.value })}
+              title="插件 nbot.llm.image 使用的别名（OpenAI 兼容 /images 接口）"
+            >
+              <option value="">不启用</option>
+              {Object.keys(mappings).map((a) => (
+                <option key={a} value={a}>
+                  {a}
+                </option>
+              ))}
+            </select>
+            <select
+              className="px-3 py-1.5 rounded-xl border border-brand-soft bg-white text-xs font-bold text-text-main focus:outline-none"
+              value={imageSettings.size ?? '1024x1024'}
+              onChange={(e) => setImageSettings({ ...imageSettings, size: e.target.value })}
+              title="默认尺寸（插件可单独指定）"
+            >
+              {IMAGE_SIZES.map((s) => (
+                <option key={s} value={s}>
+                  {s}
+                </option>
+              ))}
+            </select>
+          </div>
+        </div>
 
         {Object.keys(mappings).length ? (
           <div className="space-y-2">
             {Object.entries(mappings).map(([alias, mapping]) => {
               const current = `${mapping.provider}||${mapping.model}`;
               const providerName = providers.find((p) => p.id === mapping.provider)?.name ?? mapping.provider;
+              const strategy = mapping.strategy ?? 'failover';
+              const fallbacks = mapping.fallbacks ?? [];
               return (
                 <div
                   key={alias}
@@ -803,6 +1005,79 @@ function MappingTab({
                       <Trash2 className="w-4 h-4" />
                     </button>
                   </div>
+                  <div className="md:col-span-4 space-y-2">
+                    <div className="flex flex-wrap items-center gap-2">
+                      <span className="text-[10px] font-black text-brand/40 uppercase tracking-widest">路由策略</span>
+                      <select
+                        className="px-3 py-1.5 rounded-xl border border-brand-soft bg-white text-xs font-bold text-text-main focus:outline-none"
+                        value={strategy}
+                        onChange={(e) => patchMapping(alias, { strategy: e.target.value as RouteStrategy })}
+                      >
+                        {(Object.keys(STRATEGY_LABELS) as RouteStrategy[]).map((k) => (
+                          <option key={k} value={k}>
+                            {STRATEGY_LABELS[k]}
+                          </option>
+                        ))}
+                      </select>
+                      {strategy === 'weighted' ? (
+                        <input
+                          className="w-20 px-3 py-1.5 rounded-xl border border-brand-soft bg-white text-xs font-bold text-text-main focus:outline-none"
+                          type="number"
+                          min={1}
+                          title="首选目标权重"
+                          value={mapping.weight ?? 1}
+                          onChange={(e) => patchMapping(alias, { weight: Math.max(1, Number(e.target.value) || 1) })}
+                        />
+                      ) : null}
+                      <select
+                        className="px-3 py-1.5 rounded-xl border border-dashed border-brand-soft bg-white text-xs font-bold text-text-main focus:outline-none"
+                        value=""
+                        onChange={(e) => addFallback(alias, e.target.value)}
+                        disabled={!options.length}
+                      >
+                        <option value="">+ 添加备用目标...</option>
+                        {options.map((o) => (
+                          <option key={o.value} value={o.value}>
+                            {o.label}
+                          </option>
+                        ))}
+                      </select>
+                    </div>
+                    <div className="flex flex-wrap items-center gap-3">
+                      <span className="text-[10px] font-black text-brand/40 uppercase tracking-widest">输入能力</span>
+                      {(Object.keys(CAPABILITY_LABELS) as (keyof ModelCapabilities)[]).map((key) => (
+                        <label key={key} className="flex items-center gap-1.5 text-xs font-bold text-text-main">
+                          <input
+                            type="checkbox"
+                            checked={mapping.capabilities?.[key] !== false}
+                            onChange={(e) => setCapability(alias, key, e.target.checked)}
+                          />
+                          {CAPABILITY_LABELS[key]}
+                        </label>
+                      ))}
+                    </div>
+                    {fallbacks.map((target, index) => (
+                      <div key={`${target.provider}||${target.model}||${index}`} className="flex items-center gap-2 pl-4">
+                        <span className="text-[10px] font-black text-brand/40">备用 {index + 1}</span>
+                        <span className="min-w-0 text-xs font-bold text-text-main truncate">{labelOf(target)}</span>
+                        {strategy === 'weighted' ? (
+                          <input
+                            className="w-20 px-3 py-1 rounded-xl border border-brand-soft bg-white text-xs font-bold text-text-main focus:outline-none"
+                            type="number"
+                            min={1}
+                            title="权重"
+                            value={target.weight ?? 1}
+                            onChange={(e) =>
+                              updateFallback(alias, index, { weight: Math.max(1, Number(e.target.value) || 1) })
+                            }
+                          />
+                        ) : null}
+                        <button className="btn-danger-ghost" onClick={() => removeFallback(alias, index)} title="移除备用目标">
+                          <Trash2 className="w-3.5 h-3.5" />
+                        </button>
+                      </div>
+                    ))}
+                  </div>
                 </div>
               );
             })}
@@ -945,6 +1220,186 @@ function WebSearchTab({
   );
 }
 
+function formatTokens(n: number) {
+  if (n >= 1_000_000) return `${(n / 1_000_000).toFixed(2)}M`;
+  if (n >= 1_000) return `${(n / 1_000).toFixed(1)}K`;
+  return String(n);
+}
+
+function UsageTab({
+  enabledModels,
+  prices,
+  setPrices,
+}: {
+  enabledModels: LibraryModel[];
+  prices: Record<string, ModelPrice>;
+  setPrices: (next: Record<string, ModelPrice>) => void;
+}) {
+  const [groupBy, setGroupBy] = useState<UsageGroupBy>('model');
+  const [from, setFrom] = useState('');
+  const [to, setTo] = useState('');
+
+  const usageQuery = useQuery({
+    queryKey: ['llm-usage', groupBy, from, to],
+    queryFn: async () => {
+      const resp = await api.get('/llm/usage', {
+        params: { group_by: groupBy, from: from || undefined, to: to || undefined },
+      });
+      if (resp.data?.status !== 'success') {
+        throw new Error(resp.data?.message ?? '加载失败');
+      }
+      return resp.data.data as UsageSummary;
+    },
+    refetchOnWindowFocus: false,
+  });
+
+  // 价格按 `provider/model` 保存，与后端查找顺序一致
+  function updatePrice(key: string, field: keyof ModelPrice, raw: string) {
+    const next = { ...(prices[key] ?? {}) };
+    const value = Number(raw);
+    if (raw.trim() === '' || Number.isNaN(value)) {
+      delete next[field];
+    } else {
+      next[field] = value;
+    }
+    const all = { ...prices };
+    if (Object.keys(next).length === 0) {
+      delete all[key];
+    } else {
+      all[key] = next;
+    }
+    setPrices(all);
+  }
+
+  const priceKeys = useMemo(() => {
+    const keys = enabledModels.map((m) => `${m.provider_id}/${m.model_id}`);
+    for (const key of Object.keys(prices)) {
+      if (!keys.includes(key)) keys.push(key);
+    }
+    return keys;
+  }, [enabledModels, prices]);
+
+  const summary = usageQuery.data;
+  const inputClass =
+    'w-full px-3 py-2 rounded-xl border border-brand-soft bg-brand-soft/30 text-xs font-bold text-text-main focus:outline-none focus:ring-4 focus:ring-brand/10 transition-all';
+
+  return (
+    <div className="space-y-6">
+      <div className="flex items-center gap-3">
+        <div className="w-1.5 h-6 bg-brand rounded-full" />
+        <h2 className="text-xl font-black text-text-main">用量与计费</h2>
+      </div>
+
+      <div className="bg-sky-50/50 rounded-2xl p-5 border border-sky-100 text-xs text-text-main/70 font-medium">
+        单价按每百万 token 计，用于估算费用；图片生成模型可另填每张图片的价格。未填写的模型只统计 token。每日预算在 llm 模块配置的
+        limits 中设置（daily_tokens_per_user / daily_cost_per_user / daily_tokens_per_group /
+        daily_cost_per_group，0 为不限制）。
+      </div>
+
+      <div className="bg-white rounded-[28px] border border-brand-soft shadow-sm overflow-hidden">
+        <div className="p-6 space-y-3">
+          <div className="grid grid-cols-[minmax(0,1fr)_96px_96px_96px_96px] gap-3 text-[10px] font-black text-brand/40 uppercase tracking-widest">
+            <div>模型</div>
+            <div>输入</div>
+            <div>输出</div>
+            <div>缓存输入</div>
+            <div>每张图片</div>
+          </div>
+          {priceKeys.length === 0 ? (
+            <div className="text-xs font-bold text-text-main/40">模型库为空</div>
+          ) : (
+            priceKeys.map((key) => (
+              <div key={key} className="grid grid-cols-[minmax(0,1fr)_96px_96px_96px_96px] gap-3 items-center">
+                <div className="text-sm font-bold text-text-main truncate" title={key}>
+                  {key}
+                </div>
+                {(['prompt', 'completion', 'cached', 'image'] as const).map((field) => (
+                  <input
+                    key={field}
+                    className={inputClass}
+                    type="number"
+                    min={0}
+                    step="0.01"
+                    value={prices[key]?.[field] ?? ''}
+                    onChange={(e) => updatePrice(key, field, e.target.value)}
+                  />
+                ))}
+              </div>
+            ))
+          )}
+        </div>
+      </div>
+
+      <div className="bg-white rounded-[28px] border border-brand-soft shadow-sm overflow-hidden">
+        <div className="p-6 space-y-4">
+          <div className="flex flex-wrap items-center gap-3">
+            <select
+              className={`${inputClass} w-auto`}
+              value={groupBy}
+              onChange={(e) => setGroupBy(e.target.value as UsageGroupBy)}
+            >
+              {(Object.keys(GROUP_BY_LABELS) as UsageGroupBy[]).map((k) => (
+                <option key={k} value={k}>
+                  按{GROUP_BY_LABELS[k]}
+                </option>
+              ))}
+            </select>
+            <input className={`${inputClass} w-auto`} type="date" value={from} onChange={(e) => setFrom(e.target.value)} />
+            <span className="text-xs font-bold text-text-main/40">至</span>
+            <input className={`${inputClass} w-auto`} type="date" value={to} onChange={(e) => setTo(e.target.value)} />
+            <button className="btn-secondary" onClick={() => usageQuery.refetch()} disabled={usageQuery.isFetching}>
+              {usageQuery.isFetching ? '加载中...' : '刷新'}
+            </button>
+          </div>
+
+          {usageQuery.isError ? (
+            <div className="text-sm font-bold text-red-500">{(usageQuery.error as Error).message}</div>
+          ) : summary ? (
+            <div className="overflow-x-auto">
+              <table className="w-full text-xs">
+                <thead>
+                  <tr className="text-left text-[10px] font-black text-brand/40 uppercase tracking-widest">
+                    <th className="py-2 pr-3">{GROUP_BY_LABELS[summary.group_by] ?? summary.group_by}</th>
+                    <th className="py-2 pr-3">调用</th>
+                    <th className="py-2 pr-3">输入</th>
+                    <th className="py-2 pr-3">缓存</th>
+                    <th className="py-2 pr-3">输出</th>
+                    <th className="py-2 pr-3">费用</th>
+                    <th className="py-2 pr-3">回复缓存</th>
+                  </tr>
+                </thead>
+                <tbody className="font-bold text-text-main">
+                  {[...summary.rows, summary.total].map((row, i) => (
+                    <tr
+                      key={i}
+                      className={i === summary.rows.length ? 'border-t border-brand-soft' : undefined}
+                    >
+                      <td className="py-2 pr-3">
+                        {i === summary.rows.length ? '合计' : (row.key ?? '—')}
+                      </td>
+                      <td className="py-2 pr-3">{row.calls}</td>
+                      <td className="py-2 pr-3">{formatTokens(row.prompt_tokens)}</td>
+                      <td className="py-2 pr-3">{formatTokens(row.cached_tokens)}</td>
+                      <td className="py-2 pr-3">{formatTokens(row.completion_tokens)}</td>
+                      <td className="py-2 pr-3">{row.cost.toFixed(4)}</td>
+                      <td className="py-2 pr-3">
+                        {row.cache_hits ?? 0} / {(row.cache_hits ?? 0) + (row.cache_misses ?? 0)}
+                      </td>
+                    </tr>
+                  ))}
+                </tbody>
+              </table>
+              <div className="text-[10px] font-bold text-text-main/40 mt-2">
+                {summary.from} ~ {summary.to}
+              </div>
+            </div>
+          ) : null}
+        </div>
+      </div>
+    </div>
+  );
+}
+
 function ChatTestTab({
   providers,
   enabledModels,